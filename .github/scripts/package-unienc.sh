@@ -0,0 +1,167 @@
+#!/usr/bin/env bash
+# Build unienc_c for every architecture of a platform and package the slices the
+# way Unity and Xcode consume them. Each output is checked for the expected
+# architectures, so a missing slice fails the build instead of shipping.
+#
+#   macos    <out>/apple-darwin/libunienc_c.bundle (universal arm64 + x86_64)
+#            and <out>/<triple>/libunienc_c.dylib per architecture
+#   ios      <out>/libunienc_c.xcframework (device arm64, simulator arm64 + x86_64)
+#            and <out>/aarch64-apple-ios/libunienc_c.a (device archive)
+#   android  <out>/<triple>/libunienc_c.so per ABI
+#
+# Usage: package-unienc.sh <macos|ios|android> <unity|nuget> <debug|release|minimal> <out-dir>
+#   e.g. package-unienc.sh ios unity release ./artifacts
+#
+# The minimal profile is a size-optimized release build without the optional
+# backend features (GPU blit, profiler markers, encoder probing, downloads).
+#
+# Environment:
+#   ANDROID_ABIS      ABIs to build (default: "arm64-v8a x86_64")
+#   ANDROID_PLATFORM  minimum API level passed to cargo-ndk (default: 23)
+set -euo pipefail
+
+PLATFORM="$1"
+VARIANT="$2"
+PROFILE="$3"
+mkdir -p "$4"
+OUT="$(cd "$4" && pwd)"
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+CRATE_DIR="$SCRIPT_DIR/../../InstantReplay.Externals/unienc/crates/unienc_c"
+TARGET_DIR="$SCRIPT_DIR/../../InstantReplay.Externals/unienc/target"
+
+FEATURES=()
+case "$PROFILE" in
+  debug) CARGO_PROFILE=dev PROFILE_DIR=debug ;;
+  release) CARGO_PROFILE=release PROFILE_DIR=release ;;
+  minimal)
+    CARGO_PROFILE=release-minimal PROFILE_DIR=release-minimal
+    FEATURES=(--no-default-features -F multi-thread)
+    ;;
+  *) echo "ERROR: unknown profile: $PROFILE" >&2; exit 1 ;;
+esac
+
+# same feature selection as the per-target CI builds
+case "$VARIANT" in
+  unity) FEATURES+=(-F unity,mimalloc) ;;
+  nuget) ;;
+  *) echo "ERROR: unknown variant: $VARIANT" >&2; exit 1 ;;
+esac
+
+# cargo_build <triple> [VAR=value...]
+cargo_build() {
+  local triple="$1"
+  shift
+  rustup target add "$triple"
+  # `${FEATURES[@]+...}` keeps an empty array working under `set -u` on bash 3.2 (macOS)
+  ( cd "$CRATE_DIR" && env "$@" cargo build --target "$triple" --profile "$CARGO_PROFILE" ${FEATURES[@]+"${FEATURES[@]}"} )
+}
+
+# Fails unless the Mach-O file contains every listed architecture.
+require_archs() {
+  local lib="$1"
+  shift
+  local archs
+  archs="$(xcrun lipo -archs "$lib")"
+  for arch in "$@"; do
+    case " $archs " in
+      *" $arch "*) ;;
+      *) echo "FAIL: $lib is missing the $arch slice (has: $archs)" >&2; exit 1 ;;
+    esac
+  done
+}
+
+package_macos() {
+  local slices=()
+  for triple in aarch64-apple-darwin x86_64-apple-darwin; do
+    cargo_build "$triple"
+    mkdir -p "$OUT/$triple"
+    cp "$TARGET_DIR/$triple/$PROFILE_DIR/libunienc_c.dylib" "$OUT/$triple/"
+    slices+=("$OUT/$triple/libunienc_c.dylib")
+  done
+  require_archs "$OUT/aarch64-apple-darwin/libunienc_c.dylib" arm64
+  require_archs "$OUT/x86_64-apple-darwin/libunienc_c.dylib" x86_64
+
+  mkdir -p "$OUT/apple-darwin"
+  xcrun lipo -create -output "$OUT/apple-darwin/libunienc_c.bundle" "${slices[@]}"
+  require_archs "$OUT/apple-darwin/libunienc_c.bundle" arm64 x86_64
+}
+
+# Builds one iOS static library slice and, for the Unity variant, localizes its
+# bundled mimalloc (see localize-apple-staticlib.sh).
+#   build_ios_slice <triple> <ld-arch> <sdk> <platform> <min-os>
+build_ios_slice() {
+  local triple="$1" arch="$2" sdk="$3" platform="$4" min_os="$5"
+  local lib="$TARGET_DIR/$triple/$PROFILE_DIR/libunienc_c.a"
+  if [ "$VARIANT" = unity ]; then
+    # Turn mimalloc's tentative definitions into real BSS definitions so they
+    # can be localized (common symbols cannot be).
+    cargo_build "$triple" "CFLAGS_$triple=-fno-common" "IPHONEOS_DEPLOYMENT_TARGET=$min_os"
+    "$SCRIPT_DIR/localize-apple-staticlib.sh" "$lib" "$arch" "$sdk" "$platform" "$min_os"
+  else
+    cargo_build "$triple" "IPHONEOS_DEPLOYMENT_TARGET=$min_os"
+  fi
+  require_archs "$lib" "$arch"
+}
+
+package_ios() {
+  # arm64 simulators require iOS 14
+  build_ios_slice aarch64-apple-ios arm64 iphoneos ios 10.0
+  build_ios_slice aarch64-apple-ios-sim arm64 iphonesimulator ios-simulator 14.0
+  build_ios_slice x86_64-apple-ios x86_64 iphonesimulator ios-simulator 10.0
+
+  local work
+  work="$(mktemp -d)"
+  mkdir -p "$work/simulator"
+  xcrun lipo -create -output "$work/simulator/libunienc_c.a" \
+    "$TARGET_DIR/aarch64-apple-ios-sim/$PROFILE_DIR/libunienc_c.a" \
+    "$TARGET_DIR/x86_64-apple-ios/$PROFILE_DIR/libunienc_c.a"
+  require_archs "$work/simulator/libunienc_c.a" arm64 x86_64
+
+  mkdir -p "$OUT/aarch64-apple-ios"
+  cp "$TARGET_DIR/aarch64-apple-ios/$PROFILE_DIR/libunienc_c.a" "$OUT/aarch64-apple-ios/"
+
+  # xcodebuild refuses to overwrite an existing framework
+  rm -rf "$OUT/libunienc_c.xcframework"
+  xcodebuild -create-xcframework \
+    -library "$OUT/aarch64-apple-ios/libunienc_c.a" \
+    -library "$work/simulator/libunienc_c.a" \
+    -output "$OUT/libunienc_c.xcframework"
+  rm -rf "$work"
+}
+
+package_android() {
+  local abis="${ANDROID_ABIS:-arm64-v8a x86_64}"
+  local args=()
+  for abi in $abis; do
+    args+=(-t "$abi")
+  done
+  ( cd "$CRATE_DIR" && cargo ndk "${args[@]}" --platform "${ANDROID_PLATFORM:-23}" build --profile "$CARGO_PROFILE" ${FEATURES[@]+"${FEATURES[@]}"} )
+
+  for abi in $abis; do
+    local triple machine
+    case "$abi" in
+      arm64-v8a) triple=aarch64-linux-android; machine="ARM aarch64" ;;
+      armeabi-v7a) triple=armv7-linux-androideabi; machine="ARM," ;;
+      x86_64) triple=x86_64-linux-android; machine="x86-64" ;;
+      x86) triple=i686-linux-android; machine="Intel 80386" ;;
+      *) echo "ERROR: unknown Android ABI: $abi" >&2; exit 1 ;;
+    esac
+    local lib="$TARGET_DIR/$triple/$PROFILE_DIR/libunienc_c.so"
+    # `grep -c` rather than `grep -q`, which can SIGPIPE `file` under pipefail
+    if [ "$(file -L "$lib" | grep -c "$machine")" -eq 0 ]; then
+      echo "FAIL: $lib is not a $abi library: $(file -L "$lib")" >&2
+      exit 1
+    fi
+    mkdir -p "$OUT/$triple"
+    cp "$lib" "$OUT/$triple/"
+  done
+}
+
+case "$PLATFORM" in
+  macos) package_macos ;;
+  ios) package_ios ;;
+  android) package_android ;;
+  *) echo "ERROR: unknown platform: $PLATFORM" >&2; exit 1 ;;
+esac
+
+echo "Packaged unienc_c ($PLATFORM, $VARIANT, $PROFILE) into $OUT"
@@ -0,0 +1,151 @@
+//! Frames per second of the CPU convert, encode and mux stages of the platform backend at 720p,
+//! 1080p and 4K. Blitting needs a graphics device from Unity, so it is only measured on devices
+//! through `unienc_run_blit_benchmark`.
+//!
+//! `cargo bench -p unienc` compares against the last run; keep a baseline from a release with
+//! `--save-baseline` to compare against it with `--baseline`.
+
+use std::pin::Pin;
+use std::time::Duration;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use futures::executor::{self, ThreadPool};
+use futures::task::SpawnExt;
+use unienc::bench::{BENCH_SIZES, bench_convert, bench_encode, bench_mux, bgra_frame};
+use unienc::{EncodingSystem, PlatformEncodingSystem, Spawn, SpawnBlocking};
+
+const FRAMES: u32 = 30;
+
+#[derive(Copy, Clone)]
+struct VideoEncoderOptions {
+    width: u32,
+    height: u32,
+}
+
+impl unienc::VideoEncoderOptions for VideoEncoderOptions {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn fps_hint(&self) -> u32 {
+        30
+    }
+
+    fn bitrate(&self) -> u32 {
+        // about 0.1 bits per pixel, as recordings are configured
+        self.width * self.height * 3
+    }
+}
+
+#[derive(Copy, Clone)]
+struct AudioEncoderOptions;
+
+impl unienc::AudioEncoderOptions for AudioEncoderOptions {
+    fn sample_rate(&self) -> u32 {
+        48000
+    }
+
+    fn channels(&self) -> u32 {
+        2
+    }
+
+    fn bitrate(&self) -> u32 {
+        128000
+    }
+}
+
+#[derive(Clone)]
+struct Runtime {
+    pool: ThreadPool,
+}
+
+impl Spawn for Runtime {
+    fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        self.pool
+            .spawn(future)
+            .expect("Failed to spawn task on threaded executor");
+    }
+}
+
+impl SpawnBlocking for Runtime {
+    fn spawn_blocking<Result: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> Result + Send + 'static,
+    ) -> Pin<Box<dyn Future<Output = Result> + Send + 'static>> {
+        Box::pin(blocking::unblock(f))
+    }
+}
+
+impl unienc::Runtime for Runtime {}
+
+type System = PlatformEncodingSystem<VideoEncoderOptions, AudioEncoderOptions, Runtime>;
+
+fn new_system(runtime: &Runtime, width: u32, height: u32) -> System {
+    System::new(
+        &VideoEncoderOptions { width, height },
+        &AudioEncoderOptions,
+        runtime.clone(),
+    )
+}
+
+fn throughput(c: &mut Criterion) {
+    let runtime = Runtime {
+        pool: ThreadPool::new().expect("Failed to build pool"),
+    };
+    let path = std::env::temp_dir().join("unienc_bench.mp4");
+
+    let mut group = c.benchmark_group("throughput");
+    group
+        .throughput(Throughput::Elements(FRAMES as u64))
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10));
+
+    for (name, width, height) in BENCH_SIZES {
+        group.bench_function(BenchmarkId::new("convert", name), |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| bench_convert(width, height, FRAMES).unwrap().elapsed)
+                    .sum()
+            })
+        });
+
+        let system = new_system(&runtime, width, height);
+        group.bench_function(BenchmarkId::new("encode", name), |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| {
+                        let encode =
+                            bench_encode(&system, FRAMES, |i| bgra_frame(width, height, i));
+                        executor::block_on(encode).unwrap().elapsed
+                    })
+                    .sum()
+            })
+        });
+        group.bench_function(BenchmarkId::new("mux", name), |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| {
+                        let mux = bench_mux(
+                            &system,
+                            &AudioEncoderOptions,
+                            &path,
+                            (width, height),
+                            FRAMES,
+                        );
+                        executor::block_on(mux).unwrap().elapsed
+                    })
+                    .sum()
+            })
+        });
+        executor::block_on(system.shutdown()).unwrap();
+    }
+    group.finish();
+    let _ = std::fs::remove_file(&path);
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
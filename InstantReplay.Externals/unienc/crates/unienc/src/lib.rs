@@ -3,7 +3,54 @@ mod platform;
 pub use platform::*;
 pub use unienc_common::*;
 
+pub mod external {
+    pub use unienc_external::{
+        UniencExternalBackend, UniencExternalEmit, UniencExternalPacket, is_registered, register,
+    };
+}
+
 #[cfg(target_os = "android")]
 pub mod android {
+    pub use unienc_android_mc::codec_selection::{
+        EncoderInfo, list_encoders, list_video_encoders, set_video_encoder_policy,
+    };
+    pub use unienc_android_mc::codec_stats::{
+        MediaCodecMetrics, VideoCodecStats, set_stall_timeout, video_codec_stats,
+    };
+    pub use unienc_android_mc::media_projection::MediaProjection;
     pub use unienc_android_mc::set_java_vm;
+    #[cfg(feature = "blit")]
+    pub use unienc_android_mc::{
+        VulkanPoolStats, set_vulkan_device, set_vulkan_pool_limits, vulkan_pool_stats,
+    };
+}
+
+#[cfg(target_vendor = "apple")]
+pub mod apple {
+    pub use unienc_apple_vt::mux::set_queue_capacity as set_muxer_queue_capacity;
+    #[cfg(feature = "blit")]
+    pub use unienc_apple_vt::{capture_next_blit, set_metal_device};
+}
+
+#[cfg(target_os = "ios")]
+pub mod ios {
+    pub use unienc_apple_vt::replay_kit::{ReplayKitCapture, ReplayKitSample};
+}
+
+#[cfg(target_os = "macos")]
+pub mod macos {
+    pub use unienc_apple_vt::screen_capture::{
+        ScreenCapture, ScreenCaptureFrame, ScreenCaptureTarget,
+    };
+}
+
+#[cfg(all(
+    target_family = "unix",
+    not(target_vendor = "apple"),
+    not(target_os = "android"),
+    not(target_arch = "wasm32")
+))]
+pub mod ffmpeg {
+    pub use unienc_ffmpeg::mux::{FileLayout, set_file_layout};
+    pub use unienc_ffmpeg::process::{ProcessOptions, reap, set_process_options};
 }
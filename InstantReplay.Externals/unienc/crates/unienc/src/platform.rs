@@ -1,14 +1,14 @@
-#[cfg(target_vendor = "apple")]
+#[cfg(all(target_vendor = "apple", not(feature = "external")))]
 pub type PlatformEncodingSystem<V, A, R> = unienc_apple_vt::VideoToolboxEncodingSystem<V, A, R>;
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", not(feature = "external")))]
 pub type PlatformEncodingSystem<V, A, R> = unienc_android_mc::MediaCodecEncodingSystem<V, A, R>;
 
-#[cfg(windows)]
+#[cfg(all(windows, not(feature = "external")))]
 pub type PlatformEncodingSystem<V, A, R> =
     unienc_windows_mf::MediaFoundationEncodingSystem<V, A, R>;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", not(feature = "external")))]
 pub type PlatformEncodingSystem<V, A, R> = unienc_webcodecs::WebCodecsEncodingSystem<V, A, R>;
 
 #[cfg(all(
@@ -17,25 +17,21 @@ pub type PlatformEncodingSystem<V, A, R> = unienc_webcodecs::WebCodecsEncodingSy
         target_vendor = "apple",
         target_os = "android",
         windows,
-        target_arch = "wasm32"
+        target_arch = "wasm32",
+        feature = "external"
     ))
 ))]
 pub type PlatformEncodingSystem<V, A, R> = unienc_ffmpeg::FFmpegEncodingSystem<V, A, R>;
 
-#[cfg(not(any(
-    target_vendor = "apple",
-    target_os = "android",
-    windows,
-    unix,
-    target_arch = "wasm32"
-)))]
-pub type PlatformEncodingSystem<V, A, R> = ();
-
-#[cfg(not(any(
-    target_vendor = "apple",
-    target_os = "android",
-    windows,
-    unix,
-    target_arch = "wasm32"
-)))]
-compile_error!("Unsupported platform");
+// platforms without a backend in this repository, such as consoles, register one at runtime
+#[cfg(any(
+    feature = "external",
+    not(any(
+        target_vendor = "apple",
+        target_os = "android",
+        windows,
+        unix,
+        target_arch = "wasm32"
+    ))
+))]
+pub type PlatformEncodingSystem<V, A, R> = unienc_external::ExternalEncodingSystem<V, A, R>;
@@ -145,11 +145,11 @@ async fn test_e2e_typed<T: EncodingSystem + Send>(encoding_system: T, runtime: R
 
             video_input
                 .push(VideoSample {
-                    frame: VideoFrame::Bgra32(VideoFrameBgra32 {
-                        buffer: SharedBuffer::new_unmanaged(data),
-                        width: 1280,
-                        height: 720,
-                    }),
+                    frame: VideoFrame::Bgra32(VideoFrameBgra32::packed(
+                        SharedBuffer::new_unmanaged(data),
+                        1280,
+                        720,
+                    )),
                     timestamp: (i as f64) / 1.0 + 100.0,
                 })
                 .await
@@ -211,4 +211,5 @@ async fn test_e2e_typed<T: EncodingSystem + Send>(encoding_system: T, runtime: R
     transfer_video.await.unwrap();
     transfer_audio.await.unwrap();
     completion_handle.finish().await.unwrap();
+    encoding_system.shutdown().await.unwrap();
 }
@@ -1,6 +1,7 @@
 use jni::{JNIEnv, objects::JValue, signature::ReturnType, sys::jint};
 use std::time::Duration;
-use unienc_common::{AudioSample, Encoder, EncoderInput, EncoderOutput};
+use unienc_common::profiler::ProfilerMarker;
+use unienc_common::{AudioSample, Encoder, EncoderInput, EncoderOutput, Timebase};
 
 use crate::error::{AndroidError, Result};
 use crate::{
@@ -10,6 +11,8 @@ use crate::{
 
 use crate::java::*;
 
+static PUSH: ProfilerMarker = ProfilerMarker::new("unienc_android_mc::audio::push");
+
 pub struct MediaCodecAudioEncoder {
     input: MediaCodecAudioEncoderInput,
     output: MediaCodecAudioEncoderOutput,
@@ -121,6 +124,9 @@ impl EncoderInput for MediaCodecAudioEncoderInput {
 }
 
 async fn push_impl(this: &mut MediaCodecAudioEncoderInput, data: AudioSample) -> Result<()> {
+    // nothing below awaits, so the sample begins and ends on the same thread
+    let _guard = PUSH.begin();
+
     // Keep the encoded audio contiguous in sample-count terms (see the Apple backend for the rationale):
     // when the input timeline jumps forward (dropped or suspended audio that advances `timestamp_in_samples`
     // by more than the number of samples actually delivered), fill the gap with leading silence so
@@ -177,7 +183,7 @@ async fn push_impl(this: &mut MediaCodecAudioEncoderInput, data: AudioSample) ->
 
                 // Calculate timestamp in microseconds
                 let timestamp_us =
-                    (position_in_samples as f64 / this.sample_rate as f64 * 1_000_000.0) as i64;
+                    Timebase::MICROSECONDS.from_samples(position_in_samples, this.sample_rate);
 
                 this.last_timestamp = timestamp_us;
 
@@ -204,7 +210,7 @@ impl EncoderOutput for MediaCodecAudioEncoderOutput {
     type Data = CommonEncodedData;
 
     async fn pull(&mut self) -> unienc_common::Result<Option<Self::Data>> {
-        pull_encoded_data_with_codec(&self.codec, &mut self.end_of_stream)
+        pull_encoded_data_with_codec(&self.codec, &mut self.end_of_stream, None)
             .await
             .map_err(Into::into)
     }
@@ -0,0 +1,292 @@
+//! Choice of the H.264 encoder instance. The default encoder of some devices produces broken
+//! streams, so an encoder can be requested by name, and encoders on a denylist are replaced by
+//! another one when MediaCodec picks them.
+
+use std::sync::Mutex;
+
+use jni::JNIEnv;
+use jni::objects::{JObject, JObjectArray, JString, JValue};
+use unienc_common::{DeviceClass, EncoderCapabilities};
+
+use crate::common::{MediaCodec, get_android_api_level};
+use crate::config::MIME_TYPE_VIDEO_AVC;
+use crate::error::{AndroidError, Result};
+use crate::java::*;
+
+/// Used in place of a denied encoder when the device has it (the Codec2 software encoder of
+/// Android 10 and later).
+const FALLBACK_VIDEO_ENCODER: &str = "c2.android.avc.encoder";
+
+/// Encoder names denied unless requested by name. Entries ending with `*` match by prefix.
+/// Empty until specific encoders are confirmed broken; apps can add their own.
+const BUILTIN_DENYLIST: &[&str] = &[];
+
+// MediaCodecList.REGULAR_CODECS
+const REGULAR_CODECS: i32 = 0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncoderInfo {
+    pub name: String,
+    /// `None` before API 29, which cannot tell.
+    pub hardware_accelerated: Option<bool>,
+}
+
+struct Policy {
+    preferred: Option<String>,
+    denylist: Option<Vec<String>>,
+}
+
+static POLICY: Mutex<Policy> = Mutex::new(Policy {
+    preferred: None,
+    denylist: None,
+});
+
+/// Applies to video encoders created afterwards. `preferred` is used whenever the device has it.
+/// `denylist` replaces the built-in one, and `None` restores it.
+pub fn set_video_encoder_policy(preferred: Option<String>, denylist: Option<Vec<String>>) {
+    let mut policy = POLICY.lock().unwrap_or_else(|e| e.into_inner());
+    *policy = Policy {
+        preferred,
+        denylist,
+    };
+}
+
+/// Encoders of the device supporting `mime_type`, in MediaCodec's order of preference.
+pub fn list_encoders(mime_type: &str) -> Result<Vec<EncoderInfo>> {
+    let api_level = get_android_api_level()?;
+    scan_encoders(mime_type, |env, info, name| {
+        Ok(EncoderInfo {
+            name,
+            hardware_accelerated: is_hardware_accelerated(env, info, api_level)?,
+        })
+    })
+}
+
+/// Calls `f` with the `MediaCodecInfo` and the name of every encoder of the device supporting
+/// `mime_type`, in MediaCodec's order of preference.
+fn scan_encoders<T>(
+    mime_type: &str,
+    mut f: impl FnMut(&mut JNIEnv, &JObject, String) -> Result<T>,
+) -> Result<Vec<T>> {
+    let env = &mut attach_current_thread()?;
+    let list_class = env.find_class("android/media/MediaCodecList")?;
+    let list = env.new_object(list_class, "(I)V", &[JValue::Int(REGULAR_CODECS)])?;
+    check_jni_exception(env)?;
+    let infos = JObjectArray::from(call_object_method(
+        env,
+        &list,
+        "getCodecInfos",
+        "()[Landroid/media/MediaCodecInfo;",
+        &[],
+    )?);
+
+    let mut encoders = Vec::new();
+    for i in 0..env.get_array_length(&infos)? {
+        // devices list a hundred codecs or more, each leaving several references behind
+        let encoder = local_frame(env, |env| {
+            let info = env.get_object_array_element(&infos, i)?;
+            if !env.call_method(&info, "isEncoder", "()Z", &[])?.z()? {
+                return Ok(None);
+            }
+            let types = JObjectArray::from(call_object_method(
+                env,
+                &info,
+                "getSupportedTypes",
+                "()[Ljava/lang/String;",
+                &[],
+            )?);
+            if !contains_string(env, &types, mime_type)? {
+                return Ok(None);
+            }
+            let name = call_object_method(env, &info, "getName", "()Ljava/lang/String;", &[])?;
+            let name: String = env.get_string(&JString::from(name))?.into();
+            f(env, &info, name).map(Some)
+        })?;
+        encoders.extend(encoder);
+    }
+    Ok(encoders)
+}
+
+/// `None` before API 29, which cannot tell.
+fn is_hardware_accelerated(
+    env: &mut JNIEnv,
+    info: &JObject,
+    api_level: i32,
+) -> Result<Option<bool>> {
+    // isHardwareAccelerated is API 29+
+    if api_level < 29 {
+        return Ok(None);
+    }
+    Ok(Some(
+        env.call_method(info, "isHardwareAccelerated", "()Z", &[])?
+            .z()?,
+    ))
+}
+
+/// Highest bitrate of the H.264 encoder of `info`, and its highest frame rate at `width` by
+/// `height` unless it does not support the size.
+fn video_limits(
+    env: &mut JNIEnv,
+    info: &JObject,
+    width: u32,
+    height: u32,
+) -> Result<(u32, Option<f64>)> {
+    let mime_type = env.new_string(MIME_TYPE_VIDEO_AVC)?;
+    let capabilities = call_object_method(
+        env,
+        info,
+        "getCapabilitiesForType",
+        "(Ljava/lang/String;)Landroid/media/MediaCodecInfo$CodecCapabilities;",
+        &[JValue::Object(&mime_type)],
+    )?;
+    let video = call_object_method(
+        env,
+        &capabilities,
+        "getVideoCapabilities",
+        "()Landroid/media/MediaCodecInfo$VideoCapabilities;",
+        &[],
+    )?;
+    let bitrates = call_object_method(
+        env,
+        &video,
+        "getBitrateRange",
+        "()Landroid/util/Range;",
+        &[],
+    )?;
+    let max_bitrate =
+        call_object_method(env, &bitrates, "getUpper", "()Ljava/lang/Comparable;", &[])?;
+    let max_bitrate = env.call_method(&max_bitrate, "intValue", "()I", &[])?.i()?;
+
+    let size = [JValue::Int(width as i32), JValue::Int(height as i32)];
+    // getSupportedFrameRatesFor throws for sizes the encoder does not support
+    if !env
+        .call_method(&video, "isSizeSupported", "(II)Z", &size)?
+        .z()?
+    {
+        return Ok((max_bitrate.max(0) as u32, None));
+    }
+    let frame_rates = call_object_method(
+        env,
+        &video,
+        "getSupportedFrameRatesFor",
+        "(II)Landroid/util/Range;",
+        &size,
+    )?;
+    let max_frame_rate = call_object_method(
+        env,
+        &frame_rates,
+        "getUpper",
+        "()Ljava/lang/Comparable;",
+        &[],
+    )?;
+    let max_frame_rate = env
+        .call_method(&max_frame_rate, "doubleValue", "()D", &[])?
+        .d()?;
+    Ok((max_bitrate.max(0) as u32, Some(max_frame_rate)))
+}
+
+/// What the H.264 encoder that video encoders are likely created with can do at `width` by
+/// `height`: the preferred one if the device has it, otherwise the first one not denied.
+pub fn video_encoder_capabilities(width: u32, height: u32) -> Result<EncoderCapabilities> {
+    let (preferred, denylist) = policy();
+    let api_level = get_android_api_level()?;
+    let encoders = scan_encoders(MIME_TYPE_VIDEO_AVC, |env, info, name| {
+        let hardware_accelerated = is_hardware_accelerated(env, info, api_level)?;
+        // the limits are only a hint, which some encoders fail to report
+        let limits = match video_limits(env, info, width, height) {
+            Ok(limits) => Some(limits),
+            Err(_) => {
+                env.exception_clear()?;
+                None
+            }
+        };
+        Ok((name, hardware_accelerated, limits))
+    })?;
+    let encoder = preferred
+        .and_then(|preferred| encoders.iter().find(|(name, ..)| *name == preferred))
+        .or_else(|| {
+            encoders
+                .iter()
+                .find(|(name, ..)| !is_denied(&denylist, name))
+        });
+
+    let mut capabilities = EncoderCapabilities {
+        device_class: DeviceClass::Mobile,
+        ..Default::default()
+    };
+    if let Some((_, hardware_accelerated, limits)) = encoder {
+        capabilities.hardware_accelerated = *hardware_accelerated;
+        if let Some((max_bitrate, max_frame_rate)) = limits {
+            capabilities.max_bitrate = Some(*max_bitrate);
+            capabilities.max_frame_rate = *max_frame_rate;
+        }
+    }
+    Ok(capabilities)
+}
+
+/// H.264 encoders, which video encoders are chosen from.
+pub fn list_video_encoders() -> Result<Vec<EncoderInfo>> {
+    list_encoders(MIME_TYPE_VIDEO_AVC)
+}
+
+fn contains_string(env: &mut jni::JNIEnv, array: &JObjectArray, value: &str) -> Result<bool> {
+    for i in 0..env.get_array_length(array)? {
+        let element: String = local_frame(env, |env| {
+            let element: JObject = env.get_object_array_element(array, i)?;
+            Ok(env.get_string(&JString::from(element))?.into())
+        })?;
+        if element.eq_ignore_ascii_case(value) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn is_denied(denylist: &[String], name: &str) -> bool {
+    denylist.iter().any(|entry| match entry.strip_suffix('*') {
+        Some(prefix) => name
+            .get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(prefix)),
+        None => name.eq_ignore_ascii_case(entry),
+    })
+}
+
+/// The preferred encoder and the denylist in effect.
+fn policy() -> (Option<String>, Vec<String>) {
+    let policy = POLICY.lock().unwrap_or_else(|e| e.into_inner());
+    let denylist = policy.denylist.clone().unwrap_or_else(|| {
+        BUILTIN_DENYLIST
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect()
+    });
+    (policy.preferred.clone(), denylist)
+}
+
+/// Creates the H.264 encoder according to the policy set with [`set_video_encoder_policy`].
+pub(crate) fn create_video_encoder() -> Result<MediaCodec> {
+    let (preferred, denylist) = policy();
+
+    if let Some(preferred) = preferred {
+        match MediaCodec::create_by_name(&preferred) {
+            Ok(codec) => return Ok(codec),
+            Err(e) => unienc_common::log!("Preferred encoder {preferred} is not available: {e}"),
+        }
+    }
+
+    let codec = MediaCodec::create_encoder(MIME_TYPE_VIDEO_AVC)?;
+    let name = codec.name()?;
+    if !is_denied(&denylist, &name) {
+        return Ok(codec);
+    }
+    drop(codec);
+
+    let encoders = list_video_encoders()?;
+    let fallback = encoders
+        .iter()
+        .find(|e| e.name == FALLBACK_VIDEO_ENCODER && !is_denied(&denylist, &e.name))
+        .or_else(|| encoders.iter().find(|e| !is_denied(&denylist, &e.name)))
+        .ok_or(AndroidError::NoAllowedEncoder(name.clone()))?;
+    unienc_common::log!("Encoder {name} is denied; using {}", fallback.name);
+    MediaCodec::create_by_name(&fallback.name)
+}
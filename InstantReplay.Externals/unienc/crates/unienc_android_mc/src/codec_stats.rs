@@ -0,0 +1,153 @@
+//! Stats of the video encoder: the metrics MediaCodec reports and the frames it holds. Some
+//! encoders stop producing output while frames are queued, which would stop the recording
+//! silently, so a codec holding frames without output for the stall timeout is flushed. The frames
+//! it held are dropped and the stream resumes from a keyframe after the gap.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+use crate::common::MediaCodec;
+use crate::error::Result;
+
+static STALL_TIMEOUT_MILLIS: AtomicU64 = AtomicU64::new(5000);
+static LATEST: Mutex<Weak<CodecActivity>> = Mutex::new(Weak::new());
+
+/// Metrics from `MediaCodec.getMetrics`, API 26+. Latencies are reported by some devices only.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaCodecMetrics {
+    pub codec: Option<String>,
+    pub mime: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Microseconds from queueing a frame to its output.
+    pub latency_avg_us: Option<i64>,
+    pub latency_max_us: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VideoCodecStats {
+    /// As of the codec start, the latest stall or the end of input.
+    pub metrics: MediaCodecMetrics,
+    pub frames_queued: u64,
+    pub frames_output: u64,
+    /// Times the codec stalled and was flushed.
+    pub stalls: u32,
+}
+
+/// Changes how long a video encoder may hold frames without output before it is flushed, for
+/// encoders created afterwards. `None` never flushes.
+pub fn set_stall_timeout(timeout: Option<Duration>) {
+    let millis = timeout.map_or(0, |timeout| (timeout.as_millis() as u64).max(1));
+    STALL_TIMEOUT_MILLIS.store(millis, Ordering::Relaxed);
+}
+
+/// Stats of the latest video encoder still alive.
+pub fn video_codec_stats() -> Option<VideoCodecStats> {
+    let latest = LATEST.lock().unwrap_or_else(|e| e.into_inner()).upgrade()?;
+    Some(latest.stats())
+}
+
+/// Frames going through a video encoder, shared by its input and output.
+pub(crate) struct CodecActivity {
+    stall_timeout: Option<Duration>,
+    queued: AtomicU64,
+    output: AtomicU64,
+    stalls: AtomicU32,
+    // since the last output, or the first frame queued after the codec caught up
+    pending_since: Mutex<Option<Instant>>,
+    metrics: Mutex<MediaCodecMetrics>,
+    // held from dequeueing an input buffer to queueing it, since a flush invalidates its index
+    input: Mutex<()>,
+}
+
+impl CodecActivity {
+    /// Creates the activity of a new video encoder, reported by [`video_codec_stats`].
+    pub fn register() -> Arc<Self> {
+        let millis = STALL_TIMEOUT_MILLIS.load(Ordering::Relaxed);
+        let activity = Arc::new(Self {
+            stall_timeout: (millis > 0).then(|| Duration::from_millis(millis)),
+            queued: AtomicU64::new(0),
+            output: AtomicU64::new(0),
+            stalls: AtomicU32::new(0),
+            pending_since: Mutex::new(None),
+            metrics: Mutex::new(MediaCodecMetrics::default()),
+            input: Mutex::new(()),
+        });
+        *LATEST.lock().unwrap_or_else(|e| e.into_inner()) = Arc::downgrade(&activity);
+        activity
+    }
+
+    pub fn lock_input(&self) -> MutexGuard<'_, ()> {
+        self.input.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn frame_queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.lock_pending().get_or_insert_with(Instant::now);
+    }
+
+    pub fn frame_output(&self) {
+        let output = self.output.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut pending_since = self.lock_pending();
+        *pending_since = (self.queued.load(Ordering::Relaxed) > output).then(Instant::now);
+    }
+
+    /// Flushes `codec` if it held frames without output for the stall timeout.
+    pub fn check_stall(&self, codec: &MediaCodec) -> Result<()> {
+        let Some(timeout) = self.stall_timeout else {
+            return Ok(());
+        };
+        if !self
+            .lock_pending()
+            .is_some_and(|since| since.elapsed() >= timeout)
+        {
+            return Ok(());
+        }
+
+        let _input = self.lock_input();
+        let queued = self.queued.load(Ordering::Relaxed);
+        let held = queued.saturating_sub(self.output.load(Ordering::Relaxed));
+        unienc_common::log!(
+            "Video encoder produced no output for {:.1}s with {held} frames queued; flushing it",
+            timeout.as_secs_f64()
+        );
+        codec.flush()?;
+        codec.request_sync_frame()?;
+
+        self.output.store(queued, Ordering::Relaxed);
+        *self.lock_pending() = None;
+        self.stalls.fetch_add(1, Ordering::Relaxed);
+        self.update_metrics(codec);
+        Ok(())
+    }
+
+    pub fn update_metrics(&self, codec: &MediaCodec) {
+        match codec.metrics() {
+            Ok(Some(metrics)) => {
+                unienc_common::log_capture::event(format_args!("video codec metrics: {metrics:?}"));
+                *self.metrics.lock().unwrap_or_else(|e| e.into_inner()) = metrics;
+            }
+            Ok(None) => {}
+            Err(e) => unienc_common::log!("Failed to read codec metrics: {e}"),
+        }
+    }
+
+    fn stats(&self) -> VideoCodecStats {
+        VideoCodecStats {
+            metrics: self
+                .metrics
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+            frames_queued: self.queued.load(Ordering::Relaxed),
+            frames_output: self.output.load(Ordering::Relaxed),
+            stalls: self.stalls.load(Ordering::Relaxed),
+        }
+    }
+
+    // a plain value that stays consistent even if a holder panicked
+    fn lock_pending(&self) -> MutexGuard<'_, Option<Instant>> {
+        self.pending_since.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
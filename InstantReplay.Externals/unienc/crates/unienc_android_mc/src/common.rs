@@ -7,11 +7,87 @@ use jni::{
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::{collections::HashMap, fmt::Display, sync::Arc, time::Duration};
-use unienc_common::{EncodedData, UniencSampleKind, VideoFrameBgra32};
+use unienc_common::passthrough::h264::{AccessUnitNormalizer, EncodedAccessUnit, NalFormat};
+use unienc_common::{EncodedData, Timebase, UniencSampleKind, VideoFrameBgra32};
 
+use crate::codec_stats::{CodecActivity, MediaCodecMetrics};
+use crate::config::format_keys;
 use crate::error::{AndroidError, Result};
 use crate::java::*;
 
+const MEDIA_CODEC: &str = "android/media/MediaCodec";
+const IMAGE: &str = "android/media/Image";
+const IMAGE_PLANE: &str = "android/media/Image$Plane";
+const BUFFER_INFO: &str = "android/media/MediaCodec$BufferInfo";
+const BYTE_BUFFER: &str = "java/nio/ByteBuffer";
+
+// methods called for every frame, resolved once
+static DEQUEUE_INPUT_BUFFER: JavaMethod =
+    JavaMethod::new(MEDIA_CODEC, "dequeueInputBuffer", "(J)I");
+static GET_INPUT_BUFFER: JavaMethod =
+    JavaMethod::new(MEDIA_CODEC, "getInputBuffer", "(I)Ljava/nio/ByteBuffer;");
+static GET_INPUT_IMAGE: JavaMethod =
+    JavaMethod::new(MEDIA_CODEC, "getInputImage", "(I)Landroid/media/Image;");
+static QUEUE_INPUT_BUFFER: JavaMethod =
+    JavaMethod::new(MEDIA_CODEC, "queueInputBuffer", "(IIIJI)V");
+static DEQUEUE_OUTPUT_BUFFER: JavaMethod = JavaMethod::new(
+    MEDIA_CODEC,
+    "dequeueOutputBuffer",
+    "(Landroid/media/MediaCodec$BufferInfo;J)I",
+);
+static GET_OUTPUT_BUFFER: JavaMethod =
+    JavaMethod::new(MEDIA_CODEC, "getOutputBuffer", "(I)Ljava/nio/ByteBuffer;");
+static GET_OUTPUT_IMAGE: JavaMethod =
+    JavaMethod::new(MEDIA_CODEC, "getOutputImage", "(I)Landroid/media/Image;");
+static RELEASE_OUTPUT_BUFFER: JavaMethod =
+    JavaMethod::new(MEDIA_CODEC, "releaseOutputBuffer", "(IZ)V");
+static IMAGE_GET_WIDTH: JavaMethod = JavaMethod::new(IMAGE, "getWidth", "()I");
+static IMAGE_GET_HEIGHT: JavaMethod = JavaMethod::new(IMAGE, "getHeight", "()I");
+static IMAGE_GET_PLANES: JavaMethod =
+    JavaMethod::new(IMAGE, "getPlanes", "()[Landroid/media/Image$Plane;");
+static IMAGE_CLOSE: JavaMethod = JavaMethod::new(IMAGE, "close", "()V");
+static IMAGE_GET_CROP_RECT: JavaMethod =
+    JavaMethod::new(IMAGE, "getCropRect", "()Landroid/graphics/Rect;");
+static PLANE_GET_BUFFER: JavaMethod =
+    JavaMethod::new(IMAGE_PLANE, "getBuffer", "()Ljava/nio/ByteBuffer;");
+static PLANE_GET_PIXEL_STRIDE: JavaMethod = JavaMethod::new(IMAGE_PLANE, "getPixelStride", "()I");
+static PLANE_GET_ROW_STRIDE: JavaMethod = JavaMethod::new(IMAGE_PLANE, "getRowStride", "()I");
+pub(crate) static BUFFER_INFO_CLASS: JavaClass = JavaClass::new(BUFFER_INFO);
+static BUFFER_INFO_OFFSET: JavaField = JavaField::new(BUFFER_INFO, "offset", "I");
+static BUFFER_INFO_SIZE: JavaField = JavaField::new(BUFFER_INFO, "size", "I");
+static BUFFER_INFO_FLAGS: JavaField = JavaField::new(BUFFER_INFO, "flags", "I");
+static BUFFER_INFO_PRESENTATION_TIME: JavaField =
+    JavaField::new(BUFFER_INFO, "presentationTimeUs", "J");
+static BYTE_BUFFER_PUT: JavaMethod =
+    JavaMethod::new(BYTE_BUFFER, "put", "([B)Ljava/nio/ByteBuffer;");
+static BYTE_BUFFER_GET: JavaMethod =
+    JavaMethod::new(BYTE_BUFFER, "get", "([BII)Ljava/nio/ByteBuffer;");
+static BUFFER_SET_POSITION: JavaMethod =
+    JavaMethod::new("java/nio/Buffer", "position", "(I)Ljava/nio/Buffer;");
+static IMAGE_SET_TIMESTAMP: JavaMethod = JavaMethod::new(IMAGE, "setTimestamp", "(J)V");
+static IMAGE_GET_HARDWARE_BUFFER: JavaMethod = JavaMethod::new(
+    IMAGE,
+    "getHardwareBuffer",
+    "()Landroid/hardware/HardwareBuffer;",
+);
+static HARDWARE_BUFFER_CLOSE: JavaMethod =
+    JavaMethod::new("android/hardware/HardwareBuffer", "close", "()V");
+static IMAGE_WRITER_DEQUEUE_INPUT_IMAGE: JavaMethod = JavaMethod::new(
+    "android/media/ImageWriter",
+    "dequeueInputImage",
+    "()Landroid/media/Image;",
+);
+static IMAGE_WRITER_QUEUE_INPUT_IMAGE: JavaMethod = JavaMethod::new(
+    "android/media/ImageWriter",
+    "queueInputImage",
+    "(Landroid/media/Image;)V",
+);
+static IMAGE_READER_ACQUIRE_NEXT_IMAGE: JavaMethod = JavaMethod::new(
+    "android/media/ImageReader",
+    "acquireNextImage",
+    "()Landroid/media/Image;",
+);
+
 /// Inner struct for MediaCodec
 struct MediaCodecInner {
     codec: SafeGlobalRef,
@@ -26,11 +102,20 @@ pub struct MediaCodec {
 impl MediaCodec {
     /// Create a new MediaCodec encoder
     pub fn create_encoder(mime_type: &str) -> Result<Self> {
+        Self::create_by_type("createEncoderByType", mime_type)
+    }
+
+    /// Create a new MediaCodec decoder
+    pub fn create_decoder(mime_type: &str) -> Result<Self> {
+        Self::create_by_type("createDecoderByType", mime_type)
+    }
+
+    fn create_by_type(factory: &str, mime_type: &str) -> Result<Self> {
         let env = &mut attach_current_thread()?;
         let codec_class = env.find_class("android/media/MediaCodec")?;
         let method_id = env.get_static_method_id(
             &codec_class,
-            "createEncoderByType",
+            factory,
             "(Ljava/lang/String;)Landroid/media/MediaCodec;",
         )?;
 
@@ -51,6 +136,41 @@ impl MediaCodec {
         })
     }
 
+    /// Create a specific codec, such as one listed by [`crate::codec_selection::list_encoders`]
+    pub fn create_by_name(name: &str) -> Result<Self> {
+        let env = &mut attach_current_thread()?;
+        let codec_class = env.find_class("android/media/MediaCodec")?;
+        let name = to_java_string(env, name)?;
+        let codec = env.call_static_method(
+            codec_class,
+            "createByCodecName",
+            "(Ljava/lang/String;)Landroid/media/MediaCodec;",
+            &[JValue::Object(&name)],
+        );
+        // thrown for unknown names, and cleared so the caller can fall back to another codec
+        check_jni_exception(env)?;
+        let codec = codec
+            .map_err(|_| AndroidError::JniMethodCallFailed("createByCodecName".to_string()))?;
+
+        let codec = SafeGlobalRef::new(env, codec.l()?)?;
+
+        Ok(Self {
+            inner: Arc::new(MediaCodecInner { codec }),
+        })
+    }
+
+    pub fn name(&self) -> Result<String> {
+        let env = &mut attach_current_thread()?;
+        let name = call_object_method(
+            env,
+            self.inner.codec.as_obj(),
+            "getName",
+            "()Ljava/lang/String;",
+            &[],
+        )?;
+        Ok(env.get_string(&JString::from(name))?.into())
+    }
+
     /// Configure the codec
     pub fn configure(&self, format: &SafeGlobalRef) -> Result<()> {
         let env = &attach_current_thread()?;
@@ -68,6 +188,23 @@ impl MediaCodec {
         )
     }
 
+    /// Configure the codec as a decoder writing to buffers rather than a surface
+    pub fn configure_decoder(&self, format: &SafeGlobalRef) -> Result<()> {
+        let env = &attach_current_thread()?;
+        call_void_method(
+            env,
+            self.inner.codec.as_obj(),
+            "configure",
+            "(Landroid/media/MediaFormat;Landroid/view/Surface;Landroid/media/MediaCrypto;I)V",
+            &[
+                JValue::Object(format.as_obj()),
+                JValue::Object(&JObject::null()),
+                JValue::Object(&JObject::null()),
+                JValue::Int(0),
+            ],
+        )
+    }
+
     /// Start the codec
     pub fn start(&self) -> Result<()> {
         let env = &attach_current_thread()?;
@@ -89,11 +226,9 @@ impl MediaCodec {
     /// Dequeue an input buffer
     pub fn dequeue_input_buffer(&self, timeout: Duration) -> Result<jint> {
         let env = &mut attach_current_thread()?;
-        call_int_method(
+        DEQUEUE_INPUT_BUFFER.call_int(
             env,
             self.inner.codec.as_obj(),
-            "dequeueInputBuffer",
-            "(J)I",
             &[JValue::Long(timeout.as_micros() as jlong)],
         )
     }
@@ -101,13 +236,8 @@ impl MediaCodec {
     /// Get an input buffer
     pub fn get_input_buffer(&self, index: jint) -> Result<SafeGlobalRef> {
         let env = &mut attach_current_thread()?;
-        let buffer = call_object_method(
-            env,
-            self.inner.codec.as_obj(),
-            "getInputBuffer",
-            "(I)Ljava/nio/ByteBuffer;",
-            &[JValue::Int(index)],
-        )?;
+        let buffer =
+            GET_INPUT_BUFFER.call_object(env, self.inner.codec.as_obj(), &[JValue::Int(index)])?;
         SafeGlobalRef::new(env, buffer)
     }
 
@@ -116,21 +246,15 @@ impl MediaCodec {
         let env = &mut attach_current_thread()?;
 
         // Call getInputImage - it may return null on some devices
-        let result = env.call_method(
-            self.inner.codec.as_obj(),
-            "getInputImage",
-            "(I)Landroid/media/Image;",
-            &[JValue::Int(index)],
-        )?;
-
-        let image = result.l()?;
+        let image =
+            GET_INPUT_IMAGE.call_object(env, self.inner.codec.as_obj(), &[JValue::Int(index)])?;
         if image.is_null() {
             return Err(AndroidError::ImageNull);
         }
 
         // Get width and height
-        let width = env.call_method(&image, "getWidth", "()I", &[])?.i()? as u32;
-        let height = env.call_method(&image, "getHeight", "()I", &[])?.i()? as u32;
+        let width = IMAGE_GET_WIDTH.call_int(env, &image, &[])? as u32;
+        let height = IMAGE_GET_HEIGHT.call_int(env, &image, &[])? as u32;
 
         let image_ref = SafeGlobalRef::new(env, image)?;
         Ok(MediaImage {
@@ -149,12 +273,10 @@ impl MediaCodec {
         timestamp: i64,
         flags: jint,
     ) -> Result<()> {
-        let env = &attach_current_thread()?;
-        call_void_method(
+        let env = &mut attach_current_thread()?;
+        QUEUE_INPUT_BUFFER.call_void(
             env,
             self.inner.codec.as_obj(),
-            "queueInputBuffer",
-            "(IIIJI)V",
             &[
                 JValue::Int(index),
                 JValue::Int(offset as jint),
@@ -172,11 +294,9 @@ impl MediaCodec {
         timeout_us: i64,
     ) -> Result<jint> {
         let env = &mut attach_current_thread()?;
-        call_int_method(
+        DEQUEUE_OUTPUT_BUFFER.call_int(
             env,
             self.inner.codec.as_obj(),
-            "dequeueOutputBuffer",
-            "(Landroid/media/MediaCodec$BufferInfo;J)I",
             &[
                 JValue::Object(buffer_info.as_obj()),
                 JValue::Long(timeout_us as jlong),
@@ -187,28 +307,80 @@ impl MediaCodec {
     /// Get an output buffer
     pub fn get_output_buffer(&self, index: jint) -> Result<SafeGlobalRef> {
         let env = &mut attach_current_thread()?;
-        let buffer = call_object_method(
-            env,
-            self.inner.codec.as_obj(),
-            "getOutputBuffer",
-            "(I)Ljava/nio/ByteBuffer;",
-            &[JValue::Int(index)],
-        )?;
+        let buffer =
+            GET_OUTPUT_BUFFER.call_object(env, self.inner.codec.as_obj(), &[JValue::Int(index)])?;
         SafeGlobalRef::new(env, buffer)
     }
 
+    /// Get the decoded frame of an output buffer, which must be dropped before the buffer is
+    /// released (API Level 21+)
+    pub fn get_output_image(&self, index: jint) -> Result<MediaImage> {
+        let env = &mut attach_current_thread()?;
+
+        let image =
+            GET_OUTPUT_IMAGE.call_object(env, self.inner.codec.as_obj(), &[JValue::Int(index)])?;
+        if image.is_null() {
+            return Err(AndroidError::ImageNull);
+        }
+
+        let width = IMAGE_GET_WIDTH.call_int(env, &image, &[])? as u32;
+        let height = IMAGE_GET_HEIGHT.call_int(env, &image, &[])? as u32;
+
+        Ok(MediaImage {
+            image: SafeGlobalRef::new(env, image)?,
+            width,
+            height,
+        })
+    }
+
     /// Release an output buffer
     pub fn release_output_buffer(&self, index: jint, render: bool) -> Result<()> {
-        let env = &attach_current_thread()?;
-        call_void_method(
+        let env = &mut attach_current_thread()?;
+        RELEASE_OUTPUT_BUFFER.call_void(
             env,
             self.inner.codec.as_obj(),
-            "releaseOutputBuffer",
-            "(IZ)V",
             &[JValue::Int(index), JValue::Bool(render as jboolean)],
         )
     }
 
+    /// Get the input format (after configure)
+    pub fn get_input_format(&self) -> Result<HashMap<String, MediaFormatValue>> {
+        let env = &mut attach_current_thread()?;
+        let format = env.call_method(
+            self.inner.codec.as_obj(),
+            "getInputFormat",
+            "()Landroid/media/MediaFormat;",
+            &[],
+        )?;
+        let format_obj = format.l()?;
+        format_to_map(env, &format_obj)
+    }
+
+    /// Color formats the codec accepts for `mime_type`, in its order of preference
+    pub fn supported_color_formats(&self, mime_type: &str) -> Result<Vec<jint>> {
+        let env = &mut attach_current_thread()?;
+        let codec_info = call_object_method(
+            env,
+            self.inner.codec.as_obj(),
+            "getCodecInfo",
+            "()Landroid/media/MediaCodecInfo;",
+            &[],
+        )?;
+        let mime = to_java_string(env, mime_type)?;
+        let capabilities = call_object_method(
+            env,
+            &codec_info,
+            "getCapabilitiesForType",
+            "(Ljava/lang/String;)Landroid/media/MediaCodecInfo$CodecCapabilities;",
+            &[JValue::Object(&mime)],
+        )?;
+        let formats = env.get_field(&capabilities, "colorFormats", "[I")?.l()?;
+        let formats = jni::objects::JIntArray::from(formats);
+        let mut values = vec![0; env.get_array_length(&formats)? as usize];
+        env.get_int_array_region(&formats, 0, &mut values)?;
+        Ok(values)
+    }
+
     /// Get the output format
     pub fn get_output_format(&self) -> Result<HashMap<String, MediaFormatValue>> {
         let env = &mut attach_current_thread()?;
@@ -246,6 +418,10 @@ impl MediaCodec {
     }
 
     pub fn print_codec_info(&self) -> Result<()> {
+        // getCanonicalName and isHardwareAccelerated are API 29+
+        if get_android_api_level()? < 29 {
+            return Ok(());
+        }
         let env = &mut attach_current_thread()?;
         let codec_info = call_object_method(
             env,
@@ -267,61 +443,125 @@ impl MediaCodec {
             .call_method(codec_info, "isHardwareAccelerated", "()Z", &[])?
             .z()?;
 
-        println!(
+        unienc_common::log!(
             "MediaCodec Info: Canonical Name: {}, Hardware Accelerated: {}",
-            canonical_name_rust, is_hardware_accelerated
+            canonical_name_rust,
+            is_hardware_accelerated
         );
 
         Ok(())
     }
 
-    pub fn print_metrics(&self) -> Result<()> {
+    /// Metrics from `getMetrics`, or `None` below API 26
+    pub fn metrics(&self) -> Result<Option<MediaCodecMetrics>> {
+        if get_android_api_level()? < 26 {
+            return Ok(None);
+        }
         let env = &mut attach_current_thread()?;
-        let metrics = call_object_method(
+        let bundle = call_object_method(
             env,
             self.inner.codec.as_obj(),
             "getMetrics",
             "()Landroid/os/PersistableBundle;",
             &[],
         )?;
+        if bundle.is_null() {
+            return Ok(None);
+        }
 
-        // Get the key set
-        let key_set = env
-            .call_method(&metrics, "keySet", "()Ljava/util/Set;", &[])?
-            .l()?;
+        Ok(Some(MediaCodecMetrics {
+            codec: read_metric(env, &bundle, "codec", metric_string)?,
+            mime: read_metric(env, &bundle, "mime", metric_string)?,
+            width: read_metric(env, &bundle, "width", metric_int)?,
+            height: read_metric(env, &bundle, "height", metric_int)?,
+            latency_avg_us: read_metric(env, &bundle, "latency.avg", metric_long)?,
+            latency_max_us: read_metric(env, &bundle, "latency.max", metric_long)?,
+        }))
+    }
 
-        let iterator = env
-            .call_method(&key_set, "iterator", "()Ljava/util/Iterator;", &[])?
-            .l()?;
+    /// Drops the frames the codec holds, keeping its configuration
+    pub fn flush(&self) -> Result<()> {
+        let env = &attach_current_thread()?;
+        call_void_method(env, self.inner.codec.as_obj(), "flush", "()V", &[])
+    }
 
-        println!("MediaCodec Metrics:");
-        while env.call_method(&iterator, "hasNext", "()Z", &[])?.z()? {
-            let key = env
-                .call_method(&iterator, "next", "()Ljava/lang/Object;", &[])?
-                .l()?;
-            let key_str = JString::from(key);
-            let key_rust = env.get_string(&key_str)?.to_str()?.to_string();
+    /// Make the next output a keyframe
+    pub fn request_sync_frame(&self) -> Result<()> {
+        let env = &mut attach_current_thread()?;
+        let params = env.new_object("android/os/Bundle", "()V", &[])?;
+        let key = to_java_string(env, "request-sync")?;
+        call_void_method(
+            env,
+            &params,
+            "putInt",
+            "(Ljava/lang/String;I)V",
+            &[JValue::Object(&key), JValue::Int(0)],
+        )?;
+        call_void_method(
+            env,
+            self.inner.codec.as_obj(),
+            "setParameters",
+            "(Landroid/os/Bundle;)V",
+            &[JValue::Object(&params)],
+        )
+    }
+}
 
-            let value = env
-                .call_method(
-                    &metrics,
-                    "get",
-                    "(Ljava/lang/String;)Ljava/lang/Object;",
-                    &[JValue::Object(&key_str)],
-                )?
-                .l()?;
+/// Reads `android.media.mediacodec.<key>` from a metrics bundle, if present
+fn read_metric<T>(
+    env: &mut JNIEnv,
+    bundle: &JObject,
+    key: &str,
+    read: impl FnOnce(&mut JNIEnv, &JObject, &JString) -> Result<T>,
+) -> Result<Option<T>> {
+    local_frame(env, |env| {
+        let key = to_java_string(env, &format!("android.media.mediacodec.{key}"))?;
+        let contains = env
+            .call_method(
+                bundle,
+                "containsKey",
+                "(Ljava/lang/String;)Z",
+                &[JValue::Object(&key)],
+            )?
+            .z()?;
+        if !contains {
+            return Ok(None);
+        }
+        read(env, bundle, &key).map(Some)
+    })
+}
 
-            let value_str = env
-                .call_method(&value, "toString", "()Ljava/lang/String;", &[])?
-                .l()?;
-            let value_jstr = JString::from(value_str);
-            let value_rust = env.get_string(&value_jstr)?.to_str()?.to_string();
+fn metric_string(env: &mut JNIEnv, bundle: &JObject, key: &JString) -> Result<String> {
+    let value = call_object_method(
+        env,
+        bundle,
+        "getString",
+        "(Ljava/lang/String;)Ljava/lang/String;",
+        &[JValue::Object(key)],
+    )?;
+    Ok(env.get_string(&JString::from(value))?.into())
+}
 
-            println!("  {}: {}", key_rust, value_rust);
-        }
+fn metric_int(env: &mut JNIEnv, bundle: &JObject, key: &JString) -> Result<i32> {
+    Ok(env
+        .call_method(
+            bundle,
+            "getInt",
+            "(Ljava/lang/String;I)I",
+            &[JValue::Object(key), JValue::Int(0)],
+        )?
+        .i()?)
+}
 
-        Ok(())
-    }
+fn metric_long(env: &mut JNIEnv, bundle: &JObject, key: &JString) -> Result<i64> {
+    Ok(env
+        .call_method(
+            bundle,
+            "getLong",
+            "(Ljava/lang/String;J)J",
+            &[JValue::Object(key), JValue::Long(0)],
+        )?
+        .j()?)
 }
 
 impl Drop for MediaCodecInner {
@@ -349,48 +589,59 @@ impl MediaImage {
         let env = &mut attach_current_thread()?;
 
         // Call getPlanes() which returns Image.Plane[]
-        let planes_array = env
-            .call_method(
-                self.image.as_obj(),
-                "getPlanes",
-                "()[Landroid/media/Image$Plane;",
-                &[],
-            )?
-            .l()?;
+        let planes_array = IMAGE_GET_PLANES.call_object(env, self.image.as_obj(), &[])?;
 
         let planes_array_ref = jni::objects::JObjectArray::from(planes_array);
         let plane_count = env.get_array_length(&planes_array_ref)? as usize;
         let mut planes = Vec::with_capacity(plane_count);
 
         for i in 0..plane_count {
-            let plane = env.get_object_array_element(&planes_array_ref, i as jint)?;
-
-            // Get buffer
-            let buffer = env
-                .call_method(&plane, "getBuffer", "()Ljava/nio/ByteBuffer;", &[])?
-                .l()?;
-
-            // Get pixel stride
-            let pixel_stride = env.call_method(&plane, "getPixelStride", "()I", &[])?.i()?;
-
-            // Get row stride
-            let row_stride = env.call_method(&plane, "getRowStride", "()I", &[])?.i()?;
-
-            let buffer_ref = SafeGlobalRef::new(env, buffer)?;
-
-            let (base_ptr, _capacity, position) = get_direct_buffer_info(env, buffer_ref.as_obj())?;
-            let ptr = unsafe { base_ptr.add(position) };
-            planes.push(ImagePlane {
-                _buffer: buffer_ref,
-                ptr,
-                pixel_stride,
-                row_stride,
-            });
+            let plane = local_frame(env, |env| {
+                let plane = env.get_object_array_element(&planes_array_ref, i as jint)?;
+
+                // Get buffer
+                let buffer = PLANE_GET_BUFFER.call_object(env, &plane, &[])?;
+
+                // Get pixel stride
+                let pixel_stride = PLANE_GET_PIXEL_STRIDE.call_int(env, &plane, &[])?;
+
+                // Get row stride
+                let row_stride = PLANE_GET_ROW_STRIDE.call_int(env, &plane, &[])?;
+
+                let buffer_ref = SafeGlobalRef::new(env, buffer)?;
+
+                let (base_ptr, _capacity, position) =
+                    get_direct_buffer_info(env, buffer_ref.as_obj())?;
+                let ptr = unsafe { base_ptr.add(position) };
+                Ok(ImagePlane {
+                    _buffer: buffer_ref,
+                    ptr,
+                    pixel_stride,
+                    row_stride,
+                })
+            })?;
+            planes.push(plane);
         }
 
         Ok(planes)
     }
 
+    /// The region of the image holding the picture, as (left, top, width, height)
+    pub fn crop_rect(&self) -> Result<(u32, u32, u32, u32)> {
+        let env = &mut attach_current_thread()?;
+        let rect = IMAGE_GET_CROP_RECT.call_object(env, self.image.as_obj(), &[])?;
+        let mut field =
+            |name: &str| -> Result<u32> { Ok(env.get_field(&rect, name, "I")?.i()? as u32) };
+        let (left, top) = (field("left")?, field("top")?);
+        let (right, bottom) = (field("right")?, field("bottom")?);
+        Ok((
+            left,
+            top,
+            right.saturating_sub(left),
+            bottom.saturating_sub(top),
+        ))
+    }
+
     pub fn width(&self) -> u32 {
         self.width
     }
@@ -404,7 +655,7 @@ impl Drop for MediaImage {
     fn drop(&mut self) {
         // Close the image to release resources
         if let Ok(mut env) = attach_current_thread() {
-            let _ = env.call_method(self.image.as_obj(), "close", "()V", &[]);
+            let _ = IMAGE_CLOSE.call_void(&mut env, self.image.as_obj(), &[]);
         }
     }
 }
@@ -521,7 +772,7 @@ pub enum MediaFormatValue {
 
 /// Create MediaCodec BufferInfo
 pub fn create_buffer_info(env: &mut JNIEnv) -> Result<SafeGlobalRef> {
-    let class = env.find_class("android/media/MediaCodec$BufferInfo")?;
+    let class = BUFFER_INFO_CLASS.get(env)?;
     let obj = env.new_object(class, "()V", &[])?;
     SafeGlobalRef::new(env, obj)
 }
@@ -531,26 +782,25 @@ pub fn read_buffer_info_common(
     env: &mut JNIEnv,
     buffer_info: &SafeGlobalRef,
 ) -> Result<(usize, usize, jint, i64)> {
-    let offset = get_int_field(env, buffer_info.as_obj(), "offset")? as usize;
-    let size = get_int_field(env, buffer_info.as_obj(), "size")? as usize;
-    let flags = get_int_field(env, buffer_info.as_obj(), "flags")?;
-    let timestamp = get_long_field(env, buffer_info.as_obj(), "presentationTimeUs")? as i64;
+    let buffer_info = buffer_info.as_obj();
+    let offset = BUFFER_INFO_OFFSET.get_int(env, buffer_info)? as usize;
+    let size = BUFFER_INFO_SIZE.get_int(env, buffer_info)? as usize;
+    let flags = BUFFER_INFO_FLAGS.get_int(env, buffer_info)?;
+    let timestamp = BUFFER_INFO_PRESENTATION_TIME.get_long(env, buffer_info)? as i64;
 
     Ok((offset, size, flags, timestamp))
 }
 
 /// Write data to ByteBuffer
-pub fn write_to_buffer(env: &JNIEnv, buffer: &SafeGlobalRef, data: &[u8]) -> Result<()> {
+pub fn write_to_buffer(env: &mut JNIEnv, buffer: &SafeGlobalRef, data: &[u8]) -> Result<()> {
     let byte_array = env.new_byte_array(data.len() as jint)?;
     env.set_byte_array_region(&byte_array, 0, unsafe {
         std::slice::from_raw_parts(data.as_ptr() as *const i8, data.len())
     })?;
 
-    call_void_method(
+    BYTE_BUFFER_PUT.call_object(
         env,
         buffer.as_obj(),
-        "put",
-        "([B)Ljava/nio/ByteBuffer;",
         &[JValue::Object(&JByteArray::from(byte_array).into())],
     )?;
 
@@ -559,29 +809,21 @@ pub fn write_to_buffer(env: &JNIEnv, buffer: &SafeGlobalRef, data: &[u8]) -> Res
 
 /// Read data from ByteBuffer
 pub fn read_from_buffer(
-    env: &JNIEnv,
+    env: &mut JNIEnv,
     buffer: &SafeGlobalRef,
     offset: usize,
     size: usize,
 ) -> Result<Vec<u8>> {
     // Set position
-    call_void_method(
-        env,
-        buffer.as_obj(),
-        "position",
-        "(I)Ljava/nio/Buffer;",
-        &[JValue::Int(offset as jint)],
-    )?;
+    BUFFER_SET_POSITION.call_object(env, buffer.as_obj(), &[JValue::Int(offset as jint)])?;
 
     // Create byte array
     let byte_array = env.new_byte_array(size as jint)?;
 
     // Get data
-    call_void_method(
+    BYTE_BUFFER_GET.call_object(
         env,
         buffer.as_obj(),
-        "get",
-        "([BII)Ljava/nio/ByteBuffer;",
         &[
             JValue::Object(&byte_array),
             JValue::Int(0),
@@ -660,6 +902,30 @@ pub enum CommonEncodedDataContent {
     FormatInfo(HashMap<String, MediaFormatValue>),
 }
 
+impl CommonEncodedData {
+    /// Converts a video sample into the backend-independent form other muxers accept. Format
+    /// changes and codec config buffers only update the parameter sets of `normalizer`.
+    pub fn to_access_unit(
+        &self,
+        normalizer: &mut AccessUnitNormalizer,
+    ) -> unienc_common::Result<Option<EncodedAccessUnit>> {
+        match &self.content {
+            CommonEncodedDataContent::Buffer { data, .. } => {
+                normalizer.normalize(data, NalFormat::AnnexB, self.timestamp)
+            }
+            // csd-0 and csd-1 hold SPS and PPS with start codes
+            CommonEncodedDataContent::FormatInfo(format) => {
+                for key in [format_keys::KEY_CSD_0, format_keys::KEY_CSD_1] {
+                    if let Some(MediaFormatValue::ByteBuffer(data)) = format.get(key) {
+                        normalizer.normalize(data, NalFormat::AnnexB, self.timestamp)?;
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
 impl EncodedData for CommonEncodedData {
     fn timestamp(&self) -> f64 {
         self.timestamp
@@ -683,11 +949,21 @@ impl EncodedData for CommonEncodedData {
             CommonEncodedDataContent::FormatInfo(_) => UniencSampleKind::Metadata,
         }
     }
+
+    fn size(&self) -> usize {
+        match &self.content {
+            CommonEncodedDataContent::Buffer { data, .. } => data.len(),
+            CommonEncodedDataContent::FormatInfo(_) => 0,
+        }
+    }
 }
 
+/// Pulls the next output of `codec`. With `activity`, frames output are counted and a stalled
+/// codec is flushed.
 pub(crate) async fn pull_encoded_data_with_codec(
     codec: &MediaCodec,
     end_of_stream: &mut bool,
+    activity: Option<&CodecActivity>,
 ) -> Result<Option<CommonEncodedData>> {
     if *end_of_stream {
         return Ok(None);
@@ -713,7 +989,7 @@ pub(crate) async fn pull_encoded_data_with_codec(
                         data: encoded_data,
                         buffer_flag: flags,
                     },
-                    timestamp: timestamp as f64 / 1_000_000.0, // Convert from microseconds
+                    timestamp: Timebase::MICROSECONDS.to_seconds(timestamp),
                 };
 
                 codec.release_output_buffer(buffer_index, false)?;
@@ -721,8 +997,17 @@ pub(crate) async fn pull_encoded_data_with_codec(
                 if (flags & media_codec_buffer_flag::BUFFER_FLAG_END_OF_STREAM) != 0 {
                     *end_of_stream = true;
                 }
+                if let Some(activity) = activity
+                    && (flags & media_codec_buffer_flag::BUFFER_FLAG_CODEC_CONFIG) == 0
+                    && size > 0
+                {
+                    activity.frame_output();
+                }
                 return Ok(Some(video_data));
             } else if buffer_index == media_codec_errors::INFO_TRY_AGAIN_LATER {
+                if let Some(activity) = activity {
+                    activity.check_stall(codec)?;
+                }
                 sleep = true;
             } else if buffer_index == media_codec_errors::INFO_OUTPUT_FORMAT_CHANGED {
                 let map = codec.get_output_format()?;
@@ -767,6 +1052,11 @@ pub(crate) fn format_to_map(
     env: &mut JNIEnv,
     format: &JObject,
 ) -> Result<HashMap<String, MediaFormatValue>> {
+    // MediaFormat.getKeys and getValueTypeForKey are API 29+
+    if get_android_api_level()? < 29 {
+        return format_to_map_legacy(env, format);
+    }
+
     // serialize
     let keys = env
         .call_method(format, "getKeys", "()Ljava/util/Set;", &[])?
@@ -776,88 +1066,142 @@ pub(crate) fn format_to_map(
         .l()?;
     let mut map = HashMap::<String, MediaFormatValue>::new();
     while env.call_method(&keys_iter, "hasNext", "()Z", &[])?.z()? {
-        // key is string
+        local_frame(env, |env| {
+            // key is string
 
-        let key = env
-            .call_method(&keys_iter, "next", "()Ljava/lang/Object;", &[])?
-            .l()?;
-        let key = JString::from(key);
-        let key_type = env
-            .call_method(
-                format,
-                "getValueTypeForKey",
-                "(Ljava/lang/String;)I",
-                &[JValue::Object(&key)],
-            )?
-            .i()?;
-        let key_str = env.get_string(&key)?;
-
-        match key_type {
-            media_format_key_type::NULL => {}
-            media_format_key_type::INTEGER => {
-                let value = env
-                    .call_method(
-                        format,
-                        "getInteger",
-                        "(Ljava/lang/String;)I",
-                        &[JValue::Object(&key)],
-                    )?
-                    .i()?;
-                map.insert(key_str.into(), MediaFormatValue::Integer(value));
-            }
-            media_format_key_type::LONG => {
-                let value = env
-                    .call_method(
-                        format,
-                        "getLong",
-                        "(Ljava/lang/String;)J",
-                        &[JValue::Object(&key)],
-                    )?
-                    .j()?;
-                map.insert(key_str.into(), MediaFormatValue::Long(value));
-            }
-            media_format_key_type::FLOAT => {
-                let value = env
-                    .call_method(
-                        format,
-                        "getFloat",
-                        "(Ljava/lang/String;)F",
-                        &[JValue::Object(&key)],
-                    )?
-                    .f()?;
-                map.insert(key_str.into(), MediaFormatValue::Float(value));
-            }
-            media_format_key_type::STRING => {
-                let value = env
-                    .call_method(
-                        format,
-                        "getString",
-                        "(Ljava/lang/String;)Ljava/lang/String;",
-                        &[JValue::Object(&key)],
-                    )?
-                    .l()?;
-                let value_str = JString::from(value);
-                let value = env.get_string(&value_str)?;
-                map.insert(key_str.into(), MediaFormatValue::String(value.into()));
+            let key = env
+                .call_method(&keys_iter, "next", "()Ljava/lang/Object;", &[])?
+                .l()?;
+            let key = JString::from(key);
+            let key_type = env
+                .call_method(
+                    format,
+                    "getValueTypeForKey",
+                    "(Ljava/lang/String;)I",
+                    &[JValue::Object(&key)],
+                )?
+                .i()?;
+            if let Some(value) = read_format_value(env, format, &key, key_type)? {
+                let key_str = env.get_string(&key)?;
+                map.insert(key_str.into(), value);
             }
-            media_format_key_type::BYTEBUFFER => {
-                let value = env
-                    .call_method(
-                        format,
-                        "getByteBuffer",
-                        "(Ljava/lang/String;)Ljava/nio/ByteBuffer;",
-                        &[JValue::Object(&key)],
-                    )?
-                    .l()?;
-                let encoded_data = crate::common::read_from_buffer_all(env, &value)?;
-                map.insert(key_str.into(), MediaFormatValue::ByteBuffer(encoded_data));
+            Ok(())
+        })?;
+    }
+    Ok(map)
+}
+
+/// Keys read from output formats below API 29, where a MediaFormat cannot list its keys. These
+/// are the ones MediaMuxer needs to add a track.
+const LEGACY_FORMAT_KEYS: &[(&str, i32)] = &[
+    (format_keys::KEY_MIME, media_format_key_type::STRING),
+    (format_keys::KEY_WIDTH, media_format_key_type::INTEGER),
+    (format_keys::KEY_HEIGHT, media_format_key_type::INTEGER),
+    (format_keys::KEY_SAMPLE_RATE, media_format_key_type::INTEGER),
+    (
+        format_keys::KEY_CHANNEL_COUNT,
+        media_format_key_type::INTEGER,
+    ),
+    (format_keys::KEY_CSD_0, media_format_key_type::BYTEBUFFER),
+    (format_keys::KEY_CSD_1, media_format_key_type::BYTEBUFFER),
+];
+
+fn format_to_map_legacy(
+    env: &mut JNIEnv,
+    format: &JObject,
+) -> Result<HashMap<String, MediaFormatValue>> {
+    let mut map = HashMap::<String, MediaFormatValue>::new();
+    for &(key_str, key_type) in LEGACY_FORMAT_KEYS {
+        local_frame(env, |env| {
+            let key = to_java_string(env, key_str)?;
+            let contains = env
+                .call_method(
+                    format,
+                    "containsKey",
+                    "(Ljava/lang/String;)Z",
+                    &[JValue::Object(&key)],
+                )?
+                .z()?;
+            if contains && let Some(value) = read_format_value(env, format, &key, key_type)? {
+                map.insert(key_str.to_string(), value);
             }
-            _ => {}
-        }
+            Ok(())
+        })?;
     }
     Ok(map)
 }
 
+fn read_format_value(
+    env: &mut JNIEnv,
+    format: &JObject,
+    key: &JString,
+    key_type: i32,
+) -> Result<Option<MediaFormatValue>> {
+    let value = match key_type {
+        media_format_key_type::NULL => None,
+        media_format_key_type::INTEGER => {
+            let value = env
+                .call_method(
+                    format,
+                    "getInteger",
+                    "(Ljava/lang/String;)I",
+                    &[JValue::Object(key)],
+                )?
+                .i()?;
+            Some(MediaFormatValue::Integer(value))
+        }
+        media_format_key_type::LONG => {
+            let value = env
+                .call_method(
+                    format,
+                    "getLong",
+                    "(Ljava/lang/String;)J",
+                    &[JValue::Object(key)],
+                )?
+                .j()?;
+            Some(MediaFormatValue::Long(value))
+        }
+        media_format_key_type::FLOAT => {
+            let value = env
+                .call_method(
+                    format,
+                    "getFloat",
+                    "(Ljava/lang/String;)F",
+                    &[JValue::Object(key)],
+                )?
+                .f()?;
+            Some(MediaFormatValue::Float(value))
+        }
+        media_format_key_type::STRING => {
+            let value = env
+                .call_method(
+                    format,
+                    "getString",
+                    "(Ljava/lang/String;)Ljava/lang/String;",
+                    &[JValue::Object(key)],
+                )?
+                .l()?;
+            let value_str = JString::from(value);
+            let value = env.get_string(&value_str)?;
+            Some(MediaFormatValue::String(value.into()))
+        }
+        media_format_key_type::BYTEBUFFER => {
+            let value = env
+                .call_method(
+                    format,
+                    "getByteBuffer",
+                    "(Ljava/lang/String;)Ljava/nio/ByteBuffer;",
+                    &[JValue::Object(key)],
+                )?
+                .l()?;
+            let encoded_data = crate::common::read_from_buffer_all(env, &value)?;
+            Some(MediaFormatValue::ByteBuffer(encoded_data))
+        }
+        _ => None,
+    };
+    Ok(value)
+}
+
 /// ImageWriter wrapper (API 29+)
 /// Used to write HardwareBuffer-backed images to MediaCodec input surface
 pub struct ImageWriter {
@@ -877,11 +1221,11 @@ impl ImageWriter {
 
         let writer = if api_level >= 33 {
             // API 33+: Use ImageWriter.Builder with explicit usage flags
-            println!("Using ImageWriter.Builder for API level {}", api_level);
+            unienc_common::log!("Using ImageWriter.Builder for API level {}", api_level);
             Self::new_with_builder(env, surface, max_images, width, height)?
         } else {
             // API 29-32: Use ImageWriter.newInstance with format parameter
-            println!(
+            unienc_common::log!(
                 "Using ImageWriter.newInstance with RGBA_8888 format for API level {}",
                 api_level
             );
@@ -993,14 +1337,7 @@ impl ImageWriter {
     /// Dequeue an available input image
     pub fn dequeue_input_image(&self) -> Result<ImageWriterImage> {
         let env = &mut attach_current_thread()?;
-        let image = env
-            .call_method(
-                self.writer.as_obj(),
-                "dequeueInputImage",
-                "()Landroid/media/Image;",
-                &[],
-            )?
-            .l()?;
+        let image = IMAGE_WRITER_DEQUEUE_INPUT_IMAGE.call_object(env, self.writer.as_obj(), &[])?;
 
         if image.is_null() {
             return Err(AndroidError::DequeueImageNull);
@@ -1015,18 +1352,12 @@ impl ImageWriter {
         let env = &mut attach_current_thread()?;
 
         // Set timestamp on the image
-        env.call_method(
-            image.image.as_obj(),
-            "setTimestamp",
-            "(J)V",
-            &[JValue::Long(timestamp_ns)],
-        )?;
+        IMAGE_SET_TIMESTAMP.call_void(env, image.image.as_obj(), &[JValue::Long(timestamp_ns)])?;
 
         // Queue the image
-        env.call_method(
+        IMAGE_WRITER_QUEUE_INPUT_IMAGE.call_void(
+            env,
             self.writer.as_obj(),
-            "queueInputImage",
-            "(Landroid/media/Image;)V",
             &[JValue::Object(image.image.as_obj())],
         )?;
 
@@ -1053,14 +1384,8 @@ impl ImageWriterImage {
         let env = &mut attach_current_thread()?;
 
         // Get HardwareBuffer from Image
-        let hardware_buffer = env
-            .call_method(
-                self.image.as_obj(),
-                "getHardwareBuffer",
-                "()Landroid/hardware/HardwareBuffer;",
-                &[],
-            )?
-            .l()?;
+        let hardware_buffer =
+            IMAGE_GET_HARDWARE_BUFFER.call_object(env, self.image.as_obj(), &[])?;
 
         if hardware_buffer.is_null() {
             return Err(AndroidError::HardwareBufferNull);
@@ -1069,12 +1394,12 @@ impl ImageWriterImage {
         // Convert Java HardwareBuffer to native AHardwareBuffer*
         // This acquires a reference to the AHardwareBuffer
         let ahb = unsafe {
-            ndk_sys::AHardwareBuffer_fromHardwareBuffer(env.get_raw(), hardware_buffer.as_raw())
-        };
+            crate::hardware_buffer::from_hardware_buffer(env.get_raw(), hardware_buffer.as_raw())
+        }?;
 
         // Close the Java HardwareBuffer object to prevent resource leak warning
         // The native AHardwareBuffer reference is still valid
-        env.call_method(&hardware_buffer, "close", "()V", &[])?;
+        HARDWARE_BUFFER_CLOSE.call_void(env, &hardware_buffer, &[])?;
 
         if ahb.is_null() {
             return Err(AndroidError::AHardwareBufferNull);
@@ -1087,7 +1412,86 @@ impl ImageWriterImage {
 impl Drop for ImageWriterImage {
     fn drop(&mut self) {
         if let Ok(mut env) = attach_current_thread() {
-            let _ = env.call_method(self.image.as_obj(), "close", "()V", &[]);
+            let _ = IMAGE_CLOSE.call_void(&mut env, self.image.as_obj(), &[]);
+        }
+    }
+}
+
+/// Wrapper for ImageReader, used to read back frames rendered into its surface
+pub struct ImageReader {
+    reader: SafeGlobalRef,
+    surface: SafeGlobalRef,
+    width: u32,
+    height: u32,
+}
+
+impl ImageReader {
+    /// Create a new RGBA_8888 ImageReader whose buffers can be rendered by the GPU and read by the
+    /// CPU (API 29+)
+    pub fn new(width: u32, height: u32, max_images: i32) -> Result<Self> {
+        // PixelFormat.RGBA_8888
+        const PIXEL_FORMAT_RGBA_8888: i32 = 0x1;
+        // USAGE_CPU_READ_OFTEN (0x3) | USAGE_GPU_SAMPLED_IMAGE (0x100) | USAGE_GPU_COLOR_OUTPUT (0x200)
+        const USAGE: i64 = 0x3 | 0x100 | 0x200;
+
+        // newInstance with usage flags is not there before API 29, as in is_blit_supported
+        if get_android_api_level()? < 29 {
+            return Err(unienc_common::CommonError::BlitNotSupported.into());
+        }
+
+        let env = &mut attach_current_thread()?;
+        let reader = env
+            .call_static_method(
+                "android/media/ImageReader",
+                "newInstance",
+                "(IIIIJ)Landroid/media/ImageReader;",
+                &[
+                    JValue::Int(width as i32),
+                    JValue::Int(height as i32),
+                    JValue::Int(PIXEL_FORMAT_RGBA_8888),
+                    JValue::Int(max_images),
+                    JValue::Long(USAGE),
+                ],
+            )?
+            .l()?;
+        check_jni_exception(env)?;
+
+        let surface =
+            call_object_method(env, &reader, "getSurface", "()Landroid/view/Surface;", &[])?;
+
+        Ok(Self {
+            surface: SafeGlobalRef::new(env, surface)?,
+            reader: SafeGlobalRef::new(env, reader)?,
+            width,
+            height,
+        })
+    }
+
+    pub fn surface(&self) -> &SafeGlobalRef {
+        &self.surface
+    }
+
+    /// Acquire the next queued image, or None if no image is available yet
+    pub fn acquire_next_image(&self) -> Result<Option<MediaImage>> {
+        let env = &mut attach_current_thread()?;
+        let image = IMAGE_READER_ACQUIRE_NEXT_IMAGE.call_object(env, self.reader.as_obj(), &[])?;
+
+        if image.is_null() {
+            return Ok(None);
+        }
+
+        Ok(Some(MediaImage {
+            image: SafeGlobalRef::new(env, image)?,
+            width: self.width,
+            height: self.height,
+        }))
+    }
+}
+
+impl Drop for ImageReader {
+    fn drop(&mut self) {
+        if let Ok(env) = attach_current_thread() {
+            let _ = call_void_method(&env, self.reader.as_obj(), "close", "()V", &[]);
         }
     }
 }
@@ -1103,12 +1507,12 @@ pub fn write_bgra_to_yuv_planes_with_padding(
         return Err(AndroidError::UnsupportedPlaneCount(planes.len()));
     }
 
-    let (y_data, u_data, v_data) = sample.to_yuv420_planes(Some((padded_width, padded_height)))?;
+    let (y_data, u_data, v_data) = sample.to_yuv420_planes(Some((padded_width, padded_height)));
     /*
-    println!("padded: {}x{}", padded_width, padded_height);
-    println!("Y: {}", planes[0]);
-    println!("U: {}", planes[1]);
-    println!("V: {}", planes[2]);
+    unienc_common::log!("padded: {}x{}", padded_width, padded_height);
+    unienc_common::log!("Y: {}", planes[0]);
+    unienc_common::log!("U: {}", planes[1]);
+    unienc_common::log!("V: {}", planes[2]);
     */
 
     // Write to planes using padded dimensions
@@ -13,6 +13,8 @@ pub mod format_keys {
     pub const KEY_BITRATE: &str = "bitrate";
     pub const KEY_DURATION: &str = "durationUs";
     pub const KEY_MAX_INPUT_SIZE: &str = "max-input-size";
+    pub const KEY_CSD_0: &str = "csd-0";
+    pub const KEY_CSD_1: &str = "csd-1";
 
     // Video keys
     pub const KEY_WIDTH: &str = "width";
@@ -33,6 +35,10 @@ pub mod format_keys {
 
 pub const COLOR_FORMAT_SURFACE: jint = 0x7F000789;
 pub const COLOR_FORMAT_YUV420_FLEXIBLE: jint = 0x7F420888;
+pub const COLOR_FORMAT_YUV420_PLANAR: jint = 19;
+pub const COLOR_FORMAT_YUV420_PACKED_PLANAR: jint = 20;
+pub const COLOR_FORMAT_YUV420_SEMI_PLANAR: jint = 21;
+pub const COLOR_FORMAT_YUV420_PACKED_SEMI_PLANAR: jint = 39;
 pub const AAC_OBJECT_TYPE_AAC_LC: jint = 2;
 
 pub const MUXER_OUTPUT_FORMAT_MPEG_4: jint = 0;
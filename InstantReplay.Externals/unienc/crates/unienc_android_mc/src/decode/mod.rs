@@ -0,0 +1,295 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use jni::{
+    JNIEnv,
+    objects::{JObject, JString, JValue},
+    sys::jlong,
+};
+use tokio::sync::mpsc;
+use unienc_common::{
+    DecodedVideoFrame, Decoder, SeekMode, Timebase, VideoFrameBgra32, buffer::SharedBuffer,
+};
+
+use crate::common::{
+    ImagePlane, MediaCodec, MediaImage, create_buffer_info,
+    media_codec_buffer_flag::BUFFER_FLAG_END_OF_STREAM, read_buffer_info_common,
+    set_format_integer,
+};
+use crate::config::{COLOR_FORMAT_YUV420_FLEXIBLE, format_keys::KEY_COLOR_FORMAT};
+use crate::error::{AndroidError, OptionExt, Result};
+use crate::java::*;
+
+const SEEK_TO_PREVIOUS_SYNC: i32 = 0;
+const DEQUEUE_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Decodes the video track with MediaExtractor and a MediaCodec decoder, yielding every frame at
+/// its presentation time.
+pub struct MediaCodecDecoder {
+    input_path: PathBuf,
+    rx: mpsc::Receiver<Result<DecodedVideoFrame>>,
+}
+
+impl MediaCodecDecoder {
+    pub fn new(input_path: &Path) -> Result<Self> {
+        Self::open(input_path, None)
+    }
+
+    fn open(input_path: &Path, seek: Option<(f64, SeekMode)>) -> Result<Self> {
+        let path = input_path
+            .to_str()
+            .context("Input path is not valid UTF-8")?;
+
+        let (extractor, format, mime) = {
+            let env = &mut attach_current_thread()?;
+            let extractor = env.new_object("android/media/MediaExtractor", "()V", &[])?;
+            let path = to_java_string(env, path)?;
+            call_void_method(
+                env,
+                &extractor,
+                "setDataSource",
+                "(Ljava/lang/String;)V",
+                &[JValue::Object(&path)],
+            )?;
+
+            let (format, mime) = select_video_track(env, &extractor)?;
+            if let Some((timestamp, _)) = seek {
+                call_void_method(
+                    env,
+                    &extractor,
+                    "seekTo",
+                    "(JI)V",
+                    &[
+                        JValue::Long(Timebase::MICROSECONDS.to_units(timestamp.max(0.0))),
+                        JValue::Int(SEEK_TO_PREVIOUS_SYNC),
+                    ],
+                )?;
+            }
+            // decoded frames are read from the output buffers as YUV 420 images
+            set_format_integer(env, &format, KEY_COLOR_FORMAT, COLOR_FORMAT_YUV420_FLEXIBLE)?;
+
+            (
+                SafeGlobalRef::new(env, extractor)?,
+                SafeGlobalRef::new(env, format)?,
+                mime,
+            )
+        };
+
+        let codec = MediaCodec::create_decoder(&mime)?;
+        codec.configure_decoder(&format)?;
+        codec.start()?;
+
+        // a precise seek returns the last frame at or before the target, which is only known
+        // once the frame after it is decoded
+        let target_us = match seek {
+            Some((timestamp, SeekMode::Precise)) => {
+                Some(Timebase::MICROSECONDS.to_units(timestamp.max(0.0)))
+            }
+            _ => None,
+        };
+
+        // the codec is fed and drained in a blocking loop, so frames are decoded on a dedicated
+        // thread
+        let (tx, rx) = mpsc::channel(4);
+        std::thread::spawn(move || {
+            if let Err(err) = decode(&extractor, &codec, target_us, &tx) {
+                let _ = tx.blocking_send(Err(err));
+            }
+            if let Ok(env) = attach_current_thread() {
+                let _ = call_void_method(&env, extractor.as_obj(), "release", "()V", &[]);
+            }
+        });
+
+        Ok(Self {
+            input_path: input_path.to_owned(),
+            rx,
+        })
+    }
+}
+
+/// Selects the first video track, returning its format and MIME type.
+fn select_video_track<'a>(
+    env: &mut JNIEnv<'a>,
+    extractor: &JObject,
+) -> Result<(JObject<'a>, String)> {
+    let mime_key = to_java_string(env, "mime")?;
+    let track_count = call_int_method(env, extractor, "getTrackCount", "()I", &[])?;
+    for track in 0..track_count {
+        let format = call_object_method(
+            env,
+            extractor,
+            "getTrackFormat",
+            "(I)Landroid/media/MediaFormat;",
+            &[JValue::Int(track)],
+        )?;
+        let mime = call_object_method(
+            env,
+            &format,
+            "getString",
+            "(Ljava/lang/String;)Ljava/lang/String;",
+            &[JValue::Object(&mime_key)],
+        )?;
+        let mime: String = env.get_string(&JString::from(mime))?.into();
+        if !mime.starts_with("video/") {
+            continue;
+        }
+
+        call_void_method(env, extractor, "selectTrack", "(I)V", &[JValue::Int(track)])?;
+        return Ok((format, mime));
+    }
+    Err(AndroidError::Other("No video track found".to_string()))
+}
+
+/// Feeds the samples of the selected track to `codec` and sends the frames it outputs until the
+/// end of the stream, or until the decoder is dropped. Frames before `target_us` are dropped but
+/// the last of them.
+fn decode(
+    extractor: &SafeGlobalRef,
+    codec: &MediaCodec,
+    mut target_us: Option<jlong>,
+    tx: &mpsc::Sender<Result<DecodedVideoFrame>>,
+) -> Result<()> {
+    let mut env = attach_current_thread()?;
+    let buffer_info = create_buffer_info(&mut *env)?;
+    let mut input_ended = false;
+    // last frame at or before the target of a precise seek
+    let mut candidate = None;
+
+    loop {
+        if !input_ended {
+            input_ended = queue_sample(extractor, codec)?;
+        }
+
+        let index =
+            codec.dequeue_output_buffer(&buffer_info, DEQUEUE_TIMEOUT.as_micros() as i64)?;
+        // format changes are reported by the images themselves
+        if index < 0 {
+            continue;
+        }
+        let (_, size, flags, timestamp_us) = read_buffer_info_common(&mut *env, &buffer_info)?;
+        let frame = match size {
+            0 => None,
+            _ => Some(read_frame(&codec.get_output_image(index)?, timestamp_us)),
+        };
+        codec.release_output_buffer(index, false)?;
+
+        if let Some(frame) = frame {
+            let frame = frame?;
+            let ready = match target_us {
+                Some(target) if timestamp_us <= target => {
+                    candidate = Some(frame);
+                    Vec::new()
+                }
+                Some(_) => {
+                    target_us = None;
+                    candidate.take().into_iter().chain([frame]).collect()
+                }
+                None => vec![frame],
+            };
+            for frame in ready {
+                if tx.blocking_send(Ok(frame)).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+
+        if flags & BUFFER_FLAG_END_OF_STREAM != 0 {
+            if let Some(frame) = candidate {
+                let _ = tx.blocking_send(Ok(frame));
+            }
+            return Ok(());
+        }
+    }
+}
+
+/// Queues the next sample of the track if the codec has an input buffer free, returning true once
+/// the end of the stream is queued.
+fn queue_sample(extractor: &SafeGlobalRef, codec: &MediaCodec) -> Result<bool> {
+    let index = codec.dequeue_input_buffer(Duration::ZERO)?;
+    if index < 0 {
+        return Ok(false);
+    }
+    let buffer = codec.get_input_buffer(index)?;
+
+    let (size, timestamp_us) = {
+        let env = &mut attach_current_thread()?;
+        let size = call_int_method(
+            env,
+            extractor.as_obj(),
+            "readSampleData",
+            "(Ljava/nio/ByteBuffer;I)I",
+            &[JValue::Object(buffer.as_obj()), JValue::Int(0)],
+        )?;
+        let timestamp_us = env
+            .call_method(extractor.as_obj(), "getSampleTime", "()J", &[])?
+            .j()?;
+        env.call_method(extractor.as_obj(), "advance", "()Z", &[])?;
+        check_jni_exception(env)?;
+        (size, timestamp_us)
+    };
+
+    // -1 past the last sample
+    if size < 0 {
+        codec.queue_input_buffer(index, 0, 0, 0, BUFFER_FLAG_END_OF_STREAM)?;
+        return Ok(true);
+    }
+    codec.queue_input_buffer(index, 0, size as usize, timestamp_us, 0)?;
+    Ok(false)
+}
+
+/// Converts the visible region of a YUV 420 image to BGRA, with the BT.601 limited range matrix
+/// the encoders use.
+fn read_frame(image: &MediaImage, timestamp_us: jlong) -> Result<DecodedVideoFrame> {
+    let planes = image.get_planes()?;
+    let [y_plane, u_plane, v_plane] = planes.as_slice() else {
+        return Err(AndroidError::UnsupportedPlaneCount(planes.len()));
+    };
+    let (left, top, width, height) = image.crop_rect()?;
+
+    let sample = |plane: &ImagePlane, x: u32, y: u32| unsafe {
+        *plane
+            .ptr
+            .add(y as usize * plane.row_stride as usize + x as usize * plane.pixel_stride as usize)
+            as i32
+    };
+
+    let clamp = |value: i32| ((value + 128) >> 8).clamp(0, 255) as u8;
+
+    let mut data = vec![0u8; width as usize * height as usize * 4];
+    for (y, row) in (top..).zip(data.chunks_exact_mut(width as usize * 4)) {
+        for (x, pixel) in (left..).zip(row.chunks_exact_mut(4)) {
+            let c = 298 * (sample(y_plane, x, y) - 16);
+            let d = sample(u_plane, x / 2, y / 2) - 128;
+            let e = sample(v_plane, x / 2, y / 2) - 128;
+            pixel.copy_from_slice(&[
+                clamp(c + 516 * d),
+                clamp(c - 100 * d - 208 * e),
+                clamp(c + 409 * e),
+                255,
+            ]);
+        }
+    }
+
+    Ok(DecodedVideoFrame {
+        frame: VideoFrameBgra32::packed(SharedBuffer::new_unmanaged(data), width, height),
+        timestamp: Timebase::MICROSECONDS.to_seconds(timestamp_us),
+    })
+}
+
+impl Decoder for MediaCodecDecoder {
+    async fn pull(&mut self) -> unienc_common::Result<Option<DecodedVideoFrame>> {
+        match self.rx.recv().await {
+            Some(frame) => Ok(Some(frame?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn seek(
+        &mut self,
+        timestamp: f64,
+        mode: SeekMode,
+    ) -> unienc_common::Result<Option<DecodedVideoFrame>> {
+        *self = Self::open(&self.input_path, Some((timestamp, mode)))?;
+        self.pull().await
+    }
+}
@@ -42,6 +42,12 @@ pub enum AndroidError {
     #[error("Unsupported number of planes: {0}")]
     UnsupportedPlaneCount(usize),
 
+    #[error("Encoder supports none of the YUV 420 color formats for buffer input: {0:?}")]
+    UnsupportedColorFormats(Vec<i32>),
+
+    #[error("Input buffer of {size} bytes is too small for a frame of {required} bytes")]
+    InputBufferTooSmall { required: usize, size: usize },
+
     #[error("Failed to create byte buffer")]
     ByteBufferCreationFailed,
 
@@ -55,6 +61,9 @@ pub enum AndroidError {
     #[error("AHardwareBuffer_fromHardwareBuffer returned null")]
     AHardwareBufferNull,
 
+    #[error("AHardwareBuffer is not available on this device (requires API 26)")]
+    HardwareBufferUnavailable,
+
     // Vulkan related errors
     #[error("Null Vulkan texture pointer")]
     NullVulkanTexture,
@@ -95,6 +104,12 @@ pub enum AndroidError {
     #[error("No available descriptor sets")]
     NoAvailableDescriptorSets,
 
+    #[error("No available fences")]
+    NoAvailableFences,
+
+    #[error("Blit waiter thread is not running")]
+    BlitWaiterUnavailable,
+
     #[error("No suitable memory type found")]
     NoSuitableMemoryType,
 
@@ -108,9 +123,15 @@ pub enum AndroidError {
     #[error("Muxer already started")]
     MuxerAlreadyStarted,
 
+    #[error("Encoder {0} is denied and no other encoder is allowed")]
+    NoAllowedEncoder(String),
+
     #[error("Track does not have metadata")]
     MissingTrackMetadata,
 
+    #[error("Track format did not arrive in time; {0} samples were queued without it")]
+    TrackFormatTimeout(usize),
+
     #[error("Failed to send {0} signal")]
     ChannelSendFailed(&'static str),
 
@@ -176,8 +197,11 @@ impl CategorizedError for AndroidError {
             AndroidError::DequeueImageNull => ErrorCategory::ResourceAllocation,
             AndroidError::HardwareBufferNull => ErrorCategory::ResourceAllocation,
             AndroidError::AHardwareBufferNull => ErrorCategory::ResourceAllocation,
+            AndroidError::HardwareBufferUnavailable => ErrorCategory::Platform,
             AndroidError::NullVulkanTexture => ErrorCategory::ResourceAllocation,
             AndroidError::NoAvailableDescriptorSets => ErrorCategory::ResourceAllocation,
+            AndroidError::NoAvailableFences => ErrorCategory::ResourceAllocation,
+            AndroidError::BlitWaiterUnavailable => ErrorCategory::ResourceAllocation,
             AndroidError::NoSuitableMemoryType => ErrorCategory::ResourceAllocation,
             AndroidError::HardwareBufferMemoryAllocationFailed(_) => {
                 ErrorCategory::ResourceAllocation
@@ -199,7 +223,9 @@ impl CategorizedError for AndroidError {
 
             // Muxing errors
             AndroidError::MuxerAlreadyStarted => ErrorCategory::Muxing,
+            AndroidError::NoAllowedEncoder(_) => ErrorCategory::Configuration,
             AndroidError::MissingTrackMetadata => ErrorCategory::Muxing,
+            AndroidError::TrackFormatTimeout(_) => ErrorCategory::Muxing,
             AndroidError::InvalidOutputPath => ErrorCategory::Muxing,
 
             // Communication errors
@@ -210,6 +236,8 @@ impl CategorizedError for AndroidError {
 
             // Invalid input errors
             AndroidError::UnsupportedPlaneCount(_) => ErrorCategory::InvalidInput,
+            AndroidError::UnsupportedColorFormats(_) => ErrorCategory::Configuration,
+            AndroidError::InputBufferTooSmall { .. } => ErrorCategory::ResourceAllocation,
             AndroidError::UnsupportedGraphicsFormat(_) => ErrorCategory::InvalidInput,
             AndroidError::Utf8(_) => ErrorCategory::InvalidInput,
 
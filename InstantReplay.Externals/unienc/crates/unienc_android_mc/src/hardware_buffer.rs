@@ -0,0 +1,91 @@
+//! AHardwareBuffer functions (API 26+) resolved at runtime. The library targets an older platform,
+//! and Android binds every imported symbol at load time, so linking them directly would keep it
+//! from loading at all on older devices.
+
+use std::ffi::{CStr, c_void};
+use std::sync::OnceLock;
+
+use ndk_sys::{AHardwareBuffer, AHardwareBuffer_Desc};
+
+use crate::error::{AndroidError, Result};
+
+type ReferenceFn = unsafe extern "C" fn(*mut AHardwareBuffer);
+type DescribeFn = unsafe extern "C" fn(*const AHardwareBuffer, *mut AHardwareBuffer_Desc);
+type FromHardwareBufferFn =
+    unsafe extern "C" fn(*mut jni::sys::JNIEnv, jni::sys::jobject) -> *mut AHardwareBuffer;
+
+struct Functions {
+    acquire: ReferenceFn,
+    release: ReferenceFn,
+    describe: DescribeFn,
+    from_hardware_buffer: FromHardwareBufferFn,
+}
+
+static FUNCTIONS: OnceLock<Option<Functions>> = OnceLock::new();
+
+fn functions() -> Result<&'static Functions> {
+    FUNCTIONS
+        .get_or_init(|| unsafe { load() })
+        .as_ref()
+        .ok_or(AndroidError::HardwareBufferUnavailable)
+}
+
+unsafe fn load() -> Option<Functions> {
+    unsafe {
+        let lib = libc::dlopen(c"libandroid.so".as_ptr(), libc::RTLD_NOW);
+        if lib.is_null() {
+            return None;
+        }
+        let symbol = |name: &CStr| -> Option<*mut c_void> {
+            let symbol = libc::dlsym(lib, name.as_ptr());
+            (!symbol.is_null()).then_some(symbol)
+        };
+        Some(Functions {
+            acquire: std::mem::transmute::<*mut c_void, ReferenceFn>(symbol(
+                c"AHardwareBuffer_acquire",
+            )?),
+            release: std::mem::transmute::<*mut c_void, ReferenceFn>(symbol(
+                c"AHardwareBuffer_release",
+            )?),
+            describe: std::mem::transmute::<*mut c_void, DescribeFn>(symbol(
+                c"AHardwareBuffer_describe",
+            )?),
+            from_hardware_buffer: std::mem::transmute::<*mut c_void, FromHardwareBufferFn>(symbol(
+                c"AHardwareBuffer_fromHardwareBuffer",
+            )?),
+        })
+    }
+}
+
+/// # Safety
+/// `buffer` must be a valid AHardwareBuffer.
+pub(crate) unsafe fn acquire(buffer: *mut AHardwareBuffer) -> Result<()> {
+    unsafe { (functions()?.acquire)(buffer) };
+    Ok(())
+}
+
+/// # Safety
+/// `buffer` must be a valid AHardwareBuffer this code holds a reference to.
+pub(crate) unsafe fn release(buffer: *mut AHardwareBuffer) -> Result<()> {
+    unsafe { (functions()?.release)(buffer) };
+    Ok(())
+}
+
+/// # Safety
+/// `buffer` must be a valid AHardwareBuffer.
+pub(crate) unsafe fn describe(buffer: *const AHardwareBuffer) -> Result<AHardwareBuffer_Desc> {
+    let mut desc: AHardwareBuffer_Desc = unsafe { std::mem::zeroed() };
+    unsafe { (functions()?.describe)(buffer, &mut desc) };
+    Ok(desc)
+}
+
+/// Acquires the native buffer of an `android.hardware.HardwareBuffer`.
+///
+/// # Safety
+/// `env` must be attached to the current thread and `object` a HardwareBuffer.
+pub(crate) unsafe fn from_hardware_buffer(
+    env: *mut jni::sys::JNIEnv,
+    object: jni::sys::jobject,
+) -> Result<*mut AHardwareBuffer> {
+    Ok(unsafe { (functions()?.from_hardware_buffer)(env, object) })
+}
@@ -1,11 +1,17 @@
-use std::sync::Arc;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, OnceLock};
 
 use crate::error::{AndroidError, Result};
 use jni::{
-    AttachGuard, JNIEnv, JavaVM,
-    objects::{GlobalRef, JObject, JString},
+    JNIEnv, JavaVM,
+    objects::{GlobalRef, JClass, JFieldID, JMethodID, JObject, JString, JValue, JValueOwned},
+    signature::{Primitive, ReturnType},
+    sys::{jint, jlong, jvalue},
 };
 
+/// Local references a scope is expected to create; the frame grows beyond it if needed.
+const LOCAL_FRAME_CAPACITY: jint = 16;
+
 /// Get the global JavaVM instance
 pub fn get_java_vm() -> Result<&'static JavaVM> {
     crate::JAVA_VM
@@ -14,15 +20,268 @@ pub fn get_java_vm() -> Result<&'static JavaVM> {
 }
 
 /// Attach current thread to JVM and get JNIEnv
-pub fn attach_current_thread() -> Result<AttachGuard<'static>> {
+///
+/// The thread stays attached until it exits rather than being attached and detached around every
+/// operation. Local references created through the returned scope are freed when it is dropped,
+/// as threads that never return to Java would otherwise fill up their local reference table.
+pub fn attach_current_thread() -> Result<JniScope> {
     let vm = get_java_vm()?;
-    vm.attach_current_thread()
-        .map_err(|e| AndroidError::JvmAttachFailed(format!("{:?}", e)))
+    let env = vm
+        .attach_current_thread_permanently()
+        .map_err(|e| AndroidError::JvmAttachFailed(format!("{:?}", e)))?;
+    push_frame(&env)?;
+    Ok(JniScope { env })
+}
+
+/// Runs `f` in a frame of local references of its own, freed when it returns, so that a loop
+/// creating references on every iteration does not fill up the table of the enclosing scope. `f`
+/// must not return local references.
+pub fn local_frame<'a, T>(
+    env: &mut JNIEnv<'a>,
+    f: impl FnOnce(&mut JNIEnv<'a>) -> Result<T>,
+) -> Result<T> {
+    push_frame(env)?;
+    let result = f(env);
+    pop_frame(env);
+    result
+}
+
+fn push_frame(env: &JNIEnv) -> Result<()> {
+    env.push_local_frame(LOCAL_FRAME_CAPACITY)?;
+    #[cfg(debug_assertions)]
+    tracking::frame_pushed();
+    Ok(())
+}
+
+fn pop_frame(env: &JNIEnv) {
+    #[cfg(debug_assertions)]
+    tracking::frame_popped();
+    let _ = unsafe { env.pop_local_frame(&JObject::null()) };
+}
+
+/// Reference bookkeeping checked in debug builds, so that a leak in a long session fails an
+/// assertion where it starts instead of aborting the process once a JNI table overflows.
+#[cfg(debug_assertions)]
+mod tracking {
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Frames nested deeper than this are taken as frames that are never popped.
+    const MAX_FRAME_DEPTH: usize = 32;
+    /// Well below the 51200 global references ART allows.
+    const MAX_GLOBAL_REFS: usize = 4096;
+
+    thread_local! {
+        static FRAME_DEPTH: Cell<usize> = const { Cell::new(0) };
+    }
+    static GLOBAL_REFS: AtomicUsize = AtomicUsize::new(0);
+
+    pub fn frame_pushed() {
+        let depth = FRAME_DEPTH.get() + 1;
+        FRAME_DEPTH.set(depth);
+        assert!(
+            depth <= MAX_FRAME_DEPTH,
+            "{depth} local frames nested on this thread, some are probably never popped"
+        );
+    }
+
+    pub fn frame_popped() {
+        let depth = FRAME_DEPTH.get();
+        assert!(depth > 0, "local frame popped without being pushed");
+        FRAME_DEPTH.set(depth - 1);
+    }
+
+    /// Counts a global reference as alive until dropped.
+    pub struct TrackedGlobalRef(());
+
+    impl TrackedGlobalRef {
+        pub fn new() -> Self {
+            let count = GLOBAL_REFS.fetch_add(1, Ordering::Relaxed) + 1;
+            assert!(
+                count <= MAX_GLOBAL_REFS,
+                "{count} global references alive, some are probably leaked"
+            );
+            Self(())
+        }
+    }
+
+    impl Drop for TrackedGlobalRef {
+        fn drop(&mut self) {
+            GLOBAL_REFS.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// JNIEnv of an attached thread with its own frame of local references
+pub struct JniScope {
+    env: JNIEnv<'static>,
+}
+
+impl Deref for JniScope {
+    type Target = JNIEnv<'static>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.env
+    }
+}
+
+impl DerefMut for JniScope {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.env
+    }
+}
+
+impl Drop for JniScope {
+    fn drop(&mut self) {
+        // only global references outlive the scope
+        pop_frame(&self.env);
+    }
+}
+
+/// Java class looked up once and kept with a global reference
+pub struct JavaClass {
+    name: &'static str,
+    class: OnceLock<GlobalRef>,
+}
+
+impl JavaClass {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            class: OnceLock::new(),
+        }
+    }
+
+    pub fn get(&self, env: &mut JNIEnv) -> Result<&JClass<'static>> {
+        if let Some(class) = self.class.get() {
+            return Ok(class.as_obj().into());
+        }
+        let class = env.find_class(self.name)?;
+        let class = env
+            .new_global_ref(class)
+            .map_err(|_| AndroidError::JniGlobalRefFailed)?;
+        Ok(self.class.get_or_init(|| class).as_obj().into())
+    }
+}
+
+/// Java instance method whose ID is looked up on first use instead of by name on every call. IDs
+/// stay valid for the process since platform classes are never unloaded.
+pub struct JavaMethod {
+    class: &'static str,
+    name: &'static str,
+    sig: &'static str,
+    id: OnceLock<JMethodID>,
+}
+
+impl JavaMethod {
+    pub const fn new(class: &'static str, name: &'static str, sig: &'static str) -> Self {
+        Self {
+            class,
+            name,
+            sig,
+            id: OnceLock::new(),
+        }
+    }
+
+    fn call<'a>(
+        &self,
+        env: &mut JNIEnv<'a>,
+        obj: &JObject,
+        ret: ReturnType,
+        args: &[JValue],
+    ) -> Result<JValueOwned<'a>> {
+        let id = match self.id.get() {
+            Some(id) => *id,
+            None => {
+                let id = env.get_method_id(self.class, self.name, self.sig)?;
+                *self.id.get_or_init(|| id)
+            }
+        };
+        let args: Vec<jvalue> = args.iter().map(JValue::as_jni).collect();
+        // the signature is checked against the class when the ID is looked up
+        let result = unsafe { env.call_method_unchecked(obj, id, ret, &args) };
+        check_jni_exception(env)?;
+        result.map_err(|_| AndroidError::JniMethodCallFailed(self.name.to_string()))
+    }
+
+    pub fn call_void(&self, env: &mut JNIEnv, obj: &JObject, args: &[JValue]) -> Result<()> {
+        self.call(env, obj, ReturnType::Primitive(Primitive::Void), args)?;
+        Ok(())
+    }
+
+    pub fn call_int(&self, env: &mut JNIEnv, obj: &JObject, args: &[JValue]) -> Result<jint> {
+        self.call(env, obj, ReturnType::Primitive(Primitive::Int), args)?
+            .i()
+            .map_err(|_| AndroidError::JniUnexpectedReturnValue { expected: "int" })
+    }
+
+    pub fn call_object<'a>(
+        &self,
+        env: &mut JNIEnv<'a>,
+        obj: &JObject,
+        args: &[JValue],
+    ) -> Result<JObject<'a>> {
+        self.call(env, obj, ReturnType::Object, args)?
+            .l()
+            .map_err(|_| AndroidError::JniUnexpectedReturnValue { expected: "object" })
+    }
+}
+
+/// Java instance field whose ID is looked up on first use, like [`JavaMethod`]
+pub struct JavaField {
+    class: &'static str,
+    name: &'static str,
+    sig: &'static str,
+    id: OnceLock<JFieldID>,
+}
+
+impl JavaField {
+    pub const fn new(class: &'static str, name: &'static str, sig: &'static str) -> Self {
+        Self {
+            class,
+            name,
+            sig,
+            id: OnceLock::new(),
+        }
+    }
+
+    fn get<'a>(
+        &self,
+        env: &mut JNIEnv<'a>,
+        obj: &JObject,
+        ty: ReturnType,
+    ) -> Result<JValueOwned<'a>> {
+        let id = match self.id.get() {
+            Some(id) => *id,
+            None => {
+                let id = env.get_field_id(self.class, self.name, self.sig)?;
+                *self.id.get_or_init(|| id)
+            }
+        };
+        let result = env.get_field_unchecked(obj, id, ty);
+        check_jni_exception(env)?;
+        result.map_err(|_| AndroidError::JniFieldGetFailed(self.name.to_string()))
+    }
+
+    pub fn get_int(&self, env: &mut JNIEnv, obj: &JObject) -> Result<jint> {
+        self.get(env, obj, ReturnType::Primitive(Primitive::Int))?
+            .i()
+            .map_err(|_| AndroidError::JniUnexpectedReturnValue { expected: "int" })
+    }
+
+    pub fn get_long(&self, env: &mut JNIEnv, obj: &JObject) -> Result<jlong> {
+        self.get(env, obj, ReturnType::Primitive(Primitive::Long))?
+            .j()
+            .map_err(|_| AndroidError::JniUnexpectedReturnValue { expected: "long" })
+    }
 }
 
 /// Thread-safe wrapper for Java GlobalRef
 pub struct SafeGlobalRef {
     inner: Arc<GlobalRef>,
+    // dropped with the last clone, when the reference is deleted
+    #[cfg(debug_assertions)]
+    _tracked: Arc<tracking::TrackedGlobalRef>,
 }
 
 impl SafeGlobalRef {
@@ -32,6 +291,8 @@ impl SafeGlobalRef {
             .map_err(|_| AndroidError::JniGlobalRefFailed)?;
         Ok(Self {
             inner: Arc::new(global_ref),
+            #[cfg(debug_assertions)]
+            _tracked: Arc::new(tracking::TrackedGlobalRef::new()),
         })
     }
 
@@ -47,6 +308,8 @@ impl Clone for SafeGlobalRef {
     fn clone(&self) -> Self {
         Self {
             inner: Arc::clone(&self.inner),
+            #[cfg(debug_assertions)]
+            _tracked: Arc::clone(&self._tracked),
         }
     }
 }
@@ -110,34 +373,14 @@ pub fn call_object_method<'a>(
         .map_err(|_| AndroidError::JniUnexpectedReturnValue { expected: "object" })
 }
 
-/// Helper to get int field
-pub fn get_int_field(env: &mut JNIEnv, obj: &JObject, name: &str) -> Result<jni::sys::jint> {
-    let result = env
-        .get_field(obj, name, "I")
-        .map_err(|_| AndroidError::JniFieldGetFailed(name.to_string()))?;
-    check_jni_exception(env)?;
-    result
-        .i()
-        .map_err(|_| AndroidError::JniUnexpectedReturnValue { expected: "int" })
-}
-
-/// Helper to get long field
-pub fn get_long_field(env: &mut JNIEnv, obj: &JObject, name: &str) -> Result<jni::sys::jlong> {
-    let result = env
-        .get_field(obj, name, "J")
-        .map_err(|_| AndroidError::JniFieldGetFailed(name.to_string()))?;
-    check_jni_exception(env)?;
-    result
-        .j()
-        .map_err(|_| AndroidError::JniUnexpectedReturnValue { expected: "long" })
-}
-
 /// Convert Rust string to Java string
 pub fn to_java_string<'a>(env: &JNIEnv<'a>, s: &str) -> Result<JString<'a>> {
     env.new_string(s)
         .map_err(|_| AndroidError::JniStringCreationFailed)
 }
 
+static BUFFER_POSITION: JavaMethod = JavaMethod::new("java/nio/Buffer", "position", "()I");
+
 /// Get direct buffer address, capacity and position from DirectByteBuffer
 pub fn get_direct_buffer_info(
     env: &mut JNIEnv,
@@ -156,7 +399,7 @@ pub fn get_direct_buffer_info(
     let capacity = env.get_direct_buffer_capacity(byte_buffer)?;
 
     // Get current position
-    let position = env.call_method(buffer, "position", "()I", &[])?.i()? as usize;
+    let position = BUFFER_POSITION.call_int(env, buffer, &[])? as usize;
 
     Ok((base_address, capacity, position))
 }
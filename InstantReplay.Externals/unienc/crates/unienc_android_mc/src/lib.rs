@@ -3,30 +3,58 @@ use jni::sys::JNI_VERSION_1_6;
 use std::ffi::{c_int, c_void};
 use std::path::Path;
 use std::sync::OnceLock;
-use unienc_common::{EncodingSystem, TryFromUnityNativeTexturePointer};
+use unienc_common::{
+    DeviceClass, DiagnosticCheck, DownmixedAudioEncoder, DownmixedAudioOptions,
+    EncoderCapabilities, EncodingSystem, PaddedMuxer, PaddedVideoEncoder, PaddedVideoOptions,
+    StillImageFormat, TrackedRuntime, TryFromUnityNativeTexturePointer, VideoEncoderOptions,
+};
 
 pub mod audio;
+pub mod codec_selection;
+pub mod codec_stats;
 pub mod common;
 pub mod config;
+pub mod decode;
 pub mod error;
+mod hardware_buffer;
 mod java;
+pub mod media_projection;
 pub mod mux;
+pub mod passthrough;
+#[cfg(feature = "blit")]
+pub mod still_image;
 pub mod video;
+#[cfg(feature = "blit")]
 mod vulkan;
 
 pub use error::{AndroidError, Result};
+#[cfg(feature = "blit")]
+pub use vulkan::{VulkanPoolStats, set_vulkan_device, set_vulkan_pool_limits, vulkan_pool_stats};
 
 use audio::MediaCodecAudioEncoder;
+use common::MediaCodec;
+use config::MIME_TYPE_AUDIO_AAC;
+use decode::MediaCodecDecoder;
 use mux::MediaMuxer;
+use passthrough::{MediaCodecAacPacketizer, MediaCodecH264Packetizer};
+#[cfg(feature = "blit")]
+use still_image::BitmapStillImageCapture;
 use unienc_common::unity::UnityPlugin;
 use video::MediaCodecVideoEncoder;
 
+/// Still images are captured through the Vulkan blit.
+#[cfg(feature = "blit")]
+type PlatformStillImageCapture = BitmapStillImageCapture;
+#[cfg(not(feature = "blit"))]
+type PlatformStillImageCapture =
+    unienc_common::still_image::UnsupportedStillImageCapture<VulkanTexture>;
+
 static JAVA_VM: OnceLock<jni::JavaVM> = OnceLock::new();
 
 pub unsafe fn set_java_vm(vm: *mut jni::sys::JavaVM, _reserved: *mut c_void) -> c_int {
     unsafe {
         JAVA_VM.set(JavaVM::from_raw(vm).unwrap()).unwrap();
-        println!("JNI_OnLoad: {:?}", vm);
+        unienc_common::log!("JNI_OnLoad: {:?}", vm);
         JNI_VERSION_1_6
     }
 }
@@ -36,9 +64,9 @@ pub struct MediaCodecEncodingSystem<
     A: unienc_common::AudioEncoderOptions,
     R: unienc_common::Runtime + 'static,
 > {
-    video_options: V,
-    audio_options: A,
-    runtime: R,
+    video_options: PaddedVideoOptions<V>,
+    audio_options: DownmixedAudioOptions<A>,
+    runtime: TrackedRuntime<R>,
 }
 
 impl<
@@ -49,33 +77,86 @@ impl<
 {
     type VideoEncoderOptionsType = V;
     type AudioEncoderOptionsType = A;
-    type VideoEncoderType = MediaCodecVideoEncoder<R>;
-    type AudioEncoderType = MediaCodecAudioEncoder;
-    type MuxerType = MediaMuxer;
+    type VideoEncoderType = PaddedVideoEncoder<MediaCodecVideoEncoder<TrackedRuntime<R>>>;
+    type AudioEncoderType = DownmixedAudioEncoder<MediaCodecAudioEncoder>;
+    type MuxerType = PaddedMuxer<MediaMuxer>;
     type BlitSourceType = VulkanTexture;
     type RuntimeType = R;
+    type H264PacketizerType = MediaCodecH264Packetizer;
+    type AacPacketizerType = MediaCodecAacPacketizer;
+    type DecoderType = MediaCodecDecoder;
+    type StillImageCaptureType = PlatformStillImageCapture;
 
     fn new(video_options: &V, audio_options: &A, runtime: R) -> Self {
         Self {
-            video_options: *video_options,
-            audio_options: *audio_options,
-            runtime,
+            video_options: PaddedVideoOptions::new(*video_options),
+            audio_options: DownmixedAudioOptions::stereo(*audio_options),
+            runtime: TrackedRuntime::new(runtime),
         }
     }
 
     fn new_video_encoder(&self) -> unienc_common::Result<Self::VideoEncoderType> {
-        MediaCodecVideoEncoder::<R>::new(&self.video_options, self.runtime.clone())
+        let codec = self.video_options.codec();
+        if !self.is_codec_supported(codec) {
+            return Err(unienc_common::CommonError::CodecNotSupported(codec));
+        }
+        if self.video_options.preserve_alpha() {
+            return Err(unienc_common::CommonError::AlphaNotSupported);
+        }
+        MediaCodecVideoEncoder::new(&self.video_options, self.runtime.clone())
             .map_err(Into::into)
+            .map(PaddedVideoEncoder::new)
     }
 
     fn new_audio_encoder(&self) -> unienc_common::Result<Self::AudioEncoderType> {
-        MediaCodecAudioEncoder::new(&self.audio_options).map_err(Into::into)
+        MediaCodecAudioEncoder::new(&self.audio_options)
+            .map_err(Into::into)
+            .map(|encoder| DownmixedAudioEncoder::new(encoder, &self.audio_options))
     }
 
     fn new_muxer(&self, output_path: &Path) -> unienc_common::Result<Self::MuxerType> {
-        MediaMuxer::new(output_path, &self.video_options, &self.audio_options).map_err(Into::into)
+        MediaMuxer::new(output_path, &self.video_options, &self.audio_options)
+            .map_err(Into::into)
+            .map(|muxer| PaddedMuxer::new(muxer, output_path, &self.video_options))
+    }
+
+    fn new_h264_packetizer(&self) -> unienc_common::Result<Self::H264PacketizerType> {
+        Ok(MediaCodecH264Packetizer::new(&self.video_options))
+    }
+
+    fn new_aac_packetizer(&self) -> unienc_common::Result<Self::AacPacketizerType> {
+        MediaCodecAacPacketizer::new(&self.audio_options)
+    }
+
+    fn new_decoder(&self, input_path: &Path) -> unienc_common::Result<Self::DecoderType> {
+        MediaCodecDecoder::new(input_path).map_err(Into::into)
+    }
+
+    fn new_still_image_capture(
+        &self,
+        format: StillImageFormat,
+    ) -> unienc_common::Result<Self::StillImageCaptureType> {
+        #[cfg(feature = "blit")]
+        return Ok(BitmapStillImageCapture::new(
+            self.video_options.inner(),
+            format,
+        ));
+        #[cfg(not(feature = "blit"))]
+        {
+            let _ = format;
+            Err(unienc_common::CommonError::BlitNotSupported)
+        }
+    }
+
+    fn shutdown(self) -> impl Future<Output = unienc_common::Result<()>> + Send {
+        let idle = self.runtime.idle();
+        async move {
+            idle.await;
+            Ok(())
+        }
     }
 
+    #[cfg(feature = "blit")]
     fn is_blit_supported(&self) -> bool {
         // HardwareBuffer mode requires API 29+ (ImageWriter.newInstance with format)
         // API 28 and below must use Bgra32 mode because ImageWriter.newInstance
@@ -83,6 +164,71 @@ impl<
         let api_level = common::get_android_api_level().unwrap_or(0);
         api_level >= 29 && vulkan::is_initialized()
     }
+
+    #[cfg(feature = "blit")]
+    fn new_test_pattern(&self) -> unienc_common::Result<unienc_common::TestPattern> {
+        // there is no default device to fall back on, unlike Metal
+        if !self.is_blit_supported() {
+            return Err(unienc_common::CommonError::BlitNotSupported);
+        }
+        let options = self.video_options.inner();
+        let (width, height) =
+            unienc_common::test_pattern::test_pattern_size(options.width(), options.height());
+        let texture = vulkan::test_pattern::VulkanTestPattern::new(width, height)?;
+        Ok(unienc_common::TestPattern::new(texture, width, height))
+    }
+
+    fn self_test() -> Vec<DiagnosticCheck> {
+        let java_vm = DiagnosticCheck::from_result(
+            "java_vm",
+            java::get_java_vm(),
+            "JNI_OnLoad was called",
+            "The library must be loaded through System.loadLibrary",
+        );
+        // every other check goes through JNI
+        if !java_vm.passed {
+            return vec![java_vm];
+        }
+        #[cfg(feature = "blit")]
+        let graphics = Some(if vulkan::is_initialized() {
+            DiagnosticCheck::passed("vulkan", "Unity graphics device is Vulkan")
+        } else {
+            DiagnosticCheck::failed(
+                "vulkan",
+                "Unity graphics interface was not received; frames are read back from the CPU",
+            )
+        });
+        #[cfg(not(feature = "blit"))]
+        let graphics = None;
+        [java_vm]
+            .into_iter()
+            .chain(graphics)
+            .chain([
+                DiagnosticCheck::from_result(
+                    "h264_encoder",
+                    codec_selection::create_video_encoder(),
+                    "MediaCodec H.264 encoder available",
+                    "The device has no usable H.264 encoder",
+                ),
+                DiagnosticCheck::from_result(
+                    "aac_encoder",
+                    MediaCodec::create_encoder(MIME_TYPE_AUDIO_AAC),
+                    "MediaCodec AAC encoder available",
+                    "The device has no usable AAC encoder",
+                ),
+            ])
+            .collect()
+    }
+
+    fn encoder_capabilities(width: u32, height: u32) -> EncoderCapabilities {
+        codec_selection::video_encoder_capabilities(width, height).unwrap_or_else(|e| {
+            unienc_common::log!("Failed to query the H.264 encoder: {e}");
+            EncoderCapabilities {
+                device_class: DeviceClass::Mobile,
+                ..Default::default()
+            }
+        })
+    }
 }
 
 impl<
@@ -92,12 +238,16 @@ impl<
 > UnityPlugin for MediaCodecEncodingSystem<V, A, R>
 {
     fn unity_plugin_load(interfaces: &unity_native_plugin::interface::UnityInterfaces) {
+        #[cfg(feature = "blit")]
         vulkan::unity_plugin_load(interfaces);
+        #[cfg(not(feature = "blit"))]
+        let _ = interfaces;
     }
     fn unity_plugin_unload() {}
 }
 
 pub struct VulkanTexture {
+    #[cfg_attr(not(feature = "blit"), allow(dead_code))]
     tex: ash::vk::Image,
 }
 
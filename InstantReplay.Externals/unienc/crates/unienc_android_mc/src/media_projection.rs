@@ -0,0 +1,83 @@
+use std::ffi::c_void;
+
+use jni::objects::{JObject, JValue};
+
+use crate::error::Result;
+use crate::java::*;
+
+// DisplayManager.VIRTUAL_DISPLAY_FLAG_AUTO_MIRROR
+const VIRTUAL_DISPLAY_FLAG_AUTO_MIRROR: i32 = 16;
+
+/// A `android.media.projection.MediaProjection` granted to the app by the user.
+///
+/// On Android 14 and later the host must register a `MediaProjection.Callback` before passing the
+/// projection in, as virtual displays cannot be created otherwise.
+#[derive(Clone)]
+pub struct MediaProjection {
+    projection: SafeGlobalRef,
+}
+
+impl MediaProjection {
+    /// # Safety
+    /// `projection` must be a valid JNI reference to a MediaProjection.
+    pub unsafe fn from_raw(projection: *mut c_void) -> Result<Self> {
+        let env = &attach_current_thread()?;
+        let projection = unsafe { JObject::from_raw(projection as jni::sys::jobject) };
+        Ok(Self {
+            projection: SafeGlobalRef::new(env, projection)?,
+        })
+    }
+}
+
+/// Mirror of the default display rendered into a surface, released on drop.
+pub(crate) struct VirtualDisplay {
+    display: SafeGlobalRef,
+}
+
+impl VirtualDisplay {
+    pub fn new(
+        projection: &MediaProjection,
+        width: u32,
+        height: u32,
+        density_dpi: u32,
+        surface: &SafeGlobalRef,
+    ) -> Result<Self> {
+        let env = &mut attach_current_thread()?;
+        let name = to_java_string(env, "unienc")?;
+        let display = call_object_method(
+            env,
+            projection.projection.as_obj(),
+            "createVirtualDisplay",
+            "(Ljava/lang/String;IIIILandroid/view/Surface;Landroid/hardware/display/VirtualDisplay$Callback;Landroid/os/Handler;)Landroid/hardware/display/VirtualDisplay;",
+            &[
+                JValue::Object(&name),
+                JValue::Int(width as i32),
+                JValue::Int(height as i32),
+                JValue::Int(density_dpi as i32),
+                JValue::Int(VIRTUAL_DISPLAY_FLAG_AUTO_MIRROR),
+                JValue::Object(surface.as_obj()),
+                JValue::Object(&JObject::null()),
+                JValue::Object(&JObject::null()),
+            ],
+        )?;
+        Ok(Self {
+            display: SafeGlobalRef::new(env, display)?,
+        })
+    }
+}
+
+impl Drop for VirtualDisplay {
+    fn drop(&mut self) {
+        if let Ok(env) = &attach_current_thread() {
+            _ = call_void_method(env, self.display.as_obj(), "release", "()V", &[]);
+        }
+    }
+}
+
+/// `System.nanoTime()`, the clock surface frames of a virtual display are stamped with.
+pub(crate) fn nano_time() -> Result<i64> {
+    let env = &mut attach_current_thread()?;
+    let time = env.call_static_method("java/lang/System", "nanoTime", "()J", &[])?;
+    check_jni_exception(env)?;
+    Ok(time.j()?)
+}
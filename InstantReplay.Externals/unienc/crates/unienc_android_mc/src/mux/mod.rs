@@ -1,13 +1,29 @@
 use jni::{JNIEnv, objects::JValue, sys::jint};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use std::{path::Path, sync::Arc};
 use tokio::sync::{RwLock, oneshot};
-use unienc_common::{CompletionHandle, Muxer, MuxerInput};
+use unienc_common::output_path::output_descriptor;
+use unienc_common::{CompletionHandle, Muxer, MuxerInput, Timebase};
 
 use crate::common::*;
 use crate::config::MUXER_OUTPUT_FORMAT_MPEG_4;
 use crate::error::{AndroidError, Result};
 use crate::java::*;
 
+/// Some encoders output samples before their format. Those are held back until the format arrives,
+/// up to this many samples or this long.
+const MAX_EARLY_SAMPLES: usize = 64;
+const EARLY_SAMPLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+static BUFFER_INFO_SET: JavaMethod =
+    JavaMethod::new("android/media/MediaCodec$BufferInfo", "set", "(IIJI)V");
+static WRITE_SAMPLE_DATA: JavaMethod = JavaMethod::new(
+    "android/media/MediaMuxer",
+    "writeSampleData",
+    "(ILjava/nio/ByteBuffer;Landroid/media/MediaCodec$BufferInfo;)V",
+);
+
 pub struct MediaMuxer {
     video_input: MediaMuxerVideoInput,
     audio_input: MediaMuxerAudioInput,
@@ -20,11 +36,19 @@ enum MuxerSharedState {
     Started,                              // both video and audio have started
 }
 
+#[derive(Default)]
+struct Track {
+    index: Option<jint>,
+    // data, presentation time in microseconds and buffer flags
+    early_samples: VecDeque<(Vec<u8>, i64, jint)>,
+    first_early_sample: Option<Instant>,
+}
+
 pub struct MediaMuxerVideoInput {
     muxer: SafeGlobalRef,
     shared_state: Arc<RwLock<MuxerSharedState>>,
     finish_tx: oneshot::Sender<Result<()>>,
-    video_track_index: Option<jint>,
+    video_track: Track,
     original_width: u32,
     original_height: u32,
 }
@@ -33,7 +57,7 @@ pub struct MediaMuxerAudioInput {
     muxer: SafeGlobalRef,
     shared_state: Arc<RwLock<MuxerSharedState>>,
     finish_tx: oneshot::Sender<Result<()>>,
-    audio_track_index: Option<jint>,
+    audio_track: Track,
 }
 
 pub struct MediaMuxerCompletionHandle {
@@ -80,7 +104,7 @@ impl MediaMuxer {
                 muxer: muxer.clone(),
                 shared_state: shared_state.clone(),
                 finish_tx: video_finish_tx,
-                video_track_index: None,
+                video_track: Track::default(),
                 original_width: _video_options.width(),
                 original_height: _video_options.height(),
             },
@@ -88,7 +112,7 @@ impl MediaMuxer {
                 muxer: muxer.clone(),
                 shared_state: shared_state.clone(),
                 finish_tx: audio_finish_tx,
-                audio_track_index: None,
+                audio_track: Track::default(),
             },
             completion_handle: MediaMuxerCompletionHandle {
                 video_finish_rx,
@@ -104,16 +128,16 @@ async fn push(
     data: CommonEncodedData,
     shared_state: Arc<RwLock<MuxerSharedState>>,
     muxer: &SafeGlobalRef,
-    track_index: &mut Option<jint>,
+    track: &mut Track,
     original_width: Option<u32>,
     original_height: Option<u32>,
 ) -> Result<()> {
-    let timestamp_us = (data.timestamp * 1_000_000.0) as i64;
+    let timestamp_us = Timebase::MICROSECONDS.to_units(data.timestamp);
 
     match data.content {
         CommonEncodedDataContent::FormatInfo(mut map) => {
-            if track_index.is_some() {
-                println!("track already has metadata");
+            if track.index.is_some() {
+                unienc_common::log!("track already has metadata");
                 return Ok(());
             }
 
@@ -135,7 +159,7 @@ async fn push(
                 let mut env = attach_current_thread()?;
                 let format = crate::common::map_to_format(&mut env, &map)?;
                 let format = SafeGlobalRef::new(&env, format)?;
-                *track_index = Some(add_track(&mut env, muxer, &format)?);
+                track.index = Some(add_track(&mut env, muxer, &format)?);
             }
             match shared_state {
                 MuxerSharedState::None => {
@@ -159,10 +183,34 @@ async fn push(
                     return Err(AndroidError::MuxerAlreadyStarted);
                 }
             };
+
+            if !track.early_samples.is_empty() {
+                unienc_common::log!(
+                    "writing {} samples that arrived before the track format",
+                    track.early_samples.len()
+                );
+                let env = &mut attach_current_thread()?;
+                let track_index = track.index.ok_or(AndroidError::MissingTrackMetadata)?;
+                while let Some((data, timestamp_us, flags)) = track.early_samples.pop_front() {
+                    local_frame(env, |env| {
+                        write_sample_data(env, muxer, track_index, &data, timestamp_us, flags)
+                    })?;
+                }
+            }
+            track.first_early_sample = None;
         }
         CommonEncodedDataContent::Buffer { data, buffer_flag } => {
-            let Some(track_index) = track_index else {
-                return Err(AndroidError::MissingTrackMetadata);
+            let Some(track_index) = &track.index else {
+                let first = *track.first_early_sample.get_or_insert_with(Instant::now);
+                if track.early_samples.len() >= MAX_EARLY_SAMPLES
+                    || first.elapsed() > EARLY_SAMPLE_TIMEOUT
+                {
+                    return Err(AndroidError::TrackFormatTimeout(track.early_samples.len()));
+                }
+                track
+                    .early_samples
+                    .push_back((data, timestamp_us, buffer_flag));
+                return Ok(());
             };
             let env = &mut attach_current_thread()?;
             let flags = buffer_flag;
@@ -182,7 +230,7 @@ impl MuxerInput for MediaMuxerVideoInput {
             data,
             self.shared_state.clone(),
             &self.muxer,
-            &mut self.video_track_index,
+            &mut self.video_track,
             Some(self.original_width),
             Some(self.original_height),
         )
@@ -206,7 +254,7 @@ impl MuxerInput for MediaMuxerAudioInput {
             data,
             self.shared_state.clone(),
             &self.muxer,
-            &mut self.audio_track_index,
+            &mut self.audio_track,
             None, // No size override for audio
             None,
         )
@@ -231,7 +279,7 @@ impl CompletionHandle for MediaMuxerCompletionHandle {
 }
 
 async fn finish_completion_handle_impl(handle: MediaMuxerCompletionHandle) -> Result<()> {
-    println!("waiting for all tracks to finish");
+    unienc_common::log!("waiting for all tracks to finish");
 
     handle.video_finish_rx.await??;
     handle.audio_finish_rx.await??;
@@ -252,6 +300,41 @@ async fn finish_completion_handle_impl(handle: MediaMuxerCompletionHandle) -> Re
 fn create_media_muxer(env: &mut JNIEnv, output_path: &Path) -> Result<SafeGlobalRef> {
     let muxer_class = env.find_class("android/media/MediaMuxer")?;
 
+    // a descriptor the host opened, such as for a scoped storage document
+    if let Some(fd) = output_descriptor(output_path) {
+        let parcel = env
+            .call_static_method(
+                "android/os/ParcelFileDescriptor",
+                "fromFd",
+                "(I)Landroid/os/ParcelFileDescriptor;",
+                &[JValue::Int(fd)],
+            )?
+            .l()?;
+        check_jni_exception(env)?;
+        let descriptor = call_object_method(
+            env,
+            &parcel,
+            "getFileDescriptor",
+            "()Ljava/io/FileDescriptor;",
+            &[],
+        )?;
+        let muxer = env.new_object(
+            muxer_class,
+            "(Ljava/io/FileDescriptor;I)V",
+            &[
+                JValue::Object(&descriptor),
+                JValue::Int(MUXER_OUTPUT_FORMAT_MPEG_4),
+            ],
+        );
+        let created = check_jni_exception(env);
+        // the muxer keeps a duplicate, so the one made by fromFd is closed either way
+        let closed = call_void_method(env, &parcel, "close", "()V", &[]);
+        let muxer = muxer?;
+        created?;
+        closed?;
+        return SafeGlobalRef::new(env, muxer);
+    }
+
     let path_str = output_path
         .to_str()
         .ok_or(AndroidError::InvalidOutputPath)?;
@@ -303,26 +386,25 @@ fn write_sample_data(
     let byte_buffer = unsafe { env.new_direct_byte_buffer(data.as_ptr() as *mut u8, data.len()) }?;
 
     // Create MediaCodec.BufferInfo
-    let buffer_info_class = env.find_class("android/media/MediaCodec$BufferInfo")?;
+    let buffer_info_class = BUFFER_INFO_CLASS.get(env)?;
     let buffer_info = env.new_object(buffer_info_class, "()V", &[])?;
 
     // Set buffer info fields
-    env.set_field(&buffer_info, "offset", "I", JValue::Int(0 as jint))?;
-    env.set_field(&buffer_info, "size", "I", JValue::Int(data.len() as jint))?;
-    env.set_field(
+    BUFFER_INFO_SET.call_void(
+        env,
         &buffer_info,
-        "presentationTimeUs",
-        "J",
-        JValue::Long(timestamp),
+        &[
+            JValue::Int(0 as jint),
+            JValue::Int(data.len() as jint),
+            JValue::Long(timestamp),
+            JValue::Int(flags),
+        ],
     )?;
-    env.set_field(&buffer_info, "flags", "I", JValue::Int(flags))?;
 
     // Write sample
-    call_void_method(
+    WRITE_SAMPLE_DATA.call_void(
         env,
         muxer.as_obj(),
-        "writeSampleData",
-        "(ILjava/nio/ByteBuffer;Landroid/media/MediaCodec$BufferInfo;)V",
         &[
             JValue::Int(track_index),
             JValue::Object(&byte_buffer),
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use unienc_common::passthrough::{aac, h264::AccessUnit};
+use unienc_common::{AacPacketizer, AudioEncoderOptions, H264Packetizer, VideoEncoderOptions};
+
+use crate::common::{
+    CommonEncodedData, CommonEncodedDataContent, MediaFormatValue, media_codec_buffer_flag,
+};
+use crate::config::{MIME_TYPE_AUDIO_AAC, MIME_TYPE_VIDEO_AVC, format_keys::*};
+use crate::error::AndroidError;
+
+fn with_start_code(nal_unit: &[u8]) -> Vec<u8> {
+    let mut data = vec![0, 0, 0, 1];
+    data.extend_from_slice(nal_unit);
+    data
+}
+
+/// Builds the MediaFormat expected by MediaMuxer from the first access unit's parameter sets, then
+/// forwards access units as Annex-B samples.
+pub struct MediaCodecH264Packetizer {
+    width: u32,
+    height: u32,
+    format_sent: bool,
+}
+
+impl MediaCodecH264Packetizer {
+    pub fn new<V: VideoEncoderOptions>(options: &V) -> Self {
+        Self {
+            width: options.width(),
+            height: options.height(),
+            format_sent: false,
+        }
+    }
+}
+
+impl H264Packetizer for MediaCodecH264Packetizer {
+    type Data = CommonEncodedData;
+
+    fn packetize(
+        &mut self,
+        access_unit: &[u8],
+        timestamp: f64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        let access_unit = AccessUnit::parse(access_unit)?;
+        let mut data = Vec::with_capacity(2);
+
+        if !self.format_sent {
+            let (Some(sps), Some(pps)) = (access_unit.sps, access_unit.pps) else {
+                return Err(AndroidError::MissingTrackMetadata.into());
+            };
+            let map = HashMap::from([
+                (
+                    KEY_MIME.to_string(),
+                    MediaFormatValue::String(MIME_TYPE_VIDEO_AVC.to_string()),
+                ),
+                (
+                    KEY_WIDTH.to_string(),
+                    MediaFormatValue::Integer(self.width as i32),
+                ),
+                (
+                    KEY_HEIGHT.to_string(),
+                    MediaFormatValue::Integer(self.height as i32),
+                ),
+                (
+                    KEY_CSD_0.to_string(),
+                    MediaFormatValue::ByteBuffer(with_start_code(sps)),
+                ),
+                (
+                    KEY_CSD_1.to_string(),
+                    MediaFormatValue::ByteBuffer(with_start_code(pps)),
+                ),
+            ]);
+            data.push(CommonEncodedData {
+                content: CommonEncodedDataContent::FormatInfo(map),
+                timestamp: 0.0,
+            });
+            self.format_sent = true;
+        }
+
+        let buffer_flag = if access_unit.is_idr {
+            media_codec_buffer_flag::BUFFER_FLAG_KEY_FRAME
+        } else {
+            0
+        };
+        data.push(CommonEncodedData {
+            content: CommonEncodedDataContent::Buffer {
+                data: access_unit.to_annexb(),
+                buffer_flag,
+            },
+            timestamp,
+        });
+        Ok(data)
+    }
+}
+
+/// Emits the AAC MediaFormat (with AudioSpecificConfig as csd-0) before the first frame.
+pub struct MediaCodecAacPacketizer {
+    sample_rate: u32,
+    channels: u32,
+    format_sent: bool,
+}
+
+impl MediaCodecAacPacketizer {
+    pub fn new<A: AudioEncoderOptions>(options: &A) -> unienc_common::Result<Self> {
+        aac::audio_specific_config(options.sample_rate(), options.channels())?;
+        Ok(Self {
+            sample_rate: options.sample_rate(),
+            channels: options.channels(),
+            format_sent: false,
+        })
+    }
+}
+
+impl AacPacketizer for MediaCodecAacPacketizer {
+    type Data = CommonEncodedData;
+
+    fn packetize(
+        &mut self,
+        frame: &[u8],
+        timestamp_in_samples: u64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        let mut data = Vec::with_capacity(2);
+
+        if !self.format_sent {
+            let config = aac::audio_specific_config(self.sample_rate, self.channels)?;
+            let map = HashMap::from([
+                (
+                    KEY_MIME.to_string(),
+                    MediaFormatValue::String(MIME_TYPE_AUDIO_AAC.to_string()),
+                ),
+                (
+                    KEY_SAMPLE_RATE.to_string(),
+                    MediaFormatValue::Integer(self.sample_rate as i32),
+                ),
+                (
+                    KEY_CHANNEL_COUNT.to_string(),
+                    MediaFormatValue::Integer(self.channels as i32),
+                ),
+                (
+                    KEY_CSD_0.to_string(),
+                    MediaFormatValue::ByteBuffer(config.to_vec()),
+                ),
+            ]);
+            data.push(CommonEncodedData {
+                content: CommonEncodedDataContent::FormatInfo(map),
+                timestamp: 0.0,
+            });
+            self.format_sent = true;
+        }
+
+        data.push(CommonEncodedData {
+            content: CommonEncodedDataContent::Buffer {
+                data: frame.to_vec(),
+                buffer_flag: 0,
+            },
+            timestamp: timestamp_in_samples as f64 / self.sample_rate as f64,
+        });
+        Ok(data)
+    }
+}
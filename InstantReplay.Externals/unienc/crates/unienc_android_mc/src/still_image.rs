@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use jni::{
+    JNIEnv,
+    objects::{JObject, JValue},
+};
+use unienc_common::{
+    CommonError, StillImage, StillImageCapture, StillImageFormat, Timebase, VideoEncoderOptions,
+    VideoFrame, VideoSample,
+};
+
+use crate::VulkanTexture;
+use crate::common::{ImageReader, MediaImage};
+use crate::error::{AndroidError, Result};
+use crate::java::*;
+use crate::vulkan::hardware_buffer_surface::HardwareBufferSurface;
+
+const ACQUIRE_TIMEOUT: Duration = Duration::from_secs(1);
+const ACQUIRE_POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+/// Captures blit sources by rendering them into an ImageReader through the same HardwareBuffer
+/// blit used by the video encoder, then compresses the read-back pixels with Bitmap, or returns them
+/// as they are for [`StillImageFormat::Bgra32`].
+pub struct BitmapStillImageCapture {
+    width: u32,
+    height: u32,
+    format: StillImageFormat,
+    // created on the first capture, as the surface requires an initialized Vulkan context
+    surface: Option<(ImageReader, HardwareBufferSurface)>,
+}
+
+impl BitmapStillImageCapture {
+    pub fn new<V: VideoEncoderOptions>(options: &V, format: StillImageFormat) -> Self {
+        Self {
+            width: options.width(),
+            height: options.height(),
+            format,
+            surface: None,
+        }
+    }
+}
+
+impl StillImageCapture for BitmapStillImageCapture {
+    type Data = VideoSample<VulkanTexture>;
+
+    async fn capture(&mut self, data: Self::Data) -> unienc_common::Result<StillImage> {
+        let VideoFrame::BlitSource {
+            texture_token,
+            width,
+            height,
+            graphics_format,
+            options,
+            event_issuer,
+            ..
+        } = data.frame
+        else {
+            return Err(CommonError::StillImageRequiresBlitSource);
+        };
+
+        if self.surface.is_none() {
+            let reader = ImageReader::new(self.width, self.height, 2)?;
+            let surface = HardwareBufferSurface::new(reader.surface(), self.width, self.height, 2)?;
+            self.surface = Some((reader, surface));
+        }
+        let Some((reader, surface)) = &self.surface else {
+            unreachable!();
+        };
+
+        let frame = surface.dequeue_frame()?;
+        let frame = crate::vulkan::blit_texture_to_frame(
+            event_issuer,
+            texture_token,
+            width,
+            height,
+            graphics_format,
+            options,
+            frame,
+        )
+        .await?;
+        surface.queue_frame(frame, Timebase::NANOSECONDS.to_units(data.timestamp))?;
+
+        let image = acquire_image(reader).await?;
+        let rgba = read_rgba(&image, self.width, self.height)?;
+        drop(image);
+
+        Ok(StillImage {
+            data: compress(&rgba, self.width, self.height, self.format)?,
+            width: self.width,
+            height: self.height,
+            timestamp: data.timestamp,
+        })
+    }
+}
+
+async fn acquire_image(reader: &ImageReader) -> Result<MediaImage> {
+    let deadline = tokio::time::Instant::now() + ACQUIRE_TIMEOUT;
+    loop {
+        if let Some(image) = reader.acquire_next_image()? {
+            return Ok(image);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(AndroidError::Other(
+                "Timed out waiting for captured image".to_string(),
+            ));
+        }
+        tokio::time::sleep(ACQUIRE_POLL_INTERVAL).await;
+    }
+}
+
+/// Copy the single RGBA plane into a tightly packed buffer, dropping row padding
+fn read_rgba(image: &MediaImage, width: u32, height: u32) -> Result<Vec<u8>> {
+    let planes = image.get_planes()?;
+    let [plane] = planes.as_slice() else {
+        return Err(AndroidError::UnsupportedPlaneCount(planes.len()));
+    };
+
+    let row_len = (width * 4) as usize;
+    let mut data = vec![0u8; row_len * height as usize];
+    for (y, row) in data.chunks_exact_mut(row_len).enumerate() {
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                plane.ptr.add(y * plane.row_stride as usize),
+                row.as_mut_ptr(),
+                row_len,
+            );
+        }
+    }
+    Ok(data)
+}
+
+fn compress(rgba: &[u8], width: u32, height: u32, format: StillImageFormat) -> Result<Vec<u8>> {
+    let (format_name, quality) = match format {
+        StillImageFormat::Png => ("PNG", 100),
+        StillImageFormat::Jpeg { quality } => ("JPEG", (quality.clamp(0.0, 1.0) * 100.0) as i32),
+        StillImageFormat::Bgra32 => {
+            let mut bgra = rgba.to_vec();
+            for pixel in bgra.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+            return Ok(bgra);
+        }
+    };
+
+    let env = &mut attach_current_thread()?;
+    env.with_local_frame(8, |env| {
+        let bitmap = create_bitmap(env, width, height)?;
+
+        // ARGB_8888 bitmaps are stored as RGBA bytes
+        let mut pixels = rgba.to_vec();
+        let buffer = unsafe { env.new_direct_byte_buffer(pixels.as_mut_ptr(), pixels.len()) }?;
+        call_void_method(
+            env,
+            &bitmap,
+            "copyPixelsFromBuffer",
+            "(Ljava/nio/Buffer;)V",
+            &[JValue::Object(&buffer)],
+        )?;
+
+        let compress_format = env
+            .get_static_field(
+                "android/graphics/Bitmap$CompressFormat",
+                format_name,
+                "Landroid/graphics/Bitmap$CompressFormat;",
+            )?
+            .l()?;
+        let stream = env.new_object("java/io/ByteArrayOutputStream", "()V", &[])?;
+        let compressed = env
+            .call_method(
+                &bitmap,
+                "compress",
+                "(Landroid/graphics/Bitmap$CompressFormat;ILjava/io/OutputStream;)Z",
+                &[
+                    JValue::Object(&compress_format),
+                    JValue::Int(quality),
+                    JValue::Object(&stream),
+                ],
+            )?
+            .z()?;
+        call_void_method(env, &bitmap, "recycle", "()V", &[])?;
+        if !compressed {
+            return Err(AndroidError::Other("Failed to compress bitmap".to_string()));
+        }
+
+        let bytes = call_object_method(env, &stream, "toByteArray", "()[B", &[])?;
+        Ok(env.convert_byte_array(jni::objects::JByteArray::from(bytes))?)
+    })
+}
+
+fn create_bitmap<'a>(env: &mut JNIEnv<'a>, width: u32, height: u32) -> Result<JObject<'a>> {
+    let argb8888 = env
+        .get_static_field(
+            "android/graphics/Bitmap$Config",
+            "ARGB_8888",
+            "Landroid/graphics/Bitmap$Config;",
+        )?
+        .l()?;
+    let bitmap = env
+        .call_static_method(
+            "android/graphics/Bitmap",
+            "createBitmap",
+            "(IILandroid/graphics/Bitmap$Config;)Landroid/graphics/Bitmap;",
+            &[
+                JValue::Int(width as i32),
+                JValue::Int(height as i32),
+                JValue::Object(&argb8888),
+            ],
+        )?
+        .l()?;
+    check_jni_exception(env)?;
+    Ok(bitmap)
+}
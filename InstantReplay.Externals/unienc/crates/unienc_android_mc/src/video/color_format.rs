@@ -0,0 +1,130 @@
+//! Color formats of the buffer input mode, used when frames are pushed as BGRA pixels.
+//!
+//! `COLOR_FormatYUV420Flexible` lets the codec describe its layout through `getInputImage`, but
+//! some codecs do not support it and others return a null image for it. Codecs without it are
+//! configured with a concrete YUV 420 format instead, and frames are written into the input buffer
+//! following the standard layout of that format.
+
+use jni::sys::jint;
+
+use crate::common::{ImagePlane, MediaCodec, MediaFormatValue, get_android_api_level};
+use crate::config::{format_keys::KEY_COLOR_FORMAT, *};
+use crate::error::{AndroidError, Result};
+use crate::java::SafeGlobalRef;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BufferColorFormat {
+    /// Layout described by the input image
+    Flexible,
+    /// I420: Y plane followed by U and V planes
+    Planar(jint),
+    /// NV12: Y plane followed by interleaved U and V samples
+    SemiPlanar(jint),
+}
+
+impl BufferColorFormat {
+    fn from_value(value: jint) -> Option<Self> {
+        match value {
+            COLOR_FORMAT_YUV420_FLEXIBLE => Some(Self::Flexible),
+            COLOR_FORMAT_YUV420_PLANAR | COLOR_FORMAT_YUV420_PACKED_PLANAR => {
+                Some(Self::Planar(value))
+            }
+            COLOR_FORMAT_YUV420_SEMI_PLANAR | COLOR_FORMAT_YUV420_PACKED_SEMI_PLANAR => {
+                Some(Self::SemiPlanar(value))
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn value(self) -> jint {
+        match self {
+            Self::Flexible => COLOR_FORMAT_YUV420_FLEXIBLE,
+            Self::Planar(value) | Self::SemiPlanar(value) => value,
+        }
+    }
+
+    /// Picks the format to configure `codec` with, preferring flexible YUV, then semi-planar which
+    /// hardware encoders support most widely.
+    pub(crate) fn choose(codec: &MediaCodec) -> Result<Self> {
+        let supported = match codec.supported_color_formats(MIME_TYPE_VIDEO_AVC) {
+            Ok(supported) => supported,
+            Err(e) => {
+                unienc_common::log!("Failed to query color formats, assuming flexible YUV: {e}");
+                return Ok(Self::Flexible);
+            }
+        };
+        // getInputImage is API 21+
+        let flexible_available = get_android_api_level()? >= 21;
+        let formats: Vec<_> = supported
+            .iter()
+            .filter_map(|value| Self::from_value(*value))
+            .filter(|format| flexible_available || *format != Self::Flexible)
+            .collect();
+
+        [
+            formats.iter().find(|f| **f == Self::Flexible),
+            formats.iter().find(|f| matches!(f, Self::SemiPlanar(_))),
+            formats.iter().find(|f| matches!(f, Self::Planar(_))),
+        ]
+        .into_iter()
+        .flatten()
+        .next()
+        .copied()
+        .ok_or(AndroidError::UnsupportedColorFormats(supported))
+    }
+
+    /// Layout to write a flexible format with when the codec returns no input image: the concrete
+    /// format reported by the configured codec, or semi-planar when it reports none.
+    pub(crate) fn resolve_without_image(self, codec: &MediaCodec) -> Self {
+        if self != Self::Flexible {
+            return self;
+        }
+        let reported = match codec.get_input_format() {
+            Ok(format) => match format.get(KEY_COLOR_FORMAT) {
+                Some(MediaFormatValue::Integer(value)) => Self::from_value(*value),
+                _ => None,
+            },
+            Err(_) => None,
+        };
+        match reported {
+            Some(format @ (Self::Planar(_) | Self::SemiPlanar(_))) => format,
+            _ => Self::SemiPlanar(COLOR_FORMAT_YUV420_SEMI_PLANAR),
+        }
+    }
+
+    /// Planes of a `width` x `height` frame stored in an input buffer of `size` bytes at `ptr`.
+    pub(crate) fn buffer_planes(
+        self,
+        buffer: &SafeGlobalRef,
+        ptr: *mut u8,
+        size: usize,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<ImagePlane>> {
+        let luma_size = (width * height) as usize;
+        let required = luma_size * 3 / 2;
+        if size < required {
+            return Err(AndroidError::InputBufferTooSmall { required, size });
+        }
+        let plane = |offset: usize, pixel_stride: jint, row_stride: u32| ImagePlane {
+            _buffer: buffer.clone(),
+            ptr: unsafe { ptr.add(offset) },
+            pixel_stride,
+            row_stride: row_stride as jint,
+        };
+
+        match self {
+            Self::Planar(_) => Ok(vec![
+                plane(0, 1, width),
+                plane(luma_size, 1, width / 2),
+                plane(luma_size + luma_size / 4, 1, width / 2),
+            ]),
+            Self::SemiPlanar(_) => Ok(vec![
+                plane(0, 1, width),
+                plane(luma_size, 2, width),
+                plane(luma_size + 1, 2, width),
+            ]),
+            Self::Flexible => Err(AndroidError::ImageNull),
+        }
+    }
+}
@@ -2,17 +2,23 @@ use jni::{JNIEnv, objects::JValue, signature::ReturnType, sys::jint};
 use std::sync::Arc;
 use std::time::Duration;
 use unienc_common::{
-    Encoder, EncoderInput, EncoderOutput, TryFromUnityNativeTexturePointer, VideoFrame, VideoSample,
+    Encoder, EncoderInput, EncoderOutput, LatencyMode, Timebase, VideoFrame, VideoSample,
 };
 
-use crate::error::{AndroidError, OptionExt, Result};
+mod color_format;
+
+use crate::codec_stats::CodecActivity;
+use crate::error::{AndroidError, Result};
+use crate::media_projection::{MediaProjection, VirtualDisplay, nano_time};
 use crate::{VulkanTexture, java::*};
 
+#[cfg(feature = "blit")]
 use crate::vulkan::hardware_buffer_surface::HardwareBufferSurface;
 use crate::{
     common::{media_codec_buffer_flag::BUFFER_FLAG_END_OF_STREAM, *},
     config::{format_keys::*, *},
 };
+use color_format::BufferColorFormat;
 
 pub struct MediaCodecVideoEncoder<R: unienc_common::Runtime + 'static> {
     input: MediaCodecVideoEncoderInput<R>,
@@ -28,27 +34,35 @@ pub struct MediaCodecVideoEncoderInput<R: unienc_common::Runtime + 'static> {
     padded_height: u32,
     last_timestamp: i64,
     processor: MediaCodecVideoEncoderInputProcessor,
+    activity: Arc<CodecActivity>,
     runtime: R,
 }
 
 struct UninitializedState {
-    tx: tokio::sync::oneshot::Sender<()>,
+    // sends the offset subtracted from output timestamps, in seconds
+    tx: tokio::sync::oneshot::Sender<f64>,
     bitrate: u32,
     fps_hint: u32,
+    latency_mode: LatencyMode,
 }
 
 enum MediaCodecVideoEncoderInputProcessor {
     Uninitialized(UninitializedState),
-    Buffer(),
+    Buffer(BufferColorFormat),
+    #[cfg(feature = "blit")]
     HardwareBuffer(Arc<HardwareBufferSurface>),
+    // mirrors into the codec input surface until the input is dropped
+    MediaProjection(#[allow(dead_code)] VirtualDisplay),
 }
 
 unsafe impl<R: unienc_common::Runtime + 'static> Send for MediaCodecVideoEncoderInput<R> {}
 
 pub struct MediaCodecVideoEncoderOutput {
     codec: MediaCodec,
+    activity: Arc<CodecActivity>,
     end_of_stream: bool,
-    initialization: Option<tokio::sync::oneshot::Receiver<()>>,
+    initialization: Option<tokio::sync::oneshot::Receiver<f64>>,
+    timestamp_offset: f64,
 }
 
 impl<R: unienc_common::Runtime + 'static> Encoder for MediaCodecVideoEncoder<R> {
@@ -62,11 +76,18 @@ impl<R: unienc_common::Runtime + 'static> Encoder for MediaCodecVideoEncoder<R>
 
 impl<R: unienc_common::Runtime + 'static> Drop for MediaCodecVideoEncoderInput<R> {
     fn drop(&mut self) {
+        if !matches!(
+            self.processor,
+            MediaCodecVideoEncoderInputProcessor::Uninitialized(_)
+        ) {
+            self.activity.update_metrics(&self.codec);
+        }
         // notify end of stream
         || -> Result<()> {
             match &self.processor {
                 MediaCodecVideoEncoderInputProcessor::Uninitialized(_) => Ok(()),
-                MediaCodecVideoEncoderInputProcessor::Buffer() => loop {
+                MediaCodecVideoEncoderInputProcessor::Buffer(_) => loop {
+                    let input = self.activity.lock_input();
                     let buffer_index = self
                         .codec
                         .dequeue_input_buffer(Duration::from_millis(100))?;
@@ -81,13 +102,18 @@ impl<R: unienc_common::Runtime + 'static> Drop for MediaCodecVideoEncoderInput<R
                         return Ok(());
                     }
                     if buffer_index == media_codec_errors::INFO_TRY_AGAIN_LATER {
+                        drop(input);
                         std::thread::sleep(Duration::from_millis(10));
                     } else {
                         return Err(AndroidError::NoInputBuffer);
                     }
                 },
+                #[cfg(feature = "blit")]
                 MediaCodecVideoEncoderInputProcessor::HardwareBuffer(_) => {
-                    self.codec.print_metrics()?;
+                    self.codec.signal_end_of_input_stream()?;
+                    Ok(())
+                }
+                MediaCodecVideoEncoderInputProcessor::MediaProjection(_) => {
                     self.codec.signal_end_of_input_stream()?;
                     Ok(())
                 }
@@ -110,11 +136,12 @@ impl<R: unienc_common::Runtime + 'static> MediaCodecVideoEncoder<R> {
         let padded_height = round_up_to_16(original_height);
 
         // Create encoder using the wrapper (configure is deferred until first frame)
-        let codec = MediaCodec::create_encoder(MIME_TYPE_VIDEO_AVC)?;
+        let codec = crate::codec_selection::create_video_encoder()?;
 
         // Clone for both input and output
         let codec_input = codec.clone();
         let codec_output = codec;
+        let activity = CodecActivity::register();
 
         // initialization
         let (tx, rx) = tokio::sync::oneshot::channel();
@@ -132,25 +159,95 @@ impl<R: unienc_common::Runtime + 'static> MediaCodecVideoEncoder<R> {
                         tx,
                         bitrate: options.bitrate(),
                         fps_hint: options.fps_hint(),
+                        latency_mode: options.latency_mode(),
                     },
                 ),
+                activity: activity.clone(),
                 runtime,
             },
             output: MediaCodecVideoEncoderOutput {
                 codec: codec_output,
+                activity,
                 end_of_stream: false,
                 initialization: rx.into(),
+                timestamp_offset: 0.0,
             },
         })
     }
 }
 
+impl<R: unienc_common::Runtime + 'static> MediaCodecVideoEncoderInput<R> {
+    /// Feeds the encoder from a mirror of the device display instead of pushed frames, which
+    /// includes system UI and other activities. Must be called before any frame is pushed.
+    /// `timestamp` is the time of this call on the clock pushed samples use.
+    pub fn start_media_projection(
+        &mut self,
+        projection: &MediaProjection,
+        density_dpi: u32,
+        timestamp: f64,
+    ) -> Result<()> {
+        let MediaCodecVideoEncoderInputProcessor::Uninitialized(_) = &self.processor else {
+            return Err(AndroidError::EncoderInputMismatch);
+        };
+        let MediaCodecVideoEncoderInputProcessor::Uninitialized(state) = std::mem::replace(
+            &mut self.processor,
+            MediaCodecVideoEncoderInputProcessor::Buffer(BufferColorFormat::Flexible), // temporary placeholder
+        ) else {
+            unreachable!();
+        };
+
+        let env = &mut attach_current_thread()?;
+        let format = create_video_format_raw(
+            env,
+            self.padded_width,
+            self.padded_height,
+            state.bitrate,
+            state.fps_hint,
+            state.latency_mode,
+            COLOR_FORMAT_SURFACE,
+        )?;
+        self.codec.configure(&format)?;
+        _ = self.codec.print_codec_info();
+
+        let surface = self.codec.create_input_surface()?;
+        self.codec.start()?;
+        self.activity.update_metrics(&self.codec);
+        let display = VirtualDisplay::new(
+            projection,
+            self.padded_width,
+            self.padded_height,
+            density_dpi,
+            &surface,
+        )?;
+
+        self.processor = MediaCodecVideoEncoderInputProcessor::MediaProjection(display);
+        _ = state
+            .tx
+            .send(Timebase::NANOSECONDS.to_seconds(nano_time()?) - timestamp);
+        Ok(())
+    }
+}
+
 impl<R: unienc_common::Runtime + 'static> EncoderInput for MediaCodecVideoEncoderInput<R> {
     type Data = VideoSample<VulkanTexture>;
 
     async fn push(&mut self, data: Self::Data) -> unienc_common::Result<()> {
         push_video_impl(self, data).await.map_err(Into::into)
     }
+
+    fn request_keyframe(&mut self) -> bool {
+        // the codec is not started yet, and its first frame is a keyframe anyway
+        if let MediaCodecVideoEncoderInputProcessor::Uninitialized(_) = self.processor {
+            return true;
+        }
+        match self.codec.request_sync_frame() {
+            Ok(()) => true,
+            Err(e) => {
+                unienc_common::log!("Failed to request a keyframe: {e}");
+                false
+            }
+        }
+    }
 }
 
 async fn push_video_impl<R: unienc_common::Runtime + 'static>(
@@ -161,17 +258,17 @@ async fn push_video_impl<R: unienc_common::Runtime + 'static>(
         VideoFrame::Bgra32(frame) => {
             match &this.processor {
                 MediaCodecVideoEncoderInputProcessor::Uninitialized(_) => {
-                    // setup for buffer input mode with YUV420_FLEXIBLE
+                    // setup for buffer input mode with a YUV 420 format the codec supports
+                    let color_format = BufferColorFormat::choose(&this.codec)?;
                     let MediaCodecVideoEncoderInputProcessor::Uninitialized(state) =
                         std::mem::replace(
                             &mut this.processor,
-                            MediaCodecVideoEncoderInputProcessor::Buffer(),
+                            MediaCodecVideoEncoderInputProcessor::Buffer(color_format),
                         )
                     else {
                         unreachable!();
                     };
 
-                    // Configure encoder with YUV420_FLEXIBLE format for buffer input
                     let env = &mut attach_current_thread()?;
                     let format = create_video_format_raw(
                         env,
@@ -179,50 +276,77 @@ async fn push_video_impl<R: unienc_common::Runtime + 'static>(
                         this.padded_height,
                         state.bitrate,
                         state.fps_hint,
-                        false, // use_surface = false for buffer mode
+                        state.latency_mode,
+                        color_format.value(),
                     )?;
                     this.codec.configure(&format)?;
                     _ = this.codec.print_codec_info();
 
                     this.codec.start()?;
-                    _ = state.tx.send(());
+                    this.activity.update_metrics(&this.codec);
+                    _ = state.tx.send(0.0);
                 }
-                MediaCodecVideoEncoderInputProcessor::Buffer() => {}
+                MediaCodecVideoEncoderInputProcessor::Buffer(_) => {}
                 _ => {
                     return Err(AndroidError::EncoderInputMismatch);
                 }
             }
 
-            let mut buffer_index;
-            loop {
-                let sleep;
-                {
-                    // Get input buffer
-                    buffer_index = this
-                        .codec
-                        .dequeue_input_buffer(Duration::from_millis(100))?;
-                    if buffer_index == media_codec_errors::INFO_TRY_AGAIN_LATER {
-                        sleep = true;
-                    } else if buffer_index < 0 {
-                        return Err(AndroidError::NoInputBuffer);
-                    } else {
-                        break;
-                    }
-                }
-                if sleep {
+            let (buffer_index, _input) = loop {
+                let input = this.activity.lock_input();
+                // Get input buffer
+                let buffer_index = this
+                    .codec
+                    .dequeue_input_buffer(Duration::from_millis(100))?;
+                if buffer_index == media_codec_errors::INFO_TRY_AGAIN_LATER {
+                    // lets the output flush a stalled codec meanwhile
+                    drop(input);
                     tokio::time::sleep(Duration::from_millis(10)).await;
+                } else if buffer_index < 0 {
+                    return Err(AndroidError::NoInputBuffer);
+                } else {
+                    break (buffer_index, input);
                 }
-            }
+            };
 
             let buffer = this.codec.get_input_buffer(buffer_index)?;
             let env = &mut attach_current_thread()?;
-            let (_base_ptr, capacity, position) = get_direct_buffer_info(env, buffer.as_obj())?;
+            let (base_ptr, capacity, position) = get_direct_buffer_info(env, buffer.as_obj())?;
             let size = capacity - position;
 
-            let image = this.codec.get_input_image(buffer_index)?;
-
-            // Use Image-based approach with dynamic plane layout and padding
-            let planes = image.get_planes()?;
+            let MediaCodecVideoEncoderInputProcessor::Buffer(color_format) = &mut this.processor
+            else {
+                unreachable!();
+            };
+            // keeps the image open while its planes are written
+            let mut _image = None;
+            let planes = match *color_format {
+                BufferColorFormat::Flexible => match this.codec.get_input_image(buffer_index) {
+                    Ok(image) => _image.insert(image).get_planes()?,
+                    Err(AndroidError::ImageNull) => {
+                        *color_format = color_format.resolve_without_image(&this.codec);
+                        unienc_common::log!(
+                            "Encoder returned no input image; writing color format {} instead",
+                            color_format.value()
+                        );
+                        color_format.buffer_planes(
+                            &buffer,
+                            unsafe { base_ptr.add(position) },
+                            size,
+                            this.padded_width,
+                            this.padded_height,
+                        )?
+                    }
+                    Err(e) => return Err(e),
+                },
+                format => format.buffer_planes(
+                    &buffer,
+                    unsafe { base_ptr.add(position) },
+                    size,
+                    this.padded_width,
+                    this.padded_height,
+                )?,
+            };
             crate::common::write_bgra_to_yuv_planes_with_padding(
                 &frame,
                 this.padded_width,
@@ -230,7 +354,7 @@ async fn push_video_impl<R: unienc_common::Runtime + 'static>(
                 &planes,
             )?;
 
-            let timestamp = (data.timestamp * 1_000_000.0) as i64;
+            let timestamp = Timebase::MICROSECONDS.to_units(data.timestamp);
             this.last_timestamp = timestamp;
 
             // Queue input buffer - size is determined by the Image object
@@ -241,16 +365,19 @@ async fn push_video_impl<R: unienc_common::Runtime + 'static>(
                 timestamp, // Convert to microseconds
                 0,
             )?;
+            this.activity.frame_queued();
 
             Ok(())
         }
+        #[cfg(not(feature = "blit"))]
+        VideoFrame::BlitSource { .. } => Err(unienc_common::CommonError::BlitNotSupported.into()),
+        #[cfg(feature = "blit")]
         VideoFrame::BlitSource {
             texture_token,
             width,
             height,
             graphics_format,
-            flip_vertically,
-            is_gamma_workflow,
+            options,
             event_issuer,
             _phantom,
         } => {
@@ -258,7 +385,7 @@ async fn push_video_impl<R: unienc_common::Runtime + 'static>(
             if let MediaCodecVideoEncoderInputProcessor::Uninitialized(_) = &this.processor {
                 let MediaCodecVideoEncoderInputProcessor::Uninitialized(state) = std::mem::replace(
                     &mut this.processor,
-                    MediaCodecVideoEncoderInputProcessor::Buffer(), // temporary placeholder
+                    MediaCodecVideoEncoderInputProcessor::Buffer(BufferColorFormat::Flexible), // temporary placeholder
                 ) else {
                     unreachable!();
                 };
@@ -271,7 +398,8 @@ async fn push_video_impl<R: unienc_common::Runtime + 'static>(
                     this.padded_height,
                     state.bitrate,
                     state.fps_hint,
-                    true, // use_surface = true for hardware buffer mode
+                    state.latency_mode,
+                    COLOR_FORMAT_SURFACE,
                 )?;
                 this.codec.configure(&format)?;
                 _ = this.codec.print_codec_info();
@@ -285,12 +413,13 @@ async fn push_video_impl<R: unienc_common::Runtime + 'static>(
                     3, // max_images
                 )?;
                 this.codec.start()?;
+                this.activity.update_metrics(&this.codec);
 
                 // Replace temporary placeholder with actual HardwareBuffer processor
                 this.processor = MediaCodecVideoEncoderInputProcessor::HardwareBuffer(Arc::new(
                     hardware_buffer_surface,
                 ));
-                _ = state.tx.send(());
+                _ = state.tx.send(0.0);
             }
 
             let MediaCodecVideoEncoderInputProcessor::HardwareBuffer(hb_surface) = &this.processor
@@ -301,43 +430,20 @@ async fn push_video_impl<R: unienc_common::Runtime + 'static>(
             // Dequeue a frame from ImageWriter
             let frame = hb_surface.dequeue_frame()?;
 
-            let (tx, rx) = tokio::sync::oneshot::channel();
-            let runtime = this.runtime.clone();
-
-            event_issuer.issue_graphics_event(
-                Box::new(move |native_texture_ptr| {
-                    let result =
-                        crate::VulkanTexture::try_from_unity_native_texture_ptr(native_texture_ptr)
-                            .map_err(|_| AndroidError::NullVulkanTexture)
-                            .and_then(|texture| {
-                                let image = texture.tex;
-                                crate::vulkan::blit_to_hardware_buffer(
-                                    &image,
-                                    width,
-                                    height,
-                                    graphics_format,
-                                    flip_vertically,
-                                    is_gamma_workflow,
-                                    &frame,
-                                    runtime,
-                                )
-                            });
-                    tx.send((result, frame))
-                        .map_err(|_| AndroidError::RenderThreadSendFailed)
-                        .unwrap();
-                }),
-                *crate::vulkan::EVENT_ID
-                    .get()
-                    .context("Event ID is not reserved")?,
+            let frame = crate::vulkan::blit_texture_to_frame(
+                event_issuer,
                 texture_token,
-            );
-
-            let (blit_result, frame) = rx.await?;
-            let future = blit_result?;
-            future.await?;
+                width,
+                height,
+                graphics_format,
+                options,
+                frame,
+            )
+            .await?;
 
             // Queue the frame to MediaCodec
-            hb_surface.queue_frame(frame, (data.timestamp * 1000.0 * 1000.0 * 1000.0) as i64)?;
+            hb_surface.queue_frame(frame, Timebase::NANOSECONDS.to_units(data.timestamp))?;
+            this.activity.frame_queued();
 
             Ok(())
         }
@@ -356,11 +462,17 @@ async fn pull_video_output_impl(
     this: &mut MediaCodecVideoEncoderOutput,
 ) -> Result<Option<CommonEncodedData>> {
     if let Some(rx) = &mut this.initialization {
-        rx.await?;
+        this.timestamp_offset = rx.await?;
         this.initialization = None;
     }
 
-    pull_encoded_data_with_codec(&this.codec, &mut this.end_of_stream).await
+    let data =
+        pull_encoded_data_with_codec(&this.codec, &mut this.end_of_stream, Some(&this.activity))
+            .await?;
+    Ok(data.map(|mut data| {
+        data.timestamp -= this.timestamp_offset;
+        data
+    }))
 }
 
 // Helper functions for JNI MediaCodec calls
@@ -371,7 +483,8 @@ fn create_video_format_raw(
     padded_height: u32,
     bitrate: u32,
     fps_hint: u32,
-    use_surface: bool,
+    latency_mode: LatencyMode,
+    color_format: jint,
 ) -> Result<SafeGlobalRef> {
     let format_class = env.find_class("android/media/MediaFormat")?;
     let method_id = env.get_static_method_id(
@@ -397,23 +510,20 @@ fn create_video_format_raw(
     let format_obj = format.l()?;
 
     // Set additional parameters
-    set_format_integer(
-        env,
-        &format_obj,
-        KEY_COLOR_FORMAT,
-        if use_surface {
-            COLOR_FORMAT_SURFACE
-        } else {
-            COLOR_FORMAT_YUV420_FLEXIBLE
-        },
-    )?;
+    set_format_integer(env, &format_obj, KEY_COLOR_FORMAT, color_format)?;
 
     set_format_integer(env, &format_obj, KEY_BITRATE, bitrate as jint)?;
     set_format_integer(env, &format_obj, KEY_FRAME_RATE, fps_hint as jint)?;
     set_format_integer(env, &format_obj, KEY_I_FRAME_INTERVAL, 1)?;
 
-    set_format_integer(env, &format_obj, KEY_PRIORITY, 0)?;
-    set_format_integer(env, &format_obj, KEY_OPERATING_RATE, fps_hint as jint)?;
+    match latency_mode {
+        LatencyMode::Realtime => {
+            set_format_integer(env, &format_obj, KEY_PRIORITY, 0)?;
+            set_format_integer(env, &format_obj, KEY_OPERATING_RATE, fps_hint as jint)?;
+        }
+        // non-realtime priority, with the codec running as fast as it can
+        LatencyMode::Offline => set_format_integer(env, &format_obj, KEY_PRIORITY, 1)?,
+    }
 
     SafeGlobalRef::new(env, format_obj)
 }
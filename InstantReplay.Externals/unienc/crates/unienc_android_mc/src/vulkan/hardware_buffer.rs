@@ -1,4 +1,5 @@
 use crate::error::{AndroidError, Result};
+use crate::hardware_buffer;
 use crate::vulkan::types::{VulkanImageHandle, VulkanImageViewHandle, VulkanMemoryHandle};
 use ash::vk;
 use std::sync::Arc;
@@ -24,12 +25,11 @@ impl HardwareBufferImage {
         instance: &ash::Instance,
         ahb: *mut ndk_sys::AHardwareBuffer,
     ) -> Result<Self> {
-        // Acquire the hardware buffer to ensure it stays valid
-        unsafe { ndk_sys::AHardwareBuffer_acquire(ahb) };
-
         // Get hardware buffer description
-        let mut desc: ndk_sys::AHardwareBuffer_Desc = unsafe { std::mem::zeroed() };
-        unsafe { ndk_sys::AHardwareBuffer_describe(ahb, &mut desc) };
+        let desc = unsafe { hardware_buffer::describe(ahb) }?;
+
+        // Acquire the hardware buffer to ensure it stays valid
+        unsafe { hardware_buffer::acquire(ahb) }?;
 
         let width = desc.width;
         let height = desc.height;
@@ -174,7 +174,7 @@ impl HardwareBufferImage {
 impl Drop for HardwareBufferImage {
     fn drop(&mut self) {
         // Release the hardware buffer reference
-        unsafe { ndk_sys::AHardwareBuffer_release(self.ahb) };
+        _ = unsafe { hardware_buffer::release(self.ahb) };
     }
 }
 
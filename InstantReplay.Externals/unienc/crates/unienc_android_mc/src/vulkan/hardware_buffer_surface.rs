@@ -10,8 +10,6 @@ use ash::vk;
 /// This provides explicit control over HardwareBuffer usage flags (VIDEO_ENCODE)
 pub struct HardwareBufferSurface {
     image_writer: ImageWriter,
-    width: u32,
-    height: u32,
 }
 
 impl HardwareBufferSurface {
@@ -25,11 +23,7 @@ impl HardwareBufferSurface {
         let image_writer =
             ImageWriter::new(input_surface, max_images, width as i32, height as i32)?;
 
-        Ok(Self {
-            image_writer,
-            width,
-            height,
-        })
+        Ok(Self { image_writer })
     }
 
     /// Dequeue an available frame for rendering
@@ -49,6 +43,9 @@ impl HardwareBufferSurface {
 
         // Import the hardware buffer as a Vulkan image
         let vk_image = HardwareBufferImage::from_hardware_buffer(&cx.device, &cx.instance, ahb)?;
+        // the consumer may resize its buffers (e.g. on foldables), so the framebuffer and the render
+        // area follow the dequeued buffer rather than the size the surface was created with
+        let (width, height) = (vk_image.width, vk_image.height);
 
         // Create framebuffer for the image
         let framebuffer = VulkanFramebufferHandle::new(
@@ -57,8 +54,8 @@ impl HardwareBufferSurface {
                     &vk::FramebufferCreateInfo::default()
                         .render_pass(*cx.render_pass.render_pass)
                         .attachments(&[vk_image.vk_image_view()])
-                        .width(self.width)
-                        .height(self.height)
+                        .width(width)
+                        .height(height)
                         .layers(1),
                     None,
                 )
@@ -71,8 +68,8 @@ impl HardwareBufferSurface {
             image,
             vk_image,
             framebuffer,
-            width: self.width,
-            height: self.height,
+            width,
+            height,
         })
     }
 
@@ -2,11 +2,16 @@ mod format;
 pub mod hardware_buffer;
 pub mod hardware_buffer_surface;
 mod preprocess;
+pub(crate) mod test_pattern;
 #[allow(dead_code)]
 pub mod types;
 mod utils;
+#[cfg(all(feature = "vulkan-validation", debug_assertions))]
+mod validation;
 
-use crate::error::{AndroidError, Result, ResultExt};
+pub use utils::{VulkanPoolStats, set_vulkan_pool_limits, vulkan_pool_stats};
+
+use crate::error::{AndroidError, OptionExt, Result, ResultExt};
 use ash::vk;
 use std::fmt::Debug;
 use std::future::Future;
@@ -16,114 +21,53 @@ use std::{
     os::raw::c_void,
     sync::{Mutex, OnceLock},
 };
+use unienc_common::profiler::ProfilerMarker;
+use unienc_common::{BlitOptions, GraphicsEventIssuer, TryFromUnityNativeTexturePointer};
 use unity_native_plugin::graphics::{GfxDeviceEventType, IUnityGraphics, UnityGraphics};
-use unity_native_plugin::profiler::{
-    BuiltinProfilerCategory, IUnityProfiler, ProfilerCategoryId, ProfilerMarkerDesc,
-    ProfilerMarkerEventType, ProfilerMarkerFlag, ProfilerMarkerFlags, UnityProfiler,
-};
 use unity_native_plugin::vulkan::{
     IUnityGraphicsVulkan, UnityGraphicsVulkanV2, VulkanEventRenderPassPreCondition,
     VulkanGraphicsQueueAccess, VulkanPluginEventConfig,
 };
 
-use crate::vulkan::preprocess::PreprocessRenderPass;
+use crate::vulkan::preprocess::{BlitWaiter, PreprocessRenderPass};
 use crate::vulkan::utils::FencePool;
 
 static GRAPHICS: OnceLock<Mutex<UnityGraphics>> = OnceLock::new();
 static CONTEXT: OnceLock<Mutex<GlobalContext>> = OnceLock::new();
 pub static EVENT_ID: OnceLock<c_int> = OnceLock::new();
-static MARKERS: OnceLock<Markers> = OnceLock::new();
-static PROFILER: OnceLock<UnityProfiler> = OnceLock::new();
+
+static PREPROCESS_BLIT: ProfilerMarker =
+    ProfilerMarker::new("unienc_android_mc::vulkan::preprocess::blit");
+static PREPROCESS_BLIT_RESOURCES: ProfilerMarker =
+    ProfilerMarker::new("unienc_android_mc::vulkan::preprocess::blit::resources");
+static PREPROCESS_BLIT_COMMANDS: ProfilerMarker =
+    ProfilerMarker::new("unienc_android_mc::vulkan::preprocess::blit::commands");
+static PREPROCESS_BLIT_SUBMIT: ProfilerMarker =
+    ProfilerMarker::new("unienc_android_mc::vulkan::preprocess::blit::submit");
 
 pub(crate) fn is_initialized() -> bool {
     CONTEXT.get().is_some()
 }
 
 pub(crate) struct GlobalContext {
-    vulkan: UnityGraphicsVulkanV2,
     instance: ash::Instance,
     device: Arc<ash::Device>,
+    /// Queue blits are submitted to, which must be safe to submit to from the render thread.
+    queue: vk::Queue,
+    queue_family_index: u32,
     render_pass: Arc<PreprocessRenderPass>,
     fence_pool: Arc<FencePool>,
-}
-
-#[derive(Debug)]
-struct Markers {
-    preprocess_blit: ProfilerMarkerDesc,
-    preprocess_blit_resources: ProfilerMarkerDesc,
-    preprocess_blit_commands: ProfilerMarkerDesc,
-    preprocess_blit_submit: ProfilerMarkerDesc,
-}
-
-unsafe impl Sync for Markers {}
-
-struct MarkerGuard<'a> {
-    marker: &'a ProfilerMarkerDesc,
-}
-
-trait ProfilerMarkerDescExt {
-    fn get(&'_ self) -> MarkerGuard<'_>;
-}
-
-impl ProfilerMarkerDescExt for ProfilerMarkerDesc {
-    fn get(&'_ self) -> MarkerGuard<'_> {
-        if let Some(profiler) = PROFILER.get() {
-            profiler.emit_event(self, ProfilerMarkerEventType::Begin, &[]);
-        }
-        MarkerGuard { marker: self }
-    }
-}
-
-impl<'a> Drop for MarkerGuard<'a> {
-    fn drop(&mut self) {
-        if let Some(profiler) = PROFILER.get() {
-            profiler.emit_event(self.marker, ProfilerMarkerEventType::End, &[]);
-        }
-    }
+    blit_waiter: BlitWaiter,
 }
 
 pub(crate) fn unity_plugin_load(interfaces: &unity_native_plugin::interface::UnityInterfaces) {
-    println!("unienc: unity_plugin_load");
+    unienc_common::log!("unienc: unity_plugin_load");
     let graphics = interfaces.interface::<UnityGraphics>().unwrap();
-    let profiler = interfaces.interface::<UnityProfiler>().unwrap();
-    if profiler.is_available() {
-        _ = PROFILER.set(profiler);
-        MARKERS
-            .set(Markers {
-                preprocess_blit: profiler
-                    .create_marker(
-                        c"unienc_android_mc::vulkan::preprocess::blit",
-                        BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                        ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                        0,
-                    )
-                    .unwrap(),
-                preprocess_blit_resources: profiler
-                    .create_marker(
-                        c"unienc_android_mc::vulkan::preprocess::blit::resources",
-                        BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                        ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                        0,
-                    )
-                    .unwrap(),
-                preprocess_blit_commands: profiler
-                    .create_marker(
-                        c"unienc_android_mc::vulkan::preprocess::blit::commands",
-                        BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                        ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                        0,
-                    )
-                    .unwrap(),
-                preprocess_blit_submit: profiler
-                    .create_marker(
-                        c"unienc_android_mc::vulkan::preprocess::blit::submit",
-                        BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                        ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                        0,
-                    )
-                    .unwrap(),
-            })
-            .unwrap();
+    unienc_common::profiler::unity_plugin_load(interfaces);
+
+    #[cfg(all(feature = "vulkan-validation", debug_assertions))]
+    if let Some(vulkan) = interfaces.interface::<UnityGraphicsVulkanV2>() {
+        validation::intercept_initialization(&vulkan);
     }
 
     GRAPHICS
@@ -135,21 +79,21 @@ pub(crate) fn unity_plugin_load(interfaces: &unity_native_plugin::interface::Uni
 }
 
 extern "system" fn on_device_event(ev_type: GfxDeviceEventType) {
-    println!("unienc: on_device_event {ev_type:?}");
+    unienc_common::log!("unienc: on_device_event {ev_type:?}");
     match ev_type {
         GfxDeviceEventType::Initialize => {
             let graphics = GRAPHICS.get().unwrap().lock().unwrap();
             let renderer = graphics.renderer();
-            println!("unienc: {renderer:?}");
+            unienc_common::log!("unienc: {renderer:?}");
 
-            if renderer != unity_native_plugin::graphics::GfxRenderer::Vulkan {
+            if renderer != unity_native_plugin::graphics::GfxRenderer::Vulkan || is_initialized() {
                 return;
             }
 
             let event_id = graphics.reserve_event_id_range(1);
 
             EVENT_ID.set(event_id).unwrap();
-            println!("unienc: reserved event id {event_id}");
+            unienc_common::log!("unienc: reserved event id {event_id}");
 
             let interfaces = unity_native_plugin::interface::UnityInterfaces::get();
             let vulkan = interfaces.interface::<UnityGraphicsVulkanV2>().unwrap();
@@ -177,7 +121,7 @@ extern "system" fn on_device_event(ev_type: GfxDeviceEventType) {
                     instance,
                 )
             };
-            let device = Arc::new(unsafe {
+            let device = unsafe {
                 ash::Device::load(
                     &ash::InstanceFnV1_0::load(|name| {
                         unity_instance
@@ -187,22 +131,18 @@ extern "system" fn on_device_event(ev_type: GfxDeviceEventType) {
                     }),
                     device,
                 )
-            });
-
-            let queue_family_index = unity_instance.queue_family_index();
+            };
 
-            let render_pass = preprocess::create_pass(device.clone(), queue_family_index)
-                .context("Failed to create pipeline")
-                .unwrap();
+            let context = create_context(
+                instance,
+                device,
+                unity_instance.queue_family_index(),
+                unity_instance.graphics_queue(),
+            )
+            .unwrap();
 
             CONTEXT
-                .set(Mutex::new(GlobalContext {
-                    vulkan,
-                    device: device.clone(),
-                    instance,
-                    render_pass: Arc::new(render_pass),
-                    fence_pool: Arc::new(FencePool::new(device)),
-                }))
+                .set(Mutex::new(context))
                 .map_err(|_| AndroidError::GlobalStateSetFailed)
                 .unwrap();
         }
@@ -212,16 +152,83 @@ extern "system" fn on_device_event(ev_type: GfxDeviceEventType) {
     }
 }
 
-pub fn blit_to_hardware_buffer<R: unienc_common::Runtime + 'static>(
+fn create_context(
+    instance: ash::Instance,
+    device: ash::Device,
+    queue_family_index: u32,
+    queue: vk::Queue,
+) -> Result<GlobalContext> {
+    let device = Arc::new(device);
+    let render_pass = preprocess::create_pass(device.clone(), queue_family_index)
+        .context("Failed to create pipeline")?;
+
+    Ok(GlobalContext {
+        instance,
+        device: device.clone(),
+        queue,
+        queue_family_index,
+        render_pass: Arc::new(render_pass),
+        fence_pool: Arc::new(FencePool::new(device.clone())),
+        blit_waiter: BlitWaiter::new(device).context("Failed to start blit waiter")?,
+    })
+}
+
+/// Blits with the Vulkan device of a host that does not load the plugin from Unity, such as a
+/// custom engine or a test harness. The graphics events of its blits must be run on a thread that
+/// may submit to `queue`, with `event_id` 0. Fails if a device is already in use.
+///
+/// # Safety
+///
+/// `get_instance_proc_addr` must be the `vkGetInstanceProcAddr` of the loader `instance` was
+/// created with, the handles must be valid and belong to `instance`, and the device must outlive
+/// every blit.
+pub unsafe fn set_vulkan_device(
+    get_instance_proc_addr: *const c_void,
+    instance: *mut c_void,
+    device: *mut c_void,
+    queue_family_index: u32,
+    queue: *mut c_void,
+) -> Result<()> {
+    use ash::vk::Handle;
+
+    if is_initialized() {
+        return Err(AndroidError::GlobalStateSetFailed);
+    }
+    let instance = unsafe {
+        ash::Instance::load(
+            &ash::StaticFn {
+                get_instance_proc_addr: std::mem::transmute::<
+                    *const c_void,
+                    vk::PFN_vkGetInstanceProcAddr,
+                >(get_instance_proc_addr),
+            },
+            vk::Instance::from_raw(instance as u64),
+        )
+    };
+    let device =
+        unsafe { ash::Device::load(instance.fp_v1_0(), vk::Device::from_raw(device as u64)) };
+    let context = create_context(
+        instance,
+        device,
+        queue_family_index,
+        vk::Queue::from_raw(queue as u64),
+    )?;
+
+    CONTEXT
+        .set(Mutex::new(context))
+        .map_err(|_| AndroidError::GlobalStateSetFailed)?;
+    _ = EVENT_ID.set(0);
+    Ok(())
+}
+
+pub fn blit_to_hardware_buffer(
     src: &vk::Image,
     src_width: u32,
     src_height: u32,
     src_graphics_format: u32,
-    flip_vertically: bool,
-    is_gamma_workflow: bool,
+    options: BlitOptions,
     frame: &hardware_buffer_surface::HardwareBufferFrame,
-    runtime: R,
-) -> Result<impl Future<Output = Result<()>> + use<R>> {
+) -> Result<impl Future<Output = Result<()>> + use<>> {
     let cx = crate::vulkan::CONTEXT
         .get()
         .ok_or(AndroidError::ContextNotInitialized)?
@@ -234,9 +241,50 @@ pub fn blit_to_hardware_buffer<R: unienc_common::Runtime + 'static>(
         src_width,
         src_height,
         src_graphics_format,
-        flip_vertically,
-        is_gamma_workflow,
+        options,
         frame,
-        runtime,
     )
 }
+
+/// Issues a graphics event that blits the Unity texture behind `texture_token` into `frame` on the
+/// render thread, then waits for the GPU to finish it.
+pub async fn blit_texture_to_frame(
+    event_issuer: Box<dyn GraphicsEventIssuer + Send>,
+    texture_token: usize,
+    src_width: u32,
+    src_height: u32,
+    src_graphics_format: u32,
+    options: BlitOptions,
+    frame: hardware_buffer_surface::HardwareBufferFrame,
+) -> Result<hardware_buffer_surface::HardwareBufferFrame> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    event_issuer.issue_graphics_event(
+        Box::new(move |native_texture_ptr| {
+            let result =
+                crate::VulkanTexture::try_from_unity_native_texture_ptr(native_texture_ptr)
+                    .map_err(|_| AndroidError::NullVulkanTexture)
+                    .and_then(|texture| {
+                        let image = texture.tex;
+                        blit_to_hardware_buffer(
+                            &image,
+                            src_width,
+                            src_height,
+                            src_graphics_format,
+                            options,
+                            &frame,
+                        )
+                    });
+            tx.send((result, frame))
+                .map_err(|_| AndroidError::RenderThreadSendFailed)
+                .unwrap();
+        }),
+        *EVENT_ID.get().context("Event ID is not reserved")?,
+        texture_token,
+    );
+
+    let (blit_result, frame) = rx.await?;
+    let future = blit_result?;
+    future.await?;
+    Ok(frame)
+}
@@ -1,4 +1,5 @@
 #!/bin/bash
 
 glslangValidator preprocess.vert.glsl -V -l -o preprocess.vert.glsl.spv
-glslangValidator preprocess.frag.glsl -V -l -o preprocess.frag.glsl.spv
\ No newline at end of file
+glslangValidator preprocess.frag.glsl -V -l -o preprocess.frag.glsl.spv
+glslangValidator preprocess_equirect.frag.glsl -V -l -o preprocess_equirect.frag.glsl.spv
\ No newline at end of file
@@ -7,15 +7,24 @@ use crate::vulkan::types::{
     VulkanPipelineLayoutHandle, VulkanRenderPassHandle, VulkanSamplerHandle,
     VulkanShaderModuleHandle,
 };
-use crate::vulkan::utils::{FenceGuard, create_shader_module};
-use crate::vulkan::{GlobalContext, MARKERS, ProfilerMarkerDescExt};
+use crate::vulkan::utils::{DESCRIPTOR_SETS, FenceGuard, create_shader_module};
+use crate::vulkan::{
+    GlobalContext, PREPROCESS_BLIT, PREPROCESS_BLIT_COMMANDS, PREPROCESS_BLIT_RESOURCES,
+    PREPROCESS_BLIT_SUBMIT,
+};
 use ash::vk;
 use std::future::Future;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, mpsc};
+use tokio::sync::oneshot;
+use unienc_common::{BlitOptions, Projection};
 use unity_native_plugin::vulkan::IUnityGraphicsVulkan;
 
 const VERT: &[u8] = include_bytes!("preprocess.vert.glsl.spv");
 const FRAG: &[u8] = include_bytes!("preprocess.frag.glsl.spv");
+const FRAG_EQUIRECT: &[u8] = include_bytes!("preprocess_equirect.frag.glsl.spv");
+
+const PIPELINE_FLAT: usize = 0;
+const PIPELINE_EQUIRECT: usize = 1;
 
 #[allow(dead_code)]
 pub struct PreprocessRenderPass {
@@ -23,14 +32,21 @@ pub struct PreprocessRenderPass {
     pipeline_layout: VulkanPipelineLayoutHandle,
     shader_mod_vert: VulkanShaderModuleHandle,
     shader_mod_frag: VulkanShaderModuleHandle,
+    shader_mod_frag_equirect: VulkanShaderModuleHandle,
     desc_set_layout: VulkanDescriptorSetLayoutHandle,
     desc_sets: Arc<DescriptorSetPool>,
     sampler: VulkanSamplerHandle,
     pub(crate) render_pass: VulkanRenderPassHandle,
-    command_pool: Arc<VulkanCommandPoolHandle>,
+    command_buffers: Arc<CommandBufferRing>,
 }
 
+/// Number of descriptor sets allocated at creation and each time the pool grows.
+const DESCRIPTOR_SET_CHUNK: u32 = 4;
+
 struct DescriptorSetPool {
+    device: Arc<ash::Device>,
+    // kept alive by the render pass, which outlives every blit that can grow the pool
+    layout: vk::DescriptorSetLayout,
     sets: Mutex<Vec<VulkanDescriptorSet>>,
 }
 
@@ -40,17 +56,36 @@ struct DescriptorSetGuard {
 }
 
 impl DescriptorSetPool {
-    pub fn new(sets: Vec<VulkanDescriptorSet>) -> Self {
-        Self {
+    pub fn new(device: Arc<ash::Device>, layout: vk::DescriptorSetLayout) -> Result<Self> {
+        let sets = allocate_descriptor_sets(&device, layout, DESCRIPTOR_SET_CHUNK)?;
+        DESCRIPTOR_SETS.add(DESCRIPTOR_SET_CHUNK);
+        Ok(Self {
+            device,
+            layout,
             sets: Mutex::new(sets),
-        }
+        })
     }
-    pub fn pop(self: &Arc<Self>) -> Option<DescriptorSetGuard> {
-        let mut sets = self.sets.lock().unwrap();
-        sets.pop().map(|desc_set| DescriptorSetGuard {
+
+    /// Returns `None` when every set is in flight and the pool cannot grow any further.
+    pub fn pop(self: &Arc<Self>) -> Result<Option<DescriptorSetGuard>> {
+        let mut sets = self.sets.lock().map_err(|_| AndroidError::MutexPoisoned)?;
+        if sets.is_empty() {
+            let count = DESCRIPTOR_SETS.reserve(DESCRIPTOR_SET_CHUNK);
+            if count > 0 {
+                unienc_common::log!("Allocating {count} more descriptor sets");
+                match allocate_descriptor_sets(&self.device, self.layout, count) {
+                    Ok(new_sets) => sets.extend(new_sets),
+                    Err(e) => {
+                        DESCRIPTOR_SETS.release(count);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(sets.pop().map(|desc_set| DescriptorSetGuard {
             desc_set: Some(desc_set),
             pool: self.clone(),
-        })
+        }))
     }
 
     pub fn push(&self, desc_set: VulkanDescriptorSet) {
@@ -73,6 +108,136 @@ impl DescriptorSetGuard {
     }
 }
 
+fn allocate_descriptor_sets(
+    device: &Arc<ash::Device>,
+    layout: vk::DescriptorSetLayout,
+    count: u32,
+) -> Result<Vec<VulkanDescriptorSet>> {
+    let desc_pool = Arc::new(VulkanDescriptorPoolHandle::new(
+        unsafe {
+            device.create_descriptor_pool(
+                &vk::DescriptorPoolCreateInfo::default()
+                    // sets are freed one by one when dropped
+                    .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+                    .pool_sizes(&[vk::DescriptorPoolSize::default()
+                        .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .descriptor_count(count)])
+                    .max_sets(count),
+                None,
+            )
+        }?,
+        device.clone(),
+    ));
+
+    Ok(unsafe {
+        device.allocate_descriptor_sets(
+            &vk::DescriptorSetAllocateInfo::default()
+                .descriptor_pool(**desc_pool)
+                .set_layouts(&vec![layout; count as usize]),
+        )
+    }?
+    .iter()
+    .map(|s| VulkanDescriptorSet::new(*s, desc_pool.clone(), device.clone()))
+    .collect())
+}
+
+/// Command buffers are recorded again for every blit, but reused instead of being allocated and
+/// freed each frame. They are only allocated and recorded on the render thread, so the command
+/// pool is never used concurrently.
+struct CommandBufferRing {
+    device: Arc<ash::Device>,
+    command_pool: Arc<VulkanCommandPoolHandle>,
+    buffers: Mutex<Vec<VulkanCommandBuffer>>,
+}
+
+struct CommandBufferGuard {
+    command_buffer: Option<VulkanCommandBuffer>,
+    ring: Arc<CommandBufferRing>,
+}
+
+impl CommandBufferRing {
+    pub fn pop(self: &Arc<Self>) -> Result<CommandBufferGuard> {
+        let reused = self
+            .buffers
+            .lock()
+            .map_err(|_| AndroidError::MutexPoisoned)?
+            .pop();
+        let command_buffer = match reused {
+            Some(command_buffer) => command_buffer,
+            None => {
+                let command_buffer = unsafe {
+                    self.device.allocate_command_buffers(
+                        &vk::CommandBufferAllocateInfo::default()
+                            .command_pool(**self.command_pool)
+                            .level(vk::CommandBufferLevel::PRIMARY)
+                            .command_buffer_count(1),
+                    )
+                }?[0];
+                VulkanCommandBuffer::new(
+                    self.command_pool.clone(),
+                    command_buffer,
+                    self.device.clone(),
+                )
+            }
+        };
+        Ok(CommandBufferGuard {
+            command_buffer: Some(command_buffer),
+            ring: self.clone(),
+        })
+    }
+}
+
+impl CommandBufferGuard {
+    pub fn get(&self) -> &VulkanCommandBuffer {
+        self.command_buffer.as_ref().unwrap()
+    }
+}
+
+impl Drop for CommandBufferGuard {
+    fn drop(&mut self) {
+        if let Some(command_buffer) = self.command_buffer.take()
+            && let Ok(mut buffers) = self.ring.buffers.lock()
+        {
+            buffers.push(command_buffer);
+        }
+    }
+}
+
+type PendingBlit = (HardwareBufferBlitResources, oneshot::Sender<()>);
+
+/// Waits for submitted blits on a single thread in submission order, instead of a blocking task
+/// per frame, and releases their resources once the GPU is done with them.
+pub(crate) struct BlitWaiter {
+    sender: mpsc::Sender<PendingBlit>,
+}
+
+impl BlitWaiter {
+    pub fn new(device: Arc<ash::Device>) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel::<PendingBlit>();
+        std::thread::Builder::new()
+            .name("unienc-blit-waiter".to_string())
+            .spawn(move || {
+                for (resources, done) in receiver {
+                    let _ = unsafe {
+                        device.wait_for_fences(&[**resources.fence.get()], true, u64::MAX)
+                    };
+                    drop(resources);
+                    let _ = done.send(());
+                }
+            })
+            .map_err(|_| AndroidError::BlitWaiterUnavailable)?;
+        Ok(Self { sender })
+    }
+
+    fn wait(&self, resources: HardwareBufferBlitResources) -> Result<oneshot::Receiver<()>> {
+        let (done, receiver) = oneshot::channel();
+        self.sender
+            .send((resources, done))
+            .map_err(|_| AndroidError::BlitWaiterUnavailable)?;
+        Ok(receiver)
+    }
+}
+
 #[repr(C)]
 struct VertPushConstants {
     scale_and_tiling: [f32; 4],
@@ -114,6 +279,7 @@ pub fn create_pass(
     // create pipeline
     let shader_vert = create_shader_module(&device, VERT)?;
     let shader_frag = create_shader_module(&device, FRAG)?;
+    let shader_frag_equirect = create_shader_module(&device, FRAG_EQUIRECT)?;
 
     let set_layout = VulkanDescriptorSetLayoutHandle::new(
         unsafe {
@@ -149,75 +315,84 @@ pub fn create_pass(
         device.clone(),
     );
 
+    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default();
+    let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::default()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+        .primitive_restart_enable(false);
+    let viewport_state = vk::PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+    let rasterization_state = vk::PipelineRasterizationStateCreateInfo::default()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.0f32)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .depth_bias_enable(false)
+        .depth_bias_constant_factor(0.0f32)
+        .depth_bias_clamp(0.0f32)
+        .depth_bias_slope_factor(0.0f32);
+    let multisample_state = vk::PipelineMultisampleStateCreateInfo::default()
+        .sample_shading_enable(false)
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+        .min_sample_shading(1.0f32)
+        .alpha_to_coverage_enable(false)
+        .alpha_to_one_enable(false);
+    let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::default()
+        .color_write_mask(
+            vk::ColorComponentFlags::R
+                | vk::ColorComponentFlags::G
+                | vk::ColorComponentFlags::B
+                | vk::ColorComponentFlags::A,
+        )
+        .blend_enable(false)];
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::default()
+        .logic_op_enable(false)
+        .logic_op(vk::LogicOp::COPY)
+        .attachments(&color_blend_attachments)
+        .blend_constants([0.0, 0.0, 0.0, 0.0]);
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+    let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+        .vertex_input_state(&vertex_input_state)
+        .input_assembly_state(&input_assembly_state)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterization_state)
+        .multisample_state(&multisample_state)
+        .color_blend_state(&color_blend_state)
+        .dynamic_state(&dynamic_state)
+        .layout(*pipeline_layout)
+        .render_pass(render_pass)
+        .subpass(0)
+        .base_pipeline_handle(vk::Pipeline::null())
+        .base_pipeline_index(0);
+
+    let stages = |frag: vk::ShaderModule| {
+        [
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(*shader_vert)
+                .name(c"main"),
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag)
+                .name(c"main"),
+        ]
+    };
+    let flat_stages = stages(*shader_frag);
+    let equirect_stages = stages(*shader_frag_equirect);
+
+    // indexed by `PIPELINE_FLAT` and `PIPELINE_EQUIRECT`
     let pipelines = match unsafe {
         device.create_graphics_pipelines(
             vk::PipelineCache::null(),
-            &[vk::GraphicsPipelineCreateInfo::default()
-                .stages(&[
-                    vk::PipelineShaderStageCreateInfo::default()
-                        .stage(vk::ShaderStageFlags::VERTEX)
-                        .module(*shader_vert)
-                        .name(c"main"),
-                    vk::PipelineShaderStageCreateInfo::default()
-                        .stage(vk::ShaderStageFlags::FRAGMENT)
-                        .module(*shader_frag)
-                        .name(c"main"),
-                ])
-                .vertex_input_state(&vk::PipelineVertexInputStateCreateInfo::default())
-                .input_assembly_state(
-                    &vk::PipelineInputAssemblyStateCreateInfo::default()
-                        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-                        .primitive_restart_enable(false),
-                )
-                .viewport_state(
-                    &vk::PipelineViewportStateCreateInfo::default()
-                        .viewport_count(1)
-                        .scissor_count(1),
-                )
-                .rasterization_state(
-                    &vk::PipelineRasterizationStateCreateInfo::default()
-                        .depth_clamp_enable(false)
-                        .rasterizer_discard_enable(false)
-                        .polygon_mode(vk::PolygonMode::FILL)
-                        .line_width(1.0f32)
-                        .cull_mode(vk::CullModeFlags::NONE)
-                        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-                        .depth_bias_enable(false)
-                        .depth_bias_constant_factor(0.0f32)
-                        .depth_bias_clamp(0.0f32)
-                        .depth_bias_slope_factor(0.0f32),
-                )
-                .multisample_state(
-                    &vk::PipelineMultisampleStateCreateInfo::default()
-                        .sample_shading_enable(false)
-                        .rasterization_samples(vk::SampleCountFlags::TYPE_1)
-                        .min_sample_shading(1.0f32)
-                        .alpha_to_coverage_enable(false)
-                        .alpha_to_one_enable(false),
-                )
-                .color_blend_state(
-                    &vk::PipelineColorBlendStateCreateInfo::default()
-                        .logic_op_enable(false)
-                        .logic_op(vk::LogicOp::COPY)
-                        .attachments(&[vk::PipelineColorBlendAttachmentState::default()
-                            .color_write_mask(
-                                vk::ColorComponentFlags::R
-                                    | vk::ColorComponentFlags::G
-                                    | vk::ColorComponentFlags::B
-                                    | vk::ColorComponentFlags::A,
-                            )
-                            .blend_enable(false)])
-                        .blend_constants([0.0, 0.0, 0.0, 0.0]),
-                )
-                .dynamic_state(
-                    &vk::PipelineDynamicStateCreateInfo::default()
-                        .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]),
-                )
-                .layout(*pipeline_layout)
-                .render_pass(render_pass)
-                .subpass(0)
-                .base_pipeline_handle(vk::Pipeline::null())
-                .base_pipeline_index(0)],
+            &[
+                pipeline_info.stages(&flat_stages),
+                pipeline_info.stages(&equirect_stages),
+            ],
             None,
         )
     } {
@@ -233,23 +408,6 @@ pub fn create_pass(
         }
     };
 
-    const MAX_FRAMES_IN_FLIGHT: u32 = 4;
-
-    // create desc pool
-    let desc_pool = Arc::new(VulkanDescriptorPoolHandle::new(
-        unsafe {
-            device.create_descriptor_pool(
-                &vk::DescriptorPoolCreateInfo::default()
-                    .pool_sizes(&[vk::DescriptorPoolSize::default()
-                        .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                        .descriptor_count(MAX_FRAMES_IN_FLIGHT)])
-                    .max_sets(MAX_FRAMES_IN_FLIGHT),
-                None,
-            )
-        }?,
-        device.clone(),
-    ));
-
     let sampler = VulkanSamplerHandle::new(
         unsafe {
             device.create_sampler(
@@ -275,18 +433,7 @@ pub fn create_pass(
         device.clone(),
     );
 
-    let desc_sets = Arc::new(DescriptorSetPool::new(
-        unsafe {
-            device.allocate_descriptor_sets(
-                &vk::DescriptorSetAllocateInfo::default()
-                    .descriptor_pool(**desc_pool)
-                    .set_layouts(&[*set_layout; MAX_FRAMES_IN_FLIGHT as usize]),
-            )
-        }?
-        .iter()
-        .map(|s| VulkanDescriptorSet::new(*s, desc_pool.clone(), device.clone()))
-        .collect(),
-    ));
+    let desc_sets = Arc::new(DescriptorSetPool::new(device.clone(), *set_layout)?);
 
     let command_pool = VulkanCommandPoolHandle::new(
         unsafe {
@@ -299,55 +446,78 @@ pub fn create_pass(
         }?,
         device.clone(),
     );
+    let command_buffers = Arc::new(CommandBufferRing {
+        device: device.clone(),
+        command_pool: Arc::new(command_pool),
+        buffers: Mutex::new(Vec::new()),
+    });
 
     Ok(PreprocessRenderPass {
         pipelines,
         pipeline_layout,
         shader_mod_vert: shader_vert,
         shader_mod_frag: shader_frag,
+        shader_mod_frag_equirect: shader_frag_equirect,
         desc_set_layout: set_layout,
         desc_sets,
         sampler,
         render_pass: VulkanRenderPassHandle::new(render_pass, device.clone()),
-        command_pool: Arc::new(command_pool),
+        command_buffers,
     })
 }
 
 /// Resources for HardwareBuffer blit that need to be kept alive until GPU completes
 #[allow(dead_code)]
 struct HardwareBufferBlitResources {
-    command_buffer: VulkanCommandBuffer,
+    command_buffer: CommandBufferGuard,
     pass: Arc<PreprocessRenderPass>,
-    src_view: VulkanImageViewHandle,
+    src_views: Vec<VulkanImageViewHandle>,
     fence: FenceGuard,
-    desc_set: DescriptorSetGuard,
+    desc_sets: Vec<DescriptorSetGuard>,
 }
 
 /// Blit source image to a HardwareBuffer-backed frame
 /// Returns a Future that completes when GPU work is done
-pub fn blit_to_hardware_buffer<R: unienc_common::Runtime + 'static>(
+pub fn blit_to_hardware_buffer(
     cx: &GlobalContext,
     src: &vk::Image,
     src_width: u32,
     src_height: u32,
     src_graphics_format: u32,
-    flip_vertically: bool,
-    is_gamma_workflow: bool,
+    options: BlitOptions,
     frame: &HardwareBufferFrame,
-    runtime: R,
-) -> Result<impl Future<Output = Result<()>> + use<R>> {
-    let markers = MARKERS.get();
-    let _guard = markers.map(|m| m.preprocess_blit.get());
-    let vulkan = &cx.vulkan;
+) -> Result<impl Future<Output = Result<()>> + use<>> {
+    let _guard = PREPROCESS_BLIT.begin();
     let device = &cx.device;
     let pass = &cx.render_pass;
 
-    let Some(desc_set) = pass.desc_sets.pop() else {
-        return Err(AndroidError::NoAvailableDescriptorSets);
+    let BlitOptions {
+        flip_vertically,
+        is_gamma_workflow,
+        stereo_mode,
+        projection,
+    } = options;
+
+    // XR eyes are layers of an array image, each drawn through its own view and descriptor set.
+    // A cubemap is drawn through a single cube view of all of its faces instead.
+    let slices = match projection {
+        Projection::Flat => stereo_mode.slices(),
+        Projection::Equirectangular => &[0],
+    };
+    let (view_type, layer_count) = match projection {
+        Projection::Flat => (vk::ImageViewType::TYPE_2D, 1),
+        Projection::Equirectangular => (vk::ImageViewType::CUBE, 6),
     };
+    let mut desc_sets = Vec::with_capacity(slices.len());
+    for _ in slices {
+        let Some(desc_set) = pass.desc_sets.pop()? else {
+            return Err(AndroidError::NoAvailableDescriptorSets);
+        };
+        desc_sets.push(desc_set);
+    }
 
-    let (src_view, queue, mut command_buffers, fence) = {
-        let _guard = markers.map(|m| m.preprocess_blit_resources.get());
+    let (src_views, queue, command_buffer, fence) = {
+        let _guard = PREPROCESS_BLIT_RESOURCES.begin();
 
         let format = *GRAPHICS_FORMAT_TO_VULKAN
             .get(src_graphics_format as usize)
@@ -373,76 +543,77 @@ pub fn blit_to_hardware_buffer<R: unienc_common::Runtime + 'static>(
             }
         };
 
-        let src_view = VulkanImageViewHandle::new(
-            unsafe {
-                device.create_image_view(
-                    &vk::ImageViewCreateInfo::default()
-                        .image(*src)
-                        .view_type(vk::ImageViewType::TYPE_2D)
-                        .format(view_format)
-                        .components(
-                            vk::ComponentMapping::default()
-                                .r(vk::ComponentSwizzle::IDENTITY)
-                                .g(vk::ComponentSwizzle::IDENTITY)
-                                .b(vk::ComponentSwizzle::IDENTITY)
-                                .a(vk::ComponentSwizzle::IDENTITY),
+        let src_views = slices
+            .iter()
+            .zip(&desc_sets)
+            .map(|(&slice, desc_set)| {
+                let src_view = VulkanImageViewHandle::new(
+                    unsafe {
+                        device.create_image_view(
+                            &vk::ImageViewCreateInfo::default()
+                                .image(*src)
+                                .view_type(view_type)
+                                .format(view_format)
+                                .components(
+                                    vk::ComponentMapping::default()
+                                        .r(vk::ComponentSwizzle::IDENTITY)
+                                        .g(vk::ComponentSwizzle::IDENTITY)
+                                        .b(vk::ComponentSwizzle::IDENTITY)
+                                        .a(vk::ComponentSwizzle::IDENTITY),
+                                )
+                                .subresource_range(
+                                    vk::ImageSubresourceRange::default()
+                                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                                        .base_mip_level(0)
+                                        .level_count(1)
+                                        .base_array_layer(slice)
+                                        .layer_count(layer_count),
+                                ),
+                            None,
                         )
-                        .subresource_range(
-                            vk::ImageSubresourceRange::default()
-                                .aspect_mask(vk::ImageAspectFlags::COLOR)
-                                .base_mip_level(0)
-                                .level_count(1)
-                                .base_array_layer(0)
-                                .layer_count(1),
-                        ),
-                    None,
-                )
-            }?,
-            device.clone(),
-        );
-
-        unsafe {
-            device.update_descriptor_sets(
-                &[vk::WriteDescriptorSet::default()
-                    .dst_set(**desc_set.get())
-                    .dst_binding(0)
-                    .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(&[vk::DescriptorImageInfo::default()
-                        .sampler(*pass.sampler)
-                        .image_view(*src_view)
-                        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)])],
-                &[],
-            )
-        };
-
-        let queue = vulkan.instance().graphics_queue();
-
-        let command_buffers = unsafe {
-            device.allocate_command_buffers(
-                &vk::CommandBufferAllocateInfo::default()
-                    .command_pool(**pass.command_pool)
-                    .level(vk::CommandBufferLevel::PRIMARY)
-                    .command_buffer_count(1),
-            )
-        }
-        .map(|v| {
-            v.iter()
-                .map(|c| VulkanCommandBuffer::new(pass.command_pool.clone(), *c, device.clone()))
-                .collect::<Vec<VulkanCommandBuffer>>()
-        })?;
-
+                    }?,
+                    device.clone(),
+                );
+
+                unsafe {
+                    device.update_descriptor_sets(
+                        &[vk::WriteDescriptorSet::default()
+                            .dst_set(**desc_set.get())
+                            .dst_binding(0)
+                            .dst_array_element(0)
+                            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                            .image_info(&[vk::DescriptorImageInfo::default()
+                                .sampler(*pass.sampler)
+                                .image_view(*src_view)
+                                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)])],
+                        &[],
+                    )
+                };
+
+                Ok(src_view)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let queue = cx.queue;
+
+        let command_buffer = pass.command_buffers.pop()?;
         let fence = cx.fence_pool.pop()?;
 
-        (src_view, queue, command_buffers, fence)
+        (src_views, queue, command_buffer, fence)
     };
 
-    let command_buffer = command_buffers.swap_remove(0);
     {
-        let _guard = markers.map(|m| m.preprocess_blit_commands.get());
-        let cb = &command_buffer.command_buffer;
+        let _guard = PREPROCESS_BLIT_COMMANDS.begin();
+        let cb = &command_buffer.get().command_buffer;
 
-        unsafe { device.begin_command_buffer(*cb, &vk::CommandBufferBeginInfo::default()) }?;
+        // implicitly resets the buffer recorded for a previous blit
+        unsafe {
+            device.begin_command_buffer(
+                *cb,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            )
+        }?;
 
         let width = frame.width;
         let height = frame.height;
@@ -488,64 +659,18 @@ pub fn blit_to_hardware_buffer<R: unienc_common::Runtime + 'static>(
             )
         };
 
-        // scale to fit
-        let pixel_scale = f32::min(
-            width as f32 / src_width as f32,
-            height as f32 / src_height as f32,
-        );
-        let render_scale_x = pixel_scale * src_width as f32 / width as f32;
-        let render_scale_y = pixel_scale * src_height as f32 / height as f32;
-
-        let push_constants_vert = if flip_vertically {
-            VertPushConstants {
-                scale_and_tiling: [1f32 / render_scale_x, -1f32 / render_scale_y, 0.0, 1.0],
-            }
-        } else {
-            VertPushConstants {
-                scale_and_tiling: [1f32 / render_scale_x, 1f32 / render_scale_y, 0.0, 0.0],
-            }
-        };
-
         unsafe {
-            device.cmd_bind_pipeline(*cb, vk::PipelineBindPoint::GRAPHICS, *pass.pipelines[0])
-        };
-
-        unsafe {
-            device.cmd_push_constants(
-                *cb,
-                *pass.pipeline_layout,
-                vk::ShaderStageFlags::VERTEX,
-                0,
-                std::slice::from_ref(&push_constants_vert)
-                    .align_to::<u8>()
-                    .1,
-            )
-        };
-
-        unsafe {
-            device.cmd_bind_descriptor_sets(
+            let pipeline = match projection {
+                Projection::Flat => PIPELINE_FLAT,
+                Projection::Equirectangular => PIPELINE_EQUIRECT,
+            };
+            device.cmd_bind_pipeline(
                 *cb,
                 vk::PipelineBindPoint::GRAPHICS,
-                *pass.pipeline_layout,
-                0,
-                &[**desc_set.get()],
-                &[],
+                *pass.pipelines[pipeline],
             )
         };
 
-        let viewport = vk::Viewport {
-            x: 0.0,
-            y: 0.0,
-            width: width as f32,
-            height: height as f32,
-            min_depth: 0.0,
-            max_depth: 1.0,
-        };
-
-        unsafe {
-            device.cmd_set_viewport(*cb, 0, &[viewport]);
-        }
-
         let scissor = vk::Rect2D {
             offset: vk::Offset2D { x: 0, y: 0 },
             extent: vk::Extent2D { width, height },
@@ -555,8 +680,75 @@ pub fn blit_to_hardware_buffer<R: unienc_common::Runtime + 'static>(
             device.cmd_set_scissor(*cb, 0, &[scissor]);
         }
 
-        unsafe {
-            device.cmd_draw(*cb, 3, 1, 0, 0);
+        // each eye gets an equal share of the frame width and is scaled to fit into it
+        let region_width = width as f32 / slices.len() as f32;
+        let region_height = height as f32;
+
+        for (i, desc_set) in desc_sets.iter().enumerate() {
+            let (render_scale_x, render_scale_y) = match projection {
+                Projection::Flat => {
+                    let pixel_scale = f32::min(
+                        region_width / src_width as f32,
+                        region_height / src_height as f32,
+                    );
+                    (
+                        pixel_scale * src_width as f32 / region_width,
+                        pixel_scale * src_height as f32 / region_height,
+                    )
+                }
+                // an unwrapped cubemap always covers the whole frame
+                Projection::Equirectangular => (1.0, 1.0),
+            };
+
+            let push_constants_vert = if flip_vertically {
+                VertPushConstants {
+                    scale_and_tiling: [1f32 / render_scale_x, -1f32 / render_scale_y, 0.0, 1.0],
+                }
+            } else {
+                VertPushConstants {
+                    scale_and_tiling: [1f32 / render_scale_x, 1f32 / render_scale_y, 0.0, 0.0],
+                }
+            };
+
+            unsafe {
+                device.cmd_push_constants(
+                    *cb,
+                    *pass.pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    std::slice::from_ref(&push_constants_vert)
+                        .align_to::<u8>()
+                        .1,
+                )
+            };
+
+            unsafe {
+                device.cmd_bind_descriptor_sets(
+                    *cb,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    *pass.pipeline_layout,
+                    0,
+                    &[**desc_set.get()],
+                    &[],
+                )
+            };
+
+            let viewport = vk::Viewport {
+                x: region_width * i as f32,
+                y: 0.0,
+                width: region_width,
+                height: region_height,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+
+            unsafe {
+                device.cmd_set_viewport(*cb, 0, &[viewport]);
+            }
+
+            unsafe {
+                device.cmd_draw(*cb, 3, 1, 0, 0);
+            }
         }
 
         unsafe {
@@ -593,7 +785,7 @@ pub fn blit_to_hardware_buffer<R: unienc_common::Runtime + 'static>(
         unsafe { device.end_command_buffer(*cb) }?;
 
         {
-            let _guard = markers.map(|m| m.preprocess_blit_submit.get());
+            let _guard = PREPROCESS_BLIT_SUBMIT.begin();
             unsafe {
                 device.queue_submit(
                     queue,
@@ -605,23 +797,18 @@ pub fn blit_to_hardware_buffer<R: unienc_common::Runtime + 'static>(
         }
     }
 
-    let device = device.clone();
     let resources = HardwareBufferBlitResources {
         command_buffer,
         pass: pass.clone(),
-        src_view,
+        src_views,
         fence,
-        desc_set,
+        desc_sets,
     };
 
-    let runtime = runtime.clone();
-    let join_handle = runtime.spawn_blocking(move || {
-        let _ = unsafe { device.wait_for_fences(&[**resources.fence.get()], true, u64::MAX) };
-        drop(resources);
-    });
+    let done = cx.blit_waiter.wait(resources)?;
 
     Ok(async move {
-        join_handle.await;
+        done.await?;
         Ok(())
     })
 }
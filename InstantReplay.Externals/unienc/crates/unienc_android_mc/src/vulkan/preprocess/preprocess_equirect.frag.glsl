@@ -0,0 +1,15 @@
+#version 450
+
+layout(binding = 0) uniform samplerCube _MainTex;
+layout(location = 0) in  vec2 vs_TEXCOORD0;
+layout(location = 0) out vec4 SV_Target0;
+
+void main()
+{
+    // u spans the longitude around the horizon, v the latitude from top to bottom
+    float longitude = (vs_TEXCOORD0.x - 0.5) * 6.28318530718;
+    float latitude = (0.5 - vs_TEXCOORD0.y) * 3.14159265359;
+    vec3 direction = vec3(cos(latitude) * sin(longitude), sin(latitude), cos(latitude) * cos(longitude));
+    SV_Target0 = texture(_MainTex, direction);
+    return;
+}
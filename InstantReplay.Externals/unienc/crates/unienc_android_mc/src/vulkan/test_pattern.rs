@@ -0,0 +1,266 @@
+//! Test pattern drawn into a Vulkan image of our own. The bars are written to a device buffer from
+//! the command buffer, as host-visible memory cannot be chosen without the physical device, which
+//! neither Unity nor `set_vulkan_device` hands over.
+
+use std::os::raw::c_void;
+use std::sync::Arc;
+
+use ash::vk;
+use unienc_common::TestPatternTexture;
+use unienc_common::test_pattern::color_bars;
+
+use crate::error::{AndroidError, Result};
+use crate::vulkan::CONTEXT;
+use crate::vulkan::types::{
+    VulkanBufferHandle, VulkanCommandPoolHandle, VulkanFenceHandle, VulkanImageHandle,
+    VulkanMemoryHandle,
+};
+
+/// Most bytes `vkCmdUpdateBuffer` writes at once, which a pattern of the test pattern size fits.
+const MAX_UPDATE_SIZE: u64 = 65536;
+
+const COLOR_RANGE: vk::ImageSubresourceRange = vk::ImageSubresourceRange {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    base_mip_level: 0,
+    level_count: 1,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+
+// fields are dropped in order, so the handles go before the memory bound to them
+pub(crate) struct VulkanTestPattern {
+    width: u32,
+    height: u32,
+    command_pool: VulkanCommandPoolHandle,
+    command_buffer: vk::CommandBuffer,
+    fence: VulkanFenceHandle,
+    image: VulkanImageHandle,
+    _image_memory: VulkanMemoryHandle,
+    buffer: VulkanBufferHandle,
+    _buffer_memory: VulkanMemoryHandle,
+    device: Arc<ash::Device>,
+    drawn: bool,
+}
+
+// the command buffer is only recorded under the lock of the pattern
+unsafe impl Send for VulkanTestPattern {}
+
+impl VulkanTestPattern {
+    pub(crate) fn new(width: u32, height: u32) -> Result<Self> {
+        let cx = CONTEXT
+            .get()
+            .ok_or(AndroidError::ContextNotInitialized)?
+            .lock()
+            .map_err(|_| AndroidError::MutexPoisoned)?;
+        let device = cx.device.clone();
+        let size = width as u64 * height as u64 * 4;
+        debug_assert!(size <= MAX_UPDATE_SIZE);
+
+        let image = VulkanImageHandle::new(
+            unsafe {
+                device.create_image(
+                    &vk::ImageCreateInfo::default()
+                        .image_type(vk::ImageType::TYPE_2D)
+                        .format(vk::Format::R8G8B8A8_UNORM)
+                        .extent(vk::Extent3D {
+                            width,
+                            height,
+                            depth: 1,
+                        })
+                        .mip_levels(1)
+                        .array_layers(1)
+                        .samples(vk::SampleCountFlags::TYPE_1)
+                        .tiling(vk::ImageTiling::OPTIMAL)
+                        .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+                        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                        .initial_layout(vk::ImageLayout::UNDEFINED),
+                    None,
+                )
+            }?,
+            device.clone(),
+        );
+        let image_memory = allocate(&device, unsafe {
+            device.get_image_memory_requirements(*image)
+        })?;
+        unsafe { device.bind_image_memory(*image, *image_memory, 0) }?;
+
+        let buffer = VulkanBufferHandle::new(
+            unsafe {
+                device.create_buffer(
+                    &vk::BufferCreateInfo::default()
+                        .size(size)
+                        .usage(
+                            vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST,
+                        )
+                        .sharing_mode(vk::SharingMode::EXCLUSIVE),
+                    None,
+                )
+            }?,
+            device.clone(),
+        );
+        let buffer_memory = allocate(&device, unsafe {
+            device.get_buffer_memory_requirements(*buffer)
+        })?;
+        unsafe { device.bind_buffer_memory(*buffer, *buffer_memory, 0) }?;
+
+        let command_pool = VulkanCommandPoolHandle::new(
+            unsafe {
+                device.create_command_pool(
+                    &vk::CommandPoolCreateInfo::default()
+                        .queue_family_index(cx.queue_family_index)
+                        .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+                    None,
+                )
+            }?,
+            device.clone(),
+        );
+        let command_buffer = unsafe {
+            device.allocate_command_buffers(
+                &vk::CommandBufferAllocateInfo::default()
+                    .command_pool(*command_pool)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(1),
+            )
+        }?[0];
+        let fence = VulkanFenceHandle::new(
+            unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) }?,
+            device.clone(),
+        );
+
+        Ok(Self {
+            width,
+            height,
+            command_pool,
+            command_buffer,
+            fence,
+            image,
+            _image_memory: image_memory,
+            buffer,
+            _buffer_memory: buffer_memory,
+            device,
+            drawn: false,
+        })
+    }
+
+    /// Writes `frame` into the image and waits for it, leaving the image ready to be sampled.
+    fn upload(&mut self, frame: u32) -> Result<()> {
+        // the queue is shared with the blits, which submit under the same lock
+        let cx = CONTEXT
+            .get()
+            .ok_or(AndroidError::ContextNotInitialized)?
+            .lock()
+            .map_err(|_| AndroidError::MutexPoisoned)?;
+        let device = &self.device;
+        let cb = self.command_buffer;
+        let pixels = color_bars(self.width, self.height, frame);
+        // blits of earlier frames may still be sampling the image
+        let old_layout = match self.drawn {
+            true => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            false => vk::ImageLayout::UNDEFINED,
+        };
+
+        unsafe {
+            // implicitly resets the buffer recorded for the previous frame
+            device.begin_command_buffer(
+                cb,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            )?;
+            device.cmd_update_buffer(cb, *self.buffer, 0, &pixels);
+            device.cmd_pipeline_barrier(
+                cb,
+                vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_READ)],
+                &[],
+                &[vk::ImageMemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::SHADER_READ)
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .old_layout(old_layout)
+                    .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(*self.image)
+                    .subresource_range(COLOR_RANGE)],
+            );
+            device.cmd_copy_buffer_to_image(
+                cb,
+                *self.buffer,
+                *self.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[vk::BufferImageCopy::default()
+                    .image_subresource(vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: 0,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    })
+                    .image_extent(vk::Extent3D {
+                        width: self.width,
+                        height: self.height,
+                        depth: 1,
+                    })],
+            );
+            device.cmd_pipeline_barrier(
+                cb,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[vk::ImageMemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                    .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(*self.image)
+                    .subresource_range(COLOR_RANGE)],
+            );
+            device.end_command_buffer(cb)?;
+
+            device.queue_submit(
+                cx.queue,
+                &[vk::SubmitInfo::default().command_buffers(&[cb])],
+                *self.fence,
+            )?;
+            device
+                .wait_for_fences(&[*self.fence], true, u64::MAX)
+                .map_err(AndroidError::FenceWaitFailed)?;
+            device.reset_fences(&[*self.fence])?;
+        }
+        self.drawn = true;
+        Ok(())
+    }
+}
+
+impl TestPatternTexture for VulkanTestPattern {
+    fn draw(&mut self, frame: u32) -> unienc_common::Result<*mut c_void> {
+        self.upload(frame)?;
+        // the blit takes a VkImage* like the one Unity passes
+        Ok(&*self.image as *const vk::Image as *mut c_void)
+    }
+}
+
+// any memory type the resource allows can be bound, and speed does not matter for a test
+fn allocate(
+    device: &Arc<ash::Device>,
+    requirements: vk::MemoryRequirements,
+) -> Result<VulkanMemoryHandle> {
+    if requirements.memory_type_bits == 0 {
+        return Err(AndroidError::NoSuitableMemoryType);
+    }
+    let memory = unsafe {
+        device.allocate_memory(
+            &vk::MemoryAllocateInfo::default()
+                .allocation_size(requirements.size)
+                .memory_type_index(requirements.memory_type_bits.trailing_zeros()),
+            None,
+        )
+    }?;
+    Ok(VulkanMemoryHandle::new(memory, device.clone()))
+}
@@ -1,8 +1,89 @@
 use crate::error::{AndroidError, Result};
 use crate::vulkan::types::{VulkanFenceHandle, VulkanShaderModuleHandle};
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
+/// Descriptor sets and fences are held by each blit until the GPU completes it, so the pools
+/// bound the number of frames in flight. They grow on demand up to these limits.
+pub(crate) static DESCRIPTOR_SETS: PoolUsage = PoolUsage::new(16);
+pub(crate) static FENCES: PoolUsage = PoolUsage::new(16);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VulkanPoolStats {
+    pub descriptor_sets: u32,
+    pub descriptor_set_limit: u32,
+    /// Blits rejected because every descriptor set was in flight and the limit was reached.
+    pub descriptor_set_starvations: u64,
+    pub fences: u32,
+    pub fence_limit: u32,
+    pub fence_starvations: u64,
+}
+
+/// Changes the limits of the preprocess pools. Takes effect on the next allocation; pools already
+/// above a lowered limit are not shrunk.
+pub fn set_vulkan_pool_limits(max_descriptor_sets: u32, max_fences: u32) {
+    DESCRIPTOR_SETS
+        .limit
+        .store(max_descriptor_sets.max(1), Ordering::Relaxed);
+    FENCES.limit.store(max_fences.max(1), Ordering::Relaxed);
+}
+
+pub fn vulkan_pool_stats() -> VulkanPoolStats {
+    VulkanPoolStats {
+        descriptor_sets: DESCRIPTOR_SETS.allocated.load(Ordering::Relaxed),
+        descriptor_set_limit: DESCRIPTOR_SETS.limit.load(Ordering::Relaxed),
+        descriptor_set_starvations: DESCRIPTOR_SETS.starvations.load(Ordering::Relaxed),
+        fences: FENCES.allocated.load(Ordering::Relaxed),
+        fence_limit: FENCES.limit.load(Ordering::Relaxed),
+        fence_starvations: FENCES.starvations.load(Ordering::Relaxed),
+    }
+}
+
+pub(crate) struct PoolUsage {
+    allocated: AtomicU32,
+    limit: AtomicU32,
+    starvations: AtomicU64,
+}
+
+impl PoolUsage {
+    const fn new(limit: u32) -> Self {
+        Self {
+            allocated: AtomicU32::new(0),
+            limit: AtomicU32::new(limit),
+            starvations: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts up to `count` new items within the limit and returns how many may be created.
+    /// Counts a starvation when none may.
+    pub fn reserve(&self, count: u32) -> u32 {
+        let limit = self.limit.load(Ordering::Relaxed);
+        let reserved =
+            self.allocated
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |allocated| {
+                    (allocated < limit).then(|| allocated + count.min(limit - allocated))
+                });
+        match reserved {
+            Ok(allocated) => count.min(limit - allocated),
+            Err(_) => {
+                self.starvations.fetch_add(1, Ordering::Relaxed);
+                0
+            }
+        }
+    }
+
+    /// Counts items created regardless of the limit.
+    pub fn add(&self, count: u32) {
+        self.allocated.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Returns a reservation whose items failed to be created.
+    pub fn release(&self, count: u32) {
+        self.allocated.fetch_sub(count, Ordering::Relaxed);
+    }
+}
+
 pub fn create_shader_module(
     device: &Arc<ash::Device>,
     code: &'static [u8],
@@ -58,15 +139,17 @@ impl FencePool {
                 pool: self.clone(),
             })
         } else {
-            println!("Creating new fence");
+            if FENCES.reserve(1) == 0 {
+                return Err(AndroidError::NoAvailableFences);
+            }
+            unienc_common::log!("Creating new fence");
             let fence_info = ash::vk::FenceCreateInfo::default();
+            let fence = unsafe { self.device.create_fence(&fence_info, None) }.map_err(|e| {
+                FENCES.release(1);
+                AndroidError::Vulkan(e)
+            })?;
             Ok(FenceGuard {
-                fence: VulkanFenceHandle::new(
-                    unsafe { self.device.create_fence(&fence_info, None) }
-                        .map_err(AndroidError::Vulkan)?,
-                    self.device.clone(),
-                )
-                .into(),
+                fence: VulkanFenceHandle::new(fence, self.device.clone()).into(),
                 pool: self.clone(),
             })
         }
@@ -0,0 +1,222 @@
+//! Khronos validation of the Vulkan instance Unity creates, for debug builds with the
+//! `vulkan-validation` feature. Unity creates its instance before any plugin can see it, so the
+//! instance creation is intercepted at plugin load to enable the layer and register a messenger
+//! that logs what it reports. The layer must be packaged in the APK or enabled as a GPU debug
+//! layer of the app; without it the instance is created as usual.
+
+use std::ffi::{CStr, c_char, c_void};
+use std::sync::{Mutex, OnceLock};
+
+use ash::vk;
+use unity_native_plugin::vulkan::UnityGraphicsVulkanV2;
+
+const VALIDATION_LAYER: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+type InitCallback = unsafe extern "system" fn(
+    Option<vk::PFN_vkGetInstanceProcAddr>,
+    *mut c_void,
+) -> Option<vk::PFN_vkGetInstanceProcAddr>;
+
+static GET_INSTANCE_PROC_ADDR: OnceLock<vk::PFN_vkGetInstanceProcAddr> = OnceLock::new();
+static MESSENGER: Mutex<Option<Messenger>> = Mutex::new(None);
+
+struct Messenger {
+    instance: vk::Instance,
+    debug_utils: ash::ext::debug_utils::Instance,
+    handle: vk::DebugUtilsMessengerEXT,
+}
+
+/// Intercepts the creation of Unity's instance. Must be called from `UnityPluginLoad`, before the
+/// device is initialized.
+pub(crate) fn intercept_initialization(vulkan: &UnityGraphicsVulkanV2) {
+    let callback: InitCallback = on_initialization;
+    let intercepted = unsafe {
+        vulkan.intercept_initialization(std::mem::transmute(callback), std::ptr::null_mut())
+    };
+    if !intercepted {
+        unienc_common::log!("unienc: Vulkan initialization was not intercepted; no validation");
+    }
+}
+
+unsafe extern "system" fn on_initialization(
+    get_instance_proc_addr: Option<vk::PFN_vkGetInstanceProcAddr>,
+    _user_data: *mut c_void,
+) -> Option<vk::PFN_vkGetInstanceProcAddr> {
+    let original = get_instance_proc_addr?;
+    let _ = GET_INSTANCE_PROC_ADDR.set(original);
+    Some(intercepted_get_instance_proc_addr)
+}
+
+unsafe extern "system" fn intercepted_get_instance_proc_addr(
+    instance: vk::Instance,
+    name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    let &original = GET_INSTANCE_PROC_ADDR.get()?;
+    unsafe {
+        match CStr::from_ptr(name).to_bytes() {
+            b"vkGetInstanceProcAddr" => Some(std::mem::transmute::<
+                vk::PFN_vkGetInstanceProcAddr,
+                unsafe extern "system" fn(),
+            >(intercepted_get_instance_proc_addr)),
+            b"vkCreateInstance" => Some(std::mem::transmute::<
+                vk::PFN_vkCreateInstance,
+                unsafe extern "system" fn(),
+            >(create_instance)),
+            b"vkDestroyInstance" => Some(std::mem::transmute::<
+                vk::PFN_vkDestroyInstance,
+                unsafe extern "system" fn(),
+            >(destroy_instance)),
+            _ => original(instance, name),
+        }
+    }
+}
+
+unsafe extern "system" fn create_instance(
+    create_info: *const vk::InstanceCreateInfo<'_>,
+    allocator: *const vk::AllocationCallbacks<'_>,
+    instance: *mut vk::Instance,
+) -> vk::Result {
+    let Some(&original) = GET_INSTANCE_PROC_ADDR.get() else {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    };
+    let entry = unsafe {
+        ash::Entry::from_static_fn(ash::StaticFn {
+            get_instance_proc_addr: original,
+        })
+    };
+    let create = entry.fp_v1_0().create_instance;
+    let info = unsafe { &*create_info };
+
+    let has_layer = unsafe { entry.enumerate_instance_layer_properties() }
+        .unwrap_or_default()
+        .iter()
+        .any(|layer| layer.layer_name_as_c_str() == Ok(VALIDATION_LAYER));
+    if !has_layer {
+        unienc_common::log!(
+            "unienc: {} is not available; Vulkan validation is off",
+            VALIDATION_LAYER.to_string_lossy()
+        );
+        return unsafe { create(create_info, allocator, instance) };
+    }
+    // the layer provides the extension even where the loader does not
+    let has_debug_utils =
+        unsafe { entry.enumerate_instance_extension_properties(Some(VALIDATION_LAYER)) }
+            .unwrap_or_default()
+            .iter()
+            .any(|extension| {
+                extension.extension_name_as_c_str() == Ok(ash::ext::debug_utils::NAME)
+            });
+
+    let mut layers = unsafe { names(info.pp_enabled_layer_names, info.enabled_layer_count) };
+    if !layers
+        .iter()
+        .any(|&name| unsafe { CStr::from_ptr(name) } == VALIDATION_LAYER)
+    {
+        layers.push(VALIDATION_LAYER.as_ptr());
+    }
+    let mut extensions = unsafe {
+        names(
+            info.pp_enabled_extension_names,
+            info.enabled_extension_count,
+        )
+    };
+    if has_debug_utils
+        && !extensions
+            .iter()
+            .any(|&name| unsafe { CStr::from_ptr(name) } == ash::ext::debug_utils::NAME)
+    {
+        extensions.push(ash::ext::debug_utils::NAME.as_ptr());
+    }
+    let mut validated = *info;
+    validated.enabled_layer_count = layers.len() as u32;
+    validated.pp_enabled_layer_names = layers.as_ptr();
+    validated.enabled_extension_count = extensions.len() as u32;
+    validated.pp_enabled_extension_names = extensions.as_ptr();
+
+    let result = unsafe { create(&validated, allocator, instance) };
+    if result != vk::Result::SUCCESS {
+        unienc_common::log!(
+            "unienc: creating the Vulkan instance with validation failed ({result:?}); retrying without it"
+        );
+        return unsafe { create(create_info, allocator, instance) };
+    }
+    unienc_common::log!("unienc: Vulkan validation is on");
+
+    if has_debug_utils {
+        let instance = unsafe { *instance };
+        let loaded = unsafe { ash::Instance::load(entry.static_fn(), instance) };
+        let debug_utils = ash::ext::debug_utils::Instance::new(&entry, &loaded);
+        let messenger_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
+            .message_severity(
+                vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            )
+            .message_type(
+                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                    | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                    | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            )
+            .pfn_user_callback(Some(on_message));
+        match unsafe { debug_utils.create_debug_utils_messenger(&messenger_info, None) } {
+            Ok(handle) => {
+                *MESSENGER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Messenger {
+                    instance,
+                    debug_utils,
+                    handle,
+                });
+            }
+            Err(e) => unienc_common::log!("unienc: failed to create the debug messenger: {e:?}"),
+        }
+    }
+    result
+}
+
+unsafe extern "system" fn destroy_instance(
+    instance: vk::Instance,
+    allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    let mut messenger = MESSENGER.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(m) = messenger.take_if(|m| m.instance == instance) {
+        unsafe { m.debug_utils.destroy_debug_utils_messenger(m.handle, None) };
+    }
+    drop(messenger);
+
+    let Some(&original) = GET_INSTANCE_PROC_ADDR.get() else {
+        return;
+    };
+    if let Some(destroy) = unsafe { original(instance, c"vkDestroyInstance".as_ptr()) } {
+        let destroy: vk::PFN_vkDestroyInstance = unsafe { std::mem::transmute(destroy) };
+        unsafe { destroy(instance, allocator) };
+    }
+}
+
+unsafe extern "system" fn on_message(
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    types: vk::DebugUtilsMessageTypeFlagsEXT,
+    data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    _user_data: *mut c_void,
+) -> vk::Bool32 {
+    let to_str = |ptr: *const c_char| {
+        if ptr.is_null() {
+            "".into()
+        } else {
+            unsafe { CStr::from_ptr(ptr) }.to_string_lossy()
+        }
+    };
+    if let Some(data) = unsafe { data.as_ref() } {
+        unienc_common::log!(
+            "Vulkan {severity:?} {types:?} [{}]: {}",
+            to_str(data.p_message_id_name),
+            to_str(data.p_message)
+        );
+    }
+    // the call that triggered the message is not aborted
+    vk::FALSE
+}
+
+unsafe fn names(names: *const *const c_char, count: u32) -> Vec<*const c_char> {
+    if names.is_null() || count == 0 {
+        return Vec::new();
+    }
+    unsafe { std::slice::from_raw_parts(names, count as usize) }.to_vec()
+}
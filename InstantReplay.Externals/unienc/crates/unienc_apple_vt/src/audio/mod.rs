@@ -14,10 +14,13 @@ use objc2_core_audio_types::{
     kAudioFormatLinearPCM, kAudioFormatMPEG4AAC,
 };
 use tokio::sync::mpsc;
+use unienc_common::profiler::ProfilerMarker;
 use unienc_common::{
     AudioSample, EncodedData, Encoder, EncoderInput, EncoderOutput, UniencSampleKind,
 };
 
+static CONVERT: ProfilerMarker = ProfilerMarker::new("unienc_apple_vt::audio::convert");
+
 pub struct AudioToolboxEncoder {
     input: AudioToolboxEncoderInput,
     output: AudioToolboxEncoderOutput,
@@ -105,11 +108,14 @@ impl EncoderInput for AudioToolboxEncoderInput {
         let mut sample = Some(&data);
 
         while {
-            let num_output_packets = self.converter.fill_complex_buffer(
-                &mut sample,
-                &mut output_buffer_data,
-                &mut packet_descs,
-            )?;
+            let num_output_packets = {
+                let _guard = CONVERT.begin();
+                self.converter.fill_complex_buffer(
+                    &mut sample,
+                    &mut output_buffer_data,
+                    &mut packet_descs,
+                )?
+            };
 
             let magic_cookie = self
                 .converter
@@ -224,6 +230,10 @@ impl EncodedData for AudioPacket {
         UniencSampleKind::Key
     }
 
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
     fn set_timestamp(&mut self, timestamp: f64) {
         self.timestamp_in_samples = (timestamp * self.sample_rate as f64) as u64;
     }
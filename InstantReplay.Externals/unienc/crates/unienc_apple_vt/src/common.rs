@@ -1,6 +1,9 @@
-use std::{fmt::Debug, ops::Deref};
+use std::{fmt::Debug, ops::Deref, sync::Mutex};
 
+use block2::RcBlock;
 use objc2::{Message, rc::Retained};
+use objc2_foundation::NSError;
+use tokio::sync::oneshot;
 
 pub struct UnsafeSendRetained<T> {
     pub inner: Retained<T>,
@@ -41,3 +44,23 @@ impl<T> From<Retained<T>> for UnsafeSendRetained<T> {
         Self { inner }
     }
 }
+
+type CompletionResult = Option<UnsafeSendRetained<NSError>>;
+
+/// Block for `completionHandler:` arguments taking only an `NSError *`, resolving the receiver with
+/// the reported error, if any.
+pub(crate) fn completion_handler() -> (
+    RcBlock<dyn Fn(*mut NSError)>,
+    oneshot::Receiver<CompletionResult>,
+) {
+    let (tx, rx) = oneshot::channel();
+    let tx = Mutex::new(Some(tx));
+    let block = RcBlock::new(move |error: *mut NSError| {
+        if let Ok(mut tx) = tx.lock()
+            && let Some(tx) = tx.take()
+        {
+            _ = tx.send(unsafe { Retained::retain(error) }.map(UnsafeSendRetained::from));
+        }
+    });
+    (block, rx)
+}
@@ -0,0 +1,179 @@
+use std::path::{Path, PathBuf};
+
+use objc2::rc::{Retained, autoreleasepool};
+use objc2::runtime::AnyObject;
+use objc2_av_foundation::{
+    AVAssetReader, AVAssetReaderOutput, AVAssetReaderStatus, AVAssetReaderTrackOutput,
+    AVMediaTypeVideo, AVURLAsset,
+};
+use objc2_core_foundation::CFString;
+use objc2_core_media::{CMTime, CMTimeRange, kCMTimePositiveInfinity};
+use objc2_core_video::{
+    CVPixelBufferGetBaseAddress, CVPixelBufferGetBytesPerRow, CVPixelBufferGetHeight,
+    CVPixelBufferGetWidth, CVPixelBufferLockBaseAddress, CVPixelBufferLockFlags,
+    CVPixelBufferUnlockBaseAddress, kCVPixelBufferPixelFormatTypeKey, kCVPixelFormatType_32BGRA,
+};
+use objc2_foundation::{NSDictionary, NSNumber, NSString, NSURL};
+use tokio::sync::mpsc;
+use unienc_common::{
+    DecodedVideoFrame, Decoder, PreciseSeek, SeekMode, VideoFrameBgra32, buffer::SharedBuffer,
+};
+
+use crate::common::UnsafeSendRetained;
+use crate::error::{AppleError, OsStatusExt, Result};
+
+/// Decodes the first video track with AVAssetReader.
+pub struct AVFDecoder {
+    input_path: PathBuf,
+    rx: mpsc::Receiver<Result<DecodedVideoFrame>>,
+}
+
+impl AVFDecoder {
+    pub fn new(input_path: &Path) -> Result<Self> {
+        Self::open(input_path, None)
+    }
+
+    // AVAssetReader cannot reposition once started, so seeking opens a new reader whose time range
+    // starts at the target. The reader decodes from the preceding keyframe internally, so both seek
+    // modes are precise.
+    fn open(input_path: &Path, start_time: Option<f64>) -> Result<Self> {
+        let url =
+            NSURL::fileURLWithPath(&NSString::from_str(input_path.to_string_lossy().as_ref()));
+        let asset = unsafe { AVURLAsset::URLAssetWithURL_options(&url, None) };
+
+        // the synchronous track accessor blocks until the tracks are loaded, which is quick for a
+        // local file
+        #[allow(deprecated)]
+        let track = unsafe { asset.tracksWithMediaType(AVMediaTypeVideo.unwrap()) }
+            .firstObject()
+            .ok_or(AppleError::Other("No video track found".to_string()))?;
+
+        let reader = unsafe { AVAssetReader::assetReaderWithAsset_error(&asset)? };
+
+        let format_key: &NSString =
+            unsafe { &*(kCVPixelBufferPixelFormatTypeKey as *const CFString).cast() };
+        let format_value = NSNumber::new_u32(kCVPixelFormatType_32BGRA);
+        let output_settings = NSDictionary::<NSString, AnyObject>::from_slices(
+            &[format_key],
+            &[format_value.as_ref()],
+        );
+
+        let output = unsafe {
+            AVAssetReaderTrackOutput::assetReaderTrackOutputWithTrack_outputSettings(
+                &track,
+                Some(&output_settings),
+            )
+        };
+        unsafe {
+            output.setAlwaysCopiesSampleData(false);
+            reader.addOutput(&output);
+            if let Some(start_time) = start_time {
+                reader.setTimeRange(CMTimeRange {
+                    start: CMTime::with_seconds(start_time.max(0.0), 600),
+                    duration: kCMTimePositiveInfinity,
+                });
+            }
+        }
+
+        if !unsafe { reader.startReading() } {
+            return Err(AppleError::Other(
+                unsafe { reader.error() }
+                    .map(|e| e.to_string())
+                    .unwrap_or_else(|| "Failed to start reading".to_string()),
+            ));
+        }
+
+        // copyNextSampleBuffer blocks while decoding, so samples are read on a dedicated thread
+        let (tx, rx) = mpsc::channel(4);
+        let reader = UnsafeSendRetained::from(reader);
+        let output = UnsafeSendRetained::from(Retained::into_super(output));
+        std::thread::spawn(move || {
+            let frames = std::iter::from_fn(|| {
+                autoreleasepool(|_| read_frame(&reader, &output)).transpose()
+            });
+            for frame in PreciseSeek::new(frames, start_time) {
+                let failed = frame.is_err();
+                if tx.blocking_send(frame).is_err() || failed {
+                    break;
+                }
+            }
+            unsafe { reader.cancelReading() };
+        });
+
+        Ok(Self {
+            input_path: input_path.to_owned(),
+            rx,
+        })
+    }
+}
+
+fn read_frame(
+    reader: &AVAssetReader,
+    output: &AVAssetReaderOutput,
+) -> Result<Option<DecodedVideoFrame>> {
+    loop {
+        let Some(sample_buffer) = (unsafe { output.copyNextSampleBuffer() }) else {
+            if unsafe { reader.status() } == AVAssetReaderStatus::Failed {
+                return Err(AppleError::Other(
+                    unsafe { reader.error() }
+                        .map(|e| e.to_string())
+                        .unwrap_or_else(|| "Failed to read sample".to_string()),
+                ));
+            }
+            return Ok(None);
+        };
+
+        // sample buffers without an image (e.g. markers) are skipped
+        let Some(pixel_buffer) = (unsafe { sample_buffer.image_buffer() }) else {
+            continue;
+        };
+        let timestamp = unsafe { sample_buffer.presentation_time_stamp().seconds() };
+
+        let width = CVPixelBufferGetWidth(&pixel_buffer);
+        let height = CVPixelBufferGetHeight(&pixel_buffer);
+        let row_size = width * 4;
+        let mut data = vec![0u8; row_size * height];
+
+        unsafe {
+            CVPixelBufferLockBaseAddress(&pixel_buffer, CVPixelBufferLockFlags::ReadOnly)
+                .to_result()?;
+            let base = CVPixelBufferGetBaseAddress(&pixel_buffer) as *const u8;
+            let bytes_per_row = CVPixelBufferGetBytesPerRow(&pixel_buffer);
+            for (y, row) in data.chunks_exact_mut(row_size).enumerate() {
+                row.copy_from_slice(std::slice::from_raw_parts(
+                    base.add(y * bytes_per_row),
+                    row_size,
+                ));
+            }
+            CVPixelBufferUnlockBaseAddress(&pixel_buffer, CVPixelBufferLockFlags::ReadOnly)
+                .to_result()?;
+        }
+
+        return Ok(Some(DecodedVideoFrame {
+            frame: VideoFrameBgra32::packed(
+                SharedBuffer::new_unmanaged(data),
+                width as u32,
+                height as u32,
+            ),
+            timestamp,
+        }));
+    }
+}
+
+impl Decoder for AVFDecoder {
+    async fn pull(&mut self) -> unienc_common::Result<Option<DecodedVideoFrame>> {
+        match self.rx.recv().await {
+            Some(frame) => Ok(Some(frame?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn seek(
+        &mut self,
+        timestamp: f64,
+        _mode: SeekMode,
+    ) -> unienc_common::Result<Option<DecodedVideoFrame>> {
+        *self = Self::open(&self.input_path, Some(timestamp))?;
+        self.pull().await
+    }
+}
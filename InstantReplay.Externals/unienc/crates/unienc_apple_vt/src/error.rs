@@ -48,6 +48,9 @@ pub enum AppleError {
     #[error("CVPixelBuffer is null")]
     PixelBufferNull,
 
+    #[error("Too many frames in flight to allocate a pixel buffer")]
+    PixelBufferPoolExhausted,
+
     #[error("Failed to send blit future")]
     BlitFutureSendFailed,
 
@@ -75,6 +78,30 @@ pub enum AppleError {
     #[error("Failed to get MTLTexture from CVMetalTexture")]
     MetalTextureGetFailed,
 
+    #[error("Failed to create a texture view of an XR eye slice")]
+    MetalTextureViewCreationFailed,
+
+    // ImageIO related errors
+    #[error("Failed to encode still image")]
+    ImageDestinationFailed,
+
+    // ReplayKit related errors
+    #[error("ReplayKit screen recording is not available")]
+    ReplayKitUnavailable,
+
+    #[error("ReplayKit capture failed: {0}")]
+    ReplayKitCaptureFailed(String),
+
+    // ScreenCaptureKit related errors
+    #[error("ScreenCaptureKit is not available (requires macOS 12.3)")]
+    ScreenCaptureUnavailable,
+
+    #[error("No window or display to capture")]
+    ScreenCaptureTargetNotFound,
+
+    #[error("Screen capture failed: {0}")]
+    ScreenCaptureFailed(String),
+
     // Channel related errors
     #[error("Failed to send to channel")]
     ChannelSendFailed,
@@ -111,12 +138,17 @@ impl CategorizedError for AppleError {
         match self {
             // Platform errors (OSStatus)
             AppleError::OsStatus(_) => ErrorCategory::Platform,
+            AppleError::ReplayKitCaptureFailed(_) => ErrorCategory::Platform,
+            AppleError::ScreenCaptureFailed(_) => ErrorCategory::Platform,
 
             // Initialization errors
             AppleError::MetalNotInitialized => ErrorCategory::Initialization,
             AppleError::GlobalStateSetFailed => ErrorCategory::Initialization,
             AppleError::MetalTextureCacheCreationFailed => ErrorCategory::Initialization,
             AppleError::AudioConverterCreationFailed => ErrorCategory::Initialization,
+            AppleError::ReplayKitUnavailable => ErrorCategory::Initialization,
+            AppleError::ScreenCaptureUnavailable => ErrorCategory::Initialization,
+            AppleError::ScreenCaptureTargetNotFound => ErrorCategory::Configuration,
 
             // Resource allocation errors
             AppleError::MetalTextureRetainFailed => ErrorCategory::ResourceAllocation,
@@ -128,8 +160,13 @@ impl CategorizedError for AppleError {
             AppleError::CompressionSessionNull => ErrorCategory::ResourceAllocation,
             AppleError::NonNullCreationFailed => ErrorCategory::ResourceAllocation,
             AppleError::PixelBufferNull => ErrorCategory::ResourceAllocation,
+            AppleError::PixelBufferPoolExhausted => ErrorCategory::ResourceAllocation,
             AppleError::MetalTextureNull => ErrorCategory::ResourceAllocation,
             AppleError::MetalTextureGetFailed => ErrorCategory::ResourceAllocation,
+            AppleError::MetalTextureViewCreationFailed => ErrorCategory::ResourceAllocation,
+
+            // Encoding errors
+            AppleError::ImageDestinationFailed => ErrorCategory::Encoding,
 
             // Communication errors
             AppleError::BlitFutureSendFailed => ErrorCategory::Communication,
@@ -3,31 +3,60 @@ compile_error!("This crate can only be compiled for Apple platforms.");
 
 use std::{ffi::c_void, path::Path};
 
-use objc2::{rc::Retained, runtime::ProtocolObject};
+use objc2::runtime::ProtocolObject;
 use objc2_metal::MTLTexture;
-use unienc_common::{EncodingSystem, TryFromUnityNativeTexturePointer};
+use unienc_common::{
+    DeviceClass, DiagnosticCheck, DownmixedAudioEncoder, DownmixedAudioOptions,
+    EncoderCapabilities, EncodingSystem, PaddedMuxer, PaddedVideoEncoder, PaddedVideoOptions,
+    ProbeOptions, StillImageFormat, TryFromUnityNativeTexturePointer, VideoCodec,
+    VideoEncoderOptions,
+};
 
+#[cfg(feature = "blit")]
+use crate::still_image::ImageIOStillImageCapture;
 use crate::{
-    audio::AudioToolboxEncoder, common::UnsafeSendRetained, mux::AVFMuxer,
+    audio::AudioToolboxEncoder,
+    common::UnsafeSendRetained,
+    decode::AVFDecoder,
+    mux::AVFMuxer,
+    passthrough::{AudioToolboxAacPacketizer, VideoToolboxH264Packetizer},
     video::VideoToolboxEncoder,
 };
 mod allocator;
 pub mod audio;
 mod common;
+pub mod decode;
 pub mod error;
+#[cfg(feature = "blit")]
 mod metal;
 pub mod mux;
+pub mod passthrough;
+#[cfg(target_os = "ios")]
+pub mod replay_kit;
+#[cfg(target_os = "macos")]
+pub mod screen_capture;
+#[cfg(feature = "blit")]
+pub mod still_image;
 pub mod video;
 
 pub use error::{AppleError, OsStatusExt, Result};
+#[cfg(feature = "blit")]
+pub use metal::{capture_next_blit, set_metal_device};
+
+/// Still images are captured through the Metal blit.
+#[cfg(feature = "blit")]
+type PlatformStillImageCapture = ImageIOStillImageCapture;
+#[cfg(not(feature = "blit"))]
+type PlatformStillImageCapture =
+    unienc_common::still_image::UnsupportedStillImageCapture<MetalTexture>;
 
 pub struct VideoToolboxEncodingSystem<
     V: unienc_common::VideoEncoderOptions,
     A: unienc_common::AudioEncoderOptions,
     R: unienc_common::Runtime + 'static,
 > {
-    video_options: V,
-    audio_options: A,
+    video_options: PaddedVideoOptions<V>,
+    audio_options: DownmixedAudioOptions<A>,
     runtime: R,
 }
 
@@ -40,38 +69,151 @@ impl<
     type VideoEncoderOptionsType = V;
     type AudioEncoderOptionsType = A;
 
-    type VideoEncoderType = VideoToolboxEncoder;
+    type VideoEncoderType = PaddedVideoEncoder<VideoToolboxEncoder>;
 
-    type AudioEncoderType = AudioToolboxEncoder;
+    type AudioEncoderType = DownmixedAudioEncoder<AudioToolboxEncoder>;
 
-    type MuxerType = mux::AVFMuxer;
+    type MuxerType = PaddedMuxer<mux::AVFMuxer>;
 
     type BlitSourceType = MetalTexture;
     type RuntimeType = R;
+    type H264PacketizerType = VideoToolboxH264Packetizer;
+    type AacPacketizerType = AudioToolboxAacPacketizer;
+    type DecoderType = AVFDecoder;
+    type StillImageCaptureType = PlatformStillImageCapture;
 
     fn new(video_options: &V, audio_options: &A, runtime: R) -> Self {
         Self {
-            video_options: *video_options,
-            audio_options: *audio_options,
+            video_options: PaddedVideoOptions::new(*video_options),
+            audio_options: DownmixedAudioOptions::stereo(*audio_options),
             runtime,
         }
     }
 
     fn new_video_encoder(&self) -> unienc_common::Result<Self::VideoEncoderType> {
-        VideoToolboxEncoder::new(&self.video_options).map_err(|e| e.into())
+        let codec = self.video_options.codec();
+        if !self.is_codec_supported(codec) {
+            return Err(unienc_common::CommonError::CodecNotSupported(codec));
+        }
+        VideoToolboxEncoder::new(&self.video_options)
+            .map_err(|e| e.into())
+            .map(PaddedVideoEncoder::new)
     }
 
     fn new_audio_encoder(&self) -> unienc_common::Result<Self::AudioEncoderType> {
-        AudioToolboxEncoder::new(&self.audio_options).map_err(|e| e.into())
+        AudioToolboxEncoder::new(&self.audio_options)
+            .map_err(|e| e.into())
+            .map(|encoder| DownmixedAudioEncoder::new(encoder, &self.audio_options))
     }
 
     fn new_muxer(&self, output_path: &Path) -> unienc_common::Result<Self::MuxerType> {
-        AVFMuxer::new(output_path, &self.video_options, &self.audio_options).map_err(|e| e.into())
+        AVFMuxer::new(output_path, &self.video_options, &self.audio_options)
+            .map_err(|e| e.into())
+            .map(|muxer| PaddedMuxer::new(muxer, output_path, &self.video_options))
+    }
+
+    fn new_h264_packetizer(&self) -> unienc_common::Result<Self::H264PacketizerType> {
+        Ok(VideoToolboxH264Packetizer::default())
+    }
+
+    fn new_aac_packetizer(&self) -> unienc_common::Result<Self::AacPacketizerType> {
+        AudioToolboxAacPacketizer::new(&self.audio_options)
     }
 
+    fn new_decoder(&self, input_path: &Path) -> unienc_common::Result<Self::DecoderType> {
+        AVFDecoder::new(input_path).map_err(|e| e.into())
+    }
+
+    fn new_still_image_capture(
+        &self,
+        format: StillImageFormat,
+    ) -> unienc_common::Result<Self::StillImageCaptureType> {
+        #[cfg(feature = "blit")]
+        return Ok(ImageIOStillImageCapture::new(
+            self.video_options.inner(),
+            format,
+        ));
+        #[cfg(not(feature = "blit"))]
+        {
+            let _ = format;
+            Err(unienc_common::CommonError::BlitNotSupported)
+        }
+    }
+
+    fn shutdown(self) -> impl Future<Output = unienc_common::Result<()>> + Send {
+        // components spawn no tasks
+        std::future::ready(Ok(()))
+    }
+
+    #[cfg(feature = "blit")]
     fn is_blit_supported(&self) -> bool {
         metal::is_initialized()
     }
+
+    #[cfg(feature = "blit")]
+    fn new_test_pattern(&self) -> unienc_common::Result<unienc_common::TestPattern> {
+        let options = self.video_options.inner();
+        let (width, height) =
+            unienc_common::test_pattern::test_pattern_size(options.width(), options.height());
+        let texture = metal::MetalTestPattern::new(width, height)?;
+        Ok(unienc_common::TestPattern::new(texture, width, height))
+    }
+
+    fn is_alpha_supported(&self) -> bool {
+        true
+    }
+
+    fn is_codec_supported(&self, codec: VideoCodec) -> bool {
+        match codec {
+            VideoCodec::H264 => true,
+            // iOS devices have no ProRes encoder
+            VideoCodec::ProRes => cfg!(target_os = "macos"),
+            VideoCodec::DnxHr => false,
+        }
+    }
+
+    fn self_test() -> Vec<DiagnosticCheck> {
+        #[cfg(feature = "blit")]
+        let graphics = Some(if metal::is_initialized() {
+            DiagnosticCheck::passed("metal", "Unity graphics device is Metal")
+        } else {
+            DiagnosticCheck::failed(
+                "metal",
+                "Unity graphics interface was not received; frames are read back from the CPU",
+            )
+        });
+        #[cfg(not(feature = "blit"))]
+        let graphics = None;
+        graphics
+            .into_iter()
+            .chain([
+                DiagnosticCheck::from_result(
+                    "h264_encoder",
+                    VideoToolboxEncoder::new(&ProbeOptions),
+                    "VideoToolbox H.264 session created",
+                    "Check that VideoToolbox.framework is linked",
+                ),
+                DiagnosticCheck::from_result(
+                    "aac_encoder",
+                    AudioToolboxEncoder::new(&ProbeOptions),
+                    "AudioToolbox AAC converter created",
+                    "Check that AudioToolbox.framework is linked",
+                ),
+            ])
+            .collect()
+    }
+
+    fn encoder_capabilities(_width: u32, _height: u32) -> EncoderCapabilities {
+        match cfg!(target_os = "macos") {
+            true => EncoderCapabilities::default(),
+            // every iOS device encodes H.264 in hardware
+            false => EncoderCapabilities {
+                device_class: DeviceClass::Mobile,
+                hardware_accelerated: Some(true),
+                ..Default::default()
+            },
+        }
+    }
 }
 
 impl<
@@ -81,7 +223,10 @@ impl<
 > unienc_common::unity::UnityPlugin for VideoToolboxEncodingSystem<V, A, R>
 {
     fn unity_plugin_load(interfaces: &unity_native_plugin::interface::UnityInterfaces) {
+        #[cfg(feature = "blit")]
         metal::unity_plugin_load(interfaces);
+        #[cfg(not(feature = "blit"))]
+        let _ = interfaces;
     }
     fn unity_plugin_unload() {}
 }
@@ -90,15 +235,24 @@ pub struct MetalTexture {
     pub texture: UnsafeSendRetained<ProtocolObject<dyn MTLTexture>>,
 }
 
+#[cfg(feature = "blit")]
 impl TryFromUnityNativeTexturePointer for MetalTexture {
     fn try_from_unity_native_texture_ptr(ptr: *mut c_void) -> unienc_common::Result<Self> {
         metal::is_initialized()
             .then_some(())
             .ok_or(AppleError::MetalNotInitialized)?;
-        let retained = unsafe { Retained::<ProtocolObject<dyn MTLTexture>>::retain(ptr as *mut _) }
-            .ok_or(AppleError::MetalTextureRetainFailed)?;
+        let retained =
+            unsafe { objc2::rc::Retained::<ProtocolObject<dyn MTLTexture>>::retain(ptr as *mut _) }
+                .ok_or(AppleError::MetalTextureRetainFailed)?;
         Ok(MetalTexture {
             texture: UnsafeSendRetained { inner: retained },
         })
     }
 }
+
+#[cfg(not(feature = "blit"))]
+impl TryFromUnityNativeTexturePointer for MetalTexture {
+    fn try_from_unity_native_texture_ptr(_ptr: *mut c_void) -> unienc_common::Result<Self> {
+        Err(unienc_common::CommonError::BlitNotSupported)
+    }
+}
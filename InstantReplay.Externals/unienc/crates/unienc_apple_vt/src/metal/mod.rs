@@ -1,41 +1,46 @@
+use crate::MetalTexture;
 use crate::allocator;
 use crate::error::{AppleError, OsStatusExt, Result};
 use block2::RcBlock;
-use objc2::{rc::Retained, runtime::ProtocolObject};
+use objc2::{Message, rc::Retained, runtime::ProtocolObject};
 use objc2_core_foundation::{CFDictionary, CFNumber, CFString, CFType, kCFBooleanTrue};
 use objc2_core_video::{
     CVMetalTexture, CVMetalTextureCache, CVMetalTextureGetTexture, CVPixelBuffer,
     CVPixelBufferPool, kCVPixelBufferHeightKey, kCVPixelBufferMetalCompatibilityKey,
     kCVPixelBufferPixelFormatTypeKey, kCVPixelBufferPoolAllocationThresholdKey,
     kCVPixelBufferPoolMaximumBufferAgeKey, kCVPixelBufferPoolMinimumBufferCountKey,
-    kCVPixelBufferWidthKey, kCVPixelFormatType_32BGRA,
+    kCVPixelBufferWidthKey, kCVPixelFormatType_32BGRA, kCVReturnWouldExceedAllocationThreshold,
 };
-use objc2_foundation::NSString;
+use objc2_foundation::{NSRange, NSString, NSURL, ns_string};
 use objc2_metal::{
-    MTLBuffer, MTLCommandBuffer, MTLCommandEncoder, MTLCommandQueue, MTLCullMode, MTLDevice,
-    MTLIndexType, MTLLibrary, MTLPixelFormat, MTLPrimitiveType, MTLRenderCommandEncoder,
-    MTLRenderPassDescriptor, MTLRenderPipelineColorAttachmentDescriptor,
-    MTLRenderPipelineDescriptor, MTLRenderPipelineState, MTLResourceOptions, MTLSamplerAddressMode,
-    MTLSamplerDescriptor, MTLSamplerMinMagFilter, MTLSamplerMipFilter, MTLSamplerState, MTLTexture,
+    MTLBuffer, MTLCaptureDescriptor, MTLCaptureDestination, MTLCaptureManager, MTLCommandBuffer,
+    MTLCommandEncoder, MTLCommandQueue, MTLCreateSystemDefaultDevice, MTLCullMode, MTLDevice,
+    MTLIndexType, MTLLibrary, MTLOrigin, MTLPixelFormat, MTLPrimitiveType, MTLRegion,
+    MTLRenderCommandEncoder, MTLRenderPassDescriptor, MTLRenderPipelineColorAttachmentDescriptor,
+    MTLRenderPipelineDescriptor, MTLRenderPipelineState, MTLResource, MTLResourceOptions,
+    MTLSamplerAddressMode, MTLSamplerDescriptor, MTLSamplerMinMagFilter, MTLSamplerMipFilter,
+    MTLSamplerState, MTLSize, MTLTexture, MTLTextureDescriptor, MTLTextureType, MTLTextureUsage,
     MTLVertexAttributeDescriptor, MTLVertexBufferLayoutDescriptor, MTLVertexDescriptor,
-    MTLVertexFormat, MTLVertexStepFunction,
+    MTLVertexFormat, MTLVertexStepFunction, MTLViewport,
 };
-use std::os::raw::c_int;
+use std::os::raw::{c_int, c_void};
 use std::{
     cell::{Cell, RefCell},
     future::Future,
+    path::PathBuf,
     ptr::NonNull,
     sync::{Arc, Mutex, OnceLock},
 };
 use tokio::sync::oneshot;
-use unity_native_plugin::profiler::IUnityProfiler;
+use unienc_common::profiler::ProfilerMarker;
+use unienc_common::test_pattern::color_bars;
+use unienc_common::{
+    BlitOptions, CommonError, GraphicsEventIssuer, Projection, StereoMode, TestPatternTexture,
+    TryFromUnityNativeTexturePointer,
+};
 use unity_native_plugin::{
     graphics::{GfxDeviceEventType, IUnityGraphics, UnityGraphics},
     metal::{UnityGraphicsMetalV1Interface, UnityGraphicsMetalV2, UnityGraphicsMetalV2Interface},
-    profiler::{
-        BuiltinProfilerCategory, ProfilerCategoryId, ProfilerMarkerDesc, ProfilerMarkerEventType,
-        ProfilerMarkerFlag, ProfilerMarkerFlags, UnityProfiler,
-    },
 };
 
 use crate::common::UnsafeSendRetained;
@@ -43,62 +48,110 @@ use crate::common::UnsafeSendRetained;
 static GRAPHICS: OnceLock<Mutex<UnityGraphics>> = OnceLock::new();
 static CONTEXT: OnceLock<Mutex<GlobalContext>> = OnceLock::new();
 pub static EVENT_ID: OnceLock<c_int> = OnceLock::new();
-static MARKERS: OnceLock<Markers> = OnceLock::new();
-static PROFILER: OnceLock<UnityProfiler> = OnceLock::new();
+static CAPTURE_REQUEST: Mutex<Option<CaptureRequest>> = Mutex::new(None);
+
+static CUSTOM_BLIT: ProfilerMarker = ProfilerMarker::new("unienc_apple_vt::metal::custom_blit");
+static CUSTOM_BLIT_RESOURCES: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::resources");
+static CUSTOM_BLIT_RESOURCES_SHARED_TEXTURE: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::resources::shared_texture");
+static CUSTOM_BLIT_RESOURCES_PIXEL_BUFFER_CREATE: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::resources::pixel_buffer_create");
+static CUSTOM_BLIT_RESOURCES_METAL_TEXTURE_CREATE: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::resources::metal_texture_create");
+static CUSTOM_BLIT_RESOURCES_COMMIT_UNITY: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::resources::commit_unity");
+static CUSTOM_BLIT_RESOURCES_COMMAND_BUFFER: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::resources::command_buffer");
+static CUSTOM_BLIT_COMMANDS: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::commands");
+static CUSTOM_BLIT_COMMANDS_ENCODER_CREATE: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::commands::encoder_create");
+static CUSTOM_BLIT_COMMANDS_VERT_UNIFORMS: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::commands::vert_uniforms");
+static CUSTOM_BLIT_COMMANDS_RECORD: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::commands::record");
+static CUSTOM_BLIT_COMMANDS_END_ENCODING: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::commands::end_encoding");
+static CUSTOM_BLIT_COMMANDS_COMPLETION_HANDLER: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::commands::completion_handler");
+static CUSTOM_BLIT_SUBMIT: ProfilerMarker =
+    ProfilerMarker::new("unienc_apple_vt::metal::custom_blit::submit");
 
-#[derive(Debug)]
-struct Markers {
-    custom_blit: ProfilerMarkerDesc,
-    custom_blit_resources: ProfilerMarkerDesc,
-    custom_blit_resources_shared_texture: ProfilerMarkerDesc,
-    custom_blit_resources_pixel_buffer_create: ProfilerMarkerDesc,
-    custom_blit_resources_metal_texture_create: ProfilerMarkerDesc,
-    custom_blit_resources_commit_unity: ProfilerMarkerDesc,
-    custom_blit_resources_command_buffer: ProfilerMarkerDesc,
-    custom_blit_commands: ProfilerMarkerDesc,
-    custom_blit_commands_encoder_create: ProfilerMarkerDesc,
-    custom_blit_commands_vert_uniforms: ProfilerMarkerDesc,
-    custom_blit_commands_record: ProfilerMarkerDesc,
-    custom_blit_commands_end_encoding: ProfilerMarkerDesc,
-    custom_blit_commands_completion_handler: ProfilerMarkerDesc,
-    custom_blit_submit: ProfilerMarkerDesc,
+pub(crate) fn is_initialized() -> bool {
+    CONTEXT.get().is_some()
 }
 
-unsafe impl Sync for Markers {}
-
-struct MarkerGuard<'a> {
-    marker: &'a ProfilerMarkerDesc,
+struct CaptureRequest {
+    output: Option<PathBuf>,
 }
 
-trait ProfilerMarkerDescExt {
-    fn get(&'_ self) -> MarkerGuard<'_>;
+/// Captures the GPU work of the next blit, from a command buffer labelled "unienc blit" with debug
+/// groups around its draws. The capture opens in Xcode, or is written as a `.gputrace` document to
+/// `output`, which needs `MetalCaptureEnabled` in the app's Info.plist. Run with Metal API
+/// validation to have its messages name the same labels.
+pub fn capture_next_blit(output: Option<PathBuf>) {
+    *CAPTURE_REQUEST.lock().unwrap_or_else(|e| e.into_inner()) = Some(CaptureRequest { output });
 }
 
-impl ProfilerMarkerDescExt for ProfilerMarkerDesc {
-    fn get(&'_ self) -> MarkerGuard<'_> {
-        if let Some(profiler) = PROFILER.get() {
-            profiler.emit_event(self, ProfilerMarkerEventType::Begin, &[]);
+/// A GPU capture of the command queue, stopped when dropped.
+struct GpuCapture(Retained<MTLCaptureManager>);
+
+impl GpuCapture {
+    fn start(
+        command_queue: &ProtocolObject<dyn MTLCommandQueue>,
+        request: CaptureRequest,
+    ) -> Option<Self> {
+        let manager = MTLCaptureManager::sharedCaptureManager();
+        let descriptor = MTLCaptureDescriptor::new();
+        unsafe { descriptor.setCaptureObject(Some(command_queue.as_ref())) };
+        let destination = match &request.output {
+            Some(output) => {
+                let url = NSURL::fileURLWithPath(&NSString::from_str(&output.to_string_lossy()));
+                descriptor.setOutputURL(Some(&url));
+                MTLCaptureDestination::GPUTraceDocument
+            }
+            None => MTLCaptureDestination::DeveloperTools,
+        };
+        descriptor.setDestination(destination);
+        if !manager.supportsDestination(destination) {
+            unienc_common::log!("unienc: GPU capture to {destination:?} is not supported");
+            return None;
+        }
+        match manager.startCaptureWithDescriptor_error(&descriptor) {
+            Ok(()) => Some(Self(manager)),
+            Err(e) => {
+                unienc_common::log!(
+                    "unienc: failed to start GPU capture: {}",
+                    e.localizedDescription()
+                );
+                None
+            }
         }
-        MarkerGuard { marker: self }
     }
 }
 
-impl<'a> Drop for MarkerGuard<'a> {
+impl Drop for GpuCapture {
     fn drop(&mut self) {
-        if let Some(profiler) = PROFILER.get() {
-            profiler.emit_event(self.marker, ProfilerMarkerEventType::End, &[]);
-        }
+        self.0.stopCapture();
     }
 }
 
-pub(crate) fn is_initialized() -> bool {
-    CONTEXT.get().is_some()
+/// Where blits are submitted.
+enum CommandQueue {
+    /// Unity's queue, after the work Unity recorded so far.
+    Unity(UnityGraphicsMetalV2),
+    /// The queue of a host that does not load the plugin from Unity.
+    Host(UnsafeSendRetained<ProtocolObject<dyn MTLCommandQueue>>),
 }
 
 struct GlobalContext {
-    metal: UnityGraphicsMetalV2,
+    queue: CommandQueue,
     pipeline_state: Retained<ProtocolObject<dyn MTLRenderPipelineState>>,
     pipeline_state_srgb: Retained<ProtocolObject<dyn MTLRenderPipelineState>>,
+    // unwraps a cubemap source to an equirectangular frame
+    equirect_pipeline_state: Retained<ProtocolObject<dyn MTLRenderPipelineState>>,
+    equirect_pipeline_state_srgb: Retained<ProtocolObject<dyn MTLRenderPipelineState>>,
     vertices: UnsafeSendRetained<ProtocolObject<dyn MTLBuffer>>,
     indices: UnsafeSendRetained<ProtocolObject<dyn MTLBuffer>>,
     // The blit's sampler is invariant across frames; build once and reuse.
@@ -117,10 +170,15 @@ struct GlobalContext {
     // CVPixelBuffer + CVMetalTexture creation per-frame is the dominant
     // cost of custom_blit. A CVPixelBufferPool lets us recycle buffers,
     // which in turn lets CVMetalTextureCache return pre-mapped textures
-    // for cache hits. Lazy-initialized; recreated when dst dimensions change.
-    pixel_buffer_pool: Option<PixelBufferPoolEntry>,
+    // for cache hits. Lazy-initialized per dst size, so encoders of different
+    // sizes recording at once do not recreate each other's pools every frame.
+    // Most recently used first.
+    pixel_buffer_pools: Vec<PixelBufferPoolEntry>,
 }
 
+/// Sizes whose pixel buffer pools are kept; older ones are released.
+const MAX_PIXEL_BUFFER_POOLS: usize = 4;
+
 struct PixelBufferPoolEntry {
     width: u32,
     height: u32,
@@ -128,128 +186,10 @@ struct PixelBufferPoolEntry {
 }
 
 pub(crate) fn unity_plugin_load(interfaces: &unity_native_plugin::interface::UnityInterfaces) {
-    println!("unienc: unity_plugin_load");
+    unienc_common::log!("unienc: unity_plugin_load");
     let graphics = interfaces.interface::<UnityGraphics>().unwrap();
 
-    if let Some(profiler) = interfaces.interface::<UnityProfiler>()
-        && profiler.is_available()
-    {
-        _ = PROFILER.set(profiler);
-        _ = MARKERS.set(Markers {
-            custom_blit: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_resources: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::resources",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_resources_shared_texture: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::resources::shared_texture",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_resources_pixel_buffer_create: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::resources::pixel_buffer_create",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_resources_metal_texture_create: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::resources::metal_texture_create",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_resources_commit_unity: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::resources::commit_unity",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_resources_command_buffer: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::resources::command_buffer",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_commands: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::commands",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_commands_encoder_create: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::commands::encoder_create",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_commands_vert_uniforms: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::commands::vert_uniforms",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_commands_record: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::commands::record",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_commands_end_encoding: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::commands::end_encoding",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_commands_completion_handler: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::commands::completion_handler",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-            custom_blit_submit: profiler
-                .create_marker(
-                    c"unienc_apple_vt::metal::custom_blit::submit",
-                    BuiltinProfilerCategory::Other as ProfilerCategoryId,
-                    ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
-                    0,
-                )
-                .unwrap(),
-        });
-    }
+    unienc_common::profiler::unity_plugin_load(interfaces);
 
     GRAPHICS
         .set(Mutex::new(graphics))
@@ -268,7 +208,7 @@ struct VertexUniforms {
 }
 
 extern "system" fn on_device_event(ev_type: GfxDeviceEventType) {
-    println!("unienc: on_device_event {ev_type:?}");
+    unienc_common::log!("unienc: on_device_event {ev_type:?}");
     match ev_type {
         unity_native_plugin::graphics::GfxDeviceEventType::Initialize => {
             let graphics = GRAPHICS.get().unwrap().lock().unwrap();
@@ -283,15 +223,29 @@ extern "system" fn on_device_event(ev_type: GfxDeviceEventType) {
                 let event_id = graphics.reserve_event_id_range(1);
 
                 EVENT_ID.set(event_id).unwrap();
-                println!("unienc: reserved event id {event_id}");
+                unienc_common::log!("unienc: reserved event id {event_id}");
 
                 let interfaces = unity_native_plugin::interface::UnityInterfaces::get();
                 let metal = interfaces.interface::<UnityGraphicsMetalV2>().unwrap();
                 let device = metal.metal_device().unwrap();
-                let library = device
-                    .newLibraryWithSource_options_error(
-                        &NSString::from_str(
-                            "
+                let context = create_context(&device, CommandQueue::Unity(metal));
+                CONTEXT
+                    .set(Mutex::new(context))
+                    .map_err(|_e| AppleError::GlobalStateSetFailed)
+                    .unwrap();
+            }
+        }
+        unity_native_plugin::graphics::GfxDeviceEventType::Shutdown => {}
+        unity_native_plugin::graphics::GfxDeviceEventType::BeforeReset => {}
+        unity_native_plugin::graphics::GfxDeviceEventType::AfterReset => {}
+    }
+}
+
+fn create_context(device: &ProtocolObject<dyn MTLDevice>, queue: CommandQueue) -> GlobalContext {
+    let library = device
+        .newLibraryWithSource_options_error(
+            &NSString::from_str(
+                "
 #include <metal_stdlib>
 using namespace metal;
 
@@ -314,7 +268,7 @@ struct FShaderOutput
 };
 
 vertex VertexOut vertex_main(const VertexIn in [[stage_in]],
-                             constant VertexUniforms &uniforms [[buffer(1)]])
+                 constant VertexUniforms &uniforms [[buffer(1)]])
 {
     VertexOut out;
     out.position = in.position;
@@ -324,183 +278,382 @@ vertex VertexOut vertex_main(const VertexIn in [[stage_in]],
 }
 
 fragment FShaderOutput fragment_main(VertexOut in [[stage_in]],
-                             texture2d<half> mainTex [[texture(0)]],
-                             sampler mainSampler [[sampler(0)]])
+                 texture2d<half> mainTex [[texture(0)]],
+                 sampler mainSampler [[sampler(0)]])
 {
     bool isInside = all(in.uv >= 0.0h) && all(in.uv <= 1.0h);
     FShaderOutput out = { isInside ? mainTex.sample(mainSampler, in.uv) : half4(0.0h) };
     return out;
 }
 
-                                ",
-                        ),
-                        None,
-                    )
-                    .unwrap();
+fragment FShaderOutput fragment_equirect(VertexOut in [[stage_in]],
+                 texturecube<half> mainTex [[texture(0)]],
+                 sampler mainSampler [[sampler(0)]])
+{
+    // u spans the longitude around the horizon, v the latitude from top to bottom
+    float longitude = (in.uv.x - 0.5) * 2.0 * M_PI_F;
+    float latitude = (0.5 - in.uv.y) * M_PI_F;
+    float3 direction = float3(cos(latitude) * sin(longitude),
+                  sin(latitude),
+                  cos(latitude) * cos(longitude));
+    FShaderOutput out = { mainTex.sample(mainSampler, direction) };
+    return out;
+}
 
-                // single triangle for full screen blit
-                // pos.x pos.y pox.z pos.w uv.x uv.y
-                let vertices = &[
-                    -1.0f32, -1.0, 0.0, 1.0, 0.0, 1.0, // bottom left
-                    3.0, -1.0, 0.0, 1.0, 2.0, 1.0, // bottom right
-                    -1.0, 3.0, 0.0, 1.0, 0.0, -1.0, // top left
-                ];
-                let indices = &[0u16, 1, 2];
-                let vertices = unsafe {
-                    device.newBufferWithBytes_length_options(
-                        NonNull::new(vertices.as_ptr() as *mut _).unwrap(),
-                        vertices.len() * std::mem::size_of::<f32>(),
-                        MTLResourceOptions::CPUCacheModeWriteCombined,
-                    )
-                }
-                .unwrap();
-                let indices = unsafe {
-                    device.newBufferWithBytes_length_options(
-                        NonNull::new(indices.as_ptr() as *mut _).unwrap(),
-                        indices.len() * std::mem::size_of::<u16>(),
-                        MTLResourceOptions::CPUCacheModeWriteCombined,
-                    )
-                }
-                .unwrap();
+                    ",
+            ),
+            None,
+        )
+        .unwrap();
 
-                let pos_desc = MTLVertexAttributeDescriptor::new();
-                pos_desc.setFormat(MTLVertexFormat::Float4);
-                unsafe { pos_desc.setOffset(0) };
+    // single triangle for full screen blit
+    // pos.x pos.y pox.z pos.w uv.x uv.y
+    let vertices = &[
+        -1.0f32, -1.0, 0.0, 1.0, 0.0, 1.0, // bottom left
+        3.0, -1.0, 0.0, 1.0, 2.0, 1.0, // bottom right
+        -1.0, 3.0, 0.0, 1.0, 0.0, -1.0, // top left
+    ];
+    let indices = &[0u16, 1, 2];
+    let vertices = unsafe {
+        device.newBufferWithBytes_length_options(
+            NonNull::new(vertices.as_ptr() as *mut _).unwrap(),
+            vertices.len() * std::mem::size_of::<f32>(),
+            MTLResourceOptions::CPUCacheModeWriteCombined,
+        )
+    }
+    .unwrap();
+    let indices = unsafe {
+        device.newBufferWithBytes_length_options(
+            NonNull::new(indices.as_ptr() as *mut _).unwrap(),
+            indices.len() * std::mem::size_of::<u16>(),
+            MTLResourceOptions::CPUCacheModeWriteCombined,
+        )
+    }
+    .unwrap();
 
-                let uv_desc = MTLVertexAttributeDescriptor::new();
-                uv_desc.setFormat(MTLVertexFormat::Float2);
-                unsafe { uv_desc.setOffset(16) };
+    let pos_desc = MTLVertexAttributeDescriptor::new();
+    pos_desc.setFormat(MTLVertexFormat::Float4);
+    unsafe { pos_desc.setOffset(0) };
 
-                let vert_desc = MTLVertexDescriptor::new();
-                unsafe {
-                    vert_desc
-                        .attributes()
-                        .setObject_atIndexedSubscript(Some(&pos_desc), 0)
-                };
-                unsafe {
-                    vert_desc
-                        .attributes()
-                        .setObject_atIndexedSubscript(Some(&uv_desc), 1)
-                };
+    let uv_desc = MTLVertexAttributeDescriptor::new();
+    uv_desc.setFormat(MTLVertexFormat::Float2);
+    unsafe { uv_desc.setOffset(16) };
 
-                let layout_desc = MTLVertexBufferLayoutDescriptor::new();
-                unsafe { layout_desc.setStride(24) };
-                layout_desc.setStepFunction(MTLVertexStepFunction::PerVertex);
-                unsafe { layout_desc.setStepRate(1) };
-                unsafe {
-                    vert_desc
-                        .layouts()
-                        .setObject_atIndexedSubscript(Some(&layout_desc), 0)
-                };
+    let vert_desc = MTLVertexDescriptor::new();
+    unsafe {
+        vert_desc
+            .attributes()
+            .setObject_atIndexedSubscript(Some(&pos_desc), 0)
+    };
+    unsafe {
+        vert_desc
+            .attributes()
+            .setObject_atIndexedSubscript(Some(&uv_desc), 1)
+    };
 
-                let color_desc = MTLRenderPipelineColorAttachmentDescriptor::new();
-                color_desc.setPixelFormat(MTLPixelFormat::BGRA8Unorm);
-
-                let color_desc_srgb = MTLRenderPipelineColorAttachmentDescriptor::new();
-                color_desc_srgb.setPixelFormat(MTLPixelFormat::BGRA8Unorm_sRGB);
-
-                let pipeline_state_desc = MTLRenderPipelineDescriptor::new();
-                pipeline_state_desc.setLabel(Some(&NSString::from_str("unienc blit")));
-                pipeline_state_desc.setVertexFunction(Some(
-                    &library
-                        .newFunctionWithName(&NSString::from_str("vertex_main"))
-                        .unwrap(),
-                ));
-                pipeline_state_desc.setFragmentFunction(Some(
-                    &library
-                        .newFunctionWithName(&NSString::from_str("fragment_main"))
-                        .unwrap(),
-                ));
-                unsafe {
-                    pipeline_state_desc
-                        .colorAttachments()
-                        .setObject_atIndexedSubscript(Some(&color_desc), 0)
-                };
-                pipeline_state_desc.setVertexDescriptor(Some(&vert_desc));
+    let layout_desc = MTLVertexBufferLayoutDescriptor::new();
+    unsafe { layout_desc.setStride(24) };
+    layout_desc.setStepFunction(MTLVertexStepFunction::PerVertex);
+    unsafe { layout_desc.setStepRate(1) };
+    unsafe {
+        vert_desc
+            .layouts()
+            .setObject_atIndexedSubscript(Some(&layout_desc), 0)
+    };
 
-                let pipeline_state = device
-                    .newRenderPipelineStateWithDescriptor_error(&pipeline_state_desc)
-                    .unwrap();
+    let color_desc = MTLRenderPipelineColorAttachmentDescriptor::new();
+    color_desc.setPixelFormat(MTLPixelFormat::BGRA8Unorm);
+
+    let color_desc_srgb = MTLRenderPipelineColorAttachmentDescriptor::new();
+    color_desc_srgb.setPixelFormat(MTLPixelFormat::BGRA8Unorm_sRGB);
+
+    let pipeline_state_desc = MTLRenderPipelineDescriptor::new();
+    pipeline_state_desc.setLabel(Some(&NSString::from_str("unienc blit")));
+    pipeline_state_desc.setVertexFunction(Some(
+        &library
+            .newFunctionWithName(&NSString::from_str("vertex_main"))
+            .unwrap(),
+    ));
+    pipeline_state_desc.setFragmentFunction(Some(
+        &library
+            .newFunctionWithName(&NSString::from_str("fragment_main"))
+            .unwrap(),
+    ));
+    unsafe {
+        pipeline_state_desc
+            .colorAttachments()
+            .setObject_atIndexedSubscript(Some(&color_desc), 0)
+    };
+    pipeline_state_desc.setVertexDescriptor(Some(&vert_desc));
 
-                unsafe {
-                    pipeline_state_desc
-                        .colorAttachments()
-                        .setObject_atIndexedSubscript(Some(&color_desc_srgb), 0)
-                };
+    let pipeline_state = device
+        .newRenderPipelineStateWithDescriptor_error(&pipeline_state_desc)
+        .unwrap();
 
-                let pipeline_state_srgb = device
-                    .newRenderPipelineStateWithDescriptor_error(&pipeline_state_desc)
-                    .unwrap();
+    unsafe {
+        pipeline_state_desc
+            .colorAttachments()
+            .setObject_atIndexedSubscript(Some(&color_desc_srgb), 0)
+    };
 
-                let mut cache: *mut CVMetalTextureCache = std::ptr::null_mut();
-                unsafe {
-                    CVMetalTextureCache::create(
-                        allocator::default(),
-                        None,
-                        &device,
-                        None,
-                        NonNull::new(&mut cache).unwrap(),
-                    )
-                    .to_result()
-                    .unwrap()
-                };
-                let texture_cache = unsafe { Retained::from_raw(cache) }
-                    .expect("CVMetalTextureCache::create returned null");
-
-                let sampler_desc = MTLSamplerDescriptor::new();
-                sampler_desc.setSAddressMode(MTLSamplerAddressMode::ClampToEdge);
-                sampler_desc.setTAddressMode(MTLSamplerAddressMode::ClampToEdge);
-                sampler_desc.setMinFilter(MTLSamplerMinMagFilter::Linear);
-                sampler_desc.setMagFilter(MTLSamplerMinMagFilter::Linear);
-                sampler_desc.setMipFilter(MTLSamplerMipFilter::NotMipmapped);
-                let sampler_state = device
-                    .newSamplerStateWithDescriptor(&sampler_desc)
-                    .expect("MTLSamplerState creation failed");
-
-                let render_pass_descriptor = MTLRenderPassDescriptor::new();
-                {
-                    // Accessing colorAttachments[0] lazily allocates the
-                    // attachment owned by the descriptor; configure it once.
-                    let color_attachment = unsafe {
-                        render_pass_descriptor
-                            .colorAttachments()
-                            .objectAtIndexedSubscript(0)
-                    };
-                    color_attachment.setLoadAction(objc2_metal::MTLLoadAction::DontCare);
-                    color_attachment.setStoreAction(objc2_metal::MTLStoreAction::Store);
-                }
+    let pipeline_state_srgb = device
+        .newRenderPipelineStateWithDescriptor_error(&pipeline_state_desc)
+        .unwrap();
 
-                CONTEXT
-                    .set(Mutex::new(GlobalContext {
-                        metal,
-                        pipeline_state,
-                        pipeline_state_srgb,
-                        vertices: vertices.into(),
-                        indices: indices.into(),
-                        sampler_state,
-                        render_pass_descriptor: render_pass_descriptor.into(),
-                        texture_cache: texture_cache.into(),
-                        pixel_buffer_pool: None,
-                    }))
-                    .map_err(|_e| AppleError::GlobalStateSetFailed)
-                    .unwrap();
-            }
+    pipeline_state_desc.setLabel(Some(&NSString::from_str("unienc equirect blit")));
+    pipeline_state_desc.setFragmentFunction(Some(
+        &library
+            .newFunctionWithName(&NSString::from_str("fragment_equirect"))
+            .unwrap(),
+    ));
+
+    let equirect_pipeline_state_srgb = device
+        .newRenderPipelineStateWithDescriptor_error(&pipeline_state_desc)
+        .unwrap();
+
+    unsafe {
+        pipeline_state_desc
+            .colorAttachments()
+            .setObject_atIndexedSubscript(Some(&color_desc), 0)
+    };
+
+    let equirect_pipeline_state = device
+        .newRenderPipelineStateWithDescriptor_error(&pipeline_state_desc)
+        .unwrap();
+
+    let mut cache: *mut CVMetalTextureCache = std::ptr::null_mut();
+    unsafe {
+        CVMetalTextureCache::create(
+            allocator::default(),
+            None,
+            &device,
+            None,
+            NonNull::new(&mut cache).unwrap(),
+        )
+        .to_result()
+        .unwrap()
+    };
+    let texture_cache =
+        unsafe { Retained::from_raw(cache) }.expect("CVMetalTextureCache::create returned null");
+
+    let sampler_desc = MTLSamplerDescriptor::new();
+    sampler_desc.setSAddressMode(MTLSamplerAddressMode::ClampToEdge);
+    sampler_desc.setTAddressMode(MTLSamplerAddressMode::ClampToEdge);
+    sampler_desc.setMinFilter(MTLSamplerMinMagFilter::Linear);
+    sampler_desc.setMagFilter(MTLSamplerMinMagFilter::Linear);
+    sampler_desc.setMipFilter(MTLSamplerMipFilter::NotMipmapped);
+    let sampler_state = device
+        .newSamplerStateWithDescriptor(&sampler_desc)
+        .expect("MTLSamplerState creation failed");
+
+    let render_pass_descriptor = MTLRenderPassDescriptor::new();
+    {
+        // Accessing colorAttachments[0] lazily allocates the
+        // attachment owned by the descriptor; configure it once.
+        let color_attachment = unsafe {
+            render_pass_descriptor
+                .colorAttachments()
+                .objectAtIndexedSubscript(0)
+        };
+        color_attachment.setLoadAction(objc2_metal::MTLLoadAction::DontCare);
+        color_attachment.setStoreAction(objc2_metal::MTLStoreAction::Store);
+    }
+
+    GlobalContext {
+        queue,
+        pipeline_state,
+        pipeline_state_srgb,
+        equirect_pipeline_state,
+        equirect_pipeline_state_srgb,
+        vertices: vertices.into(),
+        indices: indices.into(),
+        sampler_state,
+        render_pass_descriptor: render_pass_descriptor.into(),
+        texture_cache: texture_cache.into(),
+        pixel_buffer_pools: Vec::new(),
+    }
+}
+
+/// Blits with the Metal device of a host that does not load the plugin from Unity, such as a
+/// custom engine or a test harness. Blits are submitted to `command_queue`, or to a queue of their
+/// own if it is null, and the host must commit the work writing a source texture before the
+/// graphics event of its blit runs. Fails if a device is already in use.
+///
+/// # Safety
+///
+/// `device` must be an `MTLDevice` and `command_queue` null or an `MTLCommandQueue` of it.
+pub unsafe fn set_metal_device(device: *mut c_void, command_queue: *mut c_void) -> Result<()> {
+    if is_initialized() {
+        return Err(AppleError::GlobalStateSetFailed);
+    }
+    let device = unsafe { Retained::<ProtocolObject<dyn MTLDevice>>::retain(device as *mut _) }
+        .ok_or(AppleError::MetalNotInitialized)?;
+    let command_queue = match unsafe {
+        Retained::<ProtocolObject<dyn MTLCommandQueue>>::retain(command_queue as *mut _)
+    } {
+        Some(command_queue) => command_queue,
+        None => device
+            .newCommandQueue()
+            .ok_or(AppleError::CommandBufferNotAvailable)?,
+    };
+
+    let context = create_context(&device, CommandQueue::Host(command_queue.into()));
+    CONTEXT
+        .set(Mutex::new(context))
+        .map_err(|_e| AppleError::GlobalStateSetFailed)?;
+    _ = EVENT_ID.set(0);
+    Ok(())
+}
+
+/// Texture the test pattern is drawn into, a new one each frame so that a draw never overwrites a
+/// texture an earlier blit still reads.
+pub(crate) struct MetalTestPattern {
+    device: UnsafeSendRetained<ProtocolObject<dyn MTLDevice>>,
+    width: u32,
+    height: u32,
+    texture: Option<UnsafeSendRetained<ProtocolObject<dyn MTLTexture>>>,
+}
+
+impl MetalTestPattern {
+    /// Draws with the device of the blits, which is the system default device if neither Unity
+    /// nor the host set one.
+    pub(crate) fn new(width: u32, height: u32) -> Result<Self> {
+        if !is_initialized() {
+            let device = MTLCreateSystemDefaultDevice().ok_or(AppleError::MetalNotInitialized)?;
+            unsafe {
+                set_metal_device(
+                    Retained::as_ptr(&device) as *mut c_void,
+                    std::ptr::null_mut(),
+                )
+            }?;
         }
-        unity_native_plugin::graphics::GfxDeviceEventType::Shutdown => {}
-        unity_native_plugin::graphics::GfxDeviceEventType::BeforeReset => {}
-        unity_native_plugin::graphics::GfxDeviceEventType::AfterReset => {}
+        let device = CONTEXT
+            .get()
+            .ok_or(AppleError::MetalNotInitialized)?
+            .lock()
+            .map_err(|e| AppleError::Other(e.to_string()))?
+            .sampler_state
+            .device();
+        Ok(Self {
+            device: device.into(),
+            width,
+            height,
+            texture: None,
+        })
+    }
+}
+
+impl TestPatternTexture for MetalTestPattern {
+    fn draw(&mut self, frame: u32) -> unienc_common::Result<*mut c_void> {
+        let descriptor = unsafe {
+            MTLTextureDescriptor::texture2DDescriptorWithPixelFormat_width_height_mipmapped(
+                MTLPixelFormat::RGBA8Unorm,
+                self.width as usize,
+                self.height as usize,
+                false,
+            )
+        };
+        descriptor.setUsage(MTLTextureUsage::ShaderRead);
+        let texture = self
+            .device
+            .newTextureWithDescriptor(&descriptor)
+            .ok_or(AppleError::MetalTextureNull)?;
+        let pixels = color_bars(self.width, self.height, frame);
+        unsafe {
+            texture.replaceRegion_mipmapLevel_withBytes_bytesPerRow(
+                MTLRegion {
+                    origin: MTLOrigin { x: 0, y: 0, z: 0 },
+                    size: MTLSize {
+                        width: self.width as usize,
+                        height: self.height as usize,
+                        depth: 1,
+                    },
+                },
+                0,
+                NonNull::new(pixels.as_ptr() as *mut c_void).unwrap(),
+                self.width as usize * 4,
+            )
+        };
+        let ptr = Retained::as_ptr(&texture) as *mut c_void;
+        self.texture = Some(texture.into());
+        Ok(ptr)
     }
 }
 
+/// Issues a graphics event that blits the Unity texture behind `texture_token` on the render
+/// thread, then waits for the GPU to finish it.
+pub(crate) async fn blit_texture(
+    event_issuer: Box<dyn GraphicsEventIssuer + Send>,
+    texture_token: usize,
+    dst_width: u32,
+    dst_height: u32,
+    options: BlitOptions,
+) -> Result<SharedTexture> {
+    let (tx, rx) = oneshot::channel();
+    event_issuer.issue_graphics_event(
+        Box::new(move |native_texture_ptr| {
+            let r = MetalTexture::try_from_unity_native_texture_ptr(native_texture_ptr)
+                .map_err(|_| AppleError::MetalTextureRetainFailed)
+                .and_then(|texture| custom_blit(&texture.texture, dst_width, dst_height, options));
+            tx.send(r)
+                .map_err(|_e| AppleError::BlitFutureSendFailed)
+                .unwrap();
+        }),
+        *EVENT_ID.get().ok_or(AppleError::EventIdNotReserved)?,
+        texture_token,
+    );
+
+    rx.await
+        .map_err(AppleError::from)?? // failed to receive
+        // failed to issue blit
+        .await // blit failed
+}
+
 pub(crate) fn custom_blit(
     source: &ProtocolObject<dyn MTLTexture>,
     dst_width: u32,
     dst_height: u32,
-    flip_vertically: bool,
-    is_gamma_workflow: bool,
+    options: BlitOptions,
 ) -> Result<impl Future<Output = Result<SharedTexture>> + Send + use<>> {
-    let markers = MARKERS.get();
-    let _blit_guard = markers.map(|m| m.custom_blit.get());
+    let _blit_guard = CUSTOM_BLIT.begin();
+
+    let BlitOptions {
+        flip_vertically,
+        is_gamma_workflow,
+        stereo_mode,
+        projection,
+    } = options;
+
+    // XR eyes are slices of a texture array; each is drawn from a 2D view of its slice
+    let eyes = match (projection, stereo_mode) {
+        (Projection::Equirectangular, _) => {
+            if source.textureType() != MTLTextureType::TypeCube {
+                return Err(CommonError::EquirectSourceNotCubemap.into());
+            }
+            vec![source.retain()]
+        }
+        (Projection::Flat, StereoMode::Mono) => vec![source.retain()],
+        (Projection::Flat, _) => {
+            if source.textureType() != MTLTextureType::Type2DArray || source.arrayLength() < 2 {
+                return Err(CommonError::StereoSourceNotTextureArray.into());
+            }
+            stereo_mode
+                .slices()
+                .iter()
+                .map(|&slice| {
+                    unsafe {
+                        source.newTextureViewWithPixelFormat_textureType_levels_slices(
+                            source.pixelFormat(),
+                            MTLTextureType::Type2D,
+                            NSRange::new(0, 1),
+                            NSRange::new(slice as usize, 1),
+                        )
+                    }
+                    .ok_or(AppleError::MetalTextureViewCreationFailed)
+                })
+                .collect::<Result<Vec<_>>>()?
+        }
+    };
 
     let mut context = CONTEXT
         .get()
@@ -508,32 +661,35 @@ pub(crate) fn custom_blit(
         .lock()
         .map_err(|e| AppleError::Other(e.to_string()))?;
 
-    let (shared_texture, command_buffer) = {
-        let _guard = markers.map(|m| m.custom_blit_resources.get());
-
-        // (Re)create the pixel buffer pool if dimensions changed.
-        let needs_new_pool = match &context.pixel_buffer_pool {
-            Some(entry) => entry.width != dst_width || entry.height != dst_height,
-            None => true,
-        };
-        if needs_new_pool {
-            let new_pool = create_pixel_buffer_pool(dst_width, dst_height)?;
-            context.pixel_buffer_pool = Some(PixelBufferPoolEntry {
-                width: dst_width,
-                height: dst_height,
-                pool: new_pool.into(),
-            });
+    let (shared_texture, command_buffer, capture) = {
+        let _guard = CUSTOM_BLIT_RESOURCES.begin();
+
+        // Move the pool for these dimensions to the front, creating it if needed.
+        let index = context
+            .pixel_buffer_pools
+            .iter()
+            .position(|entry| entry.width == dst_width && entry.height == dst_height);
+        match index {
+            Some(index) => context.pixel_buffer_pools[..=index].rotate_right(1),
+            None => {
+                let new_pool = create_pixel_buffer_pool(dst_width, dst_height)?;
+                context.pixel_buffer_pools.insert(
+                    0,
+                    PixelBufferPoolEntry {
+                        width: dst_width,
+                        height: dst_height,
+                        pool: new_pool.into(),
+                    },
+                );
+                context.pixel_buffer_pools.truncate(MAX_PIXEL_BUFFER_POOLS);
+            }
         }
 
         let cache = &context.texture_cache;
-        let pool = &context
-            .pixel_buffer_pool
-            .as_ref()
-            .expect("pixel_buffer_pool must be initialized")
-            .pool;
+        let pool = &context.pixel_buffer_pools[0].pool;
 
         let shared_texture = {
-            let _guard = markers.map(|m| m.custom_blit_resources_shared_texture.get());
+            let _guard = CUSTOM_BLIT_RESOURCES_SHARED_TEXTURE.begin();
             SharedTexture::new(
                 cache,
                 pool,
@@ -543,34 +699,49 @@ pub(crate) fn custom_blit(
             )?
             // with gamma workflow, input is unorm with gamma color space
         };
+        shared_texture
+            .metal_texture()
+            .setLabel(Some(ns_string!("unienc blit target")));
 
         // Commit Unity's current command buffer to ensure all prior GPU work
         // (including writes to the source texture) is submitted, and any active
         // encoder is ended. Then create our own command buffer on Unity's queue
         // so GPU execution order is guaranteed and resource tracking works.
-        let metal = context.metal;
-        {
-            let _guard = markers.map(|m| m.custom_blit_resources_commit_unity.get());
-            metal.commit_current_command_buffer();
-        }
-        let command_buffer = {
-            let _guard = markers.map(|m| m.custom_blit_resources_command_buffer.get());
-            let command_queue = metal
-                .command_queue()
-                .ok_or(AppleError::CommandBufferNotAvailable)?;
-            command_queue
+        let command_queue = match &context.queue {
+            CommandQueue::Unity(metal) => {
+                {
+                    let _guard = CUSTOM_BLIT_RESOURCES_COMMIT_UNITY.begin();
+                    metal.commit_current_command_buffer();
+                }
+                metal
+                    .command_queue()
+                    .ok_or(AppleError::CommandBufferNotAvailable)?
+            }
+            CommandQueue::Host(command_queue) => command_queue.inner.clone(),
+        };
+        let (command_buffer, capture) = {
+            let _guard = CUSTOM_BLIT_RESOURCES_COMMAND_BUFFER.begin();
+            // started before the command buffer is created, so that it is captured
+            let capture = CAPTURE_REQUEST
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take()
+                .and_then(|request| GpuCapture::start(&command_queue, request));
+            let command_buffer = command_queue
                 .commandBuffer()
-                .ok_or(AppleError::CommandBufferNotAvailable)?
+                .ok_or(AppleError::CommandBufferNotAvailable)?;
+            command_buffer.setLabel(Some(ns_string!("unienc blit")));
+            (command_buffer, capture)
         };
 
-        (shared_texture, command_buffer)
+        (shared_texture, command_buffer, capture)
     };
 
     let (block_ptr, rx) = {
-        let _guard = markers.map(|m| m.custom_blit_commands.get());
+        let _guard = CUSTOM_BLIT_COMMANDS.begin();
 
         let encoder = {
-            let _guard = markers.map(|m| m.custom_blit_commands_encoder_create.get());
+            let _guard = CUSTOM_BLIT_COMMANDS_ENCODER_CREATE.begin();
 
             // Reuse the cached MTLRenderPassDescriptor; mutate its
             // colorAttachment[0] in place via objectAtIndexedSubscript.
@@ -586,79 +757,132 @@ pub(crate) fn custom_blit(
             }
             .setTexture(Some(&shared_texture.metal_texture()));
 
-            command_buffer
+            let encoder = command_buffer
                 .renderCommandEncoderWithDescriptor(&context.render_pass_descriptor)
-                .ok_or(AppleError::RenderCommandEncoderCreationFailed)?
+                .ok_or(AppleError::RenderCommandEncoderCreationFailed)?;
+            encoder.setLabel(Some(ns_string!("unienc blit")));
+            encoder
         };
 
-        let vert_uniforms = {
-            let _guard = markers.map(|m| m.custom_blit_commands_vert_uniforms.get());
-
-            // scale to fit
-            let pixel_scale = f32::min(
-                dst_width as f32 / source.width() as f32,
-                dst_height as f32 / source.height() as f32,
-            );
-            let render_scale_x = pixel_scale * source.width() as f32 / dst_width as f32;
-            let render_scale_y = pixel_scale * source.height() as f32 / dst_height as f32;
+        let draws = {
+            let _guard = CUSTOM_BLIT_COMMANDS_VERT_UNIFORMS.begin();
+
+            // each eye gets an equal share of the frame width and is scaled to fit into it
+            let region_width = dst_width as f32 / eyes.len() as f32;
+            let region_height = dst_height as f32;
+            eyes.iter()
+                .enumerate()
+                .map(|(i, eye)| {
+                    let viewport = MTLViewport {
+                        originX: (region_width * i as f32) as f64,
+                        originY: 0.0,
+                        width: region_width as f64,
+                        height: region_height as f64,
+                        znear: 0.0,
+                        zfar: 1.0,
+                    };
 
-            if flip_vertically {
-                VertexUniforms {
-                    scale_and_tiling: [1f32 / render_scale_x, -1f32 / render_scale_y, 0.0, 1.0],
-                }
-            } else {
-                VertexUniforms {
-                    scale_and_tiling: [1f32 / render_scale_x, 1f32 / render_scale_y, 0.0, 0.0],
-                }
-            }
+                    // an unwrapped cubemap always covers the whole frame
+                    if projection == Projection::Equirectangular {
+                        let vert_uniforms = VertexUniforms {
+                            scale_and_tiling: if flip_vertically {
+                                [1.0, -1.0, 0.0, 1.0]
+                            } else {
+                                [1.0, 1.0, 0.0, 0.0]
+                            },
+                        };
+                        return (eye, vert_uniforms, viewport);
+                    }
+
+                    let pixel_scale = f32::min(
+                        region_width / eye.width() as f32,
+                        region_height / eye.height() as f32,
+                    );
+                    let render_scale_x = pixel_scale * eye.width() as f32 / region_width;
+                    let render_scale_y = pixel_scale * eye.height() as f32 / region_height;
+
+                    let vert_uniforms = if flip_vertically {
+                        VertexUniforms {
+                            scale_and_tiling: [
+                                1f32 / render_scale_x,
+                                -1f32 / render_scale_y,
+                                0.0,
+                                1.0,
+                            ],
+                        }
+                    } else {
+                        VertexUniforms {
+                            scale_and_tiling: [
+                                1f32 / render_scale_x,
+                                1f32 / render_scale_y,
+                                0.0,
+                                0.0,
+                            ],
+                        }
+                    };
+                    (eye, vert_uniforms, viewport)
+                })
+                .collect::<Vec<_>>()
         };
 
         {
-            let _guard = markers.map(|m| m.custom_blit_commands_record.get());
+            let _guard = CUSTOM_BLIT_COMMANDS_RECORD.begin();
 
-            if is_gamma_workflow {
-                encoder.setRenderPipelineState(&context.pipeline_state);
-            } else {
-                encoder.setRenderPipelineState(&context.pipeline_state_srgb);
-            }
+            let pipeline_state = match (projection, is_gamma_workflow) {
+                (Projection::Flat, true) => &context.pipeline_state,
+                (Projection::Flat, false) => &context.pipeline_state_srgb,
+                (Projection::Equirectangular, true) => &context.equirect_pipeline_state,
+                (Projection::Equirectangular, false) => &context.equirect_pipeline_state_srgb,
+            };
+            encoder.setRenderPipelineState(pipeline_state);
 
             encoder.setCullMode(MTLCullMode::None);
 
             // vertex
             unsafe { encoder.setVertexBuffer_offset_atIndex(Some(&*context.vertices), 0, 0) };
-            // setVertexBytes copies into Metal's per-frame scratch and avoids
-            // allocating a transient MTLBuffer for the 16-byte uniforms.
-            unsafe {
-                encoder.setVertexBytes_length_atIndex(
-                    NonNull::new(&vert_uniforms as *const VertexUniforms as *mut _)
-                        .ok_or(AppleError::NonNullCreationFailed)?,
-                    std::mem::size_of::<VertexUniforms>(),
-                    1,
-                )
-            };
 
             // fragment
-            unsafe { encoder.setFragmentTexture_atIndex(Some(source), 0) };
             unsafe { encoder.setFragmentSamplerState_atIndex(Some(&context.sampler_state), 0) };
 
-            unsafe {
-                encoder.drawIndexedPrimitives_indexCount_indexType_indexBuffer_indexBufferOffset(
-                    MTLPrimitiveType::Triangle,
-                    3,
-                    MTLIndexType::UInt16,
-                    &context.indices,
-                    0,
-                )
-            };
+            for (i, (eye, vert_uniforms, viewport)) in draws.iter().enumerate() {
+                // one draw per eye
+                encoder.pushDebugGroup(&NSString::from_str(&format!("unienc draw {i}")));
+                encoder.setViewport(*viewport);
+
+                // setVertexBytes copies into Metal's per-frame scratch and avoids
+                // allocating a transient MTLBuffer for the 16-byte uniforms.
+                unsafe {
+                    encoder.setVertexBytes_length_atIndex(
+                        NonNull::new(vert_uniforms as *const VertexUniforms as *mut _)
+                            .ok_or(AppleError::NonNullCreationFailed)?,
+                        std::mem::size_of::<VertexUniforms>(),
+                        1,
+                    )
+                };
+
+                unsafe { encoder.setFragmentTexture_atIndex(Some(eye), 0) };
+
+                unsafe {
+                    encoder
+                        .drawIndexedPrimitives_indexCount_indexType_indexBuffer_indexBufferOffset(
+                            MTLPrimitiveType::Triangle,
+                            3,
+                            MTLIndexType::UInt16,
+                            &context.indices,
+                            0,
+                        )
+                };
+                encoder.popDebugGroup();
+            }
         }
 
         {
-            let _guard = markers.map(|m| m.custom_blit_commands_end_encoding.get());
+            let _guard = CUSTOM_BLIT_COMMANDS_END_ENCODING.begin();
             encoder.endEncoding();
         }
 
         let (block_ptr, rx) = {
-            let _guard = markers.map(|m| m.custom_blit_commands_completion_handler.get());
+            let _guard = CUSTOM_BLIT_COMMANDS_COMPLETION_HANDLER.begin();
 
             let (tx, rx) = oneshot::channel();
 
@@ -691,10 +915,11 @@ pub(crate) fn custom_blit(
     };
 
     {
-        let _guard = markers.map(|m| m.custom_blit_submit.get());
+        let _guard = CUSTOM_BLIT_SUBMIT.begin();
         unsafe { command_buffer.addCompletedHandler(block_ptr) };
         command_buffer.commit();
     }
+    drop(capture);
     Ok(async move { rx.await.map_err(AppleError::from) })
 }
 
@@ -715,8 +940,9 @@ const POOL_MIN_BUFFER_COUNT: i32 = 2;
 // Excess buffers beyond MinimumBufferCount are released after this many
 // seconds of disuse.
 const POOL_MAX_BUFFER_AGE_SECS: f64 = 1.0;
-// Hard cap. Buffer allocation beyond this threshold fails with
-// kCVReturnWouldExceedAllocationThreshold; the calling code drops the frame.
+// Hard cap on frames in flight. Buffer allocation beyond this threshold fails
+// with kCVReturnWouldExceedAllocationThreshold, reported as
+// PixelBufferPoolExhausted so the calling code can drop the frame.
 const POOL_ALLOCATION_THRESHOLD: i32 = 4;
 
 fn create_pixel_buffer_pool(width: u32, height: u32) -> Result<Retained<CVPixelBufferPool>> {
@@ -769,10 +995,8 @@ impl SharedTexture {
         height: usize,
         srgb: bool,
     ) -> Result<Self> {
-        let markers = MARKERS.get();
-
         let buffer = {
-            let _guard = markers.map(|m| m.custom_blit_resources_pixel_buffer_create.get());
+            let _guard = CUSTOM_BLIT_RESOURCES_PIXEL_BUFFER_CREATE.begin();
 
             let threshold_num = CFNumber::new_i32(POOL_ALLOCATION_THRESHOLD);
             let aux_keys: [&CFString; 1] = unsafe { [kCVPixelBufferPoolAllocationThresholdKey] };
@@ -780,21 +1004,24 @@ impl SharedTexture {
             let aux_attrs = CFDictionary::from_slices(&aux_keys, &aux_values);
 
             let mut buffer: *mut CVPixelBuffer = std::ptr::null_mut();
-            unsafe {
+            let status = unsafe {
                 CVPixelBufferPool::create_pixel_buffer_with_aux_attributes(
                     allocator::default(),
                     pool,
                     Some(aux_attrs.as_opaque()),
                     NonNull::new(&mut buffer).ok_or(AppleError::NonNullCreationFailed)?,
                 )
+            };
+            if status == kCVReturnWouldExceedAllocationThreshold {
+                return Err(AppleError::PixelBufferPoolExhausted);
             }
-            .to_result()?;
+            status.to_result()?;
 
             unsafe { Retained::from_raw(buffer) }.ok_or(AppleError::PixelBufferNull)?
         };
 
         let texture = {
-            let _guard = markers.map(|m| m.custom_blit_resources_metal_texture_create.get());
+            let _guard = CUSTOM_BLIT_RESOURCES_METAL_TEXTURE_CREATE.begin();
 
             let mut texture: *mut CVMetalTexture = std::ptr::null_mut();
             unsafe {
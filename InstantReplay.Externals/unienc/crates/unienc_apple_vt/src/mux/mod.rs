@@ -2,6 +2,7 @@ use std::cell::RefCell;
 use std::ffi::{c_char, c_void};
 use std::fs;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{path::Path, ptr::NonNull};
 
 use crate::allocator;
@@ -10,8 +11,8 @@ use block2::RcBlock;
 use dispatch2::DispatchQueue;
 use objc2::rc::Retained;
 use objc2_av_foundation::{
-    AVAssetWriter, AVAssetWriterInput, AVAssetWriterStatus, AVFileTypeMPEG4, AVMediaTypeAudio,
-    AVMediaTypeVideo,
+    AVAssetWriter, AVAssetWriterInput, AVAssetWriterStatus, AVFileTypeMPEG4,
+    AVFileTypeQuickTimeMovie, AVMediaTypeAudio, AVMediaTypeVideo,
 };
 use objc2_core_audio_types::{
     AudioStreamBasicDescription, AudioStreamPacketDescription, MPEG4ObjectID, kAudioFormatMPEG4AAC,
@@ -19,14 +20,46 @@ use objc2_core_audio_types::{
 use objc2_core_media::{
     CMAudioFormatDescriptionCreate, CMAudioSampleBufferCreateReadyWithPacketDescriptions,
     CMBlockBuffer, CMFormatDescription, CMSampleBuffer, CMTime, CMVideoFormatDescriptionCreate,
-    kCMBlockBufferAssureMemoryNowFlag, kCMTimeZero, kCMVideoCodecType_H264,
+    kCMBlockBufferAssureMemoryNowFlag, kCMTimeZero, kCMVideoCodecType_HEVC,
+    kCMVideoCodecType_HEVCWithAlpha,
 };
 use objc2_foundation::{NSString, NSURL};
 use tokio::sync::{mpsc, oneshot};
 use unienc_common::{CommonError, CompletionHandle, Muxer, MuxerInput, ResultExt};
 
 use crate::common::UnsafeSendRetained;
-use crate::{audio::AudioPacket, video::VideoEncodedData};
+use crate::{
+    audio::AudioPacket,
+    video::{self, VideoEncodedData},
+};
+
+type SampleSender = mpsc::Sender<Mutex<UnsafeSendRetained<CMSampleBuffer>>>;
+
+static QUEUE_CAPACITY: AtomicUsize = AtomicUsize::new(100);
+
+/// Sets how many samples each track of muxers created afterwards queues for AVAssetWriter. Once a
+/// queue is full, pushes wait until the writer has drained half of it, which holds back the pulls
+/// from the encoder feeding the muxer instead of retaining more sample buffers.
+pub fn set_queue_capacity(capacity: usize) {
+    QUEUE_CAPACITY.store(capacity.max(1), Ordering::Relaxed);
+}
+
+async fn send_sample(
+    tx: &SampleSender,
+    sample_buffer: UnsafeSendRetained<CMSampleBuffer>,
+) -> Result<()> {
+    // resuming on every freed slot would wake the pusher for each appended sample
+    let count = if tx.capacity() == 0 {
+        (tx.max_capacity() / 2).max(1)
+    } else {
+        1
+    };
+    let mut permits = tx.reserve_many(count).await?;
+    if let Some(permit) = permits.next() {
+        permit.send(Mutex::new(sample_buffer));
+    }
+    Ok(())
+}
 
 pub struct AVFMuxer {
     writer: objc2::rc::Retained<AVAssetWriter>,
@@ -35,13 +68,13 @@ pub struct AVFMuxer {
 }
 
 pub struct AVFMuxerVideoInput {
-    tx: mpsc::Sender<Mutex<UnsafeSendRetained<CMSampleBuffer>>>,
+    tx: SampleSender,
     finish_rx: oneshot::Receiver<Result<()>>,
 }
 
 pub struct AVFMuxerAudioInput {
     asbd: AudioStreamBasicDescription,
-    tx: mpsc::Sender<Mutex<UnsafeSendRetained<CMSampleBuffer>>>,
+    tx: SampleSender,
     finish_rx: oneshot::Receiver<Result<()>>,
     format_desc: Option<Retained<CMFormatDescription>>,
 }
@@ -50,10 +83,7 @@ impl MuxerInput for AVFMuxerVideoInput {
     type Data = VideoEncodedData;
 
     async fn push(&mut self, data: Self::Data) -> unienc_common::Result<()> {
-        self.tx
-            .send(Mutex::new(data.sample_buffer))
-            .await
-            .map_err(AppleError::from)?;
+        send_sample(&self.tx, data.sample_buffer).await?;
 
         Ok(())
     }
@@ -80,10 +110,7 @@ impl MuxerInput for AVFMuxerAudioInput {
             }
         };
         let sample_buffer = create_audio_sample_buffer(&data, &format_desc)?;
-        self.tx
-            .send(Mutex::new(sample_buffer.into()))
-            .await
-            .map_err(AppleError::from)?;
+        send_sample(&self.tx, sample_buffer.into()).await?;
 
         Ok(())
     }
@@ -114,7 +141,10 @@ impl CompletionHandle for AVFMuxerCompletionHandle {
             writer.finishWritingWithCompletionHandler(&RcBlock::new(move || {
                 if let Some(tx) = tx.borrow_mut().take() {
                     if let Some(err) = writer1.error() {
-                        println!("Failed to finish writing: {}", err.to_friendly_string());
+                        unienc_common::log!(
+                            "Failed to finish writing: {}",
+                            err.to_friendly_string()
+                        );
                         tx.send(Err(CommonError::Other(err.to_friendly_string())))
                             .unwrap();
                     } else {
@@ -160,7 +190,12 @@ impl AVFMuxer {
         _ = fs::remove_file(path);
         let url = NSURL::fileURLWithPath(&NSString::from_str(path.to_string_lossy().as_ref()));
 
-        let file_type = unsafe { AVFileTypeMPEG4.unwrap() };
+        let codec = video::codec_type(video_options);
+        // MP4 cannot carry ProRes
+        let file_type = match video::is_prores(codec) {
+            true => unsafe { AVFileTypeQuickTimeMovie.unwrap() },
+            false => unsafe { AVFileTypeMPEG4.unwrap() },
+        };
         let writer = unsafe {
             objc2_av_foundation::AVAssetWriter::assetWriterWithURL_fileType_error(&url, file_type)?
         };
@@ -169,7 +204,10 @@ impl AVFMuxer {
             let mut format_desc: *const CMFormatDescription = std::ptr::null();
             CMVideoFormatDescriptionCreate(
                 allocator::default(),
-                kCMVideoCodecType_H264,
+                match codec == kCMVideoCodecType_HEVCWithAlpha {
+                    true => kCMVideoCodecType_HEVC,
+                    false => codec,
+                },
                 video_options.width() as i32,
                 video_options.height() as i32,
                 None,
@@ -240,11 +278,9 @@ impl AVFMuxer {
             writer: Retained<AVAssetWriter>,
             input: Retained<AVAssetWriterInput>,
             label: &str,
-        ) -> (
-            mpsc::Sender<Mutex<UnsafeSendRetained<CMSampleBuffer>>>,
-            oneshot::Receiver<Result<()>>,
-        ) {
-            let (tx, rx) = mpsc::channel::<Mutex<UnsafeSendRetained<CMSampleBuffer>>>(100);
+            capacity: usize,
+        ) -> (SampleSender, oneshot::Receiver<Result<()>>) {
+            let (tx, rx) = mpsc::channel::<Mutex<UnsafeSendRetained<CMSampleBuffer>>>(capacity);
             let (finish_tx, finish_rx) = oneshot::channel::<Result<()>>();
 
             let rx = RefCell::new(rx);
@@ -263,7 +299,9 @@ impl AVFMuxer {
                                 let err_msg = unsafe { writer.error() }
                                     .map(|e| e.to_friendly_string())
                                     .unwrap_or_else(|| "unknown error".to_string());
-                                println!("{label_clone}: appendSampleBuffer failed: {err_msg}");
+                                unienc_common::log!(
+                                    "{label_clone}: appendSampleBuffer failed: {err_msg}"
+                                );
                                 if let Some(finish_tx) = finish_tx.borrow_mut().take() {
                                     if finish_tx
                                         .send(Err(AppleError::AssetWriterAppendFailed(
@@ -272,12 +310,12 @@ impl AVFMuxer {
                                         )))
                                         .is_err()
                                     {
-                                        println!(
+                                        unienc_common::log!(
                                             "{label_clone}: failed to send error to finish_tx: channel closed"
                                         );
                                     }
                                 } else {
-                                    println!(
+                                    unienc_common::log!(
                                         "{label_clone}: failed to send error to finish_tx: already taken"
                                     );
                                 }
@@ -291,7 +329,9 @@ impl AVFMuxer {
                             if let Some(finish_tx) = finish_tx.borrow_mut().take() {
                                 input_clone.markAsFinished();
                                 finish_tx.send(Ok(())).unwrap_or_else(|e| {
-                                    println!("{label_clone}: failed to send finish signal: {e:?}");
+                                    unienc_common::log!(
+                                        "{label_clone}: failed to send finish signal: {e:?}"
+                                    );
                                 });
                             }
                             return;
@@ -310,8 +350,11 @@ impl AVFMuxer {
             (tx, finish_rx)
         }
 
-        let (video_tx, video_finish_rx) = connect_input(writer.clone(), video_input, "video input");
-        let (audio_tx, audio_finish_rx) = connect_input(writer.clone(), audio_input, "audio input");
+        let capacity = QUEUE_CAPACITY.load(Ordering::Relaxed);
+        let (video_tx, video_finish_rx) =
+            connect_input(writer.clone(), video_input, "video input", capacity);
+        let (audio_tx, audio_finish_rx) =
+            connect_input(writer.clone(), audio_input, "audio input", capacity);
 
         Ok(Self {
             writer,
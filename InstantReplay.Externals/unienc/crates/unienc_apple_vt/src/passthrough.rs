@@ -0,0 +1,91 @@
+use objc2_core_media::{CMSampleTimingInfo, CMTime, kCMTimeInvalid};
+use unienc_common::passthrough::{aac, h264::AccessUnit};
+use unienc_common::{AacPacketizer, AudioEncoderOptions, H264Packetizer};
+
+use crate::audio::AudioPacket;
+use crate::error::{AppleError, Result};
+use crate::video::VideoEncodedData;
+
+/// Wraps H.264 access units into sample buffers, carrying the most recent parameter sets in the
+/// format description of each sample.
+#[derive(Default)]
+pub struct VideoToolboxH264Packetizer {
+    parameter_sets: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl VideoToolboxH264Packetizer {
+    fn packetize_impl(&mut self, access_unit: &[u8], timestamp: f64) -> Result<VideoEncodedData> {
+        let access_unit = AccessUnit::parse(access_unit)?;
+
+        if let (Some(sps), Some(pps)) = (access_unit.sps, access_unit.pps) {
+            self.parameter_sets = Some((sps.to_vec(), pps.to_vec()));
+        }
+        let Some((sps, pps)) = self.parameter_sets.clone() else {
+            return Err(AppleError::Other(
+                "The first H.264 access unit must contain SPS and PPS".to_string(),
+            ));
+        };
+
+        let timing_info = CMSampleTimingInfo {
+            duration: unsafe { kCMTimeInvalid },
+            presentationTimeStamp: unsafe { CMTime::with_seconds(timestamp, 720) },
+            decodeTimeStamp: unsafe { kCMTimeInvalid },
+        };
+
+        VideoEncodedData::from_h264(
+            access_unit.to_length_prefixed(),
+            timing_info,
+            !access_unit.is_idr,
+            sps,
+            pps,
+        )
+    }
+}
+
+impl H264Packetizer for VideoToolboxH264Packetizer {
+    type Data = VideoEncodedData;
+
+    fn packetize(
+        &mut self,
+        access_unit: &[u8],
+        timestamp: f64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        Ok(vec![self.packetize_impl(access_unit, timestamp)?])
+    }
+}
+
+/// Wraps raw AAC frames into audio packets with an ES_Descriptor magic cookie.
+pub struct AudioToolboxAacPacketizer {
+    sample_rate: u32,
+    magic_cookie: Vec<u8>,
+}
+
+impl AudioToolboxAacPacketizer {
+    pub fn new<A: AudioEncoderOptions>(options: &A) -> unienc_common::Result<Self> {
+        Ok(Self {
+            sample_rate: options.sample_rate(),
+            magic_cookie: aac::es_descriptor(
+                options.sample_rate(),
+                options.channels(),
+                options.bitrate(),
+            )?,
+        })
+    }
+}
+
+impl AacPacketizer for AudioToolboxAacPacketizer {
+    type Data = AudioPacket;
+
+    fn packetize(
+        &mut self,
+        frame: &[u8],
+        timestamp_in_samples: u64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        Ok(vec![AudioPacket {
+            data: frame.to_vec(),
+            timestamp_in_samples,
+            sample_rate: self.sample_rate,
+            magic_cookie: self.magic_cookie.clone(),
+        }])
+    }
+}
@@ -0,0 +1,279 @@
+//! ReplayKit interop: records the whole app screen through RPScreenRecorder instead of Unity's
+//! render targets, and hands the delivered sample buffers to the VideoToolbox encoder and muxer.
+
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+
+use block2::RcBlock;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyObject, Bool};
+use objc2::{class, msg_send, sel};
+use objc2_core_audio_types::{
+    AudioStreamBasicDescription, kAudioFormatFlagIsBigEndian, kAudioFormatFlagIsFloat,
+    kAudioFormatFlagIsNonInterleaved, kAudioFormatFlagIsSignedInteger, kAudioFormatLinearPCM,
+};
+use objc2_core_media::{CMAudioFormatDescriptionGetStreamBasicDescription, CMSampleBuffer};
+use objc2_core_video::CVPixelBuffer;
+use objc2_foundation::NSError;
+use tokio::sync::mpsc;
+use unienc_common::AudioSample;
+
+use crate::common::{UnsafeSendRetained, completion_handler};
+use crate::error::{AppleError, NSErrorDisplay, Result};
+
+#[link(name = "ReplayKit", kind = "framework")]
+unsafe extern "C" {}
+
+// RPSampleBufferType
+const SAMPLE_BUFFER_TYPE_VIDEO: isize = 1;
+const SAMPLE_BUFFER_TYPE_AUDIO_APP: isize = 2;
+
+/// Buffers delivered while the consumer is behind are dropped beyond this.
+const CHANNEL_CAPACITY: usize = 32;
+
+pub enum ReplayKitSample {
+    Video {
+        pixel_buffer: UnsafeSendRetained<CVPixelBuffer>,
+        timestamp: f64,
+    },
+    Audio(AudioSample),
+}
+
+/// Screen capture session of the shared RPScreenRecorder. Timestamps of the produced samples are
+/// relative to the first delivered buffer, and app audio is converted to the encoder's format.
+/// Microphone buffers are ignored.
+pub struct ReplayKitCapture {
+    recorder: UnsafeSendRetained<AnyObject>,
+    // taken on stop so the receiver ends even if ReplayKit keeps the handler alive
+    tx: Arc<Mutex<Option<mpsc::Sender<ReplayKitSample>>>>,
+}
+
+impl ReplayKitCapture {
+    pub async fn start(
+        sample_rate: u32,
+        channels: u32,
+    ) -> Result<(Self, mpsc::Receiver<ReplayKitSample>)> {
+        let recorder: Retained<AnyObject> =
+            unsafe { msg_send![class!(RPScreenRecorder), sharedRecorder] };
+        let recorder = UnsafeSendRetained::from(recorder);
+        let available: Bool = unsafe { msg_send![&*recorder.inner, isAvailable] };
+        // in-app capture is iOS 11+
+        let can_capture: Bool = unsafe {
+            msg_send![
+                &*recorder.inner,
+                respondsToSelector: sel!(startCaptureWithHandler:completionHandler:)
+            ]
+        };
+        if !available.as_bool() || !can_capture.as_bool() {
+            return Err(AppleError::ReplayKitUnavailable);
+        }
+
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let tx = Arc::new(Mutex::new(Some(tx)));
+        // ReplayKit copies the blocks, so they are not held across the await below
+        let done_rx = {
+            let handler = sample_handler(tx.clone(), sample_rate, channels);
+            let (completion, done_rx) = completion_handler();
+            unsafe {
+                let _: () = msg_send![
+                    &*recorder.inner,
+                    startCaptureWithHandler: &*handler,
+                    completionHandler: &*completion
+                ];
+            }
+            done_rx
+        };
+
+        if let Some(err) = done_rx.await? {
+            return Err(AppleError::ReplayKitCaptureFailed(
+                err.inner.to_friendly_string(),
+            ));
+        }
+
+        Ok((Self { recorder, tx }, rx))
+    }
+
+    pub async fn stop(self) -> Result<()> {
+        if let Ok(mut tx) = self.tx.lock() {
+            tx.take();
+        }
+
+        let done_rx = {
+            let (completion, done_rx) = completion_handler();
+            unsafe {
+                let _: () = msg_send![&*self.recorder.inner, stopCaptureWithHandler: &*completion];
+            }
+            done_rx
+        };
+
+        if let Some(err) = done_rx.await? {
+            return Err(AppleError::ReplayKitCaptureFailed(
+                err.inner.to_friendly_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Block for `startCaptureWithHandler:`, converting each delivered buffer and forwarding it to `tx`.
+fn sample_handler(
+    tx: Arc<Mutex<Option<mpsc::Sender<ReplayKitSample>>>>,
+    sample_rate: u32,
+    channels: u32,
+) -> RcBlock<dyn Fn(*mut CMSampleBuffer, isize, *mut NSError)> {
+    let origin = Mutex::new(None::<f64>);
+    RcBlock::new(
+        move |sample_buffer: *mut CMSampleBuffer, buffer_type: isize, error: *mut NSError| {
+            let Some(sample_buffer) = (unsafe { sample_buffer.as_ref() }) else {
+                return;
+            };
+            if !error.is_null() || !unsafe { sample_buffer.data_is_ready() } {
+                return;
+            }
+
+            let pts = unsafe { sample_buffer.presentation_time_stamp().seconds() };
+            let timestamp = {
+                let Ok(mut origin) = origin.lock() else {
+                    return;
+                };
+                (pts - *origin.get_or_insert(pts)).max(0.0)
+            };
+
+            let sample = match buffer_type {
+                SAMPLE_BUFFER_TYPE_VIDEO => {
+                    let Some(image_buffer) = (unsafe { sample_buffer.image_buffer() }) else {
+                        return;
+                    };
+                    ReplayKitSample::Video {
+                        pixel_buffer: Retained::from(image_buffer).into(),
+                        timestamp,
+                    }
+                }
+                SAMPLE_BUFFER_TYPE_AUDIO_APP => {
+                    let Some(data) = read_audio(sample_buffer, sample_rate, channels) else {
+                        return;
+                    };
+                    ReplayKitSample::Audio(AudioSample {
+                        data,
+                        timestamp_in_samples: (timestamp * sample_rate as f64) as u64,
+                    })
+                }
+                _ => return,
+            };
+
+            if let Ok(tx) = tx.lock()
+                && let Some(tx) = tx.as_ref()
+            {
+                _ = tx.try_send(sample);
+            }
+        },
+    )
+}
+
+/// Reads linear PCM from an audio sample buffer as interleaved 16-bit samples with the given rate
+/// and channel count. Returns None for formats that cannot be converted.
+fn read_audio(sample_buffer: &CMSampleBuffer, sample_rate: u32, channels: u32) -> Option<Vec<i16>> {
+    let format_description = unsafe { sample_buffer.format_description() }?;
+    let asbd: &AudioStreamBasicDescription =
+        unsafe { CMAudioFormatDescriptionGetStreamBasicDescription(&format_description).as_ref() }?;
+    if asbd.mFormatID != kAudioFormatLinearPCM
+        || asbd.mFormatFlags & kAudioFormatFlagIsNonInterleaved != 0
+        || asbd.mChannelsPerFrame == 0
+    {
+        return None;
+    }
+
+    let data_buffer = unsafe { sample_buffer.data_buffer() }?;
+    let mut bytes = vec![0u8; unsafe { data_buffer.data_length() }];
+    if !bytes.is_empty() {
+        let status = unsafe {
+            data_buffer.copy_data_bytes(0, bytes.len(), NonNull::new(bytes.as_mut_ptr().cast())?)
+        };
+        if status != 0 {
+            return None;
+        }
+    }
+
+    let big_endian = asbd.mFormatFlags & kAudioFormatFlagIsBigEndian != 0;
+    let samples: Vec<f32> = match asbd.mBitsPerChannel {
+        16 if asbd.mFormatFlags & kAudioFormatFlagIsSignedInteger != 0 => bytes
+            .chunks_exact(2)
+            .map(|b| {
+                let b = [b[0], b[1]];
+                let v = if big_endian {
+                    i16::from_be_bytes(b)
+                } else {
+                    i16::from_le_bytes(b)
+                };
+                v as f32 / 32768.0
+            })
+            .collect(),
+        32 if asbd.mFormatFlags & kAudioFormatFlagIsFloat != 0 => bytes
+            .chunks_exact(4)
+            .map(|b| {
+                let b = [b[0], b[1], b[2], b[3]];
+                if big_endian {
+                    f32::from_be_bytes(b)
+                } else {
+                    f32::from_le_bytes(b)
+                }
+            })
+            .collect(),
+        _ => return None,
+    };
+
+    let frames = remix(&samples, asbd.mChannelsPerFrame as usize, channels as usize)?;
+    let frames = resample(
+        &frames,
+        channels as usize,
+        asbd.mSampleRate,
+        sample_rate as f64,
+    );
+    Some(
+        frames
+            .into_iter()
+            .map(|v| (v.clamp(-1.0, 1.0) * 32767.0) as i16)
+            .collect(),
+    )
+}
+
+/// Converts between mono and stereo; other channel layouts must already match.
+fn remix(samples: &[f32], from: usize, to: usize) -> Option<Vec<f32>> {
+    match (from, to) {
+        (from, to) if from == to => Some(samples.to_vec()),
+        (1, 2) => Some(samples.iter().flat_map(|&v| [v, v]).collect()),
+        (2, 1) => Some(
+            samples
+                .chunks_exact(2)
+                .map(|f| (f[0] + f[1]) / 2.0)
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Linear interpolation within a single buffer. ReplayKit delivers app audio at the device's rate,
+/// which often differs from the encoder's.
+fn resample(samples: &[f32], channels: usize, from: f64, to: f64) -> Vec<f32> {
+    if from == to || from <= 0.0 {
+        return samples.to_vec();
+    }
+    let input_frames = samples.len() / channels;
+    if input_frames == 0 {
+        return Vec::new();
+    }
+    let output_frames = (input_frames as f64 * to / from).round() as usize;
+    let mut output = Vec::with_capacity(output_frames * channels);
+    for i in 0..output_frames {
+        let position = i as f64 * from / to;
+        let index = (position as usize).min(input_frames - 1);
+        let next = (index + 1).min(input_frames - 1);
+        let t = (position - index as f64) as f32;
+        for c in 0..channels {
+            let a = samples[index * channels + c];
+            let b = samples[next * channels + c];
+            output.push(a + (b - a) * t);
+        }
+    }
+    output
+}
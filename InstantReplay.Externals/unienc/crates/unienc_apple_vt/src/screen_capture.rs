@@ -0,0 +1,312 @@
+//! ScreenCaptureKit frame source for macOS: records a whole window or display (such as the editor
+//! window around the game view) and hands the frames to the VideoToolbox encoder.
+
+use std::ffi::{CStr, c_char, c_int, c_void};
+use std::sync::{Mutex, OnceLock};
+
+use block2::RcBlock;
+use objc2::rc::{Allocated, Retained};
+use objc2::runtime::{AnyClass, AnyObject, Bool, MessageReceiver, NSObject, NSObjectProtocol, Sel};
+use objc2::{AnyThread, DefinedClass, class, define_class, msg_send};
+use objc2_core_foundation::CGRect;
+use objc2_core_media::{CMSampleBuffer, CMTime};
+use objc2_core_video::{CVPixelBuffer, kCVPixelFormatType_32BGRA};
+use objc2_foundation::{NSArray, NSError};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::common::{UnsafeSendRetained, completion_handler};
+use crate::error::{AppleError, NSErrorDisplay, Result};
+
+// Loaded on first use rather than linked, so the library still loads before macOS 12.3.
+const FRAMEWORK_PATH: &CStr =
+    c"/System/Library/Frameworks/ScreenCaptureKit.framework/ScreenCaptureKit";
+const RTLD_LAZY: c_int = 0x1;
+
+unsafe extern "C" {
+    fn dlopen(path: *const c_char, mode: c_int) -> *mut c_void;
+}
+
+// SCStreamOutputType
+const STREAM_OUTPUT_TYPE_SCREEN: isize = 0;
+
+/// Frames delivered while the consumer is behind are dropped beyond this.
+const CHANNEL_CAPACITY: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenCaptureTarget {
+    /// The largest on-screen window owned by this process.
+    CurrentWindow,
+    /// The main display, including other applications.
+    MainDisplay,
+}
+
+pub struct ScreenCaptureFrame {
+    pub pixel_buffer: UnsafeSendRetained<CVPixelBuffer>,
+    /// Seconds since the first delivered frame.
+    pub timestamp: f64,
+}
+
+struct StreamOutputIvars {
+    tx: Mutex<Option<mpsc::Sender<ScreenCaptureFrame>>>,
+    origin: Mutex<Option<f64>>,
+}
+
+define_class!(
+    // SAFETY:
+    // - The superclass NSObject does not have any subclassing requirements.
+    // - `StreamOutput` does not implement `Drop`.
+    #[unsafe(super(NSObject))]
+    #[name = "UniencScreenCaptureStreamOutput"]
+    #[ivars = StreamOutputIvars]
+    struct StreamOutput;
+
+    unsafe impl NSObjectProtocol for StreamOutput {}
+
+    // SCStreamOutput
+    impl StreamOutput {
+        #[unsafe(method(stream:didOutputSampleBuffer:ofType:))]
+        fn did_output_sample_buffer(
+            &self,
+            _stream: *mut AnyObject,
+            sample_buffer: *mut CMSampleBuffer,
+            output_type: isize,
+        ) {
+            if output_type != STREAM_OUTPUT_TYPE_SCREEN {
+                return;
+            }
+            let Some(sample_buffer) = (unsafe { sample_buffer.as_ref() }) else {
+                return;
+            };
+            // idle frames, sent when nothing changed, carry no image
+            let Some(image_buffer) = (unsafe { sample_buffer.image_buffer() }) else {
+                return;
+            };
+
+            let pts = unsafe { sample_buffer.presentation_time_stamp().seconds() };
+            let timestamp = {
+                let Ok(mut origin) = self.ivars().origin.lock() else {
+                    return;
+                };
+                (pts - *origin.get_or_insert(pts)).max(0.0)
+            };
+
+            if let Ok(tx) = self.ivars().tx.lock()
+                && let Some(tx) = tx.as_ref()
+            {
+                _ = tx.try_send(ScreenCaptureFrame {
+                    pixel_buffer: Retained::from(image_buffer).into(),
+                    timestamp,
+                });
+            }
+        }
+    }
+);
+
+impl StreamOutput {
+    fn new(tx: mpsc::Sender<ScreenCaptureFrame>) -> Retained<Self> {
+        let this = Self::alloc().set_ivars(StreamOutputIvars {
+            tx: Mutex::new(Some(tx)),
+            origin: Mutex::new(None),
+        });
+        unsafe { msg_send![super(this), init] }
+    }
+}
+
+/// Running SCStream capturing BGRA frames at the encoder's size.
+pub struct ScreenCapture {
+    stream: UnsafeSendRetained<AnyObject>,
+    output: UnsafeSendRetained<StreamOutput>,
+}
+
+impl ScreenCapture {
+    pub async fn start(
+        target: ScreenCaptureTarget,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> Result<(Self, mpsc::Receiver<ScreenCaptureFrame>)> {
+        Self::ensure_available()?;
+        let content = shareable_content().await?;
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+
+        // the Objective-C objects used here are not held across the await below
+        let (stream, output, done_rx) = {
+            let filter = content_filter(&content.inner, target)?;
+
+            let configuration: Retained<AnyObject> =
+                unsafe { msg_send![class!(SCStreamConfiguration), new] };
+            unsafe {
+                let _: () = msg_send![&configuration, setWidth: width as usize];
+                let _: () = msg_send![&configuration, setHeight: height as usize];
+                let _: () = msg_send![&configuration, setPixelFormat: kCVPixelFormatType_32BGRA];
+                let _: () = msg_send![
+                    &configuration,
+                    setMinimumFrameInterval: CMTime::with_seconds(1.0 / fps.max(1) as f64, 600)
+                ];
+                let _: () = msg_send![&configuration, setShowsCursor: Bool::YES];
+            }
+
+            let stream: Allocated<AnyObject> = unsafe { msg_send![class!(SCStream), alloc] };
+            let stream: Retained<AnyObject> = unsafe {
+                msg_send![
+                    stream,
+                    initWithFilter: &*filter,
+                    configuration: &*configuration,
+                    delegate: None::<&AnyObject>
+                ]
+            };
+
+            let output = StreamOutput::new(tx);
+            let output_object: &AnyObject = &output;
+            // `type:` cannot be spelled in msg_send!
+            let mut error: *mut NSError = std::ptr::null_mut();
+            let added: Bool = unsafe {
+                MessageReceiver::send_message(
+                    &*stream,
+                    Sel::register(c"addStreamOutput:type:sampleHandlerQueue:error:"),
+                    (
+                        output_object,
+                        STREAM_OUTPUT_TYPE_SCREEN,
+                        std::ptr::null::<AnyObject>(),
+                        &mut error,
+                    ),
+                )
+            };
+            if !added.as_bool() {
+                let reason = unsafe { Retained::retain(error) }
+                    .map(|err| err.to_friendly_string())
+                    .unwrap_or_default();
+                return Err(AppleError::ScreenCaptureFailed(reason));
+            }
+
+            let (completion, done_rx) = completion_handler();
+            unsafe {
+                let _: () = msg_send![&stream, startCaptureWithCompletionHandler: &*completion];
+            }
+            (
+                UnsafeSendRetained::from(stream),
+                UnsafeSendRetained::from(output),
+                done_rx,
+            )
+        };
+
+        if let Some(err) = done_rx.await? {
+            return Err(AppleError::ScreenCaptureFailed(
+                err.inner.to_friendly_string(),
+            ));
+        }
+
+        Ok((Self { stream, output }, rx))
+    }
+
+    /// Loads ScreenCaptureKit, failing if this macOS version does not have it.
+    pub fn ensure_available() -> Result<()> {
+        static AVAILABLE: OnceLock<bool> = OnceLock::new();
+        let available = *AVAILABLE.get_or_init(|| {
+            let handle = unsafe { dlopen(FRAMEWORK_PATH.as_ptr(), RTLD_LAZY) };
+            !handle.is_null() && AnyClass::get(c"SCStream").is_some()
+        });
+        if available {
+            Ok(())
+        } else {
+            Err(AppleError::ScreenCaptureUnavailable)
+        }
+    }
+
+    pub async fn stop(self) -> Result<()> {
+        // ends the receiver even if ScreenCaptureKit keeps the output alive
+        if let Ok(mut tx) = self.output.ivars().tx.lock() {
+            tx.take();
+        }
+
+        let done_rx = {
+            let (completion, done_rx) = completion_handler();
+            unsafe {
+                let _: () =
+                    msg_send![&*self.stream.inner, stopCaptureWithCompletionHandler: &*completion];
+            }
+            done_rx
+        };
+
+        if let Some(err) = done_rx.await? {
+            return Err(AppleError::ScreenCaptureFailed(
+                err.inner.to_friendly_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+async fn shareable_content() -> Result<UnsafeSendRetained<AnyObject>> {
+    let rx = {
+        let (tx, rx) = oneshot::channel();
+        let tx = Mutex::new(Some(tx));
+        let handler = RcBlock::new(move |content: *mut AnyObject, error: *mut NSError| {
+            if let Ok(mut tx) = tx.lock()
+                && let Some(tx) = tx.take()
+            {
+                let result = match unsafe { Retained::retain(content) } {
+                    Some(content) => Ok(UnsafeSendRetained::from(content)),
+                    None => Err(unsafe { Retained::retain(error) }
+                        .map(|err| err.to_friendly_string())
+                        .unwrap_or_default()),
+                };
+                _ = tx.send(result);
+            }
+        });
+        unsafe {
+            let _: () = msg_send![
+                class!(SCShareableContent),
+                getShareableContentWithCompletionHandler: &*handler
+            ];
+        }
+        rx
+    };
+
+    rx.await?.map_err(AppleError::ScreenCaptureFailed)
+}
+
+fn content_filter(content: &AnyObject, target: ScreenCaptureTarget) -> Result<Retained<AnyObject>> {
+    let filter: Allocated<AnyObject> = unsafe { msg_send![class!(SCContentFilter), alloc] };
+    match target {
+        ScreenCaptureTarget::CurrentWindow => {
+            let windows: Retained<NSArray<AnyObject>> = unsafe { msg_send![content, windows] };
+            let pid = std::process::id() as i32;
+            let window = windows
+                .iter()
+                .filter(|window| {
+                    let on_screen: Bool = unsafe { msg_send![&**window, isOnScreen] };
+                    let app: Option<Retained<AnyObject>> =
+                        unsafe { msg_send![&**window, owningApplication] };
+                    on_screen.as_bool()
+                        && app.is_some_and(|app| {
+                            let app_pid: i32 = unsafe { msg_send![&app, processID] };
+                            app_pid == pid
+                        })
+                })
+                .max_by(|a, b| {
+                    let area = |window: &AnyObject| {
+                        let frame: CGRect = unsafe { msg_send![window, frame] };
+                        frame.size.width * frame.size.height
+                    };
+                    area(a).total_cmp(&area(b))
+                })
+                .ok_or(AppleError::ScreenCaptureTargetNotFound)?;
+            Ok(unsafe { msg_send![filter, initWithDesktopIndependentWindow: &*window] })
+        }
+        ScreenCaptureTarget::MainDisplay => {
+            let displays: Retained<NSArray<AnyObject>> = unsafe { msg_send![content, displays] };
+            let display = displays
+                .firstObject()
+                .ok_or(AppleError::ScreenCaptureTargetNotFound)?;
+            let excluded = NSArray::<AnyObject>::new();
+            Ok(unsafe {
+                msg_send![
+                    filter,
+                    initWithDisplay: &*display,
+                    excludingWindows: &*excluded
+                ]
+            })
+        }
+    }
+}
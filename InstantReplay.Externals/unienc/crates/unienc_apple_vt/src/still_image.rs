@@ -0,0 +1,132 @@
+use std::ptr::NonNull;
+
+use objc2_core_foundation::{CFDictionary, CFMutableData, CFNumber, CFRetained, CFString, CFType};
+use objc2_core_graphics::CGImage;
+use objc2_core_video::{
+    CVPixelBuffer, CVPixelBufferGetBaseAddress, CVPixelBufferGetBytesPerRow,
+    CVPixelBufferGetHeight, CVPixelBufferGetWidth, CVPixelBufferLockBaseAddress,
+    CVPixelBufferLockFlags, CVPixelBufferUnlockBaseAddress,
+};
+use objc2_image_io::{CGImageDestination, kCGImageDestinationLossyCompressionQuality};
+use objc2_video_toolbox::VTCreateCGImageFromCVPixelBuffer;
+use unienc_common::{
+    CommonError, StillImage, StillImageCapture, StillImageFormat, VideoEncoderOptions, VideoFrame,
+    VideoSample,
+};
+
+use crate::error::{AppleError, OsStatusExt, Result};
+use crate::{MetalTexture, allocator, metal};
+
+/// Captures blit sources through the Metal blit pass and encodes them with ImageIO, or copies the
+/// pixels out of the blitted buffer for [`StillImageFormat::Bgra32`].
+pub struct ImageIOStillImageCapture {
+    width: u32,
+    height: u32,
+    format: StillImageFormat,
+}
+
+impl ImageIOStillImageCapture {
+    pub fn new<V: VideoEncoderOptions>(options: &V, format: StillImageFormat) -> Self {
+        Self {
+            width: options.width(),
+            height: options.height(),
+            format,
+        }
+    }
+}
+
+impl StillImageCapture for ImageIOStillImageCapture {
+    type Data = VideoSample<MetalTexture>;
+
+    async fn capture(&mut self, data: Self::Data) -> unienc_common::Result<StillImage> {
+        let VideoFrame::BlitSource {
+            texture_token,
+            options,
+            event_issuer,
+            ..
+        } = data.frame
+        else {
+            return Err(CommonError::StillImageRequiresBlitSource);
+        };
+
+        let texture = metal::blit_texture(
+            event_issuer,
+            texture_token,
+            self.width,
+            self.height,
+            options,
+        )
+        .await?;
+
+        Ok(StillImage {
+            data: encode(&texture.pixel_buffer(), self.format)?,
+            width: self.width,
+            height: self.height,
+            timestamp: data.timestamp,
+        })
+    }
+}
+
+fn encode(pixel_buffer: &CVPixelBuffer, format: StillImageFormat) -> Result<Vec<u8>> {
+    let (type_identifier, quality) = match format {
+        StillImageFormat::Png => ("public.png", None),
+        StillImageFormat::Jpeg { quality } => ("public.jpeg", Some(quality.clamp(0.0, 1.0))),
+        StillImageFormat::Bgra32 => return read_bgra(pixel_buffer),
+    };
+
+    let mut image: *mut CGImage = std::ptr::null_mut();
+    unsafe {
+        VTCreateCGImageFromCVPixelBuffer(
+            pixel_buffer,
+            None,
+            NonNull::new(&mut image).ok_or(AppleError::NonNullCreationFailed)?,
+        )
+    }
+    .to_result()?;
+    let image = unsafe {
+        CFRetained::from_raw(NonNull::new(image).ok_or(AppleError::ImageDestinationFailed)?)
+    };
+
+    let data =
+        CFMutableData::new(allocator::default(), 0).ok_or(AppleError::ImageDestinationFailed)?;
+    let destination = unsafe {
+        CGImageDestination::with_data(&data, &CFString::from_static_str(type_identifier), 1, None)
+    }
+    .ok_or(AppleError::ImageDestinationFailed)?;
+
+    let quality = quality.map(CFNumber::new_f32);
+    let properties = quality.as_ref().map(|quality| {
+        let keys: [&CFString; 1] = [unsafe { kCGImageDestinationLossyCompressionQuality }];
+        let values: [&CFType; 1] = [quality];
+        CFDictionary::from_slices(&keys, &values)
+    });
+
+    unsafe {
+        destination.add_image(&image, properties.as_ref().map(|p| p.as_opaque()));
+        if !destination.finalize() {
+            return Err(AppleError::ImageDestinationFailed);
+        }
+    }
+
+    Ok(data.to_vec())
+}
+
+/// Copy the BGRA pixels into a tightly packed buffer, dropping row padding
+fn read_bgra(pixel_buffer: &CVPixelBuffer) -> Result<Vec<u8>> {
+    let row_len = CVPixelBufferGetWidth(pixel_buffer) * 4;
+    let mut data = vec![0u8; row_len * CVPixelBufferGetHeight(pixel_buffer)];
+    unsafe {
+        CVPixelBufferLockBaseAddress(pixel_buffer, CVPixelBufferLockFlags::ReadOnly).to_result()?;
+        let base = CVPixelBufferGetBaseAddress(pixel_buffer) as *const u8;
+        let bytes_per_row = CVPixelBufferGetBytesPerRow(pixel_buffer);
+        for (y, row) in data.chunks_exact_mut(row_len).enumerate() {
+            row.copy_from_slice(std::slice::from_raw_parts(
+                base.add(y * bytes_per_row),
+                row_len,
+            ));
+        }
+        CVPixelBufferUnlockBaseAddress(pixel_buffer, CVPixelBufferLockFlags::ReadOnly)
+            .to_result()?;
+    }
+    Ok(data)
+}
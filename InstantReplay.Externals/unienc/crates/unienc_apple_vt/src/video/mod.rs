@@ -8,21 +8,27 @@ use objc2_core_foundation::{
     CFBoolean, CFDictionary, CFNumber, CFString, CFType, kCFBooleanFalse, kCFBooleanTrue,
 };
 use objc2_core_media::{
-    CMSampleBuffer, CMTime, kCMSampleAttachmentKey_NotSync, kCMTimeInvalid, kCMVideoCodecType_H264,
+    CMSampleBuffer, CMTime, CMVideoCodecType, kCMSampleAttachmentKey_NotSync, kCMTimeInvalid,
+    kCMVideoCodecType_AppleProRes422HQ, kCMVideoCodecType_AppleProRes4444, kCMVideoCodecType_H264,
+    kCMVideoCodecType_HEVCWithAlpha,
 };
 use objc2_core_video::{CVPixelBuffer, CVPixelBufferCreateWithBytes, kCVPixelFormatType_32BGRA};
 use objc2_video_toolbox::{
     VTCompressionSession, VTEncodeInfoFlags, VTSessionSetProperty,
     kVTCompressionPropertyKey_AllowFrameReordering, kVTCompressionPropertyKey_AverageBitRate,
-    kVTCompressionPropertyKey_RealTime, kVTInvalidSessionErr,
+    kVTCompressionPropertyKey_PrioritizeEncodingSpeedOverQuality,
+    kVTCompressionPropertyKey_RealTime, kVTEncodeFrameOptionKey_ForceKeyFrame,
+    kVTInvalidSessionErr,
 };
 use tokio::sync::mpsc;
 use unienc_common::{
-    EncodedData, Encoder, EncoderInput, EncoderOutput, VideoSample, buffer::SharedBuffer,
+    EncodedData, Encoder, EncoderInput, EncoderOutput, LatencyMode, VideoCodec, VideoSample,
+    buffer::SharedBuffer,
 };
 
-use crate::{MetalTexture, common::UnsafeSendRetained, metal};
-use unienc_common::TryFromUnityNativeTexturePointer;
+#[cfg(feature = "blit")]
+use crate::metal;
+use crate::{MetalTexture, common::UnsafeSendRetained};
 
 pub struct VideoToolboxEncoder {
     input: VideoToolboxEncoderInput,
@@ -34,6 +40,10 @@ pub struct VideoToolboxEncoderInput {
     width: u32,
     height: u32,
     bitrate: u32,
+    latency_mode: LatencyMode,
+    codec: CMVideoCodecType,
+    tiers: Vec<VideoToolboxEncoderInput>,
+    keyframe_requested: bool,
 }
 
 struct CompressionSession {
@@ -96,6 +106,10 @@ impl EncodedData for VideoEncodedData {
         }
     }
 
+    fn size(&self) -> usize {
+        unsafe { self.sample_buffer.total_sample_size() }
+    }
+
     fn set_timestamp(&mut self, timestamp: f64) {
         unsafe {
             self.sample_buffer
@@ -157,7 +171,7 @@ impl EncoderInput for VideoToolboxEncoderInput {
                         kCVPixelFormatType_32BGRA,
                         NonNull::new(pixel_data_ptr as *mut c_void)
                             .ok_or(AppleError::NonNullCreationFailed)?,
-                        (bgra32.width * 4) as usize,
+                        bgra32.stride as usize,
                         Some(release_pixel_buffer),
                         buffer_boxed_raw as *mut _,
                         None,
@@ -172,61 +186,65 @@ impl EncoderInput for VideoToolboxEncoderInput {
 
                 unsafe { Retained::from_raw(buffer) }.ok_or(AppleError::PixelBufferNull)?
             }
+            #[cfg(not(feature = "blit"))]
+            unienc_common::VideoFrame::BlitSource { .. } => {
+                return Err(unienc_common::CommonError::BlitNotSupported);
+            }
+            #[cfg(feature = "blit")]
             unienc_common::VideoFrame::BlitSource {
                 texture_token,
                 width: _,
                 height: _,
                 graphics_format: _,
-                flip_vertically,
-                is_gamma_workflow,
+                options,
                 event_issuer,
                 _phantom,
             } => {
                 let width = self.width;
                 let height = self.height;
 
-                let (tx, rx) = tokio::sync::oneshot::channel();
-                event_issuer.issue_graphics_event(
-                    Box::new(move |native_texture_ptr| {
-                        let r = MetalTexture::try_from_unity_native_texture_ptr(native_texture_ptr)
-                            .map_err(|_| AppleError::MetalTextureRetainFailed)
-                            .and_then(|texture| {
-                                metal::custom_blit(
-                                    &texture.texture,
-                                    width,
-                                    height,
-                                    flip_vertically,
-                                    is_gamma_workflow,
-                                )
-                            });
-                        tx.send(r)
-                            .map_err(|_e| AppleError::BlitFutureSendFailed)
-                            .unwrap();
-                    }),
-                    *crate::metal::EVENT_ID
-                        .get()
-                        .ok_or(AppleError::EventIdNotReserved)?,
-                    texture_token,
-                );
-
-                let texture = rx
-                    .await
-                    .map_err(AppleError::from)?? // failed to receive
-                    // failed to issue blit
-                    .await?; // blit failed
-                texture.pixel_buffer()
+                metal::blit_texture(event_issuer, texture_token, width, height, options)
+                    .await?
+                    .pixel_buffer()
             }
         };
 
+        Ok(self.encode_pixel_buffer(&buffer, data.timestamp)?)
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.keyframe_requested = true;
+        for tier in &mut self.tiers {
+            tier.request_keyframe();
+        }
+        true
+    }
+}
+
+impl VideoToolboxEncoderInput {
+    /// Encodes every frame encoded afterwards with `tier` too, from the same pixel buffer so that a
+    /// blit is shared. The compression session of `tier` scales it to its own size. A tier that
+    /// fails to encode a frame is dropped without failing the push.
+    pub fn add_tier(&mut self, tier: VideoToolboxEncoderInput) {
+        self.tiers.push(tier);
+    }
+
+    /// Encodes a pixel buffer produced outside of Unity, such as one delivered by ReplayKit.
+    pub fn encode_pixel_buffer(&mut self, buffer: &CVPixelBuffer, timestamp: f64) -> Result<()> {
         let mut retry = 0;
+        let properties = std::mem::take(&mut self.keyframe_requested).then(|| {
+            let keys: [&CFString; 1] = unsafe { [kVTEncodeFrameOptionKey_ForceKeyFrame] };
+            let values: [&CFType; 1] = [unsafe { kCFBooleanTrue }.expect("kCFBooleanTrue is null")];
+            CFDictionary::from_slices(&keys, &values)
+        });
 
         loop {
             let res = unsafe {
                 self.session.inner.encode_frame(
-                    &buffer,
-                    CMTime::with_seconds(data.timestamp, 720),
+                    buffer,
+                    CMTime::with_seconds(timestamp, 720),
                     kCMTimeInvalid,
-                    None,
+                    properties.as_ref().map(|p| p.as_opaque()),
                     std::ptr::null_mut(),
                     std::ptr::null_mut(),
                 )
@@ -236,14 +254,29 @@ impl EncoderInput for VideoToolboxEncoderInput {
                 // VTCompressionSession turns invalid when the app enters background on iOS
                 // retrying once
                 retry += 1;
-                self.session =
-                    CompressionSession::new(self.width, self.height, self.bitrate, &*self.tx)?;
+                self.session = CompressionSession::new(
+                    self.width,
+                    self.height,
+                    self.bitrate,
+                    self.latency_mode,
+                    self.codec,
+                    &*self.tx,
+                )?;
                 continue;
             }
 
             break res.to_result()?;
         }
 
+        // a tier that fails is dropped, which ends its output, instead of failing the push
+        self.tiers
+            .retain_mut(|tier| match tier.encode_pixel_buffer(buffer, timestamp) {
+                Ok(()) => true,
+                Err(err) => {
+                    unienc_common::log!("Dropping a video tier that failed to encode: {err}");
+                    false
+                }
+            });
         Ok(())
     }
 }
@@ -277,6 +310,8 @@ impl CompressionSession {
         width: u32,
         height: u32,
         bitrate: u32,
+        latency_mode: LatencyMode,
+        codec: CMVideoCodecType,
         tx: *const mpsc::Sender<VideoEncodedData>,
     ) -> Result<Self> {
         let mut session: *mut VTCompressionSession = std::ptr::null_mut();
@@ -286,7 +321,7 @@ impl CompressionSession {
                 allocator::default(),
                 width as i32,
                 height as i32,
-                kCMVideoCodecType_H264,
+                codec,
                 None,
                 None,
                 None,
@@ -299,49 +334,99 @@ impl CompressionSession {
 
         let session =
             unsafe { Retained::from_raw(session).ok_or(AppleError::CompressionSessionNull)? };
+        let realtime = match latency_mode {
+            LatencyMode::Realtime => unsafe { kCFBooleanTrue },
+            LatencyMode::Offline => unsafe { kCFBooleanFalse },
+        };
         unsafe {
             VTSessionSetProperty(
                 &session,
                 kVTCompressionPropertyKey_RealTime,
-                kCFBooleanTrue.map(|b| b as &CFType),
+                realtime.map(|b| b as &CFType),
             )
         }
         .to_result()?;
-        unsafe {
-            VTSessionSetProperty(
-                &session,
-                kVTCompressionPropertyKey_AllowFrameReordering,
-                kCFBooleanFalse.map(|b| b as &CFType),
-            )
+        if latency_mode == LatencyMode::Offline {
+            // macOS 12 / iOS 15 and later; older systems keep their default
+            let _ = unsafe {
+                VTSessionSetProperty(
+                    &session,
+                    kVTCompressionPropertyKey_PrioritizeEncodingSpeedOverQuality,
+                    kCFBooleanFalse.map(|b| b as &CFType),
+                )
+            };
         }
-        .to_result()?;
-        unsafe {
-            VTSessionSetProperty(
-                &session,
-                kVTCompressionPropertyKey_AverageBitRate,
-                Some(&CFNumber::new_i32(bitrate as i32)),
-            )
+        // ProRes has no inter frames to reorder and its bitrate follows from the profile, and the
+        // encoder rejects both properties
+        if !is_prores(codec) {
+            unsafe {
+                VTSessionSetProperty(
+                    &session,
+                    kVTCompressionPropertyKey_AllowFrameReordering,
+                    kCFBooleanFalse.map(|b| b as &CFType),
+                )
+            }
+            .to_result()?;
+            unsafe {
+                VTSessionSetProperty(
+                    &session,
+                    kVTCompressionPropertyKey_AverageBitRate,
+                    Some(&CFNumber::new_i32(bitrate as i32)),
+                )
+            }
+            .to_result()?;
         }
-        .to_result()?;
 
         Ok(CompressionSession { inner: session })
     }
 }
 
+/// Codec type of the VideoToolbox session for the options.
+pub(crate) fn codec_type(options: &impl unienc_common::VideoEncoderOptions) -> CMVideoCodecType {
+    // BGRA frames keep the alpha the blit sampled from the source, which HEVC encodes only with
+    // its alpha variant and ProRes only with the 4444 profile
+    match (options.codec(), options.preserve_alpha()) {
+        (VideoCodec::ProRes, false) => kCMVideoCodecType_AppleProRes422HQ,
+        (VideoCodec::ProRes, true) => kCMVideoCodecType_AppleProRes4444,
+        (_, true) => kCMVideoCodecType_HEVCWithAlpha,
+        (_, false) => kCMVideoCodecType_H264,
+    }
+}
+
+pub(crate) fn is_prores(codec: CMVideoCodecType) -> bool {
+    [
+        kCMVideoCodecType_AppleProRes422HQ,
+        kCMVideoCodecType_AppleProRes4444,
+    ]
+    .contains(&codec)
+}
+
 impl VideoToolboxEncoder {
     pub fn new(options: &impl unienc_common::VideoEncoderOptions) -> Result<Self> {
         let (tx, rx) = mpsc::channel(32);
         let tx = Box::new(tx);
 
         let (width, height, bitrate) = (options.width(), options.height(), options.bitrate());
+        let codec = codec_type(options);
 
         Ok(VideoToolboxEncoder {
             input: VideoToolboxEncoderInput {
-                session: CompressionSession::new(width, height, bitrate, &*tx)?,
+                session: CompressionSession::new(
+                    width,
+                    height,
+                    bitrate,
+                    options.latency_mode(),
+                    codec,
+                    &*tx,
+                )?,
                 tx,
                 width,
                 height,
                 bitrate,
+                latency_mode: options.latency_mode(),
+                codec,
+                tiers: Vec::new(),
+                keyframe_requested: false,
             },
             output: VideoToolboxEncoderOutput { rx },
         })
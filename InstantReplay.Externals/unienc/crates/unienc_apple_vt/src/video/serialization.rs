@@ -6,15 +6,25 @@ use std::{
 use crate::allocator;
 use bincode::{Decode, Encode};
 use objc2::rc::Retained;
-use objc2_core_foundation::{CFMutableDictionary, CFString, CFType, kCFBooleanTrue};
+use objc2_core_foundation::{
+    CFBoolean, CFDictionary, CFMutableDictionary, CFString, CFType, kCFBooleanTrue,
+};
 use objc2_core_media::{
     CMBlockBuffer, CMFormatDescription, CMSampleBuffer, CMSampleTimingInfo, CMTime, CMTimeFlags,
-    CMVideoFormatDescription, CMVideoFormatDescriptionCreateFromH264ParameterSets,
-    CMVideoFormatDescriptionGetH264ParameterSetAtIndex, kCMBlockBufferAssureMemoryNowFlag,
-    kCMSampleAttachmentKey_NotSync, kCMVideoCodecType_H264,
+    CMVideoFormatDescription, CMVideoFormatDescriptionCreate,
+    CMVideoFormatDescriptionCreateFromH264ParameterSets,
+    CMVideoFormatDescriptionCreateFromHEVCParameterSets, CMVideoFormatDescriptionGetDimensions,
+    CMVideoFormatDescriptionGetH264ParameterSetAtIndex,
+    CMVideoFormatDescriptionGetHEVCParameterSetAtIndex, kCMBlockBufferAssureMemoryNowFlag,
+    kCMFormatDescriptionExtension_ContainsAlphaChannel, kCMSampleAttachmentKey_NotSync,
+    kCMVideoCodecType_AppleProRes422HQ, kCMVideoCodecType_AppleProRes4444, kCMVideoCodecType_H264,
+    kCMVideoCodecType_HEVC,
 };
 
-use crate::{error::OsStatusExt, video::VideoEncodedData};
+use crate::{
+    error::{AppleError, OsStatusExt},
+    video::VideoEncodedData,
+};
 
 #[derive(Encode, Decode, Clone, Copy, Debug, PartialEq)]
 struct CMTimeForSerialization {
@@ -78,7 +88,14 @@ struct VideoEncodedDataForSerialization {
     data_buffer: Option<Vec<u8>>,
     timing_info: CMSampleTimingInfoForSerialization,
     not_sync: bool,
-    parameters: Option<H264ParameterSet>,
+    parameters: Option<ParameterSets>,
+}
+
+#[derive(Encode, Decode)]
+enum ParameterSets {
+    H264(H264ParameterSet),
+    Hevc(HevcParameterSets),
+    ProRes(ProResFormat),
 }
 
 #[derive(Encode, Decode)]
@@ -88,6 +105,22 @@ struct H264ParameterSet {
     pps: Vec<u8>,
 }
 
+/// VPS, SPS and PPS in order, including the ones of the alpha layer when encoded with alpha.
+#[derive(Encode, Decode)]
+struct HevcParameterSets {
+    nal_unit_header_length: i32,
+    parameter_sets: Vec<Vec<u8>>,
+    contains_alpha: bool,
+}
+
+/// ProRes frames are self-contained and their format has no parameter sets.
+#[derive(Encode, Decode)]
+struct ProResFormat {
+    codec_type: u32,
+    width: i32,
+    height: i32,
+}
+
 impl Encode for VideoEncodedData {
     fn encode<E: bincode::enc::Encoder>(
         &self,
@@ -155,55 +188,36 @@ impl Encode for VideoEncodedData {
         // format description
         let format_desc = unsafe { CMSampleBuffer::format_description(&self.sample_buffer) };
 
-        let parameters = format_desc.map(|format_desc| {
-            let Ok(format_desc) = format_desc.downcast::<CMVideoFormatDescription>() else {
-                todo!()
-            };
-            if unsafe { format_desc.media_sub_type() } != kCMVideoCodecType_H264 {
-                todo!()
-            }
-            let mut sps_ptr: *const u8 = std::ptr::null();
-            let mut sps_size: usize = 0;
-            let mut pps_ptr: *const u8 = std::ptr::null();
-            let mut pps_size: usize = 0;
-
-            let mut count: usize = 0;
-            let mut nalu_header_length: c_int = 0;
-
-            unsafe {
-                CMVideoFormatDescriptionGetH264ParameterSetAtIndex(
-                    &format_desc,
-                    0,
-                    &mut sps_ptr,
-                    &mut sps_size,
-                    &mut count,
-                    &mut nalu_header_length,
-                )
-                .to_result()
-                .unwrap()
-            };
-            unsafe {
-                CMVideoFormatDescriptionGetH264ParameterSetAtIndex(
-                    &format_desc,
-                    1,
-                    &mut pps_ptr,
-                    &mut pps_size,
-                    &mut count,
-                    &mut nalu_header_length,
-                )
-                .to_result()
-                .unwrap()
-            };
-
-            let sps = unsafe { std::slice::from_raw_parts(sps_ptr, sps_size) }.to_vec();
-            let pps = unsafe { std::slice::from_raw_parts(pps_ptr, pps_size) }.to_vec();
-
-            H264ParameterSet {
-                nal_unit_header_length: nalu_header_length as i32,
-                sps,
-                pps,
-            }
-        });
+        let parameters = format_desc
+            .map(|format_desc| {
+                let Ok(format_desc) = format_desc.downcast::<CMVideoFormatDescription>() else {
+                    return Err(bincode::error::EncodeError::OtherString(
+                        "format description is not a video format description".to_string(),
+                    ));
+                };
+                match unsafe { format_desc.media_sub_type() } {
+                    kCMVideoCodecType_H264 => {
+                        Ok(ParameterSets::H264(h264_parameter_set(&format_desc)))
+                    }
+                    kCMVideoCodecType_HEVC => {
+                        Ok(ParameterSets::Hevc(hevc_parameter_sets(&format_desc)?))
+                    }
+                    codec_type @ (kCMVideoCodecType_AppleProRes422HQ
+                    | kCMVideoCodecType_AppleProRes4444) => {
+                        let dimensions =
+                            unsafe { CMVideoFormatDescriptionGetDimensions(&format_desc) };
+                        Ok(ParameterSets::ProRes(ProResFormat {
+                            codec_type,
+                            width: dimensions.width,
+                            height: dimensions.height,
+                        }))
+                    }
+                    sub_type => Err(bincode::error::EncodeError::OtherString(format!(
+                        "unsupported codec: {sub_type:#x}"
+                    ))),
+                }
+            })
+            .transpose()?;
 
         VideoEncodedDataForSerialization {
             data_buffer,
@@ -215,16 +229,156 @@ impl Encode for VideoEncodedData {
     }
 }
 
+fn h264_parameter_set(format_desc: &CMVideoFormatDescription) -> H264ParameterSet {
+    let mut sps_ptr: *const u8 = std::ptr::null();
+    let mut sps_size: usize = 0;
+    let mut pps_ptr: *const u8 = std::ptr::null();
+    let mut pps_size: usize = 0;
+
+    let mut count: usize = 0;
+    let mut nalu_header_length: c_int = 0;
+
+    unsafe {
+        CMVideoFormatDescriptionGetH264ParameterSetAtIndex(
+            format_desc,
+            0,
+            &mut sps_ptr,
+            &mut sps_size,
+            &mut count,
+            &mut nalu_header_length,
+        )
+        .to_result()
+        .unwrap()
+    };
+    unsafe {
+        CMVideoFormatDescriptionGetH264ParameterSetAtIndex(
+            format_desc,
+            1,
+            &mut pps_ptr,
+            &mut pps_size,
+            &mut count,
+            &mut nalu_header_length,
+        )
+        .to_result()
+        .unwrap()
+    };
+
+    let sps = unsafe { std::slice::from_raw_parts(sps_ptr, sps_size) }.to_vec();
+    let pps = unsafe { std::slice::from_raw_parts(pps_ptr, pps_size) }.to_vec();
+
+    H264ParameterSet {
+        nal_unit_header_length: nalu_header_length as i32,
+        sps,
+        pps,
+    }
+}
+
+fn hevc_parameter_sets(
+    format_desc: &CMVideoFormatDescription,
+) -> std::result::Result<HevcParameterSets, bincode::error::EncodeError> {
+    let mut count: usize = 0;
+    let mut nalu_header_length: c_int = 0;
+    let mut parameter_sets = Vec::new();
+
+    // the count is only known after reading the first one
+    let mut index = 0;
+    while index == 0 || index < count {
+        let mut ptr: *const u8 = std::ptr::null();
+        let mut size: usize = 0;
+        unsafe {
+            CMVideoFormatDescriptionGetHEVCParameterSetAtIndex(
+                format_desc,
+                index,
+                &mut ptr,
+                &mut size,
+                &mut count,
+                &mut nalu_header_length,
+            )
+            .to_result()
+            .map_err(|err| {
+                bincode::error::EncodeError::OtherString(format!(
+                    "Failed to get HEVC parameter set {index}: {err:?}"
+                ))
+            })?
+        };
+        parameter_sets.push(unsafe { std::slice::from_raw_parts(ptr, size) }.to_vec());
+        index += 1;
+    }
+
+    let contains_alpha =
+        unsafe { format_desc.extension(kCMFormatDescriptionExtension_ContainsAlphaChannel) }
+            .is_some_and(|value| {
+                value
+                    .downcast::<CFBoolean>()
+                    .is_ok_and(|value| value.as_bool())
+            });
+
+    Ok(HevcParameterSets {
+        nal_unit_header_length: nalu_header_length as i32,
+        parameter_sets,
+        contains_alpha,
+    })
+}
+
+fn prores_format_description(
+    format: &ProResFormat,
+) -> std::result::Result<Retained<CMVideoFormatDescription>, String> {
+    let mut format_description_out: *const CMFormatDescription = std::ptr::null_mut();
+    unsafe {
+        CMVideoFormatDescriptionCreate(
+            allocator::default(),
+            format.codec_type,
+            format.width,
+            format.height,
+            None,
+            NonNull::new(&mut format_description_out).unwrap(),
+        )
+        .to_result()
+        .map_err(|err| format!("Failed to create CMVideoFormatDescription: {:?}", err))?;
+        Ok(Retained::from_raw(format_description_out as *mut CMVideoFormatDescription).unwrap())
+    }
+}
+
 impl Decode<()> for VideoEncodedData {
     fn decode<D: bincode::de::Decoder<Context = ()>>(
         decoder: &mut D,
     ) -> std::result::Result<Self, bincode::error::DecodeError> {
+        VideoEncodedData::from_serialization(VideoEncodedDataForSerialization::decode(decoder)?)
+            .map_err(bincode::error::DecodeError::OtherString)
+    }
+}
+
+impl VideoEncodedData {
+    /// Creates a sample from a single H.264 access unit with 4-byte length-prefixed NAL units.
+    pub(crate) fn from_h264(
+        data: Vec<u8>,
+        timing_info: CMSampleTimingInfo,
+        not_sync: bool,
+        sps: Vec<u8>,
+        pps: Vec<u8>,
+    ) -> crate::Result<Self> {
+        VideoEncodedData::from_serialization(VideoEncodedDataForSerialization {
+            data_buffer: Some(data),
+            timing_info: timing_info.into(),
+            not_sync,
+            parameters: Some(ParameterSets::H264(H264ParameterSet {
+                nal_unit_header_length: 4,
+                sps,
+                pps,
+            })),
+        })
+        .map_err(AppleError::Other)
+    }
+
+    fn from_serialization(
+        value: VideoEncodedDataForSerialization,
+    ) -> std::result::Result<Self, String> {
         let VideoEncodedDataForSerialization {
             data_buffer,
             timing_info,
             not_sync,
             mut parameters,
-        } = VideoEncodedDataForSerialization::decode(decoder)?;
+        } = value;
 
         let sample_size = data_buffer
             .as_ref()
@@ -249,12 +403,7 @@ impl Decode<()> for VideoEncodedData {
                         NonNull::new(&mut block_buffer).unwrap(),
                     )
                     .to_result()
-                    .map_err(|err| {
-                        bincode::error::DecodeError::OtherString(format!(
-                            "Failed to create CMBlockBuffer: {:?}",
-                            err
-                        ))
-                    })?;
+                    .map_err(|err| format!("Failed to create CMBlockBuffer: {:?}", err))?;
                     Retained::from_raw(block_buffer).unwrap()
                 };
 
@@ -292,43 +441,71 @@ impl Decode<()> for VideoEncodedData {
         };
 
         // format
-        let format_description = {
-            let mut parameter_set_pointers = parameters.as_mut().map_or(vec![], |parameters| {
-                vec![
-                    NonNull::new(&mut parameters.sps[0]).unwrap(),
-                    NonNull::new(&mut parameters.pps[0]).unwrap(),
-                ]
-            });
-
-            let mut parameter_set_sizes = parameters.as_mut().map_or(vec![], |parameters| {
-                vec![parameters.sps.len(), parameters.pps.len()]
-            });
+        let format_description = match &parameters {
+            Some(ParameterSets::ProRes(format)) => prores_format_description(format)?,
+            _ => {
+                let is_hevc = matches!(parameters, Some(ParameterSets::Hevc(_)));
+                // players look for the extension to composite the alpha layer
+                let extensions = match &parameters {
+                    Some(ParameterSets::Hevc(parameters)) if parameters.contains_alpha => {
+                        let keys: [&CFString; 1] =
+                            [unsafe { kCMFormatDescriptionExtension_ContainsAlphaChannel }];
+                        let values: [&CFType; 1] = [unsafe { kCFBooleanTrue.unwrap() }];
+                        Some(CFDictionary::from_slices(&keys, &values))
+                    }
+                    _ => None,
+                };
+                let (mut parameter_sets, nal_unit_header_length) = match parameters.as_mut() {
+                    Some(ParameterSets::H264(parameters)) => (
+                        vec![&mut parameters.sps, &mut parameters.pps],
+                        parameters.nal_unit_header_length,
+                    ),
+                    Some(ParameterSets::Hevc(parameters)) => (
+                        parameters.parameter_sets.iter_mut().collect(),
+                        parameters.nal_unit_header_length,
+                    ),
+                    None => (vec![], 0),
+                };
+                let mut parameter_set_pointers = parameter_sets
+                    .iter_mut()
+                    .map(|parameter_set| NonNull::new(&mut parameter_set[0]).unwrap())
+                    .collect::<Vec<_>>();
+                let mut parameter_set_sizes = parameter_sets
+                    .iter()
+                    .map(|parameter_set| parameter_set.len())
+                    .collect::<Vec<_>>();
+                let mut format_description_out: *const CMFormatDescription = std::ptr::null_mut();
 
-            let nal_unit_header_length = parameters
-                .as_mut()
-                .map_or(0, |parameters| parameters.nal_unit_header_length);
-            let mut format_description_out: *const CMFormatDescription = std::ptr::null_mut();
-
-            unsafe {
-                CMVideoFormatDescriptionCreateFromH264ParameterSets(
-                    allocator::default(),
-                    parameter_set_pointers.len(),
-                    NonNull::new(&mut parameter_set_pointers.as_mut_slice()[0]).unwrap(),
-                    NonNull::new(&mut parameter_set_sizes.as_mut_slice()[0]).unwrap(),
-                    nal_unit_header_length,
-                    NonNull::new(&mut format_description_out).unwrap(),
-                )
-                .to_result()
-                .map_err(|err| {
-                    bincode::error::DecodeError::OtherString(format!(
-                        "Failed to create CMVideoFormatDescription: {:?}",
-                        err
-                    ))
-                })?;
-            };
-            drop(parameters);
-            unsafe {
-                Retained::from_raw(format_description_out as *mut CMVideoFormatDescription).unwrap()
+                unsafe {
+                    match is_hevc {
+                        true => CMVideoFormatDescriptionCreateFromHEVCParameterSets(
+                            allocator::default(),
+                            parameter_set_pointers.len(),
+                            NonNull::new(&mut parameter_set_pointers.as_mut_slice()[0]).unwrap(),
+                            NonNull::new(&mut parameter_set_sizes.as_mut_slice()[0]).unwrap(),
+                            nal_unit_header_length,
+                            extensions.as_ref().map(|e| e.as_opaque()),
+                            NonNull::new(&mut format_description_out).unwrap(),
+                        ),
+                        false => CMVideoFormatDescriptionCreateFromH264ParameterSets(
+                            allocator::default(),
+                            parameter_set_pointers.len(),
+                            NonNull::new(&mut parameter_set_pointers.as_mut_slice()[0]).unwrap(),
+                            NonNull::new(&mut parameter_set_sizes.as_mut_slice()[0]).unwrap(),
+                            nal_unit_header_length,
+                            NonNull::new(&mut format_description_out).unwrap(),
+                        ),
+                    }
+                    .to_result()
+                    .map_err(|err| {
+                        format!("Failed to create CMVideoFormatDescription: {:?}", err)
+                    })?;
+                };
+                drop(parameter_sets);
+                unsafe {
+                    Retained::from_raw(format_description_out as *mut CMVideoFormatDescription)
+                        .unwrap()
+                }
             }
         };
         let sample_buffer = unsafe {
@@ -353,12 +530,7 @@ impl Decode<()> for VideoEncodedData {
                 NonNull::new(&mut sample_buffer_out).unwrap(),
             )
             .to_result()
-            .map_err(|err| {
-                bincode::error::DecodeError::OtherString(format!(
-                    "Failed to create CMSampleBuffer: {:?}",
-                    err
-                ))
-            })?;
+            .map_err(|err| format!("Failed to create CMSampleBuffer: {:?}", err))?;
 
             Retained::from_raw(sample_buffer_out).unwrap()
         };
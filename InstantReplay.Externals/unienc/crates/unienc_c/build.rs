@@ -4,7 +4,18 @@ fn main() {
     common_builder()
         .input_extern_file("src/lib.rs")
         .input_extern_file("src/api/audio.rs")
+        .input_extern_file("src/api/clock.rs")
+        .input_extern_file("src/api/decode.rs")
+        .input_extern_file("src/api/diagnostics.rs")
+        .input_extern_file("src/api/frame_stats.rs")
+        .input_extern_file("src/api/jpeg_spool.rs")
         .input_extern_file("src/api/mux.rs")
+        .input_extern_file("src/api/passthrough.rs")
+        .input_extern_file("src/api/replay_buffer.rs")
+        .input_extern_file("src/api/replay_data.rs")
+        .input_extern_file("src/api/replay_kit.rs")
+        .input_extern_file("src/api/screen_capture.rs")
+        .input_extern_file("src/api/still_image.rs")
         .input_extern_file("src/api/video.rs")
         .input_extern_file("src/api/runtime.rs")
         .input_extern_file("src/api/encoding_system.rs")
@@ -1,8 +1,14 @@
 use std::ffi::c_void;
+use std::sync::Arc;
 
+use super::deadline::bounded_push;
 use crate::*;
 use tokio::sync::Mutex;
-use unienc::{AudioSample, EncoderInput, EncoderOutput, ResultExt};
+use unienc::progress::{self, Stage, Track};
+use unienc::{
+    AudioSample, EncoderInput, EncoderOutput, HighlightDetector, LoudnessMeter, ResultExt,
+    WaveformAnalyzer,
+};
 
 // Audio encoder input/output functions
 #[unsafe(no_mangle)]
@@ -36,16 +42,234 @@ pub unsafe extern "C" fn unienc_audio_encoder_push(
                 data: data_slice.to_vec(),
                 timestamp_in_samples,
             };
+            let result = bounded_push(async {
+                let mut input = input.lock().await;
+                match input
+                    .as_mut()
+                    .ok_or(UniencError::resource_allocation_error("Resource is None"))
+                {
+                    Ok(input) => input
+                        .push(sample)
+                        .await
+                        .context("Failed to push audio sample")
+                        .map_err(UniencError::from_common),
+                    Err(err) => Err(err),
+                }
+            })
+            .await;
+            result.apply_callback(callback, user_data);
+        });
+    }
+}
+
+/// Enables loudness-spike and voice-activity detection on the audio pushed after this call. The
+/// callback is invoked with each hint from the thread pushing audio, for as long as the input is
+/// alive. `audio_options` must match the ones the encoding system was created with.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_audio_encoder_set_highlight_callback(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<AudioEncoderInput>>>,
+    audio_options: *const AudioEncoderOptionsNative,
+    callback: usize, /*UniencDataCallback<UniencHighlightHint>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencHighlightHint> =
+        unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(audio_options) = (unsafe { audio_options.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let detector = HighlightDetector::new(audio_options);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        match input.as_mut() {
+            Some(input) => input
+                .analyzed_mut()
+                .set_highlight_detector(detector, move |hint| {
+                    Ok::<_, UniencError>(hint).apply_callback(callback, user_data)
+                }),
+            None => UniencError::resource_allocation_error("Resource is None")
+                .apply_callback(callback, user_data),
+        }
+    });
+}
+
+/// Measures the loudness of the audio pushed after this call into `meter`, which can be shared by
+/// several inputs to measure their audio together.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_audio_encoder_set_loudness_meter(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<AudioEncoderInput>>>,
+    meter: *const std::sync::Mutex<LoudnessMeter>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || meter.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let meter = arc_from_raw_retained(meter);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input
+            .as_mut()
+            .ok_or(UniencError::resource_allocation_error("Resource is None"))
+        {
+            Ok(input) => {
+                input.analyzed_mut().set_loudness_meter(meter);
+                Ok(())
+            }
+            Err(err) => Err(err),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Delivers the audio pushed after this call as it is encoded, with the gain and drift
+/// compensation applied, for a level meter in the recording UI. The callback is invoked with each
+/// push from the thread pushing audio, for as long as the input is alive. System audio captured
+/// by `unienc_audio_encoder_set_system_audio_capture` is mixed in later and not included.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_audio_encoder_set_monitor_callback(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<AudioEncoderInput>>>,
+    callback: usize, /*UniencDataCallback<UniencAudioSamples>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencAudioSamples> = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        match input.as_mut() {
+            Some(input) => input.analyzed_mut().set_monitor(move |sample| {
+                Ok::<_, UniencError>(sample).apply_callback(callback, user_data)
+            }),
+            None => UniencError::resource_allocation_error("Resource is None")
+                .apply_callback(callback, user_data),
+        }
+    });
+}
+
+/// Scales the audio pushed after this call by `gain_db`. Used when transcoding an export to apply
+/// the gain reported by `unienc_audio_loudness_get`; remuxed audio is copied as is.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_audio_encoder_set_gain(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<AudioEncoderInput>>>,
+    gain_db: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || !gain_db.is_finite() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input
+            .as_mut()
+            .ok_or(UniencError::resource_allocation_error("Resource is None"))
+        {
+            Ok(input) => {
+                input.analyzed_mut().set_gain(gain_db);
+                Ok(())
+            }
+            Err(err) => Err(err),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Starts or stops mixing the audio other applications play (such as voice chat) into the pushed
+/// samples. Only supported on Windows, where it uses WASAPI loopback capture of the default output
+/// device.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_audio_encoder_set_system_audio_capture(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<AudioEncoderInput>>>,
+    enabled: bool,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+
+    #[cfg(not(windows))]
+    {
+        let _ = enabled;
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(windows)]
+    {
+        let input = arc_from_raw_retained(*input);
+
+        Runtime::spawn(async move {
             let mut input = input.lock().await;
             let result = match input
                 .as_mut()
                 .ok_or(UniencError::resource_allocation_error("Resource is None"))
             {
                 Ok(input) => input
-                    .push(sample)
-                    .await
-                    .context("Failed to push audio sample")
-                    .map_err(UniencError::from_common),
+                    .backend_mut()
+                    .set_system_audio_capture(enabled)
+                    .map_err(|err| UniencError::from_common(err.into())),
                 Err(err) => Err(err),
             };
             result.apply_callback(callback, user_data);
@@ -75,6 +299,7 @@ pub unsafe extern "C" fn unienc_audio_encoder_pull(
     let output = arc_from_raw_retained(*output);
 
     Runtime::spawn(async move {
+        let in_flight = progress::begin(Track::Audio, Stage::EncoderPull);
         let mut output = output.lock().await;
         let result = match output
             .as_mut()
@@ -87,6 +312,7 @@ pub unsafe extern "C" fn unienc_audio_encoder_pull(
                 .map_err(UniencError::from_common),
             Err(err) => Err(err),
         };
+        drop(in_flight);
         result.apply_callback(callback, user_data);
     });
 }
@@ -112,3 +338,106 @@ pub unsafe extern "C" fn unienc_free_audio_encoder_output(
         arc_from_raw(*audio_output);
     }
 }
+
+/// Delivers the peak/RMS values accumulated so far, one point per `interval` seconds. Call after
+/// the audio input has been finished to get the complete waveform. The data is only valid during
+/// the callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_audio_waveform_get(
+    runtime: *mut Runtime,
+    waveform: *const std::sync::Mutex<WaveformAnalyzer>,
+    callback: usize, /*UniencDataCallback<UniencWaveformData>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencWaveformData> = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(waveform) = (unsafe { waveform.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    let result = waveform
+        .lock()
+        .map(|analyzer| analyzer.points())
+        .map_err(|_| UniencError::resource_allocation_error("Waveform lock is poisoned"));
+    result.apply_callback(callback, user_data);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_audio_waveform(
+    runtime: *mut Runtime,
+    waveform: *const std::sync::Mutex<WaveformAnalyzer>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !waveform.is_null() {
+        arc_from_raw(waveform);
+    }
+}
+
+/// `audio_options` must match the ones the encoding system was created with.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_audio_loudness_meter(
+    audio_options: *const AudioEncoderOptionsNative,
+) -> *const std::sync::Mutex<LoudnessMeter> {
+    let Some(audio_options) = (unsafe { audio_options.as_ref() }) else {
+        return std::ptr::null();
+    };
+    Arc::into_raw(Arc::new(std::sync::Mutex::new(LoudnessMeter::new(
+        audio_options,
+    ))))
+}
+
+/// Delivers the integrated loudness measured so far and the gain in dB that normalizes it to
+/// `target_lufs` (-16 is a common target for online video) without the sample peak exceeding
+/// -1 dBFS. Fails if no audio above the EBU R128 absolute gate has been measured.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_audio_loudness_get(
+    runtime: *mut Runtime,
+    meter: *const std::sync::Mutex<LoudnessMeter>,
+    target_lufs: f64,
+    callback: usize, /*UniencDataCallback<UniencLoudness>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencLoudness> = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(meter) = (unsafe { meter.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    let result = match meter.lock() {
+        Ok(meter) => meter
+            .loudness()
+            .map(|loudness| (loudness, loudness.normalization_gain(target_lufs)))
+            .ok_or(UniencError::invalid_input_error(
+                "No audio has been measured",
+            )),
+        Err(_) => Err(UniencError::resource_allocation_error(
+            "Loudness meter lock is poisoned",
+        )),
+    };
+    result.apply_callback(callback, user_data);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_audio_loudness_meter(
+    runtime: *mut Runtime,
+    meter: *const std::sync::Mutex<LoudnessMeter>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !meter.is_null() {
+        arc_from_raw(meter);
+    }
+}
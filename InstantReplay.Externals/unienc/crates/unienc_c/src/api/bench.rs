@@ -0,0 +1,256 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::path::PathBuf;
+
+use crate::*;
+use unienc::EncodingSystem;
+use unienc::bench::{BenchResult, bench_convert, bench_encode, bench_mux, bgra_frame};
+
+// Device-runnable throughput benchmarks. Each run creates its own encoding system with the given
+// options, so it must not overlap a recording; hosts run them over the sizes in
+// `unienc::bench::BENCH_SIZES` to compare devices and releases.
+
+/// Measures `stage` with `frames` synthetic frames at the size of `video_options`. Mux writes a
+/// file at `output_path`, which is removed afterwards and is ignored by the other stages.
+/// `callback` is called once the run completes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_run_benchmark(
+    runtime: *mut Runtime,
+    video_options: *const VideoEncoderOptionsNative,
+    audio_options: *const AudioEncoderOptionsNative,
+    stage: UniencBenchmarkStage,
+    frames: u32,
+    output_path: *const c_char,
+    callback: usize, /*UniencDataCallback<UniencBenchmarkResult>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencBenchmarkResult> =
+        unsafe { std::mem::transmute(callback) };
+    let (Some(runtime), Some(&video_options), Some(&audio_options)) = (
+        unsafe { runtime.as_ref() },
+        unsafe { video_options.as_ref() },
+        unsafe { audio_options.as_ref() },
+    ) else {
+        Err::<UniencBenchmarkResult, _>(UniencError::invalid_input_error(
+            "Invalid input parameters",
+        ))
+        .apply_callback(callback, user_data);
+        return;
+    };
+    let output_path = match (stage, output_path.is_null()) {
+        (UniencBenchmarkStage::Mux, true) => {
+            Err::<UniencBenchmarkResult, _>(UniencError::invalid_input_error(
+                "Invalid input parameters",
+            ))
+            .apply_callback(callback, user_data);
+            return;
+        }
+        (UniencBenchmarkStage::Mux, false) => {
+            match unsafe { CStr::from_ptr(output_path) }.to_str() {
+                Ok(output_path) => PathBuf::from(output_path),
+                Err(_) => {
+                    Err::<UniencBenchmarkResult, _>(UniencError::invalid_input_error(
+                        "Invalid input parameters",
+                    ))
+                    .apply_callback(callback, user_data);
+                    return;
+                }
+            }
+        }
+        _ => PathBuf::new(),
+    };
+    let _guard = runtime.enter();
+
+    Runtime::spawn(async move {
+        let (width, height) = (video_options.width, video_options.height);
+        let result = match stage {
+            UniencBenchmarkStage::Convert => bench_convert(width, height, frames),
+            UniencBenchmarkStage::Encode => {
+                let system =
+                    PlatformEncodingSystem::new(&video_options, &audio_options, RuntimeSpawner);
+                let result = bench_encode(&system, frames, |i| bgra_frame(width, height, i)).await;
+                system.shutdown().await.and(result)
+            }
+            UniencBenchmarkStage::Mux => {
+                let system =
+                    PlatformEncodingSystem::new(&video_options, &audio_options, RuntimeSpawner);
+                let result = bench_mux(
+                    &system,
+                    &audio_options,
+                    &output_path,
+                    (width, height),
+                    frames,
+                )
+                .await;
+                let _ = std::fs::remove_file(&output_path);
+                system.shutdown().await.and(result)
+            }
+        };
+        result
+            .map(UniencBenchmarkResult::from)
+            .map_err(UniencError::from_common)
+            .apply_callback(callback, user_data);
+    });
+}
+
+/// Measures blitting the texture of `texture_token`, the size of `video_options`, into a video
+/// encoder `frames` times. Blitted frames are encoded as they are pushed, so the result includes
+/// encoding; compare it with the `Encode` stage at the same size. `callback` is called once the
+/// run completes.
+#[unsafe(no_mangle)]
+#[allow(dead_code)]
+pub unsafe extern "C" fn unienc_run_blit_benchmark(
+    runtime: *mut Runtime,
+    video_options: *const VideoEncoderOptionsNative,
+    audio_options: *const AudioEncoderOptionsNative,
+    texture_token: usize,
+    graphics_format: u32,
+    is_gamma_workflow: bool,
+    frames: u32,
+    issue_graphics_event_callback: usize, /* UniencIssueGraphicsEventCallback */
+    callback: usize,                      /*UniencDataCallback<UniencBenchmarkResult>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencBenchmarkResult> =
+        unsafe { std::mem::transmute(callback) };
+    let (Some(runtime), Some(&video_options), Some(&audio_options)) = (
+        unsafe { runtime.as_ref() },
+        unsafe { video_options.as_ref() },
+        unsafe { audio_options.as_ref() },
+    ) else {
+        Err::<UniencBenchmarkResult, _>(UniencError::invalid_input_error(
+            "Invalid input parameters",
+        ))
+        .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    #[cfg(not(feature = "unity"))]
+    {
+        Err::<UniencBenchmarkResult, _>(UniencError::platform_error("Not supported"))
+            .apply_callback(callback, user_data);
+    }
+
+    #[cfg(feature = "unity")]
+    {
+        use unienc::{BlitOptions, VideoFrame};
+
+        let issue_graphics_event_callback: crate::unity::UniencIssueGraphicsEventCallback =
+            unsafe { std::mem::transmute(issue_graphics_event_callback) };
+        let weak = runtime.weak();
+
+        Runtime::spawn(async move {
+            let system =
+                PlatformEncodingSystem::new(&video_options, &audio_options, RuntimeSpawner);
+            let blit = |_| VideoFrame::BlitSource {
+                texture_token,
+                width: video_options.width,
+                height: video_options.height,
+                graphics_format,
+                options: BlitOptions {
+                    is_gamma_workflow,
+                    ..Default::default()
+                },
+                event_issuer: Box::new(crate::unity::UniencGraphicsEventIssuer::new(
+                    issue_graphics_event_callback,
+                    weak.clone(),
+                )),
+                _phantom: std::marker::PhantomData,
+            };
+            let result = bench_encode(&system, frames, blit).await;
+            system
+                .shutdown()
+                .await
+                .and(result)
+                .map(UniencBenchmarkResult::from)
+                .map_err(UniencError::from_common)
+                .apply_callback(callback, user_data);
+        });
+    }
+}
+
+/// Encodes `frames` frames of moving color bars the backend draws into a texture of its own and
+/// blits like a Unity texture, to test the GPU path on a device without Unity. The graphics events
+/// are issued through `issue_graphics_event_callback`, or run on the runtime when it is 0, such as
+/// in a host that set its own device. Fails where the backend cannot blit. `callback` is called
+/// once the run completes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_run_test_pattern(
+    runtime: *mut Runtime,
+    video_options: *const VideoEncoderOptionsNative,
+    audio_options: *const AudioEncoderOptionsNative,
+    frames: u32,
+    issue_graphics_event_callback: usize, /* UniencIssueGraphicsEventCallback */
+    callback: usize,                      /*UniencDataCallback<UniencBenchmarkResult>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencBenchmarkResult> =
+        unsafe { std::mem::transmute(callback) };
+    let (Some(runtime), Some(&video_options), Some(&audio_options)) = (
+        unsafe { runtime.as_ref() },
+        unsafe { video_options.as_ref() },
+        unsafe { audio_options.as_ref() },
+    ) else {
+        Err::<UniencBenchmarkResult, _>(UniencError::invalid_input_error(
+            "Invalid input parameters",
+        ))
+        .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    #[cfg(not(feature = "unity"))]
+    if issue_graphics_event_callback != 0 {
+        Err::<UniencBenchmarkResult, _>(UniencError::platform_error("Not supported"))
+            .apply_callback(callback, user_data);
+        return;
+    }
+    #[cfg(feature = "unity")]
+    let (issue_graphics_event_callback, weak) = (
+        (issue_graphics_event_callback != 0).then(|| {
+            let callback: crate::unity::UniencIssueGraphicsEventCallback =
+                unsafe { std::mem::transmute(issue_graphics_event_callback) };
+            callback
+        }),
+        runtime.weak(),
+    );
+
+    Runtime::spawn(async move {
+        let system = PlatformEncodingSystem::new(&video_options, &audio_options, RuntimeSpawner);
+        let result = match system.new_test_pattern() {
+            Ok(pattern) => {
+                let frame = |i| {
+                    #[cfg(feature = "unity")]
+                    let event_issuer = issue_graphics_event_callback.map(|func| {
+                        Box::new(crate::unity::UniencGraphicsEventIssuer::new(
+                            func,
+                            weak.clone(),
+                        )) as Box<dyn unienc::GraphicsEventIssuer + Send>
+                    });
+                    #[cfg(not(feature = "unity"))]
+                    let event_issuer = None;
+                    pattern.frame(i, event_issuer)
+                };
+                bench_encode(&system, frames, frame).await
+            }
+            Err(e) => Err(e),
+        };
+        system
+            .shutdown()
+            .await
+            .and(result)
+            .map(UniencBenchmarkResult::from)
+            .map_err(UniencError::from_common)
+            .apply_callback(callback, user_data);
+    });
+}
+
+impl From<BenchResult> for UniencBenchmarkResult {
+    fn from(result: BenchResult) -> Self {
+        Self {
+            frames: result.frames,
+            seconds: result.elapsed.as_secs_f64(),
+            frames_per_second: result.frames_per_second(),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::sync::Arc;
+
+use crate::*;
+use unienc::captions::{add_caption_track, webvtt};
+use unienc::{CaptionTrack, CommonError, Cue, SpawnBlocking};
+
+// Caption tracks record accessibility captions next to a video, written as a WebVTT sidecar or as
+// a timed text track of the MP4 file. Cues use the same timestamps as the frames pushed with
+// `unienc_video_encoder_push`.
+
+/// `retention` is the number of seconds kept behind the newest cue, usually the length of the
+/// recording buffer. Zero or less keeps every cue.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_caption_track(
+    runtime: *mut Runtime,
+    retention: f64,
+) -> *const std::sync::Mutex<CaptionTrack> {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    let retention = (retention > 0.0).then_some(retention);
+    Arc::into_raw(Arc::new(std::sync::Mutex::new(CaptionTrack::new(
+        retention,
+    ))))
+}
+
+/// Shows the UTF-8 `text` from `start` to `end`. Cues overlapping a later one are cut at its start
+/// in the timed text track.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_caption_push(
+    runtime: *mut Runtime,
+    track: *const std::sync::Mutex<CaptionTrack>,
+    start: f64,
+    end: f64,
+    text: *const c_char,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(track) = (unsafe { track.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if text.is_null() || !start.is_finite() || !end.is_finite() || end < start {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Ok(text) = (unsafe { CStr::from_ptr(text) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    let result = track
+        .lock()
+        .map(|mut track| {
+            track.push(Cue {
+                start,
+                end,
+                text: text.to_string(),
+            })
+        })
+        .map_err(|_| UniencError::resource_allocation_error("Caption lock is poisoned"));
+    result.apply_callback(callback, user_data);
+}
+
+/// Writes the cues between `start_timestamp` and `end_timestamp` to `path` as WebVTT. Pass the
+/// timestamp of the first video frame written to the output file as `start_timestamp` so the
+/// captions share the video's timeline.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_caption_write_webvtt(
+    runtime: *mut Runtime,
+    track: *const std::sync::Mutex<CaptionTrack>,
+    path: *const c_char,
+    start_timestamp: f64,
+    end_timestamp: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let (Some(track), Some(path)) = (unsafe { (track.as_ref(), c_str(path)) }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    let cues = match track.lock() {
+        Ok(track) => track.cues_between(start_timestamp, end_timestamp),
+        Err(_) => {
+            UniencError::resource_allocation_error("Caption lock is poisoned")
+                .apply_callback(callback, user_data);
+            return;
+        }
+    };
+
+    Runtime::spawn(async move {
+        let result = RuntimeSpawner
+            .spawn_blocking(move || std::fs::write(path, webvtt(&cues)))
+            .await
+            .map_err(|e| UniencError::from_common(CommonError::Captions(e.to_string())));
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Adds the cues between `start_timestamp` and `end_timestamp` to the finished MP4 file at `path`
+/// as a timed text track in `language`, an ISO 639-2 code such as `eng`, or `und` if null. Pass the
+/// timestamp of the first video frame written to the file as `start_timestamp`. The file is left
+/// as is if no cue is shown.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_caption_add_track(
+    runtime: *mut Runtime,
+    track: *const std::sync::Mutex<CaptionTrack>,
+    path: *const c_char,
+    language: *const c_char,
+    start_timestamp: f64,
+    end_timestamp: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let (Some(track), Some(path)) = (unsafe { (track.as_ref(), c_str(path)) }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let language = match language.is_null() {
+        true => Some("und".to_string()),
+        false => unsafe { c_str(language) },
+    };
+    let Some(language) = language else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    let cues = match track.lock() {
+        Ok(track) => track.cues_between(start_timestamp, end_timestamp),
+        Err(_) => {
+            UniencError::resource_allocation_error("Caption lock is poisoned")
+                .apply_callback(callback, user_data);
+            return;
+        }
+    };
+
+    Runtime::spawn(async move {
+        let result = RuntimeSpawner
+            .spawn_blocking(move || {
+                let io = |e: std::io::Error| CommonError::Captions(e.to_string());
+                let file = std::fs::read(&path).map_err(io)?;
+                let file = add_caption_track(&file, &cues, &language)?;
+                std::fs::write(&path, file).map_err(io)
+            })
+            .await
+            .map_err(UniencError::from_common);
+        result.apply_callback(callback, user_data);
+    });
+}
+
+unsafe fn c_str(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    let s = unsafe { CStr::from_ptr(ptr) }.to_str().ok()?;
+    Some(s.to_string())
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_caption_track(
+    runtime: *mut Runtime,
+    track: *const std::sync::Mutex<CaptionTrack>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !track.is_null() {
+        arc_from_raw(track);
+    }
+}
@@ -0,0 +1,202 @@
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use crate::*;
+use tokio::sync::Mutex;
+use unienc::{AudioEncoderOptions, MediaClock};
+
+// A media clock is shared by the video and audio encoder inputs of a recording. Once set, pushed
+// timestamps are mapped through it, so a host clock that goes backwards does not reach the muxer.
+
+/// `fps_hint` should be the one of the video encoder options.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_media_clock(
+    runtime: *mut Runtime,
+    fps_hint: u32,
+) -> *const std::sync::Mutex<MediaClock> {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    Arc::into_raw(Arc::new(std::sync::Mutex::new(MediaClock::new(fps_hint))))
+}
+
+/// Continues the output timeline from the latest pushed sample when the host clock has been reset.
+/// Backward resets are also detected without calling this.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_media_clock_rebase(
+    runtime: *mut Runtime,
+    clock: *const std::sync::Mutex<MediaClock>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if let Some(clock) = unsafe { clock.as_ref() } {
+        clock.lock().unwrap_or_else(|e| e.into_inner()).rebase();
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_set_clock(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    clock: *const std::sync::Mutex<MediaClock>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || clock.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let clock = arc_from_raw_retained(clock);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.set_clock(clock);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// `audio_options` must match the ones the encoding system was created with.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_audio_encoder_set_clock(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<AudioEncoderInput>>>,
+    audio_options: *const AudioEncoderOptionsNative,
+    clock: *const std::sync::Mutex<MediaClock>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(audio_options) = (unsafe { audio_options.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || clock.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let clock = arc_from_raw_retained(clock);
+    let sample_rate = audio_options.sample_rate();
+    let channels = audio_options.channels();
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.set_clock(clock, sample_rate, channels);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Slowly resamples pushed audio so that it stays in sync with the wall clock, which video
+/// timestamps follow, over long recordings. Works with or without a media clock.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_audio_encoder_set_drift_compensation(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<AudioEncoderInput>>>,
+    audio_options: *const AudioEncoderOptionsNative,
+    enabled: bool,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(audio_options) = (unsafe { audio_options.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let sample_rate = audio_options.sample_rate();
+    let channels = audio_options.channels();
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.set_drift_compensation(enabled, sample_rate, channels);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_audio_encoder_get_drift_stats(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<AudioEncoderInput>>>,
+    callback: usize, /*UniencDataCallback<UniencDriftStats>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencDriftStats> = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+
+    Runtime::spawn(async move {
+        let input = input.lock().await;
+        let result = match input.as_ref() {
+            Some(input) => input.drift_stats().ok_or(UniencError::invalid_input_error(
+                "Drift compensation is not enabled",
+            )),
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_media_clock(
+    runtime: *mut Runtime,
+    clock: *const std::sync::Mutex<MediaClock>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !clock.is_null() {
+        arc_from_raw(clock);
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::*;
+use unienc::CancellationToken;
+use unienc::deadline::with_deadline;
+
+// Pushes to encoder and muxer inputs may wait indefinitely on a wedged encoder, such as one that
+// never releases its input buffers. They are bounded by the deadline set here, so the host gets an
+// error in time instead of a push that never calls back.
+
+static PUSH_DEADLINE: Mutex<Option<Duration>> = Mutex::new(None);
+static PENDING_PUSHES: Mutex<Option<CancellationToken>> = Mutex::new(None);
+
+/// Makes pushes to encoder and muxer inputs started afterwards fail with `TimeoutError` when they
+/// take more than `seconds`, including the wait for earlier pushes to the same input. Zero or less
+/// lets them wait indefinitely, which is the default. An encoder whose push timed out may not
+/// accept further samples, so the recording should be treated as failed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_set_push_deadline(seconds: f64) {
+    let deadline = (seconds > 0.0).then(|| Duration::from_secs_f64(seconds));
+    *PUSH_DEADLINE.lock().unwrap_or_else(|e| e.into_inner()) = deadline;
+}
+
+/// Makes the pushes to encoder and muxer inputs in progress fail right away, such as before
+/// tearing down a recording whose encoder stopped responding. Later pushes are not affected.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_cancel_pending_pushes() {
+    let token = PENDING_PUSHES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    if let Some(token) = token {
+        token.cancel();
+    }
+}
+
+/// Runs a push under the deadline and cancellation set by the functions above.
+pub(crate) async fn bounded_push<T>(
+    push: impl Future<Output = Result<T, UniencError>>,
+) -> Result<T, UniencError> {
+    let deadline = *PUSH_DEADLINE.lock().unwrap_or_else(|e| e.into_inner());
+    let token = PENDING_PUSHES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(CancellationToken::new)
+        .clone();
+    token
+        .run(with_deadline(deadline, async { Ok(push.await) }))
+        .await
+        .map_err(UniencError::from_common)
+        .and_then(|result| result)
+}
@@ -0,0 +1,152 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::*;
+use tokio::sync::Mutex;
+use unienc::{Decoder, EncodingSystem, ResultExt, SeekMode};
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_decoder(
+    runtime: *mut Runtime,
+    system: *const PlatformEncodingSystem,
+    input_path: *const c_char,
+    decoder_out: *mut *const Mutex<Option<DecoderImpl>>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if system.is_null() || input_path.is_null() || decoder_out.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+
+    unsafe {
+        let Ok(path_str) = CStr::from_ptr(input_path).to_str() else {
+            UniencError::invalid_input_error("Invalid input parameters")
+                .apply_callback(on_error, user_data);
+            return false;
+        };
+
+        match (*system)
+            .new_decoder(Path::new(path_str))
+            .context("Failed to create decoder")
+        {
+            Ok(decoder) => {
+                *decoder_out = Arc::into_raw(Arc::new(Mutex::new(Some(decoder))));
+                true
+            }
+            Err(err) => {
+                UniencError::from_common(err).apply_callback(on_error, user_data);
+                false
+            }
+        }
+    }
+}
+
+/// Pulls the next decoded BGRA frame. The callback receives null data after the last frame. The
+/// frame data is only valid during the callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_decoder_pull(
+    runtime: *mut Runtime,
+    decoder: SendPtr<Mutex<Option<DecoderImpl>>>,
+    callback: usize, /*UniencDataCallback<UniencDecodedFrameData>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencDecodedFrameData> =
+        unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if decoder.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    let _guard = runtime.enter();
+    let decoder = arc_from_raw_retained(*decoder);
+
+    Runtime::spawn_optimistically(async move {
+        let mut decoder = decoder.lock().await;
+        let result = match decoder
+            .as_mut()
+            .ok_or(UniencError::resource_allocation_error("Resource is None"))
+        {
+            Ok(decoder) => decoder
+                .pull()
+                .await
+                .context("Failed to pull decoded frame")
+                .map_err(UniencError::from_common),
+            Err(err) => Err(err),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Seeks to `timestamp` seconds and delivers the frame shown at that time, following the same
+/// callback contract as `unienc_decoder_pull`. With `precise` unset, the preceding keyframe is
+/// returned instead where the platform supports it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_decoder_seek(
+    runtime: *mut Runtime,
+    decoder: SendPtr<Mutex<Option<DecoderImpl>>>,
+    timestamp: f64,
+    precise: bool,
+    callback: usize, /*UniencDataCallback<UniencDecodedFrameData>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencDecodedFrameData> =
+        unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if decoder.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    let mode = if precise {
+        SeekMode::Precise
+    } else {
+        SeekMode::Keyframe
+    };
+
+    let _guard = runtime.enter();
+    let decoder = arc_from_raw_retained(*decoder);
+
+    Runtime::spawn_optimistically(async move {
+        let mut decoder = decoder.lock().await;
+        let result = match decoder
+            .as_mut()
+            .ok_or(UniencError::resource_allocation_error("Resource is None"))
+        {
+            Ok(decoder) => decoder
+                .seek(timestamp, mode)
+                .await
+                .context("Failed to seek decoder")
+                .map_err(UniencError::from_common),
+            Err(err) => Err(err),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_decoder(
+    runtime: *mut Runtime,
+    decoder: SendPtr<Mutex<Option<DecoderImpl>>>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !decoder.is_null() {
+        arc_from_raw(*decoder);
+    }
+}
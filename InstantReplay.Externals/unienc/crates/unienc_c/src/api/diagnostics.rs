@@ -0,0 +1,195 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::*;
+use unienc::log_capture::{dump_log_capture, set_log_capture};
+use unienc::profiler;
+use unienc::progress::{self, Stage, Track, TrackProgress};
+use unienc::{DiagnosticCheck, EncodingSystem, recommend};
+
+/// Checks that the native library was built for this platform and that its backend works here,
+/// before any runtime or encoding system is created. `callback` is called synchronously with one
+/// entry per check; the report is only valid during the callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_self_test(
+    callback: usize, /*UniencDataCallback<UniencSelfTestReport>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencSelfTestReport> =
+        unsafe { std::mem::transmute(callback) };
+
+    let mut checks = vec![DiagnosticCheck::passed(
+        "native_library",
+        format!(
+            "unienc {} for {}-{}",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::ARCH,
+            std::env::consts::OS
+        ),
+    )];
+    checks.extend(PlatformEncodingSystem::self_test());
+
+    Ok::<_, UniencError>(checks).apply_callback(callback, user_data);
+}
+
+/// Checks that recording with these options can work before the host offers to record: the backend
+/// self test including graphics interception, then codec, alpha, resolution and the free space in
+/// `output_dir`, which may be null to skip that check. `callback` is called synchronously with one
+/// entry per check; the report is only valid during the callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_check_support(
+    runtime: *mut Runtime,
+    video_options: *const VideoEncoderOptionsNative,
+    audio_options: *const AudioEncoderOptionsNative,
+    output_dir: *const c_char,
+    callback: usize, /*UniencDataCallback<UniencSelfTestReport>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencSelfTestReport> =
+        unsafe { std::mem::transmute(callback) };
+    let (Some(runtime), Some(video_options), Some(audio_options)) = (
+        unsafe { runtime.as_ref() },
+        unsafe { video_options.as_ref() },
+        unsafe { audio_options.as_ref() },
+    ) else {
+        Err::<Vec<DiagnosticCheck>, _>(UniencError::invalid_input_error(
+            "Invalid input parameters",
+        ))
+        .apply_callback(callback, user_data);
+        return;
+    };
+    let output_dir = match output_dir.is_null() {
+        true => None,
+        false => match unsafe { CStr::from_ptr(output_dir) }.to_str() {
+            Ok(output_dir) => Some(Path::new(output_dir)),
+            Err(_) => {
+                Err::<Vec<DiagnosticCheck>, _>(UniencError::invalid_input_error(
+                    "Invalid input parameters",
+                ))
+                .apply_callback(callback, user_data);
+                return;
+            }
+        },
+    };
+    let _guard = runtime.enter();
+
+    let system = PlatformEncodingSystem::new(video_options, audio_options, RuntimeSpawner);
+    let checks = unienc::check_support(&system, video_options, audio_options, output_dir);
+    Ok::<_, UniencError>(checks).apply_callback(callback, user_data);
+}
+
+/// Recommends the bitrate, frame rate and keyframe interval to record frames of `width` by
+/// `height` with on this device, from what its encoder supports, along with the audio bitrate.
+/// Needs no runtime or encoding system. `callback` is called synchronously.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_get_recommended_options(
+    width: u32,
+    height: u32,
+    callback: usize, /*UniencDataCallback<UniencRecommendedOptions>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencRecommendedOptions> =
+        unsafe { std::mem::transmute(callback) };
+    if width == 0 || height == 0 {
+        Err::<UniencRecommendedOptions, _>(UniencError::invalid_input_error(
+            "Invalid input parameters",
+        ))
+        .apply_callback(callback, user_data);
+        return;
+    }
+
+    let capabilities = PlatformEncodingSystem::encoder_capabilities(width, height);
+    let options = recommend(width, height, &capabilities);
+    Ok::<_, UniencError>(UniencRecommendedOptions {
+        bitrate: options.bitrate,
+        fps_hint: options.fps_hint,
+        keyframe_interval: options.keyframe_interval,
+        audio_bitrate: options.audio_bitrate,
+    })
+    .apply_callback(callback, user_data);
+}
+
+/// Keeps the last `capacity` bytes of native log lines and pipeline events in memory so they can be
+/// attached to a bug report when an export fails, discarding those kept so far. A `capacity` of
+/// zero stops keeping them.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_set_log_capture(capacity: usize) {
+    set_log_capture(capacity);
+}
+
+/// Passes the kept log lines to `callback` synchronously; they are only valid during the callback.
+/// Fails if the log capture is off.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_dump_log_capture(
+    callback: usize, /*UniencDataCallback<UniencLogCapture>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencLogCapture> = unsafe { std::mem::transmute(callback) };
+    dump_log_capture()
+        .ok_or_else(|| UniencError::invalid_input_error("Log capture is off"))
+        .apply_callback(callback, user_data);
+}
+
+/// Puts the native profiler markers in the Unity profiler `category`, or in the builtin `Other`
+/// category if it is 0xFFFF, and prepends `prefix` to their names, so captures with markers of
+/// other native plugins stay apart. `prefix` may be null for none. Fails once a marker was created,
+/// so call it before the first recording. `callback` is called synchronously.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_set_profiler_naming(
+    category: u16,
+    prefix: *const c_char,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Ok(prefix) = (!prefix.is_null())
+        .then(|| unsafe { CStr::from_ptr(prefix) }.to_str())
+        .transpose()
+    else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let category = (category != u16::MAX).then_some(category);
+    profiler::set_profiler_naming(category, prefix.unwrap_or_default())
+        .map_err(UniencError::from_common)
+        .apply_callback(callback, user_data);
+}
+
+/// Reports where each track of the export in progress stands, such as when finishing it does not
+/// call back: the last sample the muxer accepted and the encoder pulls, muxer pushes and finishes
+/// still in flight. The progress covers the whole process and is reset when a muxer is created.
+/// `callback` is called synchronously.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_get_pipeline_progress(
+    callback: usize, /*UniencDataCallback<UniencPipelineProgress>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencPipelineProgress> =
+        unsafe { std::mem::transmute(callback) };
+    Ok::<_, UniencError>(UniencPipelineProgress {
+        video: progress::snapshot(Track::Video).into(),
+        audio: progress::snapshot(Track::Audio).into(),
+    })
+    .apply_callback(callback, user_data);
+}
+
+impl From<TrackProgress> for UniencTrackProgress {
+    fn from(progress: TrackProgress) -> Self {
+        let seconds = |elapsed: Option<Duration>| elapsed.map_or(-1.0, |e| e.as_secs_f64());
+        Self {
+            last_accepted: progress.last_accepted.unwrap_or(0.0),
+            since_accepted: seconds(progress.since_accepted),
+            encoder_pull: seconds(progress.in_flight[Stage::EncoderPull as usize]),
+            muxer_push: seconds(progress.in_flight[Stage::MuxerPush as usize]),
+            sink_write: seconds(progress.in_flight[Stage::SinkWrite as usize]),
+            blocked: match progress.blocked() {
+                None => UniencPipelineStage::None,
+                Some((Stage::EncoderPull, _)) => UniencPipelineStage::EncoderPull,
+                Some((Stage::MuxerPush, _)) => UniencPipelineStage::MuxerPush,
+                Some((Stage::SinkWrite, _)) => UniencPipelineStage::SinkWrite,
+            },
+        }
+    }
+}
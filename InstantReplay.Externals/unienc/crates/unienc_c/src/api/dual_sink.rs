@@ -0,0 +1,291 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::encoding_system::{
+    new_audio_encoder_components, new_muxer_components, new_stream_muxer_components,
+    new_video_encoder_components, reconnect_from_native,
+};
+use crate::*;
+use tokio::sync::Mutex;
+use unienc::{
+    CancellationToken, CompletionHandle, EncoderInput, EncodingSystem, PixelFormat, Rendition,
+    ResultExt, SecondaryFailure, SecondaryMuxerInput, SecondaryVideoInput, VideoFrame, VideoSample,
+    buffer::SharedBuffer, frame_from_pixels,
+};
+
+// A dual sink records a session to a local file at full quality while publishing a smaller
+// rendition of it to a live stream. The audio is encoded once for both. The stream is a secondary
+// output: when it fails or stalls, it is detached and reported, and the file is written as if it
+// were recorded alone.
+
+/// Number of samples of each track queued while a muxer is busy.
+const PUMP_CAPACITY: usize = 16;
+
+/// Encoding systems of the file and the stream of a dual sink.
+pub struct DualSink {
+    #[allow(dead_code)] // kept alive until the recording completes
+    systems: [PlatformEncodingSystem; 2],
+}
+
+/// Creates encoders recording `video_options` and `audio_options` to `output_path`, a UTF-8 file
+/// path, while publishing the same frames, scaled to the size and bitrate of
+/// `stream_video_options`, to `url`: over SRT for `srt://`, WHIP for `http(s)://` authorizing with
+/// `bearer_token` if not null, and RTMP otherwise. The stream reconnects like
+/// `unienc_new_rtmp_muxer`, calling `on_stream_event` with `stream_user_data`.
+///
+/// When the stream fails for good or a push to it stalls, it is detached and `on_stream_error` is
+/// called once with `stream_user_data`; the file is recorded as if alone. Only frames in memory can
+/// be pushed to `video_input_out`. `on_complete` is called with the result of the file once both
+/// inputs have been freed and it is written, and `sink_out` is freed after that.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_dual_sink(
+    runtime: *mut Runtime,
+    video_options: *const VideoEncoderOptionsNative,
+    audio_options: *const AudioEncoderOptionsNative,
+    stream_video_options: *const VideoEncoderOptionsNative,
+    output_path: *const c_char,
+    url: *const c_char,
+    bearer_token: *const c_char,
+    retry_policy: *const UniencRetryPolicy,
+    on_stream_event: usize, /*UniencDataCallback<UniencReconnectEvent>*/
+    on_stream_error: usize, /*UniencCallback*/
+    stream_user_data: SendPtr<c_void>,
+    sink_out: *mut *mut DualSink,
+    video_input_out: *mut *const Mutex<Option<DualSinkVideoEncoderInput>>,
+    audio_input_out: *mut *const Mutex<Option<AudioEncoderInput>>,
+    on_complete: usize, /*UniencCallback*/
+    on_complete_user_data: SendPtr<c_void>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_stream_error: UniencCallback = unsafe { std::mem::transmute(on_stream_error) };
+    let on_complete: UniencCallback = unsafe { std::mem::transmute(on_complete) };
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if video_options.is_null()
+        || audio_options.is_null()
+        || stream_video_options.is_null()
+        || output_path.is_null()
+        || url.is_null()
+        || sink_out.is_null()
+        || video_input_out.is_null()
+        || audio_input_out.is_null()
+    {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+    let (path, url) = unsafe { (CStr::from_ptr(output_path), CStr::from_ptr(url)) };
+    let bearer_token = match bearer_token.is_null() {
+        true => Ok(None),
+        false => unsafe { CStr::from_ptr(bearer_token) }.to_str().map(Some),
+    };
+    let (Ok(path), Ok(url), Ok(bearer_token)) = (path.to_str(), url.to_str(), bearer_token) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    };
+    let reconnect =
+        match unsafe { reconnect_from_native(retry_policy, on_stream_event, stream_user_data) } {
+            Ok(reconnect) => reconnect,
+            Err(err) => {
+                err.apply_callback(on_error, user_data);
+                return false;
+            }
+        };
+    let (video_options, audio_options, stream_video_options) =
+        unsafe { (*video_options, *audio_options, *stream_video_options) };
+    let rendition = Rendition {
+        width: stream_video_options.width,
+        height: stream_video_options.height,
+        bitrate: stream_video_options.bitrate,
+    };
+
+    let systems = [
+        PlatformEncodingSystem::new(&video_options, &audio_options, RuntimeSpawner),
+        PlatformEncodingSystem::new(&stream_video_options, &audio_options, RuntimeSpawner),
+    ];
+    let components = (|| -> unienc::Result<_> {
+        let local = (
+            new_video_encoder_components(&systems[0])?,
+            new_audio_encoder_components(&systems[0], None)?,
+            new_muxer_components(&systems[0], Path::new(path), None)?,
+        );
+        let stream = (
+            new_video_encoder_components(&systems[1])?,
+            new_stream_muxer_components(&systems[1], url, bearer_token, reconnect)?,
+        );
+        Ok((local, stream))
+    })();
+    let (local, stream) = match components.context("Failed to create dual sink") {
+        Ok(components) => components,
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(on_error, user_data);
+            return false;
+        }
+    };
+    let (
+        (video_input, video_output),
+        (audio_input, audio_output),
+        (video_muxer_input, audio_muxer_input, completion_handle),
+    ) = local;
+    let (
+        (stream_video_input, stream_video_output),
+        (stream_video_muxer_input, stream_audio_muxer_input, stream_completion_handle),
+    ) = stream;
+
+    let failure = SecondaryFailure::new({
+        // a raw pointer is neither Send nor Sync
+        let stream_user_data = *stream_user_data as usize;
+        move |err| {
+            Err::<(), _>(UniencError::from_common(err)).apply_callback(
+                on_stream_error,
+                SendPtr::from(stream_user_data as *mut c_void),
+            )
+        }
+    });
+
+    // the stream ends on its own, so that a stalled connection never holds the file back
+    Runtime::spawn({
+        let failure = failure.clone();
+        async move {
+            let cancel = CancellationToken::new();
+            let result = async {
+                unienc::drive(
+                    stream_video_output,
+                    stream_video_muxer_input,
+                    PUMP_CAPACITY,
+                    &cancel,
+                )
+                .await
+                .context("Failed to mux stream video")?;
+                stream_completion_handle
+                    .finish()
+                    .await
+                    .context("Failed to end stream")
+            };
+            if let Err(err) = result.await {
+                failure.fail(err);
+            }
+        }
+    });
+
+    let audio_muxer_input =
+        SecondaryMuxerInput::new(audio_muxer_input, stream_audio_muxer_input, failure.clone());
+    Runtime::spawn(async move {
+        let cancel = CancellationToken::new();
+        let video = unienc::drive(video_output, video_muxer_input, PUMP_CAPACITY, &cancel);
+        let audio = unienc::drive(audio_output, audio_muxer_input, PUMP_CAPACITY, &cancel);
+        let (video, audio) = futures::future::join(video, audio).await;
+
+        let result = async {
+            video.context("Failed to mux video")?;
+            audio.context("Failed to mux audio")?;
+            completion_handle
+                .finish()
+                .await
+                .context("Failed to complete recording")
+        };
+        result
+            .await
+            .map_err(UniencError::from_common)
+            .apply_callback(on_complete, on_complete_user_data);
+    });
+
+    let video_input = SecondaryVideoInput::new(video_input, stream_video_input, rendition, failure);
+    unsafe {
+        *sink_out = Box::into_raw(Box::new(DualSink { systems }));
+        *video_input_out = Arc::into_raw(Arc::new(Mutex::new(Some(video_input))));
+        *audio_input_out = Arc::into_raw(Arc::new(Mutex::new(Some(audio_input))));
+    }
+    true
+}
+
+/// Same as `unienc_video_encoder_push_shared_buffer_with_format`, for the video input of a dual
+/// sink.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_dual_sink_video_input_push_shared_buffer(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<DualSinkVideoEncoderInput>>>,
+    buffer: SendPtr<SharedBuffer>,
+    width: u32,
+    height: u32,
+    stride: u32,
+    pixel_format: UniencPixelFormat,
+    timestamp: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    if input.is_null() || buffer.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let buffer = unsafe { Box::from_raw(*buffer) };
+    let pixel_format = match pixel_format {
+        UniencPixelFormat::Bgra32 => PixelFormat::Bgra32,
+        UniencPixelFormat::Rgba32 => PixelFormat::Rgba32,
+        UniencPixelFormat::Rgb565 => PixelFormat::Rgb565,
+    };
+    let frame = match frame_from_pixels(*buffer, width, height, stride, pixel_format) {
+        Ok(frame) => frame,
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(callback, user_data);
+            return;
+        }
+    };
+    let sample = VideoSample {
+        frame: VideoFrame::Bgra32(frame),
+        timestamp,
+    };
+
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+
+        let result = match input
+            .as_mut()
+            .ok_or(UniencError::resource_allocation_error("Resource is None"))
+        {
+            Ok(input) => input
+                .push(sample)
+                .await
+                .context("Failed to push video sample")
+                .map_err(UniencError::from_common),
+            Err(err) => Err(err),
+        };
+
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Frees the video input of a dual sink, which ends the video of both the file and the stream.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_dual_sink_video_input(
+    runtime: *mut Runtime,
+    video_input: SendPtr<Mutex<Option<DualSinkVideoEncoderInput>>>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !video_input.is_null() {
+        arc_from_raw(*video_input);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_dual_sink(sink: *mut DualSink) {
+    if !sink.is_null() {
+        unsafe {
+            let _ = Box::from_raw(sink);
+        }
+    }
+}
@@ -1,10 +1,34 @@
 use crate::*;
 use std::ffi::{CStr, c_char};
+use std::fs::File;
 use std::os::raw::c_void;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use unienc::{Encoder, EncodingSystem, Muxer, ResultExt};
+#[cfg(target_os = "android")]
+use unienc::output_path::descriptor_path;
+#[cfg(all(unix, not(target_os = "android")))]
+use unienc::output_path::duplicate_descriptor;
+#[cfg(windows)]
+use unienc::output_path::duplicate_handle;
+#[cfg(any(windows, all(unix, not(target_os = "android"))))]
+use unienc::output_path::temporary_output_path;
+use unienc::output_path::validate_output_path;
+use unienc::rtmp::{redact_stream_key, validate_rtmp_url};
+use unienc::srt::validate_srt_url;
+use unienc::whip::{validate_bearer_token, validate_whip_url, without_query};
+use unienc::{
+    AnalyzedAudioInput, CapturedAudioInput, CapturedVideoInput, ClockedAudioInput,
+    ClockedVideoInput, DedupVideoInput, DescriptorCompletionHandle, Encoder, EncodingSystem,
+    FilteredVideoInput, LimitedMuxerInput, MeasuredVideoOutput, Muxer, PacedVideoInput,
+    PngSequenceVideoInput, Reconnect, ReconnectEvent, ResultExt, RetryPolicy, SceneCutVideoInput,
+    SnapshotVideoInput, SphericalCompletionHandle, StoryboardVideoInput, TeeMuxerInput,
+    TimecodeCompletionHandle, TimelapseVideoInput, WaveformAnalyzer, detect_empty, interleave,
+};
+
+/// Seconds a track of a muxer may be pushed ahead of the other before its pushes wait.
+const MAX_INTERLEAVE_SKEW: f64 = 0.5;
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn unienc_new_encoding_system(
@@ -14,7 +38,19 @@ pub unsafe extern "C" fn unienc_new_encoding_system(
 ) -> *mut PlatformEncodingSystem {
     unsafe {
         let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
-        let system = PlatformEncodingSystem::new(&*video_options, &*audio_options, RuntimeSpawner);
+        let (video, audio) = (&*video_options, &*audio_options);
+        unienc::log_capture::event(format_args!(
+            "encoding system created: {}x{} {:?} at {} fps and {} bps, audio {} Hz x{} at {} bps",
+            video.width,
+            video.height,
+            video.codec.to_codec(),
+            video.fps_hint,
+            video.bitrate,
+            audio.sample_rate,
+            audio.channels,
+            audio.bitrate
+        ));
+        let system = PlatformEncodingSystem::new(video, audio, RuntimeSpawner);
         Box::into_raw(Box::new(system))
     }
 }
@@ -28,6 +64,40 @@ pub unsafe extern "C" fn unienc_free_encoding_system(system: *mut PlatformEncodi
     }
 }
 
+/// Frees the system once the tasks of its components have finished, reporting the errors that
+/// `unienc_free_encoding_system` ignores. `system` must not be used or freed afterwards, and a new
+/// system can be created right away.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_shutdown_encoding_system(
+    runtime: *mut Runtime,
+    system: *mut PlatformEncodingSystem,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if system.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    let _guard = runtime.enter();
+    let shutdown = unsafe { Box::from_raw(system) }.shutdown();
+
+    Runtime::spawn(async move {
+        shutdown
+            .await
+            .context("Failed to shut down encoding system")
+            .map_err(UniencError::from_common)
+            .apply_callback(callback, user_data);
+    });
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn unienc_new_video_encoder(
     runtime: *mut Runtime,
@@ -46,27 +116,37 @@ pub unsafe extern "C" fn unienc_new_video_encoder(
         return false;
     }
 
-    unsafe {
-        match (*system).new_video_encoder() {
-            Ok(encoder) => match encoder.get().context("Failed to get encoded video sample") {
-                Ok((input, output)) => {
-                    *input_out = Arc::into_raw(Arc::new(Mutex::new(Some(input))));
-                    *output_out = Arc::into_raw(Arc::new(Mutex::new(Some(output))));
-                    true
-                }
-                Err(err) => {
-                    UniencError::from_common(err).apply_callback(on_error, user_data);
-                    false
-                }
-            },
-            Err(err) => {
-                UniencError::from_common(err).apply_callback(on_error, user_data);
-                false
-            }
+    match new_video_encoder_components(unsafe { &*system }) {
+        Ok((input, output)) => unsafe {
+            *input_out = Arc::into_raw(Arc::new(Mutex::new(Some(input))));
+            *output_out = Arc::into_raw(Arc::new(Mutex::new(Some(output))));
+            true
+        },
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(on_error, user_data);
+            false
         }
     }
 }
 
+/// Encoder input and output wrapped as every video encoder of this library.
+pub(crate) fn new_video_encoder_components(
+    system: &PlatformEncodingSystem,
+) -> unienc::Result<(VideoEncoderInput, VideoEncoderOutput)> {
+    let (input, output) = system
+        .new_video_encoder()?
+        .get()
+        .context("Failed to get encoded video sample")?;
+    let input = ClockedVideoInput::new(PacedVideoInput::new(DedupVideoInput::new(
+        SceneCutVideoInput::new(FilteredVideoInput::new(StoryboardVideoInput::new(
+            PngSequenceVideoInput::new(SnapshotVideoInput::new(TimelapseVideoInput::new(
+                CapturedVideoInput::new(input),
+            ))),
+        ))),
+    )));
+    Ok((input, MeasuredVideoOutput::new(output)))
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn unienc_new_audio_encoder(
     runtime: *mut Runtime,
@@ -85,27 +165,89 @@ pub unsafe extern "C" fn unienc_new_audio_encoder(
         return false;
     }
 
+    unsafe { new_audio_encoder(&*system, None, input_out, output_out, on_error, user_data) }
+}
+
+/// Same as `unienc_new_audio_encoder`, but also accumulates peak/RMS values of the pushed audio.
+/// `audio_options` must match the ones the encoding system was created with.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_audio_encoder_with_waveform(
+    runtime: *mut Runtime,
+    system: *const PlatformEncodingSystem,
+    audio_options: *const AudioEncoderOptionsNative,
+    input_out: *mut *const Mutex<Option<AudioEncoderInput>>,
+    output_out: *mut *const Mutex<Option<AudioEncoderOutput>>,
+    waveform_out: *mut *const std::sync::Mutex<WaveformAnalyzer>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if system.is_null() || audio_options.is_null() || waveform_out.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+
     unsafe {
-        match (*system).new_audio_encoder() {
-            Ok(encoder) => match encoder.get().context("Failed to get encoded audio sample") {
-                Ok((input, output)) => {
-                    *input_out = Arc::into_raw(Arc::new(Mutex::new(Some(input))));
-                    *output_out = Arc::into_raw(Arc::new(Mutex::new(Some(output))));
-                    true
-                }
-                Err(err) => {
-                    UniencError::from_common(err).apply_callback(on_error, user_data);
-                    false
-                }
-            },
-            Err(err) => {
-                UniencError::from_common(err).apply_callback(on_error, user_data);
-                false
-            }
+        let analyzer = Arc::new(std::sync::Mutex::new(WaveformAnalyzer::new(
+            &*audio_options,
+        )));
+        if !new_audio_encoder(
+            &*system,
+            Some(analyzer.clone()),
+            input_out,
+            output_out,
+            on_error,
+            user_data,
+        ) {
+            return false;
+        }
+        *waveform_out = Arc::into_raw(analyzer);
+        true
+    }
+}
+
+unsafe fn new_audio_encoder(
+    system: &PlatformEncodingSystem,
+    analyzer: Option<Arc<std::sync::Mutex<WaveformAnalyzer>>>,
+    input_out: *mut *const Mutex<Option<AudioEncoderInput>>,
+    output_out: *mut *const Mutex<Option<AudioEncoderOutput>>,
+    on_error: UniencCallback,
+    user_data: SendPtr<c_void>,
+) -> bool {
+    match new_audio_encoder_components(system, analyzer) {
+        Ok((input, output)) => unsafe {
+            *input_out = Arc::into_raw(Arc::new(Mutex::new(Some(input))));
+            *output_out = Arc::into_raw(Arc::new(Mutex::new(Some(output))));
+            true
+        },
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(on_error, user_data);
+            false
         }
     }
 }
 
+/// Encoder input and output wrapped as every audio encoder of this library.
+pub(crate) fn new_audio_encoder_components(
+    system: &PlatformEncodingSystem,
+    analyzer: Option<Arc<std::sync::Mutex<WaveformAnalyzer>>>,
+) -> unienc::Result<(AudioEncoderInput, AudioEncoderOutput)> {
+    let (input, output) = system
+        .new_audio_encoder()?
+        .get()
+        .context("Failed to get encoded audio sample")?;
+    let input = ClockedAudioInput::new(AnalyzedAudioInput::new(
+        CapturedAudioInput::new(input),
+        analyzer,
+    ));
+    Ok((input, output))
+}
+
+/// Creates a muxer writing to `output_path`, a UTF-8 file path in a directory that exists. URIs
+/// such as Android `content://` ones are rejected; open them and use `unienc_new_muxer_with_fd`.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn unienc_new_muxer(
     runtime: *mut Runtime,
@@ -135,35 +277,467 @@ pub unsafe extern "C" fn unienc_new_muxer(
                 return false;
             }
         };
-        let path = Path::new(path_str);
-
-        match (*system).new_muxer(path) {
-            Ok(muxer) => {
-                match muxer.get_inputs().context("Failed to get muxer input") {
-                    Ok((video_input, audio_input, completion_handle)) => {
-                        // Box the completion handle and store as raw pointer
-
-                        *video_input_out = Arc::into_raw(Arc::new(Mutex::new(Some(video_input))));
-                        *audio_input_out = Arc::into_raw(Arc::new(Mutex::new(Some(audio_input))));
-                        *completion_handle_out =
-                            Arc::into_raw(Arc::new(Mutex::new(Some(completion_handle))));
-                        true
-                    }
-                    Err(err) => {
-                        UniencError::from_common(err).apply_callback(on_error, user_data);
-                        false
-                    }
-                }
+
+        store_muxer(
+            new_muxer_components(&*system, Path::new(path_str), None),
+            video_input_out,
+            audio_input_out,
+            completion_handle_out,
+            on_error,
+            user_data,
+        )
+    }
+}
+
+/// Creates a muxer writing to the open file descriptor `fd`, such as one of an Android scoped
+/// storage document opened with mode "rw", an app sandbox file or a socket. The descriptor stays
+/// owned by the caller and must stay open until the muxer completes. On Android it must be a
+/// seekable file written by `MediaMuxer` in place; elsewhere a temporary file is muxed and copied
+/// into the descriptor from its current position on completion, so it may also be a pipe or a
+/// socket. Not supported on Windows; use `unienc_new_muxer_with_handle`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_muxer_with_fd(
+    runtime: *mut Runtime,
+    system: *const PlatformEncodingSystem,
+    fd: i32,
+    video_input_out: *mut *const Mutex<Option<VideoMuxerInput>>,
+    audio_input_out: *mut *const Mutex<Option<AudioMuxerInput>>,
+    completion_handle_out: *mut *const Mutex<Option<MuxerCompletionHandle>>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if system.is_null() || fd < 0 {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (video_input_out, audio_input_out, completion_handle_out);
+        UniencError::platform_error("Not supported").apply_callback(on_error, user_data);
+        false
+    }
+
+    // MediaMuxer writes to the descriptor itself
+    #[cfg(target_os = "android")]
+    let output = descriptor_path(fd)
+        .map(|path| (path, None))
+        .ok_or_else(|| unienc::CommonError::InvalidOutputPath(format!("file descriptor {fd}")));
+    #[cfg(all(unix, not(target_os = "android")))]
+    let output =
+        unsafe { duplicate_descriptor(fd) }.map(|target| (temporary_output_path(), Some(target)));
+
+    #[cfg(unix)]
+    match output {
+        Ok((path, target)) => unsafe {
+            store_muxer(
+                new_muxer_components(&*system, &path, target),
+                video_input_out,
+                audio_input_out,
+                completion_handle_out,
+                on_error,
+                user_data,
+            )
+        },
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(on_error, user_data);
+            false
+        }
+    }
+}
+
+/// Creates a muxer writing to the open file `handle`, such as one of an app sandbox file or a
+/// pipe. The handle stays owned by the caller and must stay open until the muxer completes. A
+/// temporary file is muxed and copied into the handle from its current position on completion.
+/// Only supported on Windows.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_muxer_with_handle(
+    runtime: *mut Runtime,
+    system: *const PlatformEncodingSystem,
+    handle: *mut c_void,
+    video_input_out: *mut *const Mutex<Option<VideoMuxerInput>>,
+    audio_input_out: *mut *const Mutex<Option<AudioMuxerInput>>,
+    completion_handle_out: *mut *const Mutex<Option<MuxerCompletionHandle>>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if system.is_null() || handle.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = (video_input_out, audio_input_out, completion_handle_out);
+        UniencError::platform_error("Not supported").apply_callback(on_error, user_data);
+        false
+    }
+
+    #[cfg(windows)]
+    match unsafe { duplicate_handle(handle) } {
+        Ok(target) => unsafe {
+            store_muxer(
+                new_muxer_components(&*system, &temporary_output_path(), Some(target)),
+                video_input_out,
+                audio_input_out,
+                completion_handle_out,
+                on_error,
+                user_data,
+            )
+        },
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(on_error, user_data);
+            false
+        }
+    }
+}
+
+/// Creates a muxer publishing FLV to the RTMP `url` of an ingest server, such as
+/// `rtmp://live.twitch.tv/app/<stream key>`, instead of writing a file. The encoders are created
+/// and pushed to as for a recording; finishing the muxer ends the stream. Only H.264 can be
+/// published, and only with the ffmpeg backend; the others fail. With `retry_policy`, the
+/// connection is opened again when it fails, and the samples pushed meanwhile are buffered and sent
+/// once reconnected; `on_event` (a `UniencDataCallback<UniencReconnectEvent>`), which may be 0, is
+/// called with `event_user_data` on each change of the connection. With a null `retry_policy`,
+/// pushes fail with the connection.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_rtmp_muxer(
+    runtime: *mut Runtime,
+    system: *const PlatformEncodingSystem,
+    url: *const c_char,
+    retry_policy: *const UniencRetryPolicy,
+    on_event: usize, /*UniencDataCallback<UniencReconnectEvent>*/
+    event_user_data: SendPtr<c_void>,
+    video_input_out: *mut *const Mutex<Option<VideoMuxerInput>>,
+    audio_input_out: *mut *const Mutex<Option<AudioMuxerInput>>,
+    completion_handle_out: *mut *const Mutex<Option<MuxerCompletionHandle>>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if system.is_null() || url.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+
+    unsafe {
+        let Ok(url) = CStr::from_ptr(url).to_str() else {
+            UniencError::invalid_input_error("Invalid input parameters")
+                .apply_callback(on_error, user_data);
+            return false;
+        };
+
+        let reconnect = match reconnect_from_native(retry_policy, on_event, event_user_data) {
+            Ok(reconnect) => reconnect,
+            Err(err) => {
+                err.apply_callback(on_error, user_data);
+                return false;
+            }
+        };
+
+        store_muxer(
+            new_rtmp_muxer_components(&*system, url, reconnect),
+            video_input_out,
+            audio_input_out,
+            completion_handle_out,
+            on_error,
+            user_data,
+        )
+    }
+}
+
+/// Creates a muxer publishing over WebRTC to the WHIP endpoint `url`, for spectating with less
+/// than a second of latency, authorizing with `bearer_token`, which may be null for none. The
+/// encoders are created and pushed to as for a recording, and the audio is transcoded to Opus;
+/// finishing the muxer ends the session. Only H.264 can be published, and only with the ffmpeg
+/// backend on ffmpeg 8 or later, whose `whip` muxer runs the WebRTC session out of process; the
+/// others fail. Reconnects like `unienc_new_rtmp_muxer`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_whip_muxer(
+    runtime: *mut Runtime,
+    system: *const PlatformEncodingSystem,
+    url: *const c_char,
+    bearer_token: *const c_char,
+    retry_policy: *const UniencRetryPolicy,
+    on_event: usize, /*UniencDataCallback<UniencReconnectEvent>*/
+    event_user_data: SendPtr<c_void>,
+    video_input_out: *mut *const Mutex<Option<VideoMuxerInput>>,
+    audio_input_out: *mut *const Mutex<Option<AudioMuxerInput>>,
+    completion_handle_out: *mut *const Mutex<Option<MuxerCompletionHandle>>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if system.is_null() || url.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+
+    unsafe {
+        let (Ok(url), Ok(bearer_token)) = (
+            CStr::from_ptr(url).to_str(),
+            (!bearer_token.is_null())
+                .then(|| CStr::from_ptr(bearer_token).to_str())
+                .transpose(),
+        ) else {
+            UniencError::invalid_input_error("Invalid input parameters")
+                .apply_callback(on_error, user_data);
+            return false;
+        };
+
+        let reconnect = match reconnect_from_native(retry_policy, on_event, event_user_data) {
+            Ok(reconnect) => reconnect,
+            Err(err) => {
+                err.apply_callback(on_error, user_data);
+                return false;
             }
+        };
+
+        store_muxer(
+            new_whip_muxer_components(&*system, url, bearer_token, reconnect),
+            video_input_out,
+            audio_input_out,
+            completion_handle_out,
+            on_error,
+            user_data,
+        )
+    }
+}
+
+/// Creates a muxer publishing MPEG-TS over SRT to `url`, such as
+/// `srt://ingest.example.com:9000?streamid=match1&passphrase=...`, with the SRT options in its
+/// query. The encoders are created and pushed to as for a recording; finishing the muxer ends the
+/// stream. Only H.264 can be published, and only with the ffmpeg backend built with libsrt; the
+/// others fail. Reconnects like `unienc_new_rtmp_muxer`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_srt_muxer(
+    runtime: *mut Runtime,
+    system: *const PlatformEncodingSystem,
+    url: *const c_char,
+    retry_policy: *const UniencRetryPolicy,
+    on_event: usize, /*UniencDataCallback<UniencReconnectEvent>*/
+    event_user_data: SendPtr<c_void>,
+    video_input_out: *mut *const Mutex<Option<VideoMuxerInput>>,
+    audio_input_out: *mut *const Mutex<Option<AudioMuxerInput>>,
+    completion_handle_out: *mut *const Mutex<Option<MuxerCompletionHandle>>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if system.is_null() || url.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+
+    unsafe {
+        let Ok(url) = CStr::from_ptr(url).to_str() else {
+            UniencError::invalid_input_error("Invalid input parameters")
+                .apply_callback(on_error, user_data);
+            return false;
+        };
+
+        let reconnect = match reconnect_from_native(retry_policy, on_event, event_user_data) {
+            Ok(reconnect) => reconnect,
             Err(err) => {
-                UniencError::from_common(err).apply_callback(on_error, user_data);
-                false
+                err.apply_callback(on_error, user_data);
+                return false;
             }
+        };
+
+        store_muxer(
+            new_srt_muxer_components(&*system, url, reconnect),
+            video_input_out,
+            audio_input_out,
+            completion_handle_out,
+            on_error,
+            user_data,
+        )
+    }
+}
+
+/// Reconnection following `retry_policy`, calling back `on_event` if not 0, or none if
+/// `retry_policy` is null.
+pub(crate) unsafe fn reconnect_from_native(
+    retry_policy: *const UniencRetryPolicy,
+    on_event: usize,
+    event_user_data: SendPtr<c_void>,
+) -> Result<Option<Reconnect>, UniencError> {
+    let Some(policy) = (unsafe { retry_policy.as_ref() }) else {
+        return Ok(None);
+    };
+    let seconds = |seconds: f64| {
+        Duration::try_from_secs_f64(seconds)
+            .map_err(|_| UniencError::invalid_input_error("Invalid retry policy"))
+    };
+    let policy = RetryPolicy {
+        max_attempts: policy.max_attempts,
+        initial_backoff: seconds(policy.initial_backoff)?,
+        max_backoff: seconds(policy.max_backoff)?,
+        max_buffered: policy.max_buffered.max(0.0),
+    };
+    let on_event = (on_event != 0).then(|| {
+        let callback: UniencDataCallback<UniencReconnectEvent> =
+            unsafe { std::mem::transmute(on_event) };
+        // a raw pointer is neither Send nor Sync
+        let user_data = *event_user_data as usize;
+        Arc::new(move |event: &ReconnectEvent| {
+            Ok::<_, UniencError>(event)
+                .apply_callback(callback, SendPtr::from(user_data as *mut c_void))
+        }) as Arc<dyn Fn(&ReconnectEvent) + Send + Sync>
+    });
+    Ok(Some(Reconnect { policy, on_event }))
+}
+
+unsafe fn store_muxer(
+    components: unienc::Result<(VideoMuxerInput, AudioMuxerInput, MuxerCompletionHandle)>,
+    video_input_out: *mut *const Mutex<Option<VideoMuxerInput>>,
+    audio_input_out: *mut *const Mutex<Option<AudioMuxerInput>>,
+    completion_handle_out: *mut *const Mutex<Option<MuxerCompletionHandle>>,
+    on_error: UniencCallback,
+    user_data: SendPtr<c_void>,
+) -> bool {
+    match components {
+        Ok((video_input, audio_input, completion_handle)) => unsafe {
+            // Box the completion handle and store as raw pointer
+            *video_input_out = Arc::into_raw(Arc::new(Mutex::new(Some(video_input))));
+            *audio_input_out = Arc::into_raw(Arc::new(Mutex::new(Some(audio_input))));
+            *completion_handle_out = Arc::into_raw(Arc::new(Mutex::new(Some(completion_handle))));
+            true
+        },
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(on_error, user_data);
+            false
         }
     }
 }
 
+/// Muxer inputs and completion handle wrapped as every muxer of this library. The file at `path`
+/// is copied into `target` once complete, if any.
+pub(crate) fn new_muxer_components(
+    system: &PlatformEncodingSystem,
+    path: &Path,
+    target: Option<File>,
+) -> unienc::Result<(VideoMuxerInput, AudioMuxerInput, MuxerCompletionHandle)> {
+    validate_output_path(path)?;
+    let muxer = system.new_muxer(path)?;
+    wrap_muxer(muxer, path, target, &path.display())
+}
+
+/// Muxer inputs and completion handle publishing to the RTMP `url`, wrapped like a file muxer
+/// without a file, which the wrappers that patch finished files leave be.
+fn new_rtmp_muxer_components(
+    system: &PlatformEncodingSystem,
+    url: &str,
+    reconnect: Option<Reconnect>,
+) -> unienc::Result<(VideoMuxerInput, AudioMuxerInput, MuxerCompletionHandle)> {
+    validate_rtmp_url(url)?;
+    let muxer = system.new_rtmp_muxer(url, reconnect)?;
+    wrap_muxer(muxer, Path::new(""), None, &redact_stream_key(url))
+}
+
+/// Muxer inputs and completion handle publishing to the WHIP endpoint `url`, wrapped like
+/// [`new_rtmp_muxer_components`].
+fn new_whip_muxer_components(
+    system: &PlatformEncodingSystem,
+    url: &str,
+    bearer_token: Option<&str>,
+    reconnect: Option<Reconnect>,
+) -> unienc::Result<(VideoMuxerInput, AudioMuxerInput, MuxerCompletionHandle)> {
+    validate_whip_url(url)?;
+    if let Some(token) = bearer_token {
+        validate_bearer_token(token)?;
+    }
+    let muxer = system.new_whip_muxer(url, bearer_token, reconnect)?;
+    wrap_muxer(muxer, Path::new(""), None, &without_query(url))
+}
+
+/// Muxer inputs and completion handle publishing over SRT to `url`, wrapped like
+/// [`new_rtmp_muxer_components`].
+fn new_srt_muxer_components(
+    system: &PlatformEncodingSystem,
+    url: &str,
+    reconnect: Option<Reconnect>,
+) -> unienc::Result<(VideoMuxerInput, AudioMuxerInput, MuxerCompletionHandle)> {
+    validate_srt_url(url)?;
+    let muxer = system.new_srt_muxer(url, reconnect)?;
+    wrap_muxer(muxer, Path::new(""), None, &without_query(url))
+}
+
+/// Muxer inputs and completion handle publishing to `url` over the protocol of its scheme: SRT
+/// for `srt://`, WHIP for `http(s)://` authorizing with `bearer_token`, and RTMP otherwise.
+pub(crate) fn new_stream_muxer_components(
+    system: &PlatformEncodingSystem,
+    url: &str,
+    bearer_token: Option<&str>,
+    reconnect: Option<Reconnect>,
+) -> unienc::Result<(VideoMuxerInput, AudioMuxerInput, MuxerCompletionHandle)> {
+    let scheme = url.split_once("://").map_or("", |(scheme, _)| scheme);
+    match scheme.to_ascii_lowercase().as_str() {
+        "srt" => new_srt_muxer_components(system, url, reconnect),
+        "http" | "https" => new_whip_muxer_components(system, url, bearer_token, reconnect),
+        // reports URLs of no protocol as not RTMP
+        _ => new_rtmp_muxer_components(system, url, reconnect),
+    }
+}
+
+fn wrap_muxer(
+    muxer: <PlatformEncodingSystem as EncodingSystem>::MuxerType,
+    path: &Path,
+    target: Option<File>,
+    name: &dyn std::fmt::Display,
+) -> unienc::Result<(VideoMuxerInput, AudioMuxerInput, MuxerCompletionHandle)> {
+    let (video_input, audio_input, completion_handle) =
+        muxer.get_inputs().context("Failed to get muxer input")?;
+    unienc::log_capture::event(format_args!("muxer created for {name}"));
+    unienc::progress::reset();
+    let (video_input, completion_handle) = detect_empty(video_input, completion_handle, path);
+    let (video_input, audio_input) = interleave(video_input, audio_input, MAX_INTERLEAVE_SKEW);
+    let completion_handle = DescriptorCompletionHandle::new(
+        TimecodeCompletionHandle::new(
+            SphericalCompletionHandle::new(completion_handle, path),
+            path,
+        ),
+        path,
+        target,
+    );
+    Ok((
+        TeeMuxerInput::new(LimitedMuxerInput::video(video_input)),
+        TeeMuxerInput::new(LimitedMuxerInput::audio(audio_input)),
+        completion_handle,
+    ))
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn unienc_is_blit_supported(system: *const PlatformEncodingSystem) -> bool {
     unsafe { &*system }.is_blit_supported()
 }
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_is_alpha_supported(system: *const PlatformEncodingSystem) -> bool {
+    unsafe { &*system }.is_alpha_supported()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_is_codec_supported(
+    system: *const PlatformEncodingSystem,
+    codec: UniencVideoCodec,
+) -> bool {
+    unsafe { &*system }.is_codec_supported(codec.to_codec())
+}
@@ -0,0 +1,17 @@
+//! Registration of an encoding backend implemented outside this repository. It is called by the
+//! native code of that backend rather than from C#, so it is not part of the generated bindings.
+
+use unienc::external::UniencExternalBackend;
+
+/// Registers the backend used by encoding systems created afterwards, on platforms without one in
+/// this repository or in builds with the `external` feature. `backend` is copied. Returns `false`
+/// if it is null or a backend was already registered.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_register_external_backend(
+    backend: *const UniencExternalBackend,
+) -> bool {
+    let Some(backend) = (unsafe { backend.as_ref() }) else {
+        return false;
+    };
+    unienc::external::register(*backend)
+}
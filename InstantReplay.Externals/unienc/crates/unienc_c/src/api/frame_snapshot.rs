@@ -0,0 +1,115 @@
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use crate::*;
+use tokio::sync::Mutex;
+use unienc::{FrameSnapshot, SnapshotInfo};
+
+// A frame snapshot keeps the latest frame pushed to a video encoder input as it is encoded, so the
+// host can save a screenshot of the end of a replay from the frames it already records.
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_frame_snapshot(runtime: *mut Runtime) -> *const FrameSnapshot {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    Arc::into_raw(Arc::new(FrameSnapshot::new()))
+}
+
+/// Keeps a copy of every frame pushed to `input` afterwards as a shared buffer in `snapshot`,
+/// after filters. Blitted frames are not kept. A null `snapshot` stops keeping frames.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_set_frame_snapshot(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    snapshot: *const FrameSnapshot,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let snapshot = (!snapshot.is_null()).then(|| arc_from_raw_retained(snapshot));
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.snapshot_mut().set_snapshot(snapshot);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Copies the BGRA pixels of the latest frame kept, rows packed without padding, into `data` if
+/// its `len` is at least `width * height * 4` bytes, and reports the frame. When `data` is too
+/// small nothing is copied and `copied` is false, so a buffer can be sized from `width` and
+/// `height`. Frames keep being encoded meanwhile. `callback` is called synchronously.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_frame_snapshot_copy(
+    runtime: *mut Runtime,
+    snapshot: *const FrameSnapshot,
+    data: *mut u8,
+    len: usize,
+    callback: usize, /*UniencDataCallback<UniencFrameSnapshot>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencFrameSnapshot> =
+        unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        Err::<UniencFrameSnapshot, _>(UniencError::invalid_input_error("Invalid input parameters"))
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(snapshot) = (unsafe { snapshot.as_ref() }) else {
+        Err::<UniencFrameSnapshot, _>(UniencError::invalid_input_error("Invalid input parameters"))
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    let target = match data.is_null() {
+        true => &mut [][..],
+        false => unsafe { std::slice::from_raw_parts_mut(data, len) },
+    };
+    snapshot
+        .copy_latest(target)
+        .map(UniencFrameSnapshot::from)
+        .ok_or(UniencError::invalid_input_error(
+            "No frame has been kept yet",
+        ))
+        .apply_callback(callback, user_data);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_frame_snapshot(
+    runtime: *mut Runtime,
+    snapshot: *const FrameSnapshot,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !snapshot.is_null() {
+        arc_from_raw(snapshot);
+    }
+}
+
+impl From<SnapshotInfo> for UniencFrameSnapshot {
+    fn from(info: SnapshotInfo) -> Self {
+        Self {
+            width: info.width,
+            height: info.height,
+            timestamp: info.timestamp,
+            copied: info.copied,
+        }
+    }
+}
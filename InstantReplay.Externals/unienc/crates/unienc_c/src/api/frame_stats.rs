@@ -0,0 +1,93 @@
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use crate::*;
+use tokio::sync::Mutex;
+use unienc::FrameStatsRing;
+
+// Frame stats record the size, keyframe flag and QP (where the encoder reports it) of every
+// encoded video frame, so encoding quality can be graphed over time.
+
+/// Keeps the stats of up to `capacity` frames between drains.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_frame_stats(
+    runtime: *mut Runtime,
+    capacity: usize,
+) -> *const FrameStatsRing {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    Arc::into_raw(Arc::new(FrameStatsRing::new(capacity)))
+}
+
+/// Records the stats of frames pulled from `output` afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_set_frame_stats(
+    runtime: *mut Runtime,
+    output: SendPtr<Mutex<Option<VideoEncoderOutput>>>,
+    stats: *const FrameStatsRing,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if output.is_null() || stats.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let output = arc_from_raw_retained(*output);
+    let stats = arc_from_raw_retained(stats);
+
+    Runtime::spawn(async move {
+        let mut output = output.lock().await;
+        let result = match output.as_mut() {
+            Some(output) => {
+                output.set_frame_stats(stats);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Takes the recorded frames, oldest first. `callback` is called synchronously, and the list is
+/// only valid during it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_frame_stats_drain(
+    runtime: *mut Runtime,
+    stats: *const FrameStatsRing,
+    callback: usize, /*UniencDataCallback<UniencFrameStatsList>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencFrameStatsList> =
+        unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(stats) = (unsafe { stats.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    Ok::<_, UniencError>(stats.drain()).apply_callback(callback, user_data);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_frame_stats(
+    runtime: *mut Runtime,
+    stats: *const FrameStatsRing,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !stats.is_null() {
+        arc_from_raw(stats);
+    }
+}
@@ -1,5 +1,7 @@
 use std::os::raw::c_void;
 
+use crate::*;
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn unienc_free_graphics_event_context(context: *mut c_void) {
     #[cfg(feature = "unity")]
@@ -11,3 +13,182 @@ pub unsafe extern "C" fn unienc_free_graphics_event_context(context: *mut c_void
         }
     }
 }
+
+/// Reports the color space of the Unity project, so blit sources pushed with a contradicting
+/// `is_gamma_workflow` are warned about. Call it once when the plugin loads.
+#[unsafe(no_mangle)]
+pub extern "C" fn unienc_set_project_color_space(is_gamma_workflow: bool) {
+    unienc::color_space::set_project_color_space(if is_gamma_workflow {
+        unienc::ColorSpace::Gamma
+    } else {
+        unienc::ColorSpace::Linear
+    });
+}
+
+/// Limits how far the pools backing in-flight Vulkan blits may grow. Blits beyond the limits are
+/// dropped and counted in `unienc_vulkan_get_pool_stats`. Android builds with the `blit` feature
+/// only; can be called at any time.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_vulkan_set_pool_limits(
+    max_descriptor_sets: u32,
+    max_fences: u32,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+
+    #[cfg(not(all(target_os = "android", feature = "blit")))]
+    {
+        let _ = (max_descriptor_sets, max_fences);
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(all(target_os = "android", feature = "blit"))]
+    {
+        unienc::android::set_vulkan_pool_limits(max_descriptor_sets, max_fences);
+        Ok::<_, UniencError>(()).apply_callback(callback, user_data);
+    }
+}
+
+/// `callback` is called synchronously.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_vulkan_get_pool_stats(
+    callback: usize, /*UniencDataCallback<UniencVulkanPoolStats>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencVulkanPoolStats> =
+        unsafe { std::mem::transmute(callback) };
+
+    #[cfg(not(all(target_os = "android", feature = "blit")))]
+    {
+        Err::<UniencVulkanPoolStats, _>(UniencError::platform_error("Not supported"))
+            .apply_callback(callback, user_data);
+    }
+
+    #[cfg(all(target_os = "android", feature = "blit"))]
+    {
+        let stats = unienc::android::vulkan_pool_stats();
+        Ok::<_, UniencError>(UniencVulkanPoolStats {
+            descriptor_sets: stats.descriptor_sets,
+            descriptor_set_limit: stats.descriptor_set_limit,
+            descriptor_set_starvations: stats.descriptor_set_starvations,
+            fences: stats.fences,
+            fence_limit: stats.fence_limit,
+            fence_starvations: stats.fence_starvations,
+        })
+        .apply_callback(callback, user_data);
+    }
+}
+
+/// Captures the GPU work of the next Metal blit, which is labelled "unienc blit" with a debug group
+/// around each draw. `output` is the path of a `.gputrace` document to write, which needs
+/// `MetalCaptureEnabled` in the app's Info.plist, or null to open the capture in an attached
+/// Xcode. Apple builds with the `blit` feature only.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_metal_capture_next_blit(
+    output: *const std::ffi::c_char,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Ok(output) = (!output.is_null())
+        .then(|| unsafe { std::ffi::CStr::from_ptr(output) }.to_str())
+        .transpose()
+    else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+
+    #[cfg(not(all(target_vendor = "apple", feature = "blit")))]
+    {
+        let _ = output;
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(all(target_vendor = "apple", feature = "blit"))]
+    {
+        unienc::apple::capture_next_blit(output.map(std::path::PathBuf::from));
+        Ok::<_, UniencError>(()).apply_callback(callback, user_data);
+    }
+}
+
+/// Blits with the Vulkan device of a host that does not load the plugin from Unity, such as a
+/// custom engine or a test harness. The host runs the graphics events of blits itself: it writes
+/// the `VkImage*` of the texture to the context it is given, then calls the given function with
+/// event id 0 and the context on a thread that may submit to `queue`. Android builds with the
+/// `blit` feature only; call it before the first blit. Fails if a device is already in use.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_vulkan_set_device(
+    get_instance_proc_addr: *const c_void,
+    instance: *mut c_void,
+    device: *mut c_void,
+    queue_family_index: u32,
+    queue: *mut c_void,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    if get_instance_proc_addr.is_null() || instance.is_null() || device.is_null() || queue.is_null()
+    {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    #[cfg(not(all(target_os = "android", feature = "blit")))]
+    {
+        let _ = queue_family_index;
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(all(target_os = "android", feature = "blit"))]
+    {
+        unsafe {
+            unienc::android::set_vulkan_device(
+                get_instance_proc_addr,
+                instance,
+                device,
+                queue_family_index,
+                queue,
+            )
+        }
+        .map_err(|e| UniencError::from_common(e.into()))
+        .apply_callback(callback, user_data);
+    }
+}
+
+/// Blits with the Metal device of a host that does not load the plugin from Unity, such as a
+/// custom engine or a test harness. Blits are submitted to `command_queue`, or to a queue of their
+/// own if it is null, and the host must commit the work writing a source texture before the
+/// graphics event of its blit runs. The host runs the graphics events of blits itself: it
+/// writes the `MTLTexture` to the context it is given, then calls the given function with event id
+/// 0 and the context. Apple builds with the `blit` feature only; call it before the first blit.
+/// Fails if a device is already in use.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_metal_set_device(
+    device: *mut c_void,
+    command_queue: *mut c_void,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    if device.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    #[cfg(not(all(target_vendor = "apple", feature = "blit")))]
+    {
+        let _ = command_queue;
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(all(target_vendor = "apple", feature = "blit"))]
+    {
+        unsafe { unienc::apple::set_metal_device(device, command_queue) }
+            .map_err(|e| UniencError::from_common(e.into()))
+            .apply_callback(callback, user_data);
+    }
+}
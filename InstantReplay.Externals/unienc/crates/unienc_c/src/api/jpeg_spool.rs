@@ -0,0 +1,321 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::*;
+use unienc::{
+    InterruptedExport, JpegSpool, JpegSpoolOptions, JpegSubsampling, PixelFormat,
+    buffer::SharedBuffer, frame_from_pixels,
+};
+
+// JPEG spools keep the frames of the legacy ring-buffer recorder in the slots of a memory-mapped
+// file, encoding and writing them off the main thread. The spooled frames are enumerated when a
+// replay is saved. Exports are checkpointed next to the spool file, so that one interrupted by
+// the app quitting is found and run again on the next launch.
+
+/// Creates or truncates the spool file at `path`, with `slot_count` slots of `slot_size` bytes,
+/// discarding an export interrupted there. `quality` ranges from 0.0 to 1.0,
+/// `max_bytes_per_second` limits the disk writes or is 0 for no limit, and `queue_capacity` is the
+/// number of frames waiting to be written before pushes fail.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_jpeg_spool(
+    runtime: *mut Runtime,
+    path: *const c_char,
+    slot_count: u32,
+    slot_size: u32,
+    quality: f32,
+    subsampling: UniencJpegSubsampling,
+    max_bytes_per_second: u64,
+    queue_capacity: u32,
+    spool_out: *mut *const JpegSpool,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if path.is_null() || spool_out.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+    let Ok(path) = (unsafe { CStr::from_ptr(path) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    };
+
+    let options = JpegSpoolOptions {
+        quality,
+        subsampling: match subsampling {
+            UniencJpegSubsampling::Yuv444 => JpegSubsampling::Yuv444,
+            UniencJpegSubsampling::Yuv420 => JpegSubsampling::Yuv420,
+        },
+        max_bytes_per_second,
+        queue_capacity: queue_capacity as usize,
+        slot_count,
+        slot_size,
+    };
+    match JpegSpool::new(path.into(), options) {
+        Ok(spool) => {
+            unsafe { *spool_out = Arc::into_raw(Arc::new(spool)) };
+            true
+        }
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(on_error, user_data);
+            false
+        }
+    }
+}
+
+/// Queues a frame to be written into `slot`, replacing the frame there. The callback is invoked
+/// from the writer thread once the slot is complete, or right away if the queue is full. Rows are
+/// `stride` bytes apart, or tightly packed if `stride` is 0.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_jpeg_spool_push(
+    runtime: *mut Runtime,
+    spool: *const JpegSpool,
+    slot: u32,
+    timestamp: f64,
+    buffer: SendPtr<SharedBuffer>,
+    width: u32,
+    height: u32,
+    stride: u32,
+    pixel_format: UniencPixelFormat,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(spool) = (unsafe { spool.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if buffer.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+
+    let buffer = unsafe { Box::from_raw(*buffer) };
+    let pixel_format = match pixel_format {
+        UniencPixelFormat::Bgra32 => PixelFormat::Bgra32,
+        UniencPixelFormat::Rgba32 => PixelFormat::Rgba32,
+        UniencPixelFormat::Rgb565 => PixelFormat::Rgb565,
+    };
+    let frame = match frame_from_pixels(*buffer, width, height, stride, pixel_format) {
+        Ok(frame) => frame,
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(callback, user_data);
+            return;
+        }
+    };
+
+    let pushed = spool.push(slot, timestamp, frame, move |result| {
+        result
+            .map_err(UniencError::from_common)
+            .apply_callback(callback, user_data)
+    });
+    if let Err(err) = pushed {
+        UniencError::from_common(err).apply_callback(callback, user_data);
+    }
+}
+
+/// Delivers the frames written so far, oldest first. `callback` is called synchronously, and the
+/// list is only valid during it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_jpeg_spool_frames(
+    runtime: *mut Runtime,
+    spool: *const JpegSpool,
+    callback: usize, /*UniencDataCallback<UniencSpooledFrameList>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencSpooledFrameList> =
+        unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(spool) = (unsafe { spool.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    spool
+        .frames()
+        .map_err(UniencError::from_common)
+        .apply_callback(callback, user_data);
+}
+
+/// Starts exporting the frames written so far to `output_path`: pushes are rejected and the spool
+/// file is kept if the app quits, until `unienc_jpeg_spool_finish_export`. `metadata` of `size`
+/// bytes is kept for the next launch, such as the export settings, and may be null if `size` is 0.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_jpeg_spool_begin_export(
+    runtime: *mut Runtime,
+    spool: *const JpegSpool,
+    output_path: *const c_char,
+    metadata: *const u8,
+    size: usize,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(spool) = (unsafe { spool.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if output_path.is_null() || (metadata.is_null() && size > 0) {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Ok(output_path) = (unsafe { CStr::from_ptr(output_path) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    let metadata = match size {
+        0 => Vec::new(),
+        _ => unsafe { std::slice::from_raw_parts(metadata, size) }.to_vec(),
+    };
+    spool
+        .begin_export(output_path.into(), metadata)
+        .map_err(UniencError::from_common)
+        .apply_callback(callback, user_data);
+}
+
+/// Records how far the export has got, such as the timestamp of the last frame muxed. Each call
+/// rewrites the checkpoint, so call it every few seconds rather than per frame.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_jpeg_spool_set_export_progress(
+    runtime: *mut Runtime,
+    spool: *const JpegSpool,
+    progress: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(spool) = (unsafe { spool.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    spool
+        .set_export_progress(progress)
+        .map_err(UniencError::from_common)
+        .apply_callback(callback, user_data);
+}
+
+/// Ends the export, whether or not it succeeded, and removes its checkpoint.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_jpeg_spool_finish_export(
+    runtime: *mut Runtime,
+    spool: *const JpegSpool,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(spool) = (unsafe { spool.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    spool
+        .finish_export()
+        .map_err(UniencError::from_common)
+        .apply_callback(callback, user_data);
+}
+
+/// Looks for an export interrupted while reading the spool file at `path`, to be called on launch
+/// before the spool is created again. `callback` is called synchronously, and the export is only
+/// valid during it. The partially written output cannot be completed, so the export is run again
+/// from the frames, then `unienc_discard_interrupted_export` is called.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_find_interrupted_export(
+    path: *const c_char,
+    callback: usize, /*UniencDataCallback<UniencInterruptedExport>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencInterruptedExport> =
+        unsafe { std::mem::transmute(callback) };
+    if path.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Ok(path) = (unsafe { CStr::from_ptr(path) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+
+    InterruptedExport::find(Path::new(path))
+        .map_err(UniencError::from_common)
+        .apply_callback(callback, user_data);
+}
+
+/// Removes the spool file at `path` and the checkpoint of its interrupted export.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_discard_interrupted_export(
+    path: *const c_char,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    if path.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Ok(path) = (unsafe { CStr::from_ptr(path) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+
+    InterruptedExport::discard(Path::new(path))
+        .map_err(UniencError::from_common)
+        .apply_callback(callback, user_data);
+}
+
+/// Waits for the queued frames to be written, then removes the spool file unless it is being
+/// exported.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_jpeg_spool(runtime: *mut Runtime, spool: *const JpegSpool) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !spool.is_null() {
+        arc_from_raw(spool);
+    }
+}
@@ -0,0 +1,255 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::encoding_system::{
+    new_audio_encoder_components, new_muxer_components, new_video_encoder_components,
+};
+use crate::*;
+use tokio::sync::Mutex;
+use unienc::{
+    CancellationToken, CompletionHandle, EncoderInput, EncodingSystem, LadderMuxerInput,
+    LadderVideoInput, PixelFormat, Rendition, ResultExt, VideoFrame, VideoSample,
+    buffer::SharedBuffer, frame_from_pixels, ladder::DEFAULT_LADDER,
+};
+
+// A ladder export encodes the frames of a clip, decoded once, into renditions at several sizes in
+// parallel, with the audio encoded once and muxed into each of them. The renditions are plain MP4
+// files; serving them over HLS or DASH takes a packager to segment them and write the playlists.
+
+/// Number of samples of each track queued while a muxer is busy.
+const PUMP_CAPACITY: usize = 16;
+
+/// Encoding systems of the renditions of a ladder export.
+pub struct LadderExport {
+    #[allow(dead_code)] // kept alive until the export completes
+    systems: Vec<PlatformEncodingSystem>,
+}
+
+/// Creates encoders for renditions of a clip of `video_options` and `audio_options`: the source
+/// size and each of the `count` short sides in `short_sides` below it, or 1080, 720 and 480 if
+/// `short_sides` is null. Each rendition is written to `{name}_{label}.mp4` in `output_directory`,
+/// such as `clip_720p.mp4`. No playlist is written, as the files are not segmented.
+///
+/// Only frames in memory can be pushed to `video_input_out`, which scales them for each rendition.
+/// `on_complete` is called once both inputs have been freed and every file is written, and
+/// `export_out` is freed after that.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_ladder_export(
+    runtime: *mut Runtime,
+    video_options: *const VideoEncoderOptionsNative,
+    audio_options: *const AudioEncoderOptionsNative,
+    short_sides: *const u32,
+    count: usize,
+    output_directory: *const c_char,
+    name: *const c_char,
+    export_out: *mut *mut LadderExport,
+    video_input_out: *mut *const Mutex<Option<LadderVideoEncoderInput>>,
+    audio_input_out: *mut *const Mutex<Option<AudioEncoderInput>>,
+    on_complete: usize, /*UniencCallback*/
+    on_complete_user_data: SendPtr<c_void>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_complete: UniencCallback = unsafe { std::mem::transmute(on_complete) };
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if video_options.is_null()
+        || audio_options.is_null()
+        || output_directory.is_null()
+        || name.is_null()
+        || export_out.is_null()
+        || video_input_out.is_null()
+        || audio_input_out.is_null()
+    {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+    let (directory, name) = unsafe { (CStr::from_ptr(output_directory), CStr::from_ptr(name)) };
+    let (Ok(directory), Ok(name)) = (directory.to_str(), name.to_str()) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    };
+    let directory = Path::new(directory);
+    let short_sides = match short_sides.is_null() {
+        true => &DEFAULT_LADDER[..],
+        false => unsafe { std::slice::from_raw_parts(short_sides, count) },
+    };
+    let (source, audio_options) = unsafe { (*video_options, *audio_options) };
+
+    let renditions = Rendition::ladder(source.width, source.height, source.bitrate, short_sides);
+    let systems = renditions
+        .iter()
+        .map(|rendition| {
+            let video_options = VideoEncoderOptionsNative {
+                width: rendition.width,
+                height: rendition.height,
+                bitrate: rendition.bitrate,
+                offline: true,
+                ..source
+            };
+            PlatformEncodingSystem::new(&video_options, &audio_options, RuntimeSpawner)
+        })
+        .collect::<Vec<_>>();
+
+    let components = (|| -> unienc::Result<_> {
+        let mut video_inputs = Vec::new();
+        let mut tracks = Vec::new();
+        for (system, rendition) in systems.iter().zip(&renditions) {
+            let (video_input, video_output) = new_video_encoder_components(system)?;
+            let path = directory.join(format!("{name}_{}.mp4", rendition.label()));
+            let muxer = new_muxer_components(system, &path, None)?;
+            video_inputs.push((video_input, *rendition));
+            tracks.push((video_output, muxer));
+        }
+        let audio = new_audio_encoder_components(&systems[0], None)?;
+        Ok((video_inputs, tracks, audio))
+    })();
+    let (video_inputs, tracks, (audio_input, audio_output)) =
+        match components.context("Failed to create ladder export") {
+            Ok(components) => components,
+            Err(err) => {
+                UniencError::from_common(err).apply_callback(on_error, user_data);
+                return false;
+            }
+        };
+
+    let mut video_tracks = Vec::new();
+    let mut audio_muxer_inputs = Vec::new();
+    let mut completion_handles = Vec::new();
+    for (video_output, (video_muxer_input, audio_muxer_input, completion_handle)) in tracks {
+        video_tracks.push((video_output, video_muxer_input));
+        audio_muxer_inputs.push(audio_muxer_input);
+        completion_handles.push(completion_handle);
+    }
+    Runtime::spawn(async move {
+        let cancel = CancellationToken::new();
+        let videos = futures::future::join_all(
+            video_tracks
+                .into_iter()
+                .map(|(output, input)| unienc::drive(output, input, PUMP_CAPACITY, &cancel)),
+        );
+        let audio = unienc::drive(
+            audio_output,
+            LadderMuxerInput::new(audio_muxer_inputs),
+            PUMP_CAPACITY,
+            &cancel,
+        );
+        let (videos, audio) = futures::future::join(videos, audio).await;
+
+        let result = async {
+            for video in videos {
+                video.context("Failed to mux rendition video")?;
+            }
+            audio.context("Failed to mux rendition audio")?;
+            let finishes = completion_handles.into_iter().map(|handle| handle.finish());
+            for finish in futures::future::join_all(finishes).await {
+                finish.context("Failed to complete rendition")?;
+            }
+            Ok(())
+        };
+        result
+            .await
+            .map_err(UniencError::from_common)
+            .apply_callback(on_complete, on_complete_user_data);
+    });
+
+    unsafe {
+        *export_out = Box::into_raw(Box::new(LadderExport { systems }));
+        *video_input_out = Arc::into_raw(Arc::new(Mutex::new(Some(LadderVideoInput::new(
+            video_inputs,
+        )))));
+        *audio_input_out = Arc::into_raw(Arc::new(Mutex::new(Some(audio_input))));
+    }
+    true
+}
+
+/// Same as `unienc_video_encoder_push_shared_buffer_with_format`, for the video input of a ladder
+/// export.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_ladder_video_input_push_shared_buffer(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<LadderVideoEncoderInput>>>,
+    buffer: SendPtr<SharedBuffer>,
+    width: u32,
+    height: u32,
+    stride: u32,
+    pixel_format: UniencPixelFormat,
+    timestamp: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    if input.is_null() || buffer.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let buffer = unsafe { Box::from_raw(*buffer) };
+    let pixel_format = match pixel_format {
+        UniencPixelFormat::Bgra32 => PixelFormat::Bgra32,
+        UniencPixelFormat::Rgba32 => PixelFormat::Rgba32,
+        UniencPixelFormat::Rgb565 => PixelFormat::Rgb565,
+    };
+    let frame = match frame_from_pixels(*buffer, width, height, stride, pixel_format) {
+        Ok(frame) => frame,
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(callback, user_data);
+            return;
+        }
+    };
+    let sample = VideoSample {
+        frame: VideoFrame::Bgra32(frame),
+        timestamp,
+    };
+
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+
+        let result = match input
+            .as_mut()
+            .ok_or(UniencError::resource_allocation_error("Resource is None"))
+        {
+            Ok(input) => input
+                .push(sample)
+                .await
+                .context("Failed to push video sample")
+                .map_err(UniencError::from_common),
+            Err(err) => Err(err),
+        };
+
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Frees the video input of a ladder export, which ends the video of every rendition.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_ladder_video_input(
+    runtime: *mut Runtime,
+    video_input: SendPtr<Mutex<Option<LadderVideoEncoderInput>>>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !video_input.is_null() {
+        arc_from_raw(*video_input);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_ladder_export(export: *mut LadderExport) {
+    if !export.is_null() {
+        unsafe {
+            let _ = Box::from_raw(export);
+        }
+    }
+}
@@ -1,5 +1,27 @@
 mod audio;
+mod bench;
+mod captions;
+mod clock;
+mod deadline;
+mod decode;
+mod diagnostics;
+mod dual_sink;
+mod external;
+mod frame_snapshot;
+mod frame_stats;
+mod jpeg_spool;
+mod ladder;
 mod mux;
+mod passthrough;
+mod process;
+mod replay_buffer;
+mod replay_data;
+mod replay_kit;
+mod screen_capture;
+mod session_capture;
+mod session_dir;
+mod share;
+mod still_image;
 mod video;
 
 #[cfg(target_os = "android")]
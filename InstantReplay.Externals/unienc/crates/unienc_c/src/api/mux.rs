@@ -1,8 +1,14 @@
 use std::ffi::c_void;
+use std::sync::Arc;
 
+use super::deadline::bounded_push;
 use crate::*;
-use tokio::sync::Mutex;
-use unienc::{CompletionHandle, EncodedData, MuxerInput, ResultExt};
+use tokio::sync::{Mutex, oneshot};
+use unienc::progress::{self, Stage, Track};
+use unienc::{
+    CompletionHandle, DurationLimit, EncodedData, MuxerInput, ResultExt, TeeMuxerInput, Timecode,
+    fit_video_bitrate,
+};
 
 // Muxer input functions
 #[unsafe(no_mangle)]
@@ -47,18 +53,26 @@ pub unsafe extern "C" fn unienc_muxer_push_video(
         decoded_data.set_timestamp(timestamp);
 
         Runtime::spawn(async move {
-            let mut video_input = video_input.lock().await;
-            let result = match video_input
-                .as_mut()
-                .ok_or(UniencError::resource_allocation_error("Resource is None"))
-            {
-                Ok(video_input) => video_input
-                    .push(decoded_data)
-                    .await
-                    .context("Failed to push encoded video sample to muxer")
-                    .map_err(UniencError::from_common),
-                Err(err) => Err(err),
-            };
+            let in_flight = progress::begin(Track::Video, Stage::MuxerPush);
+            let result = bounded_push(async {
+                let mut video_input = video_input.lock().await;
+                match video_input
+                    .as_mut()
+                    .ok_or(UniencError::resource_allocation_error("Resource is None"))
+                {
+                    Ok(video_input) => video_input
+                        .push(decoded_data)
+                        .await
+                        .context("Failed to push encoded video sample to muxer")
+                        .map_err(UniencError::from_common),
+                    Err(err) => Err(err),
+                }
+            })
+            .await;
+            drop(in_flight);
+            if result.is_ok() {
+                progress::accepted(Track::Video, timestamp);
+            }
             result.apply_callback(callback, user_data);
         });
     }
@@ -106,18 +120,26 @@ pub unsafe extern "C" fn unienc_muxer_push_audio(
         decoded_data.set_timestamp(timestamp);
 
         Runtime::spawn(async move {
-            let mut audio_input = audio_input.lock().await;
-            let result = match audio_input
-                .as_mut()
-                .ok_or(UniencError::resource_allocation_error("Resource is None"))
-            {
-                Ok(audio_input) => audio_input
-                    .push(decoded_data)
-                    .await
-                    .context("Failed to push encoded audio sample to muxer")
-                    .map_err(UniencError::from_common),
-                Err(err) => Err(err),
-            };
+            let in_flight = progress::begin(Track::Audio, Stage::MuxerPush);
+            let result = bounded_push(async {
+                let mut audio_input = audio_input.lock().await;
+                match audio_input
+                    .as_mut()
+                    .ok_or(UniencError::resource_allocation_error("Resource is None"))
+                {
+                    Ok(audio_input) => audio_input
+                        .push(decoded_data)
+                        .await
+                        .context("Failed to push encoded audio sample to muxer")
+                        .map_err(UniencError::from_common),
+                    Err(err) => Err(err),
+                }
+            })
+            .await;
+            drop(in_flight);
+            if result.is_ok() {
+                progress::accepted(Track::Audio, timestamp);
+            }
             result.apply_callback(callback, user_data);
         });
     }
@@ -146,6 +168,7 @@ pub unsafe extern "C" fn unienc_muxer_finish_video(
     let video_input = arc_from_raw_retained(*video_input);
 
     Runtime::spawn(async move {
+        let _in_flight = progress::begin(Track::Video, Stage::SinkWrite);
         let mut video_input = video_input.lock().await;
         let result = match video_input
             .take()
@@ -185,6 +208,7 @@ pub unsafe extern "C" fn unienc_muxer_finish_audio(
     let audio_input = arc_from_raw_retained(*audio_input);
 
     Runtime::spawn(async move {
+        let _in_flight = progress::begin(Track::Audio, Stage::SinkWrite);
         let mut audio_input = audio_input.lock().await;
         let result = match audio_input
             .take()
@@ -201,6 +225,8 @@ pub unsafe extern "C" fn unienc_muxer_finish_audio(
     });
 }
 
+/// Completes the file once both inputs are finished. If no video sample was pushed, the file is
+/// removed and this fails with `EmptyRecording` right away on every platform.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn unienc_muxer_complete(
     runtime: *mut Runtime,
@@ -224,6 +250,11 @@ pub unsafe extern "C" fn unienc_muxer_complete(
     let handle = arc_from_raw_retained(*completion_handle);
 
     Runtime::spawn(async move {
+        // completing writes what the muxer holds for both tracks
+        let in_flight = (
+            progress::begin(Track::Video, Stage::SinkWrite),
+            progress::begin(Track::Audio, Stage::SinkWrite),
+        );
         let mut handle = handle.lock().await;
 
         let result = match handle
@@ -237,10 +268,303 @@ pub unsafe extern "C" fn unienc_muxer_complete(
                 .map_err(UniencError::from_common),
             Err(err) => Err(err),
         };
+        drop(in_flight);
+        if result.is_ok() {
+            unienc::log_capture::event(format_args!("muxer completed"));
+        }
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Tags the file as monoscopic equirectangular 360 video. The spherical metadata is written into
+/// the finished file when the muxer is completed, so this must be called before that.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_muxer_set_spherical(
+    runtime: *mut Runtime,
+    completion_handle: SendPtr<Mutex<Option<MuxerCompletionHandle>>>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if completion_handle.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    let _guard = runtime.enter();
+    let handle = arc_from_raw_retained(*completion_handle);
+
+    Runtime::spawn(async move {
+        let mut handle = handle.lock().await;
+        let result = match handle
+            .as_mut()
+            .ok_or(UniencError::resource_allocation_error("Resource is None"))
+        {
+            Ok(handle) => {
+                handle.inner_mut().inner_mut().set_spherical();
+                Ok(())
+            }
+            Err(err) => Err(err),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Adds a timecode track starting at `start_unix_seconds`, the wallclock time of the first frame,
+/// counting `frame_rate` frames per second. The track is written into the finished file when the
+/// muxer is completed, so this must be called before that.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_muxer_set_timecode(
+    runtime: *mut Runtime,
+    completion_handle: SendPtr<Mutex<Option<MuxerCompletionHandle>>>,
+    start_unix_seconds: f64,
+    frame_rate: u32,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if completion_handle.is_null() || !start_unix_seconds.is_finite() || frame_rate == 0 {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    let _guard = runtime.enter();
+    let handle = arc_from_raw_retained(*completion_handle);
+
+    Runtime::spawn(async move {
+        let mut handle = handle.lock().await;
+        let result = match handle
+            .as_mut()
+            .ok_or(UniencError::resource_allocation_error("Resource is None"))
+        {
+            Ok(handle) => {
+                handle.inner_mut().set_timecode(Timecode {
+                    start: start_unix_seconds,
+                    frame_rate,
+                });
+                Ok(())
+            }
+            Err(err) => Err(err),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Stops the muxer once samples reach `max_duration_seconds` after the first pushed one. Samples
+/// beyond the limit are dropped, including those already in flight, and each track is finished at
+/// its first such sample. Once both are finished, the muxer is completed and `on_complete` is
+/// called with the result.
+///
+/// Call this before pushing samples. A track that gets no sample past the limit is finished by
+/// its regular finish call, which keeps succeeding for tracks finished by the limit. The
+/// completion handle is consumed, so `unienc_muxer_complete` must not be called once
+/// `on_complete` has fired. `on_complete` is not called if the limit is never reached.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_muxer_set_max_duration(
+    runtime: *mut Runtime,
+    video_input: SendPtr<Mutex<Option<VideoMuxerInput>>>,
+    audio_input: SendPtr<Mutex<Option<AudioMuxerInput>>>,
+    completion_handle: SendPtr<Mutex<Option<MuxerCompletionHandle>>>,
+    max_duration_seconds: f64,
+    on_complete: usize, /*UniencCallback*/
+    on_complete_user_data: SendPtr<c_void>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    // 0 is rejected there as neither limit is set
+    unsafe {
+        unienc_muxer_set_limits(
+            runtime,
+            video_input,
+            audio_input,
+            completion_handle,
+            max_duration_seconds,
+            0,
+            on_complete,
+            on_complete_user_data,
+            callback,
+            user_data,
+        )
+    }
+}
+
+/// Delivers each encoded video sample pushed after this call to `callback` as well, serialized
+/// as `unienc_video_encoder_pull` delivers it, such as for a custom network sender. The callback is
+/// invoked from the thread pushing samples, for as long as the input is alive. Samples past a limit
+/// set with `unienc_muxer_set_limits` are still delivered.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_muxer_set_video_sample_callback(
+    runtime: *mut Runtime,
+    video_input: SendPtr<Mutex<Option<VideoMuxerInput>>>,
+    callback: usize, /*UniencDataCallback<UniencSampleData>*/
+    user_data: SendPtr<c_void>,
+) {
+    unsafe { set_sample_callback(runtime, video_input, callback, user_data) }
+}
+
+/// Same as `unienc_muxer_set_video_sample_callback`, for encoded audio samples.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_muxer_set_audio_sample_callback(
+    runtime: *mut Runtime,
+    audio_input: SendPtr<Mutex<Option<AudioMuxerInput>>>,
+    callback: usize, /*UniencDataCallback<UniencSampleData>*/
+    user_data: SendPtr<c_void>,
+) {
+    unsafe { set_sample_callback(runtime, audio_input, callback, user_data) }
+}
+
+unsafe fn set_sample_callback<I: MuxerInput<Data: EncodedData>>(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<TeeMuxerInput<I>>>>,
+    callback: usize,
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencSampleData> = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        match input.as_mut() {
+            Some(input) => input.set_sink(move |sample| {
+                Ok::<_, UniencError>(sample).apply_callback(callback, user_data)
+            }),
+            None => UniencError::resource_allocation_error("Resource is None")
+                .apply_callback(callback, user_data),
+        }
+    });
+}
+
+/// Like `unienc_muxer_set_max_duration`, also stopping the muxer before the file exceeds
+/// `max_output_bytes`, at the end of the last GOP expected to fit. A GOP larger than all previous
+/// ones is cut short instead, so the file never exceeds the size. Either limit may be 0 for none,
+/// and `on_complete` is called when the first is reached.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_muxer_set_limits(
+    runtime: *mut Runtime,
+    video_input: SendPtr<Mutex<Option<VideoMuxerInput>>>,
+    audio_input: SendPtr<Mutex<Option<AudioMuxerInput>>>,
+    completion_handle: SendPtr<Mutex<Option<MuxerCompletionHandle>>>,
+    max_duration_seconds: f64,
+    max_output_bytes: u64,
+    on_complete: usize, /*UniencCallback*/
+    on_complete_user_data: SendPtr<c_void>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let on_complete: UniencCallback = unsafe { std::mem::transmute(on_complete) };
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if video_input.is_null()
+        || audio_input.is_null()
+        || completion_handle.is_null()
+        || max_duration_seconds.is_nan()
+        || max_duration_seconds < 0.0
+        || (max_duration_seconds == 0.0 && max_output_bytes == 0)
+    {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    let _guard = runtime.enter();
+    let video_input = arc_from_raw_retained(*video_input);
+    let audio_input = arc_from_raw_retained(*audio_input);
+    let handle = arc_from_raw_retained(*completion_handle);
+
+    let (reached_tx, reached_rx) = oneshot::channel();
+    let max_duration_seconds = if max_duration_seconds == 0.0 {
+        f64::INFINITY
+    } else {
+        max_duration_seconds
+    };
+    let mut limit = DurationLimit::new(max_duration_seconds, move || {
+        let _ = reached_tx.send(());
+    });
+    if max_output_bytes > 0 {
+        limit = limit.with_max_bytes(max_output_bytes);
+    }
+    let limit = Arc::new(limit);
+
+    Runtime::spawn(async move {
+        let result = {
+            let mut video_input = video_input.lock().await;
+            let mut audio_input = audio_input.lock().await;
+            match (video_input.as_mut(), audio_input.as_mut()) {
+                (Some(video_input), Some(audio_input)) => {
+                    video_input.inner_mut().set_limit(limit.clone());
+                    audio_input.inner_mut().set_limit(limit);
+                    Ok(())
+                }
+                _ => Err(UniencError::resource_allocation_error("Resource is None")),
+            }
+        };
+        let is_set = result.is_ok();
         result.apply_callback(callback, user_data);
+        // the sender is dropped with the inputs when the limit is never reached
+        if !is_set || reached_rx.await.is_err() {
+            return;
+        }
+
+        let in_flight = (
+            progress::begin(Track::Video, Stage::SinkWrite),
+            progress::begin(Track::Audio, Stage::SinkWrite),
+        );
+        let mut handle = handle.lock().await;
+        let result = match handle
+            .take()
+            .ok_or(UniencError::resource_allocation_error("Resource is None"))
+        {
+            Ok(handle) => handle
+                .finish()
+                .await
+                .context("Failed to complete muxer")
+                .map_err(UniencError::from_common),
+            Err(err) => Err(err),
+        };
+        drop(in_flight);
+        result.apply_callback(on_complete, on_complete_user_data);
     });
 }
 
+/// Video bitrate in bits per second that keeps a file of `duration_seconds` under
+/// `max_output_bytes` with audio of `audio_bitrate`, leaving a margin for rate control and the
+/// container. 0 if the audio alone does not fit.
+#[unsafe(no_mangle)]
+pub extern "C" fn unienc_fit_video_bitrate(
+    max_output_bytes: u64,
+    duration_seconds: f64,
+    audio_bitrate: u32,
+) -> u32 {
+    fit_video_bitrate(max_output_bytes, duration_seconds, audio_bitrate).unwrap_or(0)
+}
+
 // Free functions for muxer components
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn unienc_free_muxer_video_input(
@@ -268,3 +592,77 @@ pub unsafe extern "C" fn unienc_free_muxer_completion_handle(
         arc_from_raw(*completion_handle);
     }
 }
+
+/// Number of encoded samples each track of muxers created afterwards queues before pushes wait
+/// for the file writer. Only supported on iOS and macOS.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_muxer_set_queue_capacity(
+    capacity: usize,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+
+    #[cfg(not(target_vendor = "apple"))]
+    {
+        let _ = capacity;
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(target_vendor = "apple")]
+    {
+        unienc::apple::set_muxer_queue_capacity(capacity);
+        Ok::<_, UniencError>(()).apply_callback(callback, user_data);
+    }
+}
+
+/// Sets the layout of the MP4 and QuickTime files written by muxers created afterwards: with the
+/// index moved to the front for streaming players, or in fragments so that a recording cut short
+/// by a crash remains playable. Only supported with the ffmpeg backend.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_ffmpeg_set_file_layout(
+    layout: *const UniencFileLayout,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(layout) = (unsafe { layout.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let fragment_duration = (layout.fragment_duration != 0.0)
+        .then(|| std::time::Duration::try_from_secs_f64(layout.fragment_duration))
+        .transpose();
+    let Ok(fragment_duration) = fragment_duration else {
+        UniencError::invalid_input_error("Invalid fragment duration")
+            .apply_callback(callback, user_data);
+        return;
+    };
+
+    #[cfg(not(all(
+        target_family = "unix",
+        not(target_vendor = "apple"),
+        not(target_os = "android"),
+        not(target_arch = "wasm32")
+    )))]
+    {
+        let _ = fragment_duration;
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(all(
+        target_family = "unix",
+        not(target_vendor = "apple"),
+        not(target_os = "android"),
+        not(target_arch = "wasm32")
+    ))]
+    {
+        unienc::ffmpeg::set_file_layout(unienc::ffmpeg::FileLayout {
+            faststart: layout.faststart,
+            fragmented: layout.fragmented,
+            fragment_duration,
+        });
+        Ok::<_, UniencError>(()).apply_callback(callback, user_data);
+    }
+}
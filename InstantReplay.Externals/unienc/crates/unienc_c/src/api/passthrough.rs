@@ -0,0 +1,208 @@
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use crate::*;
+use tokio::sync::Mutex;
+use unienc::{AacPacketizer, EncodingSystem, H264Packetizer, MuxerInput, ResultExt};
+
+// Passthrough functions feed elementary streams encoded outside of unienc into a muxer created
+// with unienc_new_muxer, without instantiating encoders.
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_h264_packetizer(
+    runtime: *mut Runtime,
+    system: *const PlatformEncodingSystem,
+    packetizer_out: *mut *const Mutex<Option<H264PacketizerImpl>>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if system.is_null() || packetizer_out.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+
+    unsafe {
+        match (*system)
+            .new_h264_packetizer()
+            .context("Failed to create H.264 packetizer")
+        {
+            Ok(packetizer) => {
+                *packetizer_out = Arc::into_raw(Arc::new(Mutex::new(Some(packetizer))));
+                true
+            }
+            Err(err) => {
+                UniencError::from_common(err).apply_callback(on_error, user_data);
+                false
+            }
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_aac_packetizer(
+    runtime: *mut Runtime,
+    system: *const PlatformEncodingSystem,
+    packetizer_out: *mut *const Mutex<Option<AacPacketizerImpl>>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if system.is_null() || packetizer_out.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+
+    unsafe {
+        match (*system)
+            .new_aac_packetizer()
+            .context("Failed to create AAC packetizer")
+        {
+            Ok(packetizer) => {
+                *packetizer_out = Arc::into_raw(Arc::new(Mutex::new(Some(packetizer))));
+                true
+            }
+            Err(err) => {
+                UniencError::from_common(err).apply_callback(on_error, user_data);
+                false
+            }
+        }
+    }
+}
+
+async fn push_all<I: MuxerInput>(
+    input: &Mutex<Option<I>>,
+    data: Vec<I::Data>,
+) -> Result<(), UniencError> {
+    let mut input = input.lock().await;
+    let input = input
+        .as_mut()
+        .ok_or(UniencError::resource_allocation_error("Resource is None"))?;
+    for data in data {
+        input
+            .push(data)
+            .await
+            .context("Failed to push passthrough sample to muxer")
+            .map_err(UniencError::from_common)?;
+    }
+    Ok(())
+}
+
+/// Pushes a single H.264 access unit in Annex-B format. The first access unit must be an IDR
+/// frame carrying SPS and PPS.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_muxer_push_h264(
+    runtime: *mut Runtime,
+    video_input: SendPtr<Mutex<Option<VideoMuxerInput>>>,
+    packetizer: SendPtr<Mutex<Option<H264PacketizerImpl>>>,
+    data: SendPtr<u8>,
+    size: usize,
+    timestamp: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if video_input.is_null() || packetizer.is_null() || data.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    let _guard = runtime.enter();
+    let video_input = arc_from_raw_retained(*video_input);
+    let packetizer = arc_from_raw_retained(*packetizer);
+    let access_unit = unsafe { std::slice::from_raw_parts(*data, size) }.to_vec();
+
+    Runtime::spawn(async move {
+        let result = async {
+            let data = {
+                let mut packetizer = packetizer.lock().await;
+                packetizer
+                    .as_mut()
+                    .ok_or(UniencError::resource_allocation_error("Resource is None"))?
+                    .packetize(&access_unit, timestamp)
+                    .context("Failed to packetize H.264 access unit")
+                    .map_err(UniencError::from_common)?
+            };
+            push_all(&video_input, data).await
+        }
+        .await;
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Pushes a single raw AAC-LC frame without ADTS header.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_muxer_push_aac(
+    runtime: *mut Runtime,
+    audio_input: SendPtr<Mutex<Option<AudioMuxerInput>>>,
+    packetizer: SendPtr<Mutex<Option<AacPacketizerImpl>>>,
+    data: SendPtr<u8>,
+    size: usize,
+    timestamp_in_samples: u64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if audio_input.is_null() || packetizer.is_null() || data.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    let _guard = runtime.enter();
+    let audio_input = arc_from_raw_retained(*audio_input);
+    let packetizer = arc_from_raw_retained(*packetizer);
+    let frame = unsafe { std::slice::from_raw_parts(*data, size) }.to_vec();
+
+    Runtime::spawn(async move {
+        let result = async {
+            let data = {
+                let mut packetizer = packetizer.lock().await;
+                packetizer
+                    .as_mut()
+                    .ok_or(UniencError::resource_allocation_error("Resource is None"))?
+                    .packetize(&frame, timestamp_in_samples)
+                    .context("Failed to packetize AAC frame")
+                    .map_err(UniencError::from_common)?
+            };
+            push_all(&audio_input, data).await
+        }
+        .await;
+        result.apply_callback(callback, user_data);
+    });
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_h264_packetizer(
+    packetizer: SendPtr<Mutex<Option<H264PacketizerImpl>>>,
+) {
+    if !packetizer.is_null() {
+        arc_from_raw(*packetizer);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_aac_packetizer(
+    packetizer: SendPtr<Mutex<Option<AacPacketizerImpl>>>,
+) {
+    if !packetizer.is_null() {
+        arc_from_raw(*packetizer);
+    }
+}
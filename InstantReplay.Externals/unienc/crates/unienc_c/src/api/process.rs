@@ -0,0 +1,94 @@
+use std::ffi::c_void;
+
+use crate::*;
+
+/// Sets how the ffmpeg backend starts the processes it runs afterwards: with the game's
+/// environment cleared down to what ffmpeg needs, extra variables, another working directory or a
+/// lower priority. Processes are started without a console window and killed once dropped unless
+/// `options` says otherwise, and reaped either way. Only supported with the ffmpeg backend.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_ffmpeg_set_process_options(
+    options: *const UniencProcessOptions,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(options) = (unsafe { options.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+
+    #[cfg(not(all(
+        target_family = "unix",
+        not(target_vendor = "apple"),
+        not(target_os = "android"),
+        not(target_arch = "wasm32")
+    )))]
+    {
+        let _ = options;
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(all(
+        target_family = "unix",
+        not(target_vendor = "apple"),
+        not(target_os = "android"),
+        not(target_arch = "wasm32")
+    ))]
+    {
+        match unsafe { process_options_from_native(options) } {
+            Some(options) => {
+                unienc::ffmpeg::set_process_options(options);
+                Ok::<_, UniencError>(()).apply_callback(callback, user_data);
+            }
+            None => UniencError::invalid_input_error("Invalid process options")
+                .apply_callback(callback, user_data),
+        }
+    }
+}
+
+#[cfg(all(
+    target_family = "unix",
+    not(target_vendor = "apple"),
+    not(target_os = "android"),
+    not(target_arch = "wasm32")
+))]
+unsafe fn process_options_from_native(
+    options: &UniencProcessOptions,
+) -> Option<unienc::ffmpeg::ProcessOptions> {
+    use std::ffi::CStr;
+
+    let environment = match options.environment.is_null() {
+        true => &[][..],
+        false => unsafe {
+            std::slice::from_raw_parts(options.environment, options.environment_count)
+        },
+    };
+    let environment = environment
+        .iter()
+        .map(|&variable| {
+            let variable = unsafe { variable.as_ref().map(|v| CStr::from_ptr(v)) }?;
+            let (name, value) = variable.to_str().ok()?.split_once('=')?;
+            (!name.is_empty()).then(|| (name.into(), value.into()))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let working_directory = match options.working_directory.is_null() {
+        true => None,
+        false => Some(
+            unsafe { CStr::from_ptr(options.working_directory) }
+                .to_str()
+                .ok()?
+                .into(),
+        ),
+    };
+
+    Some(unienc::ffmpeg::ProcessOptions {
+        clear_environment: options.clear_environment,
+        environment,
+        working_directory,
+        niceness: options.niceness,
+        hide_window: options.hide_window,
+        kill_on_drop: options.kill_on_drop,
+    })
+}
@@ -0,0 +1,188 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::*;
+use tokio::sync::Mutex;
+use unienc::{
+    CancellationToken, CommonError, EncodingSystem, ExportFilter, Muxer, ReplayBuffer, ResultExt,
+};
+
+// A replay buffer keeps the latest encoded samples in memory. Encoder outputs attached to it are
+// drained natively, so encoded samples no longer go through C# before being exported.
+
+/// Number of samples of each track queued while the buffer is locked by an export.
+const PUMP_CAPACITY: usize = 16;
+
+pub struct ReplayBufferSession {
+    buffer: Arc<ReplayBuffer<VideoEncodedData, AudioEncodedData>>,
+    cancel: CancellationToken,
+}
+
+/// Keeps up to `max_memory_bytes` of encoded samples, evicting the oldest GOPs first. 0 means
+/// unlimited.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_replay_buffer(
+    runtime: *mut Runtime,
+    max_memory_bytes: usize,
+) -> *const ReplayBufferSession {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    Arc::into_raw(Arc::new(ReplayBufferSession {
+        buffer: Arc::new(ReplayBuffer::new(max_memory_bytes)),
+        cancel: CancellationToken::new(),
+    }))
+}
+
+/// Takes the encoder outputs and forwards every sample pulled from them into the buffer.
+/// `callback` is called once both encoders have ended, or with the first error.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_replay_buffer_attach(
+    runtime: *mut Runtime,
+    session: *const ReplayBufferSession,
+    video_output: SendPtr<Mutex<Option<VideoEncoderOutput>>>,
+    audio_output: SendPtr<Mutex<Option<AudioEncoderOutput>>>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if session.is_null() || video_output.is_null() || audio_output.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let session = arc_from_raw_retained(session);
+    let video_output = arc_from_raw_retained(*video_output);
+    let audio_output = arc_from_raw_retained(*audio_output);
+
+    Runtime::spawn(async move {
+        let (video_output, audio_output) = (
+            video_output.lock().await.take(),
+            audio_output.lock().await.take(),
+        );
+        let (Some(video_output), Some(audio_output)) = (video_output, audio_output) else {
+            UniencError::resource_allocation_error("Resource is None")
+                .apply_callback(callback, user_data);
+            return;
+        };
+
+        let buffer = &session.buffer;
+        let (video, audio) = futures::future::join(
+            unienc::drive(
+                video_output,
+                buffer.video_input(),
+                PUMP_CAPACITY,
+                &session.cancel,
+            ),
+            unienc::drive(
+                audio_output,
+                buffer.audio_input(),
+                PUMP_CAPACITY,
+                &session.cancel,
+            ),
+        )
+        .await;
+        let result = match (video, audio) {
+            // freed while the encoders were running
+            (Err(CommonError::Cancelled), _) | (_, Err(CommonError::Cancelled)) => return,
+            (Err(err), _) => Err(err).context("Failed to buffer encoded video"),
+            (_, Err(err)) => Err(err).context("Failed to buffer encoded audio"),
+            (Ok(_), Ok(_)) => Ok(()),
+        };
+        result
+            .map_err(UniencError::from_common)
+            .apply_callback(callback, user_data);
+    });
+}
+
+/// Writes the samples of the last `duration_seconds` that `filter` keeps to an MP4 file at
+/// `output_path`, starting at the closest keyframe. 0 or less exports everything buffered. The
+/// track of a kind the filter drops is written empty. Exported samples are removed from the buffer,
+/// which keeps receiving new ones.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_replay_buffer_export(
+    runtime: *mut Runtime,
+    session: *const ReplayBufferSession,
+    system: *const PlatformEncodingSystem,
+    output_path: *const c_char,
+    duration_seconds: f64,
+    filter: UniencExportFilter,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(system) = (unsafe { system.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if session.is_null() || output_path.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Ok(path) = (unsafe { CStr::from_ptr(output_path) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+    let session = arc_from_raw_retained(session);
+
+    let inputs = system
+        .new_muxer(Path::new(path))
+        .and_then(|muxer| muxer.get_inputs())
+        .context("Failed to create muxer");
+    let (video_input, audio_input, completion_handle) = match inputs {
+        Ok(inputs) => inputs,
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(callback, user_data);
+            return;
+        }
+    };
+    let duration = (duration_seconds > 0.0).then_some(duration_seconds);
+    let filter = match filter {
+        UniencExportFilter::All => ExportFilter::All,
+        UniencExportFilter::VideoOnly => ExportFilter::VideoOnly,
+        UniencExportFilter::AudioOnly => ExportFilter::AudioOnly,
+        UniencExportFilter::Keyframes => ExportFilter::Keyframes,
+    };
+
+    Runtime::spawn(async move {
+        let result = session
+            .buffer
+            .export(
+                duration,
+                filter,
+                video_input,
+                audio_input,
+                completion_handle,
+            )
+            .await
+            .context("Failed to export replay buffer")
+            .map_err(UniencError::from_common);
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Stops forwarding samples from attached encoders, whose outputs are dropped.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_replay_buffer(
+    runtime: *mut Runtime,
+    session: *const ReplayBufferSession,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !session.is_null() {
+        arc_from_raw(session).cancel.cancel();
+    }
+}
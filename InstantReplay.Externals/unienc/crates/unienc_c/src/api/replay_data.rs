@@ -0,0 +1,191 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::sync::Arc;
+
+use crate::*;
+use unienc::{ClockOffset, CommonError, ReplayDataTrack, ReplayEvent, SpawnBlocking};
+
+// Replay data tracks record game-defined input events and state snapshots next to a video, so a
+// replay can be re-simulated as well as watched. Events use the same timestamps as the frames
+// pushed with `unienc_video_encoder_push`.
+
+/// `retention` is the number of seconds kept behind the newest event, usually the length of the
+/// recording buffer. Zero or less keeps every event.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_replay_data_track(
+    runtime: *mut Runtime,
+    retention: f64,
+) -> *const std::sync::Mutex<ReplayDataTrack> {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    let retention = (retention > 0.0).then_some(retention);
+    Arc::into_raw(Arc::new(std::sync::Mutex::new(ReplayDataTrack::new(
+        retention,
+    ))))
+}
+
+/// `data` is copied and may be null when `size` is zero.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_replay_data_push(
+    runtime: *mut Runtime,
+    track: *const std::sync::Mutex<ReplayDataTrack>,
+    timestamp: f64,
+    kind: u32,
+    data: *const u8,
+    size: usize,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(track) = (unsafe { track.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if data.is_null() && size > 0 {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+
+    let data = if size > 0 {
+        unsafe { std::slice::from_raw_parts(data, size) }.to_vec()
+    } else {
+        Vec::new()
+    };
+    let result = track
+        .lock()
+        .map(|mut track| {
+            track.push(ReplayEvent {
+                timestamp,
+                kind,
+                data,
+            })
+        })
+        .map_err(|_| UniencError::resource_allocation_error("Replay data lock is poisoned"));
+    result.apply_callback(callback, user_data);
+}
+
+/// Sets the offset of a shared reference clock measured at `timestamp`, so the written sidecar
+/// carries sync markers on that clock. `offset` is the seconds added to a pipeline timestamp to get
+/// the reference time and `uncertainty` its error bound, usually half the round trip to the time
+/// server.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_replay_data_set_clock_offset(
+    runtime: *mut Runtime,
+    track: *const std::sync::Mutex<ReplayDataTrack>,
+    timestamp: f64,
+    offset: f64,
+    uncertainty: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(track) = (unsafe { track.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if !offset.is_finite() || !uncertainty.is_finite() || uncertainty < 0.0 {
+        UniencError::invalid_input_error("Invalid clock offset")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+
+    let result = track
+        .lock()
+        .map(|mut track| {
+            track.set_clock_offset(
+                timestamp,
+                ClockOffset {
+                    offset,
+                    uncertainty,
+                },
+            )
+        })
+        .map_err(|_| UniencError::resource_allocation_error("Replay data lock is poisoned"));
+    result.apply_callback(callback, user_data);
+}
+
+/// Writes the events between `start_timestamp` and `end_timestamp` to `path`. Pass the timestamp of
+/// the first video frame written to the output file as `start_timestamp` so the sidecar shares the
+/// video's timeline.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_replay_data_write(
+    runtime: *mut Runtime,
+    track: *const std::sync::Mutex<ReplayDataTrack>,
+    path: *const c_char,
+    start_timestamp: f64,
+    end_timestamp: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(track) = (unsafe { track.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if path.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Ok(path) = (unsafe { CStr::from_ptr(path) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    let bytes = match track.lock() {
+        Ok(track) => track
+            .encode_sidecar(start_timestamp, end_timestamp)
+            .map_err(UniencError::from_common),
+        Err(_) => Err(UniencError::resource_allocation_error(
+            "Replay data lock is poisoned",
+        )),
+    };
+    let bytes = match bytes {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            err.apply_callback(callback, user_data);
+            return;
+        }
+    };
+
+    let path = path.to_string();
+    Runtime::spawn(async move {
+        let result = RuntimeSpawner
+            .spawn_blocking(move || std::fs::write(path, bytes))
+            .await
+            .map_err(|e| UniencError::from_common(CommonError::ReplayDataIo(e.to_string())));
+        result.apply_callback(callback, user_data);
+    });
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_replay_data_track(
+    runtime: *mut Runtime,
+    track: *const std::sync::Mutex<ReplayDataTrack>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !track.is_null() {
+        arc_from_raw(track);
+    }
+}
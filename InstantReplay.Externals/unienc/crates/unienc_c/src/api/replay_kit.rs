@@ -0,0 +1,154 @@
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use crate::*;
+use tokio::sync::Mutex;
+
+// ReplayKit interop mode (iOS only): RPScreenRecorder captures the screen and app audio, and the
+// delivered buffers are pushed to the encoder inputs in place of Unity frames and samples.
+
+/// Starts capturing into `video_input` and, if not null, `audio_input`. The handle is written to
+/// `capture_out` immediately and `callback` reports whether the capture actually started. Nothing
+/// else should be pushed to the inputs until the capture is stopped.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_start_replay_kit_capture(
+    runtime: *mut Runtime,
+    video_input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    audio_input: SendPtr<Mutex<Option<AudioEncoderInput>>>,
+    audio_options: *const AudioEncoderOptionsNative,
+    capture_out: *mut *const Mutex<Option<ReplayKitCaptureImpl>>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(audio_options) = (unsafe { audio_options.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if video_input.is_null() || capture_out.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+
+    let capture = Arc::new(Mutex::new(None));
+    unsafe { *capture_out = Arc::into_raw(capture.clone()) };
+
+    #[cfg(not(target_os = "ios"))]
+    {
+        let _ = (audio_input, audio_options, capture);
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(target_os = "ios")]
+    {
+        use unienc::ios::{ReplayKitCapture, ReplayKitSample};
+        use unienc::{AudioEncoderOptions, EncoderInput};
+
+        let video_input = arc_from_raw_retained(*video_input);
+        let audio_input = (!audio_input.is_null()).then(|| arc_from_raw_retained(*audio_input));
+        let (sample_rate, channels) = (audio_options.sample_rate(), audio_options.channels());
+
+        Runtime::spawn(async move {
+            let mut rx = match ReplayKitCapture::start(sample_rate, channels).await {
+                Ok((started, rx)) => {
+                    *capture.lock().await = Some(started);
+                    Ok::<_, UniencError>(()).apply_callback(callback, user_data);
+                    rx
+                }
+                Err(err) => {
+                    UniencError::from_common(err.into()).apply_callback(callback, user_data);
+                    return;
+                }
+            };
+
+            // ends once the capture is stopped
+            while let Some(sample) = rx.recv().await {
+                let result = match sample {
+                    ReplayKitSample::Video {
+                        pixel_buffer,
+                        timestamp,
+                    } => match video_input.lock().await.as_mut() {
+                        Some(input) => input.map_timestamp(timestamp).and_then(|timestamp| {
+                            input
+                                .backend_mut()
+                                .encode_pixel_buffer(&pixel_buffer, timestamp)
+                                .map_err(|err| err.into())
+                        }),
+                        None => break,
+                    },
+                    ReplayKitSample::Audio(sample) => {
+                        let Some(audio_input) = &audio_input else {
+                            continue;
+                        };
+                        match audio_input.lock().await.as_mut() {
+                            Some(input) => input.push(sample).await,
+                            None => continue,
+                        }
+                    }
+                };
+                if let Err(err) = result {
+                    unienc::log!("Failed to push ReplayKit sample: {err}");
+                }
+            }
+        });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_stop_replay_kit_capture(
+    runtime: *mut Runtime,
+    capture: SendPtr<Mutex<Option<ReplayKitCaptureImpl>>>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if capture.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let capture = arc_from_raw_retained(*capture);
+
+    Runtime::spawn(async move {
+        let capture = capture.lock().await.take();
+        let result = match capture {
+            #[cfg(target_os = "ios")]
+            Some(capture) => capture
+                .stop()
+                .await
+                .map_err(|err| UniencError::from_common(err.into())),
+            #[cfg(not(target_os = "ios"))]
+            Some(()) => Err(UniencError::platform_error("Not supported")),
+            None => Err(UniencError::resource_allocation_error(
+                "ReplayKit capture is not running",
+            )),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Freeing does not stop a running capture.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_replay_kit_capture(
+    runtime: *mut Runtime,
+    capture: SendPtr<Mutex<Option<ReplayKitCaptureImpl>>>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !capture.is_null() {
+        arc_from_raw(*capture);
+    }
+}
@@ -0,0 +1,148 @@
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use crate::*;
+use tokio::sync::Mutex;
+
+// ScreenCaptureKit frame source (macOS only): records a window or display of the desktop, such as
+// the whole editor, into a video encoder input in place of Unity frames.
+
+/// Starts capturing into `video_input`, scaled to the size in `video_options`. The handle is
+/// written to `capture_out` immediately and `callback` reports whether the capture actually
+/// started. Nothing else should be pushed to the input until the capture is stopped.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_start_screen_capture(
+    runtime: *mut Runtime,
+    video_input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    video_options: *const VideoEncoderOptionsNative,
+    target: UniencScreenCaptureTarget,
+    capture_out: *mut *const Mutex<Option<ScreenCaptureImpl>>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(video_options) = (unsafe { video_options.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if video_input.is_null() || capture_out.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+
+    let capture = Arc::new(Mutex::new(None));
+    unsafe { *capture_out = Arc::into_raw(capture.clone()) };
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (video_options, target, capture);
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        use unienc::VideoEncoderOptions;
+        use unienc::macos::{ScreenCapture, ScreenCaptureTarget};
+
+        let target = match target {
+            UniencScreenCaptureTarget::CurrentWindow => ScreenCaptureTarget::CurrentWindow,
+            UniencScreenCaptureTarget::MainDisplay => ScreenCaptureTarget::MainDisplay,
+        };
+        let (width, height, fps) = (
+            video_options.width(),
+            video_options.height(),
+            video_options.fps_hint(),
+        );
+        let video_input = arc_from_raw_retained(*video_input);
+
+        Runtime::spawn(async move {
+            let mut rx = match ScreenCapture::start(target, width, height, fps).await {
+                Ok((started, rx)) => {
+                    *capture.lock().await = Some(started);
+                    Ok::<_, UniencError>(()).apply_callback(callback, user_data);
+                    rx
+                }
+                Err(err) => {
+                    UniencError::from_common(err.into()).apply_callback(callback, user_data);
+                    return;
+                }
+            };
+
+            // ends once the capture is stopped
+            while let Some(frame) = rx.recv().await {
+                let mut input = video_input.lock().await;
+                let Some(input) = input.as_mut() else {
+                    break;
+                };
+                let result = input.map_timestamp(frame.timestamp).and_then(|timestamp| {
+                    input
+                        .backend_mut()
+                        .encode_pixel_buffer(&frame.pixel_buffer, timestamp)
+                        .map_err(|err| err.into())
+                });
+                if let Err(err) = result {
+                    unienc::log!("Failed to push captured screen frame: {err}");
+                }
+            }
+        });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_stop_screen_capture(
+    runtime: *mut Runtime,
+    capture: SendPtr<Mutex<Option<ScreenCaptureImpl>>>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if capture.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let capture = arc_from_raw_retained(*capture);
+
+    Runtime::spawn(async move {
+        let capture = capture.lock().await.take();
+        let result = match capture {
+            #[cfg(target_os = "macos")]
+            Some(capture) => capture
+                .stop()
+                .await
+                .map_err(|err| UniencError::from_common(err.into())),
+            #[cfg(not(target_os = "macos"))]
+            Some(()) => Err(UniencError::platform_error("Not supported")),
+            None => Err(UniencError::resource_allocation_error(
+                "Screen capture is not running",
+            )),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Freeing does not stop a running capture.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_screen_capture(
+    runtime: *mut Runtime,
+    capture: SendPtr<Mutex<Option<ScreenCaptureImpl>>>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !capture.is_null() {
+        arc_from_raw(*capture);
+    }
+}
@@ -0,0 +1,133 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::path::PathBuf;
+
+use crate::*;
+use unienc::session_capture::{
+    SessionReplay, read_session_capture, replay_session_capture, start_session_capture,
+    stop_session_capture, write_session_capture,
+};
+use unienc::{EncodingSystem, SpawnBlocking};
+
+// Session captures record the samples pushed to every encoder, in order, so a session can be
+// replayed into another backend or build to compare encoders on identical input, or to reproduce a
+// bug reported with one.
+
+/// Records the samples pushed to video and audio encoders from now on, discarding a capture in
+/// progress. Pixels and audio are kept until they add up to `data_limit` bytes, and only their
+/// hashes afterwards; replays push synthetic frames and silence in their place.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_start_session_capture(data_limit: usize) {
+    start_session_capture(data_limit);
+}
+
+/// Stops the capture in progress and writes it to `output_path`, or discards it if
+/// `output_path` is null. Fails if no capture is on.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_stop_session_capture(
+    runtime: *mut Runtime,
+    output_path: *const c_char,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Ok(output_path) = (!output_path.is_null())
+        .then(|| unsafe { CStr::from_ptr(output_path) }.to_str())
+        .transpose()
+    else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(samples) = stop_session_capture() else {
+        UniencError::invalid_input_error("No session capture is on")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Some(output_path) = output_path.map(PathBuf::from) else {
+        Ok::<_, UniencError>(()).apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+
+    Runtime::spawn(async move {
+        RuntimeSpawner
+            .spawn_blocking(move || write_session_capture(&samples, &output_path))
+            .await
+            .map_err(UniencError::from_common)
+            .apply_callback(callback, user_data);
+    });
+}
+
+/// Replays the capture at `capture_path` into new encoders created with `video_options` and
+/// `audio_options`, pushing its samples in their captured order and writing the result to
+/// `output_path`. Runs on its own encoding system, so it must not overlap a recording. `callback`
+/// is called once the file is complete.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_replay_session_capture(
+    runtime: *mut Runtime,
+    video_options: *const VideoEncoderOptionsNative,
+    audio_options: *const AudioEncoderOptionsNative,
+    capture_path: *const c_char,
+    output_path: *const c_char,
+    callback: usize, /*UniencDataCallback<UniencSessionReplay>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencSessionReplay> =
+        unsafe { std::mem::transmute(callback) };
+    let (Some(runtime), Some(&video_options), Some(&audio_options)) = (
+        unsafe { runtime.as_ref() },
+        unsafe { video_options.as_ref() },
+        unsafe { audio_options.as_ref() },
+    ) else {
+        Err::<UniencSessionReplay, _>(UniencError::invalid_input_error("Invalid input parameters"))
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if capture_path.is_null() || output_path.is_null() {
+        Err::<UniencSessionReplay, _>(UniencError::invalid_input_error("Invalid input parameters"))
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let (capture_path, output_path) =
+        unsafe { (CStr::from_ptr(capture_path), CStr::from_ptr(output_path)) };
+    let (Ok(capture_path), Ok(output_path)) = (capture_path.to_str(), output_path.to_str()) else {
+        Err::<UniencSessionReplay, _>(UniencError::invalid_input_error("Invalid input parameters"))
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let (capture_path, output_path) = (PathBuf::from(capture_path), PathBuf::from(output_path));
+    let _guard = runtime.enter();
+
+    Runtime::spawn(async move {
+        let result = async {
+            let samples = RuntimeSpawner
+                .spawn_blocking(move || read_session_capture(&capture_path))
+                .await?;
+            let system =
+                PlatformEncodingSystem::new(&video_options, &audio_options, RuntimeSpawner);
+            let result = replay_session_capture(&system, &samples, &output_path).await;
+            system.shutdown().await.and(result)
+        };
+        result
+            .await
+            .map(UniencSessionReplay::from)
+            .map_err(UniencError::from_common)
+            .apply_callback(callback, user_data);
+    });
+}
+
+impl From<SessionReplay> for UniencSessionReplay {
+    fn from(replay: SessionReplay) -> Self {
+        Self {
+            video_frames: replay.video_frames,
+            audio_samples: replay.audio_samples,
+            synthesized: replay.synthesized,
+            seconds: replay.elapsed.as_secs_f64(),
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::*;
+use unienc::SpawnBlocking;
+use unienc::session_dir::{PurgeReport, SessionDir, purge_stale_sessions};
+
+// Session directories hold the spool files, journals and intermediate segments of one recording
+// session. A session that completes removes its directory, and one that fails or is freed keeps it
+// for diagnostics until the stale sessions are purged, which is meant to be done on launch.
+
+/// Creates the directory of a new session in `root`, creating `root` as well if needed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_session_dir(
+    root: *const c_char,
+    session_out: *mut *mut SessionDir,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    if root.is_null() || session_out.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+    let Ok(root) = (unsafe { CStr::from_ptr(root) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    };
+
+    match SessionDir::create(Path::new(root)) {
+        Ok(session) => {
+            unsafe { *session_out = Box::into_raw(Box::new(session)) };
+            true
+        }
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(on_error, user_data);
+            false
+        }
+    }
+}
+
+/// Passes the path of the session directory to `callback` synchronously; it is only valid during
+/// the callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_session_dir_path(
+    session: *const SessionDir,
+    callback: usize, /*UniencDataCallback<UniencSessionDirPath>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencSessionDirPath> =
+        unsafe { std::mem::transmute(callback) };
+    let Some(session) = (unsafe { session.as_ref() }) else {
+        Err::<&str, _>(UniencError::invalid_input_error("Invalid input parameters"))
+            .apply_callback(callback, user_data);
+        return;
+    };
+
+    session
+        .path()
+        .to_str()
+        .ok_or_else(|| UniencError::invalid_input_error("Session directory path is not UTF-8"))
+        .apply_callback(callback, user_data);
+}
+
+/// Removes the session directory with everything in it and frees `session`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_complete_session_dir(
+    runtime: *mut Runtime,
+    session: *mut SessionDir,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if session.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let session = unsafe { Box::from_raw(session) };
+    let _guard = runtime.enter();
+
+    Runtime::spawn(async move {
+        RuntimeSpawner
+            .spawn_blocking(move || session.complete())
+            .await
+            .map_err(UniencError::from_common)
+            .apply_callback(callback, user_data);
+    });
+}
+
+/// Keeps the session directory, marking it as failed for `reason`, and frees `session`. `reason`
+/// may be null if there is none to give.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_fail_session_dir(session: *mut SessionDir, reason: *const c_char) {
+    if session.is_null() {
+        return;
+    }
+    let session = unsafe { Box::from_raw(session) };
+    let reason = if reason.is_null() {
+        "failed".into()
+    } else {
+        unsafe { CStr::from_ptr(reason) }.to_string_lossy()
+    };
+    session.fail(&reason);
+}
+
+/// Frees `session`, keeping its directory as failed if it was not completed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_session_dir(session: *mut SessionDir) {
+    if !session.is_null() {
+        unsafe {
+            let _ = Box::from_raw(session);
+        }
+    }
+}
+
+/// Removes the session directories in `root` last modified at least `retention_seconds` ago,
+/// except those of this process, whether they failed or were left by a crash. Other files in
+/// `root` are left alone.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_purge_stale_session_dirs(
+    runtime: *mut Runtime,
+    root: *const c_char,
+    retention_seconds: f64,
+    callback: usize, /*UniencDataCallback<UniencPurgeReport>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencPurgeReport> = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        Err::<UniencPurgeReport, _>(UniencError::invalid_input_error("Invalid input parameters"))
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let Ok(retention) = Duration::try_from_secs_f64(retention_seconds) else {
+        Err::<UniencPurgeReport, _>(UniencError::invalid_input_error("Invalid input parameters"))
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if root.is_null() {
+        Err::<UniencPurgeReport, _>(UniencError::invalid_input_error("Invalid input parameters"))
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Ok(root) = (unsafe { CStr::from_ptr(root) }).to_str() else {
+        Err::<UniencPurgeReport, _>(UniencError::invalid_input_error("Invalid input parameters"))
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let root = PathBuf::from(root);
+    let _guard = runtime.enter();
+
+    Runtime::spawn(async move {
+        RuntimeSpawner
+            .spawn_blocking(move || purge_stale_sessions(&root, retention))
+            .await
+            .map(UniencPurgeReport::from)
+            .map_err(UniencError::from_common)
+            .apply_callback(callback, user_data);
+    });
+}
+
+impl From<PurgeReport> for UniencPurgeReport {
+    fn from(report: PurgeReport) -> Self {
+        Self {
+            removed: report.removed,
+            kept: report.kept,
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::encoding_system::{
+    new_audio_encoder_components, new_muxer_components, new_video_encoder_components,
+};
+use crate::*;
+use tokio::sync::Mutex;
+use unienc::{
+    CancellationToken, CompletionHandle, DurationLimit, EncodingSystem, FaststartCompletionHandle,
+    LoudnessMeter, ResultExt, SharePreset,
+};
+
+// A share export encodes a clip ready to be posted to social and messaging apps in one call: the
+// encoders are set up with the settings of `SharePreset`, and their output is muxed natively.
+
+/// Number of samples of each track queued while the muxer is busy.
+const PUMP_CAPACITY: usize = 16;
+
+/// Creates an encoding system with encoders for a clip ready to be shared, written to
+/// `output_path`: scaled down to 720p, with a capped bitrate, audio normalized to the loudness
+/// measured by `loudness_meter` (which may be null), at most 60 seconds long and with the movie
+/// header at the front. `max_output_bytes` also keeps the file under a size, or is 0.
+///
+/// `video_options` and `audio_options` describe the source and `duration_seconds` its expected
+/// length; they are overwritten with the options used. Blitted frames are scaled, while pushed
+/// frames must have the frame size of the options used. Encoded samples are muxed natively, and
+/// `on_complete` is called once both inputs have been freed and the file is written. `system_out`
+/// is freed after that.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_share_export(
+    runtime: *mut Runtime,
+    video_options: *mut VideoEncoderOptionsNative,
+    audio_options: *mut AudioEncoderOptionsNative,
+    duration_seconds: f64,
+    loudness_meter: *const std::sync::Mutex<LoudnessMeter>,
+    max_output_bytes: u64,
+    output_path: *const c_char,
+    system_out: *mut *mut PlatformEncodingSystem,
+    video_input_out: *mut *const Mutex<Option<VideoEncoderInput>>,
+    audio_input_out: *mut *const Mutex<Option<AudioEncoderInput>>,
+    on_complete: usize, /*UniencCallback*/
+    on_complete_user_data: SendPtr<c_void>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_complete: UniencCallback = unsafe { std::mem::transmute(on_complete) };
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if video_options.is_null()
+        || audio_options.is_null()
+        || output_path.is_null()
+        || system_out.is_null()
+        || video_input_out.is_null()
+        || audio_input_out.is_null()
+        || duration_seconds.is_nan()
+        || duration_seconds <= 0.0
+    {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+    let Ok(path) = (unsafe { CStr::from_ptr(output_path) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    };
+    let path = Path::new(path);
+    let (video_options, audio_options) = unsafe { (&mut *video_options, &mut *audio_options) };
+
+    let preset = SharePreset {
+        max_output_bytes: (max_output_bytes > 0).then_some(max_output_bytes),
+        ..Default::default()
+    };
+    let source = *video_options;
+    audio_options.bitrate = preset.audio_bitrate(audio_options.bitrate);
+    let Some(bitrate) = preset.video_bitrate(
+        source.width,
+        source.height,
+        source.bitrate,
+        duration_seconds,
+        audio_options.bitrate,
+    ) else {
+        UniencError::invalid_input_error("The clip cannot fit in max_output_bytes")
+            .apply_callback(on_error, user_data);
+        return false;
+    };
+    (video_options.width, video_options.height) = preset.video_size(source.width, source.height);
+    video_options.bitrate = bitrate;
+    video_options.offline = true;
+    video_options.codec = UniencVideoCodec::H264;
+    let loudness = unsafe { loudness_meter.as_ref() }
+        .and_then(|meter| meter.lock().unwrap_or_else(|e| e.into_inner()).loudness());
+
+    let system = PlatformEncodingSystem::new(video_options, audio_options, RuntimeSpawner);
+    let components = new_video_encoder_components(&system).and_then(|video| {
+        let audio = new_audio_encoder_components(&system, None)?;
+        let muxer = new_muxer_components(&system, path, None)?;
+        Ok((video, audio, muxer))
+    });
+    let ((video_input, video_output), (mut audio_input, audio_output), muxer) =
+        match components.context("Failed to create share export") {
+            Ok(components) => components,
+            Err(err) => {
+                UniencError::from_common(err).apply_callback(on_error, user_data);
+                return false;
+            }
+        };
+    let (mut video_muxer_input, mut audio_muxer_input, completion_handle) = muxer;
+
+    audio_input
+        .analyzed_mut()
+        .set_gain(preset.gain_db(loudness));
+    let mut limit = DurationLimit::new(preset.max_duration, || {});
+    if let Some(max_output_bytes) = preset.max_output_bytes {
+        limit = limit.with_max_bytes(max_output_bytes);
+    }
+    let limit = Arc::new(limit);
+    video_muxer_input.inner_mut().set_limit(limit.clone());
+    audio_muxer_input.inner_mut().set_limit(limit);
+    let mut completion_handle = FaststartCompletionHandle::new(completion_handle, path);
+    completion_handle.set_faststart();
+
+    Runtime::spawn(async move {
+        let cancel = CancellationToken::new();
+        let (video, audio) = futures::future::join(
+            unienc::drive(video_output, video_muxer_input, PUMP_CAPACITY, &cancel),
+            unienc::drive(audio_output, audio_muxer_input, PUMP_CAPACITY, &cancel),
+        )
+        .await;
+        let result = match (video, audio) {
+            (Err(err), _) => Err(err).context("Failed to mux shared video"),
+            (_, Err(err)) => Err(err).context("Failed to mux shared audio"),
+            (Ok(_), Ok(_)) => completion_handle
+                .finish()
+                .await
+                .context("Failed to complete share export"),
+        };
+        result
+            .map_err(UniencError::from_common)
+            .apply_callback(on_complete, on_complete_user_data);
+    });
+
+    unsafe {
+        *system_out = Box::into_raw(Box::new(system));
+        *video_input_out = Arc::into_raw(Arc::new(Mutex::new(Some(video_input))));
+        *audio_input_out = Arc::into_raw(Arc::new(Mutex::new(Some(audio_input))));
+    }
+    true
+}
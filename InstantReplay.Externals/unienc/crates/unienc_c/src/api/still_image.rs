@@ -0,0 +1,162 @@
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use crate::*;
+use tokio::sync::Mutex;
+use unienc::{EncodingSystem, ResultExt, StillImageFormat};
+
+// Still image capture reads back blit sources outside of video encoding sessions. Bursts are
+// captured by pushing consecutive frames.
+
+/// `quality` is only used for JPEG and ranges from 0.0 to 1.0. `Bgra32` delivers the read-back
+/// pixels without encoding them. Fails where blitting is not supported.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_new_still_image_capture(
+    runtime: *mut Runtime,
+    system: *const PlatformEncodingSystem,
+    format: UniencStillImageFormat,
+    quality: f32,
+    capture_out: *mut *const Mutex<Option<StillImageCaptureImpl>>,
+    on_error: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) -> bool {
+    let on_error: UniencCallback = unsafe { std::mem::transmute(on_error) };
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+
+    if system.is_null() || capture_out.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+    // frames are read back from the GPU through a blit
+    if !unsafe { &*system }.is_blit_supported() {
+        UniencError::from_common(unienc::CommonError::BlitNotSupported)
+            .apply_callback(on_error, user_data);
+        return false;
+    }
+
+    let format = match format {
+        UniencStillImageFormat::Png => StillImageFormat::Png,
+        UniencStillImageFormat::Jpeg => StillImageFormat::Jpeg { quality },
+        UniencStillImageFormat::Bgra32 => StillImageFormat::Bgra32,
+    };
+
+    unsafe {
+        match (*system)
+            .new_still_image_capture(format)
+            .context("Failed to create still image capture")
+        {
+            Ok(capture) => {
+                *capture_out = Arc::into_raw(Arc::new(Mutex::new(Some(capture))));
+                true
+            }
+            Err(err) => {
+                UniencError::from_common(err).apply_callback(on_error, user_data);
+                false
+            }
+        }
+    }
+}
+
+/// Captures a blit source and delivers the encoded image. The image data is only valid during the
+/// callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_still_image_capture_push_blit_source(
+    runtime: *mut Runtime,
+    capture: SendPtr<Mutex<Option<StillImageCaptureImpl>>>,
+    texture_token: usize,
+    width: u32,
+    height: u32,
+    graphics_format: u32,
+    flip_vertically: bool,
+    is_gamma_workflow: bool,
+    timestamp: f64,
+    issue_graphics_event_callback: usize, /* UniencIssueGraphicsEventCallback */
+    callback: usize,                      /*UniencDataCallback<UniencStillImageData>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencStillImageData> =
+        unsafe { std::mem::transmute(callback) };
+    if capture.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+
+    let _guard = runtime.enter();
+
+    #[cfg(not(feature = "unity"))]
+    {
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(feature = "unity")]
+    {
+        use unienc::{StillImageCapture, VideoFrame, VideoSample};
+
+        let unienc_issue_graphics_event_callback: crate::unity::UniencIssueGraphicsEventCallback =
+            unsafe { std::mem::transmute(issue_graphics_event_callback) };
+
+        // weak runtime for graphics event
+        let weak = runtime.weak();
+
+        unienc::color_space::warn_gamma_workflow(graphics_format, is_gamma_workflow);
+
+        let sample = VideoSample {
+            frame: VideoFrame::BlitSource {
+                texture_token,
+                width,
+                height,
+                graphics_format,
+                options: unienc::BlitOptions {
+                    flip_vertically,
+                    is_gamma_workflow,
+                    ..Default::default()
+                },
+                event_issuer: Box::new(crate::unity::UniencGraphicsEventIssuer::new(
+                    unienc_issue_graphics_event_callback,
+                    weak,
+                )),
+                _phantom: std::marker::PhantomData,
+            },
+            timestamp,
+        };
+
+        let capture = arc_from_raw_retained(*capture);
+
+        Runtime::spawn(async move {
+            let mut capture = capture.lock().await;
+
+            let result = match capture
+                .as_mut()
+                .ok_or(UniencError::resource_allocation_error("Resource is None"))
+            {
+                Ok(capture) => capture
+                    .capture(sample)
+                    .await
+                    .context("Failed to capture still image")
+                    .map_err(UniencError::from_common),
+                Err(err) => Err(err),
+            };
+
+            result.apply_callback(callback, user_data);
+        });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_free_still_image_capture(
+    runtime: *mut Runtime,
+    capture: SendPtr<Mutex<Option<StillImageCaptureImpl>>>,
+) {
+    let _guard = unsafe { runtime.as_ref() }.unwrap().enter();
+    if !capture.is_null() {
+        arc_from_raw(*capture);
+    }
+}
@@ -1,10 +1,14 @@
-use std::ffi::c_void;
+use std::ffi::{CStr, c_char, c_void};
+use std::sync::Arc;
 
+use super::deadline::bounded_push;
 use crate::*;
 use tokio::sync::Mutex;
+use unienc::progress::{self, Stage, Track};
 use unienc::{
-    EncoderInput, EncoderOutput, ResultExt, VideoFrame, VideoFrameBgra32, VideoSample,
-    buffer::SharedBuffer,
+    DedupMode, EncoderInput, EncoderOutput, FrameRate, PacingMode, PixelFormat, PngSequence,
+    Region, RegionBlur, ResultExt, Storyboard, StoryboardOptions, TextureHook, VideoFilter,
+    VideoFrame, VideoFrameBgra32, VideoSample, buffer::SharedBuffer, frame_from_pixels,
 };
 
 // Video encoder input/output functions
@@ -18,6 +22,38 @@ pub unsafe extern "C" fn unienc_video_encoder_push_shared_buffer(
     timestamp: f64,
     callback: usize, /*UniencCallback*/
     user_data: SendPtr<c_void>,
+) {
+    unsafe {
+        unienc_video_encoder_push_shared_buffer_with_format(
+            runtime,
+            input,
+            buffer,
+            width,
+            height,
+            0,
+            UniencPixelFormat::Bgra32,
+            timestamp,
+            callback,
+            user_data,
+        )
+    }
+}
+
+/// Pushes pixels in `pixel_format`, such as RGBA or RGB565 GPU readbacks, which are converted to
+/// BGRA natively. Rows are `stride` bytes apart, or tightly packed if `stride` is 0, so padded
+/// readbacks can be pushed without repacking.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_push_shared_buffer_with_format(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    buffer: SendPtr<SharedBuffer>,
+    width: u32,
+    height: u32,
+    stride: u32,
+    pixel_format: UniencPixelFormat,
+    timestamp: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
 ) {
     let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
     if input.is_null() || buffer.is_null() {
@@ -31,12 +67,20 @@ pub unsafe extern "C" fn unienc_video_encoder_push_shared_buffer(
         return;
     };
     let buffer = unsafe { Box::from_raw(*buffer) };
+    let pixel_format = match pixel_format {
+        UniencPixelFormat::Bgra32 => PixelFormat::Bgra32,
+        UniencPixelFormat::Rgba32 => PixelFormat::Rgba32,
+        UniencPixelFormat::Rgb565 => PixelFormat::Rgb565,
+    };
+    let frame = match frame_from_pixels(*buffer, width, height, stride, pixel_format) {
+        Ok(frame) => frame,
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(callback, user_data);
+            return;
+        }
+    };
     let sample = VideoSample {
-        frame: VideoFrame::Bgra32(VideoFrameBgra32 {
-            buffer: *buffer,
-            width,
-            height,
-        }),
+        frame: VideoFrame::Bgra32(frame),
         timestamp,
     };
 
@@ -58,6 +102,85 @@ pub unsafe extern "C" fn unienc_video_encoder_push_blit_source(
     issue_graphics_event_callback: usize, /* UniencIssueGraphicsEventCallback */
     callback: usize,                      /*UniencCallback*/
     user_data: SendPtr<c_void>,
+) {
+    unsafe {
+        unienc_video_encoder_push_blit_source_with_stereo_mode(
+            runtime,
+            input,
+            texture_token,
+            width,
+            height,
+            graphics_format,
+            flip_vertically,
+            is_gamma_workflow,
+            UniencStereoMode::Mono,
+            timestamp,
+            issue_graphics_event_callback,
+            callback,
+            user_data,
+        )
+    }
+}
+
+/// Pushes a blit source that may be an XR texture array. `width` and `height` are the size of a
+/// single eye; with `SideBySide` both eyes are fitted into the frame next to each other.
+#[unsafe(no_mangle)]
+#[allow(dead_code)]
+pub unsafe extern "C" fn unienc_video_encoder_push_blit_source_with_stereo_mode(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    texture_token: usize,
+    width: u32,
+    height: u32,
+    graphics_format: u32,
+    flip_vertically: bool,
+    is_gamma_workflow: bool,
+    stereo_mode: UniencStereoMode,
+    timestamp: f64,
+    issue_graphics_event_callback: usize, /* UniencIssueGraphicsEventCallback */
+    callback: usize,                      /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    unsafe {
+        unienc_video_encoder_push_blit_source_with_projection(
+            runtime,
+            input,
+            texture_token,
+            width,
+            height,
+            graphics_format,
+            flip_vertically,
+            is_gamma_workflow,
+            stereo_mode,
+            UniencProjection::Flat,
+            timestamp,
+            issue_graphics_event_callback,
+            callback,
+            user_data,
+        )
+    }
+}
+
+/// Pushes a blit source that may also be a cubemap. With `Equirectangular` the cubemap is
+/// unwrapped to fill the whole frame and `stereo_mode` is ignored; pair it with
+/// `unienc_muxer_set_spherical` so players recognize the file as 360 video.
+#[unsafe(no_mangle)]
+#[allow(dead_code)]
+pub unsafe extern "C" fn unienc_video_encoder_push_blit_source_with_projection(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    texture_token: usize,
+    width: u32,
+    height: u32,
+    graphics_format: u32,
+    flip_vertically: bool,
+    is_gamma_workflow: bool,
+    stereo_mode: UniencStereoMode,
+    projection: UniencProjection,
+    timestamp: f64,
+    issue_graphics_event_callback: usize, /* UniencIssueGraphicsEventCallback */
+    callback: usize,                      /*UniencCallback*/
+    user_data: SendPtr<c_void>,
 ) {
     let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
     if input.is_null() {
@@ -81,20 +204,39 @@ pub unsafe extern "C" fn unienc_video_encoder_push_blit_source(
 
     #[cfg(feature = "unity")]
     {
+        use unienc::{BlitOptions, Projection, StereoMode};
+
         let unienc_issue_graphics_event_callback: crate::unity::UniencIssueGraphicsEventCallback =
             unsafe { std::mem::transmute(issue_graphics_event_callback) };
 
         // weak runtime for graphics event
         let weak = runtime.weak();
 
+        unienc::color_space::warn_gamma_workflow(graphics_format, is_gamma_workflow);
+
+        let stereo_mode = match stereo_mode {
+            UniencStereoMode::Mono => StereoMode::Mono,
+            UniencStereoMode::LeftEye => StereoMode::LeftEye,
+            UniencStereoMode::RightEye => StereoMode::RightEye,
+            UniencStereoMode::SideBySide => StereoMode::SideBySide,
+        };
+        let projection = match projection {
+            UniencProjection::Flat => Projection::Flat,
+            UniencProjection::Equirectangular => Projection::Equirectangular,
+        };
+
         let sample = VideoSample {
             frame: VideoFrame::BlitSource {
                 texture_token,
                 width,
                 height,
                 graphics_format,
-                flip_vertically,
-                is_gamma_workflow,
+                options: BlitOptions {
+                    flip_vertically,
+                    is_gamma_workflow,
+                    stereo_mode,
+                    projection,
+                },
                 event_issuer: Box::new(crate::unity::UniencGraphicsEventIssuer::new(
                     unienc_issue_graphics_event_callback,
                     weak,
@@ -117,21 +259,705 @@ unsafe fn video_encoder_push_video_sample(
     let _guard = runtime.enter();
     let input = arc_from_raw_retained(*input);
 
+    Runtime::spawn(async move {
+        let result = bounded_push(async {
+            let mut input = input.lock().await;
+            match input
+                .as_mut()
+                .ok_or(UniencError::resource_allocation_error("Resource is None"))
+            {
+                Ok(input) => input
+                    .push(sample)
+                    .await
+                    .context("Failed to push video sample")
+                    .map_err(UniencError::from_common),
+                Err(err) => Err(err),
+            }
+        })
+        .await;
+
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Records the device display through a MediaProjection (a `jobject` the user granted) instead of
+/// pushed frames. Only supported on Android, and only before anything has been pushed to `input`.
+/// `timestamp` is the current time on the clock the other samples are pushed with.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_start_media_projection(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    media_projection: *mut c_void,
+    density_dpi: u32,
+    timestamp: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || media_projection.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+
+    #[cfg(any(not(target_os = "android"), feature = "external"))]
+    {
+        let _ = (density_dpi, timestamp);
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(all(target_os = "android", not(feature = "external")))]
+    {
+        // the reference is only guaranteed to be valid during this call
+        let projection =
+            match unsafe { unienc::android::MediaProjection::from_raw(media_projection) } {
+                Ok(projection) => projection,
+                Err(err) => {
+                    UniencError::from_common(err.into()).apply_callback(callback, user_data);
+                    return;
+                }
+            };
+        let input = arc_from_raw_retained(*input);
+
+        Runtime::spawn(async move {
+            let mut input = input.lock().await;
+            let result = match input
+                .as_mut()
+                .ok_or(UniencError::resource_allocation_error("Resource is None"))
+            {
+                Ok(input) => input
+                    .backend_mut()
+                    .start_media_projection(&projection, density_dpi, timestamp)
+                    .map_err(|err| UniencError::from_common(err.into())),
+                Err(err) => Err(err),
+            };
+            result.apply_callback(callback, user_data);
+        });
+    }
+}
+
+/// Encodes every frame pushed to `input` afterwards with `tier_input` too, reusing the same blit,
+/// so that an encoding system created with smaller video options records a lower quality tier of
+/// the same frames. Frames are scaled by the tier encoder, whose output is pulled as usual.
+/// `tier_input` is consumed and must not be pushed to. A tier that fails to encode a frame is
+/// dropped, ending its output, without failing the push to `input`. Only supported on iOS and
+/// macOS.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_add_tier(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    tier_input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || tier_input.is_null() || *input == *tier_input {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+
+    #[cfg(any(not(target_vendor = "apple"), feature = "external"))]
+    {
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(all(target_vendor = "apple", not(feature = "external")))]
+    {
+        let input = arc_from_raw_retained(*input);
+        let tier_input = arc_from_raw_retained(*tier_input);
+
+        Runtime::spawn(async move {
+            let mut input = input.lock().await;
+            let mut tier_input = tier_input.lock().await;
+            let result = match (input.as_mut(), tier_input.take()) {
+                (Some(input), Some(tier_input)) => {
+                    input.backend_mut().add_tier(tier_input.into_backend());
+                    Ok(())
+                }
+                _ => Err(UniencError::resource_allocation_error("Resource is None")),
+            };
+            result.apply_callback(callback, user_data);
+        });
+    }
+}
+
+/// Retimes frames pushed afterwards to exactly `numerator`/`denominator` frames per second (such
+/// as 30000/1001), starting at the next frame. Missing frames are repeated and extra ones dropped,
+/// or with `blend`, output frames are blended from the two closest shared buffer frames. A
+/// `numerator` of 0 passes frames through with their own timestamps again.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_set_frame_rate(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    numerator: u32,
+    denominator: u32,
+    blend: bool,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || (numerator != 0 && denominator == 0) {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let mode = match blend {
+        true => PacingMode::Blend,
+        false => PacingMode::DropDuplicate,
+    };
+    let pacing = (numerator != 0).then(|| (FrameRate::new(numerator, denominator), mode));
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.paced_mut().set_frame_rate(pacing);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Detects shared buffer frames identical to the previous one from the next frame on, such as on
+/// menus and pause screens. `Skip` leaves them out, holding the previous frame for up to a second
+/// at a time, so the video has a variable frame rate; `Repeat` encodes an exact copy of the
+/// previous frame instead, which takes the fewest bits. Blitted frames are never detected.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_set_dedup(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    mode: UniencDedupMode,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let mode = match mode {
+        UniencDedupMode::Off => None,
+        UniencDedupMode::Skip => Some(DedupMode::Skip),
+        UniencDedupMode::Repeat => Some(DedupMode::Repeat),
+    };
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.dedup_mut().set_mode(mode);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Records a time-lapse from the next frame on: only every `factor`th frame pushed is encoded, and
+/// the kept frames are retimed to follow each other at the interval frames are pushed at, so the
+/// video plays `factor` times as fast at the normal frame rate. Audio pushed to `audio_input`,
+/// which may be null, is left out meanwhile, and `audio_muxer_input`, which may be null too, stops
+/// holding the video back. Audio resuming at normal speed is moved back to follow the video on the
+/// clock set on both encoders. A `factor` of 0 or 1 records at normal speed again.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_set_timelapse(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    audio_input: SendPtr<Mutex<Option<AudioEncoderInput>>>,
+    audio_muxer_input: SendPtr<Mutex<Option<AudioMuxerInput>>>,
+    factor: u32,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let audio_input = (!audio_input.is_null()).then(|| arc_from_raw_retained(*audio_input));
+    let audio_muxer_input =
+        (!audio_muxer_input.is_null()).then(|| arc_from_raw_retained(*audio_muxer_input));
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                let timelapse = input.timelapse_mut();
+                timelapse.set_factor(factor);
+                Ok(timelapse.lag())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        if let Ok(lag) = result {
+            // finished inputs have nothing left to omit
+            if let Some(audio_input) = audio_input
+                && let Some(audio_input) = audio_input.lock().await.as_mut()
+            {
+                audio_input.set_omitted(factor > 1);
+                audio_input.set_lag(lag);
+            }
+            if let Some(audio_muxer_input) = audio_muxer_input
+                && let Some(audio_muxer_input) = audio_muxer_input.lock().await.as_mut()
+                && let Some(interleaved) = audio_muxer_input.inner_mut().inner_mut()
+            {
+                interleaved.set_idle(factor > 1);
+            }
+        }
+        result.map(|_| ()).apply_callback(callback, user_data);
+    });
+}
+
+/// Asks the encoder for a keyframe at shared buffer frames whose luma histogram differs from the
+/// previous frame by more than `threshold`, the share of pixels changing brightness (around 0.5
+/// for hard cuts only), so seeking in exported replays lands on the new scene. Keyframes are
+/// requested at most twice a second. 0 turns detection off. Media Foundation and ffmpeg cannot be
+/// asked for keyframes and ignore it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_set_scene_cut_keyframes(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    threshold: f32,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || !(0.0..=1.0).contains(&threshold) {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let threshold = (threshold > 0.0).then_some(threshold);
+
     Runtime::spawn(async move {
         let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.scene_cut_mut().set_threshold(threshold);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Processes the BGRA pixels of a frame in place: `data` holds `height` rows of `width` pixels,
+/// `stride` bytes apart. Called on a worker thread.
+pub type UniencFilterCallback = unsafe extern "C" fn(
+    data: *mut u8,
+    width: u32,
+    height: u32,
+    stride: u32,
+    user_data: *mut c_void,
+);
+/// Processes the native texture of a blitted frame, called on the render thread before the
+/// encoder samples it.
+pub type UniencTextureFilterCallback =
+    unsafe extern "C" fn(texture: *mut c_void, width: u32, height: u32, user_data: *mut c_void);
+
+/// Filter calling back the host for each frame.
+struct CallbackFilter {
+    process: UniencFilterCallback,
+    texture: Option<UniencTextureFilterCallback>,
+    user_data: SendPtr<c_void>,
+}
+
+impl VideoFilter for CallbackFilter {
+    fn process(&mut self, frame: VideoFrameBgra32) -> unienc::Result<VideoFrameBgra32> {
+        let mut pixels = frame.buffer.data().to_vec();
+        unsafe {
+            (self.process)(
+                pixels.as_mut_ptr(),
+                frame.width,
+                frame.height,
+                frame.stride,
+                *self.user_data,
+            )
+        };
+        Ok(VideoFrameBgra32 {
+            buffer: SharedBuffer::new_unmanaged(pixels),
+            ..frame
+        })
+    }
+
+    fn texture_hook(&self) -> Option<TextureHook> {
+        let texture = self.texture?;
+        // a raw pointer is neither Send nor Sync
+        let user_data = *self.user_data as usize;
+        Some(Arc::new(move |ptr, width, height| unsafe {
+            texture(ptr, width, height, user_data as *mut c_void)
+        }))
+    }
+}
 
+/// Blurs `count` regions of the frames pushed to `input` afterwards, after the filters added
+/// before. Each pixel in a region becomes the average of the pixels up to `radius` away inside it.
+/// Only frames pushed as shared buffers can be blurred; blitted frames are rejected from then on.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_add_blur_regions(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    regions: *const UniencRegion,
+    count: usize,
+    radius: u32,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || (regions.is_null() && count > 0) {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let regions = match count {
+        0 => Vec::new(),
+        _ => unsafe { std::slice::from_raw_parts(regions, count) }
+            .iter()
+            .map(|region| Region {
+                x: region.x,
+                y: region.y,
+                width: region.width,
+                height: region.height,
+            })
+            .collect(),
+    };
+    let filter = RegionBlur::new(regions, radius);
+
+    unsafe { video_encoder_add_filter(runtime, input, filter, callback, user_data) };
+}
+
+/// Passes the frames pushed to `input` afterwards to `process` (a `UniencFilterCallback`), after
+/// the filters added before. Blitted frames are passed to `process_texture` (a
+/// `UniencTextureFilterCallback`) instead, or rejected if it is null. `filter_user_data` is passed
+/// to both until `input` is freed or its filters are cleared.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_add_filter_callback(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    process: usize,         /*UniencFilterCallback*/
+    process_texture: usize, /*UniencTextureFilterCallback*/
+    filter_user_data: SendPtr<c_void>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || process == 0 {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let filter = CallbackFilter {
+        process: unsafe { std::mem::transmute::<usize, UniencFilterCallback>(process) },
+        texture: (process_texture != 0).then(|| unsafe {
+            std::mem::transmute::<usize, UniencTextureFilterCallback>(process_texture)
+        }),
+        user_data: filter_user_data,
+    };
+
+    unsafe { video_encoder_add_filter(runtime, input, filter, callback, user_data) };
+}
+
+unsafe fn video_encoder_add_filter(
+    runtime: &Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    filter: impl VideoFilter,
+    callback: UniencCallback,
+    user_data: SendPtr<c_void>,
+) {
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.filtered_mut().add_filter(filter);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Removes the filters added to `input`, starting with the next frame.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_clear_filters(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.filtered_mut().clear_filters();
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Downscales every `interval`-th frame pushed to `input` afterwards into JPEG sprite sheets of
+/// `columns` x `rows` tiles, written next to `video_path` with a JSON index of the tile timestamps.
+/// Only frames pushed as shared buffers get thumbnails. `quality` ranges from 0.0 to 1.0.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_set_storyboard(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    video_path: *const c_char,
+    interval: u32,
+    tile_width: u32,
+    tile_height: u32,
+    columns: u32,
+    rows: u32,
+    quality: f32,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null()
+        || video_path.is_null()
+        || [interval, tile_width, tile_height, columns, rows].contains(&0)
+    {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Ok(video_path) = (unsafe { CStr::from_ptr(video_path) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let storyboard = Storyboard::new(
+        std::path::Path::new(video_path),
+        StoryboardOptions {
+            interval,
+            tile_width,
+            tile_height,
+            columns,
+            rows,
+            quality,
+        },
+    );
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.storyboard_mut().set_storyboard(storyboard);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Writes the remaining sheet and the index of the storyboard, and reports the first failure to
+/// write a sheet, which stops adding thumbnails without interrupting encoding.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_finish_storyboard(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
         let result = match input
             .as_mut()
             .ok_or(UniencError::resource_allocation_error("Resource is None"))
         {
             Ok(input) => input
-                .push(sample)
-                .await
-                .context("Failed to push video sample")
+                .storyboard_mut()
+                .finish_storyboard()
                 .map_err(UniencError::from_common),
             Err(err) => Err(err),
         };
+        result.apply_callback(callback, user_data);
+    });
+}
+
+/// Writes every frame pushed to `input` afterwards as an RGBA PNG into `directory`, keeping the
+/// alpha channel on encoding systems that cannot encode it. Only frames pushed as shared buffers
+/// are written.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_set_png_sequence(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    directory: *const c_char,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() || directory.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let Ok(directory) = (unsafe { CStr::from_ptr(directory) }).to_str() else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+    let sequence = match PngSequence::new(directory.into()) {
+        Ok(sequence) => sequence,
+        Err(err) => {
+            UniencError::from_common(err).apply_callback(callback, user_data);
+            return;
+        }
+    };
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input.as_mut() {
+            Some(input) => {
+                input.png_sequence_mut().set_png_sequence(sequence);
+                Ok(())
+            }
+            None => Err(UniencError::resource_allocation_error("Resource is None")),
+        };
+        result.apply_callback(callback, user_data);
+    });
+}
 
+/// Writes the index of the PNG sequence, and reports the first failure to write a frame, which
+/// stops the sequence without interrupting encoding.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_video_encoder_finish_png_sequence(
+    runtime: *mut Runtime,
+    input: SendPtr<Mutex<Option<VideoEncoderInput>>>,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    let Some(runtime) = (unsafe { runtime.as_ref() }) else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    if input.is_null() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+    let _guard = runtime.enter();
+    let input = arc_from_raw_retained(*input);
+
+    Runtime::spawn(async move {
+        let mut input = input.lock().await;
+        let result = match input
+            .as_mut()
+            .ok_or(UniencError::resource_allocation_error("Resource is None"))
+        {
+            Ok(input) => input
+                .png_sequence_mut()
+                .finish_png_sequence()
+                .map_err(UniencError::from_common),
+            Err(err) => Err(err),
+        };
         result.apply_callback(callback, user_data);
     });
 }
@@ -159,6 +985,7 @@ pub unsafe extern "C" fn unienc_video_encoder_pull(
     let output = arc_from_raw_retained(*output);
 
     Runtime::spawn_optimistically(async move {
+        let in_flight = progress::begin(Track::Video, Stage::EncoderPull);
         let mut output = output.lock().await;
         let result = match output
             .as_mut()
@@ -171,6 +998,7 @@ pub unsafe extern "C" fn unienc_video_encoder_pull(
                 .map_err(UniencError::from_common),
             Err(err) => Err(err),
         };
+        drop(in_flight);
         result.apply_callback(callback, user_data);
     });
 }
@@ -196,3 +1024,164 @@ pub unsafe extern "C" fn unienc_free_video_encoder_output(
         arc_from_raw(*video_output);
     }
 }
+
+/// Chooses the H.264 encoder of video encoders created afterwards. `preferred` is an encoder name
+/// used whenever the device has it, or null. When MediaCodec picks an encoder on the denylist,
+/// another one is used instead. `denylist` holds `denylist_count` names, which match by prefix
+/// when they end with `*`; a null `denylist` keeps the built-in one. Android only.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_android_set_video_encoder_policy(
+    preferred: *const c_char,
+    denylist: *const *const c_char,
+    denylist_count: usize,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+
+    let to_string = |ptr: *const c_char| unsafe { CStr::from_ptr(ptr) }.to_str().map(String::from);
+    let Ok(preferred) = (!preferred.is_null())
+        .then(|| to_string(preferred))
+        .transpose()
+    else {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    };
+    let denylist = if denylist.is_null() {
+        None
+    } else {
+        let entries = unsafe { std::slice::from_raw_parts(denylist, denylist_count) };
+        if entries.iter().any(|entry| entry.is_null()) {
+            UniencError::invalid_input_error("Invalid input parameters")
+                .apply_callback(callback, user_data);
+            return;
+        }
+        let Ok(entries) = entries
+            .iter()
+            .map(|entry| to_string(*entry))
+            .collect::<Result<Vec<_>, _>>()
+        else {
+            UniencError::invalid_input_error("Invalid input parameters")
+                .apply_callback(callback, user_data);
+            return;
+        };
+        Some(entries)
+    };
+
+    #[cfg(not(target_os = "android"))]
+    {
+        let _ = (preferred, denylist);
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(target_os = "android")]
+    {
+        unienc::android::set_video_encoder_policy(preferred, denylist);
+        Ok::<_, UniencError>(()).apply_callback(callback, user_data);
+    }
+}
+
+/// Lists the H.264 encoders of the device, in MediaCodec's order of preference. `callback` is
+/// called synchronously, and the list is only valid during it. Android only.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_android_list_video_encoders(
+    callback: usize, /*UniencDataCallback<UniencEncoderList>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencEncoderList> = unsafe { std::mem::transmute(callback) };
+
+    #[cfg(not(target_os = "android"))]
+    {
+        Err::<Vec<(String, bool)>, _>(UniencError::platform_error("Not supported"))
+            .apply_callback(callback, user_data);
+    }
+
+    #[cfg(target_os = "android")]
+    {
+        unienc::android::list_video_encoders()
+            .map(|encoders| {
+                encoders
+                    .into_iter()
+                    .map(|e| (e.name, e.hardware_accelerated.unwrap_or(false)))
+                    .collect::<Vec<_>>()
+            })
+            .map_err(|err| UniencError::from_common(err.into()))
+            .apply_callback(callback, user_data);
+    }
+}
+
+/// Sets how long video encoders created afterwards may hold frames without output before they are
+/// flushed. The frames they held are dropped and the recording continues from a keyframe after
+/// the gap. `seconds` of zero or less never flushes; the default is 5. Android only.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_android_set_video_stall_timeout(
+    seconds: f64,
+    callback: usize, /*UniencCallback*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencCallback = unsafe { std::mem::transmute(callback) };
+    if seconds.is_nan() {
+        UniencError::invalid_input_error("Invalid input parameters")
+            .apply_callback(callback, user_data);
+        return;
+    }
+
+    #[cfg(not(target_os = "android"))]
+    {
+        UniencError::platform_error("Not supported").apply_callback(callback, user_data);
+    }
+
+    #[cfg(target_os = "android")]
+    {
+        unienc::android::set_stall_timeout(
+            (seconds > 0.0).then(|| std::time::Duration::from_secs_f64(seconds.min(86400.0))),
+        );
+        Ok::<_, UniencError>(()).apply_callback(callback, user_data);
+    }
+}
+
+/// Stats of the latest video encoder still alive, with the metrics MediaCodec reported at its
+/// start, its latest stall or the end of its input. `callback` is called synchronously, and the
+/// codec name is only valid during it. Android only.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unienc_android_get_video_codec_stats(
+    callback: usize, /*UniencDataCallback<UniencVideoCodecStats>*/
+    user_data: SendPtr<c_void>,
+) {
+    let callback: UniencDataCallback<UniencVideoCodecStats> =
+        unsafe { std::mem::transmute(callback) };
+
+    #[cfg(not(target_os = "android"))]
+    {
+        Err::<UniencVideoCodecStats, _>(UniencError::platform_error("Not supported"))
+            .apply_callback(callback, user_data);
+    }
+
+    #[cfg(target_os = "android")]
+    {
+        let Some(stats) = unienc::android::video_codec_stats() else {
+            UniencError::resource_allocation_error("No video encoder")
+                .apply_callback(callback, user_data);
+            return;
+        };
+        // outlives the callback
+        let codec_name = stats
+            .metrics
+            .codec
+            .and_then(|name| std::ffi::CString::new(name).ok());
+        Ok::<_, UniencError>(UniencVideoCodecStats {
+            codec_name: codec_name
+                .as_ref()
+                .map_or(std::ptr::null(), |name| name.as_ptr()),
+            width: stats.metrics.width.unwrap_or(0),
+            height: stats.metrics.height.unwrap_or(0),
+            latency_avg_us: stats.metrics.latency_avg_us.unwrap_or(-1),
+            latency_max_us: stats.metrics.latency_max_us.unwrap_or(-1),
+            frames_queued: stats.frames_queued,
+            frames_output: stats.frames_output,
+            stalls: stats.stalls,
+        })
+        .apply_callback(callback, user_data);
+    }
+}
@@ -3,7 +3,12 @@ use std::ffi::{CString, c_char};
 use std::ops::Deref;
 use std::os::raw::c_void;
 use std::sync::Arc;
-use unienc::{CategorizedError, EncodedData, ErrorCategory, UniencSampleKind};
+use unienc::{
+    AudioSample, CategorizedError, DecodedVideoFrame, DiagnosticCheck, DriftStats, EncodedData,
+    ErrorCategory, FrameStats, HighlightHint, HighlightKind, InterruptedExport, Loudness,
+    ReconnectEvent, SpooledFrame, StillImage, UniencSampleKind, WaveformPoint,
+    waveform::WAVEFORM_INTERVAL,
+};
 
 // Callback types for async operations
 pub type UniencCallback = unsafe extern "C" fn(user_data: *mut c_void, error: UniencErrorNative);
@@ -56,6 +61,7 @@ pub enum UniencErrorKind {
     TimeoutError = 8,
     InvalidInput = 9,
     PlatformError = 10,
+    EmptyRecording = 11,
 }
 
 impl From<ErrorCategory> for UniencErrorKind {
@@ -71,6 +77,7 @@ impl From<ErrorCategory> for UniencErrorKind {
             ErrorCategory::Timeout => UniencErrorKind::TimeoutError,
             ErrorCategory::InvalidInput => UniencErrorKind::InvalidInput,
             ErrorCategory::Platform => UniencErrorKind::PlatformError,
+            ErrorCategory::EmptyRecording => UniencErrorKind::EmptyRecording,
         }
     }
 }
@@ -102,6 +109,10 @@ impl UniencError {
         message: None,
     };
     pub fn with_native(&self, f: impl FnOnce(&UniencErrorNative)) {
+        if self.kind != UniencErrorKind::Success {
+            let message = self.message.as_deref().unwrap_or("");
+            unienc::log_capture::event(format_args!("error {:?}: {message}", self.kind));
+        }
         let message = self
             .message
             .as_ref()
@@ -228,6 +239,31 @@ impl<Data: Default> ApplyCallback<UniencDataCallback<Data>> for UniencError {
 }
 impl<T: EncodedData> ApplyCallback<UniencDataCallback<UniencSampleData>>
     for Result<Option<T>, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencSampleData>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(Some(data)) => Ok::<_, UniencError>(data).apply_callback(callback, user_data),
+            // the end of the stream
+            Ok(None) => unsafe {
+                callback(
+                    UniencSampleData::default(),
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencSampleData::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl<T: EncodedData> ApplyCallback<UniencDataCallback<UniencSampleData>>
+    for Result<&T, UniencError>
 {
     fn apply_callback(
         &self,
@@ -235,17 +271,16 @@ impl<T: EncodedData> ApplyCallback<UniencDataCallback<UniencSampleData>>
         user_data: SendPtr<c_void>,
     ) {
         let result = match self {
-            Ok(Some(data)) => {
+            Ok(data) => {
                 let timestamp = data.timestamp();
                 let kind = data.kind();
-                match bincode::encode_to_vec(data, bincode::config::standard()) {
+                match bincode::encode_to_vec(*data, bincode::config::standard()) {
                     Ok(serialized) => Ok((serialized, timestamp, kind)),
                     Err(_) => Err(UniencError::encoding_error(
                         "Failed to serialize encoded data",
                     )),
                 }
             }
-            Ok(None) => Ok((vec![], 0.0, UniencSampleKind::Interpolated)),
             Err(e) => Err(e.clone()),
         };
 
@@ -271,11 +306,692 @@ impl<T: EncodedData> ApplyCallback<UniencDataCallback<UniencSampleData>>
     }
 }
 
+impl ApplyCallback<UniencDataCallback<UniencDecodedFrameData>>
+    for Result<Option<DecodedVideoFrame>, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencDecodedFrameData>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(Some(decoded)) => unsafe {
+                let data = decoded.frame.buffer.data();
+                callback(
+                    UniencDecodedFrameData {
+                        data: data.as_ptr(),
+                        size: data.len(),
+                        width: decoded.frame.width,
+                        height: decoded.frame.height,
+                        timestamp: decoded.timestamp,
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            // null data marks the end of the stream
+            Ok(None) => unsafe {
+                callback(
+                    UniencDecodedFrameData::default(),
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencDecodedFrameData::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencStillImageData>> for Result<StillImage, UniencError> {
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencStillImageData>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(image) => unsafe {
+                callback(
+                    UniencStillImageData {
+                        data: image.data.as_ptr(),
+                        size: image.data.len(),
+                        width: image.width,
+                        height: image.height,
+                        timestamp: image.timestamp,
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencStillImageData::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencWaveformData>>
+    for Result<Vec<WaveformPoint>, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencWaveformData>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(points) => unsafe {
+                let points: Vec<UniencWaveformPoint> = points
+                    .iter()
+                    .map(|p| UniencWaveformPoint {
+                        peak: p.peak,
+                        rms: p.rms,
+                    })
+                    .collect();
+                callback(
+                    UniencWaveformData {
+                        points: points.as_ptr(),
+                        count: points.len(),
+                        interval: WAVEFORM_INTERVAL,
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencWaveformData::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencHighlightHint>> for Result<HighlightHint, UniencError> {
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencHighlightHint>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(hint) => unsafe {
+                callback(
+                    UniencHighlightHint {
+                        kind: match hint.kind {
+                            HighlightKind::LoudnessSpike => UniencHighlightKind::LoudnessSpike,
+                            HighlightKind::VoiceActivity => UniencHighlightKind::VoiceActivity,
+                        },
+                        timestamp: hint.timestamp,
+                        score: hint.score,
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencHighlightHint::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencDriftStats>> for Result<DriftStats, UniencError> {
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencDriftStats>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(stats) => unsafe {
+                callback(
+                    UniencDriftStats {
+                        drift: stats.drift,
+                        correction: stats.correction,
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencDriftStats::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencLoudness>> for Result<(Loudness, f64), UniencError> {
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencLoudness>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok((loudness, gain)) => unsafe {
+                callback(
+                    UniencLoudness {
+                        integrated: loudness.integrated,
+                        peak: loudness.peak,
+                        gain: *gain,
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencLoudness::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencAudioSamples>> for Result<&AudioSample, UniencError> {
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencAudioSamples>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(sample) => unsafe {
+                callback(
+                    UniencAudioSamples {
+                        data: sample.data.as_ptr(),
+                        count: sample.data.len(),
+                        timestamp_in_samples: sample.timestamp_in_samples,
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencAudioSamples::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencFrameStatsList>>
+    for Result<(Vec<FrameStats>, u64), UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencFrameStatsList>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok((frames, dropped)) => unsafe {
+                let frames: Vec<UniencFrameStats> = frames
+                    .iter()
+                    .map(|frame| UniencFrameStats {
+                        timestamp: frame.timestamp,
+                        size: frame.size as u64,
+                        is_key: frame.is_key,
+                        qp: frame.qp.map_or(-1, |qp| qp as i32),
+                    })
+                    .collect();
+                callback(
+                    UniencFrameStatsList {
+                        frames: frames.as_ptr(),
+                        count: frames.len(),
+                        dropped: *dropped,
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencFrameStatsList::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencSpooledFrameList>>
+    for Result<Vec<SpooledFrame>, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencSpooledFrameList>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(frames) => unsafe {
+                let frames = native_spooled_frames(frames);
+                callback(
+                    UniencSpooledFrameList {
+                        frames: frames.as_ptr(),
+                        count: frames.len(),
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencSpooledFrameList::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencInterruptedExport>>
+    for Result<Option<InterruptedExport>, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencInterruptedExport>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(Some(export)) => unsafe {
+                let output_path =
+                    CString::new(export.output_path.to_string_lossy().as_ref()).unwrap_or_default();
+                let frames = native_spooled_frames(&export.frames);
+                callback(
+                    UniencInterruptedExport {
+                        found: true,
+                        output_path: output_path.as_ptr(),
+                        progress: export.progress,
+                        metadata: export.metadata.as_ptr(),
+                        metadata_size: export.metadata.len(),
+                        frames: UniencSpooledFrameList {
+                            frames: frames.as_ptr(),
+                            count: frames.len(),
+                        },
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Ok(None) => unsafe {
+                callback(
+                    UniencInterruptedExport::default(),
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(
+                    UniencInterruptedExport::default(),
+                    user_data.into(),
+                    *native,
+                )
+            }),
+        }
+    }
+}
+
+fn native_spooled_frames(frames: &[SpooledFrame]) -> Vec<UniencSpooledFrame> {
+    frames
+        .iter()
+        .map(|frame| UniencSpooledFrame {
+            slot: frame.slot,
+            timestamp: frame.timestamp,
+            data: frame.data.as_ptr(),
+            size: frame.data.len(),
+        })
+        .collect()
+}
+
+impl ApplyCallback<UniencDataCallback<UniencVulkanPoolStats>>
+    for Result<UniencVulkanPoolStats, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencVulkanPoolStats>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(stats) => unsafe { callback(*stats, user_data.into(), UniencErrorNative::SUCCESS) },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencVulkanPoolStats::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencVideoCodecStats>>
+    for Result<UniencVideoCodecStats, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencVideoCodecStats>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(stats) => unsafe { callback(*stats, user_data.into(), UniencErrorNative::SUCCESS) },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencVideoCodecStats::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencBenchmarkResult>>
+    for Result<UniencBenchmarkResult, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencBenchmarkResult>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(result) => unsafe {
+                callback(*result, user_data.into(), UniencErrorNative::SUCCESS)
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencBenchmarkResult::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencSessionReplay>>
+    for Result<UniencSessionReplay, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencSessionReplay>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(replay) => unsafe {
+                callback(*replay, user_data.into(), UniencErrorNative::SUCCESS)
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencSessionReplay::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencPurgeReport>>
+    for Result<UniencPurgeReport, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencPurgeReport>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(report) => unsafe {
+                callback(*report, user_data.into(), UniencErrorNative::SUCCESS)
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencPurgeReport::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencSessionDirPath>> for Result<&str, UniencError> {
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencSessionDirPath>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(path) => unsafe {
+                callback(
+                    UniencSessionDirPath {
+                        data: path.as_ptr(),
+                        len: path.len(),
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencSessionDirPath::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencFrameSnapshot>>
+    for Result<UniencFrameSnapshot, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencFrameSnapshot>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(snapshot) => unsafe {
+                callback(*snapshot, user_data.into(), UniencErrorNative::SUCCESS)
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencFrameSnapshot::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencRecommendedOptions>>
+    for Result<UniencRecommendedOptions, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencRecommendedOptions>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(options) => unsafe {
+                callback(*options, user_data.into(), UniencErrorNative::SUCCESS)
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(
+                    UniencRecommendedOptions::default(),
+                    user_data.into(),
+                    *native,
+                )
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencPipelineProgress>>
+    for Result<UniencPipelineProgress, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencPipelineProgress>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(progress) => unsafe {
+                callback(*progress, user_data.into(), UniencErrorNative::SUCCESS)
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencPipelineProgress::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+/// Encoder names and whether they are hardware accelerated.
+impl ApplyCallback<UniencDataCallback<UniencEncoderList>>
+    for Result<Vec<(String, bool)>, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencEncoderList>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(encoders) => unsafe {
+                // kept alive until the callback returns
+                let names: Vec<CString> = encoders
+                    .iter()
+                    .map(|(name, _)| CString::new(name.as_str()).unwrap_or_default())
+                    .collect();
+                let native_encoders: Vec<UniencEncoderInfo> = encoders
+                    .iter()
+                    .zip(&names)
+                    .map(|((_, hardware_accelerated), name)| UniencEncoderInfo {
+                        name: name.as_ptr(),
+                        hardware_accelerated: *hardware_accelerated,
+                    })
+                    .collect();
+                callback(
+                    UniencEncoderList {
+                        encoders: native_encoders.as_ptr(),
+                        count: native_encoders.len(),
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencEncoderList::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencSelfTestReport>>
+    for Result<Vec<DiagnosticCheck>, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencSelfTestReport>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(checks) => unsafe {
+                // kept alive until the callback returns
+                let strings: Vec<(CString, CString)> = checks
+                    .iter()
+                    .map(|check| {
+                        (
+                            CString::new(check.name).unwrap_or_default(),
+                            CString::new(check.message.as_str()).unwrap_or_default(),
+                        )
+                    })
+                    .collect();
+                let native_checks: Vec<UniencDiagnosticCheck> = checks
+                    .iter()
+                    .zip(&strings)
+                    .map(|(check, (name, message))| UniencDiagnosticCheck {
+                        name: name.as_ptr(),
+                        passed: check.passed,
+                        message: message.as_ptr(),
+                    })
+                    .collect();
+                callback(
+                    UniencSelfTestReport {
+                        checks: native_checks.as_ptr(),
+                        count: native_checks.len(),
+                        passed: checks.iter().all(|check| check.passed),
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencSelfTestReport::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencLogCapture>> for Result<String, UniencError> {
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencLogCapture>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(contents) => unsafe {
+                callback(
+                    UniencLogCapture {
+                        data: contents.as_ptr(),
+                        len: contents.len(),
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencLogCapture::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
+impl ApplyCallback<UniencDataCallback<UniencReconnectEvent>>
+    for Result<&ReconnectEvent, UniencError>
+{
+    fn apply_callback(
+        &self,
+        callback: UniencDataCallback<UniencReconnectEvent>,
+        user_data: SendPtr<c_void>,
+    ) {
+        match self {
+            Ok(event) => unsafe {
+                let (kind, attempt, dropped, error) = match event {
+                    ReconnectEvent::Disconnected { error } => {
+                        (UniencReconnectEventKind::Disconnected, 0, 0, Some(error))
+                    }
+                    ReconnectEvent::Reconnecting { attempt } => {
+                        (UniencReconnectEventKind::Reconnecting, *attempt, 0, None)
+                    }
+                    ReconnectEvent::Reconnected { attempt, dropped } => (
+                        UniencReconnectEventKind::Reconnected,
+                        *attempt,
+                        *dropped,
+                        None,
+                    ),
+                    ReconnectEvent::GaveUp { error } => {
+                        (UniencReconnectEventKind::GaveUp, 0, 0, Some(error))
+                    }
+                };
+                // kept alive until the callback returns
+                let error = error.map(|error| CString::new(error.as_str()).unwrap_or_default());
+                callback(
+                    UniencReconnectEvent {
+                        kind,
+                        attempt,
+                        dropped,
+                        error: error
+                            .as_ref()
+                            .map_or(std::ptr::null(), |error| error.as_ptr()),
+                    },
+                    user_data.into(),
+                    UniencErrorNative::SUCCESS,
+                )
+            },
+            Err(err) => err.with_native(|native| unsafe {
+                callback(UniencReconnectEvent::default(), user_data.into(), *native)
+            }),
+        }
+    }
+}
+
 // These are unused but required to let csbindgen generate the binding for specific types.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn unienc_dummy(
     _error_kind: UniencErrorKind,
     _error_native: UniencErrorNative,
     _sample: UniencSampleData,
+    _decoded_frame: UniencDecodedFrameData,
+    _still_image: UniencStillImageData,
+    _waveform: UniencWaveformData,
+    _highlight_hint: UniencHighlightHint,
+    _self_test_report: UniencSelfTestReport,
+    _drift_stats: UniencDriftStats,
+    _loudness: UniencLoudness,
+    _audio_samples: UniencAudioSamples,
+    _vulkan_pool_stats: UniencVulkanPoolStats,
+    _video_codec_stats: UniencVideoCodecStats,
+    _benchmark_result: UniencBenchmarkResult,
+    _frame_snapshot: UniencFrameSnapshot,
+    _encoder_list: UniencEncoderList,
+    _frame_stats: UniencFrameStatsList,
+    _spooled_frames: UniencSpooledFrameList,
+    _interrupted_export: UniencInterruptedExport,
+    _log_capture: UniencLogCapture,
+    _pipeline_progress: UniencPipelineProgress,
+    _reconnect_event: UniencReconnectEvent,
+    _recommended_options: UniencRecommendedOptions,
+    _session_replay: UniencSessionReplay,
+    _purge_report: UniencPurgeReport,
+    _session_dir_path: UniencSessionDirPath,
 ) {
 }
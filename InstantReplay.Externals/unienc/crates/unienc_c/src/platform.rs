@@ -9,16 +9,183 @@ pub type PlatformEncodingSystem = unienc::PlatformEncodingSystem<
 >;
 
 type VideoEncoder = <PlatformEncodingSystem as unienc::EncodingSystem>::VideoEncoderType;
-pub type VideoEncoderInput = <VideoEncoder as unienc::Encoder>::InputType;
-pub type VideoEncoderOutput = <VideoEncoder as unienc::Encoder>::OutputType;
+pub type VideoEncoderInput =
+    unienc::ClockedVideoInput<PacedVideoLayer<<VideoEncoder as unienc::Encoder>::InputType>>;
+// layers of VideoEncoderInput around the encoder input `I`, outermost first
+pub type PacedVideoLayer<I> = unienc::PacedVideoInput<DedupVideoLayer<I>>;
+pub type DedupVideoLayer<I> = unienc::DedupVideoInput<SceneCutVideoLayer<I>>;
+pub type SceneCutVideoLayer<I> = unienc::SceneCutVideoInput<FilteredVideoLayer<I>>;
+pub type FilteredVideoLayer<I> = unienc::FilteredVideoInput<StoryboardVideoLayer<I>>;
+pub type StoryboardVideoLayer<I> = unienc::StoryboardVideoInput<PngSequenceVideoLayer<I>>;
+pub type PngSequenceVideoLayer<I> = unienc::PngSequenceVideoInput<SnapshotVideoLayer<I>>;
+pub type SnapshotVideoLayer<I> = unienc::SnapshotVideoInput<TimelapseVideoLayer<I>>;
+pub type TimelapseVideoLayer<I> = unienc::TimelapseVideoInput<unienc::CapturedVideoInput<I>>;
+pub type LadderVideoEncoderInput = unienc::LadderVideoInput<VideoEncoderInput>;
+pub type DualSinkVideoEncoderInput =
+    unienc::SecondaryVideoInput<VideoEncoderInput, VideoEncoderInput>;
+pub type VideoEncoderOutput =
+    unienc::MeasuredVideoOutput<<VideoEncoder as unienc::Encoder>::OutputType>;
 type AudioEncoder = <PlatformEncodingSystem as unienc::EncodingSystem>::AudioEncoderType;
-pub type AudioEncoderInput = <AudioEncoder as unienc::Encoder>::InputType;
+pub type AudioEncoderInput =
+    unienc::ClockedAudioInput<AnalyzedAudioLayer<<AudioEncoder as unienc::Encoder>::InputType>>;
+pub type AnalyzedAudioLayer<I> = unienc::AnalyzedAudioInput<unienc::CapturedAudioInput<I>>;
 pub type AudioEncoderOutput = <AudioEncoder as unienc::Encoder>::OutputType;
 type Muxer = <PlatformEncodingSystem as unienc::EncodingSystem>::MuxerType;
-pub type VideoMuxerInput = <Muxer as unienc::Muxer>::VideoInputType;
-pub type AudioMuxerInput = <Muxer as unienc::Muxer>::AudioInputType;
-pub type MuxerCompletionHandle = <Muxer as unienc::Muxer>::CompletionHandleType;
+pub type VideoMuxerInput = unienc::TeeMuxerInput<
+    unienc::LimitedMuxerInput<
+        unienc::InterleavedMuxerInput<
+            unienc::SampledMuxerInput<<Muxer as unienc::Muxer>::VideoInputType>,
+        >,
+    >,
+>;
+pub type AudioMuxerInput = unienc::TeeMuxerInput<
+    unienc::LimitedMuxerInput<
+        unienc::InterleavedMuxerInput<<Muxer as unienc::Muxer>::AudioInputType>,
+    >,
+>;
+pub type MuxerCompletionHandle = unienc::DescriptorCompletionHandle<
+    unienc::TimecodeCompletionHandle<
+        unienc::SphericalCompletionHandle<
+            unienc::EmptyCheckCompletionHandle<<Muxer as unienc::Muxer>::CompletionHandleType>,
+        >,
+    >,
+>;
 
 pub type VideoEncodedData = <VideoEncoderOutput as EncoderOutput>::Data;
 pub type AudioEncodedData = <AudioEncoderOutput as EncoderOutput>::Data;
 pub type BlitSource = <PlatformEncodingSystem as unienc::EncodingSystem>::BlitSourceType;
+pub type H264PacketizerImpl =
+    <PlatformEncodingSystem as unienc::EncodingSystem>::H264PacketizerType;
+pub type AacPacketizerImpl = <PlatformEncodingSystem as unienc::EncodingSystem>::AacPacketizerType;
+pub type DecoderImpl = <PlatformEncodingSystem as unienc::EncodingSystem>::DecoderType;
+pub type StillImageCaptureImpl =
+    <PlatformEncodingSystem as unienc::EncodingSystem>::StillImageCaptureType;
+#[cfg(target_os = "ios")]
+pub type ReplayKitCaptureImpl = unienc::ios::ReplayKitCapture;
+// ReplayKit functions report a platform error elsewhere
+#[cfg(not(target_os = "ios"))]
+pub type ReplayKitCaptureImpl = ();
+#[cfg(target_os = "macos")]
+pub type ScreenCaptureImpl = unienc::macos::ScreenCapture;
+// ScreenCaptureKit functions report a platform error elsewhere
+#[cfg(not(target_os = "macos"))]
+pub type ScreenCaptureImpl = ();
+
+/// Typed access to the layers of [`VideoEncoderInput`], so that call sites name the layer they
+/// configure instead of counting the wrappers above it.
+pub trait VideoEncoderInputLayers {
+    /// Input of the backend's own encoder, under the padding every backend wraps it in.
+    type Backend;
+
+    fn paced_mut(&mut self) -> &mut PacedVideoLayer<unienc::PaddedVideoInput<Self::Backend>>;
+    fn dedup_mut(&mut self) -> &mut DedupVideoLayer<unienc::PaddedVideoInput<Self::Backend>>;
+    fn scene_cut_mut(&mut self)
+    -> &mut SceneCutVideoLayer<unienc::PaddedVideoInput<Self::Backend>>;
+    fn filtered_mut(&mut self) -> &mut FilteredVideoLayer<unienc::PaddedVideoInput<Self::Backend>>;
+    fn storyboard_mut(
+        &mut self,
+    ) -> &mut StoryboardVideoLayer<unienc::PaddedVideoInput<Self::Backend>>;
+    fn png_sequence_mut(
+        &mut self,
+    ) -> &mut PngSequenceVideoLayer<unienc::PaddedVideoInput<Self::Backend>>;
+    fn snapshot_mut(&mut self) -> &mut SnapshotVideoLayer<unienc::PaddedVideoInput<Self::Backend>>;
+    fn timelapse_mut(
+        &mut self,
+    ) -> &mut TimelapseVideoLayer<unienc::PaddedVideoInput<Self::Backend>>;
+    // the backend itself is only reached by the platform capture and tier functions
+    #[cfg(any(
+        target_vendor = "apple",
+        all(target_os = "android", not(feature = "external"))
+    ))]
+    fn backend_mut(&mut self) -> &mut Self::Backend;
+    #[cfg(all(target_vendor = "apple", not(feature = "external")))]
+    fn into_backend(self) -> Self::Backend;
+}
+
+impl<B> VideoEncoderInputLayers
+    for unienc::ClockedVideoInput<PacedVideoLayer<unienc::PaddedVideoInput<B>>>
+{
+    type Backend = B;
+
+    fn paced_mut(&mut self) -> &mut PacedVideoLayer<unienc::PaddedVideoInput<B>> {
+        self.inner_mut()
+    }
+
+    fn dedup_mut(&mut self) -> &mut DedupVideoLayer<unienc::PaddedVideoInput<B>> {
+        self.paced_mut().inner_mut()
+    }
+
+    fn scene_cut_mut(&mut self) -> &mut SceneCutVideoLayer<unienc::PaddedVideoInput<B>> {
+        self.dedup_mut().inner_mut()
+    }
+
+    fn filtered_mut(&mut self) -> &mut FilteredVideoLayer<unienc::PaddedVideoInput<B>> {
+        self.scene_cut_mut().inner_mut()
+    }
+
+    fn storyboard_mut(&mut self) -> &mut StoryboardVideoLayer<unienc::PaddedVideoInput<B>> {
+        self.filtered_mut().inner_mut()
+    }
+
+    fn png_sequence_mut(&mut self) -> &mut PngSequenceVideoLayer<unienc::PaddedVideoInput<B>> {
+        self.storyboard_mut().inner_mut()
+    }
+
+    fn snapshot_mut(&mut self) -> &mut SnapshotVideoLayer<unienc::PaddedVideoInput<B>> {
+        self.png_sequence_mut().inner_mut()
+    }
+
+    fn timelapse_mut(&mut self) -> &mut TimelapseVideoLayer<unienc::PaddedVideoInput<B>> {
+        self.snapshot_mut().inner_mut()
+    }
+
+    #[cfg(any(
+        target_vendor = "apple",
+        all(target_os = "android", not(feature = "external"))
+    ))]
+    fn backend_mut(&mut self) -> &mut B {
+        self.timelapse_mut().inner_mut().inner_mut().inner_mut()
+    }
+
+    #[cfg(all(target_vendor = "apple", not(feature = "external")))]
+    fn into_backend(self) -> B {
+        let timelapse = self
+            .into_inner()
+            .into_inner()
+            .into_inner()
+            .into_inner()
+            .into_inner()
+            .into_inner()
+            .into_inner()
+            .into_inner();
+        timelapse.into_inner().into_inner().into_inner()
+    }
+}
+
+/// Typed access to the layers of [`AudioEncoderInput`], as [`VideoEncoderInputLayers`].
+pub trait AudioEncoderInputLayers {
+    /// Input of the backend's own encoder, under the downmixing every backend wraps it in.
+    type Backend;
+
+    fn analyzed_mut(
+        &mut self,
+    ) -> &mut AnalyzedAudioLayer<unienc::DownmixedAudioInput<Self::Backend>>;
+    // only system audio capture, on Windows, reaches the backend itself
+    #[cfg(windows)]
+    fn backend_mut(&mut self) -> &mut Self::Backend;
+}
+
+impl<B: unienc::EncoderInput<Data = unienc::AudioSample>> AudioEncoderInputLayers
+    for unienc::ClockedAudioInput<AnalyzedAudioLayer<unienc::DownmixedAudioInput<B>>>
+{
+    type Backend = B;
+
+    fn analyzed_mut(&mut self) -> &mut AnalyzedAudioLayer<unienc::DownmixedAudioInput<B>> {
+        self.inner_mut()
+    }
+
+    #[cfg(windows)]
+    fn backend_mut(&mut self) -> &mut B {
+        self.analyzed_mut().inner_mut().inner_mut().inner_mut()
+    }
+}
@@ -1,4 +1,6 @@
-use unienc::{AudioEncoderOptions, UniencSampleKind, VideoEncoderOptions};
+use std::ffi::c_char;
+
+use unienc::{AudioEncoderOptions, LatencyMode, UniencSampleKind, VideoCodec, VideoEncoderOptions};
 
 #[repr(C)]
 pub struct UniencSampleData {
@@ -19,6 +21,622 @@ impl Default for UniencSampleData {
     }
 }
 
+#[repr(C)]
+pub struct UniencDecodedFrameData {
+    pub(crate) data: *const u8,
+    pub(crate) size: usize,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) timestamp: f64,
+}
+
+impl Default for UniencDecodedFrameData {
+    fn default() -> Self {
+        Self {
+            data: std::ptr::null(),
+            size: 0,
+            width: 0,
+            height: 0,
+            timestamp: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)] // constructed by the caller across FFI
+pub enum UniencStillImageFormat {
+    Png = 0,
+    Jpeg = 1,
+    Bgra32 = 2,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)] // constructed by the caller across FFI
+pub enum UniencPixelFormat {
+    Bgra32 = 0,
+    Rgba32 = 1,
+    Rgb565 = 2,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)] // constructed by the caller across FFI
+pub enum UniencDedupMode {
+    Off = 0,
+    Skip = 1,
+    Repeat = 2,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)] // constructed by the caller across FFI
+pub enum UniencExportFilter {
+    All = 0,
+    VideoOnly = 1,
+    AudioOnly = 2,
+    /// Video keyframes only, one after another at the frame rate of the video.
+    Keyframes = 3,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)] // constructed by the caller across FFI
+pub enum UniencJpegSubsampling {
+    Yuv444 = 0,
+    Yuv420 = 1,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)] // constructed by the caller across FFI
+pub enum UniencStereoMode {
+    Mono = 0,
+    LeftEye = 1,
+    RightEye = 2,
+    SideBySide = 3,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)] // constructed by the caller across FFI
+pub enum UniencProjection {
+    Flat = 0,
+    Equirectangular = 1,
+}
+
+/// Rectangle in pixels of a frame, counted from its first row.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UniencRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)] // constructed by the caller across FFI
+pub enum UniencVideoCodec {
+    H264 = 0,
+    ProRes = 1,
+    DnxHr = 2,
+}
+
+impl UniencVideoCodec {
+    pub(crate) fn to_codec(self) -> VideoCodec {
+        match self {
+            UniencVideoCodec::H264 => VideoCodec::H264,
+            UniencVideoCodec::ProRes => VideoCodec::ProRes,
+            UniencVideoCodec::DnxHr => VideoCodec::DnxHr,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)] // constructed by the caller across FFI
+pub enum UniencScreenCaptureTarget {
+    CurrentWindow = 0,
+    MainDisplay = 1,
+}
+
+#[repr(C)]
+pub struct UniencStillImageData {
+    pub(crate) data: *const u8,
+    pub(crate) size: usize,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) timestamp: f64,
+}
+
+impl Default for UniencStillImageData {
+    fn default() -> Self {
+        Self {
+            data: std::ptr::null(),
+            size: 0,
+            width: 0,
+            height: 0,
+            timestamp: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct UniencWaveformPoint {
+    pub(crate) peak: f32,
+    pub(crate) rms: f32,
+}
+
+#[repr(C)]
+pub struct UniencWaveformData {
+    pub(crate) points: *const UniencWaveformPoint,
+    pub(crate) count: usize,
+    pub(crate) interval: f64,
+}
+
+impl Default for UniencWaveformData {
+    fn default() -> Self {
+        Self {
+            points: std::ptr::null(),
+            count: 0,
+            interval: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UniencHighlightKind {
+    LoudnessSpike = 0,
+    VoiceActivity = 1,
+}
+
+#[repr(C)]
+pub struct UniencHighlightHint {
+    pub(crate) kind: UniencHighlightKind,
+    pub(crate) timestamp: f64,
+    pub(crate) score: f32,
+}
+
+impl Default for UniencHighlightHint {
+    fn default() -> Self {
+        Self {
+            kind: UniencHighlightKind::LoudnessSpike,
+            timestamp: 0.0,
+            score: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct UniencDriftStats {
+    /// Seconds the audio clock is ahead of the wall clock.
+    pub(crate) drift: f64,
+    /// Seconds of audio removed (positive) or inserted (negative) so far.
+    pub(crate) correction: f64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct UniencLoudness {
+    /// Integrated loudness, in LUFS.
+    pub(crate) integrated: f64,
+    /// Largest absolute sample value, normalized to 0.0..=1.0.
+    pub(crate) peak: f32,
+    /// Gain in dB that normalizes the audio to the requested target.
+    pub(crate) gain: f64,
+}
+
+#[repr(C)]
+pub struct UniencAudioSamples {
+    /// Interleaved samples, valid only during the callback.
+    pub(crate) data: *const i16,
+    pub(crate) count: usize,
+    pub(crate) timestamp_in_samples: u64,
+}
+
+impl Default for UniencAudioSamples {
+    fn default() -> Self {
+        Self {
+            data: std::ptr::null(),
+            count: 0,
+            timestamp_in_samples: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct UniencFrameStats {
+    pub(crate) timestamp: f64,
+    /// Encoded size in bytes.
+    pub(crate) size: u64,
+    pub(crate) is_key: bool,
+    /// Average quantization parameter, or -1 when the encoder does not report it.
+    pub(crate) qp: i32,
+}
+
+#[repr(C)]
+pub struct UniencFrameStatsList {
+    pub(crate) frames: *const UniencFrameStats,
+    pub(crate) count: usize,
+    /// Frames recorded but overwritten before this drain.
+    pub(crate) dropped: u64,
+}
+
+impl Default for UniencFrameStatsList {
+    fn default() -> Self {
+        Self {
+            frames: std::ptr::null(),
+            count: 0,
+            dropped: 0,
+        }
+    }
+}
+
+#[repr(C)]
+pub struct UniencSpooledFrame {
+    pub(crate) slot: u32,
+    pub(crate) timestamp: f64,
+    /// Encoded JPEG.
+    pub(crate) data: *const u8,
+    pub(crate) size: usize,
+}
+
+#[repr(C)]
+pub struct UniencSpooledFrameList {
+    pub(crate) frames: *const UniencSpooledFrame,
+    pub(crate) count: usize,
+}
+
+impl Default for UniencSpooledFrameList {
+    fn default() -> Self {
+        Self {
+            frames: std::ptr::null(),
+            count: 0,
+        }
+    }
+}
+
+/// Export interrupted in a previous session. `found` is false if there was none.
+#[repr(C)]
+pub struct UniencInterruptedExport {
+    pub(crate) found: bool,
+    /// UTF-8, null-terminated.
+    pub(crate) output_path: *const c_char,
+    pub(crate) progress: f64,
+    pub(crate) metadata: *const u8,
+    pub(crate) metadata_size: usize,
+    pub(crate) frames: UniencSpooledFrameList,
+}
+
+impl Default for UniencInterruptedExport {
+    fn default() -> Self {
+        Self {
+            found: false,
+            output_path: std::ptr::null(),
+            progress: 0.0,
+            metadata: std::ptr::null(),
+            metadata_size: 0,
+            frames: UniencSpooledFrameList::default(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct UniencVulkanPoolStats {
+    pub(crate) descriptor_sets: u32,
+    pub(crate) descriptor_set_limit: u32,
+    /// Blits dropped because no descriptor set was available.
+    pub(crate) descriptor_set_starvations: u64,
+    pub(crate) fences: u32,
+    pub(crate) fence_limit: u32,
+    pub(crate) fence_starvations: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UniencVideoCodecStats {
+    /// Null if the device does not report it.
+    pub(crate) codec_name: *const c_char,
+    /// Zero if the device does not report it.
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+    /// Microseconds from queueing a frame to its output, or -1 if the device does not report it.
+    pub(crate) latency_avg_us: i64,
+    pub(crate) latency_max_us: i64,
+    pub(crate) frames_queued: u64,
+    pub(crate) frames_output: u64,
+    /// Times the encoder stalled and was flushed.
+    pub(crate) stalls: u32,
+}
+
+impl Default for UniencVideoCodecStats {
+    fn default() -> Self {
+        Self {
+            codec_name: std::ptr::null(),
+            width: 0,
+            height: 0,
+            latency_avg_us: -1,
+            latency_max_us: -1,
+            frames_queued: 0,
+            frames_output: 0,
+            stalls: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)] // constructed by the caller across FFI
+pub enum UniencBenchmarkStage {
+    /// RGBA to BGRA and BGRA to I420 on the CPU.
+    Convert = 0,
+    Encode = 1,
+    Mux = 2,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct UniencBenchmarkResult {
+    pub(crate) frames: u32,
+    pub(crate) seconds: f64,
+    pub(crate) frames_per_second: f64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct UniencSessionReplay {
+    pub(crate) video_frames: u32,
+    pub(crate) audio_samples: u32,
+    /// Frames and audio samples pushed in place of content the capture did not keep.
+    pub(crate) synthesized: u32,
+    /// From the first push until the file was complete.
+    pub(crate) seconds: f64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct UniencPurgeReport {
+    pub(crate) removed: u32,
+    /// Directories within the retention period, of this process, or that could not be removed.
+    pub(crate) kept: u32,
+}
+
+/// Path of a session directory.
+#[repr(C)]
+pub struct UniencSessionDirPath {
+    /// UTF-8, not null-terminated.
+    pub(crate) data: *const u8,
+    pub(crate) len: usize,
+}
+
+impl Default for UniencSessionDirPath {
+    fn default() -> Self {
+        Self {
+            data: std::ptr::null(),
+            len: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct UniencFrameSnapshot {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) timestamp: f64,
+    /// False when the buffer was too small, and nothing was copied.
+    pub(crate) copied: bool,
+}
+
+#[repr(C)]
+pub struct UniencEncoderInfo {
+    pub(crate) name: *const c_char,
+    /// Always false before Android 10, which cannot tell.
+    pub(crate) hardware_accelerated: bool,
+}
+
+#[repr(C)]
+pub struct UniencEncoderList {
+    pub(crate) encoders: *const UniencEncoderInfo,
+    pub(crate) count: usize,
+}
+
+impl Default for UniencEncoderList {
+    fn default() -> Self {
+        Self {
+            encoders: std::ptr::null(),
+            count: 0,
+        }
+    }
+}
+
+#[repr(C)]
+pub struct UniencDiagnosticCheck {
+    pub(crate) name: *const c_char,
+    pub(crate) passed: bool,
+    pub(crate) message: *const c_char,
+}
+
+/// Native log lines and pipeline events kept by the log capture, oldest first.
+#[repr(C)]
+pub struct UniencLogCapture {
+    /// UTF-8, not null-terminated.
+    pub(crate) data: *const u8,
+    pub(crate) len: usize,
+}
+
+impl Default for UniencLogCapture {
+    fn default() -> Self {
+        Self {
+            data: std::ptr::null(),
+            len: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UniencPipelineStage {
+    #[default]
+    None = 0,
+    /// Waiting for the encoder to output a sample.
+    EncoderPull = 1,
+    /// Pushing an encoded sample to the muxer.
+    MuxerPush = 2,
+    /// Finishing the track or completing the file.
+    SinkWrite = 3,
+}
+
+/// Progress of one track, in seconds, where -1 means none.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UniencTrackProgress {
+    /// Timestamp of the last sample the muxer accepted, valid when `since_accepted` is not -1.
+    pub(crate) last_accepted: f64,
+    pub(crate) since_accepted: f64,
+    /// Time the oldest operation in flight at each stage has been running.
+    pub(crate) encoder_pull: f64,
+    pub(crate) muxer_push: f64,
+    pub(crate) sink_write: f64,
+    /// The stage in flight the longest, where the track is most likely blocked.
+    pub(crate) blocked: UniencPipelineStage,
+}
+
+impl Default for UniencTrackProgress {
+    fn default() -> Self {
+        Self {
+            last_accepted: 0.0,
+            since_accepted: -1.0,
+            encoder_pull: -1.0,
+            muxer_push: -1.0,
+            sink_write: -1.0,
+            blocked: UniencPipelineStage::None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct UniencPipelineProgress {
+    pub(crate) video: UniencTrackProgress,
+    pub(crate) audio: UniencTrackProgress,
+}
+
+/// When a network output reconnects after its connection fails. Durations are in seconds.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UniencRetryPolicy {
+    /// Attempts made before the output gives up; 0 gives up right away.
+    pub max_attempts: u32,
+    /// Wait before the first attempt, doubled after each failed attempt.
+    pub initial_backoff: f64,
+    pub max_backoff: f64,
+    /// Seconds of samples buffered per track while disconnected.
+    pub max_buffered: f64,
+}
+
+/// Layout of the files written by the ffmpeg backend.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct UniencFileLayout {
+    /// Whether to move the index of a file ahead of its media data once it is written.
+    pub faststart: bool,
+    /// Whether to write fragments each playable once written, so that a file cut short by a crash
+    /// plays up to its last one.
+    pub fragmented: bool,
+    /// Seconds a fragment may last at most, or 0 to start fragments at keyframes only.
+    pub fragment_duration: f64,
+}
+
+/// How the ffmpeg backend starts its processes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct UniencProcessOptions {
+    /// Whether to pass only the variables ffmpeg needs of the game's environment, such as `PATH`.
+    pub clear_environment: bool,
+    /// `environment_count` variables as `NAME=VALUE`, set on top of the environment.
+    pub environment: *const *const c_char,
+    pub environment_count: usize,
+    /// Directory the processes run in, or null for the working directory of the game.
+    pub working_directory: *const c_char,
+    /// Added to the niceness of the processes; positive values lower their priority.
+    pub niceness: i32,
+    /// Whether to start the processes without a console window, on Windows.
+    pub hide_window: bool,
+    /// Whether to kill a process dropped before it exits.
+    pub kill_on_drop: bool,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UniencReconnectEventKind {
+    Disconnected = 0,
+    Reconnecting = 1,
+    Reconnected = 2,
+    GaveUp = 3,
+}
+
+#[repr(C)]
+pub struct UniencReconnectEvent {
+    pub(crate) kind: UniencReconnectEventKind,
+    /// Attempt counted from 1, for `Reconnecting` and `Reconnected`.
+    pub(crate) attempt: u32,
+    /// Samples lost since the disconnection, for `Reconnected`.
+    pub(crate) dropped: u64,
+    /// Why the connection failed, for `Disconnected` and `GaveUp`, or null.
+    pub(crate) error: *const c_char,
+}
+
+impl Default for UniencReconnectEvent {
+    fn default() -> Self {
+        Self {
+            kind: UniencReconnectEventKind::Disconnected,
+            attempt: 0,
+            dropped: 0,
+            error: std::ptr::null(),
+        }
+    }
+}
+
+#[repr(C)]
+pub struct UniencSelfTestReport {
+    pub(crate) checks: *const UniencDiagnosticCheck,
+    pub(crate) count: usize,
+    /// Whether every check passed.
+    pub(crate) passed: bool,
+}
+
+impl Default for UniencSelfTestReport {
+    fn default() -> Self {
+        Self {
+            checks: std::ptr::null(),
+            count: 0,
+            passed: false,
+        }
+    }
+}
+
+/// Encoder settings recommended for a frame size on this device.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct UniencRecommendedOptions {
+    /// In bits per second.
+    pub(crate) bitrate: u32,
+    pub(crate) fps_hint: u32,
+    /// Frames between keyframes.
+    pub(crate) keyframe_interval: u32,
+    /// In bits per second.
+    pub(crate) audio_bitrate: u32,
+}
+
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct VideoEncoderOptionsNative {
@@ -26,6 +644,11 @@ pub struct VideoEncoderOptionsNative {
     pub height: u32,
     pub fps_hint: u32,
     pub bitrate: u32,
+    /// Prioritizes quality over latency, for sessions that transcode an export.
+    pub offline: bool,
+    /// Keeps the alpha channel, on encoding systems that support it.
+    pub preserve_alpha: bool,
+    pub codec: UniencVideoCodec,
 }
 
 #[repr(C)]
@@ -52,6 +675,21 @@ impl VideoEncoderOptions for VideoEncoderOptionsNative {
     fn bitrate(&self) -> u32 {
         self.bitrate
     }
+
+    fn latency_mode(&self) -> LatencyMode {
+        match self.offline {
+            true => LatencyMode::Offline,
+            false => LatencyMode::Realtime,
+        }
+    }
+
+    fn preserve_alpha(&self) -> bool {
+        self.preserve_alpha
+    }
+
+    fn codec(&self) -> VideoCodec {
+        self.codec.to_codec()
+    }
 }
 
 impl AudioEncoderOptions for AudioEncoderOptionsNative {
@@ -81,7 +81,7 @@ unsafe extern "system" fn graphics_event_callback_trampoline(
     let context = unsafe { Box::from_raw(user_data as *mut GraphicsEventContext) };
     let rust_data = unsafe { Box::from_raw(context.rust_context) };
     let Some(runtime) = rust_data.weak_runtime.upgrade() else {
-        println!("Failed to upgrade runtime in graphics event callback");
+        unienc::log!("Failed to upgrade runtime in graphics event callback");
         return;
     };
     let _guard = runtime.enter();
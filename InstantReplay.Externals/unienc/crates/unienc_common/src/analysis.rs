@@ -0,0 +1,94 @@
+//! Optional analysis of the audio pushed to an encoder.
+
+use std::sync::{Arc, Mutex};
+
+use crate::highlight::{HighlightDetector, HighlightHint};
+use crate::loudness::{LoudnessMeter, apply_gain};
+use crate::waveform::WaveformAnalyzer;
+use crate::{AudioSample, EncoderInput, Result};
+
+type HighlightSink = Box<dyn FnMut(HighlightHint) + Send>;
+type MonitorSink = Box<dyn FnMut(&AudioSample) + Send>;
+
+/// Audio encoder input that feeds every sample to the enabled analyzers before passing it on. A
+/// gain set on it is applied first, so the analyzers see the audio that is encoded.
+pub struct AnalyzedAudioInput<I> {
+    inner: I,
+    waveform: Option<Arc<Mutex<WaveformAnalyzer>>>,
+    highlight: Option<(HighlightDetector, HighlightSink)>,
+    loudness: Option<Arc<Mutex<LoudnessMeter>>>,
+    monitor: Option<MonitorSink>,
+    gain_db: f64,
+}
+
+impl<I: EncoderInput<Data = AudioSample>> AnalyzedAudioInput<I> {
+    pub fn new(inner: I, waveform: Option<Arc<Mutex<WaveformAnalyzer>>>) -> Self {
+        Self {
+            inner,
+            waveform,
+            highlight: None,
+            loudness: None,
+            monitor: None,
+            gain_db: 0.0,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    /// Runs `detector` on subsequent samples and passes each hint it reports to `on_hint`.
+    pub fn set_highlight_detector(
+        &mut self,
+        detector: HighlightDetector,
+        on_hint: impl FnMut(HighlightHint) + Send + 'static,
+    ) {
+        self.highlight = Some((detector, Box::new(on_hint)));
+    }
+
+    /// Measures the loudness of subsequent samples into `meter`, which stays readable after the
+    /// input is finished.
+    pub fn set_loudness_meter(&mut self, meter: Arc<Mutex<LoudnessMeter>>) {
+        self.loudness = Some(meter);
+    }
+
+    /// Passes each subsequent sample to `on_sample` once the gain is applied and before it is
+    /// encoded, such as for a level meter in the recording UI.
+    pub fn set_monitor(&mut self, on_sample: impl FnMut(&AudioSample) + Send + 'static) {
+        self.monitor = Some(Box::new(on_sample));
+    }
+
+    /// Scales subsequent samples by `gain_db`, such as a
+    /// [normalization gain](crate::loudness::Loudness::normalization_gain) measured while
+    /// recording, when the audio is encoded again for export.
+    pub fn set_gain(&mut self, gain_db: f64) {
+        self.gain_db = gain_db;
+    }
+}
+
+impl<I: EncoderInput<Data = AudioSample>> EncoderInput for AnalyzedAudioInput<I> {
+    type Data = AudioSample;
+
+    async fn push(&mut self, mut data: Self::Data) -> Result<()> {
+        if self.gain_db != 0.0 {
+            apply_gain(&mut data, self.gain_db);
+        }
+        if let Some(waveform) = &self.waveform
+            && let Ok(mut waveform) = waveform.lock()
+        {
+            waveform.push(&data);
+        }
+        if let Some((detector, on_hint)) = &mut self.highlight {
+            detector.push(&data).into_iter().for_each(on_hint);
+        }
+        if let Some(loudness) = &self.loudness
+            && let Ok(mut loudness) = loudness.lock()
+        {
+            loudness.push(&data);
+        }
+        if let Some(on_sample) = &mut self.monitor {
+            on_sample(&data);
+        }
+        self.inner.push(data).await
+    }
+}
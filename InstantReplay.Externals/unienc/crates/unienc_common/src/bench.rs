@@ -0,0 +1,216 @@
+//! Throughput of the recording stages in frames per second, so performance regressions across the
+//! backends are caught before release. The same measurements run under criterion on desktop
+//! (`cargo bench -p unienc`) and on devices through `unienc_run_benchmark`, where blitting needs a
+//! texture from the host. Frames are synthetic, and creating the encoder or muxer is not counted.
+
+use std::path::Path;
+use std::pin::pin;
+use std::time::{Duration, Instant};
+
+use crate::buffer::SharedBuffer;
+use crate::pipeline::try_join;
+use crate::{
+    AudioEncoderOptions, AudioSample, CompletionHandle, Encoder, EncoderInput, EncoderOutput,
+    EncodingSystem, Muxer, MuxerInput, PixelFormat, Result, VideoFrame, VideoFrameBgra32,
+    VideoSample, frame_from_pixels,
+};
+
+/// Sizes every stage is measured at.
+pub const BENCH_SIZES: [(&str, u32, u32); 3] = [
+    ("720p", 1280, 720),
+    ("1080p", 1920, 1080),
+    ("4K", 3840, 2160),
+];
+
+/// Frames pushed per second of the synthetic timeline.
+const FPS: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    pub frames: u32,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn frames_per_second(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            0.0 => 0.0,
+            seconds => self.frames as f64 / seconds,
+        }
+    }
+}
+
+/// Tightly packed RGBA pixels that change with `index`, so encoders cannot skip repeated frames.
+pub fn synthetic_pixels(width: u32, height: u32, index: u32) -> Vec<u8> {
+    let mut data = vec![0; width as usize * height as usize * 4];
+    for (y, row) in data.chunks_exact_mut(width as usize * 4).enumerate() {
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            pixel.copy_from_slice(&[
+                (x as u32 + index) as u8,
+                (y as u32 + index) as u8,
+                ((x + y) as u32 / 2) as u8,
+                255,
+            ]);
+        }
+    }
+    data
+}
+
+/// Converts `frames` RGBA frames to BGRA, as pushed readbacks are, and then to the I420 planes
+/// the CPU paths of MediaCodec and Media Foundation encode. Copying the source pixels is not
+/// counted.
+pub fn bench_convert(width: u32, height: u32, frames: u32) -> Result<BenchResult> {
+    let source = synthetic_pixels(width, height, 0);
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..frames {
+        let buffer = SharedBuffer::new_unmanaged(source.clone());
+        let start = Instant::now();
+        let frame = frame_from_pixels(buffer, width, height, 0, PixelFormat::Rgba32)?;
+        std::hint::black_box(frame.to_yuv420_planes(None));
+        elapsed += start.elapsed();
+    }
+    Ok(BenchResult { frames, elapsed })
+}
+
+/// Encodes `frames` frames made by `frame` with a new video encoder of `system`, from the first
+/// push until the encoder has output everything. Making a frame is counted, as copying pixels
+/// into a shared buffer is part of pushing them from the host.
+pub async fn bench_encode<S: EncodingSystem>(
+    system: &S,
+    frames: u32,
+    frame: impl FnMut(u32) -> VideoFrame<S::BlitSourceType>,
+) -> Result<BenchResult> {
+    let start = Instant::now();
+    encode(system, frames, frame).await?;
+    Ok(BenchResult {
+        frames,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Muxes `frames` video frames, encoded beforehand along with silence of the same duration, into
+/// a new file at `path`, from the first push until the file is complete.
+pub async fn bench_mux<S: EncodingSystem>(
+    system: &S,
+    audio_options: &impl AudioEncoderOptions,
+    path: &Path,
+    (width, height): (u32, u32),
+    frames: u32,
+) -> Result<BenchResult> {
+    let video = encode(system, frames, |index| bgra_frame(width, height, index)).await?;
+    let audio = encode_silence(system, audio_options, frames).await?;
+
+    let (mut video_input, mut audio_input, completion_handle) =
+        system.new_muxer(path)?.get_inputs()?;
+    let start = Instant::now();
+    // interleaving muxers wait for both tracks, so they are pushed concurrently
+    let push_video = pin!(async {
+        for sample in video {
+            video_input.push(sample).await?;
+        }
+        video_input.finish().await
+    });
+    let push_audio = pin!(async {
+        for sample in audio {
+            audio_input.push(sample).await?;
+        }
+        audio_input.finish().await
+    });
+    try_join(push_video, push_audio).await?;
+    completion_handle.finish().await?;
+    Ok(BenchResult {
+        frames,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// A frame of [`synthetic_pixels`] converted to BGRA.
+pub fn bgra_frame<B>(width: u32, height: u32, index: u32) -> VideoFrame<B> {
+    let mut data = synthetic_pixels(width, height, index);
+    for pixel in data.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+    VideoFrame::Bgra32(VideoFrameBgra32::packed(
+        SharedBuffer::new_unmanaged(data),
+        width,
+        height,
+    ))
+}
+
+type EncodedVideo<S> =
+    <<<S as EncodingSystem>::VideoEncoderType as Encoder>::OutputType as EncoderOutput>::Data;
+type EncodedAudio<S> =
+    <<<S as EncodingSystem>::AudioEncoderType as Encoder>::OutputType as EncoderOutput>::Data;
+
+async fn encode<S: EncodingSystem>(
+    system: &S,
+    frames: u32,
+    mut frame: impl FnMut(u32) -> VideoFrame<S::BlitSourceType>,
+) -> Result<Vec<EncodedVideo<S>>> {
+    let (mut input, mut output) = system.new_video_encoder()?.get()?;
+    let push = pin!(async move {
+        for index in 0..frames {
+            input
+                .push(VideoSample {
+                    frame: frame(index),
+                    timestamp: index as f64 / FPS as f64,
+                })
+                .await?;
+        }
+        // dropping the input ends the encoder
+        Ok(())
+    });
+    let pull = pin!(pull_all(&mut output));
+    Ok(try_join(push, pull).await?.1)
+}
+
+async fn encode_silence<S: EncodingSystem>(
+    system: &S,
+    options: &impl AudioEncoderOptions,
+    frames: u32,
+) -> Result<Vec<EncodedAudio<S>>> {
+    let (mut input, mut output) = system.new_audio_encoder()?.get()?;
+    let (rate, channels) = (options.sample_rate() as u64, options.channels() as u64);
+    let total = frames as u64 * rate / FPS as u64;
+    let push = pin!(async move {
+        let mut position = 0;
+        while position < total {
+            let len = (total - position).min(rate);
+            input
+                .push(AudioSample {
+                    data: vec![0; (len * channels) as usize],
+                    timestamp_in_samples: position,
+                })
+                .await?;
+            position += len;
+        }
+        Ok(())
+    });
+    let pull = pin!(pull_all(&mut output));
+    Ok(try_join(push, pull).await?.1)
+}
+
+async fn pull_all<O: EncoderOutput>(output: &mut O) -> Result<Vec<O::Data>> {
+    let mut samples = Vec::new();
+    while let Some(sample) = output.pull().await? {
+        samples.push(sample);
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_counts_every_frame() {
+        let result = bench_convert(64, 36, 3).unwrap();
+        assert_eq!(result.frames, 3);
+        assert!(result.frames_per_second() > 0.0);
+    }
+
+    #[test]
+    fn synthetic_frames_differ() {
+        assert_ne!(synthetic_pixels(16, 16, 0), synthetic_pixels(16, 16, 1));
+    }
+}
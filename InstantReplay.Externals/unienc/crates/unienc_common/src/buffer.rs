@@ -1,16 +1,13 @@
 use crate::error::{CommonError, Result};
-use std::{sync::Arc, sync::Weak};
+use std::sync::Weak;
+
+pub use unienc_core::buffer::SharedBuffer;
 
 pub struct SharedBufferPool {
     buffers: Vec<Weak<usize>>,
     limit: usize,
 }
 
-pub struct SharedBuffer {
-    len: Arc<usize>,
-    data: Vec<u8>,
-}
-
 impl SharedBufferPool {
     pub fn new(limit: usize) -> Self {
         Self {
@@ -35,36 +32,8 @@ impl SharedBufferPool {
             return Err(CommonError::BufferPoolExceeded);
         }
 
-        let len = Arc::new(size);
-        self.buffers.push(Arc::downgrade(&len));
-
-        let buffer = SharedBuffer {
-            data: vec![0u8; size],
-            len,
-        };
+        let buffer = SharedBuffer::new_unmanaged(vec![0u8; size]);
+        self.buffers.push(buffer.downgrade());
         Ok(buffer)
     }
 }
-
-impl SharedBuffer {
-    pub fn new_unmanaged(vec: Vec<u8>) -> Self {
-        let len = Arc::new(vec.len());
-        SharedBuffer { data: vec, len }
-    }
-
-    pub fn len(&self) -> usize {
-        *self.len
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
-    }
-
-    pub fn data(&self) -> &[u8] {
-        &self.data
-    }
-
-    pub fn data_mut(&mut self) -> &mut [u8] {
-        &mut self.data
-    }
-}
@@ -0,0 +1,432 @@
+//! Caption cues pushed by the host during recording, written as a sidecar WebVTT file or as a 3GPP
+//! timed text (`tx3g`) track added to the finished MP4 file, so captions survive into exported
+//! clips.
+//!
+//! Like replay data, cues are stamped with the same timestamps as the video frames pushed to the
+//! encoder and are rebased onto the first frame written to the output file.
+
+use std::collections::VecDeque;
+
+use crate::mp4::{
+    BoxResult, IDENTITY_MATRIX, MovieHeader, be_bytes, children, container, find, full_box, grow,
+    read_u32, shift_chunk_offsets, video_track,
+};
+use crate::{CommonError, Result};
+
+/// Timescale of the timed text track, in milliseconds.
+const TIMESCALE: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    /// Seconds on the video pipeline's clock; relative to the start of the video once written.
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Cues pushed during recording, kept in order of their start.
+pub struct CaptionTrack {
+    cues: VecDeque<Cue>,
+    retention: Option<f64>,
+}
+
+impl CaptionTrack {
+    /// `retention` drops cues ending more than this many seconds before the newest start, matching
+    /// the length of a bounded recording. `None` keeps every cue.
+    pub fn new(retention: Option<f64>) -> Self {
+        Self {
+            cues: VecDeque::new(),
+            retention,
+        }
+    }
+
+    pub fn push(&mut self, cue: Cue) {
+        let index = self.cues.partition_point(|c| c.start <= cue.start);
+        self.cues.insert(index, cue);
+        if let (Some(retention), Some(newest)) = (self.retention, self.cues.back()) {
+            let oldest = newest.start - retention;
+            self.cues.retain(|c| c.end >= oldest);
+        }
+    }
+
+    /// Cues shown within `start..=end`, cut to that range and with times made relative to
+    /// `start`.
+    pub fn cues_between(&self, start: f64, end: f64) -> Vec<Cue> {
+        self.cues
+            .iter()
+            .filter(|c| c.end > start && c.start < end)
+            .map(|c| Cue {
+                start: c.start.max(start) - start,
+                end: c.end.min(end) - start,
+                text: c.text.clone(),
+            })
+            .collect()
+    }
+}
+
+/// `00:01:02.345`
+fn vtt_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// WebVTT file of `cues`, whose times are relative to the start of the video.
+pub fn webvtt(cues: &[Cue]) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+    for cue in cues {
+        let text = cue
+            .text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        // a blank line would end the cue
+        let lines = text.lines().filter(|line| !line.trim().is_empty());
+        vtt.push_str(&format!(
+            "\n{} --> {}\n",
+            vtt_time(cue.start),
+            vtt_time(cue.end)
+        ));
+        for line in lines {
+            vtt.push_str(line);
+            vtt.push('\n');
+        }
+    }
+    vtt
+}
+
+/// Samples of a timed text track: each cue's text, with empty samples for the gaps between cues.
+/// Overlapping cues are cut at the start of the next one, as only one sample shows at a time.
+fn samples(cues: &[Cue]) -> Vec<(Vec<u8>, u32)> {
+    let millis = |seconds: f64| (seconds.max(0.0) * TIMESCALE as f64).round() as u32;
+    let text_sample = |text: &str| {
+        let text = &text.as_bytes()[..text.len().min(u16::MAX as usize)];
+        let mut sample = (text.len() as u16).to_be_bytes().to_vec();
+        sample.extend_from_slice(text);
+        sample
+    };
+
+    let mut sorted = cues.to_vec();
+    sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+    let mut samples = Vec::new();
+    let mut time = 0;
+    for (i, cue) in sorted.iter().enumerate() {
+        let start = millis(cue.start).max(time);
+        let next = sorted
+            .get(i + 1)
+            .map_or(u32::MAX, |next| millis(next.start));
+        let end = millis(cue.end).min(next.max(start));
+        if end <= start {
+            continue;
+        }
+        if start > time {
+            samples.push((text_sample(""), start - time));
+        }
+        samples.push((text_sample(&cue.text), end - start));
+        time = end;
+    }
+    samples
+}
+
+/// Returns a copy of an MP4 file with a timed text track of `cues`, whose times are relative to
+/// the start of the video. `language` is an ISO 639-2 code such as `eng`. The samples are appended
+/// in their own `mdat`, and chunk offsets are moved along when the track is inserted before the
+/// media data. The file is returned unchanged if no cue is shown.
+pub fn add_caption_track(file: &[u8], cues: &[Cue], language: &str) -> Result<Vec<u8>> {
+    let language = pack_language(language)
+        .ok_or_else(|| CommonError::Captions(format!("invalid language code {language:?}")))?;
+    let samples = samples(cues);
+    if samples.is_empty() {
+        return Ok(file.to_vec());
+    }
+    add(file, &samples, language)
+        .map_err(|reason| CommonError::Captions(format!("unsupported MP4 file: {reason}")))
+}
+
+/// Three lowercase letters packed into 15 bits, as `mdhd` stores them.
+fn pack_language(code: &str) -> Option<u16> {
+    let bytes: [u8; 3] = code.as_bytes().try_into().ok()?;
+    bytes.iter().try_fold(0u16, |packed, &c| {
+        c.is_ascii_lowercase()
+            .then(|| (packed << 5) | (c - 0x60) as u16)
+    })
+}
+
+fn add(file: &[u8], samples: &[(Vec<u8>, u32)], language: u16) -> BoxResult<Vec<u8>> {
+    let moov = find(file, 0..file.len(), b"moov")?;
+    let video = video_track(file, &moov)?;
+    let mvhd = find(file, moov.content(), b"mvhd")?;
+    let movie = MovieHeader::read(file, &mvhd)?;
+    // the text box covers the video, whose size ends its track header in 16.16 fixed point
+    let tkhd = find(file, video.content(), b"tkhd")?;
+    let size = (read_u32(file, tkhd.end - 8)?, read_u32(file, tkhd.end - 4)?);
+
+    let track_id = movie.next_track_id;
+    let data_len = samples.iter().map(|(data, _)| data.len()).sum::<usize>();
+    let wide_offset = (file.len() + data_len) as u64 + 4096 > u32::MAX as u64;
+    let header = TrackHeader {
+        movie: &movie,
+        track_id,
+        size,
+        language,
+    };
+    let delta = caption_trak(&header, samples, 0, wide_offset).len();
+    let sample_offset = (file.len() + delta + 8) as u64;
+    let trak = caption_trak(&header, samples, sample_offset, wide_offset);
+
+    let mut out = file.to_vec();
+    // the samples go behind everything, so a last box sized to the end of the file has to get
+    // its real size first
+    if let Some(last) = children(file, 0..file.len())?.last()
+        && read_u32(file, last.start)? == 0
+    {
+        grow(&mut out, last, 0)?;
+    }
+    shift_chunk_offsets(&mut out, &moov, moov.end, delta)?;
+    let next_track_id = movie.next_track_id_at;
+    out[next_track_id..next_track_id + 4].copy_from_slice(&(track_id + 1).to_be_bytes());
+    grow(&mut out, &moov, delta)?;
+    out.splice(moov.end..moov.end, trak);
+    out.extend(container(
+        b"mdat",
+        &samples
+            .iter()
+            .map(|(data, _)| data.clone())
+            .collect::<Vec<_>>(),
+    ));
+    Ok(out)
+}
+
+struct TrackHeader<'a> {
+    movie: &'a MovieHeader,
+    track_id: u32,
+    /// Width and height in 16.16 fixed point.
+    size: (u32, u32),
+    language: u16,
+}
+
+/// Subtitle track of `samples`, stored in a single chunk at `sample_offset`.
+fn caption_trak(
+    header: &TrackHeader,
+    samples: &[(Vec<u8>, u32)],
+    sample_offset: u64,
+    wide_offset: bool,
+) -> Vec<u8> {
+    let movie = header.movie;
+    let media_duration = samples.iter().map(|(_, duration)| duration).sum::<u32>();
+    let movie_duration = (media_duration as f64 / TIMESCALE as f64 * movie.timescale as f64)
+        .round()
+        .min(u32::MAX as f64) as u32;
+
+    // enabled and in movie
+    let mut tkhd = vec![0, 0, 0, 3];
+    // creation and modification time, track ID, reserved, duration
+    tkhd.extend(be_bytes(&[0, 0, header.track_id, 0, movie_duration]));
+    // reserved, layer, alternate group, volume and reserved
+    tkhd.extend_from_slice(&[0; 16]);
+    tkhd.extend(be_bytes(&IDENTITY_MATRIX));
+    tkhd.extend(be_bytes(&[header.size.0, header.size.1]));
+    let tkhd = container(b"tkhd", &[tkhd]);
+
+    // creation and modification time, timescale and duration
+    let mut mdhd = be_bytes(&[0, 0, TIMESCALE, media_duration]);
+    // language and quality
+    mdhd.extend_from_slice(&header.language.to_be_bytes());
+    mdhd.extend_from_slice(&[0, 0]);
+
+    // pre_defined, then the handler type, reserved and name
+    let mut hdlr = vec![0; 4];
+    hdlr.extend_from_slice(b"sbtl");
+    hdlr.extend_from_slice(&[0; 12]);
+    hdlr.extend_from_slice(b"SubtitleHandler\0");
+
+    // one self-contained data reference
+    let mut dref = 1u32.to_be_bytes().to_vec();
+    dref.extend(container(b"url ", &[vec![0, 0, 0, 1]]));
+    let dinf = container(b"dinf", &[full_box(b"dref", &dref)]);
+
+    // reserved and data reference index, display flags, centered at the bottom, transparent
+    // background
+    let mut entry = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0xff, 0, 0, 0, 0];
+    // text box of top, left, bottom and right
+    let (width, height) = ((header.size.0 >> 16) as u16, (header.size.1 >> 16) as u16);
+    entry.extend_from_slice(&[0, 0, 0, 0]);
+    entry.extend_from_slice(&height.to_be_bytes());
+    entry.extend_from_slice(&width.to_be_bytes());
+    // default style: first and last character, font ID, face, size and opaque white
+    entry.extend_from_slice(&[0, 0, 0, 0, 0, 1, 0, 18, 0xff, 0xff, 0xff, 0xff]);
+    // font table of one font
+    let mut ftab = vec![0, 1, 0, 1, 10];
+    ftab.extend_from_slice(b"Sans-Serif");
+    entry.extend(container(b"ftab", &[ftab]));
+    let mut stsd = 1u32.to_be_bytes().to_vec();
+    stsd.extend(container(b"tx3g", &[entry]));
+
+    // run-length encoded sample durations
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &(_, duration) in samples {
+        match runs.last_mut() {
+            Some((count, last)) if *last == duration => *count += 1,
+            _ => runs.push((1, duration)),
+        }
+    }
+    let mut stts = be_bytes(&[runs.len() as u32]);
+    for (count, duration) in runs {
+        stts.extend(be_bytes(&[count, duration]));
+    }
+    // sample size 0 followed by each size
+    let mut stsz = be_bytes(&[0, samples.len() as u32]);
+    for (data, _) in samples {
+        stsz.extend(be_bytes(&[data.len() as u32]));
+    }
+    let chunk_offset = match wide_offset {
+        false => full_box(b"stco", &be_bytes(&[1, sample_offset as u32])),
+        true => {
+            let mut co64 = 1u32.to_be_bytes().to_vec();
+            co64.extend_from_slice(&sample_offset.to_be_bytes());
+            full_box(b"co64", &co64)
+        }
+    };
+    let stbl = container(
+        b"stbl",
+        &[
+            full_box(b"stsd", &stsd),
+            full_box(b"stts", &stts),
+            // first chunk, samples per chunk and sample description index
+            full_box(b"stsc", &be_bytes(&[1, 1, samples.len() as u32, 1])),
+            full_box(b"stsz", &stsz),
+            chunk_offset,
+        ],
+    );
+
+    container(
+        b"trak",
+        &[
+            tkhd,
+            container(
+                b"mdia",
+                &[
+                    full_box(b"mdhd", &mdhd),
+                    full_box(b"hdlr", &hdlr),
+                    container(b"minf", &[full_box(b"nmhd", &[]), dinf, stbl]),
+                ],
+            ),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: f64, end: f64, text: &str) -> Cue {
+        Cue {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn cues_are_cut_to_the_clip_and_rebased() {
+        let mut track = CaptionTrack::new(Some(10.0));
+        track.push(cue(1.0, 2.0, "dropped"));
+        track.push(cue(14.0, 16.0, "b"));
+        track.push(cue(11.0, 13.0, "a"));
+        assert_eq!(
+            track.cues_between(12.0, 15.0),
+            [cue(0.0, 1.0, "a"), cue(2.0, 3.0, "b")]
+        );
+        assert!(track.cues_between(0.0, 5.0).is_empty());
+    }
+
+    #[test]
+    fn webvtt_escapes_text() {
+        let vtt = webvtt(&[cue(61.5, 3723.25, "<b>A & B</b>\n\nnext")]);
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n00:01:01.500 --> 01:02:03.250\n&lt;b&gt;A &amp; B&lt;/b&gt;\nnext\n"
+        );
+    }
+
+    #[test]
+    fn samples_fill_gaps_and_cut_overlaps() {
+        let samples = samples(&[cue(1.0, 3.0, "a"), cue(0.5, 1.5, "b"), cue(4.0, 5.0, "")]);
+        let expected = [
+            (vec![0, 0], 500),
+            (vec![0, 1, b'b'], 500),
+            (vec![0, 1, b'a'], 2000),
+            (vec![0, 0], 1000),
+            (vec![0, 0], 1000),
+        ];
+        assert_eq!(samples, expected);
+    }
+
+    #[test]
+    fn adds_a_track_pointing_at_its_samples() {
+        let mut tkhd = vec![0; 76];
+        tkhd.extend(be_bytes(&[1280 << 16, 720 << 16]));
+        let mut hdlr = vec![0; 4];
+        hdlr.extend_from_slice(b"vide");
+        hdlr.extend_from_slice(&[0; 13]);
+        let mut mvhd = be_bytes(&[0, 0, 600, 1200]);
+        mvhd.extend_from_slice(&[0; 76]);
+        mvhd.extend(be_bytes(&[2]));
+        let moov = container(
+            b"moov",
+            &[
+                full_box(b"mvhd", &mvhd),
+                container(
+                    b"trak",
+                    &[
+                        full_box(b"tkhd", &tkhd),
+                        container(
+                            b"mdia",
+                            &[
+                                full_box(b"hdlr", &hdlr),
+                                container(
+                                    b"minf",
+                                    &[container(b"stbl", &[full_box(b"stco", &be_bytes(&[1, 8]))])],
+                                ),
+                            ],
+                        ),
+                    ],
+                ),
+            ],
+        );
+        let file = [container(b"mdat", &[vec![1, 2, 3]]), moov].concat();
+
+        let out = add_caption_track(&file, &[cue(0.0, 1.0, "hi")], "eng").unwrap();
+        let moov = find(&out, 0..out.len(), b"moov").unwrap();
+        let traks = children(&out, moov.content())
+            .unwrap()
+            .into_iter()
+            .filter(|b| &b.kind == b"trak")
+            .collect::<Vec<_>>();
+        assert_eq!(traks.len(), 2);
+        let mut at = traks[1];
+        for kind in [b"mdia", b"minf", b"stbl", b"stco"] {
+            at = find(&out, at.content(), kind).unwrap();
+        }
+        let offset = read_u32(&out, at.content().start + 8).unwrap() as usize;
+        assert_eq!(&out[offset..offset + 4], &[0, 2, b'h', b'i']);
+        let mdhd = find(
+            &out,
+            find(&out, traks[1].content(), b"mdia").unwrap().content(),
+            b"mdhd",
+        )
+        .unwrap();
+        assert_eq!(read_u32(&out, mdhd.content().start + 16).unwrap(), 1000);
+        assert_eq!(
+            &out[mdhd.content().start + 20..mdhd.content().start + 22],
+            &pack_language("eng").unwrap().to_be_bytes()
+        );
+        assert_eq!(add_caption_track(&file, &[], "eng").unwrap(), file);
+        assert!(add_caption_track(&file, &[], "English").is_err());
+    }
+}
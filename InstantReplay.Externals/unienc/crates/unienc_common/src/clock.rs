@@ -0,0 +1,262 @@
+//! Timestamps of a recording session: a clock shared by the video and audio inputs that keeps the
+//! output timeline monotonic when the host clock resets, and conversions to the backends' native
+//! timebases.
+
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::drift::{DriftCompensator, DriftStats};
+use crate::{AudioSample, CommonError, EncoderInput, Result, Timebase, VideoSample};
+
+/// Maps host timestamps of video frames and audio samples onto the output timeline.
+///
+/// Timestamps pass through unchanged until one goes backwards, which happens when the host clock
+/// is reset (e.g. Unity's time after a reload). The clock then shifts every later timestamp of
+/// both streams so the stream continues right after its latest output, keeping video and audio in
+/// sync with each other.
+#[derive(Debug)]
+pub struct MediaClock {
+    frame_interval: f64,
+    offset: f64,
+    last_video: Option<f64>,
+    last_audio_start: Option<f64>,
+    audio_end: Option<f64>,
+    rebase_pending: bool,
+}
+
+impl MediaClock {
+    /// `fps_hint` sets the gap left after the previous video frame when rebasing.
+    pub fn new(fps_hint: u32) -> Self {
+        Self {
+            frame_interval: 1.0 / fps_hint.max(1) as f64,
+            offset: 0.0,
+            last_video: None,
+            last_audio_start: None,
+            audio_end: None,
+            rebase_pending: false,
+        }
+    }
+
+    /// Continues the timeline from the latest output on the next timestamp of either stream. Call
+    /// this when the host clock is known to have been reset, including resets that move it
+    /// forward.
+    pub fn rebase(&mut self) {
+        self.rebase_pending = true;
+    }
+
+    pub fn video_timestamp(&mut self, host: f64) -> Result<f64> {
+        if !host.is_finite() {
+            return Err(CommonError::InvalidTimestamp(host));
+        }
+        if self.rebase_pending {
+            self.continue_from_latest(host, self.frame_interval);
+        }
+
+        let mut timestamp = host + self.offset;
+        // muxers require strictly increasing video timestamps
+        if let Some(last) = self.last_video
+            && timestamp <= last
+        {
+            self.offset += last + self.frame_interval - timestamp;
+            timestamp = last + self.frame_interval;
+        }
+        self.last_video = Some(timestamp);
+        Ok(timestamp)
+    }
+
+    /// Returns the output position in samples of a push of `frames` samples per channel.
+    pub fn audio_timestamp(
+        &mut self,
+        timestamp_in_samples: u64,
+        frames: usize,
+        sample_rate: u32,
+    ) -> Result<u64> {
+        let timebase = Timebase::new(sample_rate as i64);
+        let host = timebase.to_seconds(timestamp_in_samples as i64);
+        if self.rebase_pending {
+            self.continue_from_latest(host, 0.0);
+        }
+
+        let mut timestamp = host + self.offset;
+        // small overlaps are left to the encoders, which keep their own output continuous
+        if let Some(start) = self.last_audio_start
+            && let Some(end) = self.audio_end
+            && timestamp < start
+        {
+            self.offset += end - timestamp;
+            timestamp = end;
+        }
+        let timestamp = timestamp.max(0.0);
+        self.last_audio_start = Some(timestamp);
+        self.audio_end = Some(timestamp + frames as f64 / sample_rate as f64);
+        Ok(timebase.to_units(timestamp) as u64)
+    }
+
+    fn continue_from_latest(&mut self, host: f64, gap: f64) {
+        self.rebase_pending = false;
+        let latest = match (self.last_video, self.audio_end) {
+            (Some(video), Some(audio)) => Some(video.max(audio)),
+            (latest, None) | (None, latest) => latest,
+        };
+        if let Some(latest) = latest {
+            self.offset = latest + gap - host;
+        }
+    }
+}
+
+/// Video encoder input that maps frame timestamps through a shared [`MediaClock`] once one is set.
+pub struct ClockedVideoInput<I> {
+    inner: I,
+    clock: Option<Arc<Mutex<MediaClock>>>,
+}
+
+impl<I> ClockedVideoInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self { inner, clock: None }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<Mutex<MediaClock>>) {
+        self.clock = Some(clock);
+    }
+
+    /// Maps a frame timestamp for frames passed to the inner input without going through `push`.
+    pub fn map_timestamp(&self, timestamp: f64) -> Result<f64> {
+        match &self.clock {
+            Some(clock) => lock(clock).video_timestamp(timestamp),
+            None => Ok(timestamp),
+        }
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for ClockedVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, mut data: Self::Data) -> Result<()> {
+        data.timestamp = self.map_timestamp(data.timestamp)?;
+        self.inner.push(data).await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+/// Audio counterpart of [`ClockedVideoInput`], which can also compensate the drift of the audio
+/// clock from the wall clock.
+pub struct ClockedAudioInput<I> {
+    inner: I,
+    clock: Option<(Arc<Mutex<MediaClock>>, u32, u32)>,
+    drift: Option<(DriftCompensator, Instant)>,
+    omitted: bool,
+    lag: f64,
+}
+
+impl<I> ClockedAudioInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            clock: None,
+            drift: None,
+            omitted: false,
+            lag: 0.0,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<Mutex<MediaClock>>, sample_rate: u32, channels: u32) {
+        self.clock = Some((clock, sample_rate, channels));
+    }
+
+    /// Measures subsequent pushes against the time they arrive at.
+    pub fn set_drift_compensation(&mut self, enabled: bool, sample_rate: u32, channels: u32) {
+        self.drift =
+            enabled.then(|| (DriftCompensator::new(sample_rate, channels), Instant::now()));
+    }
+
+    /// Drops subsequent pushes while `omitted`, such as for a time-lapse recording whose video
+    /// plays faster than its audio could.
+    pub fn set_omitted(&mut self, omitted: bool) {
+        self.omitted = omitted;
+    }
+
+    /// Moves subsequent samples `lag` seconds back on the timeline of the clock, to follow video
+    /// that has fallen behind its capture time, such as after a time-lapse. Needs a clock.
+    pub fn set_lag(&mut self, lag: f64) {
+        self.lag = lag.max(0.0);
+    }
+
+    pub fn drift_stats(&self) -> Option<DriftStats> {
+        self.drift
+            .as_ref()
+            .map(|(compensator, _)| compensator.stats())
+    }
+}
+
+impl<I: EncoderInput<Data = AudioSample>> EncoderInput for ClockedAudioInput<I> {
+    type Data = AudioSample;
+
+    async fn push(&mut self, mut data: Self::Data) -> Result<()> {
+        if self.omitted {
+            return Ok(());
+        }
+        if let Some((compensator, started)) = &mut self.drift {
+            data = compensator.process(data, started.elapsed().as_secs_f64());
+        }
+        if let Some((clock, sample_rate, channels)) = &self.clock {
+            let frames = data.data.len() / (*channels).max(1) as usize;
+            let lag = Timebase::new(*sample_rate as i64).to_units(self.lag) as u64;
+            data.timestamp_in_samples = lock(clock)
+                .audio_timestamp(data.timestamp_in_samples, frames, *sample_rate)?
+                .saturating_sub(lag);
+        }
+        self.inner.push(data).await
+    }
+}
+
+// the state is a handful of numbers that stay consistent even if a holder panicked
+fn lock(clock: &Mutex<MediaClock>) -> std::sync::MutexGuard<'_, MediaClock> {
+    clock.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_reset_keeps_both_streams_monotonic() {
+        let mut clock = MediaClock::new(10);
+        assert_eq!(clock.video_timestamp(5.0).unwrap(), 5.0);
+        assert_eq!(
+            clock.audio_timestamp(240_000, 4800, 48000).unwrap(),
+            240_000
+        );
+
+        // host clock restarts from zero
+        let video = clock.video_timestamp(0.0).unwrap();
+        assert!((video - 5.1).abs() < 1e-9);
+        // audio follows the shifted timeline instead of jumping back
+        let audio = clock.audio_timestamp(4800, 4800, 48000).unwrap();
+        assert_eq!(audio, 249_600);
+        assert!(clock.video_timestamp(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn explicit_rebase_continues_after_latest_output() {
+        let mut clock = MediaClock::new(30);
+        clock.video_timestamp(2.0).unwrap();
+        clock.rebase();
+        // a forward reset would otherwise leave a gap
+        assert_eq!(clock.audio_timestamp(480_000, 480, 48000).unwrap(), 96_000);
+    }
+}
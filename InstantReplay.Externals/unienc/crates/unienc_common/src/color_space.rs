@@ -0,0 +1,249 @@
+//! Checks of the gamma/linear workflow flag passed along with blit sources. A wrong flag does not
+//! fail anything, it only makes the recording washed out or too dark, so contradictions with the
+//! project's color space or the source format are reported as warnings instead.
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// Color space of the Unity project, reported by the managed side when the plugin loads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Gamma,
+    Linear,
+}
+
+const UNKNOWN: u8 = 0;
+const GAMMA: u8 = 1;
+const LINEAR: u8 = 2;
+
+static PROJECT_COLOR_SPACE: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+pub fn set_project_color_space(color_space: ColorSpace) {
+    let value = match color_space {
+        ColorSpace::Gamma => GAMMA,
+        ColorSpace::Linear => LINEAR,
+    };
+    PROJECT_COLOR_SPACE.store(value, Ordering::Relaxed);
+}
+
+/// `None` until the managed side has reported it.
+pub fn project_color_space() -> Option<ColorSpace> {
+    match PROJECT_COLOR_SPACE.load(Ordering::Relaxed) {
+        GAMMA => Some(ColorSpace::Gamma),
+        LINEAR => Some(ColorSpace::Linear),
+        _ => None,
+    }
+}
+
+/// Whether a Unity `GraphicsFormat` stores sRGB encoded values that are decoded when sampled.
+pub fn is_srgb_graphics_format(graphics_format: u32) -> bool {
+    matches!(
+        graphics_format,
+        // R8_SRGB..=R8G8B8A8_SRGB, B8G8R8_SRGB, B8G8R8A8_SRGB
+        1..=4 | 56 | 57
+        // DXT1, DXT3, DXT5 and BC7
+        | 96 | 98 | 100 | 108
+        // PVRTC
+        | 110 | 112 | 114 | 116
+        // ETC2
+        | 119 | 121 | 123
+        // ASTC 4x4 to 12x12
+        | 129 | 131 | 133 | 135 | 137 | 139
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GammaWorkflowMismatch {
+    /// The flag disagrees with the color space the project reported.
+    ProjectColorSpace { project: ColorSpace },
+    /// Gamma workflow with a source that is decoded to linear when sampled.
+    SrgbSourceInGammaWorkflow { graphics_format: u32 },
+}
+
+impl Display for GammaWorkflowMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GammaWorkflowMismatch::ProjectColorSpace { project } => write!(
+                f,
+                "is_gamma_workflow contradicts the project's {project:?} color space; \
+                 the recording will look {}",
+                match project {
+                    ColorSpace::Gamma => "washed out",
+                    ColorSpace::Linear => "too dark",
+                }
+            ),
+            GammaWorkflowMismatch::SrgbSourceInGammaWorkflow { graphics_format } => write!(
+                f,
+                "is_gamma_workflow is set but the source has the sRGB graphics format \
+                 {graphics_format}; the recording will look too dark"
+            ),
+        }
+    }
+}
+
+/// Checks the flag against `project`, the reported project color space if any, and the format of
+/// the source texture.
+pub fn check_gamma_workflow(
+    project: Option<ColorSpace>,
+    graphics_format: u32,
+    is_gamma_workflow: bool,
+) -> Option<GammaWorkflowMismatch> {
+    if is_gamma_workflow && is_srgb_graphics_format(graphics_format) {
+        return Some(GammaWorkflowMismatch::SrgbSourceInGammaWorkflow { graphics_format });
+    }
+    match project {
+        Some(project) if (project == ColorSpace::Gamma) != is_gamma_workflow => {
+            Some(GammaWorkflowMismatch::ProjectColorSpace { project })
+        }
+        _ => None,
+    }
+}
+
+/// Prints a warning the first time each kind of mismatch shows up, since blit sources are pushed
+/// every frame with the same flag.
+pub fn warn_gamma_workflow(graphics_format: u32, is_gamma_workflow: bool) {
+    static PROJECT_WARNED: AtomicBool = AtomicBool::new(false);
+    static FORMAT_WARNED: AtomicBool = AtomicBool::new(false);
+
+    let Some(mismatch) =
+        check_gamma_workflow(project_color_space(), graphics_format, is_gamma_workflow)
+    else {
+        return;
+    };
+    let warned = match mismatch {
+        GammaWorkflowMismatch::ProjectColorSpace { .. } => &PROJECT_WARNED,
+        GammaWorkflowMismatch::SrgbSourceInGammaWorkflow { .. } => &FORMAT_WARNED,
+    };
+    if !warned.swap(true, Ordering::Relaxed) {
+        crate::log!("unienc: {mismatch}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const R8G8B8A8_SRGB: u32 = 4;
+    const R8G8B8A8_UNORM: u32 = 8;
+    const B8G8R8A8_SRGB: u32 = 57;
+    const B8G8R8A8_UNORM: u32 = 58;
+
+    fn srgb_to_linear(v: f32) -> f32 {
+        if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn linear_to_srgb(v: f32) -> f32 {
+        if v <= 0.0031308 {
+            v * 12.92
+        } else {
+            1.055 * v.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
+    fn quantize(v: f32) -> u8 {
+        (v.clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+
+    /// What the blit stores for a stored source value: sRGB sources are decoded when sampled, and
+    /// the linear workflow renders into an sRGB target that encodes again.
+    fn blit(stored: u8, graphics_format: u32, is_gamma_workflow: bool) -> u8 {
+        let v = stored as f32 / 255.0;
+        let sampled = if is_srgb_graphics_format(graphics_format) {
+            srgb_to_linear(v)
+        } else {
+            v
+        };
+        quantize(if is_gamma_workflow {
+            sampled
+        } else {
+            linear_to_srgb(sampled)
+        })
+    }
+
+    /// Reference image for a stored source value: gamma projects store display values in every
+    /// texture, linear projects store them only in sRGB textures and linear values otherwise.
+    fn reference(stored: u8, project: ColorSpace, graphics_format: u32) -> u8 {
+        match project {
+            ColorSpace::Linear if !is_srgb_graphics_format(graphics_format) => {
+                quantize(linear_to_srgb(stored as f32 / 255.0))
+            }
+            _ => stored,
+        }
+    }
+
+    fn ramp() -> impl Iterator<Item = u8> {
+        (0..=255).step_by(17)
+    }
+
+    #[test]
+    fn correct_flag_matches_reference_ramp() {
+        for (project, format) in [
+            (ColorSpace::Gamma, R8G8B8A8_UNORM),
+            (ColorSpace::Gamma, B8G8R8A8_UNORM),
+            (ColorSpace::Linear, R8G8B8A8_SRGB),
+            (ColorSpace::Linear, B8G8R8A8_SRGB),
+            (ColorSpace::Linear, R8G8B8A8_UNORM),
+        ] {
+            let is_gamma = project == ColorSpace::Gamma;
+            assert_eq!(check_gamma_workflow(Some(project), format, is_gamma), None);
+            for stored in ramp() {
+                assert_eq!(
+                    blit(stored, format, is_gamma),
+                    reference(stored, project, format),
+                    "{project:?} {format} {stored}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn wrong_output_is_always_reported() {
+        for project in [ColorSpace::Gamma, ColorSpace::Linear] {
+            for format in [R8G8B8A8_SRGB, R8G8B8A8_UNORM, B8G8R8A8_SRGB, B8G8R8A8_UNORM] {
+                for is_gamma in [false, true] {
+                    let wrong = ramp().any(|stored| {
+                        blit(stored, format, is_gamma) != reference(stored, project, format)
+                    });
+                    if wrong {
+                        assert!(
+                            check_gamma_workflow(Some(project), format, is_gamma).is_some(),
+                            "{project:?} {format} {is_gamma}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reports_srgb_source_without_project_color_space() {
+        assert_eq!(
+            check_gamma_workflow(None, B8G8R8A8_SRGB, true),
+            Some(GammaWorkflowMismatch::SrgbSourceInGammaWorkflow {
+                graphics_format: B8G8R8A8_SRGB
+            })
+        );
+        assert_eq!(check_gamma_workflow(None, B8G8R8A8_UNORM, true), None);
+        assert_eq!(check_gamma_workflow(None, R8G8B8A8_UNORM, false), None);
+    }
+
+    #[test]
+    fn reports_flag_against_project() {
+        assert_eq!(
+            check_gamma_workflow(Some(ColorSpace::Gamma), R8G8B8A8_UNORM, false),
+            Some(GammaWorkflowMismatch::ProjectColorSpace {
+                project: ColorSpace::Gamma
+            })
+        );
+        assert_eq!(
+            check_gamma_workflow(Some(ColorSpace::Linear), R8G8B8A8_UNORM, true),
+            Some(GammaWorkflowMismatch::ProjectColorSpace {
+                project: ColorSpace::Linear
+            })
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Deadlines for work that may wait indefinitely, such as a push to an encoder whose input buffers
+//! are never released. The crate does not depend on an async runtime with timers, so deadlines
+//! are kept by a single timer thread started on first use, which wakes the futures that expired.
+
+use std::future::poll_fn;
+use std::pin::pin;
+use std::sync::{Condvar, Mutex, MutexGuard, Once};
+use std::task::{Poll, Waker};
+use std::time::{Duration, Instant};
+
+use crate::{CommonError, Result};
+
+struct Timer {
+    entries: Mutex<Vec<(Instant, Waker)>>,
+    changed: Condvar,
+}
+
+static TIMER: Timer = Timer {
+    entries: Mutex::new(Vec::new()),
+    changed: Condvar::new(),
+};
+
+impl Timer {
+    fn get() -> &'static Timer {
+        static START: Once = Once::new();
+        START.call_once(|| {
+            std::thread::Builder::new()
+                .name("unienc-timer".to_string())
+                .spawn(|| TIMER.run())
+                .expect("failed to start the timer thread");
+        });
+        &TIMER
+    }
+
+    fn run(&self) {
+        let mut entries = self.lock();
+        loop {
+            let now = Instant::now();
+            entries.retain(|(at, waker)| {
+                let expired = *at <= now;
+                if expired {
+                    waker.wake_by_ref();
+                }
+                !expired
+            });
+            entries = match entries.iter().map(|(at, _)| *at).min() {
+                Some(next) => {
+                    self.changed
+                        .wait_timeout(entries, next - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self
+                    .changed
+                    .wait(entries)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+
+    fn wake_at(&self, at: Instant, waker: &Waker) {
+        let mut entries = self.lock();
+        if !entries
+            .iter()
+            .any(|(other, w)| *other == at && w.will_wake(waker))
+        {
+            entries.push((at, waker.clone()));
+        }
+        self.changed.notify_one();
+    }
+
+    // entries stay consistent even if a holder panicked
+    fn lock(&self) -> MutexGuard<'_, Vec<(Instant, Waker)>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Runs `future` until it completes or `timeout` elapses, in which case `future` is dropped and
+/// [`CommonError::DeadlineExceeded`] is returned. `None` waits as long as `future` takes.
+pub async fn with_deadline<T>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(timeout) = timeout else {
+        return future.await;
+    };
+    let deadline = Instant::now() + timeout;
+    let mut future = pin!(future);
+    poll_fn(|cx| {
+        if let Poll::Ready(result) = future.as_mut().poll(cx) {
+            return Poll::Ready(result);
+        }
+        if Instant::now() >= deadline {
+            return Poll::Ready(Err(CommonError::DeadlineExceeded(timeout.as_secs_f64())));
+        }
+        Timer::get().wake_at(deadline, cx.waker());
+        Poll::Pending
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::future::pending;
+
+    #[test]
+    fn pending_work_fails_once_the_deadline_passes() {
+        let timeout = Duration::from_millis(20);
+        let start = Instant::now();
+        let result = block_on(with_deadline(Some(timeout), pending::<Result<()>>()));
+        assert!(matches!(result, Err(CommonError::DeadlineExceeded(_))));
+        assert!(start.elapsed() >= timeout);
+
+        let result = block_on(with_deadline(Some(timeout), async { Ok(1) }));
+        assert_eq!(result.unwrap(), 1);
+    }
+}
@@ -0,0 +1,205 @@
+//! Detection of frames identical to the one before, such as menus and pause screens, so static
+//! stretches cost neither encoding time nor bitrate. Frames are compared by a hash of their luma
+//! averaged over a coarse grid; frames whose averages match in every cell are treated as
+//! identical.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::buffer::SharedBuffer;
+use crate::{EncoderInput, Result, VideoFrame, VideoFrameBgra32, VideoSample};
+
+/// Cells per side of the grid luma is averaged over.
+const GRID: usize = 32;
+
+/// Longest time in seconds a duplicate frame is skipped for, so players and seeking do not wait
+/// for the next change through a long static stretch.
+pub const MAX_SKIP: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupMode {
+    /// Duplicates are not pushed, so the previous frame lasts until the next change and the
+    /// stream has a variable frame rate. With a frame rate set they fall on its frame times.
+    Skip,
+    /// Duplicates are replaced with a copy of the frame they duplicate, which encoders code with
+    /// the fewest bits, keeping the frame rate. Every frame is copied to have one at hand.
+    Repeat,
+}
+
+/// Video encoder input that drops or repeats frames identical to the previous one, once a mode
+/// is set. Blit sources are not read back on the CPU, so they always pass through.
+pub struct DedupVideoInput<I> {
+    inner: I,
+    mode: Option<DedupMode>,
+    /// Hash of the last frame pushed, and its timestamp.
+    last: Option<(u64, f64)>,
+    /// Copy of the last frame pushed, in `Repeat` mode.
+    previous: Option<VideoFrameBgra32>,
+    duplicates: u64,
+}
+
+impl<I> DedupVideoInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            mode: None,
+            last: None,
+            previous: None,
+            duplicates: 0,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Applies to frames pushed after this call. `None` passes every frame through.
+    pub fn set_mode(&mut self, mode: Option<DedupMode>) {
+        self.mode = mode;
+        self.last = None;
+        self.previous = None;
+    }
+
+    /// Frames skipped or repeated so far.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for DedupVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        let Some(mode) = self.mode else {
+            return self.inner.push(data).await;
+        };
+        let VideoFrame::Bgra32(frame) = &data.frame else {
+            self.last = None;
+            self.previous = None;
+            return self.inner.push(data).await;
+        };
+        let hash = luma_hash(frame);
+        let duplicate = self.last.is_some_and(|(last, _)| last == hash);
+
+        match mode {
+            DedupMode::Skip => {
+                if duplicate
+                    && let Some((_, pushed_at)) = self.last
+                    && data.timestamp - pushed_at < MAX_SKIP
+                {
+                    self.duplicates += 1;
+                    return Ok(());
+                }
+                self.last = Some((hash, data.timestamp));
+                self.inner.push(data).await
+            }
+            DedupMode::Repeat => {
+                let repeated = match (&self.previous, duplicate) {
+                    (Some(previous), true) => {
+                        self.duplicates += 1;
+                        copy(previous)
+                    }
+                    _ => {
+                        let VideoFrame::Bgra32(frame) = data.frame else {
+                            unreachable!();
+                        };
+                        self.last = Some((hash, data.timestamp));
+                        self.previous = Some(copy(&frame));
+                        frame
+                    }
+                };
+                self.inner
+                    .push(VideoSample {
+                        frame: VideoFrame::Bgra32(repeated),
+                        timestamp: data.timestamp,
+                    })
+                    .await
+            }
+        }
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+/// Hash of the average luma of each cell of a [`GRID`] by [`GRID`] grid over the frame, sampling
+/// every other pixel of every other row.
+fn luma_hash(frame: &VideoFrameBgra32) -> u64 {
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let stride = frame.stride as usize;
+    let data = frame.buffer.data();
+    let mut sums = [0u32; GRID * GRID];
+    let mut counts = [0u32; GRID * GRID];
+    for y in (0..height).step_by(2) {
+        let row = &data[y * stride..][..width * 4];
+        let cells = y * GRID / height * GRID;
+        for x in (0..width).step_by(2) {
+            let [b, g, r, _] = row[x * 4..][..4] else {
+                unreachable!();
+            };
+            let cell = cells + x * GRID / width;
+            sums[cell] += (29 * b as u32 + 150 * g as u32 + 77 * r as u32) >> 8;
+            counts[cell] += 1;
+        }
+    }
+
+    let mut hasher = DefaultHasher::new();
+    (width, height).hash(&mut hasher);
+    for (sum, count) in sums.iter().zip(counts) {
+        (sum / count.max(1)).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn copy(frame: &VideoFrameBgra32) -> VideoFrameBgra32 {
+    VideoFrameBgra32 {
+        buffer: SharedBuffer::new_unmanaged(frame.buffer.data().to_vec()),
+        ..*frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Frames, push};
+
+    #[test]
+    fn skips_duplicates_up_to_the_longest_skip() {
+        let mut input = DedupVideoInput::new(Frames::default());
+        input.set_mode(Some(DedupMode::Skip));
+        for (i, value) in [0, 0, 0, 100, 100].into_iter().enumerate() {
+            push(&mut input, i as f64 / 10.0, (8, 8), vec![value; 8 * 8 * 4]);
+        }
+        push(&mut input, 0.3 + MAX_SKIP, (8, 8), vec![100; 8 * 8 * 4]);
+        assert_eq!(
+            input.inner_mut().timestamps(),
+            vec![0.0, 0.3, 0.3 + MAX_SKIP]
+        );
+        assert_eq!(input.duplicates(), 3);
+    }
+
+    #[test]
+    fn repeats_the_frame_a_duplicate_matches() {
+        let mut input = DedupVideoInput::new(Frames::default());
+        input.set_mode(Some(DedupMode::Repeat));
+        push(&mut input, 0.0, (8, 8), vec![10; 8 * 8 * 4]);
+        // the second pixel is not sampled
+        let mut noisy = vec![10; 8 * 8 * 4];
+        noisy[4] = 60;
+        push(&mut input, 0.1, (8, 8), noisy);
+        push(&mut input, 0.2, (8, 8), vec![50; 8 * 8 * 4]);
+
+        let frames = input.inner_mut().pushed();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            (frames[1].timestamp, &frames[1].pixels),
+            (0.1, &vec![10; 8 * 8 * 4])
+        );
+        assert_eq!(frames[2].pixels[0], 50);
+        assert_eq!(input.duplicates(), 1);
+    }
+}
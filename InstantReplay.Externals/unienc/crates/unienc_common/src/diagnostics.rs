@@ -0,0 +1,239 @@
+//! Startup self test of the platform backend, so an incomplete install (missing framework slice,
+//! graphics interception not loaded, ffmpeg not on PATH) is reported with an actionable message
+//! before anything is recorded, and the preflight of a recording with specific options.
+
+use std::fmt::Display;
+use std::path::Path;
+
+use crate::padding::coded_size;
+use crate::{AudioEncoderOptions, EncodingSystem, VideoCodec, VideoEncoderOptions};
+
+/// Seconds of recording the output directory should have room for.
+const MIN_RECORDING_SECONDS: u64 = 60;
+/// Frame size limit of H.264 level 5.2 in macroblocks, the highest level encoders commonly support.
+const MAX_H264_MACROBLOCKS: u32 = 36864;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticCheck {
+    pub name: &'static str,
+    pub passed: bool,
+    /// What was found, or what to do about it when the check failed.
+    pub message: String,
+}
+
+impl DiagnosticCheck {
+    pub fn passed(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed: true,
+            message: message.into(),
+        }
+    }
+
+    pub fn failed(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed: false,
+            message: message.into(),
+        }
+    }
+
+    /// Passes with `ok` when `result` is Ok, otherwise fails with the error followed by `hint`.
+    pub fn from_result<T, E: Display>(
+        name: &'static str,
+        result: std::result::Result<T, E>,
+        ok: &str,
+        hint: &str,
+    ) -> Self {
+        match result {
+            Ok(_) => Self::passed(name, ok),
+            Err(err) => Self::failed(name, format!("{err}. {hint}")),
+        }
+    }
+}
+
+/// Small encoder settings every backend accepts, for creating throwaway encoders while probing.
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeOptions;
+
+impl VideoEncoderOptions for ProbeOptions {
+    fn width(&self) -> u32 {
+        256
+    }
+    fn height(&self) -> u32 {
+        256
+    }
+    fn fps_hint(&self) -> u32 {
+        30
+    }
+    fn bitrate(&self) -> u32 {
+        1_000_000
+    }
+}
+
+impl AudioEncoderOptions for ProbeOptions {
+    fn sample_rate(&self) -> u32 {
+        48000
+    }
+    fn channels(&self) -> u32 {
+        2
+    }
+    fn bitrate(&self) -> u32 {
+        128_000
+    }
+}
+
+/// Checks everything a recording with these options needs, so the host can explain why recording
+/// is unavailable before offering it instead of failing once it starts. Runs the backend's
+/// [`self_test`](EncodingSystem::self_test), which covers encoder availability and graphics
+/// interception, then checks the codec, alpha, resolution and, if `output_dir` is given, the free
+/// space where the file will be written.
+pub fn check_support<S: EncodingSystem>(
+    system: &S,
+    video_options: &S::VideoEncoderOptionsType,
+    audio_options: &S::AudioEncoderOptionsType,
+    output_dir: Option<&Path>,
+) -> Vec<DiagnosticCheck> {
+    let codec = video_options.codec();
+    let mut checks = S::self_test();
+    checks.push(if system.is_codec_supported(codec) {
+        DiagnosticCheck::passed("codec", format!("{codec:?} encoder available"))
+    } else {
+        DiagnosticCheck::failed(
+            "codec",
+            format!("{codec:?} is not supported on this platform"),
+        )
+    });
+    if video_options.preserve_alpha() {
+        checks.push(if system.is_alpha_supported() {
+            DiagnosticCheck::passed("alpha", format!("{codec:?} keeps the alpha channel"))
+        } else {
+            DiagnosticCheck::failed(
+                "alpha",
+                format!(
+                    "{codec:?} cannot keep the alpha channel here; record a PNG sequence instead"
+                ),
+            )
+        });
+    }
+    checks.push(resolution_check(
+        video_options.width(),
+        video_options.height(),
+        codec,
+    ));
+    if let Some(output_dir) = output_dir {
+        checks.push(disk_check(
+            system.available_space(output_dir),
+            bytes_per_second(video_options, audio_options),
+        ));
+    }
+    checks
+}
+
+fn resolution_check(width: u32, height: u32, codec: VideoCodec) -> DiagnosticCheck {
+    if width == 0 || height == 0 {
+        return DiagnosticCheck::failed("resolution", format!("{width}x{height} is empty"));
+    }
+    if codec == VideoCodec::H264 && width.div_ceil(16) * height.div_ceil(16) > MAX_H264_MACROBLOCKS
+    {
+        return DiagnosticCheck::failed(
+            "resolution",
+            format!("{width}x{height} exceeds H.264 level 5.2; use at most 4096x2304 or its area"),
+        );
+    }
+    let (coded_width, coded_height) = coded_size(width, height);
+    if (coded_width, coded_height) != (width, height) {
+        return DiagnosticCheck::passed(
+            "resolution",
+            format!("{width}x{height}, encoded at {coded_width}x{coded_height} and cropped"),
+        );
+    }
+    DiagnosticCheck::passed("resolution", format!("{width}x{height}"))
+}
+
+/// Expected size of the recording per second. Intra-only codecs ignore the bitrate and are
+/// estimated from the frame size instead.
+fn bytes_per_second(video: &impl VideoEncoderOptions, audio: &impl AudioEncoderOptions) -> u64 {
+    let video_bits = if video.codec().is_intra_only() {
+        let bits_per_pixel = if video.preserve_alpha() { 6 } else { 4 };
+        video.width() as u64 * video.height() as u64 * video.fps_hint() as u64 * bits_per_pixel
+    } else {
+        video.bitrate() as u64
+    };
+    (video_bits + audio.bitrate() as u64) / 8
+}
+
+fn disk_check(available: Option<u64>, bytes_per_second: u64) -> DiagnosticCheck {
+    const MB: u64 = 1024 * 1024;
+    let needed = bytes_per_second * MIN_RECORDING_SECONDS;
+    match available {
+        None => DiagnosticCheck::passed("disk", "Free space could not be determined"),
+        Some(available) if available < needed => DiagnosticCheck::failed(
+            "disk",
+            format!(
+                "{} MB free, but a minute of recording needs about {} MB",
+                available / MB,
+                needed.div_ceil(MB)
+            ),
+        ),
+        Some(available) => DiagnosticCheck::passed("disk", format!("{} MB free", available / MB)),
+    }
+}
+
+/// Bytes available to this process on the volume of `directory`.
+#[cfg(unix)]
+pub(crate) fn available_space(directory: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(directory.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+    // the field types differ between platforms
+    #[allow(clippy::useless_conversion)]
+    Some(u64::from(stat.f_bavail) * u64::from(stat.f_frsize))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn available_space(_directory: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolution_must_be_within_h264_level() {
+        assert!(resolution_check(1920, 1080, VideoCodec::H264).passed);
+        assert!(resolution_check(4096, 2304, VideoCodec::H264).passed);
+        let odd = resolution_check(1921, 1079, VideoCodec::H264);
+        assert!(odd.passed);
+        assert_eq!(odd.message, "1921x1079, encoded at 1922x1080 and cropped");
+        assert!(!resolution_check(0, 1080, VideoCodec::ProRes).passed);
+        assert!(!resolution_check(7680, 4320, VideoCodec::H264).passed);
+        assert!(resolution_check(7680, 4320, VideoCodec::ProRes).passed);
+    }
+
+    #[test]
+    fn disk_needs_room_for_a_minute() {
+        let rate = 1024 * 1024;
+        assert!(disk_check(Some(60 * rate), rate).passed);
+        let check = disk_check(Some(59 * rate), rate);
+        assert!(!check.passed);
+        assert_eq!(
+            check.message,
+            "59 MB free, but a minute of recording needs about 60 MB"
+        );
+        assert!(disk_check(None, rate).passed);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn temp_dir_has_some_space() {
+        assert!(available_space(&std::env::temp_dir()).is_some());
+        assert!(available_space(Path::new("/nonexistent/directory")).is_none());
+    }
+}
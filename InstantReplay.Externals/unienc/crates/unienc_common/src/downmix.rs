@@ -0,0 +1,236 @@
+//! Downmixing of surround audio for encoders limited to fewer channels. Unity mixes to the speaker
+//! mode of the project, so games set to 5.1 or 7.1 push more channels than most platform AAC
+//! encoders accept.
+
+use crate::profiler::ProfilerMarker;
+use crate::{AudioEncoderOptions, AudioSample, Encoder, EncoderInput, Result};
+
+static DOWNMIX: ProfilerMarker = ProfilerMarker::new("unienc_common::downmix");
+
+const HALF_POWER: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Gains of each source channel into the left and right output channels, in Unity's interleaved
+/// order (front left, front right, center, LFE, rear left, rear right, side left, side right).
+/// Centre and surround channels are mixed at -3 dB and the LFE is dropped, per ITU-R BS.775.
+fn stereo_gains(channels: u32) -> Vec<(f32, f32)> {
+    let gains: &[(f32, f32)] = match channels {
+        // quad
+        4 => &[(1.0, 0.0), (0.0, 1.0), (HALF_POWER, 0.0), (0.0, HALF_POWER)],
+        // 5.0
+        5 => &[
+            (1.0, 0.0),
+            (0.0, 1.0),
+            (HALF_POWER, HALF_POWER),
+            (HALF_POWER, 0.0),
+            (0.0, HALF_POWER),
+        ],
+        6 => &[
+            (1.0, 0.0),
+            (0.0, 1.0),
+            (HALF_POWER, HALF_POWER),
+            (0.0, 0.0),
+            (HALF_POWER, 0.0),
+            (0.0, HALF_POWER),
+        ],
+        8 => &[
+            (1.0, 0.0),
+            (0.0, 1.0),
+            (HALF_POWER, HALF_POWER),
+            (0.0, 0.0),
+            (HALF_POWER, 0.0),
+            (0.0, HALF_POWER),
+            (HALF_POWER, 0.0),
+            (0.0, HALF_POWER),
+        ],
+        // layouts Unity does not produce keep their first two channels
+        _ => &[(1.0, 0.0), (0.0, 1.0)],
+    };
+    let mut gains = gains.to_vec();
+    gains.resize(channels as usize, (0.0, 0.0));
+    // scaled so that channels at full scale together do not clip
+    let total = gains.iter().map(|(left, _)| left).sum::<f32>();
+    gains
+        .into_iter()
+        .map(|(left, right)| (left / total, right / total))
+        .collect()
+}
+
+/// Mixes interleaved PCM of `channels` channels down to interleaved stereo.
+pub fn downmix_to_stereo(data: &[i16], channels: u32) -> Vec<i16> {
+    let gains = stereo_gains(channels);
+    let mut out = Vec::with_capacity(data.len() / channels as usize * 2);
+    for frame in data.chunks_exact(channels as usize) {
+        let (mut left, mut right) = (0.0, 0.0);
+        for (&value, &(left_gain, right_gain)) in frame.iter().zip(&gains) {
+            left += value as f32 * left_gain;
+            right += value as f32 * right_gain;
+        }
+        out.push(left.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+        out.push(right.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+    }
+    out
+}
+
+/// Options of a backend's audio encoder for audio of the wrapped options, reporting the channels
+/// it is encoded with: the source channels if the backend supports them, otherwise stereo.
+#[derive(Debug, Clone, Copy)]
+pub struct DownmixedAudioOptions<A> {
+    inner: A,
+    channels: u32,
+}
+
+impl<A: AudioEncoderOptions> DownmixedAudioOptions<A> {
+    /// `is_supported` tells whether the backend encodes a number of channels as is.
+    pub fn new(inner: A, is_supported: impl Fn(u32) -> bool) -> Self {
+        let source = inner.channels();
+        let channels = match source > 2 && !is_supported(source) {
+            true => 2,
+            false => source,
+        };
+        Self { inner, channels }
+    }
+
+    /// For backends that encode at most two channels.
+    pub fn stereo(inner: A) -> Self {
+        Self::new(inner, |_| false)
+    }
+
+    pub fn source_channels(&self) -> u32 {
+        self.inner.channels()
+    }
+
+    pub fn is_downmixed(&self) -> bool {
+        self.channels != self.inner.channels()
+    }
+}
+
+impl<A: AudioEncoderOptions> AudioEncoderOptions for DownmixedAudioOptions<A> {
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    fn bitrate(&self) -> u32 {
+        self.inner.bitrate()
+    }
+}
+
+/// Audio encoder whose input takes samples of the source channels of [`DownmixedAudioOptions`]
+/// and downmixes them for the wrapped encoder when needed.
+pub struct DownmixedAudioEncoder<E> {
+    inner: E,
+    source_channels: Option<u32>,
+}
+
+impl<E> DownmixedAudioEncoder<E> {
+    pub fn new<A: AudioEncoderOptions>(inner: E, options: &DownmixedAudioOptions<A>) -> Self {
+        Self {
+            inner,
+            source_channels: options.is_downmixed().then(|| options.source_channels()),
+        }
+    }
+}
+
+impl<E: Encoder<InputType: EncoderInput<Data = AudioSample>>> Encoder for DownmixedAudioEncoder<E> {
+    type InputType = DownmixedAudioInput<E::InputType>;
+    type OutputType = E::OutputType;
+
+    fn get(self) -> Result<(Self::InputType, Self::OutputType)> {
+        let (input, output) = self.inner.get()?;
+        Ok((
+            DownmixedAudioInput {
+                inner: input,
+                source_channels: self.source_channels,
+            },
+            output,
+        ))
+    }
+}
+
+pub struct DownmixedAudioInput<I> {
+    inner: I,
+    /// `None` if samples pass through unchanged.
+    source_channels: Option<u32>,
+}
+
+impl<I> DownmixedAudioInput<I> {
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+}
+
+impl<I: EncoderInput<Data = AudioSample>> EncoderInput for DownmixedAudioInput<I> {
+    type Data = AudioSample;
+
+    async fn push(&mut self, mut data: Self::Data) -> Result<()> {
+        if let Some(channels) = self.source_channels {
+            let _guard = DOWNMIX.begin();
+            data.data = downmix_to_stereo(&data.data, channels);
+        }
+        self.inner.push(data).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    struct Options(u32);
+
+    impl AudioEncoderOptions for Options {
+        fn sample_rate(&self) -> u32 {
+            48000
+        }
+
+        fn channels(&self) -> u32 {
+            self.0
+        }
+
+        fn bitrate(&self) -> u32 {
+            128_000
+        }
+    }
+
+    #[test]
+    fn downmixes_unsupported_surround_only() {
+        let stereo_only = |channels| channels <= 2;
+        let options = DownmixedAudioOptions::new(Options(6), stereo_only);
+        assert_eq!((options.channels(), options.source_channels()), (2, 6));
+        assert!(options.is_downmixed());
+
+        let options = DownmixedAudioOptions::new(Options(6), |channels| channels == 6);
+        assert_eq!(options.channels(), 6);
+        assert!(!options.is_downmixed());
+
+        let options = DownmixedAudioOptions::new(Options(1), stereo_only);
+        assert_eq!(options.channels(), 1);
+    }
+
+    #[test]
+    fn mixes_5_1_into_stereo_without_clipping() {
+        // front left only, then center only, then LFE only
+        let data = [
+            10000, 0, 0, 0, 0, 0, //
+            0, 0, 10000, 0, 0, 0, //
+            0, 0, 0, 10000, 0, 0,
+        ];
+        let out = downmix_to_stereo(&data, 6);
+        let total = 1.0 + 2.0 * HALF_POWER;
+        let front = (10000.0 / total).round() as i16;
+        let center = (10000.0 * HALF_POWER / total).round() as i16;
+        assert_eq!(out, [front, 0, center, center, 0, 0]);
+
+        let full = [i16::MAX; 6];
+        let out = downmix_to_stereo(&full, 6);
+        assert!(out.iter().all(|&v| v >= i16::MAX - 1));
+    }
+
+    #[test]
+    fn keeps_the_front_pair_of_unknown_layouts() {
+        assert_eq!(downmix_to_stereo(&[1, 2, 3, 4, 5, 6], 3), [1, 2, 4, 5]);
+    }
+}
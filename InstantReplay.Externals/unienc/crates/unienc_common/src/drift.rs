@@ -0,0 +1,187 @@
+//! Compensation of drift between the audio sample clock and the wall clock video timestamps follow.
+//!
+//! Audio timestamps count samples delivered by the audio device, which runs slightly faster or
+//! slower than the wall clock. Over a long recording the difference grows to tens of milliseconds,
+//! so the drift is estimated against the wall clock time of each push and removed by resampling
+//! the audio very slightly. Resampling keeps the audio timeline continuous, which shifting
+//! timestamps alone would not (encoders ignore backward jumps).
+
+use crate::AudioSample;
+
+/// Length of audio over which the least delayed push is taken as the drift measurement. Pushes
+/// arrive late by varying amounts, which only ever makes the audio look behind.
+const MEASUREMENT_WINDOW: f64 = 2.0;
+/// Time constant of the smoothing applied to the measurements.
+const SMOOTHING: f64 = 20.0;
+/// Maximum correction per second of audio, i.e. a 0.1% resampling ratio.
+const MAX_SLEW: f64 = 0.001;
+/// Remaining drift left uncorrected, to not resample constantly over measurement noise.
+const TOLERANCE: f64 = 0.002;
+/// Differences larger than this are discontinuities (pauses, dropped audio), not drift.
+const RESYNC_THRESHOLD: f64 = 0.25;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DriftStats {
+    /// Estimated difference of the audio clock from the wall clock, in seconds. Positive when the
+    /// audio clock runs ahead.
+    pub drift: f64,
+    /// Audio removed (positive) or inserted (negative) so far, in seconds.
+    pub correction: f64,
+}
+
+#[derive(Debug)]
+pub struct DriftCompensator {
+    sample_rate: u32,
+    channels: usize,
+    /// Audio position and wall clock time the drift is measured against.
+    origin: Option<(u64, f64)>,
+    window_end: u64,
+    window_max: Option<f64>,
+    drift: f64,
+    correction_samples: i64,
+    pending_samples: f64,
+}
+
+impl DriftCompensator {
+    pub fn new(sample_rate: u32, channels: u32) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1),
+            channels: channels.max(1) as usize,
+            origin: None,
+            window_end: 0,
+            window_max: None,
+            drift: 0.0,
+            correction_samples: 0,
+            pending_samples: 0.0,
+        }
+    }
+
+    pub fn stats(&self) -> DriftStats {
+        DriftStats {
+            drift: self.drift,
+            correction: self.correction_samples as f64 / self.sample_rate as f64,
+        }
+    }
+
+    /// Corrects a push whose last sample was delivered at `wall`, in seconds on any monotonic
+    /// clock.
+    pub fn process(&mut self, sample: AudioSample, wall: f64) -> AudioSample {
+        let rate = self.sample_rate as f64;
+        let frames = sample.data.len() / self.channels;
+        let end = sample.timestamp_in_samples + frames as u64;
+
+        let Some((origin_position, origin_wall)) = self.origin else {
+            self.origin = Some((end, wall));
+            self.window_end = end + (MEASUREMENT_WINDOW * rate) as u64;
+            return self.shift(sample);
+        };
+
+        let measured = (end as f64 - origin_position as f64) / rate - (wall - origin_wall);
+        if (measured - self.drift).abs() > RESYNC_THRESHOLD {
+            // measure from here on so that the current estimate carries over
+            self.origin = Some((end, wall + self.drift));
+            self.window_max = None;
+            self.window_end = end + (MEASUREMENT_WINDOW * rate) as u64;
+            return self.shift(sample);
+        }
+
+        self.window_max = Some(self.window_max.map_or(measured, |max| max.max(measured)));
+        if end >= self.window_end {
+            let weight = (MEASUREMENT_WINDOW / SMOOTHING).min(1.0);
+            self.drift += (self.window_max.unwrap_or(measured) - self.drift) * weight;
+            self.window_max = None;
+            self.window_end = end + (MEASUREMENT_WINDOW * rate) as u64;
+        }
+
+        let residual = self.drift - self.correction_samples as f64 / rate;
+        if residual.abs() > TOLERANCE {
+            let limit = MAX_SLEW * frames as f64 / rate;
+            self.pending_samples += residual.clamp(-limit, limit) * rate;
+        }
+        // keep at least one frame of the push
+        let delta = (self.pending_samples.trunc() as i64).min(frames as i64 - 1);
+        if delta == 0 || frames < 2 {
+            return self.shift(sample);
+        }
+        self.pending_samples -= delta as f64;
+
+        let sample = self.shift(sample);
+        self.correction_samples += delta;
+        AudioSample {
+            data: resample(
+                &sample.data,
+                self.channels,
+                (frames as i64 - delta) as usize,
+            ),
+            timestamp_in_samples: sample.timestamp_in_samples,
+        }
+    }
+
+    fn shift(&self, sample: AudioSample) -> AudioSample {
+        AudioSample {
+            timestamp_in_samples: (sample.timestamp_in_samples as i64 - self.correction_samples)
+                .max(0) as u64,
+            data: sample.data,
+        }
+    }
+}
+
+/// Linearly interpolates interleaved audio to `frames` samples per channel.
+fn resample(data: &[i16], channels: usize, frames: usize) -> Vec<i16> {
+    let input_frames = data.len() / channels;
+    let mut output = Vec::with_capacity(frames * channels);
+    let step = if frames > 1 {
+        (input_frames - 1) as f64 / (frames - 1) as f64
+    } else {
+        0.0
+    };
+    for i in 0..frames {
+        let position = i as f64 * step;
+        let index = (position as usize).min(input_frames - 1);
+        let next = (index + 1).min(input_frames - 1);
+        let fraction = position - index as f64;
+        for channel in 0..channels {
+            let a = data[index * channels + channel] as f64;
+            let b = data[next * channels + channel] as f64;
+            output.push((a + (b - a) * fraction).round() as i16);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_audio_clock_is_resampled_to_wall_clock() {
+        let mut compensator = DriftCompensator::new(48000, 2);
+        // 100 ppm fast for 10 minutes in 20 ms pushes, delivered with up to 15 ms of latency
+        let mut next = None;
+        for i in 0..30_000u64 {
+            let sample = AudioSample {
+                data: vec![0; 960 * 2],
+                timestamp_in_samples: i * 960,
+            };
+            let wall = (i + 1) as f64 * 0.02 / 1.0001 + (i % 7) as f64 * 0.0025;
+            let output = compensator.process(sample, wall);
+            if let Some(next) = next {
+                assert_eq!(output.timestamp_in_samples, next);
+            }
+            next = Some(output.timestamp_in_samples + output.data.len() as u64 / 2);
+        }
+
+        let stats = compensator.stats();
+        assert!((stats.drift - 0.06).abs() < 0.005, "{stats:?}");
+        assert!((stats.drift - stats.correction).abs() < 0.005, "{stats:?}");
+    }
+
+    #[test]
+    fn resample_keeps_endpoints() {
+        assert_eq!(resample(&[0, 10, 20, 30, 40], 1, 3), vec![0, 20, 40]);
+        assert_eq!(
+            resample(&[0, 100, 30, 130], 2, 3),
+            vec![0, 100, 15, 115, 30, 130]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Maximum duration and size of a muxed file, enforced on the samples reaching the muxer so that
+//! frames already in flight when the limit passes do not make the file longer than requested.
+//!
+//! The size is kept under the limit by ending the file before a GOP that would likely cross it,
+//! estimated from the largest GOP so far. A GOP larger than every previous one is cut at the
+//! sample that would cross the limit instead, so the limit is never exceeded.
+
+use std::sync::{Arc, Mutex};
+
+use crate::{EncodedData, MuxerInput, Result, UniencSampleKind};
+
+/// Bytes reserved for the container headers and codec configuration.
+const CONTAINER_RESERVE: u64 = 16 * 1024;
+/// Bytes of sample tables added to the container per sample.
+const SAMPLE_OVERHEAD: u64 = 16;
+/// Share of the budget given to the encoders, as rate control overshoots the target bitrate.
+const BITRATE_MARGIN: f64 = 0.95;
+
+/// Video bitrate that keeps a file of `duration` seconds under `max_output_bytes` with audio of
+/// `audio_bitrate`, in bits per second. `None` if the audio alone does not fit.
+pub fn fit_video_bitrate(max_output_bytes: u64, duration: f64, audio_bitrate: u32) -> Option<u32> {
+    if duration.is_nan() || duration <= 0.0 {
+        return None;
+    }
+    let budget = max_output_bytes.saturating_sub(CONTAINER_RESERVE) as f64 * 8.0 * BITRATE_MARGIN;
+    let bitrate = budget / duration - audio_bitrate as f64;
+    // leaves room for the sample tables at typical frame rates
+    (bitrate >= 1.0).then(|| bitrate.min(u32::MAX as f64) as u32)
+}
+
+type CompletionCallback = Box<dyn FnOnce() + Send>;
+
+/// Shared by the video and audio inputs of a muxer. Durations are measured from the first sample
+/// pushed to either input.
+pub struct DurationLimit {
+    max_duration: f64,
+    max_bytes: Option<u64>,
+    state: Mutex<LimitState>,
+}
+
+#[derive(Default)]
+struct LimitState {
+    start: Option<f64>,
+    reached: bool,
+    bytes: u64,
+    gop_start: u64,
+    largest_gop: u64,
+    finished_tracks: u32,
+    on_complete: Option<CompletionCallback>,
+}
+
+impl DurationLimit {
+    /// `on_complete` is called once both inputs have been finished after the limit was reached,
+    /// when the muxer can be completed. `max_duration` may be infinite to only limit the size.
+    pub fn new(max_duration: f64, on_complete: impl FnOnce() + Send + 'static) -> Self {
+        Self {
+            max_duration,
+            max_bytes: None,
+            state: Mutex::new(LimitState {
+                on_complete: Some(Box::new(on_complete)),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Also limits the file to `max_bytes`, including the container overhead.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn is_reached(&self) -> bool {
+        self.lock().reached
+    }
+
+    /// `keyframe` is set for video keyframes, where GOPs start.
+    fn admits(&self, timestamp: f64, size: usize, keyframe: bool) -> bool {
+        let mut state = self.lock();
+        if state.reached {
+            return false;
+        }
+        let start = *state.start.get_or_insert(timestamp);
+        if timestamp - start >= self.max_duration {
+            state.reached = true;
+        }
+        if let (Some(max_bytes), false) = (self.max_bytes, state.reached) {
+            let budget = max_bytes.saturating_sub(CONTAINER_RESERVE);
+            if keyframe && state.bytes > 0 {
+                state.largest_gop = state.largest_gop.max(state.bytes - state.gop_start);
+                state.gop_start = state.bytes;
+                if state.bytes + state.largest_gop > budget {
+                    state.reached = true;
+                }
+            }
+            let bytes = state.bytes + size as u64 + SAMPLE_OVERHEAD;
+            if bytes > budget {
+                state.reached = true;
+            } else if !state.reached {
+                state.bytes = bytes;
+            }
+        }
+        !state.reached
+    }
+
+    fn track_finished(&self) {
+        let on_complete = {
+            let mut state = self.lock();
+            state.finished_tracks += 1;
+            match state.finished_tracks >= 2 && state.reached {
+                true => state.on_complete.take(),
+                false => None,
+            }
+        };
+        if let Some(on_complete) = on_complete {
+            on_complete();
+        }
+    }
+
+    // the state stays consistent even if a holder panicked
+    fn lock(&self) -> std::sync::MutexGuard<'_, LimitState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Muxer input that stops accepting samples once a [`DurationLimit`] is set and reached. The
+/// track is then finished right away, and later pushes and `finish` succeed without effect.
+pub struct LimitedMuxerInput<I> {
+    inner: Option<I>,
+    limit: Option<Arc<DurationLimit>>,
+    is_video: bool,
+}
+
+impl<I> LimitedMuxerInput<I> {
+    pub fn video(inner: I) -> Self {
+        Self {
+            inner: Some(inner),
+            limit: None,
+            is_video: true,
+        }
+    }
+
+    pub fn audio(inner: I) -> Self {
+        Self {
+            inner: Some(inner),
+            limit: None,
+            is_video: false,
+        }
+    }
+
+    /// The wrapped input, until the track is finished.
+    pub fn inner_mut(&mut self) -> Option<&mut I> {
+        self.inner.as_mut()
+    }
+
+    /// Set the same limit on both inputs before pushing samples.
+    pub fn set_limit(&mut self, limit: Arc<DurationLimit>) {
+        self.limit = Some(limit);
+    }
+}
+
+impl<I: MuxerInput<Data: EncodedData>> LimitedMuxerInput<I> {
+    async fn finish_inner(&mut self) -> Result<()> {
+        let Some(inner) = self.inner.take() else {
+            return Ok(());
+        };
+        let result = inner.finish().await;
+        if let Some(limit) = &self.limit {
+            limit.track_finished();
+        }
+        result
+    }
+}
+
+impl<I: MuxerInput<Data: EncodedData>> MuxerInput for LimitedMuxerInput<I> {
+    type Data = I::Data;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        let admitted = match &self.limit {
+            Some(limit) => limit.admits(
+                data.timestamp(),
+                data.size(),
+                self.is_video && data.kind() == UniencSampleKind::Key,
+            ),
+            None => true,
+        };
+        if !admitted {
+            return self.finish_inner().await;
+        }
+        match &mut self.inner {
+            Some(inner) => inner.push(data).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn finish(mut self) -> Result<()> {
+        self.finish_inner().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_starts_at_first_sample_and_latches() {
+        let limit = DurationLimit::new(10.0, || {});
+        assert!(limit.admits(5.0, 0, false));
+        assert!(limit.admits(14.9, 0, false));
+        assert!(!limit.is_reached());
+        assert!(!limit.admits(15.0, 0, false));
+        // samples of the other track that are still in flight are not admitted either
+        assert!(!limit.admits(12.0, 0, false));
+        assert!(limit.is_reached());
+    }
+
+    #[test]
+    fn completion_fires_once_both_tracks_finish_after_the_limit() {
+        let completed = Arc::new(Mutex::new(0));
+        let counter = completed.clone();
+        let limit = DurationLimit::new(1.0, move || *counter.lock().unwrap() += 1);
+
+        limit.admits(0.0, 0, false);
+        limit.track_finished();
+        assert!(!limit.admits(2.0, 0, false));
+        assert_eq!(*completed.lock().unwrap(), 0);
+        limit.track_finished();
+        limit.track_finished();
+        assert_eq!(*completed.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn size_limit_ends_before_a_gop_that_would_cross_it() {
+        let gop = 10 * (1000 + SAMPLE_OVERHEAD);
+        let limit = DurationLimit::new(f64::INFINITY, || {})
+            .with_max_bytes(CONTAINER_RESERVE + 2 * gop + gop / 2);
+        let mut admitted = 0;
+        for frame in 0..100 {
+            if !limit.admits(frame as f64 / 30.0, 1000, frame % 10 == 0) {
+                break;
+            }
+            admitted += 1;
+        }
+        // a third GOP of the same size would not fit
+        assert_eq!(admitted, 20);
+        assert!(limit.is_reached());
+    }
+
+    #[test]
+    fn size_limit_cuts_a_gop_larger_than_the_previous_ones() {
+        let limit =
+            DurationLimit::new(f64::INFINITY, || {}).with_max_bytes(CONTAINER_RESERVE + 1000);
+        assert!(limit.admits(0.0, 100, true));
+        assert!(limit.admits(0.1, 100, false));
+        assert!(limit.admits(0.2, 500, true));
+        assert!(!limit.admits(0.3, 500, false));
+        assert!(!limit.admits(0.4, 0, false));
+    }
+
+    #[test]
+    fn fits_video_bitrate_to_the_size() {
+        let bitrate = fit_video_bitrate(25_000_000, 60.0, 128_000).unwrap();
+        assert!((3_000_000..3_200_000).contains(&bitrate));
+        assert_eq!(fit_video_bitrate(100_000, 60.0, 128_000), None);
+        assert_eq!(fit_video_bitrate(25_000_000, 0.0, 128_000), None);
+    }
+}
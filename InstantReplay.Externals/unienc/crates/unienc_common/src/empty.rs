@@ -0,0 +1,119 @@
+//! Recordings stopped before any video frame reached the muxer. Platform muxers handle such a
+//! file differently, some waiting for samples that never come, so completing one skips the
+//! platform muxer and fails with [`CommonError::EmptyRecording`] on every platform instead.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{CommonError, CompletionHandle, MuxerInput, Result};
+
+/// Wraps the video input and completion handle of a muxer writing to `path` so that completing it
+/// without a video sample pushed removes the file and fails with [`CommonError::EmptyRecording`].
+pub fn detect_empty<V, C>(
+    video: V,
+    completion_handle: C,
+    path: &Path,
+) -> (SampledMuxerInput<V>, EmptyCheckCompletionHandle<C>) {
+    let sampled = Arc::new(AtomicBool::new(false));
+    (
+        SampledMuxerInput {
+            inner: video,
+            sampled: sampled.clone(),
+        },
+        EmptyCheckCompletionHandle {
+            inner: completion_handle,
+            path: path.to_owned(),
+            sampled,
+        },
+    )
+}
+
+/// Muxer input created by [`detect_empty`], recording whether a sample was pushed.
+pub struct SampledMuxerInput<I> {
+    inner: I,
+    sampled: Arc<AtomicBool>,
+}
+
+impl<I: MuxerInput> MuxerInput for SampledMuxerInput<I> {
+    type Data = I::Data;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        self.inner.push(data).await?;
+        self.sampled.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    async fn finish(self) -> Result<()> {
+        self.inner.finish().await
+    }
+}
+
+/// Completion handle created by [`detect_empty`].
+pub struct EmptyCheckCompletionHandle<C> {
+    inner: C,
+    path: PathBuf,
+    sampled: Arc<AtomicBool>,
+}
+
+impl<C: CompletionHandle + Send> CompletionHandle for EmptyCheckCompletionHandle<C> {
+    async fn finish(self) -> Result<()> {
+        if self.sampled.load(Ordering::Acquire) {
+            return self.inner.finish().await;
+        }
+        // the platform muxer is dropped unfinished, leaving at most a partial file behind
+        drop(self.inner);
+        let _ = std::fs::remove_file(&self.path);
+        Err(CommonError::EmptyRecording)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::sync::atomic::AtomicUsize;
+
+    struct Input;
+
+    impl MuxerInput for Input {
+        type Data = ();
+
+        async fn push(&mut self, _data: ()) -> Result<()> {
+            Ok(())
+        }
+
+        async fn finish(self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Handle(Arc<AtomicUsize>);
+
+    impl CompletionHandle for Handle {
+        async fn finish(self) -> Result<()> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn completing_without_a_video_sample_fails_without_finishing_the_muxer() {
+        let path = std::env::temp_dir().join("unienc_empty_recording_test.mp4");
+        std::fs::write(&path, b"partial").unwrap();
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        let (video, handle) = detect_empty(Input, Handle(finished.clone()), &path);
+        block_on(video.finish()).unwrap();
+        let result = block_on(handle.finish());
+        assert!(matches!(result, Err(CommonError::EmptyRecording)));
+        assert_eq!(finished.load(Ordering::Relaxed), 0);
+        assert!(!path.exists());
+
+        let (mut video, handle) = detect_empty(Input, Handle(finished.clone()), &path);
+        block_on(video.push(())).unwrap();
+        block_on(video.finish()).unwrap();
+        block_on(handle.finish()).unwrap();
+        assert_eq!(finished.load(Ordering::Relaxed), 1);
+    }
+}
@@ -25,6 +25,8 @@ pub enum ErrorCategory {
     InvalidInput = 9,
     /// Platform-specific error
     Platform = 10,
+    /// Recording stopped before any video frame was written
+    EmptyRecording = 11,
 }
 
 /// Trait for errors that can provide an error category
@@ -42,6 +44,117 @@ pub enum CommonError {
     #[error("Blit not supported in this encoding system")]
     BlitNotSupported,
 
+    #[error("Decoding not supported in this encoding system")]
+    DecodeNotSupported,
+
+    #[error("Still image capture requires a blit source")]
+    StillImageRequiresBlitSource,
+
+    #[error("Invalid timestamp: {0}")]
+    InvalidTimestamp(f64),
+
+    #[error("Invalid replay data: {0}")]
+    InvalidReplayData(String),
+
+    #[error("Failed to access replay data file: {0}")]
+    ReplayDataIo(String),
+
+    #[error("Invalid session capture: {0}")]
+    InvalidSessionCapture(String),
+
+    #[error("Failed to access session capture file: {0}")]
+    SessionCaptureIo(String),
+
+    #[error("Failed to access session directory: {0}")]
+    SessionDirIo(String),
+
+    #[error("Failed to write storyboard: {0}")]
+    StoryboardIo(String),
+
+    #[error("Failed to write PNG sequence: {0}")]
+    PngSequenceIo(String),
+
+    #[error("Failed to write JPEG spool: {0}")]
+    JpegSpoolIo(String),
+
+    #[error("JPEG spool queue is full")]
+    JpegSpoolFull,
+
+    #[error("Slot {slot} is outside the {count} slots of the JPEG spool")]
+    JpegSpoolSlotOutOfRange { slot: u32, count: u32 },
+
+    #[error("Encoded frame of {size} bytes does not fit JPEG spool slots of {slot_size} bytes")]
+    JpegSpoolFrameTooLarge { size: usize, slot_size: u32 },
+
+    #[error("JPEG spool is being exported")]
+    JpegSpoolExporting,
+
+    #[error("JPEG spool is not being exported")]
+    JpegSpoolNotExporting,
+
+    #[error("Alpha channel not supported in this encoding system")]
+    AlphaNotSupported,
+
+    #[error("Video codec {0:?} not supported in this encoding system")]
+    CodecNotSupported(crate::VideoCodec),
+
+    #[error("Frame buffer of {actual} bytes is smaller than the {expected} bytes of the frame")]
+    FrameBufferTooSmall { expected: usize, actual: usize },
+
+    #[error("Frame stride of {stride} bytes is shorter than its rows of {row} bytes")]
+    FrameStrideTooSmall { stride: u32, row: u32 },
+
+    #[error("Stereo capture requires a texture array with a slice per eye")]
+    StereoSourceNotTextureArray,
+
+    #[error("Equirectangular capture requires a cubemap source")]
+    EquirectSourceNotCubemap,
+
+    #[error("Failed to write spherical metadata: {0}")]
+    SphericalMetadata(String),
+
+    #[error("Failed to write timecode track: {0}")]
+    Timecode(String),
+
+    #[error("Failed to write captions: {0}")]
+    Captions(String),
+
+    #[error("Failed to write clean aperture: {0}")]
+    CleanAperture(String),
+
+    #[error("Failed to move the movie header to the front: {0}")]
+    Faststart(String),
+
+    #[error("Cancelled")]
+    Cancelled,
+
+    #[error("Did not complete within {0} seconds")]
+    DeadlineExceeded(f64),
+
+    #[error("Recording stopped before any video frame was written")]
+    EmptyRecording,
+
+    #[error("No keyframe buffered")]
+    NoKeyframeBuffered,
+
+    #[error("No external encoding backend registered")]
+    ExternalBackendNotRegistered,
+
+    #[error("Invalid output path {0}")]
+    InvalidOutputPath(String),
+
+    #[error("Live streaming not supported in this encoding system")]
+    StreamingNotSupported,
+
+    #[error("Invalid stream URL {0}")]
+    InvalidStreamUrl(String),
+
+    #[error("Live stream disconnected: {0}")]
+    StreamDisconnected(String),
+
+    #[error("Profiler markers were already created under the previous naming")]
+    ProfilerMarkersCreated,
+
     /// Error with explicit category from platform code
     #[error("{message}")]
     Categorized {
@@ -58,6 +171,43 @@ impl CategorizedError for CommonError {
         match self {
             CommonError::BufferPoolExceeded => ErrorCategory::ResourceAllocation,
             CommonError::BlitNotSupported => ErrorCategory::Configuration,
+            CommonError::DecodeNotSupported => ErrorCategory::Configuration,
+            CommonError::StillImageRequiresBlitSource => ErrorCategory::InvalidInput,
+            CommonError::InvalidTimestamp(_) => ErrorCategory::InvalidInput,
+            CommonError::InvalidReplayData(_) => ErrorCategory::InvalidInput,
+            CommonError::ReplayDataIo(_) => ErrorCategory::General,
+            CommonError::InvalidSessionCapture(_) => ErrorCategory::InvalidInput,
+            CommonError::SessionCaptureIo(_) => ErrorCategory::General,
+            CommonError::SessionDirIo(_) => ErrorCategory::General,
+            CommonError::StoryboardIo(_) => ErrorCategory::General,
+            CommonError::PngSequenceIo(_) => ErrorCategory::General,
+            CommonError::JpegSpoolIo(_) => ErrorCategory::General,
+            CommonError::JpegSpoolFull => ErrorCategory::ResourceAllocation,
+            CommonError::JpegSpoolSlotOutOfRange { .. } => ErrorCategory::InvalidInput,
+            CommonError::JpegSpoolFrameTooLarge { .. } => ErrorCategory::Configuration,
+            CommonError::JpegSpoolExporting => ErrorCategory::InvalidInput,
+            CommonError::JpegSpoolNotExporting => ErrorCategory::InvalidInput,
+            CommonError::AlphaNotSupported => ErrorCategory::Configuration,
+            CommonError::CodecNotSupported(_) => ErrorCategory::Configuration,
+            CommonError::FrameBufferTooSmall { .. } => ErrorCategory::InvalidInput,
+            CommonError::FrameStrideTooSmall { .. } => ErrorCategory::InvalidInput,
+            CommonError::StereoSourceNotTextureArray => ErrorCategory::InvalidInput,
+            CommonError::EquirectSourceNotCubemap => ErrorCategory::InvalidInput,
+            CommonError::SphericalMetadata(_) => ErrorCategory::Muxing,
+            CommonError::Timecode(_) => ErrorCategory::Muxing,
+            CommonError::Captions(_) => ErrorCategory::Muxing,
+            CommonError::CleanAperture(_) => ErrorCategory::Muxing,
+            CommonError::Faststart(_) => ErrorCategory::Muxing,
+            CommonError::Cancelled => ErrorCategory::General,
+            CommonError::DeadlineExceeded(_) => ErrorCategory::Timeout,
+            CommonError::EmptyRecording => ErrorCategory::EmptyRecording,
+            CommonError::NoKeyframeBuffered => ErrorCategory::General,
+            CommonError::ExternalBackendNotRegistered => ErrorCategory::Configuration,
+            CommonError::InvalidOutputPath(_) => ErrorCategory::InvalidInput,
+            CommonError::StreamingNotSupported => ErrorCategory::Configuration,
+            CommonError::InvalidStreamUrl(_) => ErrorCategory::InvalidInput,
+            CommonError::StreamDisconnected(_) => ErrorCategory::Communication,
+            CommonError::ProfilerMarkersCreated => ErrorCategory::Configuration,
             CommonError::Categorized { category, .. } => *category,
             CommonError::Other(_) => ErrorCategory::General,
         }
@@ -0,0 +1,161 @@
+//! Faststart layout of MP4 files, with the `moov` box ahead of the media data so that players
+//! streaming a file can start before downloading all of it. Platform muxers write `moov` last, so
+//! the finished file is rewritten with the box moved to the front.
+
+use std::path::{Path, PathBuf};
+
+use crate::mp4::{BoxResult, children, grow, map_chunk_offsets, read_u32};
+use crate::{CommonError, CompletionHandle, Result};
+
+/// Completion handle that moves `moov` to the front of the file once the wrapped muxer has written
+/// it, if requested.
+pub struct FaststartCompletionHandle<C> {
+    inner: C,
+    path: PathBuf,
+    faststart: bool,
+}
+
+impl<C> FaststartCompletionHandle<C> {
+    pub fn new(inner: C, path: &Path) -> Self {
+        Self {
+            inner,
+            path: path.to_owned(),
+            faststart: false,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    pub fn set_faststart(&mut self) {
+        self.faststart = true;
+    }
+}
+
+impl<C: CompletionHandle + Send> CompletionHandle for FaststartCompletionHandle<C> {
+    async fn finish(self) -> Result<()> {
+        self.inner.finish().await?;
+        if !self.faststart {
+            return Ok(());
+        }
+        let file = std::fs::read(&self.path).map_err(|e| CommonError::Faststart(e.to_string()))?;
+        let file = move_moov_to_front(&file)?;
+        std::fs::write(&self.path, file).map_err(|e| CommonError::Faststart(e.to_string()))
+    }
+}
+
+/// Returns a copy of an MP4 or QuickTime file with `moov` moved before the first `mdat`, and the
+/// chunk offsets of the media data it moves past shifted along. Files already laid out that way
+/// are returned as is.
+pub fn move_moov_to_front(file: &[u8]) -> Result<Vec<u8>> {
+    relayout(file)
+        .map_err(|reason| CommonError::Faststart(format!("unsupported MP4 file: {reason}")))
+}
+
+fn relayout(file: &[u8]) -> BoxResult<Vec<u8>> {
+    let boxes = children(file, 0..file.len())?;
+    let moov = boxes
+        .iter()
+        .find(|b| &b.kind == b"moov")
+        .ok_or_else(|| "missing moov box".to_string())?;
+    let Some(mdat) = boxes.iter().find(|b| &b.kind == b"mdat") else {
+        return Ok(file.to_vec());
+    };
+    if moov.start < mdat.start {
+        return Ok(file.to_vec());
+    }
+
+    let mut out = file.to_vec();
+    // a moov sized to the end of the file is no longer last once moved
+    if read_u32(file, moov.start)? == 0 {
+        grow(&mut out, moov, 0)?;
+    }
+    // only the data between the first mdat and moov moves
+    let (range, delta) = (mdat.start..moov.start, moov.end - moov.start);
+    map_chunk_offsets(&mut out, moov, |offset| match range.contains(&offset) {
+        true => offset + delta,
+        false => offset,
+    })?;
+    let moov_bytes = out.drain(moov.start..moov.end).collect::<Vec<_>>();
+    out.splice(mdat.start..mdat.start, moov_bytes);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4::{container, find, full_box};
+
+    fn plain(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut b = ((8 + payload.len()) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(kind);
+        b.extend_from_slice(payload);
+        b
+    }
+
+    fn track(chunk_offset: u32) -> Vec<u8> {
+        let mut stco = 1u32.to_be_bytes().to_vec();
+        stco.extend_from_slice(&chunk_offset.to_be_bytes());
+        container(
+            b"trak",
+            &[container(
+                b"mdia",
+                &[container(
+                    b"minf",
+                    &[container(b"stbl", &[full_box(b"stco", &stco)])],
+                )],
+            )],
+        )
+    }
+
+    fn chunk_offset(file: &[u8], trak_index: usize) -> u32 {
+        let moov = find(file, 0..file.len(), b"moov").unwrap();
+        let trak = children(file, moov.content()).unwrap()[trak_index];
+        let mdia = find(file, trak.content(), b"mdia").unwrap();
+        let minf = find(file, mdia.content(), b"minf").unwrap();
+        let stbl = find(file, minf.content(), b"stbl").unwrap();
+        let stco = find(file, stbl.content(), b"stco").unwrap();
+        read_u32(file, stco.content().start + 8).unwrap()
+    }
+
+    /// `ftyp`, an `mdat` of two chunks, then `moov`.
+    fn file() -> Vec<u8> {
+        let ftyp = plain(b"ftyp", b"isom");
+        let mdat_content = (ftyp.len() + 8) as u32;
+        let mut file = ftyp;
+        file.extend(plain(b"mdat", &[1, 2, 3, 4]));
+        file.extend(container(
+            b"moov",
+            &[track(mdat_content), track(mdat_content + 2)],
+        ));
+        file
+    }
+
+    #[test]
+    fn moves_moov_before_the_media_data() {
+        let out = move_moov_to_front(&file()).unwrap();
+        let boxes = children(&out, 0..out.len()).unwrap();
+        let kinds = boxes.iter().map(|b| &b.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [b"ftyp", b"moov", b"mdat"]);
+
+        let mdat = boxes[2];
+        assert_eq!(&out[mdat.content()], &[1, 2, 3, 4]);
+        assert_eq!(chunk_offset(&out, 0), mdat.content().start as u32);
+        assert_eq!(chunk_offset(&out, 1), mdat.content().start as u32 + 2);
+    }
+
+    #[test]
+    fn keeps_files_already_laid_out() {
+        let out = move_moov_to_front(&file()).unwrap();
+        assert_eq!(move_moov_to_front(&out).unwrap(), out);
+    }
+
+    #[test]
+    fn rejects_files_without_moov() {
+        assert!(matches!(
+            move_moov_to_front(&plain(b"mdat", &[0; 4])),
+            Err(CommonError::Faststart(_))
+        ));
+    }
+}
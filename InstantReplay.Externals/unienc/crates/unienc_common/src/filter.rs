@@ -0,0 +1,344 @@
+//! Filters applied to video frames before they are encoded, such as blurring regions that show
+//! player names. Frames in memory are processed on the CPU, while blitted frames can only be
+//! filtered by a hook that works on their native texture.
+
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use crate::buffer::SharedBuffer;
+use crate::{
+    CommonError, EncoderInput, GraphicsEventIssuer, Result, VideoFrame, VideoFrameBgra32,
+    VideoSample,
+};
+
+/// Called on the render thread with the native texture of a blitted frame, its width and its
+/// height, before the encoder samples it. It may draw into the texture.
+pub type TextureHook = Arc<dyn Fn(*mut c_void, u32, u32) + Send + Sync>;
+
+pub trait VideoFilter: Send + 'static {
+    /// Processes a frame in memory. The returned frame may have another size, which the encoder
+    /// must accept.
+    fn process(&mut self, frame: VideoFrameBgra32) -> Result<VideoFrameBgra32>;
+
+    /// Hook processing blitted frames on the GPU, or `None` if the filter only works on frames in
+    /// memory.
+    fn texture_hook(&self) -> Option<TextureHook> {
+        None
+    }
+}
+
+/// Filters applied one after another, in the order they were added.
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn VideoFilter>>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, filter: impl VideoFilter) {
+        self.filters.push(Box::new(filter));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+impl VideoFilter for FilterChain {
+    fn process(&mut self, frame: VideoFrameBgra32) -> Result<VideoFrameBgra32> {
+        self.filters
+            .iter_mut()
+            .try_fold(frame, |frame, filter| filter.process(frame))
+    }
+
+    /// Present only if every filter has one.
+    fn texture_hook(&self) -> Option<TextureHook> {
+        let hooks = self
+            .filters
+            .iter()
+            .map(|filter| filter.texture_hook())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Arc::new(move |texture, width, height| {
+            for hook in &hooks {
+                hook(texture, width, height);
+            }
+        }))
+    }
+}
+
+/// Rectangle in pixels of the frame, counted from its first row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Blurs regions of frames in memory with a box blur, so text in them becomes unreadable.
+#[derive(Debug, Clone)]
+pub struct RegionBlur {
+    regions: Vec<Region>,
+    radius: u32,
+}
+
+impl RegionBlur {
+    /// Each pixel of `regions` becomes the average of the pixels up to `radius` away from it
+    /// inside the region.
+    pub fn new(regions: Vec<Region>, radius: u32) -> Self {
+        Self {
+            regions,
+            radius: radius.max(1),
+        }
+    }
+
+    fn blur(&self, pixels: &mut [u8], stride: usize, region: Region) {
+        let (x0, y0) = (region.x as usize, region.y as usize);
+        let (width, height) = (region.width as usize, region.height as usize);
+        let radius = self.radius as usize;
+        let mut line = Vec::new();
+
+        // horizontal pass over each row, then vertical pass over each column
+        for y in y0..y0 + height {
+            line.clear();
+            line.extend((x0..x0 + width).map(|x| y * stride + x * 4));
+            box_blur_line(pixels, &line, radius);
+        }
+        for x in x0..x0 + width {
+            line.clear();
+            line.extend((y0..y0 + height).map(|y| y * stride + x * 4));
+            box_blur_line(pixels, &line, radius);
+        }
+    }
+}
+
+/// Replaces the pixels at `offsets` with the average of their neighbours up to `radius` away
+/// along the line, clamped to its ends.
+fn box_blur_line(pixels: &mut [u8], offsets: &[usize], radius: usize) {
+    let source = offsets
+        .iter()
+        .map(|&at| [pixels[at], pixels[at + 1], pixels[at + 2], pixels[at + 3]])
+        .collect::<Vec<_>>();
+    let mut sum = [0u32; 4];
+    let mut count = 0u32;
+    let add = |sum: &mut [u32; 4], pixel: &[u8; 4], sign: bool| {
+        for (sum, &value) in sum.iter_mut().zip(pixel) {
+            match sign {
+                true => *sum += value as u32,
+                false => *sum -= value as u32,
+            }
+        }
+    };
+    for pixel in source.iter().take(radius) {
+        add(&mut sum, pixel, true);
+        count += 1;
+    }
+    for (i, &at) in offsets.iter().enumerate() {
+        if let Some(pixel) = source.get(i + radius) {
+            add(&mut sum, pixel, true);
+            count += 1;
+        }
+        if i > radius {
+            add(&mut sum, &source[i - radius - 1], false);
+            count -= 1;
+        }
+        for (out, sum) in pixels[at..at + 4].iter_mut().zip(sum) {
+            *out = ((sum + count / 2) / count) as u8;
+        }
+    }
+}
+
+impl VideoFilter for RegionBlur {
+    fn process(&mut self, frame: VideoFrameBgra32) -> Result<VideoFrameBgra32> {
+        let mut pixels = frame.buffer.data().to_vec();
+        let stride = frame.stride as usize;
+        for region in &self.regions {
+            // clipped to the frame
+            let x = region.x.min(frame.width);
+            let y = region.y.min(frame.height);
+            let region = Region {
+                x,
+                y,
+                width: region.width.min(frame.width - x),
+                height: region.height.min(frame.height - y),
+            };
+            self.blur(&mut pixels, stride, region);
+        }
+        Ok(VideoFrameBgra32 {
+            buffer: SharedBuffer::new_unmanaged(pixels),
+            ..frame
+        })
+    }
+}
+
+/// Issues the graphics events of a blitted frame with the texture hook run first.
+struct HookedEventIssuer {
+    inner: Box<dyn GraphicsEventIssuer + Send>,
+    hook: TextureHook,
+    width: u32,
+    height: u32,
+}
+
+impl GraphicsEventIssuer for HookedEventIssuer {
+    fn issue_graphics_event(
+        &self,
+        callback: Box<dyn FnOnce(*mut c_void) + Send + 'static>,
+        event_id: i32,
+        texture_token: usize,
+    ) {
+        let (hook, width, height) = (self.hook.clone(), self.width, self.height);
+        self.inner.issue_graphics_event(
+            Box::new(move |texture| {
+                hook(texture, width, height);
+                callback(texture);
+            }),
+            event_id,
+            texture_token,
+        );
+    }
+}
+
+/// Video encoder input that passes frames through a filter, once one is set. Blitted frames are
+/// rejected if the filter has no texture hook, rather than encoded unfiltered.
+pub struct FilteredVideoInput<I> {
+    inner: I,
+    filter: FilterChain,
+}
+
+impl<I> FilteredVideoInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            filter: FilterChain::new(),
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Applies `filter` to frames pushed afterwards, after the filters added before it.
+    pub fn add_filter(&mut self, filter: impl VideoFilter) {
+        self.filter.push(filter);
+    }
+
+    pub fn clear_filters(&mut self) {
+        self.filter = FilterChain::new();
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for FilteredVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        if self.filter.is_empty() {
+            return self.inner.push(data).await;
+        }
+        let frame = match data.frame {
+            VideoFrame::Bgra32(frame) => VideoFrame::Bgra32(self.filter.process(frame)?),
+            VideoFrame::BlitSource {
+                texture_token,
+                width,
+                height,
+                graphics_format,
+                options,
+                event_issuer,
+                _phantom,
+            } => {
+                let hook = self
+                    .filter
+                    .texture_hook()
+                    .ok_or(CommonError::BlitNotSupported)?;
+                VideoFrame::BlitSource {
+                    texture_token,
+                    width,
+                    height,
+                    graphics_format,
+                    options,
+                    event_issuer: Box::new(HookedEventIssuer {
+                        inner: event_issuer,
+                        hook,
+                        width,
+                        height,
+                    }),
+                    _phantom,
+                }
+            }
+        };
+        self.inner
+            .push(VideoSample {
+                frame,
+                timestamp: data.timestamp,
+            })
+            .await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Invert;
+
+    impl VideoFilter for Invert {
+        fn process(&mut self, frame: VideoFrameBgra32) -> Result<VideoFrameBgra32> {
+            let pixels = frame.buffer.data().iter().map(|v| 255 - v).collect();
+            Ok(VideoFrameBgra32 {
+                buffer: SharedBuffer::new_unmanaged(pixels),
+                ..frame
+            })
+        }
+    }
+
+    fn frame(pixels: &[u8], width: u32, height: u32) -> VideoFrameBgra32 {
+        let data = pixels.iter().flat_map(|&v| [v; 4]).collect();
+        VideoFrameBgra32::packed(SharedBuffer::new_unmanaged(data), width, height)
+    }
+
+    fn gray(frame: &VideoFrameBgra32) -> Vec<u8> {
+        frame.buffer.data().chunks(4).map(|p| p[0]).collect()
+    }
+
+    #[test]
+    fn blurs_only_inside_regions() {
+        let source = frame(&[0, 90, 0, 9, 0, 0, 0, 9], 4, 2);
+        let region = Region {
+            x: 0,
+            y: 0,
+            width: 3,
+            height: 1,
+        };
+        let mut blur = RegionBlur::new(vec![region], 1);
+        let out = blur.process(source).unwrap();
+        assert_eq!(gray(&out), [45, 30, 45, 9, 0, 0, 0, 9]);
+    }
+
+    #[test]
+    fn chains_filters_in_order() {
+        let mut chain = FilterChain::new();
+        chain.push(RegionBlur::new(
+            vec![Region {
+                x: 0,
+                y: 0,
+                width: 2,
+                height: 1,
+            }],
+            1,
+        ));
+        chain.push(Invert);
+        assert!(chain.texture_hook().is_none());
+        let out = chain.process(frame(&[0, 100], 2, 1)).unwrap();
+        assert_eq!(gray(&out), [205, 205]);
+    }
+}
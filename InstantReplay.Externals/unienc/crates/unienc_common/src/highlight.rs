@@ -0,0 +1,209 @@
+//! Detection of moments worth keeping from the audio path, so hosts can pick which part of the
+//! buffer to export without analyzing the recording themselves.
+
+use crate::{AudioEncoderOptions, AudioSample};
+
+/// Length of the frames loudness is measured over, in seconds.
+const FRAME_DURATION: f64 = 0.02;
+/// Time constant of the background loudness estimate, in seconds.
+const BACKGROUND_TIME_CONSTANT: f64 = 5.0;
+/// Loudness below this is never considered interesting.
+const MIN_LEVEL_DB: f64 = -45.0;
+/// A frame this much louder than the background is reported as a spike.
+const SPIKE_THRESHOLD_DB: f64 = 12.0;
+/// Frames this much louder than the background count towards voice activity.
+const VOICE_THRESHOLD_DB: f64 = 6.0;
+/// Zero-crossing rates per sample that are typical for voiced speech.
+const VOICE_ZERO_CROSSING_RATE: std::ops::RangeInclusive<f64> = 0.01..=0.25;
+/// How long voice activity must last before it is reported, in seconds.
+const VOICE_MIN_DURATION: f64 = 0.3;
+/// Minimum interval between hints of the same kind, in seconds.
+const HOLD_OFF: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightKind {
+    /// Sudden increase in loudness, such as an explosion or a shout.
+    LoudnessSpike,
+    /// Onset of sustained speech-like sound.
+    VoiceActivity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HighlightHint {
+    pub kind: HighlightKind,
+    /// Time the moment started, in seconds.
+    pub timestamp: f64,
+    /// How far the moment stood out from the background, in dB.
+    pub score: f32,
+}
+
+/// Looks for loudness spikes and voice activity in interleaved PCM, relative to a slowly adapting
+/// estimate of the background loudness.
+pub struct HighlightDetector {
+    sample_rate: f64,
+    channels: usize,
+    frames_per_window: usize,
+    background_db: Option<f64>,
+    // accumulation of the current frame, mixed down to mono
+    sum_of_squares: f64,
+    zero_crossings: usize,
+    previous: f64,
+    count: usize,
+    frame_start: u64,
+    voice_start: Option<f64>,
+    last_spike: Option<f64>,
+    last_voice: Option<f64>,
+}
+
+impl HighlightDetector {
+    pub fn new<A: AudioEncoderOptions>(options: &A) -> Self {
+        let sample_rate = options.sample_rate().max(1) as f64;
+        Self {
+            sample_rate,
+            channels: options.channels().max(1) as usize,
+            frames_per_window: ((sample_rate * FRAME_DURATION) as usize).max(1),
+            background_db: None,
+            sum_of_squares: 0.0,
+            zero_crossings: 0,
+            previous: 0.0,
+            count: 0,
+            frame_start: 0,
+            voice_start: None,
+            last_spike: None,
+            last_voice: None,
+        }
+    }
+
+    /// Feeds a sample and returns the hints it completed.
+    pub fn push(&mut self, sample: &AudioSample) -> Vec<HighlightHint> {
+        let mut hints = Vec::new();
+        for (i, frame) in sample.data.chunks(self.channels).enumerate() {
+            if self.count == 0 {
+                self.frame_start = sample.timestamp_in_samples + i as u64;
+            }
+            let value = frame.iter().map(|&v| v as f64).sum::<f64>() / frame.len() as f64 / 32768.0;
+            self.sum_of_squares += value * value;
+            if (value >= 0.0) != (self.previous >= 0.0) {
+                self.zero_crossings += 1;
+            }
+            self.previous = value;
+            self.count += 1;
+
+            if self.count == self.frames_per_window {
+                hints.extend(self.finish_frame());
+            }
+        }
+        hints
+    }
+
+    fn finish_frame(&mut self) -> Option<HighlightHint> {
+        let rms = (self.sum_of_squares / self.count as f64).sqrt();
+        let level_db = 20.0 * rms.max(1e-9).log10();
+        let zero_crossing_rate = self.zero_crossings as f64 / self.count as f64;
+        let timestamp = self.frame_start as f64 / self.sample_rate;
+        let duration = self.count as f64 / self.sample_rate;
+
+        self.sum_of_squares = 0.0;
+        self.zero_crossings = 0;
+        self.count = 0;
+
+        let Some(background_db) = self.background_db else {
+            self.background_db = Some(level_db);
+            return None;
+        };
+        let excess_db = level_db - background_db;
+        let alpha = duration / BACKGROUND_TIME_CONSTANT;
+        self.background_db = Some(background_db + (level_db - background_db) * alpha);
+
+        if level_db < MIN_LEVEL_DB {
+            self.voice_start = None;
+            return None;
+        }
+
+        if excess_db >= SPIKE_THRESHOLD_DB && Self::held_off(self.last_spike, timestamp) {
+            self.last_spike = Some(timestamp);
+            return Some(HighlightHint {
+                kind: HighlightKind::LoudnessSpike,
+                timestamp,
+                score: excess_db as f32,
+            });
+        }
+
+        if excess_db >= VOICE_THRESHOLD_DB && VOICE_ZERO_CROSSING_RATE.contains(&zero_crossing_rate)
+        {
+            let start = *self.voice_start.get_or_insert(timestamp);
+            if timestamp + duration - start >= VOICE_MIN_DURATION
+                && Self::held_off(self.last_voice, start)
+            {
+                self.last_voice = Some(start);
+                return Some(HighlightHint {
+                    kind: HighlightKind::VoiceActivity,
+                    timestamp: start,
+                    score: excess_db as f32,
+                });
+            }
+        } else {
+            self.voice_start = None;
+        }
+
+        None
+    }
+
+    fn held_off(last: Option<f64>, timestamp: f64) -> bool {
+        last.is_none_or(|last| timestamp - last >= HOLD_OFF)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    struct Options;
+
+    impl AudioEncoderOptions for Options {
+        fn sample_rate(&self) -> u32 {
+            8000
+        }
+        fn channels(&self) -> u32 {
+            1
+        }
+        fn bitrate(&self) -> u32 {
+            0
+        }
+    }
+
+    fn tone(frequency: f64, amplitude: f64, seconds: f64) -> Vec<i16> {
+        (0..(8000.0 * seconds) as usize)
+            .map(|i| {
+                let phase = 2.0 * std::f64::consts::PI * frequency * i as f64 / 8000.0;
+                (phase.sin() * amplitude * 32767.0) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detector_reports_spikes_and_sustained_voice_once() {
+        let mut detector = HighlightDetector::new(&Options);
+        let mut hints = Vec::new();
+        let mut push = |data: Vec<i16>, timestamp_in_samples: u64| {
+            hints.extend(detector.push(&AudioSample {
+                data,
+                timestamp_in_samples,
+            }));
+        };
+
+        // quiet background, then a short loud burst at 2s
+        push(tone(200.0, 0.01, 2.0), 0);
+        push(tone(200.0, 0.9, 0.1), 16000);
+        // back to the background, then voice-like sound at 5s
+        push(tone(200.0, 0.01, 2.9), 16800);
+        push(tone(200.0, 0.025, 0.5), 40000);
+
+        assert_eq!(hints.len(), 2, "{hints:?}");
+        assert_eq!(hints[0].kind, HighlightKind::LoudnessSpike);
+        assert!((hints[0].timestamp - 2.0).abs() < 1e-9);
+        assert_eq!(hints[1].kind, HighlightKind::VoiceActivity);
+        assert!((hints[1].timestamp - 5.0).abs() < 1e-9);
+    }
+}
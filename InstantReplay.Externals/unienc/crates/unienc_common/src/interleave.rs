@@ -0,0 +1,309 @@
+//! Interleaving of the samples reaching a muxer. Muxers write samples in the order they are pushed,
+//! so a track pushed well ahead of the other leaves long runs of one track in the file, which
+//! players reading it progressively stall on.
+
+use std::future::poll_fn;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Poll, Waker};
+
+use crate::{EncodedData, MuxerInput, Result};
+
+const VIDEO: usize = 0;
+const AUDIO: usize = 1;
+
+/// Wraps the video and audio inputs of a muxer so that a push waits while its sample is more than
+/// `max_skew` seconds ahead of the other track, until that track catches up or is finished. A
+/// track that has not pushed anything yet holds nothing back, so one that starts late or not at
+/// all does not stall the other.
+///
+/// Both inputs must be pushed concurrently, and an input whose track ends early must be finished
+/// or dropped; a track that stalls stalls the other.
+pub fn interleave<V, A>(
+    video: V,
+    audio: A,
+    max_skew: f64,
+) -> (InterleavedMuxerInput<V>, InterleavedMuxerInput<A>) {
+    let shared = Arc::new(Interleaver {
+        max_skew,
+        tracks: Default::default(),
+    });
+    (
+        InterleavedMuxerInput {
+            inner: Some(video),
+            shared: shared.clone(),
+            track: VIDEO,
+        },
+        InterleavedMuxerInput {
+            inner: Some(audio),
+            shared,
+            track: AUDIO,
+        },
+    )
+}
+
+struct Interleaver {
+    max_skew: f64,
+    tracks: Mutex<[TrackState; 2]>,
+}
+
+#[derive(Default)]
+struct TrackState {
+    /// Latest timestamp pushed or waiting to be pushed.
+    frontier: Option<f64>,
+    finished: bool,
+    idle: bool,
+    waker: Option<Waker>,
+}
+
+impl Interleaver {
+    async fn wait_turn(&self, track: usize, timestamp: f64) {
+        {
+            let mut tracks = self.lock();
+            let state = &mut tracks[track];
+            state.frontier = Some(state.frontier.map_or(timestamp, |f| f.max(timestamp)));
+            // the other track may be waiting for this one to get this far
+            if let Some(waker) = tracks[1 - track].waker.take() {
+                waker.wake();
+            }
+        }
+        poll_fn(|cx| {
+            let mut tracks = self.lock();
+            let other = &tracks[1 - track];
+            // of two waiting tracks, the one further behind always proceeds
+            let ready = other.finished
+                || other.idle
+                || other
+                    .frontier
+                    .is_none_or(|frontier| timestamp - frontier <= self.max_skew);
+            if ready {
+                return Poll::Ready(());
+            }
+            tracks[track].waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn set_idle(&self, track: usize, idle: bool) {
+        let mut tracks = self.lock();
+        tracks[track].idle = idle;
+        tracks[track].frontier = None;
+        if let Some(waker) = tracks[1 - track].waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn finish(&self, track: usize) {
+        let mut tracks = self.lock();
+        tracks[track].finished = true;
+        if let Some(waker) = tracks[1 - track].waker.take() {
+            waker.wake();
+        }
+    }
+
+    // the state stays consistent even if a holder panicked
+    fn lock(&self) -> MutexGuard<'_, [TrackState; 2]> {
+        self.tracks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Muxer input created by [`interleave`]. The track counts as finished once the input is finished
+/// or dropped.
+pub struct InterleavedMuxerInput<I> {
+    inner: Option<I>,
+    shared: Arc<Interleaver>,
+    track: usize,
+}
+
+impl<I> InterleavedMuxerInput<I> {
+    /// While `idle`, the track holds nothing back, such as audio left out of a time-lapse. Where
+    /// it got to is forgotten either way, as its next sample may be retimed.
+    pub fn set_idle(&mut self, idle: bool) {
+        self.shared.set_idle(self.track, idle);
+    }
+}
+
+impl<I: MuxerInput<Data: EncodedData>> MuxerInput for InterleavedMuxerInput<I> {
+    type Data = I::Data;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        self.shared.wait_turn(self.track, data.timestamp()).await;
+        self.inner.as_mut().unwrap().push(data).await
+    }
+
+    async fn finish(mut self) -> Result<()> {
+        let inner = self.inner.take().unwrap();
+        // the other track need not wait for the muxer to finish this one
+        self.shared.finish(self.track);
+        inner.finish().await
+    }
+}
+
+impl<I> Drop for InterleavedMuxerInput<I> {
+    fn drop(&mut self) {
+        self.shared.finish(self.track);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UniencSampleKind;
+    use crate::test_util::block_on;
+    use bincode::{Decode, Encode};
+    use std::pin::{Pin, pin};
+    use std::task::Context;
+
+    #[derive(Encode, Decode)]
+    struct Sample(f64);
+
+    impl EncodedData for Sample {
+        fn timestamp(&self) -> f64 {
+            self.0
+        }
+
+        fn set_timestamp(&mut self, timestamp: f64) {
+            self.0 = timestamp;
+        }
+
+        fn kind(&self) -> UniencSampleKind {
+            UniencSampleKind::Key
+        }
+
+        fn size(&self) -> usize {
+            0
+        }
+    }
+
+    /// Track and timestamp of every sample pushed to the muxer, in order.
+    type Pushed = Arc<Mutex<Vec<(&'static str, f64)>>>;
+
+    #[derive(Clone)]
+    struct Input {
+        name: &'static str,
+        pushed: Pushed,
+    }
+
+    impl MuxerInput for Input {
+        type Data = Sample;
+
+        async fn push(&mut self, data: Sample) -> Result<()> {
+            self.pushed.lock().unwrap().push((self.name, data.0));
+            Ok(())
+        }
+
+        async fn finish(self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn inputs(
+        max_skew: f64,
+    ) -> (
+        InterleavedMuxerInput<Input>,
+        InterleavedMuxerInput<Input>,
+        Pushed,
+    ) {
+        let pushed = Pushed::default();
+        let input = |name| Input {
+            name,
+            pushed: pushed.clone(),
+        };
+        let (video, audio) = interleave(input("video"), input("audio"), max_skew);
+        (video, audio, pushed)
+    }
+
+    /// Polls `future` once and returns whether it completed.
+    fn poll_once(future: Pin<&mut impl Future<Output = Result<()>>>) -> bool {
+        match future.poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(result) => {
+                result.unwrap();
+                true
+            }
+            Poll::Pending => false,
+        }
+    }
+
+    #[test]
+    fn pushes_wait_for_the_other_track_within_the_skew() {
+        let (mut video, mut audio, pushed) = inputs(0.5);
+
+        block_on(video.push(Sample(0.0))).unwrap();
+        block_on(audio.push(Sample(0.0))).unwrap();
+        {
+            let mut ahead = pin!(video.push(Sample(2.0)));
+            assert!(!poll_once(ahead.as_mut()));
+            block_on(audio.push(Sample(1.0))).unwrap();
+            assert!(!poll_once(ahead.as_mut()));
+            block_on(audio.push(Sample(1.6))).unwrap();
+            assert!(poll_once(ahead));
+        }
+
+        block_on(audio.finish()).unwrap();
+        block_on(video.push(Sample(10.0))).unwrap();
+
+        assert_eq!(
+            *pushed.lock().unwrap(),
+            [
+                ("video", 0.0),
+                ("audio", 0.0),
+                ("audio", 1.0),
+                ("audio", 1.6),
+                ("video", 2.0),
+                ("video", 10.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_track_behind_proceeds_when_both_wait() {
+        let (mut video, mut audio, pushed) = inputs(0.0);
+        block_on(video.push(Sample(0.0))).unwrap();
+        block_on(audio.push(Sample(0.0))).unwrap();
+
+        let mut audio_push = pin!(audio.push(Sample(1.5)));
+        assert!(!poll_once(audio_push.as_mut()));
+        block_on(video.push(Sample(1.0))).unwrap();
+        assert!(!poll_once(audio_push.as_mut()));
+
+        // dropping the video input releases the audio
+        drop(video);
+        assert!(poll_once(audio_push));
+        assert_eq!(
+            *pushed.lock().unwrap(),
+            [
+                ("video", 0.0),
+                ("audio", 0.0),
+                ("video", 1.0),
+                ("audio", 1.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn a_track_that_never_pushes_stalls_nothing() {
+        let (mut video, mut audio, pushed) = inputs(0.5);
+
+        for timestamp in [0.0, 1.0, 5.0] {
+            assert!(poll_once(pin!(video.push(Sample(timestamp)))));
+        }
+
+        // once the audio starts, the video waits for it again
+        block_on(audio.push(Sample(4.0))).unwrap();
+        let mut ahead = pin!(video.push(Sample(6.0)));
+        assert!(!poll_once(ahead.as_mut()));
+        drop(audio);
+        assert!(poll_once(ahead));
+
+        assert_eq!(
+            *pushed.lock().unwrap(),
+            [
+                ("video", 0.0),
+                ("video", 1.0),
+                ("video", 5.0),
+                ("audio", 4.0),
+                ("video", 6.0)
+            ]
+        );
+    }
+}
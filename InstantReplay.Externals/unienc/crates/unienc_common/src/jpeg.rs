@@ -0,0 +1,356 @@
+//! Baseline JPEG encoder for storyboard sheets and spooled frames: YCbCr with the example tables of
+//! ITU-T T.81 Annex K, which is plenty for thumbnails and intermediate frames and keeps them
+//! encodable on every backend.
+
+/// Chroma resolution of an encoded image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JpegSubsampling {
+    /// Full resolution chroma.
+    #[default]
+    Yuv444,
+    /// Chroma halved in both directions, about half the size of 4:4:4 for game frames.
+    Yuv420,
+}
+
+impl JpegSubsampling {
+    /// Luma blocks per chroma block in each direction.
+    fn factor(self) -> u32 {
+        match self {
+            JpegSubsampling::Yuv444 => 1,
+            JpegSubsampling::Yuv420 => 2,
+        }
+    }
+}
+
+/// Natural order index of each coefficient in zigzag order.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+#[rustfmt::skip]
+const LUMA_QUANT: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61,
+    12, 12, 14, 19, 26, 58, 60, 55,
+    14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62,
+    18, 22, 37, 56, 68, 109, 103, 77,
+    24, 35, 55, 64, 81, 104, 113, 92,
+    49, 64, 78, 87, 103, 121, 120, 101,
+    72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+#[rustfmt::skip]
+const CHROMA_QUANT: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99,
+    18, 21, 26, 66, 99, 99, 99, 99,
+    24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+const DC_LUMA_BITS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const DC_CHROMA_BITS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+const AC_LUMA_BITS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+#[rustfmt::skip]
+const AC_LUMA_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+    0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5,
+    0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+const AC_CHROMA_BITS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+#[rustfmt::skip]
+const AC_CHROMA_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0,
+    0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+/// (code, length) of every symbol of a table.
+struct HuffmanTable([(u16, u8); 256]);
+
+impl HuffmanTable {
+    fn new(bits: &[u8; 16], values: &[u8]) -> Self {
+        let mut codes = [(0, 0); 256];
+        let mut code = 0u16;
+        let mut values = values.iter();
+        for (length, count) in bits.iter().enumerate() {
+            for _ in 0..*count {
+                if let Some(value) = values.next() {
+                    codes[*value as usize] = (code, length as u8 + 1);
+                }
+                code += 1;
+            }
+            code <<= 1;
+        }
+        Self(codes)
+    }
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, bits: u16, length: u8) {
+        self.buffer = (self.buffer << length) | (bits as u32 & ((1 << length) - 1));
+        self.count += length as u32;
+        while self.count >= 8 {
+            self.count -= 8;
+            let byte = (self.buffer >> self.count) as u8;
+            self.bytes.push(byte);
+            // byte stuffing
+            if byte == 0xff {
+                self.bytes.push(0);
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.count > 0 {
+            // pad with ones
+            let padding = 8 - self.count as u8;
+            self.write((1 << padding) - 1, padding);
+        }
+    }
+}
+
+/// Quant table scaled like libjpeg, with `quality` between 0.0 and 1.0.
+fn scale_quant(base: &[u16; 64], quality: f32) -> [u16; 64] {
+    let quality = (quality.clamp(0.0, 1.0) * 100.0).round().max(1.0) as u32;
+    let scale = match quality < 50 {
+        true => 5000 / quality,
+        false => 200 - quality * 2,
+    };
+    base.map(|q| ((q as u32 * scale + 50) / 100).clamp(1, 255) as u16)
+}
+
+fn segment(out: &mut Vec<u8>, marker: u8, payload: &[u8]) {
+    out.extend_from_slice(&[0xff, marker]);
+    out.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// Encodes a `width` x `height` image as a JPEG file, reading the RGB value of each pixel with
+/// `pixel(x, y)`.
+pub(crate) fn encode(
+    width: u32,
+    height: u32,
+    quality: f32,
+    subsampling: JpegSubsampling,
+    pixel: impl Fn(u32, u32) -> [u8; 3],
+) -> Vec<u8> {
+    let luma_quant = scale_quant(&LUMA_QUANT, quality);
+    let chroma_quant = scale_quant(&CHROMA_QUANT, quality);
+
+    let mut out = vec![0xff, 0xd8];
+    segment(&mut out, 0xe0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+
+    let mut dqt = Vec::with_capacity(130);
+    for (id, table) in [&luma_quant, &chroma_quant].into_iter().enumerate() {
+        dqt.push(id as u8);
+        dqt.extend(ZIGZAG.iter().map(|i| table[*i] as u8));
+    }
+    segment(&mut out, 0xdb, &dqt);
+
+    let factor = subsampling.factor();
+    let luma_sampling = (factor << 4 | factor) as u8;
+    let (w, h) = ((width as u16).to_be_bytes(), (height as u16).to_be_bytes());
+    // precision and size, then the id, sampling factors and quant table of each component
+    let mut frame_header = vec![8, h[0], h[1], w[0], w[1], 3];
+    frame_header.extend_from_slice(&[1, luma_sampling, 0, 2, 0x11, 1, 3, 0x11, 1]);
+    segment(&mut out, 0xc0, &frame_header);
+
+    let mut dht = Vec::new();
+    for (class_id, bits, values) in [
+        (0x00, &DC_LUMA_BITS, &DC_VALUES[..]),
+        (0x10, &AC_LUMA_BITS, &AC_LUMA_VALUES[..]),
+        (0x01, &DC_CHROMA_BITS, &DC_VALUES[..]),
+        (0x11, &AC_CHROMA_BITS, &AC_CHROMA_VALUES[..]),
+    ] {
+        dht.push(class_id);
+        dht.extend_from_slice(bits);
+        dht.extend_from_slice(values);
+    }
+    segment(&mut out, 0xc4, &dht);
+    segment(&mut out, 0xda, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+
+    let tables = [
+        (
+            HuffmanTable::new(&DC_LUMA_BITS, &DC_VALUES),
+            HuffmanTable::new(&AC_LUMA_BITS, &AC_LUMA_VALUES),
+            &luma_quant,
+        ),
+        (
+            HuffmanTable::new(&DC_CHROMA_BITS, &DC_VALUES),
+            HuffmanTable::new(&AC_CHROMA_BITS, &AC_CHROMA_VALUES),
+            &chroma_quant,
+        ),
+    ];
+    let cosines = cosine_table();
+    let mut writer = BitWriter {
+        bytes: out,
+        buffer: 0,
+        count: 0,
+    };
+    let mut previous_dc = [0i32; 3];
+    let mut encode_component = |component: usize, block: &[f32; 64]| {
+        let (dc_table, ac_table, quant) = &tables[(component > 0) as usize];
+        let coefficients = quantize(&forward_dct(block, &cosines), quant);
+        let dc = coefficients[0];
+        encode_block(
+            &mut writer,
+            &coefficients,
+            dc - previous_dc[component],
+            dc_table,
+            ac_table,
+        );
+        previous_dc[component] = dc;
+    };
+
+    // a minimum coded unit holds `factor` x `factor` luma blocks and one block of each chroma
+    let mcu = 8 * factor;
+    let mut mcu_pixels = vec![[0f32; 3]; (mcu * mcu) as usize];
+    for mcu_y in (0..height).step_by(mcu as usize) {
+        for mcu_x in (0..width).step_by(mcu as usize) {
+            for (i, value) in mcu_pixels.iter_mut().enumerate() {
+                // edge units repeat the last row and column
+                let x = (mcu_x + i as u32 % mcu).min(width - 1);
+                let y = (mcu_y + i as u32 / mcu).min(height - 1);
+                let [r, g, b] = pixel(x, y).map(|v| v as f32);
+                *value = [
+                    0.299 * r + 0.587 * g + 0.114 * b - 128.0,
+                    -0.168736 * r - 0.331264 * g + 0.5 * b,
+                    0.5 * r - 0.418688 * g - 0.081312 * b,
+                ];
+            }
+            let at = |x: u32, y: u32| mcu_pixels[(y * mcu + x) as usize];
+            for block in 0..factor * factor {
+                let (block_x, block_y) = (block % factor * 8, block / factor * 8);
+                let luma =
+                    std::array::from_fn(|i| at(block_x + i as u32 % 8, block_y + i as u32 / 8)[0]);
+                encode_component(0, &luma);
+            }
+            for component in 1..3 {
+                // box filter over the luma pixels covered by each chroma sample
+                let chroma = std::array::from_fn(|i| {
+                    let (x, y) = (i as u32 % 8 * factor, i as u32 / 8 * factor);
+                    let sum: f32 = (0..factor * factor)
+                        .map(|j| at(x + j % factor, y + j / factor)[component])
+                        .sum();
+                    sum / (factor * factor) as f32
+                });
+                encode_component(component, &chroma);
+            }
+        }
+    }
+    writer.flush();
+
+    let mut out = writer.bytes;
+    out.extend_from_slice(&[0xff, 0xd9]);
+    out
+}
+
+fn cosine_table() -> [[f32; 8]; 8] {
+    let mut table = [[0f32; 8]; 8];
+    for (u, row) in table.iter_mut().enumerate() {
+        let scale = if u == 0 { 0.5f32.sqrt() } else { 1.0 };
+        for (x, value) in row.iter_mut().enumerate() {
+            let angle = (2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0;
+            *value = scale * angle.cos() / 2.0;
+        }
+    }
+    table
+}
+
+fn forward_dct(block: &[f32; 64], cosines: &[[f32; 8]; 8]) -> [f32; 64] {
+    let mut rows = [0f32; 64];
+    for y in 0..8 {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..8).map(|x| cosines[u][x] * block[y * 8 + x]).sum();
+        }
+    }
+    let mut output = [0f32; 64];
+    for u in 0..8 {
+        for v in 0..8 {
+            output[v * 8 + u] = (0..8).map(|y| cosines[v][y] * rows[y * 8 + u]).sum();
+        }
+    }
+    output
+}
+
+/// Coefficients in zigzag order.
+fn quantize(coefficients: &[f32; 64], quant: &[u16; 64]) -> [i32; 64] {
+    ZIGZAG.map(|i| (coefficients[i] / quant[i] as f32).round() as i32)
+}
+
+/// Magnitude category and amplitude bits of a coefficient.
+fn magnitude(value: i32) -> (u8, u16) {
+    let size = (32 - value.unsigned_abs().leading_zeros()) as u8;
+    let bits = match value < 0 {
+        true => (value - 1) as u16,
+        false => value as u16,
+    };
+    (size, bits)
+}
+
+fn encode_block(
+    writer: &mut BitWriter,
+    coefficients: &[i32; 64],
+    dc_difference: i32,
+    dc_table: &HuffmanTable,
+    ac_table: &HuffmanTable,
+) {
+    let (size, bits) = magnitude(dc_difference);
+    let (code, length) = dc_table.0[size as usize];
+    writer.write(code, length);
+    writer.write(bits, size);
+
+    let mut run = 0;
+    for coefficient in &coefficients[1..] {
+        if *coefficient == 0 {
+            run += 1;
+            continue;
+        }
+        while run > 15 {
+            let (code, length) = ac_table.0[0xf0];
+            writer.write(code, length);
+            run -= 16;
+        }
+        let (size, bits) = magnitude(*coefficient);
+        let (code, length) = ac_table.0[(run << 4 | size) as usize];
+        writer.write(code, length);
+        writer.write(bits, size);
+        run = 0;
+    }
+    if run > 0 {
+        // end of block
+        let (code, length) = ac_table.0[0x00];
+        writer.write(code, length);
+    }
+}
@@ -0,0 +1,720 @@
+//! JPEG spool for the ring-buffer recording path, which keeps recent frames on disk while
+//! recording and transcodes them when a replay is saved. Frames are encoded on a writer thread,
+//! throttled to a byte rate so that the game keeps most of the disk bandwidth.
+//!
+//! Frames are kept in fixed-size slots of a single memory-mapped file rather than a file each,
+//! which spares the filesystem, and on Windows the antivirus, a create and close per frame. The
+//! file starts with a header and an index of the slots, all little-endian:
+//!
+//! | Offset | Size | Field |
+//! | --- | --- | --- |
+//! | 0 | 8 | `UEJSPOOL` |
+//! | 8 | 4 | Version, 1 |
+//! | 12 | 4 | Slot count |
+//! | 16 | 4 | Slot size in bytes |
+//! | 20 | 4 | Offset of the first slot |
+//! | 32 | 24 per slot | Sequence number (`u64`, 0 if empty), timestamp (`f64`), size (`u32`) |
+//!
+//! Slots follow each other from the offset of the first slot. The sequence number of a slot is
+//! cleared while it is written, so a frame whose number is set is complete. The file is truncated
+//! when a spool is created, which discards frames left by a previous session, and removed when the
+//! spool is dropped.
+//!
+//! While the frames are exported, the spool stops taking new ones and a checkpoint is kept next to
+//! the file, in the file named after it with `.export` appended. If the session ends before the
+//! export is finished, both files are left on disk and [`InterruptedExport::find`] recovers the
+//! frames on the next launch. The checkpoint holds `UEJSEXPT`, the progress (`f64`), then the UTF-8
+//! output path and the caller's metadata, each after its length (`u32`).
+
+mod mapped_file;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::jpeg::{self, JpegSubsampling};
+use crate::{CommonError, Result, VideoFrameBgra32};
+use mapped_file::MappedFile;
+
+const MAGIC: &[u8; 8] = b"UEJSPOOL";
+const CHECKPOINT_MAGIC: &[u8; 8] = b"UEJSEXPT";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 32;
+const ENTRY_SIZE: usize = 24;
+// slots start on a page boundary
+const SLOT_ALIGNMENT: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JpegSpoolOptions {
+    /// Ranges from 0.0 (smallest) to 1.0 (best).
+    pub quality: f32,
+    pub subsampling: JpegSubsampling,
+    /// Bytes written per second at most, or 0 for no limit.
+    pub max_bytes_per_second: u64,
+    /// Frames waiting for the writer thread at most. Pushes beyond it are rejected.
+    pub queue_capacity: usize,
+    pub slot_count: u32,
+    /// Largest encoded frame a slot holds. Frames encoding larger are not written.
+    pub slot_size: u32,
+}
+
+/// Frame read back from a spool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpooledFrame {
+    pub slot: u32,
+    pub timestamp: f64,
+    /// Encoded JPEG.
+    pub data: Vec<u8>,
+}
+
+type OnWritten = Box<dyn FnOnce(Result<()>) + Send>;
+
+struct Job {
+    slot: u32,
+    timestamp: f64,
+    frame: VideoFrameBgra32,
+    on_written: OnWritten,
+}
+
+pub struct JpegSpool {
+    file: Arc<Mutex<SpoolFile>>,
+    slot_count: u32,
+    tx: Option<mpsc::SyncSender<Job>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl JpegSpool {
+    /// Creates or truncates the spool file at `path`, discarding an export interrupted there; call
+    /// [`InterruptedExport::find`] first to recover it.
+    pub fn new(path: PathBuf, options: JpegSpoolOptions) -> Result<Self> {
+        Checkpoint::remove(&path)?;
+        let file = Arc::new(Mutex::new(SpoolFile::create(
+            path,
+            options.slot_count,
+            options.slot_size,
+        )?));
+
+        let (tx, rx) = mpsc::sync_channel::<Job>(options.queue_capacity.max(1));
+        let writer_file = file.clone();
+        let writer = std::thread::Builder::new()
+            .name("unienc-jpeg-spool".to_string())
+            .spawn(move || {
+                let mut throttle = Throttle::new(options.max_bytes_per_second);
+                for job in rx {
+                    let jpeg = encode(&job.frame, &options);
+                    let result = lock(&writer_file)
+                        .and_then(|mut file| file.write(job.slot, job.timestamp, &jpeg));
+                    if result.is_ok() {
+                        throttle.wait(jpeg.len() as u64);
+                    }
+                    (job.on_written)(result);
+                }
+            })
+            .map_err(|e| CommonError::JpegSpoolIo(e.to_string()))?;
+
+        Ok(Self {
+            file,
+            slot_count: options.slot_count,
+            tx: Some(tx),
+            writer: Some(writer),
+        })
+    }
+
+    /// Queues `frame` to be encoded into `slot`, replacing the frame there. `on_written` is called
+    /// on the writer thread once the slot is complete or failed to be written. Fails without
+    /// calling it if the queue is full.
+    pub fn push(
+        &self,
+        slot: u32,
+        timestamp: f64,
+        frame: VideoFrameBgra32,
+        on_written: impl FnOnce(Result<()>) + Send + 'static,
+    ) -> Result<()> {
+        if lock(&self.file)?.export.is_some() {
+            return Err(CommonError::JpegSpoolExporting);
+        }
+        if slot >= self.slot_count {
+            return Err(CommonError::JpegSpoolSlotOutOfRange {
+                slot,
+                count: self.slot_count,
+            });
+        }
+        let stride = frame.stride;
+        let row = frame.width * 4;
+        if stride < row {
+            return Err(CommonError::FrameStrideTooSmall { stride, row });
+        }
+        let (expected, actual) = (frame.min_buffer_len(), frame.buffer.len());
+        if actual < expected {
+            return Err(CommonError::FrameBufferTooSmall { expected, actual });
+        }
+
+        let job = Job {
+            slot,
+            timestamp,
+            frame,
+            on_written: Box::new(on_written),
+        };
+        match self.tx.as_ref().unwrap().try_send(job) {
+            Ok(()) => Ok(()),
+            Err(mpsc::TrySendError::Full(_)) => Err(CommonError::JpegSpoolFull),
+            Err(mpsc::TrySendError::Disconnected(_)) => Err(CommonError::JpegSpoolIo(
+                "Writer thread has stopped".to_string(),
+            )),
+        }
+    }
+
+    /// Copies the frames written so far, oldest first, for export. Frames still queued are not
+    /// included.
+    pub fn frames(&self) -> Result<Vec<SpooledFrame>> {
+        lock(&self.file)?.frames()
+    }
+
+    /// Starts exporting the frames written so far to `output_path`. Until
+    /// [`finish_export`](Self::finish_export), pushes are rejected and the spool file is left on
+    /// disk with a checkpoint if the spool is dropped or the process ends, for
+    /// [`InterruptedExport::find`]. `metadata` is kept in the checkpoint for the caller, such as the
+    /// settings to export with.
+    pub fn begin_export(&self, output_path: PathBuf, metadata: Vec<u8>) -> Result<()> {
+        let mut file = lock(&self.file)?;
+        if file.export.is_some() {
+            return Err(CommonError::JpegSpoolExporting);
+        }
+        file.map
+            .as_ref()
+            .unwrap()
+            .flush()
+            .map_err(|e| io_error(&file.path, e))?;
+        let checkpoint = Checkpoint {
+            output_path,
+            progress: 0.0,
+            metadata,
+        };
+        checkpoint.write(&file.path)?;
+        file.export = Some(checkpoint);
+        Ok(())
+    }
+
+    /// Records how far the export has got, such as the timestamp of the last frame muxed. Each call
+    /// rewrites the checkpoint, so call it every few seconds rather than per frame.
+    pub fn set_export_progress(&self, progress: f64) -> Result<()> {
+        let mut file = lock(&self.file)?;
+        let file = &mut *file;
+        let checkpoint = file
+            .export
+            .as_mut()
+            .ok_or(CommonError::JpegSpoolNotExporting)?;
+        checkpoint.progress = progress;
+        checkpoint.write(&file.path)
+    }
+
+    /// Ends the export, whether or not it succeeded, and removes its checkpoint. Pushes are
+    /// accepted again.
+    pub fn finish_export(&self) -> Result<()> {
+        let mut file = lock(&self.file)?;
+        if file.export.take().is_none() {
+            return Err(CommonError::JpegSpoolNotExporting);
+        }
+        Checkpoint::remove(&file.path)
+    }
+}
+
+/// Export that was in progress when a previous session ended, recovered from the spool file it was
+/// reading. The partially written output cannot be completed, so the export is run again from
+/// `frames`, over the output, before the export is [`discard`](Self::discard)ed.
+#[derive(Debug)]
+pub struct InterruptedExport {
+    pub output_path: PathBuf,
+    /// Last progress passed to [`JpegSpool::set_export_progress`], 0.0 if none was.
+    pub progress: f64,
+    pub metadata: Vec<u8>,
+    /// Source frames, oldest first.
+    pub frames: Vec<SpooledFrame>,
+}
+
+impl InterruptedExport {
+    /// Looks for an export interrupted while reading the spool file at `path`.
+    pub fn find(path: &Path) -> Result<Option<Self>> {
+        let Some(checkpoint) = Checkpoint::read(path)? else {
+            return Ok(None);
+        };
+        let file = std::fs::read(path).map_err(|e| io_error(path, e))?;
+        let frames = read_frames(&file).ok_or_else(|| malformed(path))?;
+        Ok(Some(Self {
+            output_path: checkpoint.output_path,
+            progress: checkpoint.progress,
+            metadata: checkpoint.metadata,
+            frames,
+        }))
+    }
+
+    /// Removes the checkpoint and the spool file at `path`.
+    pub fn discard(path: &Path) -> Result<()> {
+        Checkpoint::remove(path)?;
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(io_error(path, e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for JpegSpool {
+    /// Waits for the queued frames. The file is removed once the writer has let go of it.
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn lock(file: &Mutex<SpoolFile>) -> Result<std::sync::MutexGuard<'_, SpoolFile>> {
+    file.lock()
+        .map_err(|_| CommonError::JpegSpoolIo("Spool lock is poisoned".to_string()))
+}
+
+fn io_error(path: &Path, e: io::Error) -> CommonError {
+    CommonError::JpegSpoolIo(format!("{}: {e}", path.display()))
+}
+
+fn malformed(path: &Path) -> CommonError {
+    CommonError::JpegSpoolIo(format!("{}: File is malformed", path.display()))
+}
+
+fn encode(frame: &VideoFrameBgra32, options: &JpegSpoolOptions) -> Vec<u8> {
+    let data = frame.buffer.data();
+    let stride = frame.stride;
+    jpeg::encode(
+        frame.width,
+        frame.height,
+        options.quality,
+        options.subsampling,
+        |x, y| {
+            let i = (y * stride + x * 4) as usize;
+            // BGRA
+            [data[i + 2], data[i + 1], data[i]]
+        },
+    )
+}
+
+/// Mapped spool file, removed when dropped unless it is being exported.
+struct SpoolFile {
+    path: PathBuf,
+    map: Option<MappedFile>,
+    slot_size: u32,
+    slots_offset: usize,
+    sequence: u64,
+    export: Option<Checkpoint>,
+}
+
+impl SpoolFile {
+    fn create(path: PathBuf, slot_count: u32, slot_size: u32) -> Result<Self> {
+        let index_end = HEADER_SIZE + slot_count as usize * ENTRY_SIZE;
+        let slots_offset = index_end.next_multiple_of(SLOT_ALIGNMENT);
+        let len = (slot_count as u64)
+            .checked_mul(slot_size as u64)
+            .and_then(|slots| slots.checked_add(slots_offset as u64))
+            .and_then(|len| usize::try_from(len).ok())
+            .ok_or_else(|| CommonError::JpegSpoolIo("Spool file is too large".to_string()))?;
+
+        let mut map = MappedFile::create(&path, len).map_err(|e| io_error(&path, e))?;
+        let header = &mut map.as_mut_slice()[..HEADER_SIZE];
+        header[0..8].copy_from_slice(MAGIC);
+        header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        header[12..16].copy_from_slice(&slot_count.to_le_bytes());
+        header[16..20].copy_from_slice(&slot_size.to_le_bytes());
+        header[20..24].copy_from_slice(&(slots_offset as u32).to_le_bytes());
+
+        Ok(Self {
+            path,
+            map: Some(map),
+            slot_size,
+            slots_offset,
+            sequence: 0,
+            export: None,
+        })
+    }
+
+    fn entry_offset(slot: u32) -> usize {
+        HEADER_SIZE + slot as usize * ENTRY_SIZE
+    }
+
+    fn slot_offset(&self, slot: u32) -> usize {
+        self.slots_offset + slot as usize * self.slot_size as usize
+    }
+
+    fn write(&mut self, slot: u32, timestamp: f64, jpeg: &[u8]) -> Result<()> {
+        // frames queued before the export started
+        if self.export.is_some() {
+            return Err(CommonError::JpegSpoolExporting);
+        }
+        if jpeg.len() > self.slot_size as usize {
+            return Err(CommonError::JpegSpoolFrameTooLarge {
+                size: jpeg.len(),
+                slot_size: self.slot_size,
+            });
+        }
+        self.sequence += 1;
+        let sequence = self.sequence;
+        let entry = Self::entry_offset(slot);
+        let data = self.slot_offset(slot);
+
+        let map = self.map.as_mut().unwrap().as_mut_slice();
+        map[entry..entry + 8].fill(0);
+        map[data..data + jpeg.len()].copy_from_slice(jpeg);
+        map[entry + 8..entry + 16].copy_from_slice(&timestamp.to_le_bytes());
+        map[entry + 16..entry + 20].copy_from_slice(&(jpeg.len() as u32).to_le_bytes());
+        map[entry..entry + 8].copy_from_slice(&sequence.to_le_bytes());
+        Ok(())
+    }
+
+    fn frames(&self) -> Result<Vec<SpooledFrame>> {
+        read_frames(self.map.as_ref().unwrap().as_slice()).ok_or_else(|| malformed(&self.path))
+    }
+}
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        if self.export.is_some() {
+            return;
+        }
+        // files cannot be removed while mapped on Windows
+        drop(self.map.take());
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Reads the complete frames of a spool file, oldest first, or returns `None` if it is malformed.
+fn read_frames(file: &[u8]) -> Option<Vec<SpooledFrame>> {
+    let word = |offset: usize| {
+        Some(u32::from_le_bytes(
+            file.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    let double_word =
+        |offset: usize| -> Option<[u8; 8]> { file.get(offset..offset + 8)?.try_into().ok() };
+
+    if file.get(..8)? != MAGIC.as_slice() || word(8)? != VERSION {
+        return None;
+    }
+    let (slot_count, slot_size, slots_offset) = (word(12)?, word(16)? as usize, word(20)? as usize);
+
+    let mut frames = Vec::new();
+    for slot in 0..slot_count {
+        let entry = SpoolFile::entry_offset(slot);
+        let sequence = u64::from_le_bytes(double_word(entry)?);
+        if sequence == 0 {
+            continue;
+        }
+        let timestamp = f64::from_le_bytes(double_word(entry + 8)?);
+        let size = word(entry + 16)? as usize;
+        if size > slot_size {
+            return None;
+        }
+        let data = slots_offset + slot as usize * slot_size;
+        let frame = SpooledFrame {
+            slot,
+            timestamp,
+            data: file.get(data..data + size)?.to_vec(),
+        };
+        frames.push((sequence, frame));
+    }
+    frames.sort_by_key(|(sequence, _)| *sequence);
+    Some(frames.into_iter().map(|(_, frame)| frame).collect())
+}
+
+/// Export in progress, recorded next to the spool file.
+#[derive(Debug)]
+struct Checkpoint {
+    output_path: PathBuf,
+    progress: f64,
+    metadata: Vec<u8>,
+}
+
+impl Checkpoint {
+    fn path(spool: &Path) -> PathBuf {
+        let mut path = spool.as_os_str().to_owned();
+        path.push(".export");
+        path.into()
+    }
+
+    /// Replaces the checkpoint of `spool`, leaving the previous one if interrupted.
+    fn write(&self, spool: &Path) -> Result<()> {
+        let path = Self::path(spool);
+        let output_path = self.output_path.to_str().ok_or_else(|| {
+            CommonError::JpegSpoolIo(format!(
+                "{}: Output path is not UTF-8",
+                self.output_path.display()
+            ))
+        })?;
+
+        let mut bytes = CHECKPOINT_MAGIC.to_vec();
+        bytes.extend_from_slice(&self.progress.to_le_bytes());
+        for field in [output_path.as_bytes(), &self.metadata] {
+            bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(field);
+        }
+
+        let mut temp = path.clone().into_os_string();
+        temp.push(".tmp");
+        std::fs::write(&temp, bytes)
+            .and_then(|()| std::fs::rename(&temp, &path))
+            .map_err(|e| io_error(&path, e))
+    }
+
+    fn read(spool: &Path) -> Result<Option<Self>> {
+        let path = Self::path(spool);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(io_error(&path, e)),
+        };
+
+        Self::parse(&bytes)
+            .map(Some)
+            .ok_or_else(|| malformed(&path))
+    }
+
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+            let (field, rest) = bytes.split_at_checked(len)?;
+            *bytes = rest;
+            Some(field)
+        }
+        fn field<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+            let len = u32::from_le_bytes(take(bytes, 4)?.try_into().ok()?);
+            take(bytes, len as usize)
+        }
+
+        let mut bytes = bytes.strip_prefix(CHECKPOINT_MAGIC.as_slice())?;
+        let progress = f64::from_le_bytes(take(&mut bytes, 8)?.try_into().ok()?);
+        let output_path = std::str::from_utf8(field(&mut bytes)?).ok()?;
+        let metadata = field(&mut bytes)?;
+        Some(Self {
+            output_path: output_path.into(),
+            progress,
+            metadata: metadata.to_vec(),
+        })
+    }
+
+    fn remove(spool: &Path) -> Result<()> {
+        let path = Self::path(spool);
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(io_error(&path, e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Sleeps after writes so that the average rate since the writer became busy stays under the
+/// limit.
+struct Throttle {
+    max_bytes_per_second: u64,
+    start: Instant,
+    written: u64,
+}
+
+impl Throttle {
+    fn new(max_bytes_per_second: u64) -> Self {
+        Self {
+            max_bytes_per_second,
+            start: Instant::now(),
+            written: 0,
+        }
+    }
+
+    fn wait(&mut self, bytes: u64) {
+        if self.max_bytes_per_second == 0 {
+            return;
+        }
+        let elapsed = self.start.elapsed();
+        // idle time is not saved up for bursts
+        if elapsed > self.due() + Duration::from_secs(1) {
+            self.start = Instant::now();
+            self.written = 0;
+        }
+        self.written += bytes;
+        if let Some(remaining) = self.due().checked_sub(self.start.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+
+    /// Time the bytes written so far take at the limit.
+    fn due(&self) -> Duration {
+        Duration::from_secs_f64(self.written as f64 / self.max_bytes_per_second as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::SharedBuffer;
+
+    fn options() -> JpegSpoolOptions {
+        JpegSpoolOptions {
+            quality: 0.8,
+            subsampling: JpegSubsampling::Yuv420,
+            max_bytes_per_second: 0,
+            queue_capacity: 4,
+            slot_count: 2,
+            slot_size: 64 * 1024,
+        }
+    }
+
+    fn frame(width: u32, height: u32) -> VideoFrameBgra32 {
+        let mut bgra = vec![0u8; (width * height * 4) as usize];
+        for (i, pixel) in bgra.chunks_exact_mut(4).enumerate() {
+            pixel.copy_from_slice(&[i as u8, (i / 3) as u8, 200, 255]);
+        }
+        VideoFrameBgra32::packed(SharedBuffer::new_unmanaged(bgra), width, height)
+    }
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("jpeg-spool-{name}-{}.spool", std::process::id()))
+    }
+
+    fn push(spool: &JpegSpool, slot: u32, timestamp: f64, frame: VideoFrameBgra32) -> Result<()> {
+        let (tx, rx) = mpsc::channel();
+        spool.push(slot, timestamp, frame, move |result| {
+            tx.send(result).unwrap()
+        })?;
+        rx.recv().unwrap()
+    }
+
+    #[test]
+    fn frames_are_enumerated_oldest_first() {
+        let path = path("slots");
+        // left by a previous session
+        std::fs::write(&path, vec![0xff; 64 * 1024]).unwrap();
+
+        let spool = JpegSpool::new(path.clone(), options()).unwrap();
+        assert!(spool.frames().unwrap().is_empty());
+        for (slot, timestamp) in [(0, 0.0), (1, 0.5), (0, 1.0)] {
+            push(&spool, slot, timestamp, frame(37, 21)).unwrap();
+        }
+
+        let frames = spool.frames().unwrap();
+        let order: Vec<_> = frames.iter().map(|f| (f.slot, f.timestamp)).collect();
+        assert_eq!(order, [(1, 0.5), (0, 1.0)]);
+        let jpeg = &frames[0].data;
+        assert_eq!(&jpeg[..2], &[0xff, 0xd8]);
+        assert_eq!(&jpeg[jpeg.len() - 2..], &[0xff, 0xd9]);
+        // the frame header carries the size and 2x2 luma sampling
+        let sof = jpeg.windows(2).position(|w| w == [0xff, 0xc0]).unwrap();
+        assert_eq!(&jpeg[sof + 5..sof + 9], &[0, 21, 0, 37]);
+        assert_eq!(jpeg[sof + 11], 0x22);
+
+        let file = std::fs::read(&path).unwrap();
+        assert_eq!(&file[..8], MAGIC);
+        assert_eq!(file.len(), SLOT_ALIGNMENT + 2 * 64 * 1024);
+
+        drop(spool);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn frames_must_fit_their_slots() {
+        let options = JpegSpoolOptions {
+            slot_size: 256,
+            ..options()
+        };
+        let spool = JpegSpool::new(path("too-large"), options).unwrap();
+        assert!(matches!(
+            spool.push(2, 0.0, frame(8, 8), |_| {}),
+            Err(CommonError::JpegSpoolSlotOutOfRange { slot: 2, count: 2 })
+        ));
+        assert!(matches!(
+            push(&spool, 0, 0.0, frame(64, 64)),
+            Err(CommonError::JpegSpoolFrameTooLarge { slot_size: 256, .. })
+        ));
+        assert!(spool.frames().unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        let spool = JpegSpool::new(path("malformed"), options()).unwrap();
+        let mut short = frame(8, 8);
+        short.height = 9;
+        assert!(matches!(
+            spool.push(0, 0.0, short, |_| {}),
+            Err(CommonError::FrameBufferTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn interrupted_export_is_recovered() {
+        let path = path("interrupted");
+        let spool = JpegSpool::new(path.clone(), options()).unwrap();
+        for (slot, timestamp) in [(0, 0.0), (1, 0.5)] {
+            push(&spool, slot, timestamp, frame(16, 16)).unwrap();
+        }
+        let frames = spool.frames().unwrap();
+        spool
+            .begin_export("replay.mp4".into(), vec![1, 2, 3])
+            .unwrap();
+        spool.set_export_progress(0.25).unwrap();
+        drop(spool);
+
+        let export = InterruptedExport::find(&path).unwrap().unwrap();
+        assert_eq!(export.output_path, Path::new("replay.mp4"));
+        assert_eq!(export.progress, 0.25);
+        assert_eq!(export.metadata, [1, 2, 3]);
+        assert_eq!(export.frames, frames);
+
+        InterruptedExport::discard(&path).unwrap();
+        assert!(!path.exists());
+        assert!(InterruptedExport::find(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn finished_export_is_not_recovered() {
+        let path = path("finished");
+        let spool = JpegSpool::new(path.clone(), options()).unwrap();
+        push(&spool, 0, 0.0, frame(16, 16)).unwrap();
+        spool.begin_export("replay.mp4".into(), Vec::new()).unwrap();
+        assert!(matches!(
+            spool.begin_export("replay.mp4".into(), Vec::new()),
+            Err(CommonError::JpegSpoolExporting)
+        ));
+        assert!(matches!(
+            spool.push(1, 0.5, frame(16, 16), |_| {}),
+            Err(CommonError::JpegSpoolExporting)
+        ));
+        spool.finish_export().unwrap();
+        assert!(matches!(
+            spool.set_export_progress(1.0),
+            Err(CommonError::JpegSpoolNotExporting)
+        ));
+        push(&spool, 1, 0.5, frame(16, 16)).unwrap();
+        drop(spool);
+
+        assert!(!path.exists());
+        assert!(InterruptedExport::find(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn new_spool_discards_interrupted_export() {
+        let path = path("discarded");
+        let spool = JpegSpool::new(path.clone(), options()).unwrap();
+        spool.begin_export("replay.mp4".into(), Vec::new()).unwrap();
+        drop(spool);
+        assert!(InterruptedExport::find(&path).unwrap().is_some());
+
+        drop(JpegSpool::new(path.clone(), options()).unwrap());
+        assert!(InterruptedExport::find(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn throttle_spreads_writes_over_the_limit() {
+        let mut throttle = Throttle::new(10_000);
+        let start = Instant::now();
+        for _ in 0..3 {
+            throttle.wait(1_000);
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(300), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+    }
+}
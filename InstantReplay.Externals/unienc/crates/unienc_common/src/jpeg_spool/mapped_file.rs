@@ -0,0 +1,144 @@
+//! Read-write shared mapping of a file, whose writes reach the file without a write call per
+//! frame.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+// nothing is mapped on other platforms
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub(super) struct MappedFile {
+    ptr: *mut u8,
+    len: usize,
+    #[cfg(windows)]
+    mapping: windows::Win32::Foundation::HANDLE,
+    file: File,
+}
+
+// the mapping is only accessed through `&self` / `&mut self`
+unsafe impl Send for MappedFile {}
+
+impl MappedFile {
+    /// Creates or truncates the file at `path` to `len` zeroed bytes and maps it.
+    pub fn create(path: &Path, len: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(len as u64)?;
+        Self::map(file, len)
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+
+    /// Writes the mapped pages and the file back to disk.
+    pub fn flush(&self) -> io::Result<()> {
+        #[cfg(unix)]
+        if unsafe { libc::msync(self.ptr as *mut libc::c_void, self.len, libc::MS_SYNC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        #[cfg(windows)]
+        unsafe {
+            windows::Win32::System::Memory::FlushViewOfFile(
+                self.ptr as *const std::ffi::c_void,
+                self.len,
+            )
+        }
+        .map_err(io::Error::other)?;
+        self.file.sync_all()
+    }
+
+    #[cfg(unix)]
+    fn map(file: File, len: usize) -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+            file,
+        })
+    }
+
+    #[cfg(windows)]
+    fn map(file: File, len: usize) -> io::Result<Self> {
+        use std::os::windows::io::AsRawHandle;
+        use windows::Win32::Foundation::{CloseHandle, HANDLE};
+        use windows::Win32::System::Memory::{
+            CreateFileMappingW, FILE_MAP_ALL_ACCESS, MapViewOfFile, PAGE_READWRITE,
+        };
+        use windows::core::PCWSTR;
+
+        let mapping = unsafe {
+            CreateFileMappingW(
+                HANDLE(file.as_raw_handle()),
+                None,
+                PAGE_READWRITE,
+                (len as u64 >> 32) as u32,
+                len as u32,
+                PCWSTR::null(),
+            )
+        }
+        .map_err(io::Error::other)?;
+        let view = unsafe { MapViewOfFile(mapping, FILE_MAP_ALL_ACCESS, 0, 0, len) };
+        if view.Value.is_null() {
+            let err = io::Error::last_os_error();
+            let _ = unsafe { CloseHandle(mapping) };
+            return Err(err);
+        }
+        Ok(Self {
+            ptr: view.Value as *mut u8,
+            len,
+            mapping,
+            file,
+        })
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn map(_file: File, _len: usize) -> io::Result<Self> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+impl Drop for MappedFile {
+    #[cfg(unix)]
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+
+    #[cfg(windows)]
+    fn drop(&mut self) {
+        use windows::Win32::Foundation::CloseHandle;
+        use windows::Win32::System::Memory::{MEMORY_MAPPED_VIEW_ADDRESS, UnmapViewOfFile};
+
+        unsafe {
+            let _ = UnmapViewOfFile(MEMORY_MAPPED_VIEW_ADDRESS {
+                Value: self.ptr as *mut std::ffi::c_void,
+            });
+            let _ = CloseHandle(self.mapping);
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn drop(&mut self) {}
+}
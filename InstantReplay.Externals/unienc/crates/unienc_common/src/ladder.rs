@@ -0,0 +1,277 @@
+//! Renditions of one clip at several sizes for adaptive streaming, encoded from frames decoded
+//! once. Every rendition is encoded from the same frames with the same timestamps, but each
+//! encoder places its own keyframes, so they are not guaranteed to line up across renditions.
+
+use std::future::{Future, poll_fn};
+use std::task::Poll;
+
+use crate::buffer::SharedBuffer;
+use crate::{
+    CommonError, EncodedData, EncoderInput, MuxerInput, Result, ResultExt, VideoFrame,
+    VideoFrameBgra32, VideoSample,
+};
+
+/// Short sides of the renditions added below the source by default.
+pub const DEFAULT_LADDER: [u32; 3] = [1080, 720, 480];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rendition {
+    pub width: u32,
+    pub height: u32,
+    /// In bits per second.
+    pub bitrate: u32,
+}
+
+impl Rendition {
+    /// The source at `width` by `height` and `bitrate`, followed by a rendition for each of
+    /// `short_sides` smaller than the source, largest first. Dimensions are even as H.264
+    /// requires.
+    pub fn ladder(width: u32, height: u32, bitrate: u32, short_sides: &[u32]) -> Vec<Self> {
+        let source_short_side = width.min(height);
+        let mut short_sides = short_sides
+            .iter()
+            .copied()
+            .filter(|&side| side > 0 && side < source_short_side)
+            .collect::<Vec<_>>();
+        short_sides.sort_unstable_by(|a, b| b.cmp(a));
+        short_sides.dedup();
+
+        let source = Self {
+            width,
+            height,
+            bitrate,
+        };
+        let area = |w: u32, h: u32| w as f64 * h as f64;
+        let scaled = short_sides.into_iter().map(|side| {
+            let scale = side as f64 / source_short_side as f64;
+            let even = |size: u32| ((size as f64 * scale) as u32 & !1).max(2);
+            let (w, h) = (even(width), even(height));
+            // smaller renditions need more bits per pixel for the same quality
+            let ratio = (area(w, h) / area(width, height)).powf(0.75);
+            Self {
+                width: w,
+                height: h,
+                bitrate: ((bitrate as f64 * ratio) as u32).max(1),
+            }
+        });
+        std::iter::once(source).chain(scaled).collect()
+    }
+
+    /// Label such as `720p`, after the shorter side.
+    pub fn label(&self) -> String {
+        format!("{}p", self.width.min(self.height))
+    }
+}
+
+/// Scales `frame` to `width` by `height`, averaging the source pixels each output pixel covers.
+pub fn scale_bgra(frame: &VideoFrameBgra32, width: u32, height: u32) -> VideoFrameBgra32 {
+    let data = frame.buffer.data();
+    let (src_width, src_height) = (frame.width as usize, frame.height as usize);
+    let (width, height) = (width as usize, height as usize);
+    // source range covered by output index `i` of `len`, at least one pixel wide
+    let span = |i: usize, len: usize, src_len: usize| {
+        let start = i * src_len / len;
+        start..((i + 1) * src_len / len).max(start + 1)
+    };
+
+    let mut out = vec![0u8; width * height * 4];
+    for y in 0..height {
+        let rows = span(y, height, src_height);
+        for x in 0..width {
+            let columns = span(x, width, src_width);
+            let mut sum = [0u32; 4];
+            for row in rows.clone() {
+                let start = row * frame.stride as usize;
+                for column in columns.clone() {
+                    let pixel = &data[start + column * 4..start + column * 4 + 4];
+                    for (sum, &value) in sum.iter_mut().zip(pixel) {
+                        *sum += value as u32;
+                    }
+                }
+            }
+            let count = (rows.len() * columns.len()) as u32;
+            let at = (y * width + x) * 4;
+            for (out, sum) in out[at..at + 4].iter_mut().zip(sum) {
+                *out = ((sum + count / 2) / count) as u8;
+            }
+        }
+    }
+    VideoFrameBgra32::packed(
+        SharedBuffer::new_unmanaged(out),
+        width as u32,
+        height as u32,
+    )
+}
+
+/// Polls every future until all have completed, returning the first error.
+async fn join_all<F: Future<Output = Result<()>>>(futures: Vec<F>) -> Result<()> {
+    let mut futures = futures
+        .into_iter()
+        .map(Box::pin)
+        .map(Some)
+        .collect::<Vec<_>>();
+    let mut first_error = None;
+    poll_fn(|cx| {
+        for slot in futures.iter_mut() {
+            if let Some(future) = slot
+                && let Poll::Ready(result) = future.as_mut().poll(cx)
+            {
+                *slot = None;
+                if let Err(err) = result {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        match futures.iter().all(Option::is_none) {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    })
+    .await;
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Video encoder input that pushes each frame to the encoder of every rendition, scaled to its
+/// size. Only frames in memory can be scaled, so blit sources are rejected.
+pub struct LadderVideoInput<I> {
+    inputs: Vec<(I, Rendition)>,
+}
+
+impl<I> LadderVideoInput<I> {
+    /// `inputs` are encoders set up with the size of their rendition.
+    pub fn new(inputs: Vec<(I, Rendition)>) -> Self {
+        Self { inputs }
+    }
+}
+
+impl<B: Send + 'static, I: EncoderInput<Data = VideoSample<B>>> EncoderInput
+    for LadderVideoInput<I>
+{
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        let VideoFrame::Bgra32(frame) = data.frame else {
+            return Err(CommonError::BlitNotSupported);
+        };
+        let same_size = self
+            .inputs
+            .iter()
+            .position(|(_, r)| (r.width, r.height) == (frame.width, frame.height));
+        let mut frames = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, (_, r))| {
+                (Some(i) != same_size).then(|| scale_bgra(&frame, r.width, r.height))
+            })
+            .collect::<Vec<_>>();
+        if let Some(i) = same_size {
+            frames[i] = Some(frame);
+        }
+
+        let pushes = self
+            .inputs
+            .iter_mut()
+            .zip(frames)
+            .map(|((input, _), frame)| {
+                input.push(VideoSample {
+                    frame: VideoFrame::Bgra32(frame.unwrap()),
+                    timestamp: data.timestamp,
+                })
+            })
+            .collect();
+        join_all(pushes).await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        // every rendition is asked, so their keyframes stay aligned where possible
+        let any = !self.inputs.is_empty();
+        self.inputs
+            .iter_mut()
+            .fold(any, |all, (input, _)| input.request_keyframe() && all)
+    }
+}
+
+/// Muxer input that pushes each encoded sample to the muxers of every rendition, so that the
+/// audio is encoded once for all of them.
+pub struct LadderMuxerInput<I> {
+    inputs: Vec<I>,
+}
+
+impl<I> LadderMuxerInput<I> {
+    pub fn new(inputs: Vec<I>) -> Self {
+        Self { inputs }
+    }
+}
+
+impl<I: MuxerInput<Data: EncodedData>> MuxerInput for LadderMuxerInput<I> {
+    type Data = I::Data;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        let config = bincode::config::standard();
+        let bytes = bincode::encode_to_vec(&data, config).context("Failed to copy sample")?;
+        let mut samples = (1..self.inputs.len())
+            .map(|_| {
+                bincode::decode_from_slice(&bytes, config)
+                    .map(|(data, _)| data)
+                    .context("Failed to copy sample")
+            })
+            .collect::<Result<Vec<_>>>()?;
+        samples.push(data);
+
+        let pushes = self
+            .inputs
+            .iter_mut()
+            .zip(samples)
+            .map(|(input, sample)| input.push(sample))
+            .collect();
+        join_all(pushes).await
+    }
+
+    async fn finish(self) -> Result<()> {
+        join_all(self.inputs.into_iter().map(I::finish).collect()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ladder_keeps_the_aspect_and_skips_larger_sizes() {
+        let ladder = Rendition::ladder(1920, 1080, 8_000_000, &DEFAULT_LADDER);
+        let sizes = ladder
+            .iter()
+            .map(|r| (r.width, r.height))
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [(1920, 1080), (1280, 720), (852, 480)]);
+        assert_eq!(ladder[0].bitrate, 8_000_000);
+        assert!(ladder.windows(2).all(|w| w[0].bitrate > w[1].bitrate));
+        assert_eq!(ladder[1].label(), "720p");
+
+        let portrait = Rendition::ladder(1080, 2400, 8_000_000, &[480, 720, 720]);
+        assert_eq!((portrait[1].width, portrait[1].height), (720, 1600));
+        assert_eq!(portrait.len(), 3);
+    }
+
+    #[test]
+    fn scaling_averages_the_covered_pixels() {
+        // 4x2 frame with a stride of 5 pixels: left half black, right half white
+        let mut data = vec![0u8; 5 * 4 * 2];
+        for row in 0..2 {
+            for x in 2..4 {
+                data[row * 20 + x * 4..row * 20 + x * 4 + 4].fill(255);
+            }
+        }
+        let frame = VideoFrameBgra32 {
+            buffer: SharedBuffer::new_unmanaged(data),
+            width: 4,
+            height: 2,
+            stride: 20,
+        };
+        let scaled = scale_bgra(&frame, 2, 1);
+        assert_eq!(scaled.buffer.data(), [0, 0, 0, 0, 255, 255, 255, 255]);
+        let scaled = scale_bgra(&frame, 1, 1);
+        assert_eq!(scaled.buffer.data(), [128; 4]);
+    }
+}
@@ -1,20 +1,130 @@
 use std::ffi::c_void;
 use std::fmt::Debug;
 use std::future::Future;
-use std::mem::size_of;
 use std::path::Path;
 
-use crate::buffer::SharedBuffer;
-use bincode::{Decode, Encode};
-
+pub mod analysis;
+pub mod bench;
 pub mod buffer;
+pub mod captions;
+pub mod clock;
+pub mod color_space;
+pub mod deadline;
+pub mod dedup;
+pub mod diagnostics;
+pub mod downmix;
+pub mod drift;
+pub mod duration_limit;
+pub mod empty;
 pub mod error;
+pub mod faststart;
+pub mod filter;
+pub mod highlight;
+pub mod interleave;
+mod jpeg;
+pub mod jpeg_spool;
+pub mod ladder;
+pub mod log_capture;
+pub mod loudness;
+mod mp4;
+pub mod output_path;
+pub mod pacing;
+pub mod padding;
+pub mod passthrough;
+pub mod pipeline;
+pub mod pixel_format;
+pub mod png_sequence;
+pub mod profiler;
+pub mod progress;
+pub mod recommended;
+pub mod reconnect;
+pub mod replay_buffer;
+pub mod replay_data;
+pub mod rtmp;
 mod runtime;
+pub mod scene_cut;
+pub mod secondary;
+pub mod session_capture;
+pub mod session_dir;
+pub mod share;
+pub mod snapshot;
+pub mod spherical;
+pub mod srt;
+pub mod still_image;
+pub mod storyboard;
+pub mod stream;
+pub mod tee;
+pub mod telemetry;
+pub mod test_pattern;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod timecode;
+pub mod timelapse;
 #[cfg(feature = "unity")]
 pub mod unity;
+pub mod waveform;
+pub mod whip;
 
 pub use crate::runtime::*;
+pub use analysis::AnalyzedAudioInput;
+pub use captions::{CaptionTrack, Cue};
+pub use clock::{ClockedAudioInput, ClockedVideoInput, MediaClock};
+pub use color_space::ColorSpace;
+pub use dedup::{DedupMode, DedupVideoInput};
+pub use diagnostics::{DiagnosticCheck, ProbeOptions, check_support};
+pub use downmix::{DownmixedAudioEncoder, DownmixedAudioInput, DownmixedAudioOptions};
+pub use drift::{DriftCompensator, DriftStats};
+pub use duration_limit::{DurationLimit, LimitedMuxerInput, fit_video_bitrate};
+pub use empty::{EmptyCheckCompletionHandle, SampledMuxerInput, detect_empty};
 pub use error::{CategorizedError, CommonError, ErrorCategory, OptionExt, Result, ResultExt};
+pub use faststart::FaststartCompletionHandle;
+pub use filter::{FilterChain, FilteredVideoInput, Region, RegionBlur, TextureHook, VideoFilter};
+pub use highlight::{HighlightDetector, HighlightHint, HighlightKind};
+pub use interleave::{InterleavedMuxerInput, interleave};
+pub use jpeg::JpegSubsampling;
+pub use jpeg_spool::{InterruptedExport, JpegSpool, JpegSpoolOptions, SpooledFrame};
+pub use ladder::{LadderMuxerInput, LadderVideoInput, Rendition};
+pub use loudness::{Loudness, LoudnessMeter};
+pub use output_path::DescriptorCompletionHandle;
+pub use pacing::{FrameRate, PacedVideoInput, PacingMode};
+pub use padding::{
+    CleanApertureCompletionHandle, PaddedMuxer, PaddedVideoEncoder, PaddedVideoInput,
+    PaddedVideoOptions,
+};
+pub use passthrough::{AacPacketizer, H264Packetizer};
+pub use pipeline::{CancellationToken, drive};
+pub use pixel_format::{PixelFormat, frame_from_pixels};
+pub use png_sequence::{PngSequence, PngSequenceVideoInput};
+pub use recommended::{DeviceClass, EncoderCapabilities, RecommendedOptions, recommend};
+pub use reconnect::{
+    Reconnect, ReconnectEvent, ReconnectingCompletionHandle, ReconnectingMuxer,
+    ReconnectingMuxerInput, RetryPolicy,
+};
+pub use replay_buffer::{
+    ExportFilter, ReplayBuffer, ReplayBufferAudioInput, ReplayBufferVideoInput,
+};
+pub use replay_data::{ClockOffset, ReplayDataTrack, ReplayEvent, SyncMarker};
+pub use scene_cut::SceneCutVideoInput;
+pub use secondary::{SecondaryFailure, SecondaryMuxerInput, SecondaryVideoInput};
+pub use session_capture::{CapturedAudioInput, CapturedSample, CapturedVideoInput, SessionReplay};
+pub use session_dir::{PurgeReport, SessionDir, purge_stale_sessions};
+pub use share::SharePreset;
+pub use snapshot::{FrameSnapshot, SnapshotInfo, SnapshotVideoInput};
+pub use spherical::SphericalCompletionHandle;
+pub use still_image::{StillImage, StillImageCapture, StillImageFormat};
+pub use storyboard::{Storyboard, StoryboardOptions, StoryboardVideoInput};
+pub use stream::{MuxerSink, OutputStream};
+pub use tee::TeeMuxerInput;
+pub use telemetry::{FrameStats, FrameStatsRing, MeasuredVideoOutput};
+pub use test_pattern::{TestPattern, TestPatternTexture};
+pub use timecode::{Timecode, TimecodeCompletionHandle};
+pub use timelapse::TimelapseVideoInput;
+pub use unienc_core::{
+    AudioEncoderOptions, AudioSample, BlitOptions, EncodedData, GraphicsEventIssuer, LatencyMode,
+    Projection, StereoMode, Timebase, UniencSampleKind, VideoCodec, VideoEncoderOptions,
+    VideoFrame, VideoFrameBgra32, VideoSample, forward_audio_discontinuity,
+};
+pub use waveform::{WaveformAnalyzer, WaveformPoint};
 
 pub trait Encoder {
     type InputType: EncoderInput + 'static;
@@ -63,6 +173,14 @@ pub trait EncodingSystem {
         >;
     type BlitSourceType: TryFromUnityNativeTexturePointer + Send;
     type RuntimeType: Runtime;
+    type H264PacketizerType: H264Packetizer<
+        Data = <<Self::MuxerType as Muxer>::VideoInputType as MuxerInput>::Data,
+    >;
+    type AacPacketizerType: AacPacketizer<
+        Data = <<Self::MuxerType as Muxer>::AudioInputType as MuxerInput>::Data,
+    >;
+    type DecoderType: Decoder;
+    type StillImageCaptureType: StillImageCapture<Data = VideoSample<Self::BlitSourceType>>;
 
     fn new(
         video_options: &Self::VideoEncoderOptionsType,
@@ -72,10 +190,90 @@ pub trait EncodingSystem {
     fn new_video_encoder(&self) -> Result<Self::VideoEncoderType>;
     fn new_audio_encoder(&self) -> Result<Self::AudioEncoderType>;
     fn new_muxer(&self, output_path: &Path) -> Result<Self::MuxerType>;
+    /// Muxer publishing FLV to the RTMP `url`, checked with [`rtmp::validate_rtmp_url`], instead
+    /// of writing a file. Fails where the backend cannot publish. With `reconnect`, the connection
+    /// is opened again when it fails instead of failing the pushes.
+    fn new_rtmp_muxer(&self, url: &str, reconnect: Option<Reconnect>) -> Result<Self::MuxerType> {
+        let _ = (url, reconnect);
+        Err(CommonError::StreamingNotSupported)
+    }
+    /// Muxer publishing over WebRTC to the WHIP endpoint `url`, checked with
+    /// [`whip::validate_whip_url`], authorizing with `bearer_token` if any. Fails where the backend
+    /// cannot publish. Reconnects like [`EncodingSystem::new_rtmp_muxer`].
+    fn new_whip_muxer(
+        &self,
+        url: &str,
+        bearer_token: Option<&str>,
+        reconnect: Option<Reconnect>,
+    ) -> Result<Self::MuxerType> {
+        let _ = (url, bearer_token, reconnect);
+        Err(CommonError::StreamingNotSupported)
+    }
+    /// Muxer publishing MPEG-TS over SRT to `url`, checked with [`srt::validate_srt_url`].
+    /// Fails where the backend cannot publish. Reconnects like [`EncodingSystem::new_rtmp_muxer`].
+    fn new_srt_muxer(&self, url: &str, reconnect: Option<Reconnect>) -> Result<Self::MuxerType> {
+        let _ = (url, reconnect);
+        Err(CommonError::StreamingNotSupported)
+    }
+    fn new_h264_packetizer(&self) -> Result<Self::H264PacketizerType>;
+    fn new_aac_packetizer(&self) -> Result<Self::AacPacketizerType>;
+    fn new_decoder(&self, input_path: &Path) -> Result<Self::DecoderType>;
+    fn new_still_image_capture(
+        &self,
+        format: StillImageFormat,
+    ) -> Result<Self::StillImageCaptureType>;
+
+    /// Waits for the tasks spawned by the system and its components to finish, then releases what
+    /// the system holds, reporting the errors that dropping it would ignore. Components still alive
+    /// keep working, and a new system can be created afterwards.
+    fn shutdown(self) -> impl Future<Output = Result<()>> + Send
+    where
+        Self: Sized;
 
     fn is_blit_supported(&self) -> bool {
         false
     }
+
+    /// Moving color bars the backend draws into a texture of its own, to test the blit path of
+    /// video encoders without Unity. Fails where the backend cannot blit.
+    fn new_test_pattern(&self) -> Result<TestPattern> {
+        Err(CommonError::BlitNotSupported)
+    }
+
+    /// Whether video encoders keep the alpha channel when
+    /// [`VideoEncoderOptions::preserve_alpha`] is set, with the configured codec. Otherwise a
+    /// [`PngSequence`] keeps it.
+    fn is_alpha_supported(&self) -> bool {
+        false
+    }
+
+    /// Whether video encoders can encode `codec`.
+    fn is_codec_supported(&self, codec: VideoCodec) -> bool {
+        codec == VideoCodec::H264
+    }
+
+    /// Bytes available to this process on the volume of `directory`, if the platform can tell.
+    fn available_space(&self, directory: &Path) -> Option<u64> {
+        diagnostics::available_space(directory)
+    }
+
+    /// Checks that the backend can be used on this device, without creating an encoding system.
+    fn self_test() -> Vec<DiagnosticCheck>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+
+    /// What the video encoder of this device can do at `width` by `height`, for
+    /// [`recommend`](recommended::recommend), without creating an encoding system.
+    fn encoder_capabilities(width: u32, height: u32) -> EncoderCapabilities
+    where
+        Self: Sized,
+    {
+        let _ = (width, height);
+        EncoderCapabilities::default()
+    }
 }
 
 pub trait TryFromUnityNativeTexturePointer: Sized {
@@ -90,151 +288,109 @@ impl TryFromUnityNativeTexturePointer for UnsupportedBlitData {
     }
 }
 
-pub trait VideoEncoderOptions: Clone + Copy {
-    fn width(&self) -> u32;
-    fn height(&self) -> u32;
-    fn fps_hint(&self) -> u32;
-    fn bitrate(&self) -> u32;
+/// Decodes the video track of a file produced by the muxer into BGRA frames.
+pub trait Decoder: Send + 'static {
+    /// Returns the next frame in presentation order, or `None` after the last frame.
+    fn pull(&mut self) -> impl Future<Output = Result<Option<DecodedVideoFrame>>> + Send;
+
+    /// Repositions the decoder and returns the frame shown at `timestamp` (in seconds), or `None`
+    /// if it is past the last frame. Subsequent pulls continue with the frames following it.
+    fn seek(
+        &mut self,
+        timestamp: f64,
+        mode: SeekMode,
+    ) -> impl Future<Output = Result<Option<DecodedVideoFrame>>> + Send;
 }
 
-pub trait AudioEncoderOptions: Clone + Copy {
-    fn sample_rate(&self) -> u32;
-    fn channels(&self) -> u32;
-    fn bitrate(&self) -> u32;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekMode {
+    /// Returns the last keyframe at or before the target without decoding forward. Backends that
+    /// cannot locate keyframes cheaply behave as `Precise`.
+    Keyframe,
+    /// Decodes forward from the preceding keyframe to the last frame at or before the target.
+    Precise,
 }
 
-// #[derive(Clone)]
-pub struct VideoSample<BlitSourceType> {
-    pub frame: VideoFrame<BlitSourceType>,
+pub struct DecodedVideoFrame {
+    pub frame: VideoFrameBgra32,
+    /// Presentation time in seconds.
     pub timestamp: f64,
 }
 
-pub enum VideoFrame<BlitSourceType> {
-    Bgra32(VideoFrameBgra32),
-    BlitSource {
-        texture_token: usize,
-        width: u32,
-        height: u32,
-        graphics_format: u32,
-        flip_vertically: bool,
-        is_gamma_workflow: bool,
-        event_issuer: Box<dyn GraphicsEventIssuer + Send>,
-        _phantom: std::marker::PhantomData<BlitSourceType>,
-    },
-}
+pub struct UnsupportedDecoder;
 
-pub struct VideoFrameBgra32 {
-    pub buffer: SharedBuffer,
-    pub width: u32,
-    pub height: u32,
-}
-
-impl VideoFrameBgra32 {
-    pub fn to_yuv420_planes(
-        &self,
-        padded_size: Option<(u32, u32)>,
-    ) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
-        let data = self.buffer.data();
-        let w = padded_size.map_or(self.width, |(w, _)| w);
-        let h = padded_size.map_or(self.height, |(_, h)| h);
-        let w_half = (w + 1) >> 1;
-        let h_half = (h + 1) >> 1;
-        let padded_y_size = (w * h) as usize;
-        let padded_uv_size = (w_half * h_half) as usize;
-
-        // Create padded YUV data arrays
-        let mut y_data = vec![16u8; padded_y_size]; // Black level for Y
-        let mut u_data = vec![128u8; padded_uv_size]; // Neutral for U
-        let mut v_data = vec![128u8; padded_uv_size]; // Neutral for V
-
-        // Convert ARGB to YUV for the original image area only
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let bgra_idx = ((y * self.width + x) * 4) as usize;
-                let r = data[bgra_idx + 2] as i32;
-                let g = data[bgra_idx + 1] as i32;
-                let b = data[bgra_idx] as i32;
-
-                let y_val = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
-
-                let y_idx = (y * w + x) as usize;
-                y_data[y_idx] = y_val;
-
-                // Sample U and V for every 2x2 block (4:2:0 subsampling)
-                if x % 2 == 0 && y % 2 == 0 {
-                    let u_val = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
-                    let v_val = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
-
-                    let uv_idx = ((y / 2) * (w / 2) + (x / 2)) as usize;
-                    u_data[uv_idx] = u_val;
-                    v_data[uv_idx] = v_val;
-                }
-            }
-        }
+impl Decoder for UnsupportedDecoder {
+    async fn pull(&mut self) -> Result<Option<DecodedVideoFrame>> {
+        Err(CommonError::DecodeNotSupported)
+    }
 
-        Ok((y_data, u_data, v_data))
+    async fn seek(
+        &mut self,
+        _timestamp: f64,
+        _mode: SeekMode,
+    ) -> Result<Option<DecodedVideoFrame>> {
+        Err(CommonError::DecodeNotSupported)
     }
 }
 
-#[derive(Clone)]
-pub struct AudioSample {
-    pub data: Vec<i16>,
-    pub timestamp_in_samples: u64,
+/// Adapts frames decoded forward from a keyframe so they start with the last frame at or before
+/// `target`. Frames pass through unchanged when there is no target.
+pub struct PreciseSeek<I> {
+    frames: I,
+    target: Option<f64>,
+    pending: Option<DecodedVideoFrame>,
 }
 
-impl AudioSample {
-    /// Returns the sample data as signed 16-bit little-endian PCM bytes.
-    pub fn data_as_s16le_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(self.data.len() * size_of::<i16>());
-        for &sample in &self.data {
-            bytes.extend_from_slice(&sample.to_le_bytes());
+impl<I> PreciseSeek<I> {
+    pub fn new(frames: I, target: Option<f64>) -> Self {
+        Self {
+            frames,
+            target,
+            pending: None,
         }
-        bytes
     }
 }
 
-/// Computes the forward discontinuity (in samples) between the expected next input position and the
-/// actual timestamp of an incoming audio sample.
-///
-/// `expected_next` is the position the next input was expected at, i.e. the previous push's timestamp
-/// plus the number of frames it delivered. Returns the number of samples by which the input timeline
-/// jumped forward — to be reflected in the emitted PTS (Apple/Android) or filled with silence (FFmpeg)
-/// so audio does not drift ahead of video. Returns 0 when the input is continuous, when this is the
-/// first push (`expected_next` is `None`), or when the timestamp jumped backward (backward jumps are
-/// ignored to keep PTS monotonic, which the muxers require).
-pub fn forward_audio_discontinuity(expected_next: Option<u64>, actual_timestamp: u64) -> u64 {
-    match expected_next {
-        Some(expected) if actual_timestamp > expected => actual_timestamp - expected,
-        _ => 0,
-    }
-}
+impl<E, I: Iterator<Item = std::result::Result<DecodedVideoFrame, E>>> Iterator for PreciseSeek<I> {
+    type Item = I::Item;
 
-pub trait EncodedData: Encode + Decode<()> {
-    fn timestamp(&self) -> f64;
-    fn set_timestamp(&mut self, timestamp: f64);
-    fn kind(&self) -> UniencSampleKind;
-}
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(frame) = self.pending.take() {
+            return Some(Ok(frame));
+        }
+        let Some(target) = self.target.take() else {
+            return self.frames.next();
+        };
 
-#[repr(i8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum UniencSampleKind {
-    Interpolated = 0,
-    Key = 1,
-    Metadata = 2,
+        let mut previous = None;
+        loop {
+            match self.frames.next() {
+                Some(Ok(frame)) if frame.timestamp <= target => previous = Some(frame),
+                Some(Ok(frame)) => {
+                    return match previous {
+                        Some(previous) => {
+                            self.pending = Some(frame);
+                            Some(Ok(previous))
+                        }
+                        None => Some(Ok(frame)),
+                    };
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => return previous.map(Ok),
+            }
+        }
+    }
 }
 
 pub trait EncoderInput: Send + 'static {
     type Data: Send;
     fn push(&mut self, data: Self::Data) -> impl Future<Output = Result<()>> + Send;
-}
 
-pub trait GraphicsEventIssuer: Send + 'static {
-    fn issue_graphics_event(
-        &self,
-        callback: Box<dyn FnOnce(*mut c_void) + Send + 'static>,
-        event_id: i32,
-        texture_token: usize,
-    );
+    /// Makes the next frame pushed a keyframe, returning false where the encoder cannot be asked
+    /// for one. Wrappers forward it to the input they wrap.
+    fn request_keyframe(&mut self) -> bool {
+        false
+    }
 }
 
 pub trait EncoderOutput: Send {
@@ -245,34 +401,30 @@ pub trait EncoderOutput: Send {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::buffer::SharedBuffer;
 
     #[test]
-    fn forward_audio_discontinuity_handles_continuity_gaps_and_backward_jumps() {
-        // First push: no expectation yet, so no discontinuity.
-        assert_eq!(forward_audio_discontinuity(None, 0), 0);
-        assert_eq!(forward_audio_discontinuity(None, 48_000), 0);
-
-        // Continuous input: actual timestamp matches the expected next position.
-        assert_eq!(forward_audio_discontinuity(Some(48_000), 48_000), 0);
-
-        // Forward discontinuity (dropped / paused audio): report the gap so it can be reflected in PTS.
-        assert_eq!(forward_audio_discontinuity(Some(48_000), 72_000), 24_000);
-
-        // Backward jump: ignored to keep PTS monotonic.
-        assert_eq!(forward_audio_discontinuity(Some(48_000), 24_000), 0);
-        assert_eq!(forward_audio_discontinuity(Some(48_000), 0), 0);
-    }
-
-    #[test]
-    fn audio_sample_data_as_s16le_bytes_uses_little_endian_order() {
-        let sample = AudioSample {
-            data: vec![0x1234, -2, i16::MIN],
-            timestamp_in_samples: 0,
+    fn precise_seek_starts_at_last_frame_before_target() {
+        let decode = |timestamps: &[f64], target| {
+            let frames = timestamps.iter().map(|&timestamp| {
+                Ok::<_, ()>(DecodedVideoFrame {
+                    frame: VideoFrameBgra32::packed(SharedBuffer::new_unmanaged(vec![]), 0, 0),
+                    timestamp,
+                })
+            });
+            PreciseSeek::new(frames, target)
+                .map(|frame| frame.unwrap().timestamp)
+                .collect::<Vec<_>>()
         };
 
         assert_eq!(
-            sample.data_as_s16le_bytes(),
-            vec![0x34, 0x12, 0xfe, 0xff, 0x00, 0x80]
+            decode(&[0.9, 1.0, 1.1, 1.2], Some(1.05)),
+            vec![1.0, 1.1, 1.2]
         );
+        // the target is before the first decoded frame
+        assert_eq!(decode(&[0.5, 0.6], Some(0.0)), vec![0.5, 0.6]);
+        // the stream ends before passing the target
+        assert_eq!(decode(&[8.0, 9.0], Some(10.0)), vec![9.0]);
+        assert_eq!(decode(&[0.9, 1.0], None), vec![0.9, 1.0]);
     }
 }
@@ -0,0 +1,115 @@
+//! Capture of the native log into a ring in memory, so the host can attach what happened before a
+//! failed export to a bug report. Lines logged with [`log!`](crate::log) are printed as before and,
+//! while capture is on, also kept along with pipeline [`event`]s that are not printed. The oldest
+//! lines are dropped once the ring holds more than its capacity in bytes.
+
+use std::collections::VecDeque;
+use std::fmt::Arguments;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+static CAPTURE: Mutex<Option<LogRing>> = Mutex::new(None);
+
+/// Prints a line like `println!` and keeps it in the log capture if it is on.
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::log_capture::log(format_args!($($arg)*))
+    };
+}
+
+/// Lines kept up to a total size in bytes, each stamped with the seconds since the ring was
+/// created.
+pub struct LogRing {
+    capacity: usize,
+    lines: VecDeque<String>,
+    len: usize,
+    dropped: u64,
+    start: Instant,
+}
+
+impl LogRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lines: VecDeque::new(),
+            len: 0,
+            dropped: 0,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, message: Arguments) {
+        let line = format!("[{:.3}] {message}\n", self.start.elapsed().as_secs_f64());
+        self.len += line.len();
+        self.lines.push_back(line);
+        while self.len > self.capacity {
+            let Some(oldest) = self.lines.pop_front() else {
+                break;
+            };
+            self.len -= oldest.len();
+            self.dropped += 1;
+        }
+    }
+
+    /// The kept lines, oldest first, after a note of how many were dropped to make room.
+    pub fn contents(&self) -> String {
+        let mut contents = String::with_capacity(self.len + 40);
+        if self.dropped > 0 {
+            contents.push_str(&format!("({} earlier lines dropped)\n", self.dropped));
+        }
+        contents.extend(self.lines.iter().map(String::as_str));
+        contents
+    }
+}
+
+// lines are plain values that stay consistent even if a holder panicked
+fn lock() -> MutexGuard<'static, Option<LogRing>> {
+    CAPTURE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Starts keeping up to `capacity` bytes of lines, discarding those kept so far, or stops with a
+/// `capacity` of zero.
+pub fn set_log_capture(capacity: usize) {
+    *lock() = (capacity > 0).then(|| LogRing::new(capacity));
+}
+
+/// The kept lines, or `None` if capture is off.
+pub fn dump_log_capture() -> Option<String> {
+    lock().as_ref().map(LogRing::contents)
+}
+
+pub fn log(message: Arguments) {
+    println!("{message}");
+    if let Some(ring) = lock().as_mut() {
+        ring.push(message);
+    }
+}
+
+/// Keeps a pipeline event in the log capture without printing it, such as an error reported to
+/// the host.
+pub fn event(message: Arguments) {
+    if let Some(ring) = lock().as_mut() {
+        ring.push(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_oldest_lines_beyond_capacity() {
+        let mut ring = LogRing::new(40);
+        for i in 0..4 {
+            ring.push(format_args!("line {i}"));
+        }
+        // each line is "[0.000] line N\n", 15 bytes
+        let contents = ring.contents();
+        assert!(contents.starts_with("(2 earlier lines dropped)\n"));
+        let lines = contents.lines().skip(1).collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("] line 2"));
+        assert!(lines[1].ends_with("] line 3"));
+    }
+}
@@ -0,0 +1,228 @@
+//! Integrated loudness of the audio fed to an encoder, measured as in ITU-R BS.1770 / EBU R128,
+//! and the gain that normalizes it when the audio is encoded again for export.
+
+use crate::{AudioEncoderOptions, AudioSample};
+
+/// Loudness exported audio is normalized to, in LUFS.
+pub const DEFAULT_TARGET_LOUDNESS: f64 = -16.0;
+
+/// Sample peak normalized audio is kept under, in dBFS.
+const PEAK_CEILING: f64 = -1.0;
+
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Gating blocks are 400 ms long and start every 100 ms.
+const SUB_BLOCK_INTERVAL: f64 = 0.1;
+const SUB_BLOCKS_PER_BLOCK: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness, in LUFS.
+    pub integrated: f64,
+    /// Largest absolute sample value, normalized to 0.0..=1.0.
+    pub peak: f32,
+}
+
+impl Loudness {
+    /// Gain in dB that brings the integrated loudness to `target` LUFS, reduced as needed to keep
+    /// the sample peak under -1 dBFS.
+    pub fn normalization_gain(&self, target: f64) -> f64 {
+        let gain = target - self.integrated;
+        match self.peak > 0.0 {
+            true => gain.min(PEAK_CEILING - 20.0 * (self.peak as f64).log10()),
+            false => gain,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    /// Direct form II transposed; `state` holds the two delay elements.
+    fn process(&self, x: f64, state: &mut [f64; 2]) -> f64 {
+        let y = self.b[0] * x + state[0];
+        state[0] = self.b[1] * x - self.a[0] * y + state[1];
+        state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The two stages of the K-weighting filter, derived for any sample rate.
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    // high shelf modelling the acoustic effect of the head
+    let k = (std::f64::consts::PI * 1681.974450955533 / sample_rate).tan();
+    let q = 0.7071752369554196;
+    let vh = 10f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    // RLB high-pass
+    let k = (std::f64::consts::PI * 38.13547087602444 / sample_rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    [shelf, high_pass]
+}
+
+/// Accumulates interleaved PCM into 400 ms gating blocks with 75% overlap, all channels weighted
+/// equally. Samples are measured in the order they are pushed; gaps in the timestamps are ignored.
+pub struct LoudnessMeter {
+    channels: usize,
+    filters: [Biquad; 2],
+    /// Filter state per channel and stage.
+    states: Vec<[[f64; 2]; 2]>,
+    frames_per_sub_block: u64,
+    frames: u64,
+    sum_of_squares: f64,
+    sub_blocks: Vec<f64>,
+    /// Mean square of each complete gating block, summed over channels.
+    blocks: Vec<f64>,
+    peak: i32,
+}
+
+impl LoudnessMeter {
+    pub fn new<A: AudioEncoderOptions>(options: &A) -> Self {
+        let channels = options.channels().max(1) as usize;
+        let sample_rate = options.sample_rate().max(1) as f64;
+        Self {
+            channels,
+            filters: k_weighting(sample_rate),
+            states: vec![[[0.0; 2]; 2]; channels],
+            frames_per_sub_block: ((sample_rate * SUB_BLOCK_INTERVAL) as u64).max(1),
+            frames: 0,
+            sum_of_squares: 0.0,
+            sub_blocks: Vec::with_capacity(SUB_BLOCKS_PER_BLOCK),
+            blocks: Vec::new(),
+            peak: 0,
+        }
+    }
+
+    pub fn push(&mut self, sample: &AudioSample) {
+        for frame in sample.data.chunks_exact(self.channels) {
+            for (value, state) in frame.iter().zip(&mut self.states) {
+                self.peak = self.peak.max((*value as i32).abs());
+                let x = *value as f64 / 32768.0;
+                let y = self.filters[0].process(x, &mut state[0]);
+                let y = self.filters[1].process(y, &mut state[1]);
+                self.sum_of_squares += y * y;
+            }
+            self.frames += 1;
+            if self.frames == self.frames_per_sub_block {
+                self.finish_sub_block();
+            }
+        }
+    }
+
+    fn finish_sub_block(&mut self) {
+        if self.sub_blocks.len() == SUB_BLOCKS_PER_BLOCK {
+            self.sub_blocks.remove(0);
+        }
+        self.sub_blocks
+            .push(self.sum_of_squares / self.frames_per_sub_block as f64);
+        self.frames = 0;
+        self.sum_of_squares = 0.0;
+        if self.sub_blocks.len() == SUB_BLOCKS_PER_BLOCK {
+            self.blocks
+                .push(self.sub_blocks.iter().sum::<f64>() / SUB_BLOCKS_PER_BLOCK as f64);
+        }
+    }
+
+    /// Loudness of the audio pushed so far, or `None` until a gating block above the absolute
+    /// gate has been measured.
+    pub fn loudness(&self) -> Option<Loudness> {
+        let gated_mean = |threshold: f64| {
+            let gated = self
+                .blocks
+                .iter()
+                .filter(|&&energy| to_lufs(energy) > threshold)
+                .collect::<Vec<_>>();
+            (!gated.is_empty()).then(|| gated.iter().copied().sum::<f64>() / gated.len() as f64)
+        };
+        let relative = to_lufs(gated_mean(ABSOLUTE_GATE)?) + RELATIVE_GATE;
+        let integrated = to_lufs(gated_mean(relative.max(ABSOLUTE_GATE))?);
+        Some(Loudness {
+            integrated,
+            peak: self.peak as f32 / 32768.0,
+        })
+    }
+}
+
+fn to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// Scales interleaved PCM by `gain_db`, saturating at full scale.
+pub fn apply_gain(sample: &mut AudioSample, gain_db: f64) {
+    let gain = 10f64.powf(gain_db / 20.0);
+    for value in &mut sample.data {
+        *value = (*value as f64 * gain)
+            .round()
+            .clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    struct Options;
+
+    impl AudioEncoderOptions for Options {
+        fn sample_rate(&self) -> u32 {
+            48000
+        }
+        fn channels(&self) -> u32 {
+            2
+        }
+        fn bitrate(&self) -> u32 {
+            128000
+        }
+    }
+
+    #[test]
+    fn stereo_sine_measures_as_specified() {
+        let mut meter = LoudnessMeter::new(&Options);
+        meter.push(&AudioSample {
+            data: vec![0; 48000 * 2],
+            timestamp_in_samples: 0,
+        });
+        assert_eq!(meter.loudness(), None, "silence is below the absolute gate");
+
+        // 997 Hz at -20 dBFS on both channels reads -20 LUFS
+        let mut meter = LoudnessMeter::new(&Options);
+        let data = (0..48000 * 5)
+            .flat_map(|i| {
+                let phase = 2.0 * std::f64::consts::PI * 997.0 * i as f64 / 48000.0;
+                let value = (phase.sin() * 0.1 * 32768.0).round() as i16;
+                [value, value]
+            })
+            .collect();
+        meter.push(&AudioSample {
+            data,
+            timestamp_in_samples: 0,
+        });
+        let loudness = meter.loudness().unwrap();
+        assert!((loudness.integrated + 20.0).abs() < 0.1, "{loudness:?}");
+        assert!((loudness.normalization_gain(DEFAULT_TARGET_LOUDNESS) - 4.0).abs() < 0.1);
+        // the peak ceiling wins over the target
+        assert!((loudness.normalization_gain(0.0) - 19.0).abs() < 0.01);
+    }
+}
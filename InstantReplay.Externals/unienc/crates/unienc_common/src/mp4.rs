@@ -0,0 +1,213 @@
+//! Reading and patching the boxes of finished MP4 and QuickTime files, for metadata the platform
+//! muxers cannot write themselves. Errors are the reason the file is unsupported; callers wrap
+//! them into their own error.
+
+use std::ops::Range;
+
+pub(crate) type BoxResult<T> = std::result::Result<T, String>;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Mp4Box {
+    pub start: usize,
+    pub header_len: usize,
+    pub end: usize,
+    pub kind: [u8; 4],
+}
+
+impl Mp4Box {
+    pub fn content(&self) -> Range<usize> {
+        self.start + self.header_len..self.end
+    }
+}
+
+pub(crate) fn read_u32(data: &[u8], at: usize) -> BoxResult<u32> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "truncated box".to_string())
+}
+
+pub(crate) fn read_u64(data: &[u8], at: usize) -> BoxResult<u64> {
+    Ok(((read_u32(data, at)? as u64) << 32) | read_u32(data, at + 4)? as u64)
+}
+
+pub(crate) fn children(data: &[u8], range: Range<usize>) -> BoxResult<Vec<Mp4Box>> {
+    let mut boxes = Vec::new();
+    let mut at = range.start;
+    while at + 8 <= range.end {
+        let size = read_u32(data, at)? as usize;
+        let kind = [data[at + 4], data[at + 5], data[at + 6], data[at + 7]];
+        let (header_len, size) = match size {
+            0 => (8, range.end - at),
+            1 => (16, read_u64(data, at + 8)? as usize),
+            size => (8, size),
+        };
+        if size < header_len || at + size > range.end {
+            return Err("box exceeds its parent".to_string());
+        }
+        boxes.push(Mp4Box {
+            start: at,
+            header_len,
+            end: at + size,
+            kind,
+        });
+        at += size;
+    }
+    Ok(boxes)
+}
+
+pub(crate) fn find(data: &[u8], range: Range<usize>, kind: &[u8; 4]) -> BoxResult<Mp4Box> {
+    children(data, range)?
+        .into_iter()
+        .find(|b| &b.kind == kind)
+        .ok_or_else(|| format!("missing {} box", String::from_utf8_lossy(kind)))
+}
+
+/// First track whose handler is `vide`.
+pub(crate) fn video_track(data: &[u8], moov: &Mp4Box) -> BoxResult<Mp4Box> {
+    for trak in children(data, moov.content())? {
+        if &trak.kind == b"trak" && is_video_track(data, &trak)? {
+            return Ok(trak);
+        }
+    }
+    Err("no video track".to_string())
+}
+
+fn is_video_track(data: &[u8], trak: &Mp4Box) -> BoxResult<bool> {
+    let mdia = find(data, trak.content(), b"mdia")?;
+    let hdlr = find(data, mdia.content(), b"hdlr")?;
+    // version and flags, then pre_defined, then the handler type
+    let at = hdlr.content().start + 8;
+    Ok(data.get(at..at + 4) == Some(b"vide"))
+}
+
+pub(crate) struct MovieHeader {
+    pub timescale: u32,
+    pub duration: u64,
+    pub next_track_id: u32,
+    /// Position of the next track ID in the file.
+    pub next_track_id_at: usize,
+}
+
+impl MovieHeader {
+    pub fn read(data: &[u8], mvhd: &Mp4Box) -> BoxResult<Self> {
+        let at = mvhd.content().start;
+        // version 1 has 64-bit creation time, modification time and duration
+        let (timescale, duration, rest) = match data.get(at).copied() {
+            Some(1) => (read_u32(data, at + 20)?, read_u64(data, at + 24)?, at + 32),
+            Some(0) => (
+                read_u32(data, at + 12)?,
+                read_u32(data, at + 16)? as u64,
+                at + 20,
+            ),
+            _ => return Err("unsupported mvhd version".to_string()),
+        };
+        // rate, volume, reserved, matrix and pre_defined precede the next track ID
+        let next_track_id_at = rest + 76;
+        let next_track_id = read_u32(data, next_track_id_at)?;
+        if timescale == 0 || next_track_id == 0 || next_track_id == u32::MAX {
+            return Err("invalid mvhd".to_string());
+        }
+        Ok(Self {
+            timescale,
+            duration,
+            next_track_id,
+            next_track_id_at,
+        })
+    }
+}
+
+pub(crate) const IDENTITY_MATRIX: [u32; 9] = [0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000];
+
+pub(crate) fn be_bytes(values: &[u32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .collect()
+}
+
+/// Writes the size of `b` grown by `delta` into its header.
+pub(crate) fn grow(data: &mut [u8], b: &Mp4Box, delta: usize) -> BoxResult<()> {
+    let size = b.end - b.start + delta;
+    match b.header_len {
+        16 => data[b.start + 8..b.start + 16].copy_from_slice(&(size as u64).to_be_bytes()),
+        _ => {
+            let size = u32::try_from(size).map_err(|_| "box too large".to_string())?;
+            data[b.start..b.start + 4].copy_from_slice(&size.to_be_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Moves chunk offsets of every track that point at or past `insert_at` by `delta`.
+pub(crate) fn shift_chunk_offsets(
+    data: &mut [u8],
+    moov: &Mp4Box,
+    insert_at: usize,
+    delta: usize,
+) -> BoxResult<()> {
+    map_chunk_offsets(data, moov, |offset| match offset >= insert_at {
+        true => offset + delta,
+        false => offset,
+    })
+}
+
+/// Replaces every chunk offset of every track with `map` of it.
+pub(crate) fn map_chunk_offsets(
+    data: &mut [u8],
+    moov: &Mp4Box,
+    map: impl Fn(usize) -> usize,
+) -> BoxResult<()> {
+    let mut tables = Vec::new();
+    for trak in children(data, moov.content())? {
+        if &trak.kind != b"trak" {
+            continue;
+        }
+        let mdia = find(data, trak.content(), b"mdia")?;
+        let minf = find(data, mdia.content(), b"minf")?;
+        let stbl = find(data, minf.content(), b"stbl")?;
+        tables.extend(
+            children(data, stbl.content())?
+                .into_iter()
+                .filter(|b| &b.kind == b"stco" || &b.kind == b"co64"),
+        );
+    }
+    for table in tables {
+        let count = read_u32(data, table.content().start + 4)? as usize;
+        let entries = table.content().start + 8;
+        for i in 0..count {
+            if &table.kind == b"stco" {
+                let at = entries + i * 4;
+                let offset = map(read_u32(data, at)? as usize);
+                let offset =
+                    u32::try_from(offset).map_err(|_| "chunk offset overflows".to_string())?;
+                data[at..at + 4].copy_from_slice(&offset.to_be_bytes());
+            } else {
+                let at = entries + i * 8;
+                let offset = map(read_u64(data, at)? as usize);
+                data[at..at + 8].copy_from_slice(&(offset as u64).to_be_bytes());
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn full_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut b = Vec::with_capacity(12 + payload.len());
+    b.extend_from_slice(&((12 + payload.len()) as u32).to_be_bytes());
+    b.extend_from_slice(kind);
+    // version and flags
+    b.extend_from_slice(&[0; 4]);
+    b.extend_from_slice(payload);
+    b
+}
+
+pub(crate) fn container(kind: &[u8; 4], children: &[Vec<u8>]) -> Vec<u8> {
+    let len = 8 + children.iter().map(Vec::len).sum::<usize>();
+    let mut b = Vec::with_capacity(len);
+    b.extend_from_slice(&(len as u32).to_be_bytes());
+    b.extend_from_slice(kind);
+    for child in children {
+        b.extend_from_slice(child);
+    }
+    b
+}
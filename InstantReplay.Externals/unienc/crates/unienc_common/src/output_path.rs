@@ -0,0 +1,297 @@
+//! Output paths as the backends expect them. A path is checked before any backend converts it, so
+//! a path no backend can write fails the same way on every platform instead of deep in an HSTRING,
+//! NSURL or JNI conversion. A file descriptor or handle the host opened, such as one for an Android
+//! scoped storage document, is passed as a path naming the descriptor to the backends that
+//! recognize it with [`output_descriptor`]. The others write a temporary file that
+//! [`DescriptorCompletionHandle`] copies into the descriptor once it is complete.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{CommonError, CompletionHandle, Result};
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+const DESCRIPTOR_DIR: &str = "/proc/self/fd/";
+
+/// Prefix of paths that opt out of the Win32 path length limit.
+const EXTENDED_PREFIX: &str = r"\\?\";
+
+/// Length from which Win32 file APIs reject paths without [`EXTENDED_PREFIX`], leaving room for
+/// the 8.3 name some of them append.
+const MAX_SHORT_PATH: usize = 248;
+
+fn invalid(path: &Path, reason: &str) -> CommonError {
+    CommonError::InvalidOutputPath(format!("{}: {reason}", path.display()))
+}
+
+/// Checks that `path` names a file that can be created: not empty, not a URI, not a directory
+/// and in a directory that exists.
+pub fn validate_output_path(path: &Path) -> Result<()> {
+    let Some(text) = path.to_str() else {
+        return Err(invalid(path, "not valid Unicode"));
+    };
+    if text.is_empty() {
+        return Err(CommonError::InvalidOutputPath("empty path".to_string()));
+    }
+    if text.contains('\0') {
+        return Err(invalid(path, "contains a null character"));
+    }
+    // a Windows drive such as `C:` is not a scheme
+    if let Some((scheme, _)) = text.split_once("://")
+        && scheme.len() > 1
+    {
+        return Err(invalid(
+            path,
+            "URIs are not paths; open the document and pass its file descriptor instead",
+        ));
+    }
+    if output_descriptor(path).is_some() {
+        return Ok(());
+    }
+    if path.file_name().is_none() || path.is_dir() {
+        return Err(invalid(path, "not a file name"));
+    }
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() && !parent.is_dir() => {
+            Err(invalid(path, "the directory does not exist"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Path naming the open file descriptor `fd`, which must stay open until the muxer completes.
+/// `None` where descriptors cannot be named by a path.
+pub fn descriptor_path(fd: i32) -> Option<PathBuf> {
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    {
+        (fd >= 0).then(|| PathBuf::from(format!("{DESCRIPTOR_DIR}{fd}")))
+    }
+    #[cfg(not(any(target_os = "android", target_os = "linux")))]
+    {
+        let _ = fd;
+        None
+    }
+}
+
+/// The file descriptor `path` names if it was made by [`descriptor_path`].
+pub fn output_descriptor(path: &Path) -> Option<i32> {
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    {
+        path.to_str()?.strip_prefix(DESCRIPTOR_DIR)?.parse().ok()
+    }
+    #[cfg(not(any(target_os = "android", target_os = "linux")))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// A duplicate of the open file descriptor `fd`, which the host keeps owning.
+///
+/// # Safety
+///
+/// `fd` must be open for the duration of this call.
+#[cfg(unix)]
+pub unsafe fn duplicate_descriptor(fd: i32) -> Result<File> {
+    use std::os::fd::BorrowedFd;
+
+    unsafe { BorrowedFd::borrow_raw(fd) }
+        .try_clone_to_owned()
+        .map(File::from)
+        .map_err(|e| CommonError::InvalidOutputPath(format!("file descriptor {fd}: {e}")))
+}
+
+/// A duplicate of the open file `handle`, which the host keeps owning.
+///
+/// # Safety
+///
+/// `handle` must be open for the duration of this call.
+#[cfg(windows)]
+pub unsafe fn duplicate_handle(handle: std::os::windows::io::RawHandle) -> Result<File> {
+    use std::os::windows::io::BorrowedHandle;
+
+    unsafe { BorrowedHandle::borrow_raw(handle) }
+        .try_clone_to_owned()
+        .map(File::from)
+        .map_err(|e| CommonError::InvalidOutputPath(format!("file handle {handle:?}: {e}")))
+}
+
+/// Unused path in the temporary directory for a file to copy into a descriptor.
+pub fn temporary_output_path() -> PathBuf {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("unienc-{}-{n}.mp4", std::process::id()))
+}
+
+/// Completion handle that copies the file the wrapped muxer wrote into a descriptor once it is
+/// complete, from the current position of the descriptor, and removes the file. The descriptor
+/// does not need to be seekable, so it may be a pipe or a socket. Without a descriptor the file
+/// is left where it is.
+pub struct DescriptorCompletionHandle<C> {
+    inner: C,
+    path: PathBuf,
+    target: Option<File>,
+}
+
+impl<C> DescriptorCompletionHandle<C> {
+    pub fn new(inner: C, path: &Path, target: Option<File>) -> Self {
+        Self {
+            inner,
+            path: path.to_owned(),
+            target,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+}
+
+impl<C: CompletionHandle + Send> CompletionHandle for DescriptorCompletionHandle<C> {
+    async fn finish(self) -> Result<()> {
+        let result = self.inner.finish().await;
+        let Some(mut target) = self.target else {
+            return result;
+        };
+        let copied = result.and_then(|()| {
+            let mut file = File::open(&self.path)
+                .map_err(|e| CommonError::Other(format!("Failed to read muxed file: {e}")))?;
+            std::io::copy(&mut file, &mut target).map_err(|e| {
+                CommonError::Other(format!("Failed to write output descriptor: {e}"))
+            })?;
+            // pipes and sockets cannot be synced
+            let _ = target.sync_all();
+            Ok(())
+        });
+        let _ = std::fs::remove_file(&self.path);
+        copied
+    }
+}
+
+/// `path` in the form Win32 file APIs accept beyond their length limit, if it is a long absolute
+/// path. Such paths are passed through unparsed, so separators are normalized and `.` and `..`
+/// are resolved here. Other paths are returned as they are.
+pub fn extended_length_path(path: &Path) -> Cow<'_, Path> {
+    match path.to_str().and_then(extended_length) {
+        Some(extended) => Cow::Owned(PathBuf::from(extended)),
+        None => Cow::Borrowed(path),
+    }
+}
+
+fn extended_length(path: &str) -> Option<String> {
+    if path.len() < MAX_SHORT_PATH || path.starts_with(EXTENDED_PREFIX) {
+        return None;
+    }
+    let path = path.replace('/', r"\");
+    let (prefix, rest) = if let Some(unc) = path.strip_prefix(r"\\") {
+        (format!(r"{EXTENDED_PREFIX}UNC\"), unc)
+    } else if path.as_bytes().get(1) == Some(&b':') && path.as_bytes().get(2) == Some(&b'\\') {
+        (format!("{EXTENDED_PREFIX}{}", &path[..3]), &path[3..])
+    } else {
+        // relative paths cannot be extended
+        return None;
+    };
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    Some(prefix + &components.join(r"\"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    #[test]
+    fn rejects_paths_no_backend_can_create() {
+        let dir = std::env::temp_dir();
+        assert!(validate_output_path(&dir.join("リプレイ 🎮.mp4")).is_ok());
+        assert!(validate_output_path(Path::new("replay.mp4")).is_ok());
+
+        for path in [
+            PathBuf::new(),
+            PathBuf::from("content://media/external/video/1"),
+            dir.clone(),
+            dir.join("missing").join("replay.mp4"),
+        ] {
+            assert!(
+                matches!(
+                    validate_output_path(&path),
+                    Err(CommonError::InvalidOutputPath(_))
+                ),
+                "{}",
+                path.display()
+            );
+        }
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn names_descriptors_by_path() {
+        let path = descriptor_path(7).unwrap();
+        assert_eq!(output_descriptor(&path), Some(7));
+        assert_eq!(output_descriptor(Path::new("/tmp/7")), None);
+    }
+
+    struct Written(PathBuf);
+
+    impl CompletionHandle for Written {
+        async fn finish(self) -> Result<()> {
+            std::fs::write(&self.0, b"movie").map_err(|e| CommonError::Other(e.to_string()))
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copies_into_the_descriptor_and_removes_the_file() {
+        use std::io::{Read, Seek};
+        use std::os::fd::AsRawFd;
+
+        let path = temporary_output_path();
+        let target_path = temporary_output_path();
+        let mut target = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&target_path)
+            .unwrap();
+        let duplicate = unsafe { duplicate_descriptor(target.as_raw_fd()) }.unwrap();
+
+        let handle = DescriptorCompletionHandle::new(Written(path.clone()), &path, Some(duplicate));
+        block_on(handle.finish()).unwrap();
+        assert!(!path.exists());
+
+        let mut contents = Vec::new();
+        target.rewind().unwrap();
+        target.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, b"movie");
+        std::fs::remove_file(target_path).unwrap();
+    }
+
+    #[test]
+    fn extends_long_absolute_paths() {
+        let long = "a".repeat(MAX_SHORT_PATH);
+        assert_eq!(extended_length(r"C:\short.mp4"), None);
+        assert_eq!(
+            extended_length(&format!(r"C:/videos/./x/../{long}.mp4")),
+            Some(format!(r"\\?\C:\videos\{long}.mp4"))
+        );
+        assert_eq!(
+            extended_length(&format!(r"\\server\share\{long}.mp4")),
+            Some(format!(r"\\?\UNC\server\share\{long}.mp4"))
+        );
+        assert_eq!(extended_length(&format!(r"videos\{long}.mp4")), None);
+        assert_eq!(extended_length(&format!(r"\\?\C:\{long}")), None);
+    }
+}
@@ -0,0 +1,279 @@
+//! Conversion of irregular frame timing to an exact output frame rate, so every backend produces
+//! the same constant frame rate video whatever it does with timestamps.
+
+use crate::buffer::SharedBuffer;
+use crate::{EncoderInput, Result, VideoFrame, VideoFrameBgra32, VideoSample};
+
+/// Frames per second as a fraction, such as 30000/1001 for 29.97.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRate {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl FrameRate {
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        Self {
+            numerator: numerator.max(1),
+            denominator: denominator.max(1),
+        }
+    }
+
+    /// Time of output frame `index`, in seconds from the first frame.
+    fn time(&self, index: u64) -> f64 {
+        index as f64 * self.denominator as f64 / self.numerator as f64
+    }
+
+    /// Number of output frame intervals in `seconds`.
+    fn frames(&self, seconds: f64) -> f64 {
+        seconds * self.numerator as f64 / self.denominator as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacingMode {
+    /// Each output frame is the input frame closest to its time.
+    DropDuplicate,
+    /// Output frames between two input frames are blended from both by their distance in time.
+    Blend,
+}
+
+struct Pacer {
+    rate: FrameRate,
+    mode: PacingMode,
+    start: Option<f64>,
+    /// Index of the next output frame.
+    next: u64,
+    /// Copy of the last frame pushed as BGRA pixels, and its time since the first frame.
+    previous: Option<(VideoFrameBgra32, f64)>,
+}
+
+/// Video encoder input that retimes frames to an exact frame rate once one is set. Gaps are
+/// filled with copies of the previous frame and frames arriving faster than the rate are dropped.
+/// Blit sources cannot be copied or blended, so gaps after them are left unfilled and blending
+/// falls back to picking the closest frame.
+pub struct PacedVideoInput<I> {
+    inner: I,
+    pacer: Option<Pacer>,
+}
+
+impl<I> PacedVideoInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self { inner, pacer: None }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Output frames start at the first frame pushed after this call. `None` passes frames
+    /// through unchanged.
+    pub fn set_frame_rate(&mut self, pacing: Option<(FrameRate, PacingMode)>) {
+        self.pacer = pacing.map(|(rate, mode)| Pacer {
+            rate,
+            mode,
+            start: None,
+            next: 0,
+            previous: None,
+        });
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> PacedVideoInput<I> {
+    async fn push_copy(&mut self, frame: &VideoFrameBgra32, timestamp: f64) -> Result<()> {
+        self.inner
+            .push(VideoSample {
+                frame: VideoFrame::Bgra32(copy(frame)),
+                timestamp,
+            })
+            .await
+    }
+
+    /// Pushes copies of the previous frame up to the output frame closest to `time`, then the
+    /// frame itself there unless an earlier frame already took it.
+    async fn push_closest(
+        &mut self,
+        mut data: VideoSample<B>,
+        start: f64,
+        time: f64,
+    ) -> Result<()> {
+        let Some(pacer) = &mut self.pacer else {
+            return self.inner.push(data).await;
+        };
+        let (rate, next) = (pacer.rate, pacer.next);
+        let position = rate.frames(time).round();
+        let previous = remember(&mut pacer.previous, &data.frame, time);
+        if position < next as f64 {
+            return Ok(());
+        }
+        let index = position as u64;
+        pacer.next = index + 1;
+
+        if let Some((previous, _)) = &previous {
+            for i in next..index {
+                self.push_copy(previous, start + rate.time(i)).await?;
+            }
+        }
+        data.timestamp = start + rate.time(index);
+        self.inner.push(data).await
+    }
+
+    /// Pushes the output frames up to `time`, blended from the previous frame and this one.
+    async fn push_blended(
+        &mut self,
+        data: VideoSample<B>,
+        start: f64,
+        time: f64,
+        previous: (VideoFrameBgra32, f64),
+    ) -> Result<()> {
+        let Some(pacer) = &mut self.pacer else {
+            return self.inner.push(data).await;
+        };
+        let (rate, next) = (pacer.rate, pacer.next);
+        let VideoFrame::Bgra32(frame) = &data.frame else {
+            return self.push_closest(data, start, time).await;
+        };
+        pacer.previous = Some((copy(frame), time));
+        let position = rate.frames(time).floor();
+        if position < next as f64 {
+            return Ok(());
+        }
+        let last = position as u64;
+        pacer.next = last + 1;
+
+        let (previous, previous_time) = previous;
+        for i in next..=last {
+            let weight = (rate.time(i) - previous_time) / (time - previous_time);
+            if i == last && weight >= 1.0 - 1e-6 {
+                return self
+                    .inner
+                    .push(VideoSample {
+                        frame: data.frame,
+                        timestamp: start + rate.time(i),
+                    })
+                    .await;
+            }
+            let blended = blend(&previous, frame, weight.clamp(0.0, 1.0));
+            self.inner
+                .push(VideoSample {
+                    frame: VideoFrame::Bgra32(blended),
+                    timestamp: start + rate.time(i),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for PacedVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        let Some(pacer) = &mut self.pacer else {
+            return self.inner.push(data).await;
+        };
+        let start = *pacer.start.get_or_insert(data.timestamp);
+        let time = data.timestamp - start;
+
+        let previous = match (&pacer.mode, &pacer.previous, &data.frame) {
+            (PacingMode::Blend, Some((previous, previous_time)), VideoFrame::Bgra32(frame))
+                if time > *previous_time
+                    && (previous.width, previous.height, previous.stride)
+                        == (frame.width, frame.height, frame.stride) =>
+            {
+                pacer.previous.take()
+            }
+            _ => None,
+        };
+        match previous {
+            Some(previous) => self.push_blended(data, start, time, previous).await,
+            None => self.push_closest(data, start, time).await,
+        }
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+/// Keeps a copy of `frame` for filling later gaps, returning the previously kept one.
+fn remember(
+    previous: &mut Option<(VideoFrameBgra32, f64)>,
+    frame: &VideoFrame<impl Send>,
+    time: f64,
+) -> Option<(VideoFrameBgra32, f64)> {
+    let current = match frame {
+        VideoFrame::Bgra32(frame) => Some((copy(frame), time)),
+        VideoFrame::BlitSource { .. } => None,
+    };
+    std::mem::replace(previous, current)
+}
+
+fn copy(frame: &VideoFrameBgra32) -> VideoFrameBgra32 {
+    VideoFrameBgra32 {
+        buffer: SharedBuffer::new_unmanaged(frame.buffer.data().to_vec()),
+        ..*frame
+    }
+}
+
+/// `weight` of 0.0 gives `a` and 1.0 gives `b`.
+fn blend(a: &VideoFrameBgra32, b: &VideoFrameBgra32, weight: f64) -> VideoFrameBgra32 {
+    let weight = (weight * 256.0).round() as u32;
+    let data = a
+        .buffer
+        .data()
+        .iter()
+        .zip(b.buffer.data())
+        .map(|(&a, &b)| ((a as u32 * (256 - weight) + b as u32 * weight + 128) >> 8) as u8)
+        .collect();
+    VideoFrameBgra32 {
+        buffer: SharedBuffer::new_unmanaged(data),
+        ..*a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Frames, push};
+
+    /// Value of the first byte of each frame pushed to `frames`.
+    fn values(frames: &Frames) -> Vec<u8> {
+        frames
+            .pushed()
+            .iter()
+            .map(|frame| frame.pixels[0])
+            .collect()
+    }
+
+    #[test]
+    fn retimes_to_fractional_rate() {
+        let rate = FrameRate::new(30000, 1001);
+        let mut input = PacedVideoInput::new(Frames::default());
+        input.set_frame_rate(Some((rate, PacingMode::DropDuplicate)));
+        // a gap of three frames, then two frames within one interval
+        for (timestamp, value) in [(10.0, 0), (10.1, 100), (10.11, 200), (10.134, 250)] {
+            push(&mut input, timestamp, (1, 1), vec![value; 4]);
+        }
+        let timestamps = input.inner_mut().timestamps();
+        assert_eq!(timestamps.len(), 5);
+        for (i, timestamp) in timestamps.iter().enumerate() {
+            assert!((timestamp - 10.0 - i as f64 * 1001.0 / 30000.0).abs() < 1e-9);
+        }
+        assert_eq!(
+            values(input.inner_mut()),
+            vec![0, 0, 0, 100, 250],
+            "200 is dropped"
+        );
+
+        let mut input = PacedVideoInput::new(Frames::default());
+        input.set_frame_rate(Some((FrameRate::new(4, 1), PacingMode::Blend)));
+        push(&mut input, 0.0, (1, 1), vec![0; 4]);
+        push(&mut input, 1.0, (1, 1), vec![200; 4]);
+        assert_eq!(values(input.inner_mut()), vec![0, 50, 100, 150, 200]);
+    }
+}
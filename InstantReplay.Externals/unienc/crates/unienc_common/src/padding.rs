@@ -0,0 +1,410 @@
+//! Video sizes every platform encodes the same way. H.264 and the intermediate codecs subsample
+//! chroma by two, and platform encoders disagree on odd sizes: Media Foundation rejects them,
+//! VideoToolbox accepts some, and MediaCodec pads them itself. Encoders are instead created at the
+//! size rounded up to even, frames in memory are padded by repeating their last column and row,
+//! and the finished file carries a clean aperture (`clap`) with the original size, so players crop
+//! the padding away.
+
+use std::path::{Path, PathBuf};
+
+use crate::buffer::SharedBuffer;
+use crate::mp4::{
+    BoxResult, be_bytes, children, container, find, grow, read_u32, shift_chunk_offsets,
+    video_track,
+};
+use crate::{
+    CommonError, CompletionHandle, Encoder, EncoderInput, LatencyMode, Muxer, Result, VideoCodec,
+    VideoEncoderOptions, VideoFrame, VideoFrameBgra32, VideoSample,
+};
+
+/// Size encoders are created at for frames of `width` by `height`: both rounded up to even.
+pub fn coded_size(width: u32, height: u32) -> (u32, u32) {
+    (width.next_multiple_of(2), height.next_multiple_of(2))
+}
+
+/// Video encoder options at the coded size of the wrapped options, which backends create their
+/// encoders and muxers with.
+#[derive(Debug, Clone, Copy)]
+pub struct PaddedVideoOptions<V> {
+    inner: V,
+    width: u32,
+    height: u32,
+}
+
+impl<V: VideoEncoderOptions> PaddedVideoOptions<V> {
+    pub fn new(inner: V) -> Self {
+        let (width, height) = coded_size(inner.width(), inner.height());
+        Self {
+            inner,
+            width,
+            height,
+        }
+    }
+
+    /// Options at the original size, for decoders and still images that are not padded.
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    pub fn is_padded(&self) -> bool {
+        (self.width, self.height) != (self.inner.width(), self.inner.height())
+    }
+}
+
+impl<V: VideoEncoderOptions> VideoEncoderOptions for PaddedVideoOptions<V> {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn fps_hint(&self) -> u32 {
+        self.inner.fps_hint()
+    }
+
+    fn bitrate(&self) -> u32 {
+        self.inner.bitrate()
+    }
+
+    fn latency_mode(&self) -> LatencyMode {
+        self.inner.latency_mode()
+    }
+
+    fn preserve_alpha(&self) -> bool {
+        self.inner.preserve_alpha()
+    }
+
+    fn codec(&self) -> VideoCodec {
+        self.inner.codec()
+    }
+}
+
+/// Video encoder whose input pads frames in memory to the coded size of [`PaddedVideoOptions`].
+pub struct PaddedVideoEncoder<E> {
+    inner: E,
+}
+
+impl<E> PaddedVideoEncoder<E> {
+    pub fn new(inner: E) -> Self {
+        Self { inner }
+    }
+}
+
+impl<B: Send + 'static, E: Encoder<InputType: EncoderInput<Data = VideoSample<B>>>> Encoder
+    for PaddedVideoEncoder<E>
+{
+    type InputType = PaddedVideoInput<E::InputType>;
+    type OutputType = E::OutputType;
+
+    fn get(self) -> Result<(Self::InputType, Self::OutputType)> {
+        let (input, output) = self.inner.get()?;
+        Ok((PaddedVideoInput { inner: input }, output))
+    }
+}
+
+/// Pads frames in memory of odd sizes to even. Blitted frames are scaled to the coded size by the
+/// backend, which stretches them by at most a pixel.
+pub struct PaddedVideoInput<I> {
+    inner: I,
+}
+
+impl<I> PaddedVideoInput<I> {
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for PaddedVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, mut data: Self::Data) -> Result<()> {
+        if let VideoFrame::Bgra32(frame) = &data.frame {
+            let (width, height) = coded_size(frame.width, frame.height);
+            if (width, height) != (frame.width, frame.height) {
+                data.frame = VideoFrame::Bgra32(pad_bgra(frame, width, height));
+            }
+        }
+        self.inner.push(data).await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+/// Copies `frame` into a packed frame of `width` by `height`, no smaller than it, repeating its
+/// last column and row into the padding so the encoder does not bleed a border into the picture.
+pub fn pad_bgra(frame: &VideoFrameBgra32, width: u32, height: u32) -> VideoFrameBgra32 {
+    let data = frame.buffer.data();
+    let (src_width, src_height) = (frame.width as usize, frame.height as usize);
+    let mut out = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height as usize {
+        let start = y.min(src_height - 1) * frame.stride as usize;
+        let row = &data[start..start + src_width * 4];
+        out.extend_from_slice(row);
+        let last = &row[row.len() - 4..];
+        for _ in src_width..width as usize {
+            out.extend_from_slice(last);
+        }
+    }
+    VideoFrameBgra32::packed(SharedBuffer::new_unmanaged(out), width, height)
+}
+
+/// Muxer whose file is cropped back to the original size of [`PaddedVideoOptions`] once written.
+pub struct PaddedMuxer<M> {
+    inner: M,
+    path: PathBuf,
+    /// Original size, or `None` if the video is not padded.
+    visible: Option<(u32, u32)>,
+}
+
+impl<M> PaddedMuxer<M> {
+    pub fn new<V: VideoEncoderOptions>(
+        inner: M,
+        path: &Path,
+        options: &PaddedVideoOptions<V>,
+    ) -> Self {
+        let original = options.inner();
+        Self {
+            inner,
+            path: path.to_owned(),
+            visible: options
+                .is_padded()
+                .then(|| (original.width(), original.height())),
+        }
+    }
+
+    /// Muxer whose output is not a file, such as a live stream, which keeps the padding.
+    pub fn streamed(inner: M) -> Self {
+        Self {
+            inner,
+            path: PathBuf::new(),
+            visible: None,
+        }
+    }
+}
+
+impl<M: Muxer<CompletionHandleType: Send>> Muxer for PaddedMuxer<M> {
+    type VideoInputType = M::VideoInputType;
+    type AudioInputType = M::AudioInputType;
+    type CompletionHandleType = CleanApertureCompletionHandle<M::CompletionHandleType>;
+
+    fn get_inputs(
+        self,
+    ) -> Result<(
+        Self::VideoInputType,
+        Self::AudioInputType,
+        Self::CompletionHandleType,
+    )> {
+        let (video_input, audio_input, completion_handle) = self.inner.get_inputs()?;
+        Ok((
+            video_input,
+            audio_input,
+            CleanApertureCompletionHandle {
+                inner: completion_handle,
+                path: self.path,
+                visible: self.visible,
+            },
+        ))
+    }
+}
+
+/// Completion handle that adds the clean aperture to the file once the wrapped muxer has written
+/// it, if the video is padded.
+pub struct CleanApertureCompletionHandle<C> {
+    inner: C,
+    path: PathBuf,
+    visible: Option<(u32, u32)>,
+}
+
+impl<C: CompletionHandle + Send> CompletionHandle for CleanApertureCompletionHandle<C> {
+    async fn finish(self) -> Result<()> {
+        self.inner.finish().await?;
+        let Some((width, height)) = self.visible else {
+            return Ok(());
+        };
+        let file =
+            std::fs::read(&self.path).map_err(|e| CommonError::CleanAperture(e.to_string()))?;
+        let file = add_clean_aperture(&file, width, height)?;
+        std::fs::write(&self.path, file).map_err(|e| CommonError::CleanAperture(e.to_string()))
+    }
+}
+
+/// Returns a copy of an MP4 or QuickTime file whose video shows only its top left `width` by
+/// `height` pixels: the track header takes that size and the sample entry gets a `clap` box, and a
+/// square `pasp` box unless it has one. The file is returned as is if the sample entry is no larger.
+pub fn add_clean_aperture(file: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    crop(file, width, height)
+        .map_err(|reason| CommonError::CleanAperture(format!("unsupported MP4 file: {reason}")))
+}
+
+fn crop(file: &[u8], width: u32, height: u32) -> BoxResult<Vec<u8>> {
+    let moov = find(file, 0..file.len(), b"moov")?;
+    let trak = video_track(file, &moov)?;
+    let tkhd = find(file, trak.content(), b"tkhd")?;
+    let mdia = find(file, trak.content(), b"mdia")?;
+    let minf = find(file, mdia.content(), b"minf")?;
+    let stbl = find(file, minf.content(), b"stbl")?;
+    let stsd = find(file, stbl.content(), b"stsd")?;
+    // version, flags and entry count precede the sample entries
+    let entries = stsd.content().start + 8..stsd.end;
+    let entry = children(file, entries)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "no sample entry".to_string())?;
+
+    // coded width and height follow the 24 bytes of reserved and predefined fields
+    let coded = read_u32(file, entry.content().start + 24)?;
+    let (coded_width, coded_height) = (coded >> 16, coded & 0xffff);
+    if width >= coded_width && height >= coded_height {
+        return Ok(file.to_vec());
+    }
+    let (width, height) = (width.min(coded_width), height.min(coded_height));
+
+    // extension boxes follow the 78 bytes of the visual sample entry
+    let extensions = children(file, entry.content().start + 78..entry.end)?;
+    // offsets of the aperture center from the picture center, as fractions over 2
+    let offset = |visible: u32, coded: u32| (visible as i32 - coded as i32) as u32;
+    let mut boxes = container(
+        b"clap",
+        &[be_bytes(&[
+            width,
+            1,
+            height,
+            1,
+            offset(width, coded_width),
+            2,
+            offset(height, coded_height),
+            2,
+        ])],
+    );
+    if !extensions.iter().any(|b| &b.kind == b"pasp") {
+        boxes.extend(container(b"pasp", &[be_bytes(&[1, 1])]));
+    }
+    let insert_at = entry.end;
+    let delta = boxes.len();
+
+    let mut out = file.to_vec();
+    // the track header ends with its width and height in 16.16 fixed point
+    out[tkhd.end - 8..tkhd.end].copy_from_slice(&be_bytes(&[width << 16, height << 16]));
+    shift_chunk_offsets(&mut out, &moov, insert_at, delta)?;
+    for parent in [moov, trak, mdia, minf, stbl, stsd, entry] {
+        grow(&mut out, &parent, delta)?;
+    }
+    out.splice(insert_at..insert_at, boxes);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4::full_box;
+
+    fn plain(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut b = ((8 + payload.len()) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(kind);
+        b.extend_from_slice(payload);
+        b
+    }
+
+    fn video_file(width: u16, height: u16) -> Vec<u8> {
+        let mut tkhd = vec![0; 72];
+        tkhd.extend(be_bytes(&[(width as u32) << 16, (height as u32) << 16]));
+        let mut hdlr = vec![0; 4];
+        hdlr.extend_from_slice(b"vide");
+        hdlr.extend_from_slice(&[0; 13]);
+        let mut avc1 = vec![0; 24];
+        avc1.extend_from_slice(&width.to_be_bytes());
+        avc1.extend_from_slice(&height.to_be_bytes());
+        avc1.resize(78, 0);
+        let mut stsd = 1u32.to_be_bytes().to_vec();
+        stsd.extend(plain(b"avc1", &avc1));
+        let ftyp = plain(b"ftyp", b"isom");
+        let mdat = plain(b"mdat", &[1, 2]);
+        let stco = [1u32, (ftyp.len() + 8) as u32];
+        let trak = container(
+            b"trak",
+            &[
+                full_box(b"tkhd", &tkhd),
+                container(
+                    b"mdia",
+                    &[
+                        full_box(b"hdlr", &hdlr),
+                        container(
+                            b"minf",
+                            &[container(
+                                b"stbl",
+                                &[
+                                    full_box(b"stsd", &stsd),
+                                    full_box(b"stco", &be_bytes(&stco)),
+                                ],
+                            )],
+                        ),
+                    ],
+                ),
+            ],
+        );
+        [ftyp, mdat, container(b"moov", &[trak])].concat()
+    }
+
+    #[test]
+    fn pads_frames_by_repeating_edges() {
+        // 3x1 frame with a stride of 4 pixels
+        let data = [1, 2, 3, 9].iter().flat_map(|&v| [v; 4]).collect();
+        let frame = VideoFrameBgra32 {
+            buffer: SharedBuffer::new_unmanaged(data),
+            width: 3,
+            height: 1,
+            stride: 16,
+        };
+        assert_eq!(coded_size(3, 1), (4, 2));
+        let padded = pad_bgra(&frame, 4, 2);
+        let gray = padded
+            .buffer
+            .data()
+            .chunks(4)
+            .map(|p| p[0])
+            .collect::<Vec<_>>();
+        assert_eq!(gray, [1, 2, 3, 3, 1, 2, 3, 3]);
+        assert_eq!(coded_size(1920, 1080), (1920, 1080));
+    }
+
+    #[test]
+    fn crops_to_the_original_size() {
+        let file = video_file(1080, 1920);
+        let out = add_clean_aperture(&file, 1079, 1919).unwrap();
+
+        let moov = find(&out, 0..out.len(), b"moov").unwrap();
+        let trak = video_track(&out, &moov).unwrap();
+        let tkhd = find(&out, trak.content(), b"tkhd").unwrap();
+        assert_eq!(read_u32(&out, tkhd.end - 8).unwrap(), 1079 << 16);
+        assert_eq!(read_u32(&out, tkhd.end - 4).unwrap(), 1919 << 16);
+
+        let stsd = [b"mdia", b"minf", b"stbl", b"stsd"]
+            .iter()
+            .fold(trak, |b, kind| find(&out, b.content(), kind).unwrap());
+        let entry = children(&out, stsd.content().start + 8..stsd.end).unwrap()[0];
+        let extensions = children(&out, entry.content().start + 78..entry.end).unwrap();
+        assert_eq!(
+            extensions.iter().map(|b| &b.kind).collect::<Vec<_>>(),
+            [b"clap", b"pasp"]
+        );
+        let clap = extensions[0].content();
+        let fields = (0..8)
+            .map(|i| read_u32(&out, clap.start + i * 4).unwrap() as i32)
+            .collect::<Vec<_>>();
+        assert_eq!(fields, [1079, 1, 1919, 1, -1, 2, -1, 2]);
+
+        // the media data precedes the movie header, so its offset is unchanged
+        let mdat = find(&out, 0..out.len(), b"mdat").unwrap();
+        assert_eq!(&out[mdat.content()], &[1, 2]);
+
+        assert_eq!(add_clean_aperture(&file, 1080, 1920).unwrap(), file);
+    }
+}
@@ -0,0 +1,820 @@
+//! Muxing of elementary streams that were encoded outside of unienc.
+//!
+//! Packetizers convert H.264 access units and raw AAC frames into the data each platform muxer
+//! accepts, so the muxers can be used standalone without instantiating the matching encoders.
+
+use crate::{CommonError, ErrorCategory, Result};
+
+/// Converts H.264 access units into muxer input data.
+pub trait H264Packetizer: Send + 'static {
+    type Data: Send;
+
+    /// Converts a single access unit in Annex-B byte stream format. `timestamp` is the presentation
+    /// time in seconds. The first access unit must be an IDR frame carrying SPS and PPS.
+    fn packetize(&mut self, access_unit: &[u8], timestamp: f64) -> Result<Vec<Self::Data>>;
+}
+
+/// Converts raw AAC-LC frames into muxer input data.
+pub trait AacPacketizer: Send + 'static {
+    type Data: Send;
+
+    /// Converts a single raw AAC frame (without ADTS header). `timestamp_in_samples` is the
+    /// presentation time of the frame in samples at the configured sample rate.
+    fn packetize(&mut self, frame: &[u8], timestamp_in_samples: u64) -> Result<Vec<Self::Data>>;
+}
+
+fn invalid_input(message: impl Into<String>) -> CommonError {
+    CommonError::Categorized {
+        category: ErrorCategory::InvalidInput,
+        message: message.into(),
+    }
+}
+
+pub mod h264 {
+    use super::invalid_input;
+    use crate::Result;
+
+    pub const NAL_UNIT_TYPE_SLICE: u8 = 1;
+    pub const NAL_UNIT_TYPE_IDR: u8 = 5;
+    pub const NAL_UNIT_TYPE_SEI: u8 = 6;
+    pub const NAL_UNIT_TYPE_SPS: u8 = 7;
+    pub const NAL_UNIT_TYPE_PPS: u8 = 8;
+    pub const NAL_UNIT_TYPE_AUD: u8 = 9;
+
+    /// Returns `nal_unit_type` of a NAL unit without start code.
+    pub fn nal_unit_type(nal_unit: &[u8]) -> Option<u8> {
+        nal_unit.first().map(|header| header & 0x1f)
+    }
+
+    /// Iterates over NAL units in an Annex-B byte stream. Yielded NAL units do not include start
+    /// codes.
+    pub fn annexb_nal_units(data: &[u8]) -> AnnexBNalUnits<'_> {
+        AnnexBNalUnits { data }
+    }
+
+    pub struct AnnexBNalUnits<'a> {
+        data: &'a [u8],
+    }
+
+    /// Finds the next 0x000001 start code. Returns the position the start code begins at, including
+    /// preceding zero bytes, and the position the NAL unit content starts at.
+    pub fn find_start_code(data: &[u8]) -> Option<(usize, usize)> {
+        data.windows(3)
+            .position(|window| window == [0x00, 0x00, 0x01])
+            .map(|pos| {
+                let mut start = pos;
+                while start > 0 && data[start - 1] == 0x00 {
+                    start -= 1;
+                }
+                (start, pos + 3)
+            })
+    }
+
+    impl<'a> Iterator for AnnexBNalUnits<'a> {
+        type Item = &'a [u8];
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let (_, content_start) = find_start_code(self.data)?;
+            let rest = &self.data[content_start..];
+            let end = find_start_code(rest).map_or(rest.len(), |(next, _)| next);
+            let nal_unit = &rest[..end];
+            self.data = &rest[end..];
+            Some(nal_unit)
+        }
+    }
+
+    /// An access unit split into its parameter sets and picture data.
+    pub struct AccessUnit<'a> {
+        pub sps: Option<&'a [u8]>,
+        pub pps: Option<&'a [u8]>,
+        /// Slice and SEI NAL units, excluding parameter sets and access unit delimiters.
+        pub nal_units: Vec<&'a [u8]>,
+        pub is_idr: bool,
+    }
+
+    impl<'a> AccessUnit<'a> {
+        pub fn parse(data: &'a [u8]) -> Result<Self> {
+            let mut access_unit = AccessUnit {
+                sps: None,
+                pps: None,
+                nal_units: Vec::new(),
+                is_idr: false,
+            };
+
+            for nal_unit in annexb_nal_units(data) {
+                match nal_unit_type(nal_unit) {
+                    Some(NAL_UNIT_TYPE_SPS) => access_unit.sps = Some(nal_unit),
+                    Some(NAL_UNIT_TYPE_PPS) => access_unit.pps = Some(nal_unit),
+                    Some(NAL_UNIT_TYPE_AUD) | None => {}
+                    Some(ty) => {
+                        if ty == NAL_UNIT_TYPE_IDR {
+                            access_unit.is_idr = true;
+                        }
+                        access_unit.nal_units.push(nal_unit);
+                    }
+                }
+            }
+
+            if access_unit.nal_units.is_empty() {
+                return Err(invalid_input("H.264 access unit contains no slice"));
+            }
+
+            Ok(access_unit)
+        }
+
+        /// Picture data with 4-byte length prefixes (AVCC), as expected by MP4 muxers.
+        pub fn to_length_prefixed(&self) -> Vec<u8> {
+            let mut data =
+                Vec::with_capacity(self.nal_units.iter().map(|n| n.len() + 4).sum::<usize>());
+            for nal_unit in &self.nal_units {
+                data.extend_from_slice(&(nal_unit.len() as u32).to_be_bytes());
+                data.extend_from_slice(nal_unit);
+            }
+            data
+        }
+
+        /// Picture data with 4-byte start codes (Annex-B).
+        pub fn to_annexb(&self) -> Vec<u8> {
+            let mut data =
+                Vec::with_capacity(self.nal_units.iter().map(|n| n.len() + 4).sum::<usize>());
+            for nal_unit in &self.nal_units {
+                data.extend_from_slice(&[0, 0, 0, 1]);
+                data.extend_from_slice(nal_unit);
+            }
+            data
+        }
+    }
+
+    /// How NAL units are delimited in encoded video data.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum NalFormat {
+        /// Start codes, as produced by MediaCodec and Media Foundation.
+        AnnexB,
+        /// Big-endian length prefixes (AVCC), as stored in MP4 and produced by VideoToolbox.
+        LengthPrefixed { length_size: usize },
+    }
+
+    impl NalFormat {
+        /// Format of `data`: Annex-B when it starts with a start code, otherwise 4-byte length
+        /// prefixes, which is what every backend producing them uses.
+        pub fn detect(data: &[u8]) -> Self {
+            if data.starts_with(&[0, 0, 1]) || data.starts_with(&[0, 0, 0, 1]) {
+                NalFormat::AnnexB
+            } else {
+                NalFormat::LengthPrefixed { length_size: 4 }
+            }
+        }
+    }
+
+    /// Splits encoded data into NAL units without start codes or length prefixes.
+    pub fn nal_units(data: &[u8], format: NalFormat) -> Result<Vec<&[u8]>> {
+        let length_size = match format {
+            NalFormat::AnnexB => return Ok(annexb_nal_units(data).collect()),
+            NalFormat::LengthPrefixed { length_size } => length_size,
+        };
+        if !(1..=4).contains(&length_size) {
+            return Err(invalid_input(format!(
+                "Invalid NAL unit length size: {length_size}"
+            )));
+        }
+
+        let mut nal_units = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (length, tail) = rest
+                .split_at_checked(length_size)
+                .ok_or_else(|| invalid_input("Truncated NAL unit length"))?;
+            let length = length
+                .iter()
+                .fold(0usize, |length, byte| length << 8 | *byte as usize);
+            let (nal_unit, tail) = tail
+                .split_at_checked(length)
+                .ok_or_else(|| invalid_input("Truncated NAL unit"))?;
+            nal_units.push(nal_unit);
+            rest = tail;
+        }
+        Ok(nal_units)
+    }
+
+    /// SPS and PPS NAL units of encoded data, in order.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct ParameterSets<'a> {
+        pub sps: Vec<&'a [u8]>,
+        pub pps: Vec<&'a [u8]>,
+    }
+
+    impl<'a> ParameterSets<'a> {
+        pub fn extract(data: &'a [u8], format: NalFormat) -> Result<Self> {
+            let mut parameter_sets = Self::default();
+            for nal_unit in nal_units(data, format)? {
+                match nal_unit_type(nal_unit) {
+                    Some(NAL_UNIT_TYPE_SPS) => parameter_sets.sps.push(nal_unit),
+                    Some(NAL_UNIT_TYPE_PPS) => parameter_sets.pps.push(nal_unit),
+                    _ => {}
+                }
+            }
+            Ok(parameter_sets)
+        }
+
+        /// The first SPS, parsed.
+        pub fn sequence(&self) -> Result<SequenceParameterSet> {
+            let sps = self
+                .sps
+                .first()
+                .ok_or_else(|| invalid_input("No H.264 sequence parameter set"))?;
+            SequenceParameterSet::parse(sps)
+        }
+    }
+
+    /// Fields of a sequence parameter set (ITU-T H.264 7.3.2.1.1) that describe the stream.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SequenceParameterSet {
+        pub profile_idc: u8,
+        /// `constraint_set0_flag` to `constraint_set5_flag` and the reserved bits, as in `avcC`.
+        pub constraint_flags: u8,
+        /// Ten times the level, such as 31 for level 3.1.
+        pub level_idc: u8,
+        /// 0 for monochrome, 1 for 4:2:0, 2 for 4:2:2 and 3 for 4:4:4.
+        pub chroma_format_idc: u8,
+        pub bit_depth_luma: u8,
+        pub bit_depth_chroma: u8,
+        /// Size of the pictures after cropping, which the coded size is a multiple of 16 of.
+        pub width: u32,
+        pub height: u32,
+    }
+
+    impl SequenceParameterSet {
+        /// Parses an SPS NAL unit without start code or length prefix.
+        pub fn parse(nal_unit: &[u8]) -> Result<Self> {
+            if nal_unit_type(nal_unit) != Some(NAL_UNIT_TYPE_SPS) {
+                return Err(invalid_input("Not an H.264 sequence parameter set"));
+            }
+            let mut reader = BitReader::new(&nal_unit[1..]);
+            let profile_idc = reader.bits(8)? as u8;
+            let constraint_flags = reader.bits(8)? as u8;
+            let level_idc = reader.bits(8)? as u8;
+            reader.ue()?; // seq_parameter_set_id
+
+            let (mut chroma_format_idc, mut bit_depth_luma, mut bit_depth_chroma) = (1, 8, 8);
+            let mut separate_colour_planes = false;
+            if matches!(
+                profile_idc,
+                100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+            ) {
+                chroma_format_idc = reader.ue()?;
+                if chroma_format_idc == 3 {
+                    separate_colour_planes = reader.bit()?;
+                }
+                bit_depth_luma = reader.ue()? + 8;
+                bit_depth_chroma = reader.ue()? + 8;
+                if chroma_format_idc > 3 || bit_depth_luma > 14 || bit_depth_chroma > 14 {
+                    return Err(invalid_input("Invalid H.264 chroma format or bit depth"));
+                }
+                reader.bit()?; // qpprime_y_zero_transform_bypass_flag
+                if reader.bit()? {
+                    let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+                    for list in 0..lists {
+                        if reader.bit()? {
+                            reader.skip_scaling_list(if list < 6 { 16 } else { 64 })?;
+                        }
+                    }
+                }
+            }
+
+            reader.ue()?; // log2_max_frame_num_minus4
+            match reader.ue()? {
+                0 => {
+                    reader.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+                }
+                1 => {
+                    reader.bit()?; // delta_pic_order_always_zero_flag
+                    reader.se()?; // offset_for_non_ref_pic
+                    reader.se()?; // offset_for_top_to_bottom_field
+                    for _ in 0..reader.ue()? {
+                        reader.se()?; // offset_for_ref_frame
+                    }
+                }
+                _ => {}
+            }
+            reader.ue()?; // max_num_ref_frames
+            reader.bit()?; // gaps_in_frame_num_value_allowed_flag
+            let width_in_mbs = reader.ue()?.saturating_add(1);
+            let height_in_map_units = reader.ue()?.saturating_add(1);
+            let frame_mbs_only = reader.bit()?;
+            if !frame_mbs_only {
+                reader.bit()?; // mb_adaptive_frame_field_flag
+            }
+            reader.bit()?; // direct_8x8_inference_flag
+            let fields = if frame_mbs_only { 1 } else { 2 };
+            let (Some(mut width), Some(mut height)) = (
+                width_in_mbs.checked_mul(16),
+                height_in_map_units.checked_mul(16 * fields),
+            ) else {
+                return Err(invalid_input("Invalid H.264 picture size"));
+            };
+
+            if reader.bit()? {
+                let (unit_x, unit_y) = match (chroma_format_idc, separate_colour_planes) {
+                    (0, _) | (3, true) => (1, fields),
+                    (1, _) => (2, 2 * fields),
+                    (2, _) => (2, fields),
+                    _ => (1, fields),
+                };
+                let (left, right) = (reader.ue()?, reader.ue()?);
+                let (top, bottom) = (reader.ue()?, reader.ue()?);
+                let invalid = || invalid_input("Invalid H.264 frame cropping");
+                width = left
+                    .checked_add(right)
+                    .and_then(|crop| crop.checked_mul(unit_x))
+                    .and_then(|crop| width.checked_sub(crop))
+                    .filter(|&width| width > 0)
+                    .ok_or_else(invalid)?;
+                height = top
+                    .checked_add(bottom)
+                    .and_then(|crop| crop.checked_mul(unit_y))
+                    .and_then(|crop| height.checked_sub(crop))
+                    .filter(|&height| height > 0)
+                    .ok_or_else(invalid)?;
+            }
+
+            Ok(Self {
+                profile_idc,
+                constraint_flags,
+                level_idc,
+                chroma_format_idc: chroma_format_idc as u8,
+                bit_depth_luma: bit_depth_luma as u8,
+                bit_depth_chroma: bit_depth_chroma as u8,
+                width,
+                height,
+            })
+        }
+
+        /// RFC 6381 codec string such as `avc1.64001f`, for HLS playlists and MIME types.
+        pub fn codec_string(&self) -> String {
+            format!(
+                "avc1.{:02x}{:02x}{:02x}",
+                self.profile_idc, self.constraint_flags, self.level_idc
+            )
+        }
+
+        /// Whether `avcC` records the chroma format and bit depths for the profile.
+        fn has_format_extension(&self) -> bool {
+            matches!(self.profile_idc, 100 | 110 | 122 | 144)
+        }
+    }
+
+    /// Reader of the bits of an RBSP, skipping emulation prevention bytes.
+    struct BitReader<'a> {
+        data: &'a [u8],
+        byte: usize,
+        bit: u32,
+        zeros: usize,
+    }
+
+    impl<'a> BitReader<'a> {
+        fn new(data: &'a [u8]) -> Self {
+            Self {
+                data,
+                byte: 0,
+                bit: 0,
+                zeros: 0,
+            }
+        }
+
+        fn bit(&mut self) -> Result<bool> {
+            if self.bit == 0 {
+                // 0x03 after two zero bytes only keeps start codes out of the payload
+                if self.zeros >= 2 && self.data.get(self.byte) == Some(&0x03) {
+                    self.byte += 1;
+                    self.zeros = 0;
+                }
+                let Some(&byte) = self.data.get(self.byte) else {
+                    return Err(invalid_input("Truncated H.264 sequence parameter set"));
+                };
+                self.zeros = if byte == 0 { self.zeros + 1 } else { 0 };
+            }
+            let value = self.data[self.byte] >> (7 - self.bit) & 1 == 1;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.byte += 1;
+            }
+            Ok(value)
+        }
+
+        fn bits(&mut self, count: u32) -> Result<u32> {
+            (0..count).try_fold(0, |value, _| Ok(value << 1 | self.bit()? as u32))
+        }
+
+        /// Unsigned Exp-Golomb code.
+        fn ue(&mut self) -> Result<u32> {
+            let mut leading_zeros = 0;
+            while !self.bit()? {
+                leading_zeros += 1;
+                if leading_zeros > 31 {
+                    return Err(invalid_input("Invalid Exp-Golomb code"));
+                }
+            }
+            Ok(((1u64 << leading_zeros) - 1 + self.bits(leading_zeros)? as u64) as u32)
+        }
+
+        /// Signed Exp-Golomb code.
+        fn se(&mut self) -> Result<i32> {
+            let code = self.ue()?;
+            let magnitude = code.div_ceil(2) as i32;
+            Ok(if code % 2 == 1 { magnitude } else { -magnitude })
+        }
+
+        fn skip_scaling_list(&mut self, size: usize) -> Result<()> {
+            let (mut last, mut next) = (8, 8);
+            for _ in 0..size {
+                if next != 0 {
+                    next = (last + self.se()? + 256) % 256;
+                }
+                if next != 0 {
+                    last = next;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// AVCDecoderConfigurationRecord (ISO/IEC 14496-15): the payload of an `avcC` box, which is
+    /// also the `avcC` atom of VideoToolbox format descriptions.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct DecoderConfigurationRecord {
+        /// Size of the NAL unit length prefixes of the samples it describes.
+        pub length_size: usize,
+        pub sps: Vec<Vec<u8>>,
+        pub pps: Vec<Vec<u8>>,
+    }
+
+    impl DecoderConfigurationRecord {
+        /// A record for samples with 4-byte length prefixes.
+        pub fn new(sps: Vec<u8>, pps: Vec<u8>) -> Self {
+            Self {
+                length_size: 4,
+                sps: vec![sps],
+                pps: vec![pps],
+            }
+        }
+
+        pub fn parse(data: &[u8]) -> Result<Self> {
+            let truncated = || invalid_input("Truncated AVC decoder configuration record");
+            let (header, mut rest) = data.split_at_checked(6).ok_or_else(truncated)?;
+            if header[0] != 1 {
+                return Err(invalid_input(format!(
+                    "Unsupported AVC decoder configuration version: {}",
+                    header[0]
+                )));
+            }
+            let length_size = (header[4] & 0x03) as usize + 1;
+
+            let sps = read_parameter_sets(&mut rest, (header[5] & 0x1f) as usize)?;
+            let (pps_count, mut rest) = rest.split_first().ok_or_else(truncated)?;
+            let pps = read_parameter_sets(&mut rest, *pps_count as usize)?;
+
+            Ok(Self {
+                length_size,
+                sps,
+                pps,
+            })
+        }
+
+        pub fn to_bytes(&self) -> Result<Vec<u8>> {
+            // profile_idc, constraint flags and level_idc follow the SPS NAL unit header
+            let Some(profile) = self.sps.first().and_then(|sps| sps.get(1..4)) else {
+                return Err(invalid_input("AVC decoder configuration requires an SPS"));
+            };
+            if !(1..=4).contains(&self.length_size) || self.length_size == 3 {
+                return Err(invalid_input(format!(
+                    "Invalid NAL unit length size: {}",
+                    self.length_size
+                )));
+            }
+            if self.sps.len() > 31 || self.pps.len() > 255 {
+                return Err(invalid_input("Too many H.264 parameter sets"));
+            }
+
+            let mut data = vec![1];
+            data.extend_from_slice(profile);
+            data.push(0xfc | (self.length_size as u8 - 1));
+            data.push(0xe0 | self.sps.len() as u8);
+            write_parameter_sets(&mut data, &self.sps);
+            data.push(self.pps.len() as u8);
+            write_parameter_sets(&mut data, &self.pps);
+            // the high profiles add the chroma format and bit depths, without SPS extensions
+            if let Ok(sps) = SequenceParameterSet::parse(&self.sps[0])
+                && sps.has_format_extension()
+            {
+                data.extend_from_slice(&[
+                    0xfc | sps.chroma_format_idc,
+                    0xf8 | (sps.bit_depth_luma - 8),
+                    0xf8 | (sps.bit_depth_chroma - 8),
+                    0,
+                ]);
+            }
+            Ok(data)
+        }
+    }
+
+    fn read_parameter_sets(data: &mut &[u8], count: usize) -> Result<Vec<Vec<u8>>> {
+        let truncated = || invalid_input("Truncated AVC decoder configuration record");
+        let mut parameter_sets = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (length, rest) = data.split_at_checked(2).ok_or_else(truncated)?;
+            let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+            let (parameter_set, rest) = rest.split_at_checked(length).ok_or_else(truncated)?;
+            parameter_sets.push(parameter_set.to_vec());
+            *data = rest;
+        }
+        Ok(parameter_sets)
+    }
+
+    fn write_parameter_sets(data: &mut Vec<u8>, parameter_sets: &[Vec<u8>]) {
+        for parameter_set in parameter_sets {
+            data.extend_from_slice(&(parameter_set.len() as u16).to_be_bytes());
+            data.extend_from_slice(parameter_set);
+        }
+    }
+
+    /// Encoded H.264 picture in the form every [`H264Packetizer`](super::H264Packetizer) accepts:
+    /// an Annex-B access unit that carries SPS and PPS when it is an IDR frame. Samples of one
+    /// backend are converted to it with [`AccessUnitNormalizer`] to be muxed by another.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct EncodedAccessUnit {
+        pub data: Vec<u8>,
+        /// Presentation time in seconds.
+        pub timestamp: f64,
+        pub is_idr: bool,
+    }
+
+    /// Converts encoded samples into [`EncodedAccessUnit`]s, keeping the latest parameter sets to
+    /// repeat them before IDR frames whose samples do not carry them in-band.
+    #[derive(Default)]
+    pub struct AccessUnitNormalizer {
+        sps: Option<Vec<u8>>,
+        pps: Option<Vec<u8>>,
+    }
+
+    impl AccessUnitNormalizer {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Uses the parameter sets of an out-of-band configuration, such as an `avcC` box.
+        pub fn set_configuration(&mut self, record: &DecoderConfigurationRecord) {
+            if let Some(sps) = record.sps.first() {
+                self.sps = Some(sps.clone());
+            }
+            if let Some(pps) = record.pps.first() {
+                self.pps = Some(pps.clone());
+            }
+        }
+
+        /// Returns `None` for samples that only carry parameter sets, such as codec config buffers.
+        pub fn normalize(
+            &mut self,
+            data: &[u8],
+            format: NalFormat,
+            timestamp: f64,
+        ) -> Result<Option<EncodedAccessUnit>> {
+            let mut picture = Vec::new();
+            let mut is_idr = false;
+            for nal_unit in nal_units(data, format)? {
+                match nal_unit_type(nal_unit) {
+                    Some(NAL_UNIT_TYPE_SPS) => self.sps = Some(nal_unit.to_vec()),
+                    Some(NAL_UNIT_TYPE_PPS) => self.pps = Some(nal_unit.to_vec()),
+                    Some(NAL_UNIT_TYPE_AUD) | None => {}
+                    Some(ty) => {
+                        is_idr |= ty == NAL_UNIT_TYPE_IDR;
+                        picture.push(nal_unit);
+                    }
+                }
+            }
+            if picture.is_empty() {
+                return Ok(None);
+            }
+
+            let mut data = Vec::new();
+            if is_idr {
+                let (Some(sps), Some(pps)) = (&self.sps, &self.pps) else {
+                    return Err(invalid_input(
+                        "H.264 IDR frame arrived before its parameter sets",
+                    ));
+                };
+                for parameter_set in [sps, pps] {
+                    data.extend_from_slice(&[0, 0, 0, 1]);
+                    data.extend_from_slice(parameter_set);
+                }
+            }
+            for nal_unit in picture {
+                data.extend_from_slice(&[0, 0, 0, 1]);
+                data.extend_from_slice(nal_unit);
+            }
+            Ok(Some(EncodedAccessUnit {
+                data,
+                timestamp,
+                is_idr,
+            }))
+        }
+    }
+}
+
+pub mod aac {
+    use super::invalid_input;
+    use crate::Result;
+
+    /// Samples per AAC-LC frame.
+    pub const SAMPLES_PER_FRAME: u64 = 1024;
+
+    const SAMPLING_FREQUENCIES: [u32; 13] = [
+        96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+    ];
+
+    pub fn sampling_frequency_index(sample_rate: u32) -> Result<u8> {
+        SAMPLING_FREQUENCIES
+            .iter()
+            .position(|&f| f == sample_rate)
+            .map(|i| i as u8)
+            .ok_or_else(|| invalid_input(format!("Unsupported AAC sample rate: {sample_rate}")))
+    }
+
+    fn channel_configuration(channels: u32) -> Result<u8> {
+        match channels {
+            1..=6 => Ok(channels as u8),
+            8 => Ok(7),
+            _ => Err(invalid_input(format!(
+                "Unsupported AAC channel count: {channels}"
+            ))),
+        }
+    }
+
+    /// AudioSpecificConfig (ISO/IEC 14496-3) for AAC-LC.
+    pub fn audio_specific_config(sample_rate: u32, channels: u32) -> Result<[u8; 2]> {
+        const AOT_AAC_LC: u8 = 2;
+        let freq = sampling_frequency_index(sample_rate)?;
+        let channels = channel_configuration(channels)?;
+        Ok([
+            (AOT_AAC_LC << 3) | (freq >> 1),
+            ((freq & 1) << 7) | (channels << 3),
+        ])
+    }
+
+    /// ES_Descriptor (ISO/IEC 14496-1) wrapping the AudioSpecificConfig, in the form used as the
+    /// payload of an `esds` box and as the AAC magic cookie on Apple platforms.
+    pub fn es_descriptor(sample_rate: u32, channels: u32, bitrate: u32) -> Result<Vec<u8>> {
+        const ES_DESCR_TAG: u8 = 0x03;
+        const DECODER_CONFIG_DESCR_TAG: u8 = 0x04;
+        const DEC_SPECIFIC_INFO_TAG: u8 = 0x05;
+        const SL_CONFIG_DESCR_TAG: u8 = 0x06;
+        const OBJECT_TYPE_MPEG4_AUDIO: u8 = 0x40;
+        const STREAM_TYPE_AUDIO: u8 = 0x05;
+
+        let asc = audio_specific_config(sample_rate, channels)?;
+
+        let mut decoder_config = vec![OBJECT_TYPE_MPEG4_AUDIO, (STREAM_TYPE_AUDIO << 2) | 1];
+        decoder_config.extend_from_slice(&[0, 0, 0]); // bufferSizeDB
+        decoder_config.extend_from_slice(&bitrate.to_be_bytes()); // maxBitrate
+        decoder_config.extend_from_slice(&bitrate.to_be_bytes()); // avgBitrate
+        decoder_config.extend_from_slice(&[DEC_SPECIFIC_INFO_TAG, asc.len() as u8]);
+        decoder_config.extend_from_slice(&asc);
+
+        let mut es = vec![0, 0, 0]; // ES_ID and flags
+        es.extend_from_slice(&[DECODER_CONFIG_DESCR_TAG, decoder_config.len() as u8]);
+        es.extend_from_slice(&decoder_config);
+        es.extend_from_slice(&[SL_CONFIG_DESCR_TAG, 1, 0x02]);
+
+        let mut descriptor = vec![ES_DESCR_TAG, es.len() as u8];
+        descriptor.extend_from_slice(&es);
+        Ok(descriptor)
+    }
+
+    /// ADTS header for an AAC-LC frame of `payload_len` bytes.
+    pub fn adts_header(sample_rate: u32, channels: u32, payload_len: usize) -> Result<[u8; 7]> {
+        const PROFILE_AAC_LC: u8 = 1; // object type - 1
+        let freq = sampling_frequency_index(sample_rate)?;
+        let channels = channel_configuration(channels)?;
+        let frame_len = payload_len + 7;
+        if frame_len > 0x1fff {
+            return Err(invalid_input("AAC frame is too large for ADTS"));
+        }
+        Ok([
+            0xff,
+            0xf1, // MPEG-4, layer 0, no CRC
+            (PROFILE_AAC_LC << 6) | (freq << 2) | (channels >> 2),
+            ((channels & 3) << 6) | ((frame_len >> 11) as u8),
+            ((frame_len >> 3) & 0xff) as u8,
+            (((frame_len & 7) as u8) << 5) | 0x1f,
+            0xfc,
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn access_unit_parse_splits_parameter_sets_and_slices() {
+        let data = [
+            0, 0, 0, 1, 0x09, 0xf0, // AUD
+            0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1e, // SPS
+            0, 0, 1, 0x68, 0xce, // PPS (3-byte start code)
+            0, 0, 0, 1, 0x65, 0x88, 0x80, // IDR slice
+        ];
+        let au = h264::AccessUnit::parse(&data).unwrap();
+        assert_eq!(au.sps, Some(&[0x67, 0x42, 0x00, 0x1e][..]));
+        assert_eq!(au.pps, Some(&[0x68, 0xce][..]));
+        assert!(au.is_idr);
+        assert_eq!(au.to_length_prefixed(), vec![0, 0, 0, 3, 0x65, 0x88, 0x80]);
+    }
+
+    #[test]
+    fn normalizer_repeats_out_of_band_parameter_sets_before_idr() {
+        let sps = vec![0x67, 0x42, 0x00, 0x1e];
+        let pps = vec![0x68, 0xce];
+        let record = h264::DecoderConfigurationRecord::new(sps.clone(), pps.clone());
+        let record = h264::DecoderConfigurationRecord::parse(&record.to_bytes().unwrap()).unwrap();
+        assert_eq!((record.sps[0].clone(), record.pps[0].clone()), (sps, pps));
+
+        let mut normalizer = h264::AccessUnitNormalizer::new();
+        let avcc = h264::NalFormat::LengthPrefixed { length_size: 4 };
+        let idr = [0, 0, 0, 3, 0x65, 0x88, 0x80];
+        assert!(normalizer.normalize(&idr, avcc, 0.0).is_err());
+
+        normalizer.set_configuration(&record);
+        let access_unit = normalizer.normalize(&idr, avcc, 0.5).unwrap().unwrap();
+        assert!(access_unit.is_idr);
+        let parsed = h264::AccessUnit::parse(&access_unit.data).unwrap();
+        assert_eq!(parsed.sps, Some(&[0x67, 0x42, 0x00, 0x1e][..]));
+        assert_eq!(parsed.to_length_prefixed(), idr);
+
+        // codec config buffers only update the parameter sets
+        let config = [0, 0, 0, 1, 0x67, 0x64, 0x00, 0x28, 0, 0, 0, 1, 0x68, 0xee];
+        let annexb = h264::NalFormat::AnnexB;
+        assert_eq!(normalizer.normalize(&config, annexb, 1.0).unwrap(), None);
+        assert!(
+            h264::nal_units(&[0, 0, 0, 9, 0x41], avcc).is_err(),
+            "truncated NAL unit"
+        );
+    }
+
+    #[test]
+    fn reads_the_stream_format_from_the_sps() {
+        // High profile level 4.0, 1920x1088 cropped to 1080
+        let sps = [
+            0x67, 0x64, 0x00, 0x28, 0xac, 0xd9, 0x40, 0x78, 0x02, 0x27, 0xe5, 0x40,
+        ];
+        let parsed = h264::SequenceParameterSet::parse(&sps).unwrap();
+        assert_eq!((parsed.width, parsed.height), (1920, 1080));
+        assert_eq!((parsed.profile_idc, parsed.level_idc), (100, 40));
+        assert_eq!(parsed.codec_string(), "avc1.640028");
+        assert!(h264::SequenceParameterSet::parse(&sps[..8]).is_err());
+
+        // Baseline 640x480 with emulation prevention bytes in a long Exp-Golomb code
+        let escaped = [
+            0x67, 0x42, 0xc0, 0x1e, 0x80, 0x00, 0x00, 0x03, 0x01, 0x00, 0x00, 0x03, 0x00, 0x01,
+            0x90, 0x0a, 0x03, 0xd9,
+        ];
+        let parsed = h264::SequenceParameterSet::parse(&escaped).unwrap();
+        assert_eq!((parsed.width, parsed.height), (640, 480));
+        assert_eq!(parsed.codec_string(), "avc1.42c01e");
+
+        let mut length_prefixed = vec![0, 0, 0, sps.len() as u8];
+        length_prefixed.extend_from_slice(&sps);
+        length_prefixed.extend_from_slice(&[0, 0, 0, 2, 0x68, 0xce]);
+        let format = h264::NalFormat::detect(&length_prefixed);
+        assert_eq!(format, h264::NalFormat::LengthPrefixed { length_size: 4 });
+        let parameter_sets = h264::ParameterSets::extract(&length_prefixed, format).unwrap();
+        assert_eq!(parameter_sets.pps, [&[0x68, 0xce][..]]);
+        assert_eq!(parameter_sets.sequence().unwrap().height, 1080);
+
+        // the high profiles record their chroma format and bit depths
+        let record = h264::DecoderConfigurationRecord::new(sps.to_vec(), vec![0x68, 0xce]);
+        let bytes = record.to_bytes().unwrap();
+        assert_eq!(bytes[bytes.len() - 4..], [0xfd, 0xf8, 0xf8, 0x00]);
+        assert_eq!(
+            h264::DecoderConfigurationRecord::parse(&bytes).unwrap(),
+            record
+        );
+    }
+
+    #[test]
+    fn aac_headers_match_reference_values() {
+        // 48 kHz stereo AAC-LC
+        assert_eq!(aac::audio_specific_config(48000, 2).unwrap(), [0x11, 0x90]);
+        assert_eq!(
+            aac::adts_header(48000, 2, 100).unwrap(),
+            [0xff, 0xf1, 0x4c, 0x80, 0x0d, 0x7f, 0xfc]
+        );
+        assert!(aac::sampling_frequency_index(12345).is_err());
+
+        let esds = aac::es_descriptor(48000, 2, 128_000).unwrap();
+        assert_eq!(esds[..2], [0x03, esds.len() as u8 - 2]);
+        assert_eq!(
+            esds[esds.len() - 7..],
+            [0x05, 0x02, 0x11, 0x90, 0x06, 0x01, 0x02]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! Forwarding of encoder output to a muxer input, shared by every backend.
+//!
+//! [`drive`] pulls encoded samples and pushes them to the muxer concurrently, through a bounded
+//! queue so that a slow push does not stop the encoder from being drained. It is a plain future
+//! and runs on whichever runtime polls it.
+
+use std::collections::VecDeque;
+use std::future::{Future, poll_fn};
+use std::pin::{Pin, pin};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Poll, Waker};
+
+use crate::{CommonError, EncoderOutput, MuxerInput, Result};
+
+/// Stops the pipelines it is passed to. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<CancellationState>,
+}
+
+#[derive(Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+        let wakers = std::mem::take(&mut *lock(&self.inner.wakers));
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Runs `future` until it completes or the token is cancelled, in which case `future` is
+    /// dropped and [`CommonError::Cancelled`] is returned.
+    pub async fn run<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        let mut future = pin!(future);
+        poll_fn(|cx| {
+            if self.is_cancelled() {
+                return Poll::Ready(Err(CommonError::Cancelled));
+            }
+            if let Poll::Ready(result) = future.as_mut().poll(cx) {
+                return Poll::Ready(result);
+            }
+            let mut wakers = lock(&self.inner.wakers);
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+            drop(wakers);
+            // cancelled before the waker was registered
+            if self.is_cancelled() {
+                return Poll::Ready(Err(CommonError::Cancelled));
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// Pulls every sample of `output` into `input`, and finishes `input` once the encoder has ended.
+/// Up to `capacity` samples are buffered while a push is in progress. Returns the number of
+/// samples forwarded.
+///
+/// The first error of either side stops both right away, and `input` is then dropped without
+/// being finished, as it is when `cancel` is cancelled.
+pub async fn drive<O, I>(
+    mut output: O,
+    mut input: I,
+    capacity: usize,
+    cancel: &CancellationToken,
+) -> Result<u64>
+where
+    O: EncoderOutput,
+    I: MuxerInput<Data = O::Data>,
+{
+    let queue = Queue::new(capacity.max(1));
+
+    cancel
+        .run(async {
+            let forwarded = {
+                let producer = pin!(async {
+                    while let Some(sample) = output.pull().await? {
+                        queue.send(sample).await;
+                    }
+                    queue.close();
+                    Ok(())
+                });
+                let consumer = pin!(async {
+                    let mut forwarded = 0;
+                    while let Some(sample) = queue.recv().await {
+                        input.push(sample).await?;
+                        forwarded += 1;
+                    }
+                    Ok(forwarded)
+                });
+
+                try_join(producer, consumer).await?.1
+            };
+            input.finish().await?;
+            Ok(forwarded)
+        })
+        .await
+}
+
+/// Polls both futures until both succeed or either fails.
+pub(crate) async fn try_join<A, B>(
+    mut a: Pin<&mut impl Future<Output = Result<A>>>,
+    mut b: Pin<&mut impl Future<Output = Result<B>>>,
+) -> Result<(A, B)> {
+    let (mut a_output, mut b_output) = (None, None);
+    poll_fn(|cx| {
+        if a_output.is_none()
+            && let Poll::Ready(output) = a.as_mut().poll(cx)
+        {
+            a_output = Some(output?);
+        }
+        if b_output.is_none()
+            && let Poll::Ready(output) = b.as_mut().poll(cx)
+        {
+            b_output = Some(output?);
+        }
+        if a_output.is_some() && b_output.is_some() {
+            Poll::Ready(Ok((a_output.take().unwrap(), b_output.take().unwrap())))
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+struct Queue<T> {
+    capacity: usize,
+    state: Mutex<QueueState<T>>,
+}
+
+struct QueueState<T> {
+    items: VecDeque<T>,
+    closed: bool,
+    producer: Option<Waker>,
+    consumer: Option<Waker>,
+}
+
+impl<T> Queue<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(QueueState {
+                items: VecDeque::with_capacity(capacity),
+                closed: false,
+                producer: None,
+                consumer: None,
+            }),
+        }
+    }
+
+    async fn send(&self, item: T) {
+        let mut item = Some(item);
+        poll_fn(|cx| {
+            let mut state = lock(&self.state);
+            if state.items.len() < self.capacity {
+                state.items.extend(item.take());
+                if let Some(consumer) = state.consumer.take() {
+                    consumer.wake();
+                }
+                Poll::Ready(())
+            } else {
+                state.producer = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Returns `None` once the queue is closed and every item has been received.
+    async fn recv(&self) -> Option<T> {
+        poll_fn(|cx| {
+            let mut state = lock(&self.state);
+            if let Some(item) = state.items.pop_front() {
+                if let Some(producer) = state.producer.take() {
+                    producer.wake();
+                }
+                return Poll::Ready(Some(item));
+            }
+            if state.closed {
+                return Poll::Ready(None);
+            }
+            state.consumer = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn close(&self) {
+        let mut state = lock(&self.state);
+        state.closed = true;
+        if let Some(consumer) = state.consumer.take() {
+            consumer.wake();
+        }
+    }
+}
+
+// wakers and queued samples stay consistent even if a holder panicked
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use crate::{EncodedData, UniencSampleKind};
+    use bincode::{Decode, Encode};
+
+    #[derive(Encode, Decode)]
+    struct Sample(f64);
+
+    impl EncodedData for Sample {
+        fn timestamp(&self) -> f64 {
+            self.0
+        }
+        fn set_timestamp(&mut self, timestamp: f64) {
+            self.0 = timestamp;
+        }
+        fn kind(&self) -> UniencSampleKind {
+            UniencSampleKind::Interpolated
+        }
+        fn size(&self) -> usize {
+            size_of::<f64>()
+        }
+    }
+
+    struct Output {
+        remaining: u32,
+        fail: bool,
+    }
+
+    impl EncoderOutput for Output {
+        type Data = Sample;
+
+        async fn pull(&mut self) -> Result<Option<Sample>> {
+            if self.remaining == 0 {
+                return match self.fail {
+                    true => Err(CommonError::Other("encoder failed".to_string())),
+                    false => Ok(None),
+                };
+            }
+            self.remaining -= 1;
+            Ok(Some(Sample(self.remaining as f64)))
+        }
+    }
+
+    #[derive(Default)]
+    struct Input {
+        pushed: Arc<Mutex<Vec<f64>>>,
+        finished: Arc<AtomicBool>,
+        fail_after: Option<usize>,
+    }
+
+    impl MuxerInput for Input {
+        type Data = Sample;
+
+        async fn push(&mut self, data: Sample) -> Result<()> {
+            let mut pushed = lock(&self.pushed);
+            if self.fail_after.is_some_and(|limit| pushed.len() >= limit) {
+                return Err(CommonError::Other("muxer failed".to_string()));
+            }
+            pushed.push(data.0);
+            Ok(())
+        }
+
+        async fn finish(self) -> Result<()> {
+            self.finished.store(true, Ordering::Release);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn forwards_every_sample_and_finishes() {
+        let input = Input::default();
+        let (pushed, finished) = (input.pushed.clone(), input.finished.clone());
+        let output = Output {
+            remaining: 5,
+            fail: false,
+        };
+
+        let cancel = CancellationToken::new();
+        let pipeline = drive(output, input, 2, &cancel);
+        // backends spawn it on multi-threaded runtimes
+        fn assert_send(_: &impl Send) {}
+        assert_send(&pipeline);
+
+        let forwarded = block_on(pipeline).unwrap();
+        assert_eq!(forwarded, 5);
+        assert_eq!(*lock(&pushed), vec![4.0, 3.0, 2.0, 1.0, 0.0]);
+        assert!(finished.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn errors_stop_without_finishing() {
+        let input = Input::default();
+        let finished = input.finished.clone();
+        let output = Output {
+            remaining: 3,
+            fail: true,
+        };
+        let result = block_on(drive(output, input, 4, &CancellationToken::new()));
+        assert!(matches!(result, Err(CommonError::Other(message)) if message == "encoder failed"));
+        assert!(!finished.load(Ordering::Acquire));
+
+        let input = Input {
+            fail_after: Some(1),
+            ..Default::default()
+        };
+        let output = Output {
+            remaining: 10,
+            fail: false,
+        };
+        let result = block_on(drive(output, input, 4, &CancellationToken::new()));
+        assert!(matches!(result, Err(CommonError::Other(message)) if message == "muxer failed"));
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let output = Output {
+            remaining: 1,
+            fail: false,
+        };
+        let result = block_on(drive(output, Input::default(), 4, &cancel));
+        assert!(matches!(result, Err(CommonError::Cancelled)));
+    }
+}
@@ -0,0 +1,220 @@
+//! Pixel formats CPU frames can be pushed in. Encoders only take BGRA, so other formats are
+//! converted once when the frame is pushed.
+
+use crate::buffer::SharedBuffer;
+use crate::{CommonError, Result, VideoFrameBgra32};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Bgra32,
+    Rgba32,
+    /// 16-bit little-endian, red in the high bits.
+    Rgb565,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Bgra32 | PixelFormat::Rgba32 => 4,
+            PixelFormat::Rgb565 => 2,
+        }
+    }
+}
+
+/// Takes pixels in `format` with rows `stride` bytes apart, or tightly packed if `stride` is 0.
+/// RGBA is swizzled in place, keeping the stride, and RGB565 is expanded into a new packed
+/// buffer.
+pub fn frame_from_pixels(
+    mut buffer: SharedBuffer,
+    width: u32,
+    height: u32,
+    stride: u32,
+    format: PixelFormat,
+) -> Result<VideoFrameBgra32> {
+    let row = width * format.bytes_per_pixel() as u32;
+    let stride = match stride {
+        0 => row,
+        stride if stride < row => {
+            return Err(CommonError::FrameStrideTooSmall { stride, row });
+        }
+        stride => stride,
+    };
+    let len = match height {
+        0 => 0,
+        height => (height - 1) as usize * stride as usize + row as usize,
+    };
+    if buffer.data().len() < len {
+        return Err(CommonError::FrameBufferTooSmall {
+            expected: len,
+            actual: buffer.data().len(),
+        });
+    }
+    let (stride, row) = (stride as usize, row as usize);
+    match format {
+        PixelFormat::Bgra32 => {}
+        PixelFormat::Rgba32 => {
+            let data = buffer.data_mut();
+            for y in 0..height as usize {
+                swap_red_blue(&mut data[y * stride..][..row]);
+            }
+        }
+        PixelFormat::Rgb565 => {
+            let packed = width as usize * 4;
+            let mut bgra = vec![0; packed * height as usize];
+            for y in 0..height as usize {
+                rgb565_to_bgra(
+                    &buffer.data()[y * stride..][..row],
+                    &mut bgra[y * packed..][..packed],
+                );
+            }
+            return Ok(VideoFrameBgra32::packed(
+                SharedBuffer::new_unmanaged(bgra),
+                width,
+                height,
+            ));
+        }
+    }
+    Ok(VideoFrameBgra32 {
+        buffer,
+        width,
+        height,
+        stride: stride as u32,
+    })
+}
+
+/// Converts RGBA to BGRA and back.
+fn swap_red_blue(pixels: &mut [u8]) {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("ssse3") {
+        // SAFETY: SSSE3 was detected at runtime
+        unsafe { swap_red_blue_ssse3(pixels) };
+        return;
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        // SAFETY: NEON is part of the aarch64 baseline
+        unsafe { swap_red_blue_neon(pixels) };
+        return;
+    }
+    #[allow(unreachable_code)]
+    swap_red_blue_scalar(pixels);
+}
+
+fn swap_red_blue_scalar(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn swap_red_blue_ssse3(pixels: &mut [u8]) {
+    use std::arch::x86_64::*;
+
+    let shuffle = _mm_setr_epi8(2, 1, 0, 3, 6, 5, 4, 7, 10, 9, 8, 11, 14, 13, 12, 15);
+    let mut chunks = pixels.chunks_exact_mut(16);
+    for chunk in &mut chunks {
+        let ptr = chunk.as_mut_ptr() as *mut __m128i;
+        // SAFETY: the chunk is 16 bytes long
+        unsafe { _mm_storeu_si128(ptr, _mm_shuffle_epi8(_mm_loadu_si128(ptr), shuffle)) };
+    }
+    swap_red_blue_scalar(chunks.into_remainder());
+}
+
+#[cfg(target_arch = "aarch64")]
+unsafe fn swap_red_blue_neon(pixels: &mut [u8]) {
+    use std::arch::aarch64::*;
+
+    let mut chunks = pixels.chunks_exact_mut(64);
+    for chunk in &mut chunks {
+        // SAFETY: the chunk is 64 bytes long
+        unsafe {
+            let mut channels = vld4q_u8(chunk.as_ptr());
+            std::mem::swap(&mut channels.0, &mut channels.2);
+            vst4q_u8(chunk.as_mut_ptr(), channels);
+        }
+    }
+    swap_red_blue_scalar(chunks.into_remainder());
+}
+
+/// Channels are widened by replicating their high bits, so full intensity stays 255. Written as
+/// independent per-pixel arithmetic so the compiler vectorizes it.
+fn rgb565_to_bgra(pixels: &[u8], bgra: &mut [u8]) {
+    for (pixel, out) in pixels.chunks_exact(2).zip(bgra.chunks_exact_mut(4)) {
+        let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+        let r = (value >> 11) as u8 & 0x1f;
+        let g = (value >> 5) as u8 & 0x3f;
+        let b = value as u8 & 0x1f;
+        out[0] = (b << 3) | (b >> 2);
+        out[1] = (g << 2) | (g >> 4);
+        out[2] = (r << 3) | (r >> 2);
+        out[3] = 0xff;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_to_bgra() {
+        // not a multiple of the vector width, so the remainder is converted too
+        let rgba = (0..4 * 37).map(|i| i as u8).collect::<Vec<_>>();
+        let frame = frame_from_pixels(
+            SharedBuffer::new_unmanaged(rgba.clone()),
+            37,
+            1,
+            0,
+            PixelFormat::Rgba32,
+        )
+        .unwrap();
+        let mut expected = rgba;
+        swap_red_blue_scalar(&mut expected);
+        assert_eq!(frame.buffer.data(), expected);
+
+        // red, green / blue, white, in rows padded to 6 bytes
+        let rgb565 = [0xf800u16, 0x07e0, 0, 0x001f, 0xffff, 0]
+            .iter()
+            .flat_map(|p| p.to_le_bytes())
+            .collect::<Vec<_>>();
+        let frame = frame_from_pixels(
+            SharedBuffer::new_unmanaged(rgb565),
+            2,
+            2,
+            6,
+            PixelFormat::Rgb565,
+        )
+        .unwrap();
+        assert_eq!(frame.stride, 8);
+        assert_eq!(
+            frame.buffer.data(),
+            [
+                0, 0, 255, 255, 0, 255, 0, 255, 255, 0, 0, 255, 255, 255, 255, 255
+            ]
+        );
+
+        assert!(matches!(
+            frame_from_pixels(
+                SharedBuffer::new_unmanaged(vec![0; 7]),
+                2,
+                1,
+                0,
+                PixelFormat::Bgra32
+            ),
+            Err(CommonError::FrameBufferTooSmall {
+                expected: 8,
+                actual: 7
+            })
+        ));
+        assert!(matches!(
+            frame_from_pixels(
+                SharedBuffer::new_unmanaged(vec![0; 16]),
+                2,
+                2,
+                6,
+                PixelFormat::Rgba32
+            ),
+            Err(CommonError::FrameStrideTooSmall { stride: 6, row: 8 })
+        ));
+    }
+}
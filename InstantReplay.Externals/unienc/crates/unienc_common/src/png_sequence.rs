@@ -0,0 +1,307 @@
+//! PNG sequence of a recording, the fallback for keeping the alpha channel on backends whose
+//! encoders cannot (see [`EncodingSystem::is_alpha_supported`](crate::EncodingSystem)). Every
+//! frame is written as an RGBA PNG into a directory, with a JSON index of the frame timestamps so
+//! editors can import the sequence at its real timing.
+//!
+//! Frames are taken from frames pushed as BGRA pixels; blit sources are not read back.
+
+mod png;
+
+use std::path::PathBuf;
+
+use crate::{CommonError, EncoderInput, Result, VideoFrame, VideoFrameBgra32, VideoSample};
+
+pub struct PngSequence {
+    directory: PathBuf,
+    start: Option<f64>,
+    /// Seconds since the first frame, per frame written.
+    times: Vec<f64>,
+}
+
+impl PngSequence {
+    /// Frames are named `frame_000000.png`, `frame_000001.png`, ... and the index `frames.json`.
+    /// `directory` is created if missing.
+    pub fn new(directory: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&directory)
+            .map_err(|e| CommonError::PngSequenceIo(e.to_string()))?;
+        Ok(Self {
+            directory,
+            start: None,
+            times: Vec::new(),
+        })
+    }
+
+    pub fn push(&mut self, frame: &VideoFrameBgra32, timestamp: f64) -> Result<()> {
+        // malformed frames are reported by the encoder
+        let data = frame.buffer.data();
+        if frame.width == 0
+            || frame.height == 0
+            || frame.stride < frame.width * 4
+            || data.len() < frame.min_buffer_len()
+        {
+            return Ok(());
+        }
+
+        let png = png::encode(data, frame.width, frame.height, frame.stride);
+        let name = frame_name(self.times.len());
+        std::fs::write(self.directory.join(name), png)
+            .map_err(|e| CommonError::PngSequenceIo(e.to_string()))?;
+        let start = *self.start.get_or_insert(timestamp);
+        self.times.push(timestamp - start);
+        Ok(())
+    }
+
+    /// Writes the index.
+    pub fn finish(self) -> Result<()> {
+        std::fs::write(self.directory.join("frames.json"), self.index())
+            .map_err(|e| CommonError::PngSequenceIo(e.to_string()))
+    }
+
+    fn index(&self) -> String {
+        let frames = self
+            .times
+            .iter()
+            .enumerate()
+            .map(|(index, time)| format!(r#"{{"file":"{}","time":{time}}}"#, frame_name(index)))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(r#"{{"frames":[{frames}]}}"#)
+    }
+}
+
+fn frame_name(index: usize) -> String {
+    format!("frame_{index:06}.png")
+}
+
+/// Video encoder input that also writes the frames pushed as BGRA pixels to a PNG sequence once
+/// one is set. Sequence failures do not interrupt encoding; the first one is reported by
+/// [`finish_png_sequence`](Self::finish_png_sequence).
+pub struct PngSequenceVideoInput<I> {
+    inner: I,
+    sequence: Option<PngSequence>,
+    error: Option<CommonError>,
+}
+
+impl<I> PngSequenceVideoInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            sequence: None,
+            error: None,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    pub fn set_png_sequence(&mut self, sequence: PngSequence) {
+        self.sequence = Some(sequence);
+        self.error = None;
+    }
+
+    /// Writes the index of the sequence and stops writing frames.
+    pub fn finish_png_sequence(&mut self) -> Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        match self.sequence.take() {
+            Some(sequence) => sequence.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for PngSequenceVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        if let (Some(sequence), VideoFrame::Bgra32(frame)) = (&mut self.sequence, &data.frame)
+            && let Err(error) = sequence.push(frame, data.timestamp)
+        {
+            self.sequence = None;
+            self.error = Some(error);
+        }
+        self.inner.push(data).await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::SharedBuffer;
+
+    /// Inflates a zlib stream of fixed Huffman blocks, the only kind the encoder writes.
+    fn inflate(zlib: &[u8]) -> Vec<u8> {
+        let data = &zlib[2..zlib.len() - 4];
+        let mut bit = 0usize;
+        let mut read = |count: u32| {
+            let mut value = 0u32;
+            for i in 0..count {
+                value |= ((data[bit / 8] >> (bit % 8)) as u32 & 1) << i;
+                bit += 1;
+            }
+            value
+        };
+        assert_eq!(read(3), 0b011);
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            // fixed Huffman codes are read most significant bit first
+            let mut code = 0u32;
+            let mut length = 0;
+            let symbol = loop {
+                code = (code << 1) | read(1);
+                length += 1;
+                match (length, code) {
+                    (7, 0..=0x17) => break code + 256,
+                    (8, 0x30..=0xbf) => break code - 0x30,
+                    (8, 0xc0..=0xc7) => break code - 0xc0 + 280,
+                    (9, 0x190..=0x1ff) => break code - 0x190 + 144,
+                    _ => assert!(length < 9),
+                }
+            };
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let i = symbol as usize - 257;
+                    let length =
+                        png::LENGTH_BASE[i] as usize + read(png::LENGTH_EXTRA[i] as u32) as usize;
+                    let i = (0..5).fold(0, |code, _| (code << 1) | read(1)) as usize;
+                    let distance = png::DISTANCE_BASE[i] as usize
+                        + read(png::DISTANCE_EXTRA[i] as u32) as usize;
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Decodes the filtered rows of an RGBA PNG written by the encoder.
+    fn decode(file: &[u8]) -> (u32, u32, Vec<u8>) {
+        assert_eq!(&file[..8], b"\x89PNG\r\n\x1a\n");
+        let width = u32::from_be_bytes(file[16..20].try_into().unwrap());
+        let height = u32::from_be_bytes(file[20..24].try_into().unwrap());
+        assert_eq!(&file[24..29], &[8, 6, 0, 0, 0]);
+        let idat = 8 + 25;
+        let length = u32::from_be_bytes(file[idat..idat + 4].try_into().unwrap()) as usize;
+        assert_eq!(&file[idat + 4..idat + 8], b"IDAT");
+        let filtered = inflate(&file[idat + 8..idat + 8 + length]);
+
+        let row_len = width as usize * 4;
+        let mut pixels = vec![0u8; row_len * height as usize];
+        for y in 0..height as usize {
+            let filter = filtered[y * (row_len + 1)];
+            for i in 0..row_len {
+                let value = filtered[y * (row_len + 1) + 1 + i];
+                let a = if i >= 4 {
+                    pixels[y * row_len + i - 4]
+                } else {
+                    0
+                };
+                let b = if y > 0 {
+                    pixels[(y - 1) * row_len + i]
+                } else {
+                    0
+                };
+                let c = if i >= 4 && y > 0 {
+                    pixels[(y - 1) * row_len + i - 4]
+                } else {
+                    0
+                };
+                let predictor = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    4 => {
+                        let p = a as i16 + b as i16 - c as i16;
+                        let (pa, pb, pc) = (
+                            (p - a as i16).abs(),
+                            (p - b as i16).abs(),
+                            (p - c as i16).abs(),
+                        );
+                        if pa <= pb && pa <= pc {
+                            a
+                        } else if pb <= pc {
+                            b
+                        } else {
+                            c
+                        }
+                    }
+                    _ => panic!("filter {filter}"),
+                };
+                pixels[y * row_len + i] = value.wrapping_add(predictor);
+            }
+        }
+        (width, height, pixels)
+    }
+
+    #[test]
+    fn frames_keep_alpha_through_padded_rows() {
+        let (width, height, stride) = (37u32, 23u32, 37 * 4 + 12);
+        let mut bgra = vec![0u8; (stride * height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * stride + x * 4) as usize;
+                // a gradient overlay with a transparent border
+                let alpha = if x < 4 || y < 4 {
+                    0
+                } else {
+                    (x * 7 + y * 3) as u8
+                };
+                bgra[i..i + 4].copy_from_slice(&[x as u8 * 5, y as u8 * 9, (x ^ y) as u8, alpha]);
+            }
+        }
+
+        let file = png::encode(&bgra, width, height, stride);
+        let (decoded_width, decoded_height, rgba) = decode(&file);
+        assert_eq!((decoded_width, decoded_height), (width, height));
+        for y in 0..height {
+            for x in 0..width {
+                let source = &bgra[(y * stride + x * 4) as usize..][..4];
+                let decoded = &rgba[((y * width + x) * 4) as usize..][..4];
+                assert_eq!(
+                    decoded,
+                    &[source[2], source[1], source[0], source[3]],
+                    "{x} {y}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn frames_and_index_are_written_into_the_directory() {
+        let directory = std::env::temp_dir().join(format!("png-sequence-{}", std::process::id()));
+        let mut sequence = PngSequence::new(directory.clone()).unwrap();
+        for i in 0..3 {
+            let frame = VideoFrameBgra32::packed(
+                SharedBuffer::new_unmanaged(vec![i as u8 * 60; 8 * 4 * 4]),
+                8,
+                4,
+            );
+            sequence.push(&frame, 3.0 + i as f64 * 0.25).unwrap();
+        }
+        sequence.finish().unwrap();
+
+        let frame = std::fs::read(directory.join("frame_000002.png")).unwrap();
+        let (_, _, rgba) = decode(&frame);
+        assert!(rgba.iter().all(|value| *value == 120));
+        let index = std::fs::read_to_string(directory.join("frames.json")).unwrap();
+        assert_eq!(
+            index,
+            r#"{"frames":[{"file":"frame_000000.png","time":0},{"file":"frame_000001.png","time":0.25},{"file":"frame_000002.png","time":0.5}]}"#
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+//! PNG encoder for sequence frames: 8-bit RGBA rows filtered with the minimum sum of absolute
+//! differences heuristic, compressed into a single fixed Huffman deflate block. Overlays are mostly
+//! flat or fully transparent, which this compresses well without dynamic Huffman tables.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+pub(super) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(super) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+pub(super) const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(super) const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const WINDOW: usize = 1 << 15;
+const HASH_BITS: u32 = 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Candidates compared per position; more barely helps on game frames.
+const MAX_CHAIN: usize = 32;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = match c & 1 {
+                1 => 0xedb88320 ^ (c >> 1),
+                _ => c >> 1,
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for byte in chunks.iter().flat_map(|chunk| chunk.iter()) {
+        crc = CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // largest block that cannot overflow before the modulo
+    for block in data.chunks(5552) {
+        for byte in block {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Deflate writes bits from the least significant end; Huffman codes are reversed beforehand.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, bits: u32, length: u8) {
+        self.buffer |= (bits as u64 & ((1 << length) - 1)) << self.count;
+        self.count += length as u32;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn write_code(&mut self, code: u32, length: u8) {
+        self.write(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn flush(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn write_literal(writer: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASE.partition_point(|base| *base as usize <= length) - 1;
+    write_literal(writer, 257 + code as u16);
+    writer.write(
+        (length - LENGTH_BASE[code] as usize) as u32,
+        LENGTH_EXTRA[code],
+    );
+    let code = DISTANCE_BASE.partition_point(|base| *base as usize <= distance) - 1;
+    writer.write_code(code as u32, 5);
+    writer.write(
+        (distance - DISTANCE_BASE[code] as usize) as u32,
+        DISTANCE_EXTRA[code],
+    );
+}
+
+fn hash(data: &[u8]) -> usize {
+    let value = u32::from_le_bytes([data[0], data[1], data[2], 0]);
+    (value.wrapping_mul(0x9e3779b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Greedy LZ77 over a hash chain, in one final fixed Huffman block.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        bytes: Vec::with_capacity(data.len() / 4),
+        buffer: 0,
+        count: 0,
+    };
+    // BFINAL, fixed Huffman
+    writer.write(0b011, 3);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let insert = |head: &mut [usize], prev: &mut [usize], pos: usize| {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(&data[pos..]);
+            prev[pos % WINDOW] = head[h];
+            head[h] = pos;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        if pos + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash(&data[pos..])];
+            let mut chain = 0;
+            while candidate != usize::MAX && pos - candidate < WINDOW && chain < MAX_CHAIN {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[pos..pos + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    (best_length, best_distance) = (length, pos - candidate);
+                    if length == max_length {
+                        break;
+                    }
+                }
+                let next = prev[candidate % WINDOW];
+                // entries older than the window were overwritten by newer positions
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            write_match(&mut writer, best_length, best_distance);
+            for p in pos..pos + best_length {
+                insert(&mut head, &mut prev, p);
+            }
+            pos += best_length;
+        } else {
+            write_literal(&mut writer, data[pos] as u16);
+            insert(&mut head, &mut prev, pos);
+            pos += 1;
+        }
+    }
+    write_literal(&mut writer, 256);
+    writer.flush()
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Value the filter type `filter` predicts for byte `i` of `row`, from the bytes to its left and
+/// the ones of `prior`, the row above.
+fn predict(filter: u8, row: &[u8], prior: &[u8], i: usize) -> u8 {
+    let a = if i >= 4 { row[i - 4] } else { 0 };
+    let b = prior[i];
+    let c = if i >= 4 { prior[i - 4] } else { 0 };
+    match filter {
+        0 => 0,
+        1 => a,
+        2 => b,
+        3 => ((a as u16 + b as u16) / 2) as u8,
+        _ => paeth(a, b, c),
+    }
+}
+
+/// Appends `row` filtered with the filter type whose bytes have the smallest sum as signed values.
+fn filter_row(out: &mut Vec<u8>, row: &[u8], prior: &[u8]) {
+    let filter = (0..5u8)
+        .min_by_key(|filter| {
+            (0..row.len())
+                .map(|i| {
+                    (row[i].wrapping_sub(predict(*filter, row, prior, i)) as i8).unsigned_abs()
+                        as u64
+                })
+                .sum::<u64>()
+        })
+        .unwrap_or_default();
+    out.push(filter);
+    out.extend((0..row.len()).map(|i| row[i].wrapping_sub(predict(filter, row, prior, i))));
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out.extend_from_slice(&crc32(&[kind, payload]).to_be_bytes());
+}
+
+/// Encodes `height` rows of `width` BGRA pixels, `stride` bytes apart, as an RGBA PNG file.
+pub(super) fn encode(bgra: &[u8], width: u32, height: u32, stride: u32) -> Vec<u8> {
+    let row_len = width as usize * 4;
+    let mut filtered = Vec::with_capacity((row_len + 1) * height as usize);
+    let mut prior = vec![0u8; row_len];
+    let mut row = vec![0u8; row_len];
+    for y in 0..height as usize {
+        let source = &bgra[y * stride as usize..][..row_len];
+        for (rgba, bgra) in row.chunks_exact_mut(4).zip(source.chunks_exact(4)) {
+            rgba.copy_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+        }
+        filter_row(&mut filtered, &row, &prior);
+        std::mem::swap(&mut prior, &mut row);
+    }
+
+    // zlib: 32K window, no dictionary, default level
+    let mut zlib = vec![0x78, 0x9c];
+    zlib.extend_from_slice(&deflate(&filtered));
+    zlib.extend_from_slice(&adler32(&filtered).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per sample, RGBA, deflate, adaptive filtering, no interlace
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut out = Vec::with_capacity(zlib.len() + 64);
+    out.extend_from_slice(&SIGNATURE);
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"IDAT", &zlib);
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
@@ -0,0 +1,165 @@
+//! Unity profiler markers shared by the backends. A marker is created the first time it is begun,
+//! in the category and with the name prefix the host set by then, so captures of several native
+//! plugins can be told apart. Markers do nothing without the `profiler` feature or until
+//! [`unity_plugin_load`] found Unity's profiler.
+
+use std::marker::PhantomData;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{CommonError, Result};
+
+#[cfg(feature = "profiler")]
+use std::ffi::{CStr, CString};
+#[cfg(feature = "profiler")]
+use std::sync::OnceLock;
+#[cfg(feature = "profiler")]
+use unity_native_plugin::profiler::{
+    BuiltinProfilerCategory, IUnityProfiler, ProfilerCategoryId, ProfilerMarkerDesc,
+    ProfilerMarkerEventType, ProfilerMarkerFlag, ProfilerMarkerFlags, UnityProfiler,
+};
+
+#[cfg(feature = "profiler")]
+static PROFILER: OnceLock<UnityProfiler> = OnceLock::new();
+
+static NAMING: Mutex<Naming> = Mutex::new(Naming {
+    category: None,
+    prefix: String::new(),
+    frozen: false,
+});
+
+struct Naming {
+    category: Option<u16>,
+    prefix: String,
+    /// Set once a marker is created, as Unity cannot rename or move markers.
+    frozen: bool,
+}
+
+fn lock() -> MutexGuard<'static, Naming> {
+    NAMING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Puts markers in `category`, a Unity `ProfilerCategory` id, or in the builtin `Other` category
+/// if `None`, and prepends `prefix` to their names as it is. Fails once a marker was created, so
+/// call it before the first recording.
+pub fn set_profiler_naming(category: Option<u16>, prefix: &str) -> Result<()> {
+    let mut naming = lock();
+    if naming.frozen {
+        return Err(CommonError::ProfilerMarkersCreated);
+    }
+    naming.category = category;
+    naming.prefix = prefix.to_string();
+    Ok(())
+}
+
+/// Keeps Unity's profiler for the markers if it is available.
+#[cfg(feature = "unity")]
+pub fn unity_plugin_load(interfaces: &unity_native_plugin::interface::UnityInterfaces) {
+    #[cfg(feature = "profiler")]
+    if let Some(profiler) = interfaces.interface::<UnityProfiler>()
+        && profiler.is_available()
+    {
+        _ = PROFILER.set(profiler);
+    }
+    #[cfg(not(feature = "profiler"))]
+    let _ = interfaces;
+}
+
+#[cfg(feature = "profiler")]
+struct MarkerDesc(ProfilerMarkerDesc);
+
+// descriptions are immutable once Unity created them
+#[cfg(feature = "profiler")]
+unsafe impl Send for MarkerDesc {}
+#[cfg(feature = "profiler")]
+unsafe impl Sync for MarkerDesc {}
+
+/// A profiler marker, declared as a `static` at the code it measures.
+pub struct ProfilerMarker {
+    #[cfg_attr(not(feature = "profiler"), allow(dead_code))]
+    name: &'static str,
+    #[cfg(feature = "profiler")]
+    desc: OnceLock<Option<MarkerDesc>>,
+}
+
+impl ProfilerMarker {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            #[cfg(feature = "profiler")]
+            desc: OnceLock::new(),
+        }
+    }
+
+    /// Begins a sample that ends when the guard is dropped. The guard cannot be sent, as Unity
+    /// requires a sample to end on the thread it began on, so it cannot be held across an await.
+    pub fn begin(&'static self) -> ProfilerMarkerGuard {
+        #[cfg(feature = "profiler")]
+        if let Some(profiler) = PROFILER.get()
+            && let Some(desc) = self.desc.get_or_init(|| self.create(profiler))
+        {
+            profiler.emit_event(&desc.0, ProfilerMarkerEventType::Begin, &[]);
+            return ProfilerMarkerGuard {
+                desc: Some(desc),
+                _not_send: PhantomData,
+            };
+        }
+        ProfilerMarkerGuard {
+            #[cfg(feature = "profiler")]
+            desc: None,
+            _not_send: PhantomData,
+        }
+    }
+
+    #[cfg(feature = "profiler")]
+    fn create(&self, profiler: &UnityProfiler) -> Option<MarkerDesc> {
+        let mut naming = lock();
+        naming.frozen = true;
+        let name = CString::new(format!("{}{}", naming.prefix, self.name)).ok()?;
+        // markers live as long as the process, and Unity may keep the name
+        let name: &'static CStr = Box::leak(name.into_boxed_c_str());
+        let category = naming
+            .category
+            .unwrap_or(BuiltinProfilerCategory::Other as ProfilerCategoryId);
+        profiler
+            .create_marker(
+                name,
+                category,
+                ProfilerMarkerFlags::new(ProfilerMarkerFlag::Default),
+                0,
+            )
+            .ok()
+            .map(MarkerDesc)
+    }
+}
+
+pub struct ProfilerMarkerGuard {
+    #[cfg(feature = "profiler")]
+    desc: Option<&'static MarkerDesc>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ProfilerMarkerGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "profiler")]
+        if let (Some(profiler), Some(desc)) = (PROFILER.get(), self.desc) {
+            profiler.emit_event(&desc.0, ProfilerMarkerEventType::End, &[]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_naming_once_a_marker_exists() {
+        set_profiler_naming(Some(3), "Game/").unwrap();
+        assert_eq!(lock().prefix, "Game/");
+        lock().frozen = true;
+        assert!(matches!(
+            set_profiler_naming(None, ""),
+            Err(CommonError::ProfilerMarkersCreated)
+        ));
+        assert_eq!(lock().category, Some(3));
+    }
+}
@@ -0,0 +1,156 @@
+//! Progress of the stages an export goes through, so a hang can be triaged from a report of the
+//! stage that stopped returning instead of a debugger. Each track keeps the operations in flight
+//! at each stage and the last sample the muxer accepted. The progress is kept for the whole
+//! process, like the log capture, and is reset when a muxer is created.
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Track {
+    Video,
+    Audio,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Waiting for the encoder to output a sample.
+    EncoderPull,
+    /// Pushing an encoded sample to the muxer.
+    MuxerPush,
+    /// Finishing the track, or completing the file, which writes what the muxer holds.
+    SinkWrite,
+}
+
+const STAGES: [Stage; 3] = [Stage::EncoderPull, Stage::MuxerPush, Stage::SinkWrite];
+
+struct TrackState {
+    accepted: Option<(f64, Instant)>,
+    /// Start of each operation in flight, per stage.
+    in_flight: [Vec<Instant>; 3],
+}
+
+static PROGRESS: Mutex<[TrackState; 2]> = Mutex::new([
+    TrackState {
+        accepted: None,
+        in_flight: [Vec::new(), Vec::new(), Vec::new()],
+    },
+    TrackState {
+        accepted: None,
+        in_flight: [Vec::new(), Vec::new(), Vec::new()],
+    },
+]);
+
+// plain values that stay consistent even if a holder panicked
+fn lock() -> MutexGuard<'static, [TrackState; 2]> {
+    PROGRESS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// An operation at a stage, in flight until dropped.
+pub struct InFlight {
+    track: Track,
+    stage: Stage,
+    start: Instant,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut tracks = lock();
+        let in_flight = &mut tracks[self.track as usize].in_flight[self.stage as usize];
+        if let Some(index) = in_flight.iter().position(|start| *start == self.start) {
+            in_flight.swap_remove(index);
+        }
+    }
+}
+
+/// Marks an operation of `track` at `stage` as in flight until the returned value is dropped.
+pub fn begin(track: Track, stage: Stage) -> InFlight {
+    let start = Instant::now();
+    lock()[track as usize].in_flight[stage as usize].push(start);
+    InFlight {
+        track,
+        stage,
+        start,
+    }
+}
+
+/// Records that the muxer accepted a sample of `track` with `timestamp` in seconds.
+pub fn accepted(track: Track, timestamp: f64) {
+    lock()[track as usize].accepted = Some((timestamp, Instant::now()));
+}
+
+/// Forgets the samples accepted so far, such as when a new export starts. Operations in flight
+/// are kept as they are still running.
+pub fn reset() {
+    for track in lock().iter_mut() {
+        track.accepted = None;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackProgress {
+    /// Timestamp of the last sample the muxer accepted.
+    pub last_accepted: Option<f64>,
+    /// Time since the muxer accepted that sample.
+    pub since_accepted: Option<Duration>,
+    /// Time the oldest operation in flight at each stage has been running, indexed by [`Stage`].
+    pub in_flight: [Option<Duration>; 3],
+}
+
+impl TrackProgress {
+    /// The stage whose oldest operation in flight has been running the longest, where the track
+    /// is most likely blocked.
+    pub fn blocked(&self) -> Option<(Stage, Duration)> {
+        STAGES
+            .into_iter()
+            .zip(self.in_flight)
+            .filter_map(|(stage, elapsed)| Some((stage, elapsed?)))
+            .max_by_key(|(_, elapsed)| *elapsed)
+    }
+}
+
+/// The progress of `track` as of now.
+pub fn snapshot(track: Track) -> TrackProgress {
+    let now = Instant::now();
+    let tracks = lock();
+    let state = &tracks[track as usize];
+    TrackProgress {
+        last_accepted: state.accepted.map(|(timestamp, _)| timestamp),
+        since_accepted: state.accepted.map(|(_, at)| now - at),
+        in_flight: state
+            .in_flight
+            .each_ref()
+            .map(|starts| starts.iter().min().map(|start| now - *start)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_oldest_stage_in_flight() {
+        // the progress is shared by the process, so this is the only test touching it
+        reset();
+        let pull = begin(Track::Audio, Stage::EncoderPull);
+        std::thread::sleep(Duration::from_millis(5));
+        let push = begin(Track::Audio, Stage::MuxerPush);
+        accepted(Track::Audio, 1.5);
+
+        let progress = snapshot(Track::Audio);
+        assert_eq!(progress.last_accepted, Some(1.5));
+        assert_eq!(progress.blocked().unwrap().0, Stage::EncoderPull);
+        assert!(progress.in_flight[Stage::SinkWrite as usize].is_none());
+
+        drop(pull);
+        assert_eq!(
+            snapshot(Track::Audio).blocked().unwrap().0,
+            Stage::MuxerPush
+        );
+        drop(push);
+        assert_eq!(snapshot(Track::Audio).blocked(), None);
+
+        reset();
+        assert_eq!(snapshot(Track::Audio).last_accepted, None);
+    }
+}
@@ -0,0 +1,115 @@
+//! Encoder settings recommended for recording frames of a given size on the current device, so
+//! that hosts need not hardcode one bitrate for every resolution. Backends describe their encoder
+//! with [`EncoderCapabilities`], and [`recommend`] tunes the settings to it.
+
+/// Area of 1080p, which the reference bitrates are for.
+const REFERENCE_AREA: f64 = 1920.0 * 1080.0;
+/// Frame rate the reference bitrates are for.
+const REFERENCE_FPS: f64 = 30.0;
+/// Lowest bitrate recommended, below which encoders fall apart at any size.
+const MIN_BITRATE: u32 = 500_000;
+/// Seconds between keyframes, short enough for replay clips to start close to where requested.
+const KEYFRAME_INTERVAL_SECONDS: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceClass {
+    /// Phones and tablets, whose encoders share power and heat with the game.
+    Mobile,
+    #[default]
+    Desktop,
+}
+
+/// What the video encoder of the device can do at a frame size, as far as the backend can tell.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EncoderCapabilities {
+    pub device_class: DeviceClass,
+    /// `None` if the backend cannot tell.
+    pub hardware_accelerated: Option<bool>,
+    /// Highest bitrate the encoder accepts, in bits per second.
+    pub max_bitrate: Option<u32>,
+    /// Highest frame rate the encoder supports at the size.
+    pub max_frame_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecommendedOptions {
+    /// In bits per second.
+    pub bitrate: u32,
+    pub fps_hint: u32,
+    /// Frames between keyframes.
+    pub keyframe_interval: u32,
+    /// In bits per second, for stereo.
+    pub audio_bitrate: u32,
+}
+
+/// Settings for recording frames of `width` by `height` with an encoder of `capabilities`. The
+/// bitrate grows slower than the area, since larger frames need fewer bits per pixel for the same
+/// quality, and mobile hardware encoders get more of it for being less efficient per bit.
+pub fn recommend(
+    width: u32,
+    height: u32,
+    capabilities: &EncoderCapabilities,
+) -> RecommendedOptions {
+    let area = width.max(1) as f64 * height.max(1) as f64;
+    let software = capabilities.hardware_accelerated == Some(false);
+    let (reference_bitrate, audio_bitrate) = match capabilities.device_class {
+        DeviceClass::Mobile => (10_000_000.0, 128_000),
+        DeviceClass::Desktop => (8_000_000.0, 192_000),
+    };
+
+    // 60 fps only where the encoder has headroom for it: desktop hardware up to 1440p
+    let mut fps_hint = match capabilities.device_class {
+        DeviceClass::Desktop if !software && area <= 2560.0 * 1440.0 => 60,
+        _ => 30,
+    };
+    if let Some(max_frame_rate) = capabilities.max_frame_rate {
+        fps_hint = fps_hint.min((max_frame_rate as u32).max(1));
+    }
+
+    let scale = (area / REFERENCE_AREA).powf(0.75) * (fps_hint as f64 / REFERENCE_FPS).sqrt();
+    let mut bitrate = ((reference_bitrate * scale) as u32).max(MIN_BITRATE);
+    if let Some(max_bitrate) = capabilities.max_bitrate {
+        bitrate = bitrate.min(max_bitrate.max(1));
+    }
+
+    RecommendedOptions {
+        bitrate,
+        fps_hint,
+        keyframe_interval: fps_hint * KEYFRAME_INTERVAL_SECONDS,
+        audio_bitrate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_with_the_size_and_the_device() {
+        let phone = EncoderCapabilities {
+            device_class: DeviceClass::Mobile,
+            hardware_accelerated: Some(true),
+            max_bitrate: Some(20_000_000),
+            max_frame_rate: None,
+        };
+        let at_1080p = recommend(1920, 1080, &phone);
+        let at_1440p = recommend(2560, 1440, &phone);
+        assert_eq!(at_1080p.fps_hint, 30);
+        assert_eq!(at_1080p.bitrate, 10_000_000);
+        assert!(at_1440p.bitrate > 15_000_000 && at_1440p.bitrate < 16_000_000);
+        assert_eq!(at_1440p.keyframe_interval, 30);
+
+        let limited = EncoderCapabilities {
+            max_bitrate: Some(12_000_000),
+            max_frame_rate: Some(24.0),
+            ..phone
+        };
+        let at_1440p = recommend(2560, 1440, &limited);
+        assert_eq!((at_1440p.bitrate, at_1440p.fps_hint), (12_000_000, 24));
+
+        let desktop = recommend(1920, 1080, &EncoderCapabilities::default());
+        assert_eq!(desktop.fps_hint, 60);
+        assert_eq!(desktop.audio_bitrate, 192_000);
+        assert_eq!(recommend(16, 16, &phone).bitrate, MIN_BITRATE);
+    }
+}
@@ -0,0 +1,757 @@
+//! Reconnection of network outputs, so that a live stream survives a Wi-Fi drop. When a push to
+//! the connection fails, the samples pushed meanwhile are buffered up to a limit, and the next
+//! pushes once a backoff has passed open a new connection, send the metadata samples pushed so
+//! far and then the buffer, starting the video at a keyframe. There is no timer in this crate, so
+//! the attempts are made from pushes, which keep coming while a recording runs.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::{
+    CommonError, CompletionHandle, EncodedData, Muxer, MuxerInput, Result, ResultExt,
+    UniencSampleKind,
+};
+
+/// When reconnection attempts are made and how much is buffered meanwhile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts made after the connection fails before the output gives up; 0 gives up right away.
+    pub max_attempts: u32,
+    /// Wait before the first attempt, doubled after each failed attempt.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Seconds of samples buffered per track while disconnected. Older samples are dropped, video
+    /// up to the next keyframe.
+    pub max_buffered: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+            max_buffered: 10.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before attempt `attempt`, counted from 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(1 << attempt.min(31))
+            .min(self.max_backoff)
+    }
+}
+
+/// Change of the connection of a network output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReconnectEvent {
+    /// A push failed; samples are buffered from now on.
+    Disconnected { error: String },
+    /// Attempt `attempt`, counted from 1, is being made.
+    Reconnecting { attempt: u32 },
+    /// The connection was opened again and the buffer sent. `dropped` samples were lost since the
+    /// disconnection, because the buffer was full or the video waited for a keyframe.
+    Reconnected { attempt: u32, dropped: u64 },
+    /// Every attempt failed; pushes and the completion fail from now on.
+    GaveUp { error: String },
+}
+
+/// How a network output reconnects, and the callback told about it. The callback is invoked from
+/// the pushing thread, without any lock held.
+#[derive(Clone)]
+pub struct Reconnect {
+    pub policy: RetryPolicy,
+    pub on_event: Option<EventCallback>,
+}
+
+type EventCallback = Arc<dyn Fn(&ReconnectEvent) + Send + Sync>;
+
+type Connector<M> = Box<dyn Fn() -> Result<M> + Send + Sync>;
+
+/// Muxer whose connection is opened again by `connect` when pushing to it fails, or the muxer
+/// itself for outputs that do not reconnect.
+pub struct ReconnectingMuxer<M> {
+    muxer: M,
+    retry: Option<(Connector<M>, Reconnect)>,
+}
+
+impl<M> ReconnectingMuxer<M> {
+    /// Passes everything through to `muxer`, returning its errors as they are.
+    pub fn direct(muxer: M) -> Self {
+        Self { muxer, retry: None }
+    }
+
+    /// Connects with `connect` now, and again whenever the connection fails.
+    pub fn new(
+        connect: impl Fn() -> Result<M> + Send + Sync + 'static,
+        reconnect: Reconnect,
+    ) -> Result<Self> {
+        Ok(Self {
+            muxer: connect()?,
+            retry: Some((Box::new(connect), reconnect)),
+        })
+    }
+}
+
+impl<M> Muxer for ReconnectingMuxer<M>
+where
+    M: Muxer<
+            VideoInputType: MuxerInput<Data: EncodedData>,
+            AudioInputType: MuxerInput<Data: EncodedData>,
+            CompletionHandleType: Send,
+        > + 'static,
+{
+    type VideoInputType = ReconnectingMuxerInput<M, M::VideoInputType>;
+    type AudioInputType = ReconnectingMuxerInput<M, M::AudioInputType>;
+    type CompletionHandleType = ReconnectingCompletionHandle<M>;
+
+    fn get_inputs(
+        self,
+    ) -> Result<(
+        Self::VideoInputType,
+        Self::AudioInputType,
+        Self::CompletionHandleType,
+    )> {
+        let (video, audio, completion) = self.muxer.get_inputs()?;
+        let shared = Arc::new(Shared {
+            link: Mutex::new(Link {
+                generation: 0,
+                state: State::Connected,
+                video: Track::new(video, true),
+                audio: Track::new(audio, false),
+                completion: Some(completion),
+                dropped: 0,
+            }),
+            retry: self.retry,
+        });
+        Ok((
+            ReconnectingMuxerInput {
+                shared: shared.clone(),
+                select: video_track,
+            },
+            ReconnectingMuxerInput {
+                shared: shared.clone(),
+                select: audio_track,
+            },
+            ReconnectingCompletionHandle { shared },
+        ))
+    }
+}
+
+enum State {
+    Connected,
+    /// Attempt `attempt`, counted from 0, is made by the first push after `next_at`.
+    Disconnected {
+        attempt: u32,
+        next_at: Instant,
+        error: String,
+    },
+    Connecting,
+    GaveUp(String),
+}
+
+struct Track<I: MuxerInput> {
+    /// Input of the current connection, taken while a push to it is in progress.
+    input: Option<I>,
+    /// Metadata samples pushed so far, encoded, which every new connection starts with.
+    metadata: Vec<Vec<u8>>,
+    backlog: VecDeque<I::Data>,
+    needs_key: bool,
+    waiting_for_key: bool,
+    finished: bool,
+}
+
+impl<I: MuxerInput<Data: EncodedData>> Track<I> {
+    fn new(input: I, needs_key: bool) -> Self {
+        Self {
+            input: Some(input),
+            metadata: Vec::new(),
+            backlog: VecDeque::new(),
+            needs_key,
+            waiting_for_key: false,
+            finished: false,
+        }
+    }
+
+    /// Drops the input and the backlog, returning how many samples were dropped.
+    fn disconnect(&mut self) -> u64 {
+        let dropped = self.backlog.len() as u64;
+        self.input = None;
+        self.backlog.clear();
+        self.waiting_for_key = self.needs_key;
+        dropped
+    }
+
+    /// Whether `data` is lost because the video waits for a keyframe after losing samples.
+    fn skips(&mut self, data: &I::Data) -> bool {
+        match data.kind() {
+            UniencSampleKind::Key => self.waiting_for_key = false,
+            UniencSampleKind::Interpolated => return self.waiting_for_key,
+            UniencSampleKind::Metadata => {}
+        }
+        false
+    }
+
+    /// Buffers `data` while disconnected, returning how many samples were dropped.
+    fn buffer(&mut self, data: I::Data, max_buffered: f64) -> u64 {
+        if data.kind() == UniencSampleKind::Metadata {
+            // already kept with the metadata
+            return 0;
+        }
+        self.backlog.push_back(data);
+        let mut dropped = 0;
+        while let (Some(front), Some(back)) = (self.backlog.front(), self.backlog.back()) {
+            if back.timestamp() - front.timestamp() <= max_buffered {
+                break;
+            }
+            self.backlog.pop_front();
+            dropped += 1;
+            while self.needs_key
+                && self
+                    .backlog
+                    .front()
+                    .is_some_and(|data| data.kind() != UniencSampleKind::Key)
+            {
+                self.backlog.pop_front();
+                dropped += 1;
+            }
+        }
+        if self.backlog.is_empty() {
+            self.waiting_for_key = self.needs_key;
+        }
+        dropped
+    }
+}
+
+struct Link<M: Muxer> {
+    /// Incremented on every disconnection, so that pushes to an old connection are not mistaken
+    /// for pushes to the current one.
+    generation: u64,
+    state: State,
+    video: Track<M::VideoInputType>,
+    audio: Track<M::AudioInputType>,
+    completion: Option<M::CompletionHandleType>,
+    dropped: u64,
+}
+
+impl<
+    M: Muxer<
+            VideoInputType: MuxerInput<Data: EncodedData>,
+            AudioInputType: MuxerInput<Data: EncodedData>,
+        >,
+> Link<M>
+{
+    fn disconnect(&mut self) {
+        self.generation += 1;
+        self.dropped += self.video.disconnect() + self.audio.disconnect();
+        self.completion = None;
+    }
+}
+
+type Select<M, I> = fn(&mut Link<M>) -> &mut Track<I>;
+
+fn video_track<M: Muxer>(link: &mut Link<M>) -> &mut Track<M::VideoInputType> {
+    &mut link.video
+}
+
+fn audio_track<M: Muxer>(link: &mut Link<M>) -> &mut Track<M::AudioInputType> {
+    &mut link.audio
+}
+
+struct Shared<M: Muxer> {
+    link: Mutex<Link<M>>,
+    retry: Option<(Connector<M>, Reconnect)>,
+}
+
+impl<M> Shared<M>
+where
+    M: Muxer<
+            VideoInputType: MuxerInput<Data: EncodedData>,
+            AudioInputType: MuxerInput<Data: EncodedData>,
+            CompletionHandleType: Send,
+        > + 'static,
+{
+    fn lock(&self) -> MutexGuard<'_, Link<M>> {
+        self.link.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn emit(&self, event: ReconnectEvent) {
+        if let Some((
+            _,
+            Reconnect {
+                on_event: Some(on_event),
+                ..
+            },
+        )) = &self.retry
+        {
+            on_event(&event);
+        }
+    }
+
+    /// Schedules attempt `attempt` after a failure, or gives up.
+    fn retry_later(
+        &self,
+        link: &mut Link<M>,
+        attempt: u32,
+        error: String,
+    ) -> Option<ReconnectEvent> {
+        let (_, reconnect) = self.retry.as_ref()?;
+        if attempt >= reconnect.policy.max_attempts {
+            link.state = State::GaveUp(error.clone());
+            return Some(ReconnectEvent::GaveUp { error });
+        }
+        link.state = State::Disconnected {
+            attempt,
+            next_at: Instant::now() + reconnect.policy.backoff(attempt),
+            error,
+        };
+        None
+    }
+
+    fn disconnect(&self, link: &mut Link<M>, error: String) -> Vec<ReconnectEvent> {
+        link.disconnect();
+        let mut events = vec![ReconnectEvent::Disconnected {
+            error: error.clone(),
+        }];
+        events.extend(self.retry_later(link, 0, error));
+        events
+    }
+
+    async fn reconnect_if_due(&self) {
+        let Some((connect, _)) = &self.retry else {
+            return;
+        };
+        let (attempt, generation) = {
+            let mut link = self.lock();
+            match link.state {
+                State::Disconnected {
+                    attempt, next_at, ..
+                } if Instant::now() >= next_at => {
+                    link.state = State::Connecting;
+                    (attempt, link.generation)
+                }
+                _ => return,
+            }
+        };
+        self.emit(ReconnectEvent::Reconnecting {
+            attempt: attempt + 1,
+        });
+
+        // a failed connection leaves the backlog as it is, unlike a failed replay
+        let (connected, result) = match connect().and_then(|muxer| muxer.get_inputs()) {
+            Ok((video, audio, completion)) => (
+                true,
+                self.replay(video, audio, completion, generation).await,
+            ),
+            Err(e) => (false, Err(e)),
+        };
+
+        let event = {
+            let mut link = self.lock();
+            if link.generation != generation {
+                // the new connection already failed, and was handled by the push that noticed
+                return;
+            }
+            match result {
+                Ok(()) => {
+                    link.state = State::Connected;
+                    Some(ReconnectEvent::Reconnected {
+                        attempt: attempt + 1,
+                        dropped: std::mem::take(&mut link.dropped),
+                    })
+                }
+                Err(e) => {
+                    if connected {
+                        link.disconnect();
+                    }
+                    self.retry_later(&mut link, attempt + 1, e.to_string())
+                }
+            }
+        };
+        if let Some(event) = event {
+            self.emit(event);
+        }
+    }
+
+    async fn replay(
+        &self,
+        video: M::VideoInputType,
+        audio: M::AudioInputType,
+        completion: M::CompletionHandleType,
+        generation: u64,
+    ) -> Result<()> {
+        self.replay_track(video_track, video, generation).await?;
+        self.replay_track(audio_track, audio, generation).await?;
+        let mut link = self.lock();
+        if link.generation == generation {
+            link.completion = Some(completion);
+        }
+        Ok(())
+    }
+
+    /// Sends the metadata and the backlog of a track to the new connection, then hands it the
+    /// input, or finishes it if the track was finished meanwhile.
+    async fn replay_track<I: MuxerInput<Data: EncodedData>>(
+        &self,
+        select: Select<M, I>,
+        mut input: I,
+        generation: u64,
+    ) -> Result<()> {
+        let metadata = select(&mut self.lock()).metadata.clone();
+        for bytes in metadata {
+            let (data, _) = bincode::decode_from_slice(&bytes, bincode::config::standard())
+                .context("Failed to copy sample")?;
+            input.push(data).await?;
+        }
+        loop {
+            let data = {
+                let mut link = self.lock();
+                if link.generation != generation {
+                    return Ok(());
+                }
+                let track = select(&mut link);
+                match track.backlog.pop_front() {
+                    Some(data) => data,
+                    None if track.finished => break,
+                    None => {
+                        track.input = Some(input);
+                        return Ok(());
+                    }
+                }
+            };
+            input.push(data).await?;
+        }
+        input.finish().await
+    }
+}
+
+/// Muxer input of a [`ReconnectingMuxer`], which buffers the samples while disconnected.
+pub struct ReconnectingMuxerInput<M: Muxer, I: MuxerInput> {
+    shared: Arc<Shared<M>>,
+    select: Select<M, I>,
+}
+
+impl<M, I> MuxerInput for ReconnectingMuxerInput<M, I>
+where
+    M: Muxer<
+            VideoInputType: MuxerInput<Data: EncodedData>,
+            AudioInputType: MuxerInput<Data: EncodedData>,
+            CompletionHandleType: Send,
+        > + 'static,
+    I: MuxerInput<Data: EncodedData>,
+{
+    type Data = I::Data;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        self.shared.reconnect_if_due().await;
+        let retry = self
+            .shared
+            .retry
+            .as_ref()
+            .map(|(_, reconnect)| reconnect.policy);
+
+        let (mut input, generation) = {
+            let mut link = self.shared.lock();
+            if let State::GaveUp(error) = &link.state {
+                return Err(CommonError::StreamDisconnected(error.clone()));
+            }
+            let generation = link.generation;
+            let track = (self.select)(&mut link);
+            if track.skips(&data) {
+                link.dropped += 1;
+                return Ok(());
+            }
+            if retry.is_some() && data.kind() == UniencSampleKind::Metadata {
+                let bytes = bincode::encode_to_vec(&data, bincode::config::standard())
+                    .context("Failed to copy sample")?;
+                track.metadata.push(bytes);
+            }
+            match track.input.take() {
+                Some(input) => (input, generation),
+                None => {
+                    let max_buffered = retry.map_or(0.0, |policy| policy.max_buffered);
+                    let dropped = track.buffer(data, max_buffered);
+                    link.dropped += dropped;
+                    return Ok(());
+                }
+            }
+        };
+
+        let result = input.push(data).await;
+        let events = {
+            let mut link = self.shared.lock();
+            let current = link.generation == generation;
+            match result {
+                Ok(()) => {
+                    if current {
+                        (self.select)(&mut link).input = Some(input);
+                    }
+                    return Ok(());
+                }
+                Err(e) if retry.is_none() => {
+                    (self.select)(&mut link).input = Some(input);
+                    return Err(e);
+                }
+                Err(e) => {
+                    link.dropped += 1;
+                    if !current {
+                        return Ok(());
+                    }
+                    self.shared.disconnect(&mut link, e.to_string())
+                }
+            }
+        };
+        for event in events {
+            self.shared.emit(event);
+        }
+        Ok(())
+    }
+
+    async fn finish(self) -> Result<()> {
+        let input = {
+            let mut link = self.shared.lock();
+            let track = (self.select)(&mut link);
+            track.finished = true;
+            track.input.take()
+        };
+        match input {
+            Some(input) => input.finish().await,
+            // sent by the replay if the connection is opened again
+            None => Ok(()),
+        }
+    }
+}
+
+/// Completion handle of a [`ReconnectingMuxer`], which fails unless the output is connected.
+pub struct ReconnectingCompletionHandle<M: Muxer> {
+    shared: Arc<Shared<M>>,
+}
+
+impl<M> CompletionHandle for ReconnectingCompletionHandle<M>
+where
+    M: Muxer<
+            VideoInputType: MuxerInput<Data: EncodedData>,
+            AudioInputType: MuxerInput<Data: EncodedData>,
+            CompletionHandleType: Send,
+        > + 'static,
+{
+    async fn finish(self) -> Result<()> {
+        let completion = {
+            let mut link = self.shared.lock();
+            match &link.state {
+                State::Connected => link.completion.take(),
+                State::Disconnected { error, .. } | State::GaveUp(error) => {
+                    return Err(CommonError::StreamDisconnected(error.clone()));
+                }
+                State::Connecting => None,
+            }
+        };
+        match completion {
+            Some(completion) => completion.finish().await,
+            None => Err(CommonError::StreamDisconnected(
+                "finished while reconnecting".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::{Decode, Encode};
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::task::{Context, Poll, Waker};
+
+    #[derive(Encode, Decode, Debug, Clone, Copy, PartialEq)]
+    struct Sample(f64, u8);
+
+    impl EncodedData for Sample {
+        fn timestamp(&self) -> f64 {
+            self.0
+        }
+
+        fn set_timestamp(&mut self, timestamp: f64) {
+            self.0 = timestamp;
+        }
+
+        fn kind(&self) -> UniencSampleKind {
+            match self.1 {
+                0 => UniencSampleKind::Interpolated,
+                1 => UniencSampleKind::Key,
+                _ => UniencSampleKind::Metadata,
+            }
+        }
+
+        fn size(&self) -> usize {
+            0
+        }
+    }
+
+    /// Connection that fails the push after `fail_after` samples.
+    #[derive(Clone)]
+    struct Connection {
+        pushed: Arc<Mutex<Vec<Sample>>>,
+        fail_after: Option<usize>,
+    }
+
+    impl MuxerInput for Connection {
+        type Data = Sample;
+
+        async fn push(&mut self, data: Sample) -> Result<()> {
+            let mut pushed = self.pushed.lock().unwrap();
+            if self.fail_after.is_some_and(|count| pushed.len() >= count) {
+                return Err(CommonError::Categorized {
+                    category: crate::ErrorCategory::Communication,
+                    message: "Broken pipe".to_string(),
+                });
+            }
+            pushed.push(data);
+            Ok(())
+        }
+
+        async fn finish(self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl CompletionHandle for Connection {
+        async fn finish(self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Muxer for Connection {
+        type VideoInputType = Connection;
+        type AudioInputType = Connection;
+        type CompletionHandleType = Connection;
+
+        fn get_inputs(self) -> Result<(Connection, Connection, Connection)> {
+            Ok((self.clone(), self.clone(), self))
+        }
+    }
+
+    fn run<T>(future: impl Future<Output = T>) -> T {
+        let Poll::Ready(result) = pin!(future).poll(&mut Context::from_waker(Waker::noop())) else {
+            panic!("future did not complete");
+        };
+        result
+    }
+
+    #[test]
+    fn buffers_while_disconnected_and_resumes_at_a_keyframe() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let connected = Arc::new(Mutex::new(Vec::new()));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let muxer = ReconnectingMuxer::new(
+            {
+                let connected = connected.clone();
+                move || {
+                    let fail_after = match attempts.fetch_add(1, Ordering::SeqCst) {
+                        0 => Some(3),
+                        1..=4 => return Err(CommonError::StreamDisconnected("offline".into())),
+                        _ => None,
+                    };
+                    let pushed = Arc::new(Mutex::new(Vec::new()));
+                    connected.lock().unwrap().push(pushed.clone());
+                    Ok(Connection { pushed, fail_after })
+                }
+            },
+            Reconnect {
+                policy: RetryPolicy {
+                    initial_backoff: Duration::ZERO,
+                    max_buffered: 0.6,
+                    ..Default::default()
+                },
+                on_event: Some({
+                    let events = events.clone();
+                    Arc::new(move |event: &ReconnectEvent| {
+                        events.lock().unwrap().push(event.clone())
+                    })
+                }),
+            },
+        )
+        .unwrap();
+        let (mut video, _audio, completion) = muxer.get_inputs().unwrap();
+
+        run(async {
+            for sample in [
+                Sample(0.0, 2),
+                Sample(0.0, 1),
+                Sample(0.5, 0),
+                // fails and disconnects
+                Sample(1.0, 0),
+                // each push from here makes an attempt, the fifth of which succeeds
+                Sample(1.5, 0),
+                Sample(2.0, 1),
+                Sample(2.5, 0),
+                Sample(3.0, 1),
+                Sample(3.5, 0),
+            ] {
+                video.push(sample).await.unwrap();
+            }
+            completion.finish().await.unwrap();
+        });
+
+        let connected = connected.lock().unwrap();
+        assert_eq!(connected.len(), 2);
+        assert_eq!(
+            *connected[1].lock().unwrap(),
+            vec![Sample(0.0, 2), Sample(3.0, 1), Sample(3.5, 0)]
+        );
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 7);
+        assert!(matches!(events[0], ReconnectEvent::Disconnected { .. }));
+        assert_eq!(
+            events[6],
+            ReconnectEvent::Reconnected {
+                attempt: 5,
+                dropped: 4
+            }
+        );
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        let muxer = ReconnectingMuxer::new(
+            {
+                let attempts = AtomicU32::new(0);
+                move || match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 => Ok(Connection {
+                        pushed: Default::default(),
+                        fail_after: Some(0),
+                    }),
+                    _ => Err(CommonError::StreamDisconnected("offline".into())),
+                }
+            },
+            Reconnect {
+                policy: RetryPolicy {
+                    max_attempts: 1,
+                    initial_backoff: Duration::ZERO,
+                    ..Default::default()
+                },
+                on_event: None,
+            },
+        )
+        .unwrap();
+        let (mut video, _audio, completion) = muxer.get_inputs().unwrap();
+
+        run(async {
+            // fails and disconnects; the next push makes the only attempt and gives up
+            video.push(Sample(0.0, 1)).await.unwrap();
+            assert!(matches!(
+                video.push(Sample(0.5, 0)).await,
+                Err(CommonError::StreamDisconnected(_))
+            ));
+            assert!(completion.finish().await.is_err());
+        });
+        assert_eq!(
+            RetryPolicy::default().backoff(10),
+            RetryPolicy::default().max_backoff
+        );
+    }
+}
@@ -0,0 +1,428 @@
+//! In-memory ring of encoded samples for instant replay, without an intermediate file.
+//!
+//! Encoder outputs are forwarded into the ring with [`drive`](crate::drive) through the inputs
+//! returned by [`ReplayBuffer::video_input`] and [`ReplayBuffer::audio_input`]. The oldest video
+//! GOPs are evicted once the memory limit is reached, so the buffered video always starts at a
+//! keyframe, and [`ReplayBuffer::export`] muxes the latest samples into a file, or the kinds of
+//! them an [`ExportFilter`] keeps.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{
+    CommonError, CompletionHandle, EncodedData, MuxerInput, Result, ResultExt, UniencSampleKind,
+};
+
+/// Samples an export keeps. Each track keeps its metadata, as some muxers wait for the format of
+/// both tracks before writing any sample, so the track of a dropped kind is written empty.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFilter {
+    #[default]
+    All,
+    VideoOnly,
+    AudioOnly,
+    /// Video keyframes only, played one after another at the frame rate of the video for a
+    /// hyperlapse-style summary.
+    Keyframes,
+}
+
+impl ExportFilter {
+    /// Drops the samples the filter excludes from those taken with [`ReplayBuffer::take_last`].
+    pub fn apply<V: EncodedData, A: EncodedData>(self, video: &mut Vec<V>, audio: &mut Vec<A>) {
+        match self {
+            ExportFilter::All => {}
+            ExportFilter::VideoOnly => audio.retain(is_metadata),
+            ExportFilter::AudioOnly => video.retain(is_metadata),
+            ExportFilter::Keyframes => {
+                audio.retain(is_metadata);
+                let frames: Vec<f64> = video
+                    .iter()
+                    .filter(|data| data.kind() != UniencSampleKind::Metadata)
+                    .map(|data| data.timestamp())
+                    .collect();
+                let interval = match frames.as_slice() {
+                    [first, .., last] => (last - first) / (frames.len() - 1) as f64,
+                    _ => 0.0,
+                };
+                video.retain(|data| data.kind() != UniencSampleKind::Interpolated);
+                let keyframes = video
+                    .iter_mut()
+                    .filter(|data| data.kind() != UniencSampleKind::Metadata);
+                for (i, data) in keyframes.enumerate() {
+                    data.set_timestamp(i as f64 * interval);
+                }
+            }
+        }
+    }
+}
+
+fn is_metadata<T: EncodedData>(data: &T) -> bool {
+    data.kind() == UniencSampleKind::Metadata
+}
+
+pub struct ReplayBuffer<V, A> {
+    max_bytes: usize,
+    state: Mutex<BufferState<V, A>>,
+}
+
+struct BufferState<V, A> {
+    video: VecDeque<V>,
+    audio: VecDeque<A>,
+    // kept across exports, serialized as samples are not necessarily cloneable
+    video_metadata: Vec<Vec<u8>>,
+    audio_metadata: Vec<Vec<u8>>,
+    latest_video_timestamp: Option<f64>,
+    bytes: usize,
+}
+
+impl<V: EncodedData, A: EncodedData> ReplayBuffer<V, A> {
+    /// Keeps up to `max_bytes` of encoded samples. 0 means unlimited.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            state: Mutex::new(BufferState {
+                video: VecDeque::new(),
+                audio: VecDeque::new(),
+                video_metadata: Vec::new(),
+                audio_metadata: Vec::new(),
+                latest_video_timestamp: None,
+                bytes: 0,
+            }),
+        }
+    }
+
+    /// Bytes of encoded samples currently buffered.
+    pub fn bytes(&self) -> usize {
+        self.lock().bytes
+    }
+
+    pub fn push_video(&self, data: V) -> Result<()> {
+        let mut state = self.lock();
+        if data.kind() == UniencSampleKind::Metadata {
+            state.video_metadata.push(serialize(&data)?);
+            return Ok(());
+        }
+        // MediaCodec (Android) may produce an out-of-order frame with timestamp=0 at the end of
+        // stream
+        let timestamp = data.timestamp();
+        if state
+            .latest_video_timestamp
+            .is_none_or(|latest| timestamp >= latest)
+        {
+            state.latest_video_timestamp = Some(timestamp);
+        }
+        state.bytes += data.size();
+        state.video.push_back(data);
+        state.evict(self.max_bytes);
+        Ok(())
+    }
+
+    pub fn push_audio(&self, data: A) -> Result<()> {
+        let mut state = self.lock();
+        if data.kind() == UniencSampleKind::Metadata {
+            state.audio_metadata.push(serialize(&data)?);
+            return Ok(());
+        }
+        state.bytes += data.size();
+        state.audio.push_back(data);
+        state.evict(self.max_bytes);
+        Ok(())
+    }
+
+    /// Takes the samples of the last `duration` seconds, or all of them, starting at the keyframe
+    /// closest to that point. Timestamps of each track are rebased to start at 0, and metadata is
+    /// put first. Samples before the start are discarded.
+    pub fn take_last(&self, duration: Option<f64>) -> Result<(Vec<V>, Vec<A>)> {
+        let (video, audio, video_metadata, audio_metadata, latest) = {
+            let mut state = self.lock();
+            state.bytes = 0;
+            (
+                std::mem::take(&mut state.video),
+                std::mem::take(&mut state.audio),
+                state.video_metadata.clone(),
+                state.audio_metadata.clone(),
+                state.latest_video_timestamp.unwrap_or(0.0),
+            )
+        };
+
+        let keyframes = video
+            .iter()
+            .enumerate()
+            .filter(|(_, data)| data.kind() == UniencSampleKind::Key);
+        let video_start = match duration {
+            Some(duration) => {
+                let expected = latest - duration;
+                keyframes
+                    .min_by(|(_, a), (_, b)| {
+                        let a = (a.timestamp() - expected).abs();
+                        let b = (b.timestamp() - expected).abs();
+                        a.total_cmp(&b)
+                    })
+                    .map(|(i, _)| i)
+            }
+            None => keyframes.map(|(i, _)| i).next(),
+        }
+        .ok_or(CommonError::NoKeyframeBuffered)?;
+
+        let audio_start = match audio.back() {
+            Some(last) => {
+                let actual_duration = latest - video[video_start].timestamp();
+                let expected = last.timestamp() - actual_duration;
+                audio
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| {
+                        let a = (a.timestamp() - expected).abs();
+                        let b = (b.timestamp() - expected).abs();
+                        a.total_cmp(&b)
+                    })
+                    .map(|(i, _)| i)
+                    .unwrap_or(0)
+            }
+            None => 0,
+        };
+
+        Ok((
+            rebase(video_metadata, video.into_iter().skip(video_start))?,
+            rebase(audio_metadata, audio.into_iter().skip(audio_start))?,
+        ))
+    }
+
+    /// Muxes the samples of the last `duration` seconds, or all of them, that `filter` keeps and
+    /// completes the muxer. Buffering continues, so later exports contain the samples pushed after
+    /// this one.
+    pub async fn export<VI, AI, H>(
+        &self,
+        duration: Option<f64>,
+        filter: ExportFilter,
+        mut video_input: VI,
+        mut audio_input: AI,
+        completion_handle: H,
+    ) -> Result<()>
+    where
+        VI: MuxerInput<Data = V>,
+        AI: MuxerInput<Data = A>,
+        H: CompletionHandle,
+    {
+        let (mut video, mut audio) = self.take_last(duration)?;
+        filter.apply(&mut video, &mut audio);
+        // pushed in timestamp order so that the tracks are interleaved in the file
+        let mut video = video.into_iter().peekable();
+        let mut audio = audio.into_iter().peekable();
+        loop {
+            match (video.peek(), audio.peek()) {
+                (Some(v), Some(a)) if v.timestamp() <= a.timestamp() => {
+                    video_input.push(video.next().unwrap()).await?
+                }
+                (_, Some(_)) => audio_input.push(audio.next().unwrap()).await?,
+                (Some(_), None) => video_input.push(video.next().unwrap()).await?,
+                (None, None) => break,
+            }
+        }
+        video_input.finish().await?;
+        audio_input.finish().await?;
+        completion_handle.finish().await
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BufferState<V, A>> {
+        // samples stay consistent even if a holder panicked
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<V: EncodedData + Send + 'static, A: EncodedData + Send + 'static> ReplayBuffer<V, A> {
+    pub fn video_input(self: &Arc<Self>) -> ReplayBufferVideoInput<V, A> {
+        ReplayBufferVideoInput(self.clone())
+    }
+
+    pub fn audio_input(self: &Arc<Self>) -> ReplayBufferAudioInput<V, A> {
+        ReplayBufferAudioInput(self.clone())
+    }
+}
+
+impl<V: EncodedData, A: EncodedData> BufferState<V, A> {
+    /// Drops the oldest video GOPs, and the audio before the remaining video, until the buffer
+    /// fits in `max_bytes`.
+    fn evict(&mut self, max_bytes: usize) {
+        while max_bytes > 0 && self.bytes > max_bytes {
+            if let Some(data) = self.video.pop_front() {
+                self.bytes -= data.size();
+                while let Some(data) = self
+                    .video
+                    .pop_front_if(|data| data.kind() != UniencSampleKind::Key)
+                {
+                    self.bytes -= data.size();
+                }
+                let Some(start) = self.video.front().map(|data| data.timestamp()) else {
+                    continue;
+                };
+                while let Some(data) = self.audio.pop_front_if(|data| data.timestamp() < start) {
+                    self.bytes -= data.size();
+                }
+            } else if let Some(data) = self.audio.pop_front() {
+                self.bytes -= data.size();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+fn serialize<T: EncodedData>(data: &T) -> Result<Vec<u8>> {
+    bincode::encode_to_vec(data, bincode::config::standard())
+        .context("Failed to serialize metadata sample")
+}
+
+/// Prepends the decoded `metadata` to `samples`, and shifts their timestamps to start at 0.
+fn rebase<T: EncodedData>(
+    metadata: Vec<Vec<u8>>,
+    samples: impl Iterator<Item = T>,
+) -> Result<Vec<T>> {
+    let mut rebased = metadata
+        .iter()
+        .map(|bytes| {
+            bincode::decode_from_slice(bytes, bincode::config::standard())
+                .map(|(data, _)| data)
+                .context("Failed to deserialize metadata sample")
+        })
+        .collect::<Result<Vec<T>>>()?;
+    let mut start = None;
+    for mut data in samples {
+        let start = *start.get_or_insert(data.timestamp());
+        data.set_timestamp(data.timestamp() - start);
+        rebased.push(data);
+    }
+    Ok(rebased)
+}
+
+/// Muxer input that pushes video samples into a [`ReplayBuffer`]. Finishing it has no effect.
+pub struct ReplayBufferVideoInput<V, A>(Arc<ReplayBuffer<V, A>>);
+
+/// Muxer input that pushes audio samples into a [`ReplayBuffer`]. Finishing it has no effect.
+pub struct ReplayBufferAudioInput<V, A>(Arc<ReplayBuffer<V, A>>);
+
+impl<V, A> MuxerInput for ReplayBufferVideoInput<V, A>
+where
+    V: EncodedData + Send + 'static,
+    A: EncodedData + Send + 'static,
+{
+    type Data = V;
+
+    async fn push(&mut self, data: V) -> Result<()> {
+        self.0.push_video(data)
+    }
+
+    async fn finish(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<V, A> MuxerInput for ReplayBufferAudioInput<V, A>
+where
+    V: EncodedData + Send + 'static,
+    A: EncodedData + Send + 'static,
+{
+    type Data = A;
+
+    async fn push(&mut self, data: A) -> Result<()> {
+        self.0.push_audio(data)
+    }
+
+    async fn finish(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::{Decode, Encode};
+
+    #[derive(Encode, Decode, Debug, PartialEq)]
+    struct Sample(f64, u8);
+
+    impl EncodedData for Sample {
+        fn timestamp(&self) -> f64 {
+            self.0
+        }
+        fn set_timestamp(&mut self, timestamp: f64) {
+            self.0 = timestamp;
+        }
+        fn kind(&self) -> UniencSampleKind {
+            match self.1 {
+                0 => UniencSampleKind::Interpolated,
+                1 => UniencSampleKind::Key,
+                _ => UniencSampleKind::Metadata,
+            }
+        }
+        fn size(&self) -> usize {
+            10
+        }
+    }
+
+    #[test]
+    fn evicts_whole_gops_and_exports_from_a_keyframe() {
+        // room for 6 samples
+        let buffer = ReplayBuffer::<Sample, Sample>::new(60);
+        buffer.push_video(Sample(0.0, 2)).unwrap();
+        for i in 0..6 {
+            // a keyframe every 2 seconds
+            buffer
+                .push_video(Sample(i as f64, (i % 2 == 0) as u8))
+                .unwrap();
+            buffer.push_audio(Sample(i as f64 + 0.5, 0)).unwrap();
+        }
+        // the first two GOPs and the audio before them were evicted
+        assert_eq!(buffer.bytes(), 40);
+
+        let (video, audio) = buffer.take_last(Some(1.0)).unwrap();
+        assert_eq!(
+            video,
+            vec![Sample(0.0, 2), Sample(0.0, 1), Sample(1.0, 0)],
+            "metadata is put first and timestamps start at 0"
+        );
+        assert_eq!(audio, vec![Sample(0.0, 0), Sample(1.0, 0)]);
+        assert_eq!(buffer.bytes(), 0);
+
+        // metadata is kept for the next export
+        buffer.push_video(Sample(6.0, 1)).unwrap();
+        let (video, _) = buffer.take_last(None).unwrap();
+        assert_eq!(video, vec![Sample(0.0, 2), Sample(0.0, 1)]);
+        assert!(matches!(
+            buffer.take_last(None),
+            Err(CommonError::NoKeyframeBuffered)
+        ));
+    }
+
+    #[test]
+    fn filters_exported_samples_by_kind() {
+        let take = || {
+            let video = vec![
+                Sample(0.0, 2),
+                Sample(0.0, 1),
+                Sample(0.5, 0),
+                Sample(1.0, 0),
+                Sample(1.5, 1),
+                Sample(2.0, 0),
+            ];
+            (video, vec![Sample(0.0, 2), Sample(0.0, 0), Sample(1.0, 0)])
+        };
+
+        let (mut video, mut audio) = take();
+        ExportFilter::VideoOnly.apply(&mut video, &mut audio);
+        assert_eq!((video.len(), audio), (6, vec![Sample(0.0, 2)]));
+
+        let (mut video, mut audio) = take();
+        ExportFilter::AudioOnly.apply(&mut video, &mut audio);
+        assert_eq!((video, audio.len()), (vec![Sample(0.0, 2)], 3));
+
+        let (mut video, mut audio) = take();
+        ExportFilter::Keyframes.apply(&mut video, &mut audio);
+        assert_eq!(
+            video,
+            vec![Sample(0.0, 2), Sample(0.0, 1), Sample(0.5, 1)],
+            "keyframes follow each other at the frame interval"
+        );
+        assert_eq!(audio, vec![Sample(0.0, 2)]);
+    }
+}
@@ -0,0 +1,307 @@
+//! Replay data track: game-defined input events and state snapshots recorded next to the video and
+//! saved as a compact sidecar file, so a replay can be watched or re-simulated.
+//!
+//! Events are stamped with the same timestamps as the video frames pushed to the encoder, and the
+//! sidecar is rebased onto the first frame written to the output file, so both share a timeline.
+//!
+//! Once the host supplies the offset of a shared reference clock, the sidecar also carries
+//! [`SyncMarker`]s at a fixed interval, so a server can align replays recorded on different
+//! devices onto that clock.
+
+use std::collections::VecDeque;
+use std::path::Path;
+
+use bincode::{Decode, Encode};
+
+use crate::{CommonError, Result};
+
+/// Leading bytes of every sidecar file.
+pub const REPLAY_DATA_MAGIC: [u8; 4] = *b"URPD";
+pub const REPLAY_DATA_VERSION: u8 = 1;
+
+/// Event kind reserved for [`SyncMarker`]s; games should not push events of this kind.
+pub const SYNC_MARKER_KIND: u32 = u32::MAX;
+/// Seconds between sync markers, short enough that drift between two markers stays well below a
+/// frame.
+pub const SYNC_MARKER_INTERVAL: f64 = 1.0;
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct ReplayEvent {
+    /// Seconds on the video pipeline's clock; relative to the start of the video once loaded from a
+    /// sidecar.
+    pub timestamp: f64,
+    /// Game-defined tag, e.g. to tell input events from state snapshots.
+    pub kind: u32,
+    pub data: Vec<u8>,
+}
+
+/// Offset of a shared reference clock from the video pipeline's clock, as measured by the host
+/// against a time server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockOffset {
+    /// Seconds added to a pipeline timestamp to get the reference time.
+    pub offset: f64,
+    /// Bound on the error of `offset` in seconds, half the round trip of the measurement.
+    pub uncertainty: f64,
+}
+
+impl ClockOffset {
+    /// Offset from the four times of an NTP-style exchange: `sent` and `received` on the pipeline's
+    /// clock, `server_received` and `server_sent` on the reference clock.
+    pub fn from_exchange(sent: f64, server_received: f64, server_sent: f64, received: f64) -> Self {
+        let round_trip = (received - sent) - (server_sent - server_received);
+        Self {
+            offset: ((server_received - sent) + (server_sent - received)) / 2.0,
+            uncertainty: round_trip.max(0.0) / 2.0,
+        }
+    }
+}
+
+/// Reference time of the point of the video timeline the marker event is stamped with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncMarker {
+    pub reference_time: f64,
+    pub uncertainty: f64,
+}
+
+impl SyncMarker {
+    /// The marker carried by `event`, if it is one.
+    pub fn from_event(event: &ReplayEvent) -> Option<Self> {
+        if event.kind != SYNC_MARKER_KIND {
+            return None;
+        }
+        let data: &[u8; 16] = event.data.as_slice().try_into().ok()?;
+        let (reference_time, uncertainty) = data.split_at(8);
+        Some(Self {
+            reference_time: f64::from_le_bytes(reference_time.try_into().unwrap()),
+            uncertainty: f64::from_le_bytes(uncertainty.try_into().unwrap()),
+        })
+    }
+
+    fn into_event(self, timestamp: f64) -> ReplayEvent {
+        let mut data = self.reference_time.to_le_bytes().to_vec();
+        data.extend_from_slice(&self.uncertainty.to_le_bytes());
+        ReplayEvent {
+            timestamp,
+            kind: SYNC_MARKER_KIND,
+            data,
+        }
+    }
+}
+
+/// Events pushed during recording, kept in timestamp order.
+pub struct ReplayDataTrack {
+    events: VecDeque<ReplayEvent>,
+    /// Clock offsets by the pipeline timestamp they were measured at, in timestamp order.
+    offsets: VecDeque<(f64, ClockOffset)>,
+    retention: Option<f64>,
+}
+
+impl ReplayDataTrack {
+    /// `retention` drops events older than this many seconds behind the newest one, matching the
+    /// length of a bounded recording. `None` keeps every event.
+    pub fn new(retention: Option<f64>) -> Self {
+        Self {
+            events: VecDeque::new(),
+            offsets: VecDeque::new(),
+            retention,
+        }
+    }
+
+    pub fn push(&mut self, event: ReplayEvent) {
+        // events usually arrive in order, but a late one is placed after those with the same time
+        let index = self
+            .events
+            .partition_point(|e| e.timestamp <= event.timestamp);
+        self.events.insert(index, event);
+        self.drop_expired();
+    }
+
+    /// Sets the offset of the reference clock measured at `timestamp`, which applies from there
+    /// until the next one. Measuring again every few seconds keeps the markers within the drift of
+    /// the device's clock. The first offset also applies to the time before it.
+    pub fn set_clock_offset(&mut self, timestamp: f64, offset: ClockOffset) {
+        let index = self.offsets.partition_point(|(t, _)| *t <= timestamp);
+        self.offsets.insert(index, (timestamp, offset));
+        self.drop_expired();
+    }
+
+    fn drop_expired(&mut self) {
+        let Some(retention) = self.retention else {
+            return;
+        };
+        let newest = self.events.back().map(|e| e.timestamp);
+        let newest = match (newest, self.offsets.back().map(|(t, _)| *t)) {
+            (Some(a), Some(b)) => a.max(b),
+            (a, b) => match a.or(b) {
+                Some(newest) => newest,
+                None => return,
+            },
+        };
+        while self
+            .events
+            .front()
+            .is_some_and(|e| e.timestamp < newest - retention)
+        {
+            self.events.pop_front();
+        }
+        // the newest expired offset still applies to the start of the retained range
+        while self
+            .offsets
+            .get(1)
+            .is_some_and(|(t, _)| *t <= newest - retention)
+        {
+            self.offsets.pop_front();
+        }
+    }
+
+    fn offset_at(&self, timestamp: f64) -> Option<ClockOffset> {
+        let index = self.offsets.partition_point(|(t, _)| *t <= timestamp);
+        self.offsets
+            .get(index.saturating_sub(1))
+            .map(|(_, offset)| *offset)
+    }
+
+    /// Events within `start..=end`, with timestamps made relative to `start`. Once a clock offset
+    /// is set, sync markers every [`SYNC_MARKER_INTERVAL`] from `start` are included.
+    pub fn events_between(&self, start: f64, end: f64) -> Vec<ReplayEvent> {
+        let mut events = self
+            .events
+            .iter()
+            .filter(|e| e.timestamp >= start && e.timestamp <= end)
+            .map(|e| ReplayEvent {
+                timestamp: e.timestamp - start,
+                ..e.clone()
+            })
+            .collect::<Vec<_>>();
+
+        if !self.offsets.is_empty() {
+            let count = ((end - start) / SYNC_MARKER_INTERVAL).floor().max(-1.0) as i64 + 1;
+            for i in 0..count {
+                let time = i as f64 * SYNC_MARKER_INTERVAL;
+                let offset = self.offset_at(start + time).unwrap();
+                let marker = SyncMarker {
+                    reference_time: start + time + offset.offset,
+                    uncertainty: offset.uncertainty,
+                };
+                events.push(marker.into_event(time));
+            }
+            // stable, so markers follow game events of the same time
+            events.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+        }
+        events
+    }
+
+    /// Encodes the events within `start..=end` as a sidecar. `start` should be the timestamp of the
+    /// first video frame written to the output file.
+    pub fn encode_sidecar(&self, start: f64, end: f64) -> Result<Vec<u8>> {
+        let mut bytes = REPLAY_DATA_MAGIC.to_vec();
+        bytes.push(REPLAY_DATA_VERSION);
+        bincode::encode_into_std_write(
+            self.events_between(start, end),
+            &mut bytes,
+            bincode::config::standard(),
+        )
+        .map_err(|e| CommonError::InvalidReplayData(e.to_string()))?;
+        Ok(bytes)
+    }
+}
+
+pub fn decode_sidecar(bytes: &[u8]) -> Result<Vec<ReplayEvent>> {
+    let Some(payload) = bytes
+        .strip_prefix(&REPLAY_DATA_MAGIC)
+        .and_then(|rest| rest.strip_prefix(&[REPLAY_DATA_VERSION]))
+    else {
+        return Err(CommonError::InvalidReplayData(
+            "not a replay data file of a supported version".to_string(),
+        ));
+    };
+    let (events, _) = bincode::decode_from_slice(payload, bincode::config::standard())
+        .map_err(|e| CommonError::InvalidReplayData(e.to_string()))?;
+    Ok(events)
+}
+
+pub fn read_sidecar(path: &Path) -> Result<Vec<ReplayEvent>> {
+    let bytes = std::fs::read(path).map_err(|e| CommonError::ReplayDataIo(e.to_string()))?;
+    decode_sidecar(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(timestamp: f64, kind: u32) -> ReplayEvent {
+        ReplayEvent {
+            timestamp,
+            kind,
+            data: vec![kind as u8],
+        }
+    }
+
+    #[test]
+    fn sidecar_keeps_retained_range_relative_to_start() {
+        let mut track = ReplayDataTrack::new(Some(10.0));
+        track.push(event(1.0, 0));
+        track.push(event(12.0, 2));
+        // late event lands before the newer one
+        track.push(event(11.5, 1));
+        track.push(event(15.0, 3));
+
+        let events = decode_sidecar(&track.encode_sidecar(11.0, 14.0).unwrap()).unwrap();
+        assert_eq!(events, vec![event(0.5, 1), event(1.0, 2)]);
+        // dropped by retention
+        assert!(track.events_between(0.0, 2.0).is_empty());
+    }
+
+    #[test]
+    fn sync_markers_follow_the_latest_offset() {
+        let mut track = ReplayDataTrack::new(Some(10.0));
+        track.push(event(100.5, 1));
+        track.set_clock_offset(
+            101.5,
+            ClockOffset::from_exchange(101.5, 2000.0, 2000.0, 102.0),
+        );
+        // set after a later one
+        track.set_clock_offset(
+            100.2,
+            ClockOffset {
+                offset: 1000.0,
+                uncertainty: 0.002,
+            },
+        );
+
+        let events = decode_sidecar(&track.encode_sidecar(100.0, 102.5).unwrap()).unwrap();
+        let markers = events
+            .iter()
+            .filter_map(|e| SyncMarker::from_event(e).map(|m| (e.timestamp, m.reference_time)))
+            .collect::<Vec<_>>();
+        // the first offset also applies before it was measured
+        assert_eq!(markers, vec![(0.0, 1100.0), (1.0, 1101.0), (2.0, 2000.25)]);
+        assert_eq!(
+            SyncMarker::from_event(&events[3]).unwrap().uncertainty,
+            0.25
+        );
+        assert_eq!(events[1], event(0.5, 1));
+
+        // the offset in effect at the start of the retained range is kept
+        track.push(event(112.0, 2));
+        let events = track.events_between(110.0, 112.0);
+        assert_eq!(
+            SyncMarker::from_event(&events[0]).unwrap().reference_time,
+            2008.25
+        );
+        assert!(
+            track
+                .events_between(100.0, 101.0)
+                .iter()
+                .all(|e| e.kind == SYNC_MARKER_KIND)
+        );
+    }
+
+    #[test]
+    fn no_markers_without_an_offset() {
+        let mut track = ReplayDataTrack::new(None);
+        track.push(event(1.0, 0));
+        assert_eq!(track.events_between(0.0, 5.0), vec![event(1.0, 0)]);
+    }
+}
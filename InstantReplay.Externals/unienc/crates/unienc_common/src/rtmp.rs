@@ -0,0 +1,88 @@
+//! Live publishing to RTMP ingest servers such as Twitch's and YouTube's. The encoded samples go
+//! through the same encoders and muxer wrappers as a recording; backends that can publish return
+//! a muxer writing FLV to the URL from [`EncodingSystem::new_rtmp_muxer`](crate::EncodingSystem).
+//! The stream key is the last segment of the URL path and is kept out of logs.
+
+use crate::{CommonError, Result};
+
+/// Schemes of the URLs publishers accept.
+const SCHEMES: [&str; 2] = ["rtmp", "rtmps"];
+
+fn invalid(url: &str, reason: &str) -> CommonError {
+    CommonError::InvalidStreamUrl(format!("{}: {reason}", redact_stream_key(url)))
+}
+
+/// Checks that `url` is an `rtmp://` or `rtmps://` URL with a host, an application and a stream
+/// key, such as `rtmp://live.twitch.tv/app/<stream key>`.
+pub fn validate_rtmp_url(url: &str) -> Result<()> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return Err(invalid(url, "not a URL"));
+    };
+    if !SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) {
+        return Err(invalid(url, "not an RTMP URL"));
+    }
+    if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(invalid(url, "contains whitespace"));
+    }
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    if host.is_empty() || host.starts_with(':') {
+        return Err(invalid(url, "no host"));
+    }
+    match path.rsplit_once('/') {
+        Some((app, key)) if !app.is_empty() && !key.is_empty() => Ok(()),
+        _ => Err(invalid(url, "no application and stream key")),
+    }
+}
+
+/// `url` with its stream key replaced, for logs and error messages.
+pub fn redact_stream_key(url: &str) -> String {
+    let path_start = url.find("://").map_or(0, |at| at + 3);
+    match url[path_start..].rfind('/') {
+        Some(at) if path_start + at + 1 < url.len() => {
+            format!("{}/<stream key>", &url[..path_start + at])
+        }
+        _ => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_ingest_urls_only() {
+        assert!(validate_rtmp_url("rtmp://live.twitch.tv/app/live_123_abc").is_ok());
+        assert!(validate_rtmp_url("RTMPS://a.rtmp.youtube.com:443/live2/x-y-z").is_ok());
+
+        for url in [
+            "",
+            "live.twitch.tv/app/key",
+            "https://live.twitch.tv/app/key",
+            "rtmp:///app/key",
+            "rtmp://live.twitch.tv/app/",
+            "rtmp://live.twitch.tv/key",
+            "rtmp://live.twitch.tv/app/my key",
+        ] {
+            assert!(
+                matches!(
+                    validate_rtmp_url(url),
+                    Err(CommonError::InvalidStreamUrl(_))
+                ),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_stream_key_out_of_messages() {
+        assert_eq!(
+            redact_stream_key("rtmp://live.twitch.tv/app/live_123_abc"),
+            "rtmp://live.twitch.tv/app/<stream key>"
+        );
+        assert_eq!(redact_stream_key("rtmp://host"), "rtmp://host");
+        let Err(err) = validate_rtmp_url("rtmp://live.twitch.tv/app/secret key") else {
+            panic!("accepted a key with a space");
+        };
+        assert!(!err.to_string().contains("secret"));
+    }
+}
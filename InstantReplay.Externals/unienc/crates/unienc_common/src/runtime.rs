@@ -1,4 +1,6 @@
 use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Poll, Waker};
 
 pub trait Spawn {
     fn spawn(&self, future: impl Future<Output = ()> + Send + 'static);
@@ -26,3 +28,155 @@ pub trait SpawnExt: Spawn {
 }
 
 impl<T: Spawn + ?Sized> SpawnExt for T {}
+
+/// Runtime counting the tasks spawned through it, so that an encoding system can wait for the
+/// tasks of its components when it is shut down.
+pub struct TrackedRuntime<R> {
+    inner: R,
+    tasks: Arc<Mutex<Tasks>>,
+}
+
+#[derive(Default)]
+struct Tasks {
+    running: usize,
+    idle_wakers: Vec<Waker>,
+}
+
+/// Counts a task as running until it is dropped, finished or not.
+struct TaskGuard(Arc<Mutex<Tasks>>);
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        let mut tasks = lock(&self.0);
+        tasks.running -= 1;
+        if tasks.running == 0 {
+            tasks.idle_wakers.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+fn lock(tasks: &Mutex<Tasks>) -> MutexGuard<'_, Tasks> {
+    tasks.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<R: Runtime> TrackedRuntime<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            tasks: Default::default(),
+        }
+    }
+
+    /// Completes once no task spawned through this runtime or its clones is running.
+    pub fn idle(&self) -> impl Future<Output = ()> + Send + use<R> {
+        let tasks = self.tasks.clone();
+        std::future::poll_fn(move |cx| {
+            let mut tasks = lock(&tasks);
+            if tasks.running == 0 {
+                return Poll::Ready(());
+            }
+            if !tasks.idle_wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                tasks.idle_wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+    }
+
+    fn track(&self) -> TaskGuard {
+        lock(&self.tasks).running += 1;
+        TaskGuard(self.tasks.clone())
+    }
+}
+
+impl<R: Runtime> Clone for TrackedRuntime<R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            tasks: self.tasks.clone(),
+        }
+    }
+}
+
+impl<R: Runtime> Spawn for TrackedRuntime<R> {
+    fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        let guard = self.track();
+        self.inner.spawn(async move {
+            future.await;
+            drop(guard);
+        });
+    }
+}
+
+impl<R: Runtime> SpawnBlocking for TrackedRuntime<R> {
+    fn spawn_blocking<Result: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> Result + Send + 'static,
+    ) -> Pin<Box<dyn Future<Output = Result> + Send + 'static>> {
+        let guard = self.track();
+        // the work keeps running when the returned future is dropped
+        self.inner.spawn_blocking(move || {
+            let _guard = guard;
+            f()
+        })
+    }
+}
+
+impl<R: Runtime> Runtime for TrackedRuntime<R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Runs spawned futures on threads of their own.
+    #[derive(Clone)]
+    struct ThreadRuntime;
+
+    impl Spawn for ThreadRuntime {
+        fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+            std::thread::spawn(move || block_on(future));
+        }
+    }
+
+    impl SpawnBlocking for ThreadRuntime {
+        fn spawn_blocking<Result: Send + 'static>(
+            &self,
+            f: impl FnOnce() -> Result + Send + 'static,
+        ) -> Pin<Box<dyn Future<Output = Result> + Send + 'static>> {
+            let handle = std::thread::spawn(f);
+            Box::pin(async move { handle.join().unwrap() })
+        }
+    }
+
+    impl Runtime for ThreadRuntime {}
+
+    #[test]
+    fn idle_waits_for_spawned_tasks() {
+        let runtime = TrackedRuntime::new(ThreadRuntime);
+        block_on(runtime.idle());
+
+        let (spawned, blocking) = (
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicBool::new(false)),
+        );
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let done = spawned.clone();
+        runtime.clone().spawn(async move {
+            let _ = rx.recv();
+            done.store(true, Ordering::Release);
+        });
+        let done = blocking.clone();
+        // dropping the future does not stop the work
+        drop(runtime.spawn_blocking(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            done.store(true, Ordering::Release);
+        }));
+
+        let idle = runtime.idle();
+        drop(tx);
+        block_on(idle);
+        assert!(spawned.load(Ordering::Acquire));
+        assert!(blocking.load(Ordering::Acquire));
+    }
+}
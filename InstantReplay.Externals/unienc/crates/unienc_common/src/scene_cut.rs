@@ -0,0 +1,139 @@
+//! Detection of hard cuts, such as a camera switch or a loading screen, so the encoder can start a
+//! keyframe there and seeking in an exported replay lands on the new scene. Frames are compared by
+//! a histogram of their luma, which barely changes with motion but shifts at a cut.
+
+use crate::{EncoderInput, Result, VideoFrame, VideoFrameBgra32, VideoSample};
+
+/// Bins of the luma histogram.
+const BINS: usize = 64;
+
+/// Shortest time in seconds between requested keyframes, so flashes and fades do not turn every
+/// frame into one.
+pub const MIN_INTERVAL: f64 = 0.5;
+
+/// Video encoder input that asks the encoder for a keyframe at frames whose luma histogram differs
+/// from the previous frame by more than a threshold, once one is set. Encoders that cannot be
+/// asked keep their own keyframe interval. Blit sources are not read back on the CPU, so they are
+/// not compared.
+pub struct SceneCutVideoInput<I> {
+    inner: I,
+    threshold: Option<f32>,
+    previous: Option<[f32; BINS]>,
+    /// Timestamp of the last keyframe requested.
+    last_cut: Option<f64>,
+    cuts: u64,
+}
+
+impl<I> SceneCutVideoInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            threshold: None,
+            previous: None,
+            last_cut: None,
+            cuts: 0,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// `threshold` is the share of pixels, between 0 and 1, that must move to another luma bin for
+    /// a frame to be a cut; around 0.5 catches hard cuts only. `None` requests no keyframes.
+    pub fn set_threshold(&mut self, threshold: Option<f32>) {
+        self.threshold = threshold;
+        self.previous = None;
+    }
+
+    /// Keyframes requested so far.
+    pub fn cuts(&self) -> u64 {
+        self.cuts
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for SceneCutVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        let Some(threshold) = self.threshold else {
+            return self.inner.push(data).await;
+        };
+        let VideoFrame::Bgra32(frame) = &data.frame else {
+            self.previous = None;
+            return self.inner.push(data).await;
+        };
+        let histogram = luma_histogram(frame);
+        let cut = self
+            .previous
+            .replace(histogram)
+            .is_some_and(|previous| delta(&previous, &histogram) > threshold)
+            && self
+                .last_cut
+                .is_none_or(|last| data.timestamp - last >= MIN_INTERVAL);
+        if cut && self.inner.request_keyframe() {
+            self.last_cut = Some(data.timestamp);
+            self.cuts += 1;
+        }
+        self.inner.push(data).await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+/// Histogram of the luma of every other pixel of every other row, normalized to sum to 1.
+fn luma_histogram(frame: &VideoFrameBgra32) -> [f32; BINS] {
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let stride = frame.stride as usize;
+    let data = frame.buffer.data();
+    let mut counts = [0u32; BINS];
+    for y in (0..height).step_by(2) {
+        let row = &data[y * stride..][..width * 4];
+        for pixel in row.chunks_exact(4).step_by(2) {
+            let [b, g, r, _] = pixel[..4] else {
+                unreachable!();
+            };
+            let luma = (29 * b as u32 + 150 * g as u32 + 77 * r as u32) >> 8;
+            counts[luma as usize * BINS / 256] += 1;
+        }
+    }
+
+    let total = counts.iter().sum::<u32>().max(1) as f32;
+    counts.map(|count| count as f32 / total)
+}
+
+/// Share of pixels that moved to another bin, between 0 and 1.
+fn delta(a: &[f32; BINS], b: &[f32; BINS]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b).abs()).sum::<f32>() / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Frames, push};
+
+    #[test]
+    fn requests_keyframes_at_cuts_no_closer_than_the_interval() {
+        let mut input = SceneCutVideoInput::new(Frames::default());
+        input.set_threshold(Some(0.5));
+        for (i, value) in [10, 11, 200, 20, 20, 200].into_iter().enumerate() {
+            push(&mut input, i as f64 / 4.0, (8, 8), vec![value; 8 * 8 * 4]);
+        }
+        assert_eq!(input.inner_mut().keyframes(), vec![0.5, 1.25]);
+        assert_eq!(input.cuts(), 2);
+    }
+
+    #[test]
+    fn requests_nothing_without_a_threshold() {
+        let mut input = SceneCutVideoInput::new(Frames::default());
+        push(&mut input, 0.0, (8, 8), vec![10; 8 * 8 * 4]);
+        push(&mut input, 1.0, (8, 8), vec![200; 8 * 8 * 4]);
+        assert!(input.inner_mut().keyframes().is_empty());
+    }
+}
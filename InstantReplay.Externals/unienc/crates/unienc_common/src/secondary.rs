@@ -0,0 +1,292 @@
+//! Outputs fed alongside a primary one in a failure domain of their own, such as a live stream of
+//! a local recording. Every sample goes to both, but when the secondary output fails, or a push
+//! to it takes longer than [`SECONDARY_PUSH_TIMEOUT`], it is detached and reported instead of
+//! failing the push, so the primary output is written as if it were alone.
+
+use std::future::poll_fn;
+use std::pin::pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::deadline::with_deadline;
+use crate::ladder::scale_bgra;
+use crate::{
+    CommonError, EncodedData, EncoderInput, MuxerInput, Rendition, Result, ResultExt, VideoFrame,
+    VideoSample,
+};
+
+/// Longest a push to a secondary output may take before it is detached, so that a stalled
+/// connection holds the primary output back only once.
+pub const SECONDARY_PUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+type FailureCallback = Box<dyn FnOnce(CommonError) + Send>;
+
+/// Failure of a secondary output, shared by the inputs feeding its tracks so that a failure of
+/// one detaches all of them and is reported once.
+#[derive(Clone)]
+pub struct SecondaryFailure {
+    state: Arc<Mutex<(bool, Option<FailureCallback>)>>,
+}
+
+impl SecondaryFailure {
+    pub fn new(on_failure: impl FnOnce(CommonError) + Send + 'static) -> Self {
+        Self {
+            state: Arc::new(Mutex::new((false, Some(Box::new(on_failure))))),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, (bool, Option<FailureCallback>)> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Detaches the secondary output from every input sharing this, reporting `err` unless it
+    /// had already failed.
+    pub fn fail(&self, err: CommonError) {
+        let on_failure = {
+            let mut state = self.lock();
+            state.0 = true;
+            state.1.take()
+        };
+        if let Some(on_failure) = on_failure {
+            on_failure(err);
+        }
+    }
+
+    pub fn has_failed(&self) -> bool {
+        self.lock().0
+    }
+}
+
+/// Secondary input of a track, dropped once the secondary output has failed.
+struct Secondary<S> {
+    input: Option<S>,
+    failure: SecondaryFailure,
+}
+
+impl<S> Secondary<S> {
+    fn get(&mut self) -> Option<&mut S> {
+        if self.failure.has_failed() {
+            self.input = None;
+        }
+        self.input.as_mut()
+    }
+
+    fn take(&mut self) -> Option<S> {
+        self.get();
+        self.input.take()
+    }
+
+    fn fail(&mut self, err: CommonError) {
+        self.input = None;
+        self.failure.fail(err);
+    }
+}
+
+/// Polls both futures until both have completed.
+async fn join<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+    let (mut a, mut b) = (pin!(a), pin!(b));
+    let (mut a_output, mut b_output) = (None, None);
+    poll_fn(|cx| {
+        if a_output.is_none()
+            && let Poll::Ready(output) = a.as_mut().poll(cx)
+        {
+            a_output = Some(output);
+        }
+        if b_output.is_none()
+            && let Poll::Ready(output) = b.as_mut().poll(cx)
+        {
+            b_output = Some(output);
+        }
+        match a_output.is_some() && b_output.is_some() {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    })
+    .await;
+    (a_output.unwrap(), b_output.unwrap())
+}
+
+/// Video encoder input that also pushes each frame, scaled to the size of a rendition, to the
+/// encoder of a secondary output. Only frames in memory can be scaled, so a blit source detaches
+/// the secondary output and goes to the primary one alone.
+pub struct SecondaryVideoInput<P, S> {
+    primary: P,
+    secondary: Secondary<S>,
+    rendition: Rendition,
+}
+
+impl<P, S> SecondaryVideoInput<P, S> {
+    /// `secondary` is an encoder set up with the size of `rendition`.
+    pub fn new(primary: P, secondary: S, rendition: Rendition, failure: SecondaryFailure) -> Self {
+        Self {
+            primary,
+            secondary: Secondary {
+                input: Some(secondary),
+                failure,
+            },
+            rendition,
+        }
+    }
+
+    pub fn primary_mut(&mut self) -> &mut P {
+        &mut self.primary
+    }
+}
+
+impl<B, P, S> EncoderInput for SecondaryVideoInput<P, S>
+where
+    B: Send + 'static,
+    P: EncoderInput<Data = VideoSample<B>>,
+    S: EncoderInput<Data = VideoSample<B>>,
+{
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        let (width, height) = (self.rendition.width, self.rendition.height);
+        let Some(secondary) = self.secondary.get() else {
+            return self.primary.push(data).await;
+        };
+        let VideoFrame::Bgra32(frame) = &data.frame else {
+            self.secondary.fail(CommonError::BlitNotSupported);
+            return self.primary.push(data).await;
+        };
+        let copy = VideoSample {
+            frame: VideoFrame::Bgra32(scale_bgra(frame, width, height)),
+            timestamp: data.timestamp,
+        };
+
+        let (primary, secondary) = join(
+            self.primary.push(data),
+            with_deadline(Some(SECONDARY_PUSH_TIMEOUT), secondary.push(copy)),
+        )
+        .await;
+        if let Err(err) = secondary {
+            self.secondary.fail(err);
+        }
+        primary
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        if let Some(secondary) = self.secondary.get() {
+            secondary.request_keyframe();
+        }
+        self.primary.request_keyframe()
+    }
+}
+
+/// Muxer input that also pushes each encoded sample to the muxer of a secondary output, so that
+/// the track is encoded once for both.
+pub struct SecondaryMuxerInput<P, S> {
+    primary: P,
+    secondary: Secondary<S>,
+}
+
+impl<P, S> SecondaryMuxerInput<P, S> {
+    pub fn new(primary: P, secondary: S, failure: SecondaryFailure) -> Self {
+        Self {
+            primary,
+            secondary: Secondary {
+                input: Some(secondary),
+                failure,
+            },
+        }
+    }
+}
+
+impl<D, P, S> MuxerInput for SecondaryMuxerInput<P, S>
+where
+    D: EncodedData + Send,
+    P: MuxerInput<Data = D>,
+    S: MuxerInput<Data = D>,
+{
+    type Data = D;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        let Some(secondary) = self.secondary.get() else {
+            return self.primary.push(data).await;
+        };
+        let config = bincode::config::standard();
+        let copy = bincode::encode_to_vec(&data, config)
+            .context("Failed to copy sample")
+            .and_then(|bytes| {
+                bincode::decode_from_slice(&bytes, config).context("Failed to copy sample")
+            });
+        let (copy, _) = match copy {
+            Ok(copy) => copy,
+            Err(err) => {
+                self.secondary.fail(err);
+                return self.primary.push(data).await;
+            }
+        };
+
+        let (primary, secondary) = join(
+            self.primary.push(data),
+            with_deadline(Some(SECONDARY_PUSH_TIMEOUT), secondary.push(copy)),
+        )
+        .await;
+        if let Err(err) = secondary {
+            self.secondary.fail(err);
+        }
+        primary
+    }
+
+    async fn finish(mut self) -> Result<()> {
+        let Some(secondary) = self.secondary.take() else {
+            return self.primary.finish().await;
+        };
+        let (primary, secondary) = join(
+            self.primary.finish(),
+            with_deadline(Some(SECONDARY_PUSH_TIMEOUT), secondary.finish()),
+        )
+        .await;
+        if let Err(err) = secondary {
+            self.secondary.fail(err);
+        }
+        primary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Frames, push};
+
+    fn sizes(frames: &Frames) -> Vec<(u32, u32)> {
+        let pushed = frames.pushed();
+        pushed
+            .iter()
+            .map(|frame| (frame.width, frame.height))
+            .collect()
+    }
+
+    #[test]
+    fn detaches_a_failing_secondary_output() {
+        let (primary, secondary) = (Frames::default(), Frames::failing_after(1));
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let failure = SecondaryFailure::new({
+            let failures = failures.clone();
+            move |err| failures.lock().unwrap().push(err.to_string())
+        });
+        let mut input = SecondaryVideoInput::new(
+            primary.clone(),
+            secondary.clone(),
+            Rendition {
+                width: 2,
+                height: 2,
+                bitrate: 1,
+            },
+            failure.clone(),
+        );
+
+        for i in 0..3 {
+            push(&mut input, i as f64, (4, 4), vec![0; 4 * 4 * 4]);
+        }
+        failure.fail(CommonError::StreamDisconnected("again".to_string()));
+
+        assert_eq!(sizes(&primary), vec![(4, 4); 3]);
+        assert_eq!(sizes(&secondary), vec![(2, 2)]);
+        assert_eq!(failures.lock().unwrap().len(), 1);
+        assert!(failure.has_failed());
+    }
+}
@@ -0,0 +1,482 @@
+//! Session captures: the exact sequence of samples pushed to the encoders of a recording, saved so
+//! that it can be replayed into any backend later. Replaying the same capture into two encoders
+//! compares them on identical input, and a capture sent along with a bug report reproduces the
+//! session that triggered it.
+//!
+//! While a capture is on, every video frame and audio sample pushed to an encoder through
+//! [`CapturedVideoInput`] or [`CapturedAudioInput`] is recorded in push order, with its timestamp
+//! and a hash of its content. The content itself is kept up to a limit in bytes; beyond it, and for
+//! frames blitted from textures, replays push synthetic frames of the same size and silence of the
+//! same length instead, which still reproduces the timing of the session.
+//!
+//! A capture is saved as [`SESSION_CAPTURE_MAGIC`], the version byte, then the samples encoded
+//! with bincode's standard configuration.
+
+use std::path::Path;
+use std::pin::pin;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use bincode::{Decode, Encode};
+
+use crate::bench::bgra_frame;
+use crate::buffer::SharedBuffer;
+use crate::pipeline::try_join;
+use crate::{
+    AudioSample, CancellationToken, CommonError, CompletionHandle, Encoder, EncoderInput,
+    EncodingSystem, Muxer, Result, VideoFrame, VideoFrameBgra32, VideoSample, drive,
+};
+
+/// Leading bytes of every session capture file.
+pub const SESSION_CAPTURE_MAGIC: [u8; 4] = *b"URSC";
+pub const SESSION_CAPTURE_VERSION: u8 = 1;
+
+/// Number of encoded samples of each track queued while a muxer is busy during a replay.
+const REPLAY_QUEUE_CAPACITY: usize = 16;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub enum CapturedSample {
+    Video {
+        timestamp: f64,
+        width: u32,
+        height: u32,
+        /// [`content_hash`] of the BGRA rows without their padding.
+        hash: u64,
+        /// The BGRA rows without their padding, unless over the data limit.
+        pixels: Option<Vec<u8>>,
+    },
+    /// Frame blitted from a texture, whose pixels are not readable.
+    Blit {
+        timestamp: f64,
+        width: u32,
+        height: u32,
+    },
+    Audio {
+        timestamp_in_samples: u64,
+        /// Interleaved samples of every channel.
+        len: u32,
+        /// [`content_hash`] of the samples as 16-bit little-endian PCM.
+        hash: u64,
+        /// Unless over the data limit.
+        data: Option<Vec<i16>>,
+    },
+}
+
+impl CapturedSample {
+    /// Same sample regardless of whether its content was kept.
+    pub fn matches(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Video {
+                    timestamp,
+                    width,
+                    height,
+                    hash,
+                    ..
+                },
+                Self::Video {
+                    timestamp: other_timestamp,
+                    width: other_width,
+                    height: other_height,
+                    hash: other_hash,
+                    ..
+                },
+            ) => {
+                timestamp.to_bits() == other_timestamp.to_bits()
+                    && (width, height, hash) == (other_width, other_height, other_hash)
+            }
+            (
+                Self::Audio {
+                    timestamp_in_samples,
+                    len,
+                    hash,
+                    ..
+                },
+                Self::Audio {
+                    timestamp_in_samples: other_timestamp,
+                    len: other_len,
+                    hash: other_hash,
+                    ..
+                },
+            ) => (timestamp_in_samples, len, hash) == (other_timestamp, other_len, other_hash),
+            (blit @ Self::Blit { .. }, other) => blit == other,
+            _ => false,
+        }
+    }
+}
+
+/// Index of the first sample of `a` that differs from that of `b`, or of the end of the shorter
+/// one if only their lengths differ.
+pub fn first_divergence(a: &[CapturedSample], b: &[CapturedSample]) -> Option<usize> {
+    a.iter()
+        .zip(b)
+        .position(|(a, b)| !a.matches(b))
+        .or_else(|| (a.len() != b.len()).then_some(a.len().min(b.len())))
+}
+
+/// FNV-1a of `chunks` in order, folding in a 64-bit little-endian word at a time and the bytes
+/// left over at the end of each chunk one at a time. Stable across builds and platforms, unlike
+/// the hashers of the standard library.
+pub fn content_hash<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+    for chunk in chunks {
+        let words = chunk.chunks_exact(8);
+        let rest = words.remainder();
+        for word in words {
+            hash = (hash ^ u64::from_le_bytes(word.try_into().unwrap())).wrapping_mul(FNV_PRIME);
+        }
+        for &byte in rest {
+            hash = (hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+struct Capture {
+    samples: Vec<CapturedSample>,
+    data_limit: usize,
+    data_len: usize,
+}
+
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+fn lock() -> MutexGuard<'static, Option<Capture>> {
+    CAPTURE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Records the samples pushed to captured inputs from now on, discarding a capture in progress.
+/// The content of the samples is kept until it adds up to `data_limit` bytes; 0 keeps hashes only.
+pub fn start_session_capture(data_limit: usize) {
+    *lock() = Some(Capture {
+        samples: Vec::new(),
+        data_limit,
+        data_len: 0,
+    });
+}
+
+/// Stops the capture in progress, returning its samples, or `None` if none was on.
+pub fn stop_session_capture() -> Option<Vec<CapturedSample>> {
+    lock().take().map(|capture| capture.samples)
+}
+
+pub fn is_capturing() -> bool {
+    lock().is_some()
+}
+
+/// Whether the content of a sample of `len` bytes is kept, counting it if so, or `None` if no
+/// capture is on.
+fn reserve(len: usize) -> Option<bool> {
+    let mut capture = lock();
+    let capture = capture.as_mut()?;
+    let keep = capture.data_len + len <= capture.data_limit;
+    if keep {
+        capture.data_len += len;
+    }
+    Some(keep)
+}
+
+fn record(sample: CapturedSample) {
+    if let Some(capture) = lock().as_mut() {
+        capture.samples.push(sample);
+    }
+}
+
+fn capture_frame(frame: &VideoFrameBgra32, timestamp: f64) {
+    let row_len = frame.width as usize * 4;
+    let Some(keep) = reserve(row_len * frame.height as usize) else {
+        return;
+    };
+    let data = frame.buffer.data();
+    let rows =
+        || (0..frame.height as usize).map(move |y| &data[y * frame.stride as usize..][..row_len]);
+    record(CapturedSample::Video {
+        timestamp,
+        width: frame.width,
+        height: frame.height,
+        hash: content_hash(rows()),
+        pixels: keep.then(|| rows().flatten().copied().collect()),
+    });
+}
+
+fn capture_audio(sample: &AudioSample) {
+    let Some(keep) = reserve(sample.data.len() * size_of::<i16>()) else {
+        return;
+    };
+    record(CapturedSample::Audio {
+        timestamp_in_samples: sample.timestamp_in_samples,
+        len: sample.data.len() as u32,
+        hash: content_hash([sample.data_as_s16le_bytes().as_slice()]),
+        data: keep.then(|| sample.data.clone()),
+    });
+}
+
+/// Video encoder input recording each frame in the session capture, if one is on, before passing
+/// it on.
+pub struct CapturedVideoInput<I> {
+    inner: I,
+}
+
+impl<I> CapturedVideoInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for CapturedVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        match &data.frame {
+            VideoFrame::Bgra32(frame) => capture_frame(frame, data.timestamp),
+            VideoFrame::BlitSource { width, height, .. } => {
+                if is_capturing() {
+                    record(CapturedSample::Blit {
+                        timestamp: data.timestamp,
+                        width: *width,
+                        height: *height,
+                    });
+                }
+            }
+        }
+        self.inner.push(data).await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+/// Audio encoder input recording each sample in the session capture, if one is on, before passing
+/// it on.
+pub struct CapturedAudioInput<I> {
+    inner: I,
+}
+
+impl<I> CapturedAudioInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+}
+
+impl<I: EncoderInput<Data = AudioSample>> EncoderInput for CapturedAudioInput<I> {
+    type Data = AudioSample;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        capture_audio(&data);
+        self.inner.push(data).await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+pub fn encode_session_capture(samples: &[CapturedSample]) -> Result<Vec<u8>> {
+    let mut bytes = SESSION_CAPTURE_MAGIC.to_vec();
+    bytes.push(SESSION_CAPTURE_VERSION);
+    bincode::encode_into_std_write(samples, &mut bytes, bincode::config::standard())
+        .map_err(|e| CommonError::InvalidSessionCapture(e.to_string()))?;
+    Ok(bytes)
+}
+
+pub fn decode_session_capture(bytes: &[u8]) -> Result<Vec<CapturedSample>> {
+    let Some(payload) = bytes
+        .strip_prefix(&SESSION_CAPTURE_MAGIC)
+        .and_then(|rest| rest.strip_prefix(&[SESSION_CAPTURE_VERSION]))
+    else {
+        return Err(CommonError::InvalidSessionCapture(
+            "not a session capture of a supported version".to_string(),
+        ));
+    };
+    let (samples, _) = bincode::decode_from_slice(payload, bincode::config::standard())
+        .map_err(|e| CommonError::InvalidSessionCapture(e.to_string()))?;
+    Ok(samples)
+}
+
+pub fn write_session_capture(samples: &[CapturedSample], path: &Path) -> Result<()> {
+    std::fs::write(path, encode_session_capture(samples)?)
+        .map_err(|e| CommonError::SessionCaptureIo(e.to_string()))
+}
+
+pub fn read_session_capture(path: &Path) -> Result<Vec<CapturedSample>> {
+    let bytes = std::fs::read(path).map_err(|e| CommonError::SessionCaptureIo(e.to_string()))?;
+    decode_session_capture(&bytes)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionReplay {
+    pub video_frames: u32,
+    pub audio_samples: u32,
+    /// Video frames and audio samples pushed in place of content that was not kept.
+    pub synthesized: u32,
+    /// From the first push until the file is complete.
+    pub elapsed: Duration,
+}
+
+/// Pushes `samples` in their captured order to new encoders of `system`, muxing their output into
+/// a new file at `path`.
+pub async fn replay_session_capture<S: EncodingSystem>(
+    system: &S,
+    samples: &[CapturedSample],
+    path: &Path,
+) -> Result<SessionReplay> {
+    let (mut video_input, video_output) = system.new_video_encoder()?.get()?;
+    let (mut audio_input, audio_output) = system.new_audio_encoder()?.get()?;
+    let (video_muxer_input, audio_muxer_input, completion_handle) =
+        system.new_muxer(path)?.get_inputs()?;
+
+    let start = Instant::now();
+    let cancel = CancellationToken::new();
+    let push = pin!(async {
+        let mut replay = SessionReplay {
+            video_frames: 0,
+            audio_samples: 0,
+            synthesized: 0,
+            elapsed: Duration::ZERO,
+        };
+        for sample in samples {
+            match sample {
+                CapturedSample::Video {
+                    timestamp,
+                    width,
+                    height,
+                    pixels: Some(pixels),
+                    ..
+                } => {
+                    let buffer = SharedBuffer::new_unmanaged(pixels.clone());
+                    let frame = VideoFrameBgra32::packed(buffer, *width, *height);
+                    video_input
+                        .push(VideoSample {
+                            frame: VideoFrame::Bgra32(frame),
+                            timestamp: *timestamp,
+                        })
+                        .await?;
+                }
+                CapturedSample::Video {
+                    timestamp,
+                    width,
+                    height,
+                    pixels: None,
+                    ..
+                }
+                | CapturedSample::Blit {
+                    timestamp,
+                    width,
+                    height,
+                } => {
+                    video_input
+                        .push(VideoSample {
+                            frame: bgra_frame(*width, *height, replay.video_frames),
+                            timestamp: *timestamp,
+                        })
+                        .await?;
+                    replay.synthesized += 1;
+                }
+                CapturedSample::Audio {
+                    timestamp_in_samples,
+                    len,
+                    data,
+                    ..
+                } => {
+                    if data.is_none() {
+                        replay.synthesized += 1;
+                    }
+                    audio_input
+                        .push(AudioSample {
+                            data: data.clone().unwrap_or_else(|| vec![0; *len as usize]),
+                            timestamp_in_samples: *timestamp_in_samples,
+                        })
+                        .await?;
+                    replay.audio_samples += 1;
+                    continue;
+                }
+            }
+            replay.video_frames += 1;
+        }
+        // dropping the inputs ends the encoders
+        drop((video_input, audio_input));
+        Ok(replay)
+    });
+    let video = pin!(drive(
+        video_output,
+        video_muxer_input,
+        REPLAY_QUEUE_CAPACITY,
+        &cancel
+    ));
+    let audio = pin!(drive(
+        audio_output,
+        audio_muxer_input,
+        REPLAY_QUEUE_CAPACITY,
+        &cancel
+    ));
+    let muxed = pin!(try_join(video, audio));
+    let (mut replay, _) = try_join(push, muxed).await?;
+    completion_handle.finish().await?;
+
+    replay.elapsed = start.elapsed();
+    Ok(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Frames, push_frame};
+
+    #[test]
+    fn records_pushes_and_finds_where_captures_diverge() {
+        let mut input = CapturedVideoInput::new(Frames::default());
+        let push = |input: &mut CapturedVideoInput<Frames>, index: u32, stride: usize| {
+            let frame = VideoFrameBgra32 {
+                buffer: SharedBuffer::new_unmanaged(vec![index as u8; stride * 2]),
+                width: 2,
+                height: 2,
+                stride: stride as u32,
+            };
+            push_frame(input, index as f64 / 30.0, frame);
+        };
+
+        push(&mut input, 0, 8);
+        start_session_capture(16);
+        push(&mut input, 1, 8);
+        // the padding of the rows is neither hashed nor kept
+        push(&mut input, 2, 12);
+        let samples = stop_session_capture().unwrap();
+        push(&mut input, 3, 8);
+        assert_eq!(input.inner_mut().pushed().len(), 4);
+
+        let [first, second] = &samples[..] else {
+            panic!("expected two samples, got {samples:?}");
+        };
+        let CapturedSample::Video {
+            hash,
+            pixels: Some(pixels),
+            ..
+        } = first
+        else {
+            panic!("the first frame should be kept");
+        };
+        assert_eq!((*hash, pixels.len()), (content_hash([&[1; 8][..]; 2]), 16));
+        assert!(matches!(second, CapturedSample::Video { pixels: None, .. }));
+
+        let decoded = decode_session_capture(&encode_session_capture(&samples).unwrap()).unwrap();
+        assert_eq!(decoded, samples);
+        assert_eq!(first_divergence(&samples, &decoded), None);
+        assert_eq!(first_divergence(&samples, &samples[..1]), Some(1));
+        assert_eq!(first_divergence(&samples[1..], &samples[..1]), Some(0));
+    }
+}
@@ -0,0 +1,226 @@
+//! Session directories: a directory of its own for the spool files, journals and intermediate
+//! segments of each recording session, so that what a session leaves behind is in one place and
+//! can be cleaned up. A session that completes removes its directory. One that fails, or is dropped
+//! before completing, keeps it for diagnostics with a [`FAILED_MARKER`] file giving the reason, and
+//! one cut short by a crash keeps it as is. [`purge_stale_sessions`], meant to be called at startup,
+//! removes the directories kept longer than a retention period.
+//!
+//! Directories are named [`SESSION_DIR_PREFIX`] followed by the ID of the process that created
+//! them, the creation time in milliseconds since the Unix epoch and a counter.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, SystemTime};
+
+use crate::{CommonError, Result};
+
+pub const SESSION_DIR_PREFIX: &str = "unienc-session-";
+/// File written into the directory of a failed session, holding the reason as UTF-8.
+pub const FAILED_MARKER: &str = "FAILED";
+
+static NEXT_SESSION: AtomicU32 = AtomicU32::new(0);
+
+fn io_error(path: &Path, err: std::io::Error) -> CommonError {
+    CommonError::SessionDirIo(format!("{}: {err}", path.display()))
+}
+
+pub struct SessionDir {
+    path: PathBuf,
+    finished: bool,
+}
+
+impl SessionDir {
+    /// Creates the directory of a new session in `root`, creating `root` as well if needed.
+    pub fn create(root: &Path) -> Result<Self> {
+        std::fs::create_dir_all(root).map_err(|e| io_error(root, e))?;
+        let created = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = root.join(format!(
+            "{SESSION_DIR_PREFIX}{}-{created}-{}",
+            std::process::id(),
+            NEXT_SESSION.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir(&path).map_err(|e| io_error(&path, e))?;
+        Ok(Self {
+            path,
+            finished: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of a file named `name` in the directory.
+    pub fn file(&self, name: impl AsRef<Path>) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Removes the directory with everything in it.
+    pub fn complete(mut self) -> Result<()> {
+        self.finished = true;
+        std::fs::remove_dir_all(&self.path).map_err(|e| io_error(&self.path, e))
+    }
+
+    /// Keeps the directory, marking it as failed for `reason`, and returns its path.
+    pub fn fail(mut self, reason: &str) -> PathBuf {
+        self.finished = true;
+        mark_failed(&self.path, reason);
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Drop for SessionDir {
+    fn drop(&mut self) {
+        if !self.finished {
+            mark_failed(&self.path, "dropped before it completed");
+        }
+    }
+}
+
+fn mark_failed(path: &Path, reason: &str) {
+    let marker = path.join(FAILED_MARKER);
+    if let Err(err) = std::fs::write(&marker, reason) {
+        crate::log!("Failed to mark {} as failed: {err}", path.display());
+    }
+}
+
+/// ID of the process that created the session directory `name`.
+fn owner(name: &str) -> Option<u32> {
+    let (pid, _) = name.strip_prefix(SESSION_DIR_PREFIX)?.split_once('-')?;
+    pid.parse().ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PurgeReport {
+    pub removed: u32,
+    /// Directories within the retention period, of this process, or that could not be removed.
+    pub kept: u32,
+}
+
+/// Time the directory or file at `path` was last modified, or anything in it: a session writing to
+/// its files does not update the modification time of its directory, so a session still recording
+/// in another process would look stale by the directory alone.
+fn last_modified(path: &Path, metadata: &std::fs::Metadata) -> Option<SystemTime> {
+    let mut newest = metadata.modified().ok()?;
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path).ok()?.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if let Some(modified) = last_modified(&entry.path(), &metadata) {
+                newest = newest.max(modified);
+            }
+        }
+    }
+    Some(newest)
+}
+
+/// Removes the session directories in `root` nothing in which was modified for at least
+/// `retention`, failed or not, except those of this process. Other files and directories in
+/// `root` are left alone, and a `root` that does not exist has none to remove.
+pub fn purge_stale_sessions(root: &Path, retention: Duration) -> Result<PurgeReport> {
+    let mut report = PurgeReport::default();
+    let entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(report),
+        Err(err) => return Err(io_error(root, err)),
+    };
+    let now = SystemTime::now();
+    for entry in entries {
+        let entry = entry.map_err(|e| io_error(root, e))?;
+        let name = entry.file_name();
+        let Some(owner) = name.to_str().and_then(owner) else {
+            continue;
+        };
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !metadata.is_dir() {
+            continue;
+        }
+        let age = last_modified(&entry.path(), &metadata)
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        if owner == std::process::id() || age < retention {
+            report.kept += 1;
+            continue;
+        }
+        match std::fs::remove_dir_all(entry.path()) {
+            Ok(()) => report.removed += 1,
+            Err(err) => {
+                crate::log!("Failed to remove {}: {err}", entry.path().display());
+                report.kept += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_failed_sessions_until_purged() {
+        let root = std::env::temp_dir().join(format!("unienc-session-dir-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        let completed = SessionDir::create(&root).unwrap();
+        std::fs::write(completed.file("spool"), b"frames").unwrap();
+        let completed_path = completed.path().to_path_buf();
+        completed.complete().unwrap();
+        assert!(!completed_path.exists());
+
+        let failed = SessionDir::create(&root).unwrap().fail("encoder failed");
+        let dropped = SessionDir::create(&root).unwrap().path().to_path_buf();
+        assert_eq!(
+            std::fs::read_to_string(failed.join(FAILED_MARKER)).unwrap(),
+            "encoder failed"
+        );
+        assert!(dropped.join(FAILED_MARKER).exists());
+
+        // left by a crashed process
+        let crashed = root.join(format!("{SESSION_DIR_PREFIX}0-0-0"));
+        std::fs::create_dir(&crashed).unwrap();
+        std::fs::write(root.join("other"), b"").unwrap();
+
+        let report = purge_stale_sessions(&root, Duration::ZERO).unwrap();
+        assert_eq!((report.removed, report.kept), (1, 2));
+        assert!(!crashed.exists() && failed.exists() && root.join("other").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn keeps_sessions_still_written_by_another_process() {
+        let root =
+            std::env::temp_dir().join(format!("unienc-session-dir-{}-active", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let day_ago = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+        let set_modified = |path: &Path| {
+            std::fs::File::open(path)
+                .unwrap()
+                .set_modified(day_ago)
+                .unwrap();
+        };
+
+        // both created a day ago, one still appending to its spool
+        let active = root.join(format!("{SESSION_DIR_PREFIX}0-0-0"));
+        let abandoned = root.join(format!("{SESSION_DIR_PREFIX}0-0-1"));
+        for dir in [&active, &abandoned] {
+            std::fs::create_dir(dir).unwrap();
+            std::fs::write(dir.join("spool"), b"frames").unwrap();
+        }
+        set_modified(&abandoned.join("spool"));
+        set_modified(&active);
+        set_modified(&abandoned);
+
+        let report = purge_stale_sessions(&root, Duration::from_secs(60 * 60)).unwrap();
+        assert_eq!((report.removed, report.kept), (1, 1));
+        assert!(active.exists() && !abandoned.exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+//! Settings of clips exported to be shared on social and messaging apps: scaled down to 720p, with
+//! a bitrate fitting common upload limits, normalized audio and at most a minute long.
+
+use crate::duration_limit::fit_video_bitrate;
+use crate::loudness::{DEFAULT_TARGET_LOUDNESS, Loudness};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SharePreset {
+    /// Frames are scaled down so that their shorter side is at most this many pixels.
+    pub max_short_side: u32,
+    /// In bits per second.
+    pub max_video_bitrate: u32,
+    /// In bits per second.
+    pub max_audio_bitrate: u32,
+    /// In seconds.
+    pub max_duration: f64,
+    /// Loudness the audio is normalized to, in LUFS.
+    pub target_loudness: f64,
+    /// Size the file is kept under, such as the upload limit of the app it is shared to.
+    pub max_output_bytes: Option<u64>,
+}
+
+impl Default for SharePreset {
+    fn default() -> Self {
+        Self {
+            max_short_side: 720,
+            max_video_bitrate: 4_000_000,
+            max_audio_bitrate: 128_000,
+            max_duration: 60.0,
+            target_loudness: DEFAULT_TARGET_LOUDNESS,
+            max_output_bytes: None,
+        }
+    }
+}
+
+impl SharePreset {
+    /// Frame size for frames of `width` by `height`, keeping the aspect ratio. Dimensions are even
+    /// as H.264 requires.
+    pub fn video_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let short_side = width.min(height);
+        let scale = match short_side > self.max_short_side {
+            true => self.max_short_side as f64 / short_side as f64,
+            false => 1.0,
+        };
+        let even = |size: u32| ((size as f64 * scale) as u32 & !1).max(2);
+        (even(width), even(height))
+    }
+
+    /// Video bitrate for a clip of `duration` seconds scaled down from frames of `width` by
+    /// `height` encoded at `source_bitrate`. `None` if the clip cannot fit in
+    /// [`max_output_bytes`](Self::max_output_bytes).
+    pub fn video_bitrate(
+        &self,
+        width: u32,
+        height: u32,
+        source_bitrate: u32,
+        duration: f64,
+        audio_bitrate: u32,
+    ) -> Option<u32> {
+        let (scaled_width, scaled_height) = self.video_size(width, height);
+        let area = |w: u32, h: u32| w as f64 * h as f64;
+        // scaling down keeps the quality of the source at a proportionally lower bitrate
+        let scaled = source_bitrate as f64 * area(scaled_width, scaled_height)
+            / area(width.max(1), height.max(1));
+        let bitrate = (scaled as u32).clamp(1, self.max_video_bitrate);
+        match self.max_output_bytes {
+            Some(max_bytes) => {
+                let duration = duration.min(self.max_duration);
+                fit_video_bitrate(max_bytes, duration, audio_bitrate).map(|fit| bitrate.min(fit))
+            }
+            None => Some(bitrate),
+        }
+    }
+
+    pub fn audio_bitrate(&self, source_bitrate: u32) -> u32 {
+        source_bitrate.min(self.max_audio_bitrate)
+    }
+
+    /// Gain in dB normalizing audio of `loudness`, or 0 if it was not measured.
+    pub fn gain_db(&self, loudness: Option<Loudness>) -> f64 {
+        loudness.map_or(0.0, |loudness| {
+            loudness.normalization_gain(self.target_loudness)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_the_shorter_side_to_720() {
+        let preset = SharePreset::default();
+        assert_eq!(preset.video_size(1920, 1080), (1280, 720));
+        assert_eq!(preset.video_size(1080, 2400), (720, 1600));
+        assert_eq!(preset.video_size(1280, 720), (1280, 720));
+        assert_eq!(preset.video_size(641, 361), (640, 360));
+    }
+
+    #[test]
+    fn bitrate_follows_the_scale_and_the_limits() {
+        let mut preset = SharePreset::default();
+        // 1080p at 6 Mbps keeps the quality at 720p with 4/9 of it, capped at 4 Mbps
+        assert_eq!(
+            preset.video_bitrate(1920, 1080, 6_000_000, 60.0, 128_000),
+            Some(2_666_666)
+        );
+        assert_eq!(
+            preset.video_bitrate(1920, 1080, 20_000_000, 60.0, 128_000),
+            Some(4_000_000)
+        );
+
+        preset.max_output_bytes = Some(10_000_000);
+        let fitted = preset
+            .video_bitrate(1920, 1080, 20_000_000, 120.0, 128_000)
+            .unwrap();
+        // fitted to the 60 seconds kept rather than the 120 recorded
+        assert_eq!(Some(fitted), fit_video_bitrate(10_000_000, 60.0, 128_000));
+        preset.max_output_bytes = Some(100_000);
+        assert_eq!(
+            preset.video_bitrate(1920, 1080, 20_000_000, 60.0, 128_000),
+            None
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! The latest frame handed to the encoder, kept so the host can save a screenshot of the end of a
+//! replay without capturing the screen a second time. Frames are kept after filters, as they are
+//! encoded, and each one overwrites the previous copy.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{EncoderInput, Result, VideoFrame, VideoFrameBgra32, VideoSample};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapshotInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Timestamp of the frame in seconds, on the timeline of the encoder.
+    pub timestamp: f64,
+    /// False when the target was too small for the pixels, which were not copied then.
+    pub copied: bool,
+}
+
+#[derive(Default)]
+struct Latest {
+    /// BGRA pixels, rows packed without padding.
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    timestamp: f64,
+}
+
+/// Copy of the latest frame pushed to the inputs it is set on, readable while they encode.
+#[derive(Default)]
+pub struct FrameSnapshot {
+    latest: Mutex<Option<Latest>>,
+}
+
+impl FrameSnapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn store(&self, frame: &VideoFrameBgra32, timestamp: f64) {
+        let row_len = frame.width as usize * 4;
+        let data = frame.buffer.data();
+        let mut latest = self.lock();
+        // the allocation is reused while the size does not change
+        let latest = latest.get_or_insert_with(Latest::default);
+        latest.pixels.clear();
+        for row in 0..frame.height as usize {
+            latest
+                .pixels
+                .extend_from_slice(&data[row * frame.stride as usize..][..row_len]);
+        }
+        latest.width = frame.width;
+        latest.height = frame.height;
+        latest.timestamp = timestamp;
+    }
+
+    /// Copies the pixels of the latest frame into the start of `target` if it holds
+    /// `width * height * 4` bytes. `None` if no frame was kept yet.
+    pub fn copy_latest(&self, target: &mut [u8]) -> Option<SnapshotInfo> {
+        let latest = self.lock();
+        let latest = latest.as_ref()?;
+        let copied = target.len() >= latest.pixels.len();
+        if copied {
+            target[..latest.pixels.len()].copy_from_slice(&latest.pixels);
+        }
+        Some(SnapshotInfo {
+            width: latest.width,
+            height: latest.height,
+            timestamp: latest.timestamp,
+            copied,
+        })
+    }
+
+    // a plain value that stays consistent even if a holder panicked
+    fn lock(&self) -> MutexGuard<'_, Option<Latest>> {
+        self.latest.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Video encoder input that keeps a copy of the frames pushed as BGRA pixels in a
+/// [`FrameSnapshot`] once one is set. Blit sources are not read back on the CPU, so they are not
+/// kept, and the snapshot holds the last frame pushed as pixels.
+pub struct SnapshotVideoInput<I> {
+    inner: I,
+    snapshot: Option<Arc<FrameSnapshot>>,
+}
+
+impl<I> SnapshotVideoInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            snapshot: None,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// `None` stops keeping frames; the snapshot keeps the last one.
+    pub fn set_snapshot(&mut self, snapshot: Option<Arc<FrameSnapshot>>) {
+        self.snapshot = snapshot;
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for SnapshotVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        if let (Some(snapshot), VideoFrame::Bgra32(frame)) = (&self.snapshot, &data.frame) {
+            snapshot.store(frame, data.timestamp);
+        }
+        self.inner.push(data).await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::SharedBuffer;
+
+    #[test]
+    fn copies_the_latest_frame_without_row_padding() {
+        let snapshot = FrameSnapshot::new();
+        assert_eq!(snapshot.copy_latest(&mut []), None);
+
+        // 2x2 pixels in rows of 12 bytes
+        let mut data = vec![0; 24];
+        data[..8].fill(1);
+        data[12..20].fill(2);
+        let frame = VideoFrameBgra32 {
+            stride: 12,
+            ..VideoFrameBgra32::packed(SharedBuffer::new_unmanaged(data), 2, 2)
+        };
+        snapshot.store(&frame, 1.5);
+
+        let mut target = vec![0; 16];
+        let info = snapshot.copy_latest(&mut target[..8]).unwrap();
+        assert!(!info.copied);
+        assert_eq!((info.width, info.height, info.timestamp), (2, 2, 1.5));
+        assert!(snapshot.copy_latest(&mut target).unwrap().copied);
+        assert_eq!(target, [[1; 8], [2; 8]].concat());
+    }
+}
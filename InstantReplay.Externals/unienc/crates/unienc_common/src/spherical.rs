@@ -0,0 +1,221 @@
+//! Spherical video metadata (Spherical Video V2) for 360 recordings. Platform muxers cannot add
+//! custom boxes to the video sample entry, so the finished MP4 is rewritten with `st3d` and `sv3d`
+//! boxes appended to it, which players read to render the frames as an equirectangular sphere.
+
+use std::path::{Path, PathBuf};
+
+use crate::mp4::{
+    BoxResult, children, container, find, full_box, grow, shift_chunk_offsets, video_track,
+};
+use crate::{CommonError, CompletionHandle, Result};
+
+/// Completion handle that tags the file as equirectangular 360 video once the wrapped muxer has
+/// written it, if requested.
+pub struct SphericalCompletionHandle<C> {
+    inner: C,
+    path: PathBuf,
+    spherical: bool,
+}
+
+impl<C> SphericalCompletionHandle<C> {
+    pub fn new(inner: C, path: &Path) -> Self {
+        Self {
+            inner,
+            path: path.to_owned(),
+            spherical: false,
+        }
+    }
+
+    pub fn set_spherical(&mut self) {
+        self.spherical = true;
+    }
+}
+
+impl<C: CompletionHandle + Send> CompletionHandle for SphericalCompletionHandle<C> {
+    async fn finish(self) -> Result<()> {
+        self.inner.finish().await?;
+        if !self.spherical {
+            return Ok(());
+        }
+        let file =
+            std::fs::read(&self.path).map_err(|e| CommonError::SphericalMetadata(e.to_string()))?;
+        let file = inject_spherical_metadata(&file)?;
+        std::fs::write(&self.path, file).map_err(|e| CommonError::SphericalMetadata(e.to_string()))
+    }
+}
+
+/// Returns a copy of an MP4 file whose video sample entry carries mono equirectangular metadata.
+/// Chunk offsets are moved along when the metadata is inserted before the media data.
+pub fn inject_spherical_metadata(file: &[u8]) -> Result<Vec<u8>> {
+    inject(file)
+        .map_err(|reason| CommonError::SphericalMetadata(format!("unsupported MP4 file: {reason}")))
+}
+
+fn inject(file: &[u8]) -> BoxResult<Vec<u8>> {
+    let moov = find(file, 0..file.len(), b"moov")?;
+    let trak = video_track(file, &moov)?;
+    let mdia = find(file, trak.content(), b"mdia")?;
+    let minf = find(file, mdia.content(), b"minf")?;
+    let stbl = find(file, minf.content(), b"stbl")?;
+    let stsd = find(file, stbl.content(), b"stsd")?;
+    // version, flags and entry count precede the sample entries
+    let entries = stsd.content().start + 8..stsd.end;
+    let entry = children(file, entries)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "no sample entry".to_string())?;
+
+    let metadata = spherical_boxes();
+    let insert_at = entry.end;
+    let delta = metadata.len();
+
+    let mut out = file.to_vec();
+    shift_chunk_offsets(&mut out, &moov, insert_at, delta)?;
+    for parent in [moov, trak, mdia, minf, stbl, stsd, entry] {
+        grow(&mut out, &parent, delta)?;
+    }
+    out.splice(insert_at..insert_at, metadata);
+    Ok(out)
+}
+
+/// Mono `st3d` followed by an `sv3d` declaring a full equirectangular projection.
+fn spherical_boxes() -> Vec<u8> {
+    // stereo_mode 0: monoscopic
+    let mut boxes = full_box(b"st3d", &[0]);
+    boxes.extend(container(
+        b"sv3d",
+        &[
+            full_box(b"svhd", b"unienc\0"),
+            container(
+                b"proj",
+                &[
+                    // yaw, pitch and roll
+                    full_box(b"prhd", &[0; 12]),
+                    // no cropping from any edge
+                    full_box(b"equi", &[0; 16]),
+                ],
+            ),
+        ],
+    ));
+    boxes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4::read_u32;
+
+    fn plain(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut b = ((8 + payload.len()) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(kind);
+        b.extend_from_slice(payload);
+        b
+    }
+
+    fn track(handler: &[u8; 4], chunk_offset: u32) -> Vec<u8> {
+        // pre_defined, then the handler type
+        let mut hdlr = vec![0; 4];
+        hdlr.extend_from_slice(handler);
+        hdlr.extend_from_slice(&[0; 13]);
+        let mut stsd = 1u32.to_be_bytes().to_vec();
+        stsd.extend(plain(b"avc1", &[0; 78]));
+        let mut stco = 1u32.to_be_bytes().to_vec();
+        stco.extend_from_slice(&chunk_offset.to_be_bytes());
+        container(
+            b"trak",
+            &[container(
+                b"mdia",
+                &[
+                    full_box(b"hdlr", &hdlr),
+                    container(
+                        b"minf",
+                        &[container(
+                            b"stbl",
+                            &[full_box(b"stsd", &stsd), full_box(b"stco", &stco)],
+                        )],
+                    ),
+                ],
+            )],
+        )
+    }
+
+    fn chunk_offsets(file: &[u8]) -> Vec<u32> {
+        let moov = find(file, 0..file.len(), b"moov").unwrap();
+        children(file, moov.content())
+            .unwrap()
+            .iter()
+            .map(|trak| {
+                let mdia = find(file, trak.content(), b"mdia").unwrap();
+                let minf = find(file, mdia.content(), b"minf").unwrap();
+                let stbl = find(file, minf.content(), b"stbl").unwrap();
+                let stco = find(file, stbl.content(), b"stco").unwrap();
+                read_u32(file, stco.content().start + 8).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn injects_into_video_sample_entry() {
+        let ftyp = plain(b"ftyp", b"isom");
+        let moov_len = container(b"moov", &[track(b"soun", 0), track(b"vide", 0)]).len();
+        let mdat_at = (ftyp.len() + moov_len + 8) as u32;
+        let moov = container(
+            b"moov",
+            &[track(b"soun", mdat_at), track(b"vide", mdat_at + 2)],
+        );
+        let file = [ftyp, moov, plain(b"mdat", &[1, 2, 3, 4])].concat();
+
+        let out = inject_spherical_metadata(&file).unwrap();
+        let delta = spherical_boxes().len();
+        assert_eq!(out.len(), file.len() + delta);
+
+        // media data moved behind the metadata, and chunk offsets with it
+        let mdat = find(&out, 0..out.len(), b"mdat").unwrap();
+        assert_eq!(&out[mdat.content()], &[1, 2, 3, 4]);
+        assert_eq!(
+            chunk_offsets(&out),
+            [mdat.content().start as u32, mdat.content().start as u32 + 2]
+        );
+
+        let moov = find(&out, 0..out.len(), b"moov").unwrap();
+        let traks = children(&out, moov.content()).unwrap();
+        let video = &traks[1];
+        let stsd = [b"mdia", b"minf", b"stbl", b"stsd"]
+            .iter()
+            .fold(*video, |b, kind| find(&out, b.content(), kind).unwrap());
+        let entry = children(&out, stsd.content().start + 8..stsd.end).unwrap()[0];
+        let tags = children(&out, entry.content().start + 78..entry.end).unwrap();
+        assert_eq!(
+            tags.iter().map(|b| &b.kind).collect::<Vec<_>>(),
+            [b"st3d", b"sv3d"]
+        );
+
+        // the audio track is left alone
+        let audio = find(&out, traks[0].content(), b"mdia").unwrap();
+        assert_eq!(audio.end - audio.start, track(b"soun", 0).len() - 8);
+    }
+
+    #[test]
+    fn keeps_offsets_before_trailing_moov() {
+        let ftyp = plain(b"ftyp", b"isom");
+        let mdat_at = ftyp.len() as u32 + 8;
+        let file = [
+            ftyp,
+            plain(b"mdat", &[1, 2]),
+            container(b"moov", &[track(b"vide", mdat_at)]),
+        ]
+        .concat();
+
+        let out = inject_spherical_metadata(&file).unwrap();
+        assert_eq!(chunk_offsets(&out), [mdat_at]);
+    }
+
+    #[test]
+    fn rejects_files_without_video() {
+        let file = container(b"moov", &[track(b"soun", 0)]);
+        assert!(matches!(
+            inject_spherical_metadata(&file),
+            Err(CommonError::SphericalMetadata(_))
+        ));
+    }
+}
@@ -0,0 +1,70 @@
+//! Publishing MPEG-TS over SRT, which esports ingest servers commonly take. Like
+//! [`rtmp`](crate::rtmp), the encoded samples go through the encoders and muxer wrappers of a
+//! recording; backends that can publish return a muxer sending them to the URL from
+//! [`EncodingSystem::new_srt_muxer`](crate::EncodingSystem). Options such as `streamid`,
+//! `passphrase` and `latency` are passed in the query of the URL, which is kept out of logs.
+
+use crate::whip::without_query;
+use crate::{CommonError, Result};
+
+fn invalid(url: &str, reason: &str) -> CommonError {
+    CommonError::InvalidStreamUrl(format!("{}: {reason}", without_query(url)))
+}
+
+/// Checks that `url` is an `srt://` URL with a host and a port, such as
+/// `srt://ingest.example.com:9000?streamid=match1&latency=120000`.
+pub fn validate_srt_url(url: &str) -> Result<()> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return Err(invalid(url, "not a URL"));
+    };
+    if !scheme.eq_ignore_ascii_case("srt") {
+        return Err(invalid(url, "not an SRT URL"));
+    }
+    if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(invalid(url, "contains whitespace"));
+    }
+    let authority = rest.split(['/', '?']).next().unwrap_or_default();
+    // a listener binds every interface when the host is left out
+    let Some((_, port)) = authority.rsplit_once(':') else {
+        return Err(invalid(url, "no port"));
+    };
+    match port.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(()),
+        _ => Err(invalid(url, "invalid port")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_srt_urls_with_a_port() {
+        assert!(validate_srt_url("srt://ingest.example.com:9000").is_ok());
+        assert!(validate_srt_url("SRT://[::1]:9000?streamid=#!::r=live/match1,m=publish").is_ok());
+        assert!(validate_srt_url("srt://:9000?mode=listener").is_ok());
+
+        for url in [
+            "",
+            "ingest.example.com:9000",
+            "udp://ingest.example.com:9000",
+            "srt://ingest.example.com",
+            "srt://ingest.example.com:0",
+            "srt://ingest.example.com:srt",
+            "srt://ingest.example.com:9000?streamid=a b",
+        ] {
+            assert!(
+                matches!(validate_srt_url(url), Err(CommonError::InvalidStreamUrl(_))),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_passphrase_out_of_messages() {
+        let Err(err) = validate_srt_url("srt://ingest.example.com?passphrase=secret") else {
+            panic!("accepted a URL without a port");
+        };
+        assert!(!err.to_string().contains("secret"));
+    }
+}
@@ -0,0 +1,48 @@
+//! Capture of still images through the blit path, independent of video encoding sessions. With
+//! [`StillImageFormat::Bgra32`] the read-back pixels are returned as they are, for screenshots
+//! with the same scaling and color handling as recordings.
+
+use std::future::Future;
+use std::marker::PhantomData;
+
+use crate::{CommonError, Result, TryFromUnityNativeTexturePointer, VideoSample};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StillImageFormat {
+    Png,
+    /// `quality` ranges from 0.0 (smallest) to 1.0 (best).
+    Jpeg {
+        quality: f32,
+    },
+    /// BGRA pixels without encoding, rows packed without padding.
+    Bgra32,
+}
+
+pub struct StillImage {
+    /// Image encoded in the capture's format, or its pixels for [`StillImageFormat::Bgra32`].
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// Timestamp of the captured frame in seconds.
+    pub timestamp: f64,
+}
+
+/// Reads back blit sources and encodes them as still images. A burst is captured by pushing
+/// consecutive frames; each capture is independent of the others.
+pub trait StillImageCapture: Send + 'static {
+    type Data: Send;
+
+    fn capture(&mut self, data: Self::Data) -> impl Future<Output = Result<StillImage>> + Send;
+}
+
+pub struct UnsupportedStillImageCapture<BlitSourceType>(PhantomData<fn() -> BlitSourceType>);
+
+impl<BlitSourceType: TryFromUnityNativeTexturePointer + Send + 'static> StillImageCapture
+    for UnsupportedStillImageCapture<BlitSourceType>
+{
+    type Data = VideoSample<BlitSourceType>;
+
+    async fn capture(&mut self, _data: Self::Data) -> Result<StillImage> {
+        Err(CommonError::BlitNotSupported)
+    }
+}
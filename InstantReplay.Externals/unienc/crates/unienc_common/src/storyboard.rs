@@ -0,0 +1,330 @@
+//! Storyboard of a recording: every Nth frame is downscaled into a tile of JPEG sprite sheets,
+//! written next to the video with a JSON index of the tile timestamps, so scrubbing UIs can show
+//! thumbnails without decoding the video.
+//!
+//! Thumbnails are taken from frames pushed as BGRA pixels; blit sources are not read back.
+
+use std::path::{Path, PathBuf};
+
+use crate::jpeg::{self, JpegSubsampling};
+use crate::{CommonError, EncoderInput, Result, VideoFrame, VideoFrameBgra32, VideoSample};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StoryboardOptions {
+    /// A thumbnail is taken every `interval` frames, starting with the first one.
+    pub interval: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// Tiles per sheet are `columns` x `rows`, filled row by row.
+    pub columns: u32,
+    pub rows: u32,
+    /// Ranges from 0.0 (smallest) to 1.0 (best).
+    pub quality: f32,
+}
+
+struct Thumbnail {
+    /// Seconds since the first frame pushed.
+    time: f64,
+    sheet: usize,
+    x: u32,
+    y: u32,
+}
+
+pub struct Storyboard {
+    options: StoryboardOptions,
+    directory: PathBuf,
+    stem: String,
+    frames: u64,
+    start: Option<f64>,
+    /// RGB pixels of the sheet being filled
+    sheet: Vec<u8>,
+    tiles_in_sheet: u32,
+    sheets: Vec<String>,
+    thumbnails: Vec<Thumbnail>,
+}
+
+impl Storyboard {
+    /// Sheets and the index are named after `video_path`: `replay.mp4` gives
+    /// `replay_storyboard_0.jpg`, `replay_storyboard_1.jpg`, ... and `replay_storyboard.json`.
+    pub fn new(video_path: &Path, options: StoryboardOptions) -> Self {
+        let options = StoryboardOptions {
+            interval: options.interval.max(1),
+            tile_width: options.tile_width.max(1),
+            tile_height: options.tile_height.max(1),
+            columns: options.columns.max(1),
+            rows: options.rows.max(1),
+            ..options
+        };
+        Self {
+            directory: video_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            stem: video_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            frames: 0,
+            start: None,
+            sheet: vec![0; sheet_len(&options)],
+            tiles_in_sheet: 0,
+            sheets: Vec::new(),
+            thumbnails: Vec::new(),
+            options,
+        }
+    }
+
+    pub fn push(&mut self, frame: &VideoFrameBgra32, timestamp: f64) -> Result<()> {
+        let index = self.frames;
+        self.frames += 1;
+        if !index.is_multiple_of(self.options.interval as u64) {
+            return Ok(());
+        }
+        // malformed frames are reported by the encoder
+        let data = frame.buffer.data();
+        if frame.width == 0
+            || frame.height == 0
+            || frame.stride < frame.width * 4
+            || data.len() < frame.min_buffer_len()
+        {
+            return Ok(());
+        }
+
+        let StoryboardOptions {
+            tile_width,
+            tile_height,
+            columns,
+            ..
+        } = self.options;
+        let x = self.tiles_in_sheet % columns * tile_width;
+        let y = self.tiles_in_sheet / columns * tile_height;
+        let sheet_width = columns * tile_width;
+
+        // box filter over the source pixels covered by each tile pixel
+        for ty in 0..tile_height {
+            let y0 = ty * frame.height / tile_height;
+            let y1 = ((ty + 1) * frame.height / tile_height).max(y0 + 1);
+            for tx in 0..tile_width {
+                let x0 = tx * frame.width / tile_width;
+                let x1 = ((tx + 1) * frame.width / tile_width).max(x0 + 1);
+                let mut sum = [0u32; 3];
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        let i = (sy * frame.stride + sx * 4) as usize;
+                        // BGRA
+                        sum[0] += data[i + 2] as u32;
+                        sum[1] += data[i + 1] as u32;
+                        sum[2] += data[i] as u32;
+                    }
+                }
+                let count = (y1 - y0) * (x1 - x0);
+                let o = (((y + ty) * sheet_width + x + tx) * 3) as usize;
+                for (value, sum) in self.sheet[o..o + 3].iter_mut().zip(sum) {
+                    *value = (sum / count) as u8;
+                }
+            }
+        }
+
+        let start = *self.start.get_or_insert(timestamp);
+        self.thumbnails.push(Thumbnail {
+            time: timestamp - start,
+            sheet: self.sheets.len(),
+            x,
+            y,
+        });
+        self.tiles_in_sheet += 1;
+        if self.tiles_in_sheet == columns * self.options.rows {
+            self.write_sheet()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the last, partially filled sheet and the index.
+    pub fn finish(mut self) -> Result<()> {
+        self.write_sheet()?;
+        let path = self
+            .directory
+            .join(format!("{}_storyboard.json", self.stem));
+        std::fs::write(path, self.index()).map_err(|e| CommonError::StoryboardIo(e.to_string()))
+    }
+
+    fn write_sheet(&mut self) -> Result<()> {
+        if self.tiles_in_sheet == 0 {
+            return Ok(());
+        }
+        let StoryboardOptions {
+            tile_width,
+            tile_height,
+            columns,
+            quality,
+            ..
+        } = self.options;
+        // a partial sheet is cropped to its filled rows
+        let height = self.tiles_in_sheet.div_ceil(columns) * tile_height;
+        let width = columns * tile_width;
+        let jpeg = jpeg::encode(width, height, quality, JpegSubsampling::Yuv444, |x, y| {
+            let i = ((y * width + x) * 3) as usize;
+            [self.sheet[i], self.sheet[i + 1], self.sheet[i + 2]]
+        });
+
+        let name = format!("{}_storyboard_{}.jpg", self.stem, self.sheets.len());
+        std::fs::write(self.directory.join(&name), jpeg)
+            .map_err(|e| CommonError::StoryboardIo(e.to_string()))?;
+        self.sheets.push(name);
+        self.sheet.fill(0);
+        self.tiles_in_sheet = 0;
+        Ok(())
+    }
+
+    fn index(&self) -> String {
+        let StoryboardOptions {
+            tile_width,
+            tile_height,
+            columns,
+            rows,
+            ..
+        } = self.options;
+        let sheets = self
+            .sheets
+            .iter()
+            .map(|name| json_string(name))
+            .collect::<Vec<_>>()
+            .join(",");
+        let thumbnails = self
+            .thumbnails
+            .iter()
+            .map(|t| {
+                format!(
+                    r#"{{"time":{},"sheet":{},"x":{},"y":{}}}"#,
+                    t.time, t.sheet, t.x, t.y
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            r#"{{"tile_width":{tile_width},"tile_height":{tile_height},"columns":{columns},"rows":{rows},"sheets":[{sheets}],"thumbnails":[{thumbnails}]}}"#
+        )
+    }
+}
+
+fn sheet_len(options: &StoryboardOptions) -> usize {
+    (options.columns * options.tile_width * options.rows * options.tile_height * 3) as usize
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Video encoder input that adds the frames pushed as BGRA pixels to a storyboard once one is set.
+/// Storyboard failures do not interrupt encoding; the first one is reported by
+/// [`finish_storyboard`](Self::finish_storyboard).
+pub struct StoryboardVideoInput<I> {
+    inner: I,
+    storyboard: Option<Storyboard>,
+    error: Option<CommonError>,
+}
+
+impl<I> StoryboardVideoInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            storyboard: None,
+            error: None,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    pub fn set_storyboard(&mut self, storyboard: Storyboard) {
+        self.storyboard = Some(storyboard);
+        self.error = None;
+    }
+
+    /// Writes the storyboard files and stops taking thumbnails.
+    pub fn finish_storyboard(&mut self) -> Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        match self.storyboard.take() {
+            Some(storyboard) => storyboard.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for StoryboardVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        if let (Some(storyboard), VideoFrame::Bgra32(frame)) = (&mut self.storyboard, &data.frame)
+            && let Err(error) = storyboard.push(frame, data.timestamp)
+        {
+            self.storyboard = None;
+            self.error = Some(error);
+        }
+        self.inner.push(data).await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::SharedBuffer;
+
+    #[test]
+    fn sheets_and_index_are_written_next_to_the_video() {
+        let directory = std::env::temp_dir().join(format!("storyboard-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut storyboard = Storyboard::new(
+            &directory.join("replay.mp4"),
+            StoryboardOptions {
+                interval: 2,
+                tile_width: 4,
+                tile_height: 2,
+                columns: 2,
+                rows: 1,
+                quality: 0.8,
+            },
+        );
+        for i in 0..6 {
+            let frame = VideoFrameBgra32::packed(
+                SharedBuffer::new_unmanaged(vec![i as u8 * 40; 16 * 8 * 4]),
+                16,
+                8,
+            );
+            storyboard.push(&frame, 10.0 + i as f64 * 0.5).unwrap();
+        }
+        storyboard.finish().unwrap();
+
+        let sheet = std::fs::read(directory.join("replay_storyboard_1.jpg")).unwrap();
+        assert_eq!(&sheet[..2], &[0xff, 0xd8]);
+        assert_eq!(&sheet[sheet.len() - 2..], &[0xff, 0xd9]);
+        let index = std::fs::read_to_string(directory.join("replay_storyboard.json")).unwrap();
+        assert_eq!(
+            index,
+            r#"{"tile_width":4,"tile_height":2,"columns":2,"rows":1,"sheets":["replay_storyboard_0.jpg","replay_storyboard_1.jpg"],"thumbnails":[{"time":0,"sheet":0,"x":0,"y":0},{"time":1,"sheet":0,"x":4,"y":0},{"time":2,"sheet":1,"x":0,"y":0}]}"#
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+//! `futures` adapters of the pipeline traits, for Rust consumers that would rather throttle,
+//! buffer or fan out samples with combinators than with pull and push loops: [`OutputStream`]
+//! yields the samples of an encoder output, and [`MuxerSink`] pushes samples to a muxer input,
+//! finishing it when closed. Like [`drive`](crate::drive), they run on whichever runtime polls
+//! them.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use futures_core::{FusedStream, Stream};
+use futures_sink::Sink;
+
+use crate::{CommonError, EncoderOutput, MuxerInput, Result};
+
+/// Pull or push in progress, which owns the output or input until it completes.
+type Step<T, D> = Pin<Box<dyn Future<Output = (T, Result<D>)> + Send>>;
+
+enum StreamState<O: EncoderOutput> {
+    Idle(O),
+    Pulling(Step<O, Option<O::Data>>),
+    Ended,
+}
+
+/// Samples of an encoder output. The stream ends when the encoder does, or right after the first
+/// error.
+pub struct OutputStream<O: EncoderOutput> {
+    state: StreamState<O>,
+}
+
+impl<O: EncoderOutput + 'static> OutputStream<O> {
+    pub fn new(output: O) -> Self {
+        Self {
+            state: StreamState::Idle(output),
+        }
+    }
+}
+
+// the output is only ever moved into a boxed future, never pinned in place
+impl<O: EncoderOutput> Unpin for OutputStream<O> {}
+
+impl<O: EncoderOutput + 'static> Stream for OutputStream<O> {
+    type Item = Result<O::Data>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let mut pull: Step<O, Option<O::Data>> =
+            match std::mem::replace(&mut this.state, StreamState::Ended) {
+                StreamState::Idle(mut output) => Box::pin(async move {
+                    let result = output.pull().await;
+                    (output, result)
+                }),
+                StreamState::Pulling(pull) => pull,
+                StreamState::Ended => return Poll::Ready(None),
+            };
+        let Poll::Ready((output, result)) = pull.as_mut().poll(cx) else {
+            this.state = StreamState::Pulling(pull);
+            return Poll::Pending;
+        };
+        match result {
+            Ok(Some(sample)) => {
+                this.state = StreamState::Idle(output);
+                Poll::Ready(Some(Ok(sample)))
+            }
+            Ok(None) => Poll::Ready(None),
+            Err(err) => Poll::Ready(Some(Err(err))),
+        }
+    }
+}
+
+impl<O: EncoderOutput + 'static> FusedStream for OutputStream<O> {
+    fn is_terminated(&self) -> bool {
+        matches!(self.state, StreamState::Ended)
+    }
+}
+
+enum SinkState<I: MuxerInput> {
+    Idle(I),
+    Pushing(Step<I, ()>),
+    Finishing(Pin<Box<dyn Future<Output = Result<()>> + Send>>),
+    Closed,
+}
+
+/// Muxer input accepting samples one push at a time. Closing the sink finishes the input; a sink
+/// dropped before it is closed drops the input without finishing it.
+pub struct MuxerSink<I: MuxerInput> {
+    state: SinkState<I>,
+}
+
+impl<I: MuxerInput> MuxerSink<I> {
+    pub fn new(input: I) -> Self {
+        Self {
+            state: SinkState::Idle(input),
+        }
+    }
+
+    /// Waits for the push in progress, if any, returning its result.
+    fn poll_pushed(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let SinkState::Pushing(push) = &mut self.state else {
+            return Poll::Ready(Ok(()));
+        };
+        let (input, result) = ready!(push.as_mut().poll(cx));
+        self.state = SinkState::Idle(input);
+        Poll::Ready(result)
+    }
+}
+
+// the input is only ever moved into a boxed future, never pinned in place
+impl<I: MuxerInput> Unpin for MuxerSink<I> {}
+
+impl<I: MuxerInput> Sink<I::Data> for MuxerSink<I> {
+    type Error = CommonError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_pushed(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: I::Data) -> Result<()> {
+        let this = self.get_mut();
+        match std::mem::replace(&mut this.state, SinkState::Closed) {
+            SinkState::Idle(mut input) => {
+                this.state = SinkState::Pushing(Box::pin(async move {
+                    let result = input.push(item).await;
+                    (input, result)
+                }));
+                Ok(())
+            }
+            state => {
+                this.state = state;
+                Err(CommonError::Other("Muxer input is not ready".to_string()))
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_pushed(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pushed(cx))?;
+        let mut finish = match std::mem::replace(&mut this.state, SinkState::Closed) {
+            SinkState::Idle(input) => Box::pin(input.finish()),
+            SinkState::Finishing(finish) => finish,
+            SinkState::Pushing(_) => unreachable!("the push was waited for"),
+            SinkState::Closed => return Poll::Ready(Ok(())),
+        };
+        let Poll::Ready(result) = finish.as_mut().poll(cx) else {
+            this.state = SinkState::Finishing(finish);
+            return Poll::Pending;
+        };
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use crate::{EncodedData, UniencSampleKind};
+    use bincode::{Decode, Encode};
+    use std::future::poll_fn;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Encode, Decode)]
+    struct Sample(f64);
+
+    impl EncodedData for Sample {
+        fn timestamp(&self) -> f64 {
+            self.0
+        }
+        fn set_timestamp(&mut self, timestamp: f64) {
+            self.0 = timestamp;
+        }
+        fn kind(&self) -> UniencSampleKind {
+            UniencSampleKind::Interpolated
+        }
+        fn size(&self) -> usize {
+            size_of::<f64>()
+        }
+    }
+
+    /// Encoder yielding `remaining` samples, then failing.
+    struct Output {
+        remaining: u32,
+    }
+
+    impl EncoderOutput for Output {
+        type Data = Sample;
+
+        async fn pull(&mut self) -> Result<Option<Sample>> {
+            if self.remaining == 0 {
+                return Err(CommonError::Other("encoder failed".to_string()));
+            }
+            self.remaining -= 1;
+            Ok(Some(Sample(self.remaining as f64)))
+        }
+    }
+
+    #[derive(Default)]
+    struct Input {
+        pushed: Arc<Mutex<Vec<f64>>>,
+    }
+
+    impl MuxerInput for Input {
+        type Data = Sample;
+
+        async fn push(&mut self, data: Sample) -> Result<()> {
+            self.pushed.lock().unwrap().push(data.0);
+            Ok(())
+        }
+
+        async fn finish(self) -> Result<()> {
+            self.pushed.lock().unwrap().push(-1.0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn forwards_samples_until_the_first_error() {
+        let mut stream = OutputStream::new(Output { remaining: 2 });
+        let input = Input::default();
+        let pushed = Arc::clone(&input.pushed);
+        let mut sink = MuxerSink::new(input);
+
+        let mut errors = 0;
+        while let Some(sample) = block_on(poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))) {
+            let Ok(sample) = sample else {
+                errors += 1;
+                continue;
+            };
+            block_on(poll_fn(|cx| Pin::new(&mut sink).poll_ready(cx))).unwrap();
+            Pin::new(&mut sink).start_send(sample).unwrap();
+        }
+        block_on(poll_fn(|cx| Pin::new(&mut sink).poll_close(cx))).unwrap();
+
+        assert_eq!(errors, 1);
+        assert!(stream.is_terminated());
+        assert_eq!(*pushed.lock().unwrap(), [1.0, 0.0, -1.0]);
+        assert!(Pin::new(&mut sink).start_send(Sample(2.0)).is_err());
+    }
+}
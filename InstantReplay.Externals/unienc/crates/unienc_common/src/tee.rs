@@ -0,0 +1,88 @@
+//! Delivery of the encoded samples written to a file to another consumer as well, such as a
+//! custom network sender, without encoding them twice.
+
+use crate::{MuxerInput, Result};
+
+type SampleSink<T> = Box<dyn FnMut(&T) + Send>;
+
+/// Muxer input that passes each sample to a sink, if set, before pushing it to the wrapped input.
+pub struct TeeMuxerInput<I: MuxerInput> {
+    inner: I,
+    sink: Option<SampleSink<I::Data>>,
+}
+
+impl<I: MuxerInput> TeeMuxerInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self { inner, sink: None }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    /// Passes subsequent samples to `on_sample`. Samples are delivered even after the wrapped
+    /// input stops accepting them, such as past a [`DurationLimit`](crate::DurationLimit).
+    pub fn set_sink(&mut self, on_sample: impl FnMut(&I::Data) + Send + 'static) {
+        self.sink = Some(Box::new(on_sample));
+    }
+}
+
+impl<I: MuxerInput> MuxerInput for TeeMuxerInput<I> {
+    type Data = I::Data;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        if let Some(on_sample) = &mut self.sink {
+            on_sample(&data);
+        }
+        self.inner.push(data).await
+    }
+
+    async fn finish(self) -> Result<()> {
+        self.inner.finish().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+
+    struct Input(Arc<Mutex<Vec<u32>>>);
+
+    impl MuxerInput for Input {
+        type Data = u32;
+
+        async fn push(&mut self, data: u32) -> Result<()> {
+            self.0.lock().unwrap().push(data);
+            Ok(())
+        }
+
+        async fn finish(self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn push(input: &mut TeeMuxerInput<Input>, data: u32) {
+        let future = pin!(input.push(data));
+        let poll = future.poll(&mut Context::from_waker(Waker::noop()));
+        assert!(matches!(poll, Poll::Ready(Ok(()))));
+    }
+
+    #[test]
+    fn delivers_samples_to_both() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let mut input = TeeMuxerInput::new(Input(written.clone()));
+
+        push(&mut input, 1);
+        let sink = delivered.clone();
+        input.set_sink(move |&data| sink.lock().unwrap().push(data));
+        push(&mut input, 2);
+        push(&mut input, 3);
+
+        assert_eq!(*written.lock().unwrap(), [1, 2, 3]);
+        assert_eq!(*delivered.lock().unwrap(), [2, 3]);
+    }
+}
@@ -0,0 +1,128 @@
+//! Per-frame telemetry of encoded video, to graph encoding quality over time and spot bitrate
+//! starvation in busy scenes.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::{EncodedData, EncoderOutput, Result, UniencSampleKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    /// Presentation time in seconds.
+    pub timestamp: f64,
+    /// Encoded size in bytes.
+    pub size: usize,
+    pub is_key: bool,
+    /// Average quantization parameter, for encoders that report it.
+    pub qp: Option<u32>,
+}
+
+/// Stats of the latest frames, kept until they are drained. The oldest entries are dropped once
+/// `capacity` is reached.
+pub struct FrameStatsRing {
+    capacity: usize,
+    state: Mutex<RingState>,
+}
+
+struct RingState {
+    frames: VecDeque<FrameStats>,
+    dropped: u64,
+}
+
+impl FrameStatsRing {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            state: Mutex::new(RingState {
+                frames: VecDeque::with_capacity(capacity),
+                dropped: 0,
+            }),
+        }
+    }
+
+    pub fn record(&self, stats: FrameStats) {
+        let mut state = self.lock();
+        if state.frames.len() == self.capacity {
+            state.frames.pop_front();
+            state.dropped += 1;
+        }
+        state.frames.push_back(stats);
+    }
+
+    /// Takes the recorded frames, oldest first, and the number of frames dropped since the
+    /// previous drain.
+    pub fn drain(&self) -> (Vec<FrameStats>, u64) {
+        let mut state = self.lock();
+        let dropped = std::mem::take(&mut state.dropped);
+        (state.frames.drain(..).collect(), dropped)
+    }
+
+    // entries are plain values that stay consistent even if a holder panicked
+    fn lock(&self) -> std::sync::MutexGuard<'_, RingState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Video encoder output that records the stats of every pulled frame once a ring is set.
+pub struct MeasuredVideoOutput<O> {
+    inner: O,
+    stats: Option<Arc<FrameStatsRing>>,
+}
+
+impl<O> MeasuredVideoOutput<O> {
+    pub fn new(inner: O) -> Self {
+        Self { inner, stats: None }
+    }
+
+    pub fn set_frame_stats(&mut self, stats: Arc<FrameStatsRing>) {
+        self.stats = Some(stats);
+    }
+}
+
+impl<O: EncoderOutput> EncoderOutput for MeasuredVideoOutput<O> {
+    type Data = O::Data;
+
+    async fn pull(&mut self) -> Result<Option<Self::Data>> {
+        let data = self.inner.pull().await?;
+        if let (Some(stats), Some(data)) = (&self.stats, &data) {
+            // parameter sets and format changes are not frames
+            let kind = data.kind();
+            if kind != UniencSampleKind::Metadata {
+                stats.record(FrameStats {
+                    timestamp: data.timestamp(),
+                    size: data.size(),
+                    is_key: kind == UniencSampleKind::Key,
+                    qp: data.qp(),
+                });
+            }
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_keeps_latest_frames_and_counts_dropped() {
+        let ring = FrameStatsRing::new(2);
+        for i in 0..3 {
+            ring.record(FrameStats {
+                timestamp: i as f64,
+                size: 100 * i,
+                is_key: i == 0,
+                qp: None,
+            });
+        }
+
+        let (frames, dropped) = ring.drain();
+        assert_eq!(dropped, 1);
+        assert_eq!(
+            frames.iter().map(|f| f.timestamp).collect::<Vec<_>>(),
+            vec![1.0, 2.0]
+        );
+        assert_eq!(ring.drain(), (vec![], 0));
+    }
+}
@@ -0,0 +1,150 @@
+//! Moving color bars a backend draws into a texture of its own and blits in place of a Unity
+//! texture, so the GPU paths of blitting and encoding can be tested on a device without Unity. The
+//! bars scroll by a texel per frame, so encoders cannot skip repeated frames.
+
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use crate::{BlitOptions, GraphicsEventIssuer, Result, VideoFrame};
+
+/// Longer side of the pattern texture in texels; the blit scales it to the frame.
+pub const TEST_PATTERN_SIZE: u32 = 128;
+
+/// `GraphicsFormat.R8G8B8A8_UNorm`, the format of the pixels of [`color_bars`].
+pub const TEST_PATTERN_GRAPHICS_FORMAT: u32 = 8;
+
+/// The 75% color bars, from left to right.
+const BARS: [[u8; 3]; 8] = [
+    [191, 191, 191],
+    [191, 191, 0],
+    [0, 191, 191],
+    [0, 191, 0],
+    [191, 0, 191],
+    [191, 0, 0],
+    [0, 0, 191],
+    [0, 0, 0],
+];
+
+/// Size of the pattern texture for frames of `width` by `height`, with the same aspect ratio.
+pub fn test_pattern_size(width: u32, height: u32) -> (u32, u32) {
+    let scale = |side: u32, other: u32| (side * TEST_PATTERN_SIZE / other.max(1)).max(1);
+    if width >= height {
+        (TEST_PATTERN_SIZE, scale(height, width))
+    } else {
+        (scale(width, height), TEST_PATTERN_SIZE)
+    }
+}
+
+/// Tightly packed RGBA pixels of `frame` of the color bars.
+pub fn color_bars(width: u32, height: u32, frame: u32) -> Vec<u8> {
+    let mut data = vec![0; width as usize * height as usize * 4];
+    for row in data.chunks_exact_mut(width as usize * 4) {
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let x = (x as u64 + frame as u64) % width as u64;
+            let [r, g, b] = BARS[(x * BARS.len() as u64 / width as u64) as usize];
+            pixel.copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+    data
+}
+
+/// A texture of the backend the color bars are drawn into.
+pub trait TestPatternTexture: Send + 'static {
+    /// Draws `frame` of the color bars and returns the pointer the blit takes in place of a Unity
+    /// texture, which stays valid until the next draw.
+    fn draw(&mut self, frame: u32) -> Result<*mut c_void>;
+}
+
+/// Makes video frames that blit the color bars drawn into a backend texture.
+pub struct TestPattern {
+    texture: Arc<Mutex<dyn TestPatternTexture>>,
+    width: u32,
+    height: u32,
+}
+
+impl TestPattern {
+    /// `texture` is `width` by `height` texels, such as the [`test_pattern_size`] of the frames.
+    pub fn new(texture: impl TestPatternTexture, width: u32, height: u32) -> Self {
+        Self {
+            texture: Arc::new(Mutex::new(texture)),
+            width,
+            height,
+        }
+    }
+
+    /// `frame` of the pattern, to push to a video encoder. The bars are drawn and blitted in the
+    /// graphics event of `event_issuer`, such as on Unity's render thread, or right away without
+    /// one.
+    pub fn frame<B>(
+        &self,
+        frame: u32,
+        event_issuer: Option<Box<dyn GraphicsEventIssuer + Send>>,
+    ) -> VideoFrame<B> {
+        VideoFrame::BlitSource {
+            texture_token: frame as usize,
+            width: self.width,
+            height: self.height,
+            graphics_format: TEST_PATTERN_GRAPHICS_FORMAT,
+            options: BlitOptions::default(),
+            event_issuer: Box::new(TestPatternEventIssuer {
+                texture: self.texture.clone(),
+                inner: event_issuer,
+            }),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+struct TestPatternEventIssuer {
+    texture: Arc<Mutex<dyn TestPatternTexture>>,
+    inner: Option<Box<dyn GraphicsEventIssuer + Send>>,
+}
+
+impl GraphicsEventIssuer for TestPatternEventIssuer {
+    fn issue_graphics_event(
+        &self,
+        callback: Box<dyn FnOnce(*mut c_void) + Send>,
+        event_id: i32,
+        texture_token: usize,
+    ) {
+        let texture = self.texture.clone();
+        let draw = move |_: *mut c_void| {
+            // held until the blit is issued, so the next draw cannot overwrite it before that
+            let mut texture = texture.lock().unwrap_or_else(|e| e.into_inner());
+            match texture.draw(texture_token as u32) {
+                Ok(ptr) => callback(ptr),
+                Err(e) => {
+                    crate::log!("unienc: failed to draw the test pattern: {e}");
+                    // the blit fails on the null texture and reports it
+                    callback(std::ptr::null_mut());
+                }
+            }
+        };
+        match &self.inner {
+            Some(inner) => inner.issue_graphics_event(Box::new(draw), event_id, texture_token),
+            None => draw(std::ptr::null_mut()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_aspect_ratio() {
+        assert_eq!(test_pattern_size(1920, 1080), (128, 72));
+        assert_eq!(test_pattern_size(1080, 1920), (72, 128));
+        assert_eq!(test_pattern_size(4000, 1), (128, 1));
+    }
+
+    #[test]
+    fn scrolls_the_bars() {
+        let first = color_bars(16, 2, 0);
+        assert_eq!(&first[..4], &[191, 191, 191, 255]);
+        assert_eq!(&first[15 * 4..16 * 4], &[0, 0, 0, 255]);
+        // the first texel of the second bar moved to the left edge
+        assert_eq!(&color_bars(16, 2, 2)[..4], &[191, 191, 0, 255]);
+    }
+}
@@ -0,0 +1,153 @@
+//! Helpers shared by the unit tests of this crate.
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::buffer::SharedBuffer;
+use crate::{CommonError, EncoderInput, Result, VideoFrame, VideoFrameBgra32, VideoSample};
+
+struct ThreadWaker(std::thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs `future` to completion on the current thread, parking it while the future is pending.
+pub(crate) fn block_on<T>(future: impl Future<Output = T>) -> T {
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+/// Frame pushed to [`Frames`], with its rows unpadded.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Pushed {
+    pub timestamp: f64,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Recorded {
+    pushed: Vec<Pushed>,
+    keyframes: Vec<f64>,
+    keyframe_requested: bool,
+}
+
+/// Video encoder recording the frames pushed to it. Clones share the recording, so it can be read
+/// after the encoder is moved into a wrapper.
+#[derive(Clone)]
+pub(crate) struct Frames {
+    recorded: Arc<Mutex<Recorded>>,
+    fail_after: usize,
+}
+
+impl Default for Frames {
+    fn default() -> Self {
+        Self::failing_after(usize::MAX)
+    }
+}
+
+impl Frames {
+    /// Encoder whose pushes fail once `count` frames have been pushed.
+    pub(crate) fn failing_after(count: usize) -> Self {
+        Self {
+            recorded: Arc::default(),
+            fail_after: count,
+        }
+    }
+
+    fn recorded(&self) -> MutexGuard<'_, Recorded> {
+        self.recorded.lock().unwrap()
+    }
+
+    pub(crate) fn pushed(&self) -> Vec<Pushed> {
+        self.recorded().pushed.clone()
+    }
+
+    pub(crate) fn timestamps(&self) -> Vec<f64> {
+        let recorded = self.recorded();
+        recorded
+            .pushed
+            .iter()
+            .map(|frame| frame.timestamp)
+            .collect()
+    }
+
+    /// Timestamps of the frames pushed after a keyframe was requested.
+    pub(crate) fn keyframes(&self) -> Vec<f64> {
+        self.recorded().keyframes.clone()
+    }
+}
+
+impl EncoderInput for Frames {
+    type Data = VideoSample<()>;
+
+    async fn push(&mut self, data: Self::Data) -> Result<()> {
+        let mut recorded = self.recorded();
+        if recorded.pushed.len() >= self.fail_after {
+            return Err(CommonError::StreamDisconnected("Broken pipe".to_string()));
+        }
+        let VideoFrame::Bgra32(frame) = data.frame else {
+            unreachable!();
+        };
+        let row = frame.width as usize * 4;
+        let pixels = frame
+            .buffer
+            .data()
+            .chunks(frame.stride as usize)
+            .take(frame.height as usize)
+            .flat_map(|line| &line[..row])
+            .copied()
+            .collect();
+        if std::mem::take(&mut recorded.keyframe_requested) {
+            recorded.keyframes.push(data.timestamp);
+        }
+        recorded.pushed.push(Pushed {
+            timestamp: data.timestamp,
+            width: frame.width,
+            height: frame.height,
+            pixels,
+        });
+        Ok(())
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.recorded().keyframe_requested = true;
+        true
+    }
+}
+
+/// Pushes a `width` by `height` frame of packed `pixels` at `timestamp` to `input`.
+pub(crate) fn push<I: EncoderInput<Data = VideoSample<()>>>(
+    input: &mut I,
+    timestamp: f64,
+    (width, height): (u32, u32),
+    pixels: Vec<u8>,
+) {
+    let frame = VideoFrameBgra32::packed(SharedBuffer::new_unmanaged(pixels), width, height);
+    push_frame(input, timestamp, frame);
+}
+
+/// Pushes `frame` at `timestamp` to `input`.
+pub(crate) fn push_frame<I: EncoderInput<Data = VideoSample<()>>>(
+    input: &mut I,
+    timestamp: f64,
+    frame: VideoFrameBgra32,
+) {
+    let sample = VideoSample {
+        frame: VideoFrame::Bgra32(frame),
+        timestamp,
+    };
+    block_on(input.push(sample)).unwrap();
+}
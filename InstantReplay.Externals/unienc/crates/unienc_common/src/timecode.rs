@@ -0,0 +1,384 @@
+//! QuickTime timecode track (`tmcd`) starting at the wallclock time of the first frame, so editors
+//! can line up replays of the same match recorded by several players. Platform muxers cannot write
+//! one, so like the spherical metadata it is added to the finished MP4 or QuickTime file: a track
+//! whose single sample is the frame number since midnight UTC, referenced by the video track.
+
+use std::path::{Path, PathBuf};
+
+use crate::mp4::{
+    BoxResult, IDENTITY_MATRIX, MovieHeader, be_bytes, children, container, find, full_box, grow,
+    read_u32, shift_chunk_offsets, video_track,
+};
+use crate::{CommonError, CompletionHandle, Result};
+
+/// Start of a timecode track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timecode {
+    /// Wallclock time of the first frame, in seconds since the Unix epoch.
+    pub start: f64,
+    /// Timecode frames per second, usually the frame rate of the video.
+    pub frame_rate: u32,
+}
+
+impl Timecode {
+    /// Frames since midnight UTC at the start, wrapping every 24 hours like timecode does.
+    pub fn start_frame(&self) -> u32 {
+        (self.start.rem_euclid(86400.0) * self.frame_rate as f64).floor() as u32
+    }
+}
+
+/// Completion handle that adds a timecode track to the file once the wrapped muxer has written
+/// it, if requested.
+pub struct TimecodeCompletionHandle<C> {
+    inner: C,
+    path: PathBuf,
+    timecode: Option<Timecode>,
+}
+
+impl<C> TimecodeCompletionHandle<C> {
+    pub fn new(inner: C, path: &Path) -> Self {
+        Self {
+            inner,
+            path: path.to_owned(),
+            timecode: None,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    pub fn set_timecode(&mut self, timecode: Timecode) {
+        self.timecode = Some(timecode);
+    }
+}
+
+impl<C: CompletionHandle + Send> CompletionHandle for TimecodeCompletionHandle<C> {
+    async fn finish(self) -> Result<()> {
+        self.inner.finish().await?;
+        let Some(timecode) = self.timecode else {
+            return Ok(());
+        };
+        let file = std::fs::read(&self.path).map_err(|e| CommonError::Timecode(e.to_string()))?;
+        let file = add_timecode_track(&file, timecode)?;
+        std::fs::write(&self.path, file).map_err(|e| CommonError::Timecode(e.to_string()))
+    }
+}
+
+/// Returns a copy of an MP4 or QuickTime file with a timecode track for the video track. The
+/// timecode sample is appended in its own `mdat`, and chunk offsets are moved along when the
+/// track is inserted before the media data.
+pub fn add_timecode_track(file: &[u8], timecode: Timecode) -> Result<Vec<u8>> {
+    if timecode.frame_rate == 0 || timecode.frame_rate > u8::MAX as u32 {
+        return Err(CommonError::Timecode(format!(
+            "unsupported frame rate {}",
+            timecode.frame_rate
+        )));
+    }
+    add(file, timecode)
+        .map_err(|reason| CommonError::Timecode(format!("unsupported MP4 file: {reason}")))
+}
+
+fn add(file: &[u8], timecode: Timecode) -> BoxResult<Vec<u8>> {
+    let moov = find(file, 0..file.len(), b"moov")?;
+    let video = video_track(file, &moov)?;
+    let mvhd = find(file, moov.content(), b"mvhd")?;
+    let movie = MovieHeader::read(file, &mvhd)?;
+
+    let track_id = movie.next_track_id;
+    let reference = container(b"tmcd", &[track_id.to_be_bytes().to_vec()]);
+    // into the track references of the video track, which are created if missing
+    let (reference, reference_parents) = match find(file, video.content(), b"tref") {
+        Ok(tref) => (reference, vec![video, tref]),
+        Err(_) => (container(b"tref", &[reference]), vec![video]),
+    };
+    let reference_at = reference_parents
+        .last()
+        .map_or(video.end, |parent| parent.end);
+
+    // the track is a few hundred bytes, so this decides the size of its chunk offset table
+    let wide_offset = file.len() as u64 + 4096 > u32::MAX as u64;
+    let track_len = timecode_trak(timecode, &movie, track_id, 0, wide_offset).len();
+    let delta = reference.len() + track_len;
+    let sample_offset = (file.len() + delta + 8) as u64;
+    let trak = timecode_trak(timecode, &movie, track_id, sample_offset, wide_offset);
+
+    let mut out = file.to_vec();
+    // the sample goes behind everything, so a last box sized to the end of the file has to get
+    // its real size first
+    if let Some(last) = children(file, 0..file.len())?.last()
+        && read_u32(file, last.start)? == 0
+    {
+        grow(&mut out, last, 0)?;
+    }
+    shift_chunk_offsets(&mut out, &moov, moov.end, delta)?;
+    let next_track_id = movie.next_track_id_at;
+    out[next_track_id..next_track_id + 4].copy_from_slice(&(track_id + 1).to_be_bytes());
+    grow(&mut out, &moov, delta)?;
+    for parent in &reference_parents {
+        grow(&mut out, parent, reference.len())?;
+    }
+    // the later position first, so the earlier one stays valid
+    out.splice(moov.end..moov.end, trak);
+    out.splice(reference_at..reference_at, reference);
+    out.extend(container(
+        b"mdat",
+        &[timecode.start_frame().to_be_bytes().to_vec()],
+    ));
+    Ok(out)
+}
+
+/// Track of a single timecode sample at `sample_offset` lasting the whole movie.
+fn timecode_trak(
+    timecode: Timecode,
+    movie: &MovieHeader,
+    track_id: u32,
+    sample_offset: u64,
+    wide_offset: bool,
+) -> Vec<u8> {
+    let frame_rate = timecode.frame_rate;
+    let movie_duration = u32::try_from(movie.duration).unwrap_or(u32::MAX);
+    let media_duration = (movie.duration as f64 / movie.timescale as f64 * frame_rate as f64)
+        .round()
+        .min(u32::MAX as f64) as u32;
+
+    // enabled and in movie
+    let mut tkhd = vec![0, 0, 0, 3];
+    // creation and modification time, track ID, reserved, duration
+    tkhd.extend(be_bytes(&[0, 0, track_id, 0, movie_duration]));
+    // reserved, layer, alternate group, volume and reserved
+    tkhd.extend_from_slice(&[0; 16]);
+    tkhd.extend(be_bytes(&IDENTITY_MATRIX));
+    // no width and height
+    tkhd.extend_from_slice(&[0; 8]);
+    let tkhd = container(b"tkhd", &[tkhd]);
+
+    // creation and modification time, timescale and duration
+    let mut mdhd = be_bytes(&[0, 0, frame_rate, media_duration]);
+    // language "und" and quality
+    mdhd.extend_from_slice(&[0x55, 0xc4, 0, 0]);
+
+    // pre_defined, then the handler type, reserved and name
+    let mut hdlr = vec![0; 4];
+    hdlr.extend_from_slice(b"tmcd");
+    hdlr.extend_from_slice(&[0; 12]);
+    hdlr.extend_from_slice(b"TimeCodeHandler\0");
+
+    // graphics mode, opcolor, balance and reserved
+    let gmin = [0, 0x40, 0x80, 0, 0x80, 0, 0x80, 0, 0, 0, 0, 0];
+    // text font, face, size, reserved, black text on white and the font name
+    let mut tcmi = vec![0, 0, 0, 0, 0, 12, 0, 0, 0, 0, 0, 0, 0, 0];
+    tcmi.extend_from_slice(&[0xff; 6]);
+    tcmi.push(13);
+    tcmi.extend_from_slice(b"Lucida Grande");
+    let gmhd = container(
+        b"gmhd",
+        &[
+            full_box(b"gmin", &gmin),
+            container(b"tmcd", &[full_box(b"tcmi", &tcmi)]),
+        ],
+    );
+
+    // one self-contained data reference
+    let mut dref = 1u32.to_be_bytes().to_vec();
+    dref.extend(container(b"url ", &[vec![0, 0, 0, 1]]));
+    let dinf = container(b"dinf", &[full_box(b"dref", &dref)]);
+
+    // reserved and data reference index, reserved
+    let mut entry = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+    // 24 hour maximum, timescale and frame duration, frames per second and reserved
+    entry.extend(be_bytes(&[0x2, frame_rate, 1]));
+    entry.extend_from_slice(&[frame_rate as u8, 0]);
+    let mut stsd = 1u32.to_be_bytes().to_vec();
+    stsd.extend(container(b"tmcd", &[entry]));
+
+    let chunk_offset = match wide_offset {
+        false => full_box(b"stco", &be_bytes(&[1, sample_offset as u32])),
+        true => {
+            let mut co64 = 1u32.to_be_bytes().to_vec();
+            co64.extend_from_slice(&sample_offset.to_be_bytes());
+            full_box(b"co64", &co64)
+        }
+    };
+    let stbl = container(
+        b"stbl",
+        &[
+            full_box(b"stsd", &stsd),
+            full_box(b"stts", &be_bytes(&[1, 1, media_duration])),
+            // first chunk, samples per chunk and sample description index
+            full_box(b"stsc", &be_bytes(&[1, 1, 1, 1])),
+            // sample size and count
+            full_box(b"stsz", &be_bytes(&[4, 1])),
+            chunk_offset,
+        ],
+    );
+
+    container(
+        b"trak",
+        &[
+            tkhd,
+            container(
+                b"mdia",
+                &[
+                    full_box(b"mdhd", &mdhd),
+                    full_box(b"hdlr", &hdlr),
+                    container(b"minf", &[gmhd, dinf, stbl]),
+                ],
+            ),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4::Mp4Box;
+
+    fn plain(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        container(kind, &[payload.to_vec()])
+    }
+
+    fn mvhd(timescale: u32, duration: u32, next_track_id: u32) -> Vec<u8> {
+        let mut mvhd = be_bytes(&[0, 0, timescale, duration]);
+        mvhd.extend_from_slice(&[0; 76]);
+        mvhd.extend(be_bytes(&[next_track_id]));
+        full_box(b"mvhd", &mvhd)
+    }
+
+    fn track(handler: &[u8; 4], chunk_offset: u32) -> Vec<u8> {
+        // pre_defined, then the handler type
+        let mut hdlr = vec![0; 4];
+        hdlr.extend_from_slice(handler);
+        hdlr.extend_from_slice(&[0; 13]);
+        container(
+            b"trak",
+            &[container(
+                b"mdia",
+                &[
+                    full_box(b"hdlr", &hdlr),
+                    container(
+                        b"minf",
+                        &[container(
+                            b"stbl",
+                            &[full_box(b"stco", &be_bytes(&[1, chunk_offset]))],
+                        )],
+                    ),
+                ],
+            )],
+        )
+    }
+
+    fn path(file: &[u8], from: Mp4Box, kinds: &[&[u8; 4]]) -> Mp4Box {
+        kinds
+            .iter()
+            .fold(from, |b, kind| find(file, b.content(), kind).unwrap())
+    }
+
+    fn chunk_offset(file: &[u8], trak: Mp4Box) -> u32 {
+        let stco = path(file, trak, &[b"mdia", b"minf", b"stbl", b"stco"]);
+        read_u32(file, stco.content().start + 8).unwrap()
+    }
+
+    fn file(moov_first: bool) -> Vec<u8> {
+        let ftyp = plain(b"ftyp", b"isom");
+        let moov = |at: u32| {
+            container(
+                b"moov",
+                &[
+                    mvhd(600, 1200, 3),
+                    track(b"vide", at),
+                    track(b"soun", at + 2),
+                ],
+            )
+        };
+        let mdat = plain(b"mdat", &[1, 2, 3, 4]);
+        match moov_first {
+            true => {
+                let at = (ftyp.len() + moov(0).len() + 8) as u32;
+                [ftyp, moov(at), mdat].concat()
+            }
+            false => {
+                let at = (ftyp.len() + 8) as u32;
+                [ftyp, mdat, moov(at)].concat()
+            }
+        }
+    }
+
+    const TIMECODE: Timecode = Timecode {
+        // 2024-05-01 12:34:56.5 UTC
+        start: 1714566896.5,
+        frame_rate: 30,
+    };
+
+    #[test]
+    fn start_frame_counts_from_midnight_utc() {
+        assert_eq!(TIMECODE.start_frame(), ((12 * 60 + 34) * 60 + 56) * 30 + 15);
+    }
+
+    #[test]
+    fn adds_referenced_track_with_the_start_frame() {
+        for moov_first in [true, false] {
+            let file = file(moov_first);
+            let out = add_timecode_track(&file, TIMECODE).unwrap();
+
+            // media data and chunk offsets of the existing tracks still agree
+            let mdat = find(&out, 0..out.len(), b"mdat").unwrap();
+            assert_eq!(&out[mdat.content()], &[1, 2, 3, 4]);
+            let moov = find(&out, 0..out.len(), b"moov").unwrap();
+            let traks = children(&out, moov.content())
+                .unwrap()
+                .into_iter()
+                .filter(|b| &b.kind == b"trak")
+                .collect::<Vec<_>>();
+            assert_eq!(traks.len(), 3);
+            assert_eq!(chunk_offset(&out, traks[0]), mdat.content().start as u32);
+            assert_eq!(
+                chunk_offset(&out, traks[1]),
+                mdat.content().start as u32 + 2
+            );
+
+            // the sample is the frame number and the track spans the movie
+            let timecode = traks[2];
+            let sample = chunk_offset(&out, timecode) as usize;
+            assert_eq!(
+                read_u32(&out, sample).unwrap(),
+                TIMECODE.start_frame(),
+                "{moov_first}"
+            );
+            let tkhd = find(&out, timecode.content(), b"tkhd").unwrap();
+            assert_eq!(read_u32(&out, tkhd.content().start + 12).unwrap(), 3);
+            assert_eq!(read_u32(&out, tkhd.content().start + 20).unwrap(), 1200);
+            let mdhd = path(&out, timecode, &[b"mdia", b"mdhd"]);
+            // 2 seconds at 30 frames per second
+            assert_eq!(read_u32(&out, mdhd.content().start + 16).unwrap(), 60);
+
+            let tref = find(&out, traks[0].content(), b"tref").unwrap();
+            let reference = find(&out, tref.content(), b"tmcd").unwrap();
+            assert_eq!(read_u32(&out, reference.content().start).unwrap(), 3);
+            let mvhd = find(&out, moov.content(), b"mvhd").unwrap();
+            assert_eq!(read_u32(&out, mvhd.content().start + 96).unwrap(), 4);
+        }
+    }
+
+    #[test]
+    fn sizes_a_last_box_running_to_the_end() {
+        let mut file = file(true);
+        let mdat = find(&file, 0..file.len(), b"mdat").unwrap();
+        file[mdat.start..mdat.start + 4].copy_from_slice(&[0; 4]);
+
+        let out = add_timecode_track(&file, TIMECODE).unwrap();
+        let boxes = children(&out, 0..out.len()).unwrap();
+        let kinds = boxes.iter().map(|b| &b.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [b"ftyp", b"moov", b"mdat", b"mdat"]);
+        assert_eq!(&out[boxes[2].content()], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn rejects_files_without_video() {
+        let file = container(b"moov", &[mvhd(600, 0, 2), track(b"soun", 0)]);
+        assert!(matches!(
+            add_timecode_track(&file, TIMECODE),
+            Err(CommonError::Timecode(_))
+        ));
+    }
+}
@@ -0,0 +1,287 @@
+//! Time-lapse recording: only every Nth frame is encoded, with its timestamp moved so the kept
+//! frames follow each other at the interval they were captured at, so the video plays N times as
+//! fast at the capture frame rate. Audio cannot be sped up along with it, so the audio input drops
+//! it meanwhile (see [`ClockedAudioInput::set_omitted`](crate::ClockedAudioInput::set_omitted)) and
+//! is moved back by [`TimelapseVideoInput::lag`] once it resumes.
+
+use crate::{EncoderInput, Result, VideoSample};
+
+/// Video encoder input that keeps every Nth frame once a speed-up factor is set, blit sources
+/// included, and divides the time since the factor was set by it.
+pub struct TimelapseVideoInput<I> {
+    inner: I,
+    factor: u32,
+    /// Frames seen since the factor was set.
+    seen: u64,
+    /// Timestamp of the first frame since the factor was set, and the one it was output at.
+    anchor: Option<(f64, f64)>,
+    /// Input and output timestamp of the last frame pushed.
+    last: Option<(f64, f64)>,
+    dropped: u64,
+}
+
+impl<I> TimelapseVideoInput<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            factor: 1,
+            seen: 0,
+            anchor: None,
+            last: None,
+            dropped: 0,
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Applies to frames pushed after this call, continuing the output timeline from the last
+    /// frame. 0 and 1 pass every frame through at its own time again.
+    pub fn set_factor(&mut self, factor: u32) {
+        self.factor = factor.max(1);
+        self.seen = 0;
+        self.anchor = None;
+    }
+
+    pub fn factor(&self) -> u32 {
+        self.factor
+    }
+
+    /// Seconds the output timeline has fallen behind the pushed timestamps through the speed-ups so
+    /// far.
+    pub fn lag(&self) -> f64 {
+        self.last.map_or(0.0, |(input, output)| input - output)
+    }
+
+    /// Frames left out so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn map_timestamp(&mut self, timestamp: f64) -> f64 {
+        let (input, output) = *self.anchor.get_or_insert_with(|| match self.last {
+            // the first frame after a change follows the last one at its own interval
+            Some((last_input, last_output)) => {
+                (timestamp, last_output + (timestamp - last_input).max(0.0))
+            }
+            None => (timestamp, timestamp),
+        });
+        output + (timestamp - input) / self.factor as f64
+    }
+}
+
+impl<B: Send, I: EncoderInput<Data = VideoSample<B>>> EncoderInput for TimelapseVideoInput<I> {
+    type Data = VideoSample<B>;
+
+    async fn push(&mut self, mut data: Self::Data) -> Result<()> {
+        let keep = self.seen.is_multiple_of(self.factor as u64);
+        self.seen += 1;
+        if !keep {
+            self.dropped += 1;
+            return Ok(());
+        }
+        let input = data.timestamp;
+        data.timestamp = self.map_timestamp(input);
+        self.last = Some((input, data.timestamp));
+        self.inner.push(data).await
+    }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.inner.request_keyframe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Frames, block_on, push};
+    use crate::{
+        AudioSample, ClockedAudioInput, EncodedData, InterleavedMuxerInput, MediaClock, MuxerInput,
+        UniencSampleKind, interleave,
+    };
+    use bincode::{Decode, Encode};
+    use std::pin::pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+
+    fn push_at(input: &mut TimelapseVideoInput<Frames>, timestamp: f64) {
+        push(input, timestamp, (1, 1), vec![0; 4]);
+    }
+
+    #[test]
+    fn keeps_every_nth_frame_at_the_capture_interval() {
+        let mut input = TimelapseVideoInput::new(Frames::default());
+        push_at(&mut input, 0.0);
+        push_at(&mut input, 0.1);
+        input.set_factor(4);
+        for i in 2..11 {
+            push_at(&mut input, i as f64 / 10.0);
+        }
+        input.set_factor(1);
+        push_at(&mut input, 1.1);
+
+        let timestamps = input
+            .inner_mut()
+            .timestamps()
+            .iter()
+            .map(|timestamp| (timestamp * 1000.0).round() / 1000.0)
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps, vec![0.0, 0.1, 0.2, 0.3, 0.4, 0.5]);
+        assert_eq!(input.dropped(), 6);
+    }
+
+    #[derive(Encode, Decode)]
+    struct Sample(f64);
+
+    impl EncodedData for Sample {
+        fn timestamp(&self) -> f64 {
+            self.0
+        }
+
+        fn set_timestamp(&mut self, timestamp: f64) {
+            self.0 = timestamp;
+        }
+
+        fn kind(&self) -> UniencSampleKind {
+            UniencSampleKind::Key
+        }
+
+        fn size(&self) -> usize {
+            0
+        }
+    }
+
+    /// Muxer input recording the timestamps pushed to it, rounded to milliseconds.
+    #[derive(Clone, Default)]
+    struct Track(Arc<Mutex<Vec<f64>>>);
+
+    impl Track {
+        fn timestamps(&self) -> Vec<f64> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl MuxerInput for Track {
+        type Data = Sample;
+
+        async fn push(&mut self, data: Sample) -> Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push((data.0 * 1000.0).round() / 1000.0);
+            Ok(())
+        }
+
+        async fn finish(self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Audio encoder passing the timestamps of its samples on as they are pushed.
+    #[derive(Clone, Default)]
+    struct Audio(Arc<Mutex<Vec<u64>>>);
+
+    impl EncoderInput for Audio {
+        type Data = AudioSample;
+
+        async fn push(&mut self, data: AudioSample) -> Result<()> {
+            self.0.lock().unwrap().push(data.timestamp_in_samples);
+            Ok(())
+        }
+    }
+
+    const SAMPLE_RATE: u32 = 100;
+
+    /// Encoder inputs of a recording whose encoded samples are passed on to an interleaved muxer
+    /// as soon as they are output.
+    struct Recording {
+        video: TimelapseVideoInput<Frames>,
+        audio: ClockedAudioInput<Audio>,
+        frames: Frames,
+        encoded_audio: Audio,
+        video_muxer: InterleavedMuxerInput<Track>,
+        audio_muxer: InterleavedMuxerInput<Track>,
+        muxed: (usize, usize),
+    }
+
+    impl Recording {
+        fn new(video_track: Track, audio_track: Track) -> Self {
+            let (frames, encoded_audio) = (Frames::default(), Audio::default());
+            let mut audio = ClockedAudioInput::new(encoded_audio.clone());
+            audio.set_clock(Arc::new(Mutex::new(MediaClock::new(10))), SAMPLE_RATE, 1);
+            let (video_muxer, audio_muxer) = interleave(video_track, audio_track, 0.5);
+            Self {
+                video: TimelapseVideoInput::new(frames.clone()),
+                audio,
+                frames,
+                encoded_audio,
+                video_muxer,
+                audio_muxer,
+                muxed: (0, 0),
+            }
+        }
+
+        /// Records a frame and 0.1 seconds of audio at each tenth of a second of `range`.
+        fn record(&mut self, range: std::ops::Range<u64>) {
+            for i in range {
+                push(&mut self.video, i as f64 / 10.0, (1, 1), vec![0; 4]);
+                for timestamp in self.frames.timestamps().split_off(self.muxed.0) {
+                    self.muxed.0 += 1;
+                    push_now(&mut self.video_muxer, timestamp);
+                }
+
+                let sample = AudioSample {
+                    data: vec![0; 10],
+                    timestamp_in_samples: i * 10,
+                };
+                block_on(self.audio.push(sample)).unwrap();
+                let encoded = self.encoded_audio.0.lock().unwrap()[self.muxed.1..].to_vec();
+                for timestamp in encoded {
+                    self.muxed.1 += 1;
+                    push_now(&mut self.audio_muxer, timestamp as f64 / SAMPLE_RATE as f64);
+                }
+            }
+        }
+
+        /// Sets the speed-up as `unienc_video_encoder_set_timelapse` does.
+        fn set_factor(&mut self, factor: u32) {
+            self.video.set_factor(factor);
+            self.audio.set_omitted(factor > 1);
+            self.audio.set_lag(self.video.lag());
+            self.audio_muxer.set_idle(factor > 1);
+        }
+    }
+
+    /// Pushes a sample at `timestamp`, failing instead of waiting if the other track holds it back.
+    fn push_now(input: &mut InterleavedMuxerInput<Track>, timestamp: f64) {
+        let push = pin!(input.push(Sample(timestamp)));
+        match push.poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(result) => result.unwrap(),
+            Poll::Pending => panic!("push at {timestamp} stalled"),
+        }
+    }
+
+    #[test]
+    fn audio_rejoins_the_video_after_an_interleaved_time_lapse() {
+        let (video_track, audio_track) = (Track::default(), Track::default());
+        let mut recording = Recording::new(video_track.clone(), audio_track.clone());
+
+        recording.record(0..10);
+        // the video falls behind the last audio while it is sped up
+        recording.set_factor(4);
+        recording.record(10..50);
+        recording.set_factor(1);
+        recording.record(50..55);
+
+        let video = video_track.timestamps();
+        let audio = audio_track.timestamps();
+        assert_eq!((video.len(), audio.len()), (10 + 10 + 5, 10 + 5));
+        assert_eq!(video[19..], [1.9, 2.3, 2.4, 2.5, 2.6, 2.7]);
+        assert_eq!(audio[10..], video[20..]);
+    }
+}
@@ -0,0 +1,136 @@
+//! Peak/RMS summaries of the audio fed to an encoder, for rendering waveforms without decoding the
+//! output again.
+
+use crate::{AudioEncoderOptions, AudioSample};
+
+/// Length of the window each [`WaveformPoint`] summarizes, in seconds.
+pub const WAVEFORM_INTERVAL: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WaveformPoint {
+    /// Largest absolute sample value in the window, normalized to 0.0..=1.0.
+    pub peak: f32,
+    /// Root mean square of the samples in the window, normalized to 0.0..=1.0.
+    pub rms: f32,
+}
+
+#[derive(Default)]
+struct Window {
+    peak: i32,
+    sum_of_squares: f64,
+    count: u64,
+}
+
+impl Window {
+    fn to_point(&self) -> WaveformPoint {
+        if self.count == 0 {
+            return WaveformPoint::default();
+        }
+        WaveformPoint {
+            peak: self.peak as f32 / 32768.0,
+            rms: ((self.sum_of_squares / self.count as f64).sqrt() / 32768.0) as f32,
+        }
+    }
+}
+
+/// Accumulates interleaved PCM into per-[`WAVEFORM_INTERVAL`] windows across all channels. Windows
+/// are placed by each sample's timestamp, so gaps in the input show up as silence.
+pub struct WaveformAnalyzer {
+    channels: u64,
+    frames_per_window: u64,
+    points: Vec<WaveformPoint>,
+    current_index: u64,
+    current: Window,
+}
+
+impl WaveformAnalyzer {
+    pub fn new<A: AudioEncoderOptions>(options: &A) -> Self {
+        Self {
+            channels: options.channels().max(1) as u64,
+            frames_per_window: ((options.sample_rate() as f64 * WAVEFORM_INTERVAL) as u64).max(1),
+            points: Vec::new(),
+            current_index: 0,
+            current: Window::default(),
+        }
+    }
+
+    pub fn push(&mut self, sample: &AudioSample) {
+        for (i, frame) in sample.data.chunks(self.channels as usize).enumerate() {
+            let index = (sample.timestamp_in_samples + i as u64) / self.frames_per_window;
+            if index < self.current_index {
+                // backward jumps would rewrite finished windows; ignore them
+                continue;
+            }
+            if index > self.current_index {
+                self.points.push(self.current.to_point());
+                let skipped = (index - self.current_index - 1) as usize;
+                self.points
+                    .extend(std::iter::repeat_n(WaveformPoint::default(), skipped));
+                self.current_index = index;
+                self.current = Window::default();
+            }
+            for &value in frame {
+                let value = value as i32;
+                self.current.peak = self.current.peak.max(value.abs());
+                self.current.sum_of_squares += (value * value) as f64;
+                self.current.count += 1;
+            }
+        }
+    }
+
+    /// All windows so far, including the one still being filled.
+    pub fn points(&self) -> Vec<WaveformPoint> {
+        let mut points = self.points.clone();
+        if self.current.count > 0 {
+            points.push(self.current.to_point());
+        }
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    struct Options;
+
+    impl AudioEncoderOptions for Options {
+        fn sample_rate(&self) -> u32 {
+            100
+        }
+        fn channels(&self) -> u32 {
+            2
+        }
+        fn bitrate(&self) -> u32 {
+            0
+        }
+    }
+
+    #[test]
+    fn analyzer_splits_windows_by_timestamp_and_fills_gaps() {
+        // 10 frames per window at 100 Hz
+        let mut analyzer = WaveformAnalyzer::new(&Options);
+        analyzer.push(&AudioSample {
+            data: [16384, -16384].repeat(10),
+            timestamp_in_samples: 0,
+        });
+        analyzer.push(&AudioSample {
+            data: vec![-32768, 0],
+            timestamp_in_samples: 25,
+        });
+
+        let points = analyzer.points();
+        assert_eq!(points.len(), 3);
+        assert_eq!(
+            points[0],
+            WaveformPoint {
+                peak: 0.5,
+                rms: 0.5
+            }
+        );
+        assert_eq!(points[1], WaveformPoint::default());
+        assert_eq!(points[2].peak, 1.0);
+        assert!((points[2].rms - 0.5f32.sqrt()).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,90 @@
+//! Low-latency publishing over WebRTC to WHIP endpoints (RFC 9725), for spectating a player's
+//! screen within a second. Like [`rtmp`](crate::rtmp), the encoded samples go through the encoders
+//! and muxer wrappers of a recording; backends that can publish return a muxer sending them as RTP
+//! from [`EncodingSystem::new_whip_muxer`](crate::EncodingSystem). WebRTC carries audio as Opus,
+//! so the backend transcodes the AAC track.
+//!
+//! No WebRTC stack is linked in: the ffmpeg backend hands the session to ffmpeg's `whip` muxer,
+//! which does the signalling, ICE, DTLS and the RTP packetization of H.264 and Opus, and the
+//! other backends cannot publish.
+
+use crate::{CommonError, Result};
+
+fn invalid(url: &str, reason: &str) -> CommonError {
+    CommonError::InvalidStreamUrl(format!("{}: {reason}", without_query(url)))
+}
+
+/// Checks that `url` is an `http://` or `https://` URL with a host, which the offer is posted to.
+pub fn validate_whip_url(url: &str) -> Result<()> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return Err(invalid(url, "not a URL"));
+    };
+    if !matches!(scheme.to_ascii_lowercase().as_str(), "http" | "https") {
+        return Err(invalid(url, "not an HTTP URL"));
+    }
+    if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(invalid(url, "contains whitespace"));
+    }
+    let host = rest.split(['/', '?']).next().unwrap_or_default();
+    if host.is_empty() || host.starts_with(':') {
+        return Err(invalid(url, "no host"));
+    }
+    Ok(())
+}
+
+/// Checks that `token` can be sent as a bearer token in the `Authorization` header.
+pub fn validate_bearer_token(token: &str) -> Result<()> {
+    if token.is_empty() || !token.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(CommonError::InvalidStreamUrl(
+            "the bearer token must be printable ASCII without spaces".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// `url` without its query, which some services put access tokens in, for logs and error messages.
+pub fn without_query(url: &str) -> &str {
+    url.split_once('?').map_or(url, |(url, _)| url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_http_endpoints_only() {
+        assert!(validate_whip_url("https://whip.example.com/v1/whip/room").is_ok());
+        assert!(validate_whip_url("http://192.168.0.2:8080?token=x").is_ok());
+
+        for url in [
+            "",
+            "whip.example.com/whip",
+            "rtmp://whip.example.com/whip",
+            "https:///whip",
+            "https://:443/whip",
+            "https://whip.example.com/my room",
+        ] {
+            assert!(
+                matches!(
+                    validate_whip_url(url),
+                    Err(CommonError::InvalidStreamUrl(_))
+                ),
+                "{url}"
+            );
+        }
+        assert!(validate_bearer_token("eyJhbGciOi.J9-_").is_ok());
+        assert!(validate_bearer_token("two words").is_err());
+    }
+
+    #[test]
+    fn keeps_the_query_out_of_messages() {
+        assert_eq!(
+            without_query("https://whip.example.com/whip?token=secret"),
+            "https://whip.example.com/whip"
+        );
+        let Err(err) = validate_whip_url("https://whip.example.com/a b?token=secret") else {
+            panic!("accepted a URL with a space");
+        };
+        assert!(!err.to_string().contains("secret"));
+    }
+}
@@ -0,0 +1,36 @@
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+pub struct SharedBuffer {
+    len: Arc<usize>,
+    data: Vec<u8>,
+}
+
+impl SharedBuffer {
+    pub fn new_unmanaged(vec: Vec<u8>) -> Self {
+        let len = Arc::new(vec.len());
+        SharedBuffer { data: vec, len }
+    }
+
+    /// Handle that stays upgradable while the buffer is alive, for pools that bound the memory
+    /// handed out.
+    pub fn downgrade(&self) -> Weak<usize> {
+        Arc::downgrade(&self.len)
+    }
+
+    pub fn len(&self) -> usize {
+        *self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
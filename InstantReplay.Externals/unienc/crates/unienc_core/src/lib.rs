@@ -0,0 +1,42 @@
+//! Data model of unienc: encoder options, the samples pushed into encoders, encoded data and
+//! timebases. It has no async runtime or platform dependency and builds with `no_std` + `alloc`,
+//! so engines with their own encoding pipeline can share the sample model and its serialization.
+//! `unienc_common` re-exports everything here.
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod buffer;
+mod options;
+mod sample;
+mod timebase;
+
+pub use options::{AudioEncoderOptions, LatencyMode, VideoCodec, VideoEncoderOptions};
+pub use sample::{
+    AudioSample, BlitOptions, GraphicsEventIssuer, Projection, StereoMode, VideoFrame,
+    VideoFrameBgra32, VideoSample, forward_audio_discontinuity,
+};
+pub use timebase::Timebase;
+
+use bincode::{Decode, Encode};
+
+pub trait EncodedData: Encode + Decode<()> {
+    fn timestamp(&self) -> f64;
+    fn set_timestamp(&mut self, timestamp: f64);
+    fn kind(&self) -> UniencSampleKind;
+    /// Encoded size in bytes.
+    fn size(&self) -> usize;
+    /// Average quantization parameter of a video frame, for encoders that report it.
+    fn qp(&self) -> Option<u32> {
+        None
+    }
+}
+
+#[repr(i8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniencSampleKind {
+    Interpolated = 0,
+    Key = 1,
+    Metadata = 2,
+}
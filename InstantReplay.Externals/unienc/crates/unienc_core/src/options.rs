@@ -0,0 +1,59 @@
+pub trait VideoEncoderOptions: Clone + Copy {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    fn fps_hint(&self) -> u32;
+    fn bitrate(&self) -> u32;
+    fn latency_mode(&self) -> LatencyMode {
+        LatencyMode::Realtime
+    }
+    /// Keeps the alpha channel of the frames in the encoded video. Encoding systems that cannot
+    /// fail to create encoders with `CommonError::AlphaNotSupported`.
+    fn preserve_alpha(&self) -> bool {
+        false
+    }
+    /// Encoding systems that do not support the codec fail to create encoders with
+    /// `CommonError::CodecNotSupported`.
+    fn codec(&self) -> VideoCodec {
+        VideoCodec::H264
+    }
+}
+
+/// How a video encoder trades latency for quality. Backends without such settings ignore it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LatencyMode {
+    /// Frames are encoded as soon as they are pushed, for recording while the game runs.
+    #[default]
+    Realtime,
+    /// Realtime constraints are lifted and the encoder may spend more time and threads on each
+    /// frame, for transcoding an export. Frames are still not reordered.
+    Offline,
+}
+
+/// Codec of the encoded video.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VideoCodec {
+    #[default]
+    H264,
+    /// Apple ProRes 422 HQ, or ProRes 4444 with [`VideoEncoderOptions::preserve_alpha`], for
+    /// editing the recording without another lossy generation. Written into a QuickTime movie, so
+    /// the output should be named `.mov`. Every frame is intra coded and the bitrate is ignored.
+    ProRes,
+    /// Avid DNxHR HQ in a QuickTime movie, the intermediate codec most editors on Windows and
+    /// Linux import natively. Every frame is intra coded and the bitrate is ignored.
+    DnxHr,
+}
+
+impl VideoCodec {
+    /// Whether every frame is a keyframe, as with the intermediate codecs.
+    pub fn is_intra_only(self) -> bool {
+        self != VideoCodec::H264
+    }
+}
+
+pub trait AudioEncoderOptions: Clone + Copy {
+    fn sample_rate(&self) -> u32;
+    /// Channels of the pushed samples, interleaved in the order of Unity's speaker modes. Encoding
+    /// systems that cannot encode surround layouts downmix them to stereo.
+    fn channels(&self) -> u32;
+    fn bitrate(&self) -> u32;
+}
@@ -0,0 +1,216 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+
+use crate::buffer::SharedBuffer;
+
+// #[derive(Clone)]
+pub struct VideoSample<BlitSourceType> {
+    pub frame: VideoFrame<BlitSourceType>,
+    pub timestamp: f64,
+}
+
+pub enum VideoFrame<BlitSourceType> {
+    Bgra32(VideoFrameBgra32),
+    BlitSource {
+        texture_token: usize,
+        width: u32,
+        height: u32,
+        graphics_format: u32,
+        options: BlitOptions,
+        event_issuer: Box<dyn GraphicsEventIssuer + Send>,
+        _phantom: core::marker::PhantomData<BlitSourceType>,
+    },
+}
+
+/// How the blit pass samples a blit source into the encoded frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlitOptions {
+    pub flip_vertically: bool,
+    pub is_gamma_workflow: bool,
+    pub stereo_mode: StereoMode,
+    pub projection: Projection,
+}
+
+/// Which eyes of an XR texture array are captured. Except for `Mono`, the source must be a
+/// texture array with a slice per eye, left first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StereoMode {
+    /// The source is a plain 2D texture.
+    #[default]
+    Mono,
+    LeftEye,
+    RightEye,
+    /// Both eyes next to each other, left eye on the left half.
+    SideBySide,
+}
+
+impl StereoMode {
+    /// Array slices drawn from left to right, each into an equal share of the frame width.
+    pub fn slices(self) -> &'static [u32] {
+        match self {
+            StereoMode::Mono | StereoMode::LeftEye => &[0],
+            StereoMode::RightEye => &[1],
+            StereoMode::SideBySide => &[0, 1],
+        }
+    }
+}
+
+/// How the blit source maps onto the encoded frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Projection {
+    /// The source is scaled to fit the frame.
+    #[default]
+    Flat,
+    /// The source is a cubemap, unwrapped to fill the whole frame with a 360 degree view. Only
+    /// monoscopic capture is supported, so the stereo mode is ignored.
+    Equirectangular,
+}
+
+pub struct VideoFrameBgra32 {
+    pub buffer: SharedBuffer,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes from the start of one row to the next, at least `width * 4`.
+    pub stride: u32,
+}
+
+impl VideoFrameBgra32 {
+    /// Frame with rows packed without padding.
+    pub fn packed(buffer: SharedBuffer, width: u32, height: u32) -> Self {
+        Self {
+            buffer,
+            width,
+            height,
+            stride: width * 4,
+        }
+    }
+
+    /// Bytes the buffer must hold; the last row needs no padding.
+    pub fn min_buffer_len(&self) -> usize {
+        match self.height {
+            0 => 0,
+            height => (height - 1) as usize * self.stride as usize + self.width as usize * 4,
+        }
+    }
+
+    pub fn to_yuv420_planes(&self, padded_size: Option<(u32, u32)>) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let data = self.buffer.data();
+        let w = padded_size.map_or(self.width, |(w, _)| w);
+        let h = padded_size.map_or(self.height, |(_, h)| h);
+        let w_half = (w + 1) >> 1;
+        let h_half = (h + 1) >> 1;
+        let padded_y_size = (w * h) as usize;
+        let padded_uv_size = (w_half * h_half) as usize;
+
+        // Create padded YUV data arrays
+        let mut y_data = vec![16u8; padded_y_size]; // Black level for Y
+        let mut u_data = vec![128u8; padded_uv_size]; // Neutral for U
+        let mut v_data = vec![128u8; padded_uv_size]; // Neutral for V
+
+        // Convert ARGB to YUV for the original image area only
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let bgra_idx = (y * self.stride + x * 4) as usize;
+                let r = data[bgra_idx + 2] as i32;
+                let g = data[bgra_idx + 1] as i32;
+                let b = data[bgra_idx] as i32;
+
+                let y_val = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+
+                let y_idx = (y * w + x) as usize;
+                y_data[y_idx] = y_val;
+
+                // Sample U and V for every 2x2 block (4:2:0 subsampling)
+                if x % 2 == 0 && y % 2 == 0 {
+                    let u_val = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+                    let v_val = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+
+                    let uv_idx = ((y / 2) * (w / 2) + (x / 2)) as usize;
+                    u_data[uv_idx] = u_val;
+                    v_data[uv_idx] = v_val;
+                }
+            }
+        }
+
+        (y_data, u_data, v_data)
+    }
+}
+
+#[derive(Clone)]
+pub struct AudioSample {
+    pub data: Vec<i16>,
+    pub timestamp_in_samples: u64,
+}
+
+impl AudioSample {
+    /// Returns the sample data as signed 16-bit little-endian PCM bytes.
+    pub fn data_as_s16le_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.data.len() * size_of::<i16>());
+        for &sample in &self.data {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// Computes the forward discontinuity (in samples) between the expected next input position and the
+/// actual timestamp of an incoming audio sample.
+///
+/// `expected_next` is the position the next input was expected at, i.e. the previous push's timestamp
+/// plus the number of frames it delivered. Returns the number of samples by which the input timeline
+/// jumped forward — to be reflected in the emitted PTS (Apple/Android) or filled with silence (FFmpeg)
+/// so audio does not drift ahead of video. Returns 0 when the input is continuous, when this is the
+/// first push (`expected_next` is `None`), or when the timestamp jumped backward (backward jumps are
+/// ignored to keep PTS monotonic, which the muxers require).
+pub fn forward_audio_discontinuity(expected_next: Option<u64>, actual_timestamp: u64) -> u64 {
+    match expected_next {
+        Some(expected) if actual_timestamp > expected => actual_timestamp - expected,
+        _ => 0,
+    }
+}
+
+pub trait GraphicsEventIssuer: Send + 'static {
+    fn issue_graphics_event(
+        &self,
+        callback: Box<dyn FnOnce(*mut c_void) + Send + 'static>,
+        event_id: i32,
+        texture_token: usize,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forward_audio_discontinuity_handles_continuity_gaps_and_backward_jumps() {
+        // First push: no expectation yet, so no discontinuity.
+        assert_eq!(forward_audio_discontinuity(None, 0), 0);
+        assert_eq!(forward_audio_discontinuity(None, 48_000), 0);
+
+        // Continuous input: actual timestamp matches the expected next position.
+        assert_eq!(forward_audio_discontinuity(Some(48_000), 48_000), 0);
+
+        // Forward discontinuity (dropped / paused audio): report the gap so it can be reflected in PTS.
+        assert_eq!(forward_audio_discontinuity(Some(48_000), 72_000), 24_000);
+
+        // Backward jump: ignored to keep PTS monotonic.
+        assert_eq!(forward_audio_discontinuity(Some(48_000), 24_000), 0);
+        assert_eq!(forward_audio_discontinuity(Some(48_000), 0), 0);
+    }
+
+    #[test]
+    fn audio_sample_data_as_s16le_bytes_uses_little_endian_order() {
+        let sample = AudioSample {
+            data: vec![0x1234, -2, i16::MIN],
+            timestamp_in_samples: 0,
+        };
+
+        assert_eq!(
+            sample.data_as_s16le_bytes(),
+            vec![0x34, 0x12, 0xfe, 0xff, 0x00, 0x80]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+/// Integer time units of a backend API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timebase {
+    units_per_second: i64,
+}
+
+impl Timebase {
+    /// MediaCodec and MediaMuxer presentation times.
+    pub const MICROSECONDS: Self = Self::new(1_000_000);
+    /// Android surface and image timestamps.
+    pub const NANOSECONDS: Self = Self::new(1_000_000_000);
+    /// Media Foundation sample times.
+    pub const HUNDRED_NANOSECONDS: Self = Self::new(10_000_000);
+
+    pub const fn new(units_per_second: i64) -> Self {
+        Self { units_per_second }
+    }
+
+    pub const fn units_per_second(self) -> i64 {
+        self.units_per_second
+    }
+
+    /// Rounds to the nearest unit, so converting back and forth does not drift.
+    pub fn to_units(self, seconds: f64) -> i64 {
+        round(seconds * self.units_per_second as f64)
+    }
+
+    pub fn to_seconds(self, units: i64) -> f64 {
+        units as f64 / self.units_per_second as f64
+    }
+
+    /// Converts a position in samples without going through floating point seconds.
+    pub fn from_samples(self, samples: u64, sample_rate: u32) -> i64 {
+        (samples as i128 * self.units_per_second as i128 / sample_rate as i128) as i64
+    }
+}
+
+/// `f64::round` needs `std`; rounds half away from zero the same way, saturating like `as`.
+fn round(value: f64) -> i64 {
+    let truncated = value as i64;
+    // exact while the value fits in the mantissa, beyond which it has no fraction anyway
+    let fraction = value - truncated as f64;
+    if fraction >= 0.5 {
+        truncated.saturating_add(1)
+    } else if fraction <= -0.5 {
+        truncated.saturating_sub(1)
+    } else {
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timebase_round_trips() {
+        let timebase = Timebase::HUNDRED_NANOSECONDS;
+        assert_eq!(timebase.to_units(0.1), 1_000_000);
+        assert_eq!(timebase.to_seconds(timebase.to_units(1.0 / 3.0)), 0.3333333);
+        assert_eq!(
+            Timebase::MICROSECONDS.from_samples(48_000, 48000),
+            1_000_000
+        );
+    }
+
+    #[test]
+    fn units_round_half_away_from_zero() {
+        let timebase = Timebase::new(1);
+        assert_eq!(timebase.to_units(2.5), 3);
+        assert_eq!(timebase.to_units(-2.5), -3);
+        assert_eq!(timebase.to_units(0.49999999999999994), 0);
+        assert_eq!(timebase.to_units(-1.4), -1);
+        assert_eq!(timebase.to_units(f64::NAN), 0);
+        assert_eq!(timebase.to_units(1e300), i64::MAX);
+    }
+}
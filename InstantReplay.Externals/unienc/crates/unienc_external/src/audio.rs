@@ -0,0 +1,94 @@
+use futures::StreamExt;
+use futures::channel::mpsc;
+use unienc_common::{AudioSample, Encoder, EncoderInput, EncoderOutput, ErrorCategory, Runtime};
+
+use crate::ExternalEncodedData;
+use crate::backend::{EncoderHandle, backend_error};
+
+pub struct ExternalAudioEncoder<R: Runtime> {
+    input: ExternalAudioEncoderInput<R>,
+    output: ExternalAudioEncoderOutput,
+}
+pub struct ExternalAudioEncoderInput<R: Runtime> {
+    handle: Option<EncoderHandle>,
+    runtime: R,
+}
+pub struct ExternalAudioEncoderOutput {
+    rx: mpsc::UnboundedReceiver<ExternalEncodedData>,
+}
+
+impl<R: Runtime> ExternalAudioEncoder<R> {
+    pub fn new<A: unienc_common::AudioEncoderOptions>(
+        options: &A,
+        runtime: &R,
+    ) -> unienc_common::Result<Self> {
+        let (handle, rx) = EncoderHandle::new(|backend, sink, emit| unsafe {
+            (backend.create_audio_encoder)(
+                backend.context,
+                options.sample_rate(),
+                options.channels(),
+                options.bitrate(),
+                sink,
+                emit,
+            )
+        })?;
+        Ok(Self {
+            input: ExternalAudioEncoderInput {
+                handle: Some(handle),
+                runtime: runtime.clone(),
+            },
+            output: ExternalAudioEncoderOutput { rx },
+        })
+    }
+}
+
+impl<R: Runtime + 'static> Encoder for ExternalAudioEncoder<R> {
+    type InputType = ExternalAudioEncoderInput<R>;
+    type OutputType = ExternalAudioEncoderOutput;
+
+    fn get(self) -> unienc_common::Result<(Self::InputType, Self::OutputType)> {
+        Ok((self.input, self.output))
+    }
+}
+
+impl<R: Runtime + 'static> EncoderInput for ExternalAudioEncoderInput<R> {
+    type Data = AudioSample;
+
+    async fn push(&mut self, data: Self::Data) -> unienc_common::Result<()> {
+        let handle = self.handle.as_ref().unwrap();
+        let pushed = unsafe {
+            (handle.backend().push_audio_samples)(
+                handle.encoder(),
+                data.data.as_ptr(),
+                data.data.len(),
+                data.timestamp_in_samples,
+            )
+        };
+        match pushed {
+            true => Ok(()),
+            false => Err(backend_error(
+                ErrorCategory::Encoding,
+                "Failed to push audio samples",
+            )),
+        }
+    }
+}
+
+impl<R: Runtime> Drop for ExternalAudioEncoderInput<R> {
+    fn drop(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        // finishing flushes the encoder, which may block
+        let finish = self.runtime.spawn_blocking(move || drop(handle));
+        self.runtime.spawn(finish)
+    }
+}
+
+impl EncoderOutput for ExternalAudioEncoderOutput {
+    type Data = ExternalEncodedData;
+
+    async fn pull(&mut self) -> unienc_common::Result<Option<Self::Data>> {
+        Ok(self.rx.next().await)
+    }
+}
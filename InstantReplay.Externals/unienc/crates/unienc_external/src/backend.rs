@@ -0,0 +1,266 @@
+//! The function table an external backend registers, and the handles wrapping the objects it
+//! creates.
+
+use std::ffi::{CStr, c_char, c_void};
+use std::sync::OnceLock;
+
+use futures::channel::mpsc;
+use unienc_common::{CommonError, ErrorCategory, Result};
+
+use crate::ExternalEncodedData;
+
+/// Encoded packet, passed to the backend's muxer and back from its encoders.
+#[repr(C)]
+pub struct UniencExternalPacket {
+    pub data: *const u8,
+    pub size: usize,
+    pub timestamp: f64,
+    pub is_key: bool,
+}
+
+/// Hands an encoded packet from a backend encoder to unienc; the data is copied before it returns.
+/// `sink` is the one passed when the encoder was created.
+pub type UniencExternalEmit =
+    unsafe extern "C" fn(sink: *mut c_void, packet: *const UniencExternalPacket);
+
+/// Functions of an encoding backend implemented outside this repository, for platforms whose SDKs
+/// cannot be published. Every function may be called from any thread, and `context` is passed to
+/// the ones creating objects. Functions returning `bool` report failure with `false`; the objects
+/// they take stay valid.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UniencExternalBackend {
+    pub context: *mut c_void,
+    /// Creates an H.264 encoder, or returns null. Packets are passed to `emit` with `sink` in
+    /// decode order, the first one a key frame with parameter sets in Annex-B form.
+    pub create_video_encoder: unsafe extern "C" fn(
+        context: *mut c_void,
+        width: u32,
+        height: u32,
+        fps_hint: u32,
+        bitrate: u32,
+        sink: *mut c_void,
+        emit: UniencExternalEmit,
+    ) -> *mut c_void,
+    /// Encodes BGRA pixels, rows `stride` bytes apart. `timestamp` is in seconds.
+    pub push_video_frame: unsafe extern "C" fn(
+        encoder: *mut c_void,
+        data: *const u8,
+        size: usize,
+        width: u32,
+        height: u32,
+        stride: u32,
+        timestamp: f64,
+    ) -> bool,
+    /// Creates an AAC-LC encoder, or returns null. Packets are raw AAC frames, passed to `emit`
+    /// with `sink`.
+    pub create_audio_encoder: unsafe extern "C" fn(
+        context: *mut c_void,
+        sample_rate: u32,
+        channels: u32,
+        bitrate: u32,
+        sink: *mut c_void,
+        emit: UniencExternalEmit,
+    ) -> *mut c_void,
+    /// Encodes `count` interleaved signed 16-bit samples, the first at `timestamp_in_samples`.
+    pub push_audio_samples: unsafe extern "C" fn(
+        encoder: *mut c_void,
+        data: *const i16,
+        count: usize,
+        timestamp_in_samples: u64,
+    ) -> bool,
+    /// Emits the packets still pending and frees a video or audio encoder. `emit` must not be
+    /// called for it after this returns.
+    pub finish_encoder: unsafe extern "C" fn(encoder: *mut c_void),
+    /// Creates a muxer writing an MP4 file to the UTF-8 path `output_path`, or returns null.
+    pub create_muxer: unsafe extern "C" fn(
+        context: *mut c_void,
+        output_path: *const c_char,
+        width: u32,
+        height: u32,
+        fps_hint: u32,
+        sample_rate: u32,
+        channels: u32,
+    ) -> *mut c_void,
+    pub write_video_packet:
+        unsafe extern "C" fn(muxer: *mut c_void, packet: *const UniencExternalPacket) -> bool,
+    pub write_audio_packet:
+        unsafe extern "C" fn(muxer: *mut c_void, packet: *const UniencExternalPacket) -> bool,
+    /// Finalizes the file and frees the muxer. Also called for muxers whose recording was
+    /// abandoned, with a result that is ignored.
+    pub finish_muxer: unsafe extern "C" fn(muxer: *mut c_void) -> bool,
+}
+
+// the backend is required to be callable from any thread
+unsafe impl Send for UniencExternalBackend {}
+unsafe impl Sync for UniencExternalBackend {}
+
+static BACKEND: OnceLock<UniencExternalBackend> = OnceLock::new();
+
+/// Registers the backend used by every encoding system created afterwards. Only the first
+/// registration succeeds.
+pub fn register(backend: UniencExternalBackend) -> bool {
+    BACKEND.set(backend).is_ok()
+}
+
+pub fn is_registered() -> bool {
+    BACKEND.get().is_some()
+}
+
+pub(crate) fn backend() -> Result<&'static UniencExternalBackend> {
+    BACKEND
+        .get()
+        .ok_or(CommonError::ExternalBackendNotRegistered)
+}
+
+pub(crate) fn backend_error(category: ErrorCategory, message: &str) -> CommonError {
+    CommonError::Categorized {
+        category,
+        message: format!("External backend: {message}"),
+    }
+}
+
+type Sink = mpsc::UnboundedSender<ExternalEncodedData>;
+
+unsafe extern "C" fn emit(sink: *mut c_void, packet: *const UniencExternalPacket) {
+    let (sink, packet) = unsafe { ((sink as *const Sink).as_ref(), packet.as_ref()) };
+    let (Some(sink), Some(packet)) = (sink, packet) else {
+        return;
+    };
+    let data = match packet.data.is_null() {
+        true => Vec::new(),
+        false => unsafe { std::slice::from_raw_parts(packet.data, packet.size) }.to_vec(),
+    };
+    // the output was dropped; the rest of the recording is discarded anyway
+    let _ = sink.unbounded_send(ExternalEncodedData {
+        data,
+        timestamp: packet.timestamp,
+        is_key: packet.is_key,
+    });
+}
+
+/// Encoder created by the backend, finished when dropped.
+pub(crate) struct EncoderHandle {
+    backend: &'static UniencExternalBackend,
+    encoder: *mut c_void,
+    sink: *mut Sink,
+}
+
+// the backend is required to be callable from any thread
+unsafe impl Send for EncoderHandle {}
+
+impl EncoderHandle {
+    pub fn new(
+        create: impl FnOnce(
+            &'static UniencExternalBackend,
+            *mut c_void,
+            UniencExternalEmit,
+        ) -> *mut c_void,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<ExternalEncodedData>)> {
+        let backend = backend()?;
+        let (tx, rx) = mpsc::unbounded();
+        let sink = Box::into_raw(Box::new(tx));
+        let encoder = create(backend, sink as *mut c_void, emit);
+        if encoder.is_null() {
+            drop(unsafe { Box::from_raw(sink) });
+            return Err(backend_error(
+                ErrorCategory::Initialization,
+                "Failed to create encoder",
+            ));
+        }
+        Ok((
+            Self {
+                backend,
+                encoder,
+                sink,
+            },
+            rx,
+        ))
+    }
+
+    pub fn backend(&self) -> &'static UniencExternalBackend {
+        self.backend
+    }
+
+    pub fn encoder(&self) -> *mut c_void {
+        self.encoder
+    }
+}
+
+impl Drop for EncoderHandle {
+    fn drop(&mut self) {
+        unsafe { (self.backend.finish_encoder)(self.encoder) };
+        // ends the output once the last packets were emitted
+        drop(unsafe { Box::from_raw(self.sink) });
+    }
+}
+
+/// Muxer created by the backend, finished when dropped unless [`finish`](Self::finish) was called.
+pub(crate) struct MuxerHandle {
+    backend: &'static UniencExternalBackend,
+    muxer: *mut c_void,
+}
+
+// the backend is required to be callable from any thread
+unsafe impl Send for MuxerHandle {}
+
+impl MuxerHandle {
+    pub fn new(output_path: &CStr, video: [u32; 3], audio: [u32; 2]) -> Result<Self> {
+        let backend = backend()?;
+        let muxer = unsafe {
+            (backend.create_muxer)(
+                backend.context,
+                output_path.as_ptr(),
+                video[0],
+                video[1],
+                video[2],
+                audio[0],
+                audio[1],
+            )
+        };
+        if muxer.is_null() {
+            return Err(backend_error(
+                ErrorCategory::Initialization,
+                "Failed to create muxer",
+            ));
+        }
+        Ok(Self { backend, muxer })
+    }
+
+    pub fn write(&self, video: bool, data: &ExternalEncodedData) -> Result<()> {
+        let packet = UniencExternalPacket {
+            data: data.data.as_ptr(),
+            size: data.data.len(),
+            timestamp: data.timestamp,
+            is_key: data.is_key,
+        };
+        let write = match video {
+            true => self.backend.write_video_packet,
+            false => self.backend.write_audio_packet,
+        };
+        match unsafe { write(self.muxer, &packet) } {
+            true => Ok(()),
+            false => Err(backend_error(
+                ErrorCategory::Muxing,
+                "Failed to write packet",
+            )),
+        }
+    }
+
+    pub fn finish(self) -> Result<()> {
+        let this = std::mem::ManuallyDrop::new(self);
+        match unsafe { (this.backend.finish_muxer)(this.muxer) } {
+            true => Ok(()),
+            false => Err(backend_error(
+                ErrorCategory::Muxing,
+                "Failed to finish muxer",
+            )),
+        }
+    }
+}
+
+impl Drop for MuxerHandle {
+    fn drop(&mut self) {
+        let _ = unsafe { (self.backend.finish_muxer)(self.muxer) };
+    }
+}
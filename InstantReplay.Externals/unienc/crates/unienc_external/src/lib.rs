@@ -0,0 +1,166 @@
+//! Encoding system forwarding to a backend registered at runtime through a C function table, for
+//! platforms whose SDKs are under NDA and cannot be built from this repository. The backend is
+//! implemented out of tree and registered with [`register`] (`unienc_register_external_backend`
+//! in the C API) before the first encoding system is created.
+//!
+//! The backend encodes BGRA frames to H.264 and interleaved PCM to AAC, and muxes both into an
+//! MP4 file. Blit sources, decoding and still image capture are not forwarded.
+
+mod audio;
+mod backend;
+mod mux;
+pub mod passthrough;
+mod video;
+
+use crate::audio::ExternalAudioEncoder;
+use crate::mux::ExternalMuxer;
+use crate::passthrough::{ExternalAacPacketizer, ExternalH264Packetizer};
+use crate::video::ExternalVideoEncoder;
+use bincode::{Decode, Encode};
+use std::path::Path;
+use unienc_common::{
+    AudioEncoderOptions, DiagnosticCheck, DownmixedAudioEncoder, DownmixedAudioOptions,
+    EncodedData, EncodingSystem, PaddedMuxer, PaddedVideoEncoder, PaddedVideoOptions,
+    StillImageFormat, TrackedRuntime, UniencSampleKind, UnsupportedBlitData, UnsupportedDecoder,
+    VideoEncoderOptions, still_image::UnsupportedStillImageCapture,
+};
+
+pub use backend::{
+    UniencExternalBackend, UniencExternalEmit, UniencExternalPacket, is_registered, register,
+};
+
+pub struct ExternalEncodingSystem<
+    V: unienc_common::VideoEncoderOptions,
+    A: unienc_common::AudioEncoderOptions,
+    R: unienc_common::Runtime,
+> {
+    video_options: PaddedVideoOptions<V>,
+    audio_options: DownmixedAudioOptions<A>,
+    runtime: TrackedRuntime<R>,
+}
+
+#[derive(Encode, Decode, Debug)]
+pub struct ExternalEncodedData {
+    pub(crate) data: Vec<u8>,
+    pub(crate) timestamp: f64,
+    pub(crate) is_key: bool,
+}
+
+impl<
+    V: unienc_common::VideoEncoderOptions,
+    A: unienc_common::AudioEncoderOptions,
+    R: unienc_common::Runtime + 'static,
+> EncodingSystem for ExternalEncodingSystem<V, A, R>
+{
+    type VideoEncoderOptionsType = V;
+    type AudioEncoderOptionsType = A;
+    type VideoEncoderType = PaddedVideoEncoder<ExternalVideoEncoder<TrackedRuntime<R>>>;
+    type AudioEncoderType = DownmixedAudioEncoder<ExternalAudioEncoder<TrackedRuntime<R>>>;
+    type MuxerType = PaddedMuxer<ExternalMuxer>;
+    type BlitSourceType = UnsupportedBlitData;
+    type RuntimeType = R;
+    type H264PacketizerType = ExternalH264Packetizer;
+    type AacPacketizerType = ExternalAacPacketizer;
+    type DecoderType = UnsupportedDecoder;
+    type StillImageCaptureType = UnsupportedStillImageCapture<UnsupportedBlitData>;
+
+    fn new(video_options: &V, audio_options: &A, runtime: R) -> Self {
+        Self {
+            video_options: PaddedVideoOptions::new(*video_options),
+            audio_options: DownmixedAudioOptions::stereo(*audio_options),
+            runtime: TrackedRuntime::new(runtime),
+        }
+    }
+
+    fn new_video_encoder(&self) -> unienc_common::Result<Self::VideoEncoderType> {
+        let codec = self.video_options.codec();
+        if !self.is_codec_supported(codec) {
+            return Err(unienc_common::CommonError::CodecNotSupported(codec));
+        }
+        if self.video_options.preserve_alpha() {
+            return Err(unienc_common::CommonError::AlphaNotSupported);
+        }
+        ExternalVideoEncoder::new(&self.video_options, &self.runtime).map(PaddedVideoEncoder::new)
+    }
+
+    fn new_audio_encoder(&self) -> unienc_common::Result<Self::AudioEncoderType> {
+        ExternalAudioEncoder::new(&self.audio_options, &self.runtime)
+            .map(|encoder| DownmixedAudioEncoder::new(encoder, &self.audio_options))
+    }
+
+    fn new_muxer(&self, output_path: &Path) -> unienc_common::Result<Self::MuxerType> {
+        ExternalMuxer::new(output_path, &self.video_options, &self.audio_options)
+            .map(|muxer| PaddedMuxer::new(muxer, output_path, &self.video_options))
+    }
+
+    fn new_h264_packetizer(&self) -> unienc_common::Result<Self::H264PacketizerType> {
+        Ok(ExternalH264Packetizer)
+    }
+
+    fn new_aac_packetizer(&self) -> unienc_common::Result<Self::AacPacketizerType> {
+        Ok(ExternalAacPacketizer {
+            sample_rate: self.audio_options.sample_rate(),
+        })
+    }
+
+    fn new_decoder(&self, _input_path: &Path) -> unienc_common::Result<Self::DecoderType> {
+        Err(unienc_common::CommonError::DecodeNotSupported)
+    }
+
+    fn new_still_image_capture(
+        &self,
+        _format: StillImageFormat,
+    ) -> unienc_common::Result<Self::StillImageCaptureType> {
+        Err(unienc_common::CommonError::BlitNotSupported)
+    }
+
+    fn shutdown(self) -> impl Future<Output = unienc_common::Result<()>> + Send {
+        let idle = self.runtime.idle();
+        async move {
+            idle.await;
+            Ok(())
+        }
+    }
+
+    fn self_test() -> Vec<DiagnosticCheck> {
+        vec![match is_registered() {
+            true => DiagnosticCheck::passed("external backend", "Registered"),
+            false => DiagnosticCheck::failed(
+                "external backend",
+                "Call unienc_register_external_backend before recording",
+            ),
+        }]
+    }
+}
+
+// the backend talks to the engine itself
+#[cfg(feature = "unity")]
+impl<
+    V: unienc_common::VideoEncoderOptions,
+    A: unienc_common::AudioEncoderOptions,
+    R: unienc_common::Runtime + 'static,
+> unienc_common::unity::UnityPlugin for ExternalEncodingSystem<V, A, R>
+{
+}
+
+impl EncodedData for ExternalEncodedData {
+    fn timestamp(&self) -> f64 {
+        self.timestamp
+    }
+
+    fn set_timestamp(&mut self, timestamp: f64) {
+        self.timestamp = timestamp;
+    }
+
+    fn kind(&self) -> UniencSampleKind {
+        if self.is_key {
+            UniencSampleKind::Key
+        } else {
+            UniencSampleKind::Interpolated
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+}
@@ -0,0 +1,134 @@
+use std::ffi::CString;
+use std::sync::{Arc, Mutex};
+
+use futures::channel::oneshot;
+use futures::join;
+use unienc_common::{CommonError, CompletionHandle, Muxer, MuxerInput, OptionExt, ResultExt};
+
+use crate::ExternalEncodedData;
+use crate::backend::MuxerHandle;
+
+pub struct ExternalMuxer {
+    video: ExternalVideoInput,
+    audio: ExternalAudioInput,
+    completion: ExternalCompletionHandle,
+}
+pub struct ExternalVideoInput {
+    muxer: Arc<Mutex<Option<MuxerHandle>>>,
+    finish_tx: Option<oneshot::Sender<()>>,
+}
+pub struct ExternalAudioInput {
+    muxer: Arc<Mutex<Option<MuxerHandle>>>,
+    finish_tx: Option<oneshot::Sender<()>>,
+}
+pub struct ExternalCompletionHandle {
+    muxer: Arc<Mutex<Option<MuxerHandle>>>,
+    video_finish_rx: Option<oneshot::Receiver<()>>,
+    audio_finish_rx: Option<oneshot::Receiver<()>>,
+}
+
+impl ExternalMuxer {
+    pub fn new<V: unienc_common::VideoEncoderOptions, A: unienc_common::AudioEncoderOptions>(
+        output_path: &std::path::Path,
+        video_options: &V,
+        audio_options: &A,
+    ) -> unienc_common::Result<Self> {
+        let output_path = CString::new(
+            output_path
+                .to_str()
+                .context("Output path is not valid UTF-8")?,
+        )
+        .context("Output path contains a null character")?;
+        let muxer = Arc::new(Mutex::new(Some(MuxerHandle::new(
+            &output_path,
+            [
+                video_options.width(),
+                video_options.height(),
+                video_options.fps_hint(),
+            ],
+            [audio_options.sample_rate(), audio_options.channels()],
+        )?)));
+
+        let (video_finish_tx, video_finish_rx) = oneshot::channel();
+        let (audio_finish_tx, audio_finish_rx) = oneshot::channel();
+
+        Ok(Self {
+            video: ExternalVideoInput {
+                muxer: muxer.clone(),
+                finish_tx: video_finish_tx.into(),
+            },
+            audio: ExternalAudioInput {
+                muxer: muxer.clone(),
+                finish_tx: audio_finish_tx.into(),
+            },
+            completion: ExternalCompletionHandle {
+                muxer,
+                video_finish_rx: video_finish_rx.into(),
+                audio_finish_rx: audio_finish_rx.into(),
+            },
+        })
+    }
+}
+
+impl Muxer for ExternalMuxer {
+    type VideoInputType = ExternalVideoInput;
+    type AudioInputType = ExternalAudioInput;
+    type CompletionHandleType = ExternalCompletionHandle;
+
+    fn get_inputs(
+        self,
+    ) -> unienc_common::Result<(
+        Self::VideoInputType,
+        Self::AudioInputType,
+        Self::CompletionHandleType,
+    )> {
+        Ok((self.video, self.audio, self.completion))
+    }
+}
+
+impl MuxerInput for ExternalVideoInput {
+    type Data = ExternalEncodedData;
+
+    async fn push(&mut self, data: Self::Data) -> unienc_common::Result<()> {
+        let muxer_guard = self.muxer.lock().unwrap();
+        muxer_guard.as_ref().unwrap().write(true, &data)
+    }
+
+    async fn finish(mut self) -> unienc_common::Result<()> {
+        self.finish_tx
+            .take()
+            .unwrap()
+            .send(())
+            .map_err(|e| CommonError::Other(format!("Failed to finish video: {:?}", e)))?;
+        Ok(())
+    }
+}
+
+impl MuxerInput for ExternalAudioInput {
+    type Data = ExternalEncodedData;
+
+    async fn push(&mut self, data: Self::Data) -> unienc_common::Result<()> {
+        let muxer_guard = self.muxer.lock().unwrap();
+        muxer_guard.as_ref().unwrap().write(false, &data)
+    }
+
+    async fn finish(mut self) -> unienc_common::Result<()> {
+        self.finish_tx
+            .take()
+            .unwrap()
+            .send(())
+            .map_err(|e| CommonError::Other(format!("Failed to finish audio: {:?}", e)))?;
+        Ok(())
+    }
+}
+
+impl CompletionHandle for ExternalCompletionHandle {
+    async fn finish(mut self) -> unienc_common::Result<()> {
+        let _ = join!(
+            self.video_finish_rx.take().unwrap(),
+            self.audio_finish_rx.take().unwrap()
+        );
+        let muxer = self.muxer.lock().unwrap().take().unwrap();
+        muxer.finish()
+    }
+}
@@ -0,0 +1,45 @@
+use unienc_common::passthrough::h264::AccessUnit;
+use unienc_common::{AacPacketizer, H264Packetizer};
+
+use crate::ExternalEncodedData;
+
+/// Backend muxers take Annex-B access units as the backend encoders produce them, so this only
+/// validates the input and detects key frames.
+pub struct ExternalH264Packetizer;
+
+impl H264Packetizer for ExternalH264Packetizer {
+    type Data = ExternalEncodedData;
+
+    fn packetize(
+        &mut self,
+        access_unit: &[u8],
+        timestamp: f64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        let is_key = AccessUnit::parse(access_unit)?.is_idr;
+        Ok(vec![ExternalEncodedData {
+            data: access_unit.to_vec(),
+            timestamp,
+            is_key,
+        }])
+    }
+}
+
+pub struct ExternalAacPacketizer {
+    pub(crate) sample_rate: u32,
+}
+
+impl AacPacketizer for ExternalAacPacketizer {
+    type Data = ExternalEncodedData;
+
+    fn packetize(
+        &mut self,
+        frame: &[u8],
+        timestamp_in_samples: u64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        Ok(vec![ExternalEncodedData {
+            data: frame.to_vec(),
+            timestamp: timestamp_in_samples as f64 / self.sample_rate as f64,
+            is_key: true,
+        }])
+    }
+}
@@ -0,0 +1,106 @@
+use futures::StreamExt;
+use futures::channel::mpsc;
+use unienc_common::{
+    Encoder, EncoderInput, EncoderOutput, ErrorCategory, Runtime, UnsupportedBlitData, VideoFrame,
+    VideoSample,
+};
+
+use crate::ExternalEncodedData;
+use crate::backend::{EncoderHandle, backend_error};
+
+pub struct ExternalVideoEncoder<R: Runtime> {
+    input: ExternalVideoEncoderInput<R>,
+    output: ExternalVideoEncoderOutput,
+}
+pub struct ExternalVideoEncoderInput<R: Runtime> {
+    handle: Option<EncoderHandle>,
+    runtime: R,
+}
+pub struct ExternalVideoEncoderOutput {
+    rx: mpsc::UnboundedReceiver<ExternalEncodedData>,
+}
+
+impl<R: Runtime> ExternalVideoEncoder<R> {
+    pub fn new<V: unienc_common::VideoEncoderOptions>(
+        options: &V,
+        runtime: &R,
+    ) -> unienc_common::Result<Self> {
+        let (handle, rx) = EncoderHandle::new(|backend, sink, emit| unsafe {
+            (backend.create_video_encoder)(
+                backend.context,
+                options.width(),
+                options.height(),
+                options.fps_hint(),
+                options.bitrate(),
+                sink,
+                emit,
+            )
+        })?;
+        Ok(Self {
+            input: ExternalVideoEncoderInput {
+                handle: Some(handle),
+                runtime: runtime.clone(),
+            },
+            output: ExternalVideoEncoderOutput { rx },
+        })
+    }
+}
+
+impl<R: Runtime + 'static> Encoder for ExternalVideoEncoder<R> {
+    type InputType = ExternalVideoEncoderInput<R>;
+    type OutputType = ExternalVideoEncoderOutput;
+
+    fn get(self) -> unienc_common::Result<(Self::InputType, Self::OutputType)> {
+        Ok((self.input, self.output))
+    }
+}
+
+impl<R: Runtime + 'static> EncoderInput for ExternalVideoEncoderInput<R> {
+    type Data = VideoSample<UnsupportedBlitData>;
+
+    async fn push(&mut self, data: Self::Data) -> unienc_common::Result<()> {
+        let VideoFrame::Bgra32(frame) = data.frame else {
+            return Err(unienc_common::CommonError::BlitNotSupported);
+        };
+
+        let handle = self.handle.as_ref().unwrap();
+        let pixels = frame.buffer.data();
+        let pushed = unsafe {
+            (handle.backend().push_video_frame)(
+                handle.encoder(),
+                pixels.as_ptr(),
+                pixels.len(),
+                frame.width,
+                frame.height,
+                frame.stride,
+                data.timestamp,
+            )
+        };
+        match pushed {
+            true => Ok(()),
+            false => Err(backend_error(
+                ErrorCategory::Encoding,
+                "Failed to push video frame",
+            )),
+        }
+    }
+}
+
+impl<R: Runtime> Drop for ExternalVideoEncoderInput<R> {
+    fn drop(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        // finishing flushes the encoder, which may block
+        let finish = self.runtime.spawn_blocking(move || drop(handle));
+        self.runtime.spawn(finish)
+    }
+}
+
+impl EncoderOutput for ExternalVideoEncoderOutput {
+    type Data = ExternalEncodedData;
+
+    async fn pull(&mut self) -> unienc_common::Result<Option<Self::Data>> {
+        Ok(self.rx.next().await)
+    }
+}
@@ -173,8 +173,8 @@ impl EncoderOutput for FFmpegAudioEncoderOutput {
 pub struct AudioEncodedData {
     pub(crate) header: Vec<u8>,
     pub(crate) payload: Vec<u8>,
-    timestamp_in_samples: u64,
-    sample_rate: u32,
+    pub(crate) timestamp_in_samples: u64,
+    pub(crate) sample_rate: u32,
 }
 
 impl EncodedData for AudioEncodedData {
@@ -189,4 +189,8 @@ impl EncodedData for AudioEncodedData {
     fn kind(&self) -> UniencSampleKind {
         UniencSampleKind::Interpolated
     }
+
+    fn size(&self) -> usize {
+        self.header.len() + self.payload.len()
+    }
 }
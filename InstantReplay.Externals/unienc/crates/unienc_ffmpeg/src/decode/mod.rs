@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use tokio::{io::AsyncReadExt, process::ChildStdout};
+use unienc_common::{DecodedVideoFrame, Decoder, SeekMode, VideoFrameBgra32, buffer::SharedBuffer};
+
+use crate::{
+    error::{FFmpegError, Result},
+    ffmpeg, process,
+};
+
+/// Decodes the first video stream at its own size, yielding every frame with its presentation
+/// time relative to the start of the file.
+pub struct FFmpegDecoder {
+    _ffmpeg: ffmpeg::FFmpeg,
+    output: ChildStdout,
+    input_path: PathBuf,
+    width: u32,
+    height: u32,
+    /// Presentation times of the frames not pulled yet.
+    timestamps: VecDeque<f64>,
+}
+
+/// What ffprobe reports of the first video stream.
+struct Probe {
+    width: u32,
+    height: u32,
+    /// Presentation times of the frames in presentation order, relative to the start of the file.
+    timestamps: Vec<f64>,
+}
+
+impl FFmpegDecoder {
+    pub fn new<P: AsRef<Path>>(input_path: P) -> Result<Self> {
+        Self::open(input_path.as_ref().to_owned(), 0.0)
+    }
+
+    /// Opens the file at the frame shown at `timestamp`.
+    fn open(input_path: PathBuf, timestamp: f64) -> Result<Self> {
+        let probe = probe(&input_path)?;
+
+        // timestamps are printed with microsecond precision
+        let start_time = probe
+            .timestamps
+            .iter()
+            .rev()
+            .find(|frame| **frame <= timestamp + 1e-6)
+            .map_or(timestamp, |frame| frame - 1e-6);
+
+        // decode into raw BGRA frames as they are stored: unrotated, unscaled and with the
+        // timestamps of the stream, so the probed packets line up with the decoded frames.
+        // -ss before the input seeks accurately by decoding forward from the preceding keyframe
+        // and dropping the frames before it.
+        let mut ffmpeg = ffmpeg::Builder::new()
+            .input_file(
+                ["-noautorotate", "-ss", &format!("{}", start_time.max(0.0))],
+                input_path.as_os_str(),
+            )
+            .build(
+                [
+                    "-map",
+                    "0:v:0",
+                    "-fps_mode",
+                    "passthrough",
+                    "-f",
+                    "rawvideo",
+                    "-pix_fmt",
+                    "bgra",
+                ],
+                ffmpeg::Destination::Stdout,
+            )?;
+
+        let output = ffmpeg
+            .stdout
+            .take()
+            .ok_or(FFmpegError::OutputNotAvailable)?;
+
+        let timestamps = probe
+            .timestamps
+            .into_iter()
+            .filter(|frame| *frame >= start_time)
+            .collect();
+
+        Ok(Self {
+            _ffmpeg: ffmpeg,
+            output,
+            input_path,
+            width: probe.width,
+            height: probe.height,
+            timestamps,
+        })
+    }
+}
+
+/// Reads the size of the first video stream and the timestamps of its packets without decoding
+/// them.
+fn probe(input_path: &Path) -> Result<Probe> {
+    let output = process::command(ffmpeg::FFPROBE_PATH.as_os_str())
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "format=start_time:stream=width,height:packet=pts_time",
+            "-of",
+            "compact=p=0",
+        ])
+        .arg(input_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(FFmpegError::Other(format!(
+            "ffprobe failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // one line per section, such as `width=1920|height=1080` or `pts_time=0.033333`
+    let mut width = None;
+    let mut height = None;
+    let mut start_time = 0.0;
+    let mut timestamps = Vec::new();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for (key, value) in stdout
+        .lines()
+        .flat_map(|line| line.split('|'))
+        .filter_map(|field| field.split_once('='))
+    {
+        match key {
+            "width" => width = value.parse().ok(),
+            "height" => height = value.parse().ok(),
+            "start_time" => start_time = value.parse().unwrap_or(0.0),
+            // N/A for packets without one
+            "pts_time" => timestamps.extend(value.parse::<f64>().ok()),
+            _ => {}
+        }
+    }
+
+    let (Some(width), Some(height)) = (width, height) else {
+        return Err(FFmpegError::Other(format!(
+            "No video stream found in {}",
+            input_path.display()
+        )));
+    };
+
+    // packets are listed in decoding order
+    for timestamp in &mut timestamps {
+        *timestamp -= start_time;
+    }
+    timestamps.sort_by(f64::total_cmp);
+
+    Ok(Probe {
+        width,
+        height,
+        timestamps,
+    })
+}
+
+impl Decoder for FFmpegDecoder {
+    async fn pull(&mut self) -> unienc_common::Result<Option<DecodedVideoFrame>> {
+        let mut data = vec![0u8; self.width as usize * self.height as usize * 4];
+        match self.output.read_exact(&mut data).await {
+            Ok(_) => {}
+            // a truncated trailing frame is dropped as well
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(FFmpegError::from(e).into()),
+        }
+
+        let timestamp = self.timestamps.pop_front().ok_or_else(|| {
+            FFmpegError::Other("FFmpeg decoded more frames than the stream has".to_string())
+        })?;
+
+        Ok(Some(DecodedVideoFrame {
+            frame: VideoFrameBgra32::packed(
+                SharedBuffer::new_unmanaged(data),
+                self.width,
+                self.height,
+            ),
+            timestamp,
+        }))
+    }
+
+    // FFmpeg does not report where a keyframe seek lands, so both modes seek precisely
+    async fn seek(
+        &mut self,
+        timestamp: f64,
+        _mode: SeekMode,
+    ) -> unienc_common::Result<Option<DecodedVideoFrame>> {
+        *self = Self::open(self.input_path.clone(), timestamp.max(0.0))?;
+        self.pull().await
+    }
+}
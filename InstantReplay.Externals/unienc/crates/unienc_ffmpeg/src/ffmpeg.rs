@@ -1,16 +1,19 @@
 use std::{
     ffi::{OsStr, OsString},
+    io::{BufRead, BufReader},
     os::fd::{AsRawFd, FromRawFd},
+    path::Path,
     process::{ExitStatus, Stdio},
     sync::LazyLock,
 };
 
 use tokio::{
     io::AsyncWrite,
-    process::{Child, ChildStdin, ChildStdout, Command},
+    process::{ChildStdin, ChildStdout},
 };
 
 use crate::error::{FFmpegError, Result};
+use crate::process::{self, ManagedChild};
 
 pub static FFMPEG_PATH: LazyLock<OsString> = LazyLock::new(|| {
     let res: Result<OsString> = std::process::Command::new("which")
@@ -41,15 +44,26 @@ pub static FFMPEG_PATH: LazyLock<OsString> = LazyLock::new(|| {
         fallback.unwrap_or(OsString::from("ffmpeg"))
     });
 
-    println!("using FFmpeg at: {}", res.to_str().unwrap());
+    unienc_common::log!("using FFmpeg at: {}", res.to_str().unwrap());
 
     res
 });
 
+/// ffprobe, which comes with ffmpeg: the one next to [`FFMPEG_PATH`], or the one in PATH.
+pub static FFPROBE_PATH: LazyLock<OsString> =
+    LazyLock::new(|| match Path::new(FFMPEG_PATH.as_os_str()).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir
+            .join(format!("ffprobe{}", std::env::consts::EXE_SUFFIX))
+            .into_os_string(),
+        _ => OsString::from("ffprobe"),
+    });
+
 #[derive(Default)]
 pub struct Builder {
+    input_files: Vec<(Vec<OsString>, OsString)>,
     inputs: Vec<Vec<OsString>>,
     use_stdin: bool,
+    secrets: Vec<String>,
 }
 
 pub enum Input {
@@ -91,7 +105,7 @@ impl AsyncWrite for Input {
 }
 
 pub struct FFmpeg {
-    child: Child,
+    child: ManagedChild,
     pub inputs: Option<Vec<Input>>,
     pub stdout: Option<ChildStdout>,
 }
@@ -112,21 +126,43 @@ impl Builder {
         self
     }
 
+    /// Adds a file input. File inputs precede piped inputs in FFmpeg's input order.
+    pub fn input_file(
+        mut self,
+        options: impl IntoIterator<Item: AsRef<OsStr>>,
+        path: impl AsRef<OsStr>,
+    ) -> Self {
+        self.input_files.push((
+            options.into_iter().map(|s| s.as_ref().to_owned()).collect(),
+            path.as_ref().to_owned(),
+        ));
+        self
+    }
+
     pub fn use_stdin(mut self, use_stdin: bool) -> Self {
         self.use_stdin = use_stdin;
         self
     }
 
+    /// Keeps `secret`, such as a stream key in the destination, out of the logged command and of
+    /// the errors ffmpeg reports.
+    pub fn redact(mut self, secret: impl Into<String>) -> Self {
+        self.secrets.push(secret.into());
+        self
+    }
+
     pub fn build(
         self,
         output_options: impl IntoIterator<Item: AsRef<OsStr>>,
         dest: Destination,
     ) -> Result<FFmpeg> {
-        let mut command = Command::new(FFMPEG_PATH.as_os_str());
+        let mut command = process::command(FFMPEG_PATH.as_os_str());
 
-        command
-            .kill_on_drop(true)
-            .args(["-y", "-loglevel", "error"]);
+        command.args(["-y", "-loglevel", "error"]);
+
+        for (options, path) in self.input_files {
+            command.args(options).arg("-i").arg(path);
+        }
 
         let mut inputs = Vec::new();
         let mut pending_fd = Vec::new();
@@ -164,9 +200,30 @@ impl Builder {
             Destination::Stdout => command.stdout(Stdio::piped()).arg(OsString::from("-")),
         };
 
-        println!("Running FFmpeg: {command:?}");
-
-        let mut child = command.spawn()?;
+        // without the environment, which may hold credentials
+        let logged = std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| format!("{arg:?}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        unienc_common::log!("Running FFmpeg: {}", redact(logged, &self.secrets));
+
+        // errors such as a failure to open the destination quote it, so they are logged through
+        // the same redaction instead of going to the inherited stderr
+        let (stderr, stderr_writer) = std::io::pipe()?;
+        command.stderr(stderr_writer);
+
+        let mut child = process::spawn(command)?;
+
+        let secrets = self.secrets;
+        std::thread::spawn(move || {
+            for line in BufReader::new(stderr)
+                .lines()
+                .map_while(std::io::Result::ok)
+            {
+                unienc_common::log!("FFmpeg: {}", redact(line, &secrets));
+            }
+        });
 
         drop(pending_fd);
 
@@ -189,6 +246,14 @@ impl Builder {
     }
 }
 
+/// Replaces every non-empty one of `secrets` in `text`.
+fn redact(mut text: String, secrets: &[String]) -> String {
+    for secret in secrets.iter().filter(|secret| !secret.is_empty()) {
+        text = text.replace(secret.as_str(), "<redacted>");
+    }
+    text
+}
+
 impl FFmpeg {
     pub async fn wait(mut self) -> Result<ExitStatus> {
         Ok(self.child.wait().await?)
@@ -1,17 +1,27 @@
 use std::path::Path;
-use unienc_common::{EncodingSystem, UnsupportedBlitData};
+use unienc_common::{
+    DiagnosticCheck, DownmixedAudioEncoder, DownmixedAudioOptions, EncoderCapabilities,
+    EncodingSystem, PaddedMuxer, PaddedVideoEncoder, PaddedVideoOptions, Reconnect,
+    ReconnectingMuxer, StillImageFormat, UnsupportedBlitData, VideoCodec, VideoEncoderOptions,
+    still_image::UnsupportedStillImageCapture,
+};
 
 pub mod audio;
+pub mod decode;
 pub mod error;
 mod ffmpeg;
 pub mod mux;
+pub mod passthrough;
+pub mod process;
 mod utils;
 pub mod video;
 
 pub use error::{FFmpegError, Result};
 
 use audio::FFmpegAudioEncoder;
+use decode::FFmpegDecoder;
 use mux::FFmpegMuxer;
+use passthrough::{FFmpegAacPacketizer, FFmpegH264Packetizer};
 use video::FFmpegVideoEncoder;
 
 pub struct FFmpegEncodingSystem<
@@ -19,43 +29,186 @@ pub struct FFmpegEncodingSystem<
     A: unienc_common::AudioEncoderOptions,
     R: unienc_common::Runtime,
 > {
-    video_options: V,
-    audio_options: A,
+    video_options: PaddedVideoOptions<V>,
+    audio_options: DownmixedAudioOptions<A>,
     _runtime: std::marker::PhantomData<R>,
 }
 
 impl<
-    V: unienc_common::VideoEncoderOptions,
+    V: unienc_common::VideoEncoderOptions + Send + Sync + 'static,
     A: unienc_common::AudioEncoderOptions,
     R: unienc_common::Runtime,
 > EncodingSystem for FFmpegEncodingSystem<V, A, R>
 {
     type VideoEncoderOptionsType = V;
     type AudioEncoderOptionsType = A;
-    type VideoEncoderType = FFmpegVideoEncoder;
-    type AudioEncoderType = FFmpegAudioEncoder;
-    type MuxerType = FFmpegMuxer;
+    type VideoEncoderType = PaddedVideoEncoder<FFmpegVideoEncoder>;
+    type AudioEncoderType = DownmixedAudioEncoder<FFmpegAudioEncoder>;
+    type MuxerType = PaddedMuxer<ReconnectingMuxer<FFmpegMuxer>>;
     type BlitSourceType = UnsupportedBlitData;
     type RuntimeType = R;
+    type H264PacketizerType = FFmpegH264Packetizer;
+    type AacPacketizerType = FFmpegAacPacketizer;
+    type DecoderType = FFmpegDecoder;
+    type StillImageCaptureType = UnsupportedStillImageCapture<UnsupportedBlitData>;
 
     fn new(video_options: &V, audio_options: &A, runtime: R) -> Self {
         Self {
-            video_options: *video_options,
-            audio_options: *audio_options,
+            video_options: PaddedVideoOptions::new(*video_options),
+            // the native AAC encoder has default layouts for 5.1 and 7.1
+            audio_options: DownmixedAudioOptions::new(*audio_options, |channels| {
+                matches!(channels, 6 | 8)
+            }),
             _runtime: std::marker::PhantomData,
         }
     }
 
     fn new_video_encoder(&self) -> unienc_common::Result<Self::VideoEncoderType> {
-        FFmpegVideoEncoder::new(&self.video_options).map_err(|e| e.into())
+        let codec = self.video_options.codec();
+        if !self.is_codec_supported(codec) {
+            return Err(unienc_common::CommonError::CodecNotSupported(codec));
+        }
+        if self.video_options.preserve_alpha() && !self.is_alpha_supported() {
+            return Err(unienc_common::CommonError::AlphaNotSupported);
+        }
+        FFmpegVideoEncoder::new(&self.video_options)
+            .map_err(|e| e.into())
+            .map(PaddedVideoEncoder::new)
     }
 
     fn new_audio_encoder(&self) -> unienc_common::Result<Self::AudioEncoderType> {
-        FFmpegAudioEncoder::new(&self.audio_options).map_err(|e| e.into())
+        FFmpegAudioEncoder::new(&self.audio_options)
+            .map_err(|e| e.into())
+            .map(|encoder| DownmixedAudioEncoder::new(encoder, &self.audio_options))
     }
 
     fn new_muxer(&self, output_path: &Path) -> unienc_common::Result<Self::MuxerType> {
         FFmpegMuxer::new(output_path, &self.video_options, &self.audio_options)
             .map_err(|e| e.into())
+            .map(|muxer| {
+                PaddedMuxer::new(
+                    ReconnectingMuxer::direct(muxer),
+                    output_path,
+                    &self.video_options,
+                )
+            })
+    }
+
+    fn new_rtmp_muxer(
+        &self,
+        url: &str,
+        reconnect: Option<Reconnect>,
+    ) -> unienc_common::Result<Self::MuxerType> {
+        let (url, video_options) = (url.to_string(), self.video_options);
+        streamed_muxer(
+            move || FFmpegMuxer::new_rtmp(&url, &video_options),
+            reconnect,
+        )
+    }
+
+    fn new_whip_muxer(
+        &self,
+        url: &str,
+        bearer_token: Option<&str>,
+        reconnect: Option<Reconnect>,
+    ) -> unienc_common::Result<Self::MuxerType> {
+        let (url, video_options) = (url.to_string(), self.video_options);
+        let bearer_token = bearer_token.map(str::to_string);
+        streamed_muxer(
+            move || FFmpegMuxer::new_whip(&url, bearer_token.as_deref(), &video_options),
+            reconnect,
+        )
+    }
+
+    fn new_srt_muxer(
+        &self,
+        url: &str,
+        reconnect: Option<Reconnect>,
+    ) -> unienc_common::Result<Self::MuxerType> {
+        let (url, video_options) = (url.to_string(), self.video_options);
+        streamed_muxer(
+            move || FFmpegMuxer::new_srt(&url, &video_options),
+            reconnect,
+        )
+    }
+
+    fn new_h264_packetizer(&self) -> unienc_common::Result<Self::H264PacketizerType> {
+        Ok(FFmpegH264Packetizer)
+    }
+
+    fn new_aac_packetizer(&self) -> unienc_common::Result<Self::AacPacketizerType> {
+        FFmpegAacPacketizer::new(&self.audio_options)
+    }
+
+    fn new_decoder(&self, input_path: &Path) -> unienc_common::Result<Self::DecoderType> {
+        FFmpegDecoder::new(input_path).map_err(|e| e.into())
+    }
+
+    fn new_still_image_capture(
+        &self,
+        _format: StillImageFormat,
+    ) -> unienc_common::Result<Self::StillImageCaptureType> {
+        Err(unienc_common::CommonError::BlitNotSupported)
+    }
+
+    fn shutdown(self) -> impl Future<Output = unienc_common::Result<()>> + Send {
+        // components spawn no tasks, but abandoned processes may be left to reap
+        process::reap();
+        std::future::ready(Ok(()))
+    }
+
+    fn is_alpha_supported(&self) -> bool {
+        // only the ProRes 4444 profile has an alpha channel
+        self.video_options.codec() == VideoCodec::ProRes
+    }
+
+    fn is_codec_supported(&self, _codec: VideoCodec) -> bool {
+        // the ProRes and DNxHR encoders are built into ffmpeg, unlike the H.264 ones
+        true
+    }
+
+    fn self_test() -> Vec<DiagnosticCheck> {
+        let path = ffmpeg::FFMPEG_PATH.to_string_lossy();
+        let version = process::command(ffmpeg::FFMPEG_PATH.as_os_str())
+            .arg("-version")
+            .output();
+        vec![match version {
+            Ok(output) if output.status.success() => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let version = stdout.lines().next().unwrap_or_default();
+                DiagnosticCheck::passed("ffmpeg", format!("{version} ({path})"))
+            }
+            Ok(output) => DiagnosticCheck::failed(
+                "ffmpeg",
+                format!("{path} -version exited with {}", output.status),
+            ),
+            Err(err) => DiagnosticCheck::failed(
+                "ffmpeg",
+                format!(
+                    "Failed to run {path:?}: {err}. Install ffmpeg and make sure it is on PATH"
+                ),
+            ),
+        }]
+    }
+
+    fn encoder_capabilities(_width: u32, _height: u32) -> EncoderCapabilities {
+        EncoderCapabilities {
+            hardware_accelerated: video::hardware_accelerated(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Muxer for a network output, connected with `connect` again after the connection fails if
+/// `reconnect` is set. A new ffmpeg process is started for every connection.
+fn streamed_muxer(
+    connect: impl Fn() -> Result<FFmpegMuxer> + Send + Sync + 'static,
+    reconnect: Option<Reconnect>,
+) -> unienc_common::Result<PaddedMuxer<ReconnectingMuxer<FFmpegMuxer>>> {
+    let connect = move || connect().map_err(unienc_common::CommonError::from);
+    match reconnect {
+        Some(reconnect) => ReconnectingMuxer::new(connect, reconnect),
+        None => connect().map(ReconnectingMuxer::direct),
     }
+    .map(PaddedMuxer::streamed)
 }
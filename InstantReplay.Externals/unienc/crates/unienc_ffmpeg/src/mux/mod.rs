@@ -1,7 +1,10 @@
+use std::ffi::OsString;
 use std::path::Path;
+use std::sync::RwLock;
+use std::time::Duration;
 
 use tokio::io::AsyncWriteExt;
-use unienc_common::{CompletionHandle, Muxer, MuxerInput};
+use unienc_common::{CommonError, CompletionHandle, Muxer, MuxerInput, VideoCodec};
 
 use crate::{
     audio::AudioEncodedData,
@@ -10,6 +13,53 @@ use crate::{
     video::VideoEncodedData,
 };
 
+/// Layout of the files the muxer writes. By default ffmpeg writes `moov`, the index of the file,
+/// after the media data once the recording completes, so a file cut short by a crash cannot be
+/// played.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FileLayout {
+    /// Whether to move `moov` ahead of the media data once the recording completes, so that
+    /// players streaming the file can start before downloading all of it.
+    pub faststart: bool,
+    /// Whether to write the media data in fragments, each indexed as soon as it is written, so
+    /// that a file cut short plays up to its last complete fragment. Fragmented files start with
+    /// `moov` and need no faststart.
+    pub fragmented: bool,
+    /// Longest a fragment may last, or `None` to start fragments at keyframes only.
+    pub fragment_duration: Option<Duration>,
+}
+
+impl FileLayout {
+    /// Options of ffmpeg's `mp4` and `mov` muxers writing files laid out this way.
+    fn output_options(&self) -> Vec<String> {
+        let movflags = match (self.fragmented, self.faststart) {
+            // the random access index at the end locates fragments by their offset, which the
+            // clean aperture added to `moov` of padded videos shifts; players do without it
+            (true, _) => "+frag_keyframe+empty_moov+default_base_moof+skip_trailer",
+            (false, true) => "+faststart",
+            (false, false) => return Vec::new(),
+        };
+        let mut options = vec!["-movflags".to_string(), movflags.to_string()];
+        if let Some(duration) = self.fragment_duration.filter(|_| self.fragmented) {
+            let micros = duration.as_micros().max(1);
+            options.extend(["-frag_duration".to_string(), micros.to_string()]);
+        }
+        options
+    }
+}
+
+static FILE_LAYOUT: RwLock<Option<FileLayout>> = RwLock::new(None);
+
+/// Sets the layout of the files written by muxers created afterwards.
+pub fn set_file_layout(layout: FileLayout) {
+    *FILE_LAYOUT.write().unwrap_or_else(|e| e.into_inner()) = Some(layout);
+}
+
+pub fn file_layout() -> FileLayout {
+    let layout = FILE_LAYOUT.read().unwrap_or_else(|e| e.into_inner());
+    layout.unwrap_or_default()
+}
+
 pub struct FFmpegMuxer {
     video: FFmpegMuxerVideoInput,
     audio: FFmpegMuxerAudioInput,
@@ -34,17 +84,124 @@ impl FFmpegMuxer {
         video_options: &impl unienc_common::VideoEncoderOptions,
         audio_options: &impl unienc_common::AudioEncoderOptions,
     ) -> Result<Self> {
-        // raw H.264 frame cannot have timestamp, so we need to assume CFR (encoder also supports CFR)
-        let mut ffmpeg = ffmpeg::Builder::new()
-            .use_stdin(true)
-            .input(["-f", "h264", "-r", &format!("{}", video_options.fps_hint())])
-            .input(["-f", "aac"])
-            .build(
-                [
+        let cfr = format!("{}", video_options.fps_hint());
+        let layout_options = file_layout().output_options();
+        let (video_input_options, mut output_options) = match video_options.codec() {
+            // raw H.264 frame cannot have timestamp, so we need to assume CFR (encoder also supports CFR)
+            VideoCodec::H264 => (
+                vec!["-f", "h264", "-r", &cfr],
+                vec![
                     "-pix_fmt", "yuv420p", "-c:v", "copy", "-c:a", "copy", "-f", "mp4",
                 ],
-                ffmpeg::Destination::Path(output_path.as_ref().as_os_str().to_owned()),
-            )?;
+            ),
+            // fragments carry their timestamps; MP4 cannot carry the intermediate codecs
+            _ => (
+                vec!["-f", "mov"],
+                vec!["-c:v", "copy", "-c:a", "copy", "-f", "mov"],
+            ),
+        };
+        output_options.extend(layout_options.iter().map(String::as_str));
+        Self::spawn(
+            ffmpeg::Builder::new(),
+            video_input_options,
+            output_options,
+            output_path.as_ref().as_os_str().to_owned(),
+        )
+    }
+
+    /// Publishes FLV to the RTMP `url` instead of writing a file. FLV only carries H.264 here.
+    pub fn new_rtmp(
+        url: &str,
+        video_options: &impl unienc_common::VideoEncoderOptions,
+    ) -> Result<Self> {
+        let codec = video_options.codec();
+        if codec != VideoCodec::H264 {
+            return Err(FFmpegError::Common(CommonError::CodecNotSupported(codec)));
+        }
+        let cfr = format!("{}", video_options.fps_hint());
+        let key = url.rsplit_once('/').map_or("", |(_, key)| key);
+        Self::spawn(
+            ffmpeg::Builder::new().redact(key),
+            vec!["-f", "h264", "-r", &cfr],
+            // the duration and size are unknown until the stream ends, and cannot be sought back to
+            vec![
+                "-c:v",
+                "copy",
+                "-c:a",
+                "copy",
+                "-f",
+                "flv",
+                "-flvflags",
+                "no_duration_filesize",
+            ],
+            url.into(),
+        )
+    }
+
+    /// Publishes over WebRTC to the WHIP endpoint `url` with ffmpeg's `whip` muxer, which needs
+    /// ffmpeg 8 built with TLS. The AAC track is transcoded to the Opus that WebRTC carries, and
+    /// the H.264 track is sent as is.
+    pub fn new_whip(
+        url: &str,
+        bearer_token: Option<&str>,
+        video_options: &impl unienc_common::VideoEncoderOptions,
+    ) -> Result<Self> {
+        let codec = video_options.codec();
+        if codec != VideoCodec::H264 {
+            return Err(FFmpegError::Common(CommonError::CodecNotSupported(codec)));
+        }
+        let cfr = format!("{}", video_options.fps_hint());
+        let mut output_options = vec![
+            "-c:v", "copy", "-c:a", "libopus", "-ar", "48000", "-ac", "2", "-b:a", "128k", "-f",
+            "whip",
+        ];
+        let mut builder = ffmpeg::Builder::new();
+        if let Some((_, query)) = url.split_once('?') {
+            builder = builder.redact(query);
+        }
+        if let Some(token) = bearer_token {
+            output_options.extend(["-authorization", token]);
+            builder = builder.redact(token);
+        }
+        Self::spawn(
+            builder,
+            vec!["-f", "h264", "-r", &cfr],
+            output_options,
+            url.into(),
+        )
+    }
+
+    /// Publishes MPEG-TS over SRT to `url`, which needs ffmpeg built with libsrt. The options in
+    /// the query of the URL, such as a passphrase, are kept out of the logged command.
+    pub fn new_srt(
+        url: &str,
+        video_options: &impl unienc_common::VideoEncoderOptions,
+    ) -> Result<Self> {
+        let codec = video_options.codec();
+        if codec != VideoCodec::H264 {
+            return Err(FFmpegError::Common(CommonError::CodecNotSupported(codec)));
+        }
+        let cfr = format!("{}", video_options.fps_hint());
+        let query = url.split_once('?').map_or("", |(_, query)| query);
+        Self::spawn(
+            ffmpeg::Builder::new().redact(query),
+            vec!["-f", "h264", "-r", &cfr],
+            vec!["-c:v", "copy", "-c:a", "copy", "-f", "mpegts"],
+            url.into(),
+        )
+    }
+
+    fn spawn(
+        builder: ffmpeg::Builder,
+        video_input_options: Vec<&str>,
+        output_options: Vec<&str>,
+        destination: OsString,
+    ) -> Result<Self> {
+        let mut ffmpeg = builder
+            .use_stdin(true)
+            .input(video_input_options)
+            .input(["-f", "aac"])
+            .build(output_options, ffmpeg::Destination::Path(destination))?;
 
         let mut inputs = ffmpeg
             .inputs
@@ -87,10 +244,11 @@ impl MuxerInput for FFmpegMuxerVideoInput {
     async fn push(&mut self, data: Self::Data) -> unienc_common::Result<()> {
         let input = self.input.as_mut().ok_or(FFmpegError::InputNotAvailable)?;
         match data {
-            VideoEncodedData::ParameterSet(payload) => {
+            VideoEncodedData::ParameterSet(payload) | VideoEncodedData::InitSegment(payload) => {
                 input.write_all(&payload).await.map_err(FFmpegError::from)?;
             }
-            VideoEncodedData::Slice { payload, .. } => {
+            VideoEncodedData::Slice { payload, .. }
+            | VideoEncodedData::Fragment { payload, .. } => {
                 input.write_all(&payload).await.map_err(FFmpegError::from)?;
             }
         }
@@ -146,7 +304,7 @@ impl MuxerInput for FFmpegMuxerAudioInput {
 impl CompletionHandle for FFmpegCompletionHandle {
     async fn finish(self) -> unienc_common::Result<()> {
         let result = self.child.wait().await?;
-        println!("FFmpeg exited: {}", result);
+        unienc_common::log!("FFmpeg exited: {}", result);
         if result.success() {
             Ok(())
         } else {
@@ -154,3 +312,30 @@ impl CompletionHandle for FFmpegCompletionHandle {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragments_take_precedence_over_faststart() {
+        let mut layout = FileLayout {
+            faststart: true,
+            fragmented: false,
+            fragment_duration: Some(Duration::from_millis(500)),
+        };
+        assert_eq!(layout.output_options(), ["-movflags", "+faststart"]);
+
+        layout.fragmented = true;
+        assert_eq!(
+            layout.output_options(),
+            [
+                "-movflags",
+                "+frag_keyframe+empty_moov+default_base_moof+skip_trailer",
+                "-frag_duration",
+                "500000",
+            ]
+        );
+        assert!(FileLayout::default().output_options().is_empty());
+    }
+}
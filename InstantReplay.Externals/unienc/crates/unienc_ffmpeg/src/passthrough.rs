@@ -0,0 +1,68 @@
+use unienc_common::passthrough::{aac, h264::AccessUnit};
+use unienc_common::{AacPacketizer, AudioEncoderOptions, H264Packetizer};
+
+use crate::{audio::AudioEncodedData, video::VideoEncodedData};
+
+/// Splits Annex-B access units into the parameter sets and slices written to the muxer's raw H.264
+/// input.
+#[derive(Default)]
+pub struct FFmpegH264Packetizer;
+
+impl H264Packetizer for FFmpegH264Packetizer {
+    type Data = VideoEncodedData;
+
+    fn packetize(
+        &mut self,
+        access_unit: &[u8],
+        timestamp: f64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        let access_unit = AccessUnit::parse(access_unit)?;
+        let mut data = Vec::with_capacity(3);
+        for parameter_set in [access_unit.sps, access_unit.pps].into_iter().flatten() {
+            let mut payload = vec![0, 0, 0, 1];
+            payload.extend_from_slice(parameter_set);
+            data.push(VideoEncodedData::ParameterSet(payload));
+        }
+        data.push(VideoEncodedData::Slice {
+            payload: access_unit.to_annexb(),
+            timestamp,
+            is_idr: access_unit.is_idr,
+        });
+        Ok(data)
+    }
+}
+
+/// Prefixes raw AAC frames with ADTS headers for the muxer's ADTS input.
+pub struct FFmpegAacPacketizer {
+    sample_rate: u32,
+    channels: u32,
+}
+
+impl FFmpegAacPacketizer {
+    pub fn new<A: AudioEncoderOptions>(options: &A) -> unienc_common::Result<Self> {
+        // validate the configuration up front instead of failing on the first frame
+        aac::adts_header(options.sample_rate(), options.channels(), 0)?;
+        Ok(Self {
+            sample_rate: options.sample_rate(),
+            channels: options.channels(),
+        })
+    }
+}
+
+impl AacPacketizer for FFmpegAacPacketizer {
+    type Data = AudioEncodedData;
+
+    fn packetize(
+        &mut self,
+        frame: &[u8],
+        timestamp_in_samples: u64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        let header = aac::adts_header(self.sample_rate, self.channels, frame.len())?;
+        Ok(vec![AudioEncodedData {
+            header: header.to_vec(),
+            payload: frame.to_vec(),
+            timestamp_in_samples,
+            sample_rate: self.sample_rate,
+        }])
+    }
+}
@@ -0,0 +1,203 @@
+//! How this backend starts ffmpeg processes. By default they get the environment and working
+//! directory of the game, which may carry variables such as `LD_PRELOAD` meant for the game
+//! alone; [`set_process_options`] narrows that down for processes started afterwards. Dropped
+//! processes are killed and reaped, so none is left running or as a zombie once a recording is
+//! abandoned.
+
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+
+use tokio::process::Child;
+
+/// Variables of the game's environment passed on when it is cleared: what ffmpeg needs to find
+/// its libraries, drivers and temporary directory.
+pub const KEPT_VARIABLES: [&str; 13] = [
+    "PATH",
+    "HOME",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "LANG",
+    "LC_ALL",
+    "LD_LIBRARY_PATH",
+    "XDG_RUNTIME_DIR",
+    "LIBVA_DRIVER_NAME",
+    "LIBVA_DRIVERS_PATH",
+    "SYSTEMROOT",
+    "SSL_CERT_FILE",
+];
+
+/// Windows' `CREATE_NO_WINDOW`, which keeps console programs from flashing a window.
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessOptions {
+    /// Whether to pass only [`KEPT_VARIABLES`] of the game's environment.
+    pub clear_environment: bool,
+    /// Variables set on top of the game's environment, or of what is kept of it.
+    pub environment: Vec<(OsString, OsString)>,
+    /// Directory the processes run in instead of the game's working directory. Relative output
+    /// paths are resolved by the game before they are passed, so they are not affected.
+    pub working_directory: Option<PathBuf>,
+    /// Added to the niceness of the processes; positive values lower their priority below the
+    /// game's. Raising it needs privileges and is ignored without them.
+    pub niceness: i32,
+    /// Whether to start the processes without a console window, on Windows.
+    pub hide_window: bool,
+    /// Whether to kill a process dropped before it exits, as when a recording is abandoned.
+    pub kill_on_drop: bool,
+}
+
+impl Default for ProcessOptions {
+    fn default() -> Self {
+        Self {
+            clear_environment: false,
+            environment: Vec::new(),
+            working_directory: None,
+            niceness: 0,
+            hide_window: true,
+            kill_on_drop: true,
+        }
+    }
+}
+
+static OPTIONS: RwLock<Option<ProcessOptions>> = RwLock::new(None);
+
+/// Processes dropped before they were reaped.
+static ORPHANS: Mutex<Vec<Child>> = Mutex::new(Vec::new());
+
+/// Sets how the processes started afterwards are started.
+pub fn set_process_options(options: ProcessOptions) {
+    *OPTIONS.write().unwrap_or_else(|e| e.into_inner()) = Some(options);
+}
+
+pub fn process_options() -> ProcessOptions {
+    let options = OPTIONS.read().unwrap_or_else(|e| e.into_inner());
+    options.clone().unwrap_or_default()
+}
+
+/// Environment of the processes when it is cleared, from the game's `inherited` one.
+fn kept_environment(
+    inherited: impl IntoIterator<Item = (OsString, OsString)>,
+) -> impl Iterator<Item = (OsString, OsString)> {
+    inherited.into_iter().filter(|(name, _)| {
+        KEPT_VARIABLES
+            .iter()
+            .any(|kept| OsStr::new(kept).eq_ignore_ascii_case(name))
+    })
+}
+
+/// Command running `program` with the current options. [`spawn`] starts it as a managed child.
+pub(crate) fn command(program: impl AsRef<OsStr>) -> std::process::Command {
+    let options = process_options();
+    let mut command = std::process::Command::new(program);
+
+    if options.clear_environment {
+        command
+            .env_clear()
+            .envs(kept_environment(std::env::vars_os()));
+    }
+    command.envs(options.environment);
+    if let Some(directory) = options.working_directory {
+        command.current_dir(directory);
+    }
+
+    #[cfg(unix)]
+    if options.niceness != 0 {
+        use std::os::unix::process::CommandExt;
+        let niceness = options.niceness;
+        // SAFETY: nice is async-signal-safe, and its failure is ignored
+        unsafe {
+            command.pre_exec(move || {
+                libc::nice(niceness);
+                Ok(())
+            });
+        }
+    }
+    #[cfg(windows)]
+    if options.hide_window {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+}
+
+/// Starts `command`, killing the process once the returned child is dropped before it exits if
+/// the options say so.
+pub(crate) fn spawn(command: std::process::Command) -> std::io::Result<ManagedChild> {
+    reap();
+    let child = tokio::process::Command::from(command).spawn()?;
+    Ok(ManagedChild {
+        child: Some(child),
+        kill_on_drop: process_options().kill_on_drop,
+    })
+}
+
+/// Reaps the processes that have exited since they were dropped, returning how many are still
+/// running. Called whenever a process is started.
+pub fn reap() -> usize {
+    let mut orphans = ORPHANS.lock().unwrap_or_else(|e| e.into_inner());
+    orphans.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+    orphans.len()
+}
+
+/// Child process that is killed when dropped before it exits, then reaped by [`reap`].
+pub(crate) struct ManagedChild {
+    child: Option<Child>,
+    kill_on_drop: bool,
+}
+
+impl std::ops::Deref for ManagedChild {
+    type Target = Child;
+
+    fn deref(&self) -> &Child {
+        self.child.as_ref().unwrap()
+    }
+}
+
+impl std::ops::DerefMut for ManagedChild {
+    fn deref_mut(&mut self) -> &mut Child {
+        self.child.as_mut().unwrap()
+    }
+}
+
+impl Drop for ManagedChild {
+    fn drop(&mut self) {
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+        if !matches!(child.try_wait(), Ok(None)) {
+            return;
+        }
+        if self.kill_on_drop {
+            let _ = child.start_kill();
+        }
+        ORPHANS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_what_ffmpeg_needs() {
+        let inherited = [
+            ("PATH", "/usr/bin"),
+            ("LD_PRELOAD", "/opt/game/overlay.so"),
+            ("FFREPORT", "file=ffmpeg.log"),
+            ("SystemRoot", "C:\\Windows"),
+        ]
+        .map(|(name, value)| (OsString::from(name), OsString::from(value)));
+
+        let kept = kept_environment(inherited)
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(kept, ["PATH", "SystemRoot"]);
+    }
+}
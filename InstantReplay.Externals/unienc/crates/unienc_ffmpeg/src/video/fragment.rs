@@ -0,0 +1,140 @@
+use crate::error::{FFmpegError, Result};
+
+/// Part of a fragmented QuickTime stream.
+pub enum Segment<'a> {
+    /// `ftyp` and `moov`, needed to read any fragment.
+    Init(&'a [u8]),
+    /// A `moof` and its `mdat`, one frame with `frag_every_frame`.
+    Fragment(&'a [u8]),
+}
+
+/// Splits a fragmented QuickTime stream into the initialization segment and the fragments, like
+/// [`NaluReader`](super::nalu::NaluReader) does for H.264 byte streams.
+#[derive(Default)]
+pub struct FragmentReader {
+    current: Vec<u8>,
+    /// Length of the boxes at the start of `current` that belong to the next segment.
+    pending: usize,
+}
+
+/// Size of the top-level box at the start of `data`, if its header is complete.
+fn box_size(data: &[u8]) -> Result<Option<usize>> {
+    if data.len() < 8 {
+        return Ok(None);
+    }
+    let size = match u32::from_be_bytes(data[0..4].try_into().unwrap()) {
+        1 => {
+            if data.len() < 16 {
+                return Ok(None);
+            }
+            u64::from_be_bytes(data[8..16].try_into().unwrap()) as usize
+        }
+        // extends to the end of the stream, which a pipe never announces
+        0 => return Err(FFmpegError::Other("Unbounded MOV box in stream".into())),
+        size => size as usize,
+    };
+    if size < 8 {
+        return Err(FFmpegError::Other("Invalid MOV box size".into()));
+    }
+    Ok(Some(size))
+}
+
+impl FragmentReader {
+    pub fn push(&mut self, data: &[u8], emit: &mut impl FnMut(Segment)) -> Result<()> {
+        self.current.extend_from_slice(data);
+        while let Some(size) = box_size(&self.current[self.pending..])? {
+            let start = self.pending;
+            if self.current.len() < start + size {
+                break;
+            }
+            let segment = match &self.current[start + 4..start + 8] {
+                b"ftyp" | b"moof" => None,
+                b"moov" => Some(Segment::Init(&self.current[..start + size])),
+                b"mdat" => Some(Segment::Fragment(&self.current[..start + size])),
+                // mfra at the end and free space are not needed to read fragments
+                _ => {
+                    self.current.drain(start..start + size);
+                    continue;
+                }
+            };
+            match segment {
+                Some(segment) => {
+                    emit(segment);
+                    self.current.drain(..start + size);
+                    self.pending = 0;
+                }
+                None => self.pending += size,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn end(self, _emit: &mut impl FnMut(Segment)) -> Result<()> {
+        match self.current.is_empty() {
+            true => Ok(()),
+            false => Err(FFmpegError::Other("Truncated MOV stream".into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mov_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn splits_stream_at_any_chunk_size() {
+        let ftyp = mov_box(b"ftyp", b"qt  ");
+        let moov = mov_box(b"moov", &[1; 40]);
+        let fragments = (0..3u8)
+            .map(|i| [mov_box(b"moof", &[i; 20]), mov_box(b"mdat", &[i; 300])].concat())
+            .collect::<Vec<_>>();
+        // 64-bit size
+        let mut mfra = vec![0, 0, 0, 1];
+        mfra.extend_from_slice(b"mfra");
+        mfra.extend_from_slice(&24u64.to_be_bytes());
+        mfra.extend_from_slice(&[0; 8]);
+        let stream = [
+            ftyp.clone(),
+            moov.clone(),
+            fragments.concat(),
+            mov_box(b"free", &[]),
+            mfra,
+        ]
+        .concat();
+
+        for chunk_size in [1, 7, 64, stream.len()] {
+            let mut reader = FragmentReader::default();
+            let mut init = Vec::new();
+            let mut read = Vec::new();
+            let mut emit = |segment: Segment| match segment {
+                Segment::Init(data) => init.push(data.to_vec()),
+                Segment::Fragment(data) => read.push(data.to_vec()),
+            };
+            for chunk in stream.chunks(chunk_size) {
+                reader.push(chunk, &mut emit).unwrap();
+            }
+            reader.end(&mut emit).unwrap();
+            assert_eq!(
+                init,
+                [[ftyp.clone(), moov.clone()].concat()],
+                "{chunk_size}"
+            );
+            assert_eq!(read, fragments, "{chunk_size}");
+        }
+    }
+
+    #[test]
+    fn truncated_stream_fails_at_end() {
+        let mut reader = FragmentReader::default();
+        let moof = mov_box(b"moof", &[0; 20]);
+        reader.push(&moof[..10], &mut |_| {}).unwrap();
+        assert!(reader.end(&mut |_| {}).is_err());
+    }
+}
@@ -1,27 +1,33 @@
 use std::{
-    process::Command,
     sync::{Arc, LazyLock},
     vec,
 };
 
 use bincode::{Decode, Encode};
-use cros_codecs::codec::h264::parser::NaluType;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     process::ChildStdout,
 };
 use unienc_common::{
     EncodedData, Encoder, EncoderInput, EncoderOutput, UniencSampleKind, UnsupportedBlitData,
-    VideoEncoderOptions, VideoFrame, VideoFrameBgra32, VideoSample, buffer::SharedBuffer,
+    VideoCodec, VideoEncoderOptions, VideoFrame, VideoFrameBgra32, VideoSample,
+    buffer::SharedBuffer,
+    passthrough::h264::{
+        NAL_UNIT_TYPE_IDR, NAL_UNIT_TYPE_PPS, NAL_UNIT_TYPE_SLICE, NAL_UNIT_TYPE_SPS,
+    },
 };
 
 use crate::{
     error::{FFmpegError, Result},
     ffmpeg,
     utils::Cfr,
-    video::nalu::{NalUnit, NaluReader},
+    video::{
+        fragment::{FragmentReader, Segment},
+        nalu::{NalUnit, NaluReader},
+    },
 };
 
+mod fragment;
 mod nalu;
 
 pub struct FFmpegVideoEncoder {
@@ -47,13 +53,22 @@ pub struct FFmpegVideoEncoderOutput {
     reader_state: Option<ReaderState>,
     buffer_rx: std::sync::mpsc::Receiver<VideoEncodedData>,
     cfr: u32,
-    reader: Option<NaluReader>,
+    reader: Option<StreamReader>,
+}
+
+/// Reader of the encoded stream: raw H.264, or a fragmented QuickTime movie for the intermediate
+/// codecs, which have no raw stream format ffmpeg can write.
+enum StreamReader {
+    H264(NaluReader),
+    Mov(FragmentReader),
 }
 
+/// H.264 encoder passed to ffmpeg: the first preferred one that works on this system.
+#[cfg(feature = "encoder-probe")]
 static FFMPEG_CODEC: LazyLock<String> = LazyLock::new(|| {
     (|| -> Result<String> {
         // enumerate supported encoders
-        let codecs = Command::new(ffmpeg::FFMPEG_PATH.as_os_str())
+        let codecs = crate::process::command(ffmpeg::FFMPEG_PATH.as_os_str())
             .args(["-y", "-loglevel", "error", "-encoders"])
             .stdout(std::process::Stdio::piped())
             .spawn()?
@@ -88,8 +103,8 @@ static FFMPEG_CODEC: LazyLock<String> = LazyLock::new(|| {
         // ffmpeg -encoders returns encoders including not actually available on the system
         // so we need to verify by trying to create a simple command line
         let encoder = encoder_candidates.find(|e| {
-            println!("Testing ffmpeg H.264 encoder: {}", e);
-            let res = Command::new(ffmpeg::FFMPEG_PATH.as_os_str())
+            unienc_common::log!("Testing ffmpeg H.264 encoder: {}", e);
+            let res = crate::process::command(ffmpeg::FFMPEG_PATH.as_os_str())
                 .args([
                     "-y",
                     "-loglevel",
@@ -114,24 +129,73 @@ static FFMPEG_CODEC: LazyLock<String> = LazyLock::new(|| {
 
         let encoder = encoder.ok_or(FFmpegError::NoSuitableEncoder)?;
 
-        println!("Using H.264 encoder: {}", encoder);
+        unienc_common::log!("Using H.264 encoder: {}", encoder);
 
         Ok(encoder.to_string())
     })()
     .map_err(|e| {
-        println!("Error determining ffmpeg H.264 encoder: {}", e);
+        unienc_common::log!("Error determining ffmpeg H.264 encoder: {}", e);
         e
     })
     .unwrap_or("h264".to_string())
 });
 
+/// Lets ffmpeg pick its default H.264 encoder, without spawning it at startup to probe encoders.
+#[cfg(not(feature = "encoder-probe"))]
+static FFMPEG_CODEC: LazyLock<String> = LazyLock::new(|| "h264".to_string());
+
+/// Whether the H.264 encoder passed to ffmpeg encodes in hardware, or `None` when ffmpeg picks its
+/// default one. Probes the encoders the first time, like creating an encoder.
+pub(crate) fn hardware_accelerated() -> Option<bool> {
+    match FFMPEG_CODEC.as_str() {
+        "h264" => None,
+        "libx264" => Some(false),
+        _ => Some(true),
+    }
+}
+
 impl FFmpegVideoEncoder {
     pub fn new<V: VideoEncoderOptions>(options: &V) -> Result<Self> {
         let width = options.width();
         let height = options.height();
         let cfr = options.fps_hint();
 
-        // encode raw BGRA frames into H.264 stream
+        let cfr_arg = format!("{cfr}");
+        let bitrate = format!("{}", options.bitrate());
+        let (output_options, reader) = match options.codec() {
+            VideoCodec::H264 => (
+                vec![
+                    "-f",
+                    "h264",
+                    "-pix_fmt",
+                    "yuv420p",
+                    "-r",
+                    &cfr_arg,
+                    "-c:v",
+                    &*FFMPEG_CODEC,
+                    "-b:v",
+                    &bitrate,
+                    "-force_key_frames",
+                    "expr:gte(t,n_forced*1)",
+                ],
+                StreamReader::H264(NaluReader::default()),
+            ),
+            codec => {
+                // a fragment per frame so that frames can be buffered and muxed one by one
+                let mut output_options = vec![
+                    "-f",
+                    "mov",
+                    "-movflags",
+                    "empty_moov+frag_every_frame",
+                    "-r",
+                    &cfr_arg,
+                ];
+                output_options.extend(intermediate_codec_options(codec, options.preserve_alpha()));
+                (output_options, StreamReader::Mov(FragmentReader::default()))
+            }
+        };
+
+        // encode raw BGRA frames into H.264 stream or intermediate codec fragments
         let mut ffmpeg = ffmpeg::Builder::new()
             .use_stdin(true)
             .input([
@@ -144,23 +208,7 @@ impl FFmpegVideoEncoder {
                 "-framerate",
                 &format!("{cfr}"),
             ])
-            .build(
-                [
-                    "-f",
-                    "h264",
-                    "-pix_fmt",
-                    "yuv420p",
-                    "-r",
-                    &format!("{cfr}"),
-                    "-c:v",
-                    &*FFMPEG_CODEC,
-                    "-b:v",
-                    &format!("{}", options.bitrate()),
-                    "-force_key_frames",
-                    "expr:gte(t,n_forced*1)",
-                ],
-                ffmpeg::Destination::Stdout,
-            )?;
+            .build(output_options, ffmpeg::Destination::Stdout)?;
 
         let input = ffmpeg
             .inputs
@@ -193,12 +241,42 @@ impl FFmpegVideoEncoder {
                 }),
                 buffer_rx,
                 cfr,
-                reader: Some(NaluReader::default()),
+                reader: Some(reader),
             },
         })
     }
 }
 
+/// Encoder options for ProRes 422 HQ or 4444, or DNxHR HQ. The bitrate follows from the profile.
+fn intermediate_codec_options(codec: VideoCodec, preserve_alpha: bool) -> [&'static str; 6] {
+    match (codec, preserve_alpha) {
+        (VideoCodec::DnxHr, _) => [
+            "-c:v",
+            "dnxhd",
+            "-profile:v",
+            "dnxhr_hq",
+            "-pix_fmt",
+            "yuv422p",
+        ],
+        (_, false) => [
+            "-c:v",
+            "prores_ks",
+            "-profile:v",
+            "3",
+            "-pix_fmt",
+            "yuv422p10le",
+        ],
+        (_, true) => [
+            "-c:v",
+            "prores_ks",
+            "-profile:v",
+            "4",
+            "-pix_fmt",
+            "yuva444p10le",
+        ],
+    }
+}
+
 impl Encoder for FFmpegVideoEncoder {
     type InputType = FFmpegVideoEncoderInput;
     type OutputType = FFmpegVideoEncoderOutput;
@@ -217,8 +295,11 @@ impl EncoderInput for FFmpegVideoEncoderInput {
         };
 
         let timestamp = data.timestamp;
-        let frame = if frame.width != self.width || frame.height != self.height {
-            // resize (crop or trim)
+        let frame = if frame.width != self.width
+            || frame.height != self.height
+            || frame.stride != frame.width * 4
+        {
+            // resize (crop or trim) and repack rows
             let bgra = frame.buffer.data();
             let mut resized = vec![0u8; (self.width * self.height * 4) as usize];
 
@@ -226,7 +307,7 @@ impl EncoderInput for FFmpegVideoEncoderInput {
             let h = u32::min(self.height, frame.height);
 
             for y in 0..h {
-                let src_start = (y * frame.width * 4) as usize;
+                let src_start = (y * frame.stride) as usize;
                 let src_end = src_start + (w * 4) as usize;
                 let dst_start = (y * self.width * 4) as usize;
                 let dst_end = dst_start + (w * 4) as usize;
@@ -237,6 +318,7 @@ impl EncoderInput for FFmpegVideoEncoderInput {
             VideoFrameBgra32 {
                 width: self.width,
                 height: self.height,
+                stride: self.width * 4,
                 buffer: SharedBuffer::new_unmanaged(resized),
             }
         } else {
@@ -292,15 +374,15 @@ impl EncoderOutput for FFmpegVideoEncoderOutput {
 
             fn create_emit<'a>(state: &'a mut ReaderState, cfr: u32) -> impl FnMut(&NalUnit) + 'a {
                 move |nalu: &NalUnit| {
-                    match nalu.nalu.header.type_ {
+                    match nalu.nal_unit_type {
                         // parameter set used by decoder
-                        NaluType::Sps | NaluType::Pps => {
+                        NAL_UNIT_TYPE_SPS | NAL_UNIT_TYPE_PPS => {
                             _ = state
                                 .buffer_tx
                                 .send(VideoEncodedData::ParameterSet(nalu.data.to_vec()));
                         }
                         // interpolated frame
-                        NaluType::Slice => {
+                        NAL_UNIT_TYPE_SLICE => {
                             let frame_index = state.frame_index;
                             state.frame_index += 1;
                             _ = state.buffer_tx.send(VideoEncodedData::Slice {
@@ -310,7 +392,7 @@ impl EncoderOutput for FFmpegVideoEncoderOutput {
                             });
                         }
                         // key frame
-                        NaluType::SliceIdr => {
+                        NAL_UNIT_TYPE_IDR => {
                             let frame_index = state.frame_index;
                             state.frame_index += 1;
                             _ = state.buffer_tx.send(VideoEncodedData::Slice {
@@ -320,9 +402,30 @@ impl EncoderOutput for FFmpegVideoEncoderOutput {
                             });
                         }
                         _ => {
-                            println!("Ignoring NALU type: {:?}", nalu.nalu.header.type_);
+                            unienc_common::log!("Ignoring NALU type: {}", nalu.nal_unit_type);
+                        }
+                    };
+                }
+            }
+
+            fn create_segment_emit<'a>(
+                state: &'a mut ReaderState,
+                cfr: u32,
+            ) -> impl FnMut(Segment) + 'a {
+                move |segment: Segment| {
+                    let data = match segment {
+                        Segment::Init(data) => VideoEncodedData::InitSegment(data.to_vec()),
+                        // every frame of the intermediate codecs is a keyframe
+                        Segment::Fragment(data) => {
+                            let frame_index = state.frame_index;
+                            state.frame_index += 1;
+                            VideoEncodedData::Fragment {
+                                payload: data.to_vec(),
+                                timestamp: frame_index as f64 / cfr as f64,
+                            }
                         }
                     };
+                    _ = state.buffer_tx.send(data);
                 }
             }
 
@@ -335,7 +438,14 @@ impl EncoderOutput for FFmpegVideoEncoderOutput {
                 let Some(reader) = self.reader.take() else {
                     unreachable!();
                 };
-                reader.end(&mut create_emit(&mut state, self.cfr))?;
+                match reader {
+                    StreamReader::H264(reader) => {
+                        reader.end(&mut create_emit(&mut state, self.cfr))?
+                    }
+                    StreamReader::Mov(reader) => {
+                        reader.end(&mut create_segment_emit(&mut state, self.cfr))?
+                    }
+                }
             } else {
                 let Some(state) = &mut self.reader_state else {
                     unreachable!();
@@ -346,7 +456,14 @@ impl EncoderOutput for FFmpegVideoEncoderOutput {
                 };
 
                 let buf = &buf[..read];
-                reader.push(buf, &mut create_emit(state, self.cfr))?;
+                match reader {
+                    StreamReader::H264(reader) => {
+                        reader.push(buf, &mut create_emit(state, self.cfr))?
+                    }
+                    StreamReader::Mov(reader) => {
+                        reader.push(buf, &mut create_segment_emit(state, self.cfr))?
+                    }
+                }
                 continue;
             }
         }
@@ -361,23 +478,35 @@ pub enum VideoEncodedData {
         timestamp: f64,
         is_idr: bool,
     },
+    /// `ftyp` and `moov` of the fragmented QuickTime stream of an intermediate codec.
+    InitSegment(Vec<u8>),
+    /// `moof` and `mdat` of a frame of an intermediate codec.
+    Fragment {
+        payload: Vec<u8>,
+        timestamp: f64,
+    },
 }
 
 impl EncodedData for VideoEncodedData {
     fn timestamp(&self) -> f64 {
         match self {
-            VideoEncodedData::ParameterSet(_) => 0.0,
-            VideoEncodedData::Slice { timestamp, .. } => *timestamp,
+            VideoEncodedData::ParameterSet(_) | VideoEncodedData::InitSegment(_) => 0.0,
+            VideoEncodedData::Slice { timestamp, .. }
+            | VideoEncodedData::Fragment { timestamp, .. } => *timestamp,
         }
     }
 
     fn set_timestamp(&mut self, value: f64) {
         match self {
-            VideoEncodedData::ParameterSet(_items) => {}
+            VideoEncodedData::ParameterSet(_items) | VideoEncodedData::InitSegment(_items) => {}
             VideoEncodedData::Slice {
                 payload: _,
                 timestamp,
                 is_idr: _,
+            }
+            | VideoEncodedData::Fragment {
+                payload: _,
+                timestamp,
             } => {
                 *timestamp = value;
             }
@@ -386,12 +515,15 @@ impl EncodedData for VideoEncodedData {
 
     fn kind(&self) -> UniencSampleKind {
         match self {
-            VideoEncodedData::ParameterSet(_items) => UniencSampleKind::Metadata,
+            VideoEncodedData::ParameterSet(_items) | VideoEncodedData::InitSegment(_items) => {
+                UniencSampleKind::Metadata
+            }
             VideoEncodedData::Slice {
                 payload: _,
                 timestamp: _,
                 is_idr: true,
-            } => UniencSampleKind::Key,
+            }
+            | VideoEncodedData::Fragment { .. } => UniencSampleKind::Key,
             VideoEncodedData::Slice {
                 payload: _,
                 timestamp: _,
@@ -399,4 +531,14 @@ impl EncodedData for VideoEncodedData {
             } => UniencSampleKind::Interpolated,
         }
     }
+
+    fn size(&self) -> usize {
+        match self {
+            VideoEncodedData::ParameterSet(items) | VideoEncodedData::InitSegment(items) => {
+                items.len()
+            }
+            VideoEncodedData::Slice { payload, .. }
+            | VideoEncodedData::Fragment { payload, .. } => payload.len(),
+        }
+    }
 }
@@ -1,33 +1,32 @@
-use std::io::Cursor;
-
-use cros_codecs::codec::h264::parser::Nalu;
+use unienc_common::passthrough::h264::{NalFormat, find_start_code, nal_unit_type};
 
 use crate::error::{FFmpegError, Result};
 
+/// Splits H.264 read in chunks of any size into NAL units, in Annex-B or with length prefixes
+/// (AVCC), whichever the first chunk starts with.
 #[derive(Default)]
 pub struct NaluReader {
+    format: Option<NalFormat>,
     current: Vec<u8>,
 }
 
-// finds start code of NAL unit 0x000001 and returns its position
-// the first value is the start position of the NAL unit including preceding zero bytes and the second is the position the content starts at
-fn get_start_position(data: &[u8]) -> Option<(usize, usize)> {
-    data.windows(3)
-        .position(|window| window == [0x00, 0x00, 0x01])
-        .map(|pos| {
-            let mut start = pos;
-            while start > 0 && data[start - 1] == 00 {
-                start -= 1;
-            }
-            (start, pos + 3)
-        })
-}
-
 pub struct NalUnit<'a> {
-    pub nalu: Nalu<'a>,
+    pub nal_unit_type: u8,
+    /// The NAL unit with its start code or length prefix.
     pub data: &'a [u8],
 }
 
+impl<'a> NalUnit<'a> {
+    fn new(data: &'a [u8], content_start: usize) -> Result<Self> {
+        let nal_unit_type = nal_unit_type(&data[content_start..])
+            .ok_or_else(|| FFmpegError::Other("Invalid NALU".into()))?;
+        Ok(Self {
+            nal_unit_type,
+            data,
+        })
+    }
+}
+
 impl NaluReader {
     pub fn push(&mut self, data: &[u8], emit: &mut impl FnMut(&NalUnit)) -> Result<()> {
         self.current.extend_from_slice(data);
@@ -35,20 +34,27 @@ impl NaluReader {
     }
 
     fn drain(&mut self, emit: &mut impl FnMut(&NalUnit)) -> Result<()> {
-        if let Some((start_pos, mut nalu_pos)) = get_start_position(&self.current) {
+        // a length prefix is at least as long as the longest start code
+        if self.format.is_none() && self.current.len() >= 4 {
+            self.format = Some(NalFormat::detect(&self.current));
+        }
+        match self.format {
+            Some(NalFormat::AnnexB) => self.drain_annexb(emit),
+            Some(NalFormat::LengthPrefixed { length_size }) => {
+                self.drain_length_prefixed(length_size, emit)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn drain_annexb(&mut self, emit: &mut impl FnMut(&NalUnit)) -> Result<()> {
+        if let Some((start_pos, mut nalu_pos)) = find_start_code(&self.current) {
             if start_pos != 0 {
                 return Err(FFmpegError::Other("Invalid start code".into()));
             }
 
-            while let Some((next, next_nalu_pos)) = get_start_position(&self.current[nalu_pos..]) {
-                let Ok(nalu) = Nalu::next(&mut Cursor::new(&self.current)) else {
-                    return Err(FFmpegError::Other("Invalid NALU".into()));
-                };
-
-                let nal_unit = NalUnit {
-                    nalu,
-                    data: &self.current[..nalu_pos + next],
-                };
+            while let Some((next, next_nalu_pos)) = find_start_code(&self.current[nalu_pos..]) {
+                let nal_unit = NalUnit::new(&self.current[..nalu_pos + next], nalu_pos)?;
 
                 emit(&nal_unit);
 
@@ -59,19 +65,69 @@ impl NaluReader {
         Ok(())
     }
 
+    fn drain_length_prefixed(
+        &mut self,
+        length_size: usize,
+        emit: &mut impl FnMut(&NalUnit),
+    ) -> Result<()> {
+        let mut start = 0;
+        while let Some(prefix) = self.current.get(start..start + length_size) {
+            let length = prefix
+                .iter()
+                .fold(0usize, |length, byte| length << 8 | *byte as usize);
+            let end = start + length_size + length;
+            if end > self.current.len() {
+                break;
+            }
+            emit(&NalUnit::new(&self.current[start..end], length_size)?);
+            start = end;
+        }
+        self.current.drain(..start);
+        Ok(())
+    }
+
     pub fn end(mut self, emit: &mut impl FnMut(&NalUnit)) -> Result<()> {
         self.drain(emit)?;
-        let mut cursor = Cursor::new(self.current.as_slice());
-        match Nalu::next(&mut cursor) {
-            Ok(nalu) => {
-                let nal_unit = NalUnit {
-                    nalu,
-                    data: &self.current,
-                };
-                emit(&nal_unit);
+        if self.current.is_empty() {
+            return Ok(());
+        }
+        match self.format {
+            Some(NalFormat::AnnexB) => {
+                let (_, nalu_pos) = find_start_code(&self.current)
+                    .ok_or_else(|| FFmpegError::Other("Invalid start code".into()))?;
+                emit(&NalUnit::new(&self.current, nalu_pos)?);
                 Ok(())
             }
-            Err(err) => Err(FFmpegError::Other(err.to_string())),
+            _ => Err(FFmpegError::Other("Truncated NALU".into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_both_formats_across_chunks() {
+        let annexb = [
+            0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x68, 0xce, 0, 0, 0, 1, 0x65, 0x88,
+        ];
+        let length_prefixed = [
+            0, 0, 0, 2, 0x67, 0x42, 0, 0, 0, 2, 0x68, 0xce, 0, 0, 0, 2, 0x65, 0x88,
+        ];
+
+        for data in [&annexb[..], &length_prefixed[..]] {
+            let mut units = Vec::new();
+            let mut emit = |unit: &NalUnit| units.push((unit.nal_unit_type, unit.data.to_vec()));
+            let mut reader = NaluReader::default();
+            for chunk in data.chunks(3) {
+                reader.push(chunk, &mut emit).unwrap();
+            }
+            reader.end(&mut emit).unwrap();
+
+            let (types, units): (Vec<_>, Vec<_>) = units.into_iter().unzip();
+            assert_eq!(types, [7, 8, 5]);
+            assert_eq!(units.concat(), data);
         }
     }
 }
@@ -26,7 +26,7 @@ pub struct WebCodecsAudioEncoderOutput {
 #[derive(Encode, Decode, Debug)]
 pub struct AudioEncodedData {
     pub(crate) data: Vec<u8>,
-    timestamp: f64,
+    pub(crate) timestamp: f64,
 }
 
 impl<R: Runtime> WebCodecsAudioEncoder<R> {
@@ -76,7 +76,7 @@ impl<R: Runtime + 'static> EncoderInput for WebCodecsAudioEncoderInput<R> {
                             timestamp,
                         };
                         if let Err(err) = tx.try_send(encoded_data) {
-                            println!(
+                            unienc_common::log!(
                                 "WebCodecsAudioEncoder: Failed to send encoded data: {}",
                                 err
                             );
@@ -142,4 +142,8 @@ impl EncodedData for AudioEncodedData {
     fn kind(&self) -> UniencSampleKind {
         UniencSampleKind::Key
     }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
 }
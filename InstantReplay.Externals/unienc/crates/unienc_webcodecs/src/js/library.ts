@@ -144,6 +144,7 @@ window["unienc_webcodecs"] = {
         {
             width: number,
             height: number,
+            stride: number,
             timestamp: number,
             isKey: boolean
         },
@@ -193,7 +194,7 @@ window["unienc_webcodecs"] = {
                 layout: [
                     {
                         offset: 0,
-                        stride: options.width * 4  // BGRA = 4 bytes per pixel
+                        stride: options.stride
                     }
                 ]
             };
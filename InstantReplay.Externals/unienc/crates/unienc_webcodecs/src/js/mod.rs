@@ -35,10 +35,11 @@ impl VideoEncoderHandle {
         data: &[u8],
         width: u32,
         height: u32,
+        stride: u32,
         timestamp: f64,
         is_key: bool,
     ) -> Result<(), JavaScriptError> {
-        LIBRARY.push_video_frame(self.id, data, width, height, timestamp, is_key)
+        LIBRARY.push_video_frame(self.id, data, width, height, stride, timestamp, is_key)
     }
 
     pub async fn flush(&self) -> Result<(), JavaScriptError> {
@@ -97,6 +98,7 @@ impl Drop for AudioEncoderHandle {
     }
 }
 
+#[cfg(feature = "download")]
 pub fn make_download(parts: &[Vec<u8>], mime: &str, filename: &str) {
     LIBRARY.make_download(parts, mime, filename);
 }
@@ -241,12 +243,14 @@ impl Library {
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn push_video_frame(
         &self,
         encoder_index: i32,
         data: &[u8],
         width: u32,
         height: u32,
+        stride: u32,
         timestamp: f64,
         is_key: bool,
     ) -> Result<(), JavaScriptError> {
@@ -257,10 +261,11 @@ impl Library {
             const dataLength = {data_length};
             const width = {width};
             const height = {height};
+            const stride = {stride};
             const timestamp = {timestamp};
             const isKey = {is_key};
             const dataArray = Module.HEAPU8.subarray(dataPtr, dataPtr + dataLength);
-            window.unienc_webcodecs.video.push(encoderIndex, dataArray, {{width, height, timestamp, isKey}});
+            window.unienc_webcodecs.video.push(encoderIndex, dataArray, {{width, height, stride, timestamp, isKey}});
             ",
             data_ptr = data.as_ptr() as usize,
             data_length = data.len(),
@@ -386,6 +391,7 @@ impl Library {
         );
         self.run_script(&script)
     }
+    #[cfg(feature = "download")]
     fn make_download(
         &self,
         parts: &[Vec<u8>],
@@ -422,6 +428,7 @@ impl Library {
     }
 }
 
+#[cfg(feature = "download")]
 #[repr(C)]
 struct Part {
     ptr: *const u8,
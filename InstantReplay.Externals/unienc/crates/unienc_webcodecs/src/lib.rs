@@ -2,22 +2,28 @@ mod audio;
 mod emscripten;
 mod js;
 mod mux;
+pub mod passthrough;
 mod video;
 
 use crate::audio::WebCodecsAudioEncoder;
 use crate::mux::WebCodecsMuxer;
+use crate::passthrough::{WebCodecsAacPacketizer, WebCodecsH264Packetizer};
 use crate::video::WebCodecsVideoEncoder;
 use std::path::Path;
-use unienc_common::{EncodingSystem, UnsupportedBlitData};
+use unienc_common::{
+    AudioEncoderOptions, DownmixedAudioEncoder, DownmixedAudioOptions, EncodingSystem, PaddedMuxer,
+    PaddedVideoEncoder, PaddedVideoOptions, StillImageFormat, TrackedRuntime, UnsupportedBlitData,
+    UnsupportedDecoder, VideoEncoderOptions, still_image::UnsupportedStillImageCapture,
+};
 
 pub struct WebCodecsEncodingSystem<
     V: unienc_common::VideoEncoderOptions,
     A: unienc_common::AudioEncoderOptions,
     R: unienc_common::Runtime,
 > {
-    video_options: V,
-    audio_options: A,
-    runtime: R,
+    video_options: PaddedVideoOptions<V>,
+    audio_options: DownmixedAudioOptions<A>,
+    runtime: TrackedRuntime<R>,
 }
 
 impl<
@@ -28,30 +34,77 @@ impl<
 {
     type VideoEncoderOptionsType = V;
     type AudioEncoderOptionsType = A;
-    type VideoEncoderType = WebCodecsVideoEncoder<R>;
-    type AudioEncoderType = WebCodecsAudioEncoder<R>;
-    type MuxerType = WebCodecsMuxer;
+    type VideoEncoderType = PaddedVideoEncoder<WebCodecsVideoEncoder<TrackedRuntime<R>>>;
+    type AudioEncoderType = DownmixedAudioEncoder<WebCodecsAudioEncoder<TrackedRuntime<R>>>;
+    type MuxerType = PaddedMuxer<WebCodecsMuxer>;
     type BlitSourceType = UnsupportedBlitData;
     type RuntimeType = R;
+    type H264PacketizerType = WebCodecsH264Packetizer;
+    type AacPacketizerType = WebCodecsAacPacketizer;
+    // the muxer output is handed to the browser as a download, so there is no file to decode
+    type DecoderType = UnsupportedDecoder;
+    type StillImageCaptureType = UnsupportedStillImageCapture<UnsupportedBlitData>;
 
     fn new(video_options: &V, audio_options: &A, runtime: R) -> Self {
         Self {
-            video_options: *video_options,
-            audio_options: *audio_options,
-            runtime,
+            video_options: PaddedVideoOptions::new(*video_options),
+            audio_options: DownmixedAudioOptions::stereo(*audio_options),
+            runtime: TrackedRuntime::new(runtime),
         }
     }
 
     fn new_video_encoder(&self) -> unienc_common::Result<Self::VideoEncoderType> {
-        WebCodecsVideoEncoder::new(&self.video_options, &self.runtime).map_err(|e| e.into())
+        let codec = self.video_options.codec();
+        if !self.is_codec_supported(codec) {
+            return Err(unienc_common::CommonError::CodecNotSupported(codec));
+        }
+        // the MP4 muxer cannot carry the alpha side data of VP9, the only codec browsers encode it with
+        if self.video_options.preserve_alpha() {
+            return Err(unienc_common::CommonError::AlphaNotSupported);
+        }
+        WebCodecsVideoEncoder::new(&self.video_options, &self.runtime)
+            .map_err(|e| e.into())
+            .map(PaddedVideoEncoder::new)
     }
 
     fn new_audio_encoder(&self) -> unienc_common::Result<Self::AudioEncoderType> {
-        WebCodecsAudioEncoder::new(&self.audio_options, &self.runtime).map_err(|e| e.into())
+        WebCodecsAudioEncoder::new(&self.audio_options, &self.runtime)
+            .map_err(|e| e.into())
+            .map(|encoder| DownmixedAudioEncoder::new(encoder, &self.audio_options))
     }
 
     fn new_muxer(&self, output_path: &Path) -> unienc_common::Result<Self::MuxerType> {
         WebCodecsMuxer::new(output_path, &self.video_options, &self.audio_options)
             .map_err(|e| e.into())
+            .map(|muxer| PaddedMuxer::new(muxer, output_path, &self.video_options))
+    }
+
+    fn new_h264_packetizer(&self) -> unienc_common::Result<Self::H264PacketizerType> {
+        Ok(WebCodecsH264Packetizer)
+    }
+
+    fn new_aac_packetizer(&self) -> unienc_common::Result<Self::AacPacketizerType> {
+        Ok(WebCodecsAacPacketizer {
+            sample_rate: self.audio_options.sample_rate(),
+        })
+    }
+
+    fn new_decoder(&self, _input_path: &Path) -> unienc_common::Result<Self::DecoderType> {
+        Err(unienc_common::CommonError::DecodeNotSupported)
+    }
+
+    fn new_still_image_capture(
+        &self,
+        _format: StillImageFormat,
+    ) -> unienc_common::Result<Self::StillImageCaptureType> {
+        Err(unienc_common::CommonError::BlitNotSupported)
+    }
+
+    fn shutdown(self) -> impl Future<Output = unienc_common::Result<()>> + Send {
+        let idle = self.runtime.idle();
+        async move {
+            idle.await;
+            Ok(())
+        }
     }
 }
@@ -1,4 +1,5 @@
 use crate::audio::AudioEncodedData;
+#[cfg(feature = "download")]
 use crate::js::make_download;
 use crate::video::VideoEncodedData;
 use futures::channel::oneshot;
@@ -6,9 +7,9 @@ use futures::join;
 use muxide::api::{AacProfile, AudioCodec, MuxerBuilder, VideoCodec};
 use std::io::Write;
 use std::sync::{Arc, Mutex};
-use unienc_common::{
-    CommonError, CompletionHandle, EncodedData, Muxer, MuxerInput, OptionExt, ResultExt,
-};
+#[cfg(feature = "download")]
+use unienc_common::OptionExt;
+use unienc_common::{CommonError, CompletionHandle, EncodedData, Muxer, MuxerInput, ResultExt};
 
 #[derive(Clone)]
 struct FragmentWrite {
@@ -22,9 +23,9 @@ impl FragmentWrite {
         }
     }
 
-    fn with_ref(&self, f: impl FnOnce(&[Vec<u8>])) {
+    fn with_ref<R>(&self, f: impl FnOnce(&[Vec<u8>]) -> R) -> R {
         let inner_guard = self.inner.lock().unwrap();
-        f(&inner_guard);
+        f(&inner_guard)
     }
 }
 
@@ -54,7 +55,12 @@ pub struct WebCodecsAudioInput {
     finish_tx: Option<oneshot::Sender<()>>,
 }
 pub struct WebCodecsCompletionHandle {
+    /// Offered to the browser as a download.
+    #[cfg(feature = "download")]
     filename: String,
+    /// Written on the Emscripten file system.
+    #[cfg(not(feature = "download"))]
+    output_path: std::path::PathBuf,
     writer: FragmentWrite,
     muxer: Arc<Mutex<Option<muxide::api::Muxer<FragmentWrite>>>>,
     video_finish_rx: Option<oneshot::Receiver<()>>,
@@ -68,6 +74,7 @@ impl WebCodecsMuxer {
         audio_options: &A,
     ) -> unienc_common::Result<Self> {
         let writer = FragmentWrite::new();
+        #[cfg(feature = "download")]
         let filename = output_path
             .file_name()
             .context("Output path has no filename")?
@@ -105,7 +112,10 @@ impl WebCodecsMuxer {
                 finish_tx: audio_finish_tx.into(),
             },
             completion: WebCodecsCompletionHandle {
+                #[cfg(feature = "download")]
                 filename,
+                #[cfg(not(feature = "download"))]
+                output_path: output_path.to_path_buf(),
                 writer,
                 muxer,
                 video_finish_rx: video_finish_rx.into(),
@@ -185,8 +195,13 @@ impl CompletionHandle for WebCodecsCompletionHandle {
         let muxer = muxer_guard.take().unwrap();
         muxer.finish().context("Failed to finish audio")?;
 
+        #[cfg(feature = "download")]
         self.writer
             .with_ref(|fragments| make_download(fragments, "video/mp4", &self.filename));
+        #[cfg(not(feature = "download"))]
+        self.writer
+            .with_ref(|fragments| std::fs::write(&self.output_path, fragments.concat()))
+            .context("Failed to write output file")?;
 
         Ok(())
     }
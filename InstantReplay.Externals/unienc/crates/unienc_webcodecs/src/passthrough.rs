@@ -0,0 +1,43 @@
+use unienc_common::passthrough::h264::AccessUnit;
+use unienc_common::{AacPacketizer, H264Packetizer};
+
+use crate::{audio::AudioEncodedData, video::VideoEncodedData};
+
+/// muxide takes Annex-B access units as-is, so this only validates the input and detects key frames.
+pub struct WebCodecsH264Packetizer;
+
+impl H264Packetizer for WebCodecsH264Packetizer {
+    type Data = VideoEncodedData;
+
+    fn packetize(
+        &mut self,
+        access_unit: &[u8],
+        timestamp: f64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        let is_key = AccessUnit::parse(access_unit)?.is_idr;
+        Ok(vec![VideoEncodedData {
+            data: access_unit.to_vec(),
+            timestamp,
+            is_key,
+        }])
+    }
+}
+
+pub struct WebCodecsAacPacketizer {
+    pub(crate) sample_rate: u32,
+}
+
+impl AacPacketizer for WebCodecsAacPacketizer {
+    type Data = AudioEncodedData;
+
+    fn packetize(
+        &mut self,
+        frame: &[u8],
+        timestamp_in_samples: u64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        Ok(vec![AudioEncodedData {
+            data: frame.to_vec(),
+            timestamp: timestamp_in_samples as f64 / self.sample_rate as f64,
+        }])
+    }
+}
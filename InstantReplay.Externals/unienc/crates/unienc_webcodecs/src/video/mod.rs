@@ -20,6 +20,7 @@ pub struct WebCodecsVideoEncoderInput<R: Runtime> {
     fps_hint: f64,
     tx: mpsc::Sender<VideoEncodedData>,
     prev_key_timestamp: Option<f64>,
+    keyframe_requested: bool,
     runtime: R,
 }
 
@@ -30,7 +31,7 @@ pub struct WebCodecsVideoEncoderOutput {
 #[derive(Encode, Decode, Debug)]
 pub struct VideoEncodedData {
     pub(crate) data: Vec<u8>,
-    timestamp: f64,
+    pub(crate) timestamp: f64,
     pub(crate) is_key: bool,
 }
 
@@ -49,6 +50,7 @@ impl<R: Runtime> WebCodecsVideoEncoder<R> {
                 encoder_handle: None,
                 tx,
                 prev_key_timestamp: None,
+                keyframe_requested: false,
                 runtime: runtime.clone(),
             },
             output: WebCodecsVideoEncoderOutput { rx },
@@ -89,7 +91,7 @@ impl<R: Runtime + 'static> EncoderInput for WebCodecsVideoEncoderInput<R> {
                             is_key,
                         };
                         if let Err(err) = tx.try_send(encoded_data) {
-                            println!(
+                            unienc_common::log!(
                                 "WebCodecsVideoEncoder: Failed to send encoded data: {}",
                                 err
                             );
@@ -108,7 +110,8 @@ impl<R: Runtime + 'static> EncoderInput for WebCodecsVideoEncoderInput<R> {
             Some(prev) => data.timestamp - prev,
             None => f64::INFINITY,
         };
-        if since_prev_key >= 1.0 {
+        let is_key = since_prev_key >= 1.0 || std::mem::take(&mut self.keyframe_requested);
+        if is_key {
             self.prev_key_timestamp = Some(data.timestamp);
         }
         encoder_handle
@@ -116,12 +119,18 @@ impl<R: Runtime + 'static> EncoderInput for WebCodecsVideoEncoderInput<R> {
                 pixels,
                 frame.width,
                 frame.height,
+                frame.stride,
                 data.timestamp,
-                since_prev_key >= 1.0,
+                is_key,
             )
             .context("Failed to push video frame to WebCodecs EncoderHandle")?;
         Ok(())
     }
+
+    fn request_keyframe(&mut self) -> bool {
+        self.keyframe_requested = true;
+        true
+    }
 }
 
 impl<R: Runtime> Drop for WebCodecsVideoEncoderInput<R> {
@@ -164,4 +173,8 @@ impl EncodedData for VideoEncodedData {
             unienc_common::UniencSampleKind::Interpolated
         }
     }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
 }
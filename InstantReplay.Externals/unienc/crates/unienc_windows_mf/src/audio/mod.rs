@@ -3,12 +3,13 @@ use bincode::{Decode, Encode};
 use tokio::sync::mpsc;
 use unienc_common::{
     AudioEncoderOptions, AudioSample, EncodedData, Encoder, EncoderInput, EncoderOutput, Runtime,
-    UniencSampleKind,
+    Timebase, UniencSampleKind,
 };
 use windows::Win32::Media::MediaFoundation::*;
 
 use crate::WindowsError;
 use crate::common::*;
+use crate::loopback::LoopbackCapture;
 use crate::mft::Transform;
 
 pub struct MediaFoundationAudioEncoder {
@@ -53,6 +54,7 @@ impl MediaFoundationAudioEncoder {
             },
             input_type,
             output_type,
+            &[],
             runtime,
         )?;
 
@@ -76,6 +78,7 @@ impl Encoder for MediaFoundationAudioEncoder {
                 transform: self.transform,
                 sample_rate: self.sample_rate,
                 channels: self.channels,
+                loopback: None,
             },
             AudioEncoderOutputImpl {
                 receiver: self.output_rx,
@@ -89,6 +92,19 @@ pub struct AudioEncoderInputImpl {
     transform: Transform,
     sample_rate: u32,
     channels: u32,
+    loopback: Option<LoopbackCapture>,
+}
+
+impl AudioEncoderInputImpl {
+    /// Starts or stops mixing system audio (WASAPI loopback) into the pushed samples.
+    pub fn set_system_audio_capture(&mut self, enabled: bool) -> Result<()> {
+        self.loopback = if enabled {
+            Some(LoopbackCapture::new(self.sample_rate, self.channels)?)
+        } else {
+            None
+        };
+        Ok(())
+    }
 }
 
 pub struct AudioEncoderOutputImpl {
@@ -99,7 +115,11 @@ pub struct AudioEncoderOutputImpl {
 impl EncoderInput for AudioEncoderInputImpl {
     type Data = AudioSample;
 
-    async fn push(&mut self, data: Self::Data) -> unienc_common::Result<()> {
+    async fn push(&mut self, mut data: Self::Data) -> unienc_common::Result<()> {
+        if let Some(loopback) = &self.loopback {
+            loopback.mix_into(&mut data.data);
+        }
+
         let sample = UnsafeSend(unsafe { MFCreateSample().map_err(WindowsError::from)? });
 
         // BGRA to NV12
@@ -136,17 +156,17 @@ impl EncoderInput for AudioEncoderInputImpl {
         unsafe {
             sample
                 .SetSampleTime(
-                    (data.timestamp_in_samples as f64 / self.sample_rate as f64 * 10_000_000_f64)
-                        as i64,
+                    Timebase::HUNDRED_NANOSECONDS
+                        .from_samples(data.timestamp_in_samples, self.sample_rate),
                 )
                 .map_err(WindowsError::from)?
         };
         unsafe {
             sample
-                .SetSampleDuration(
-                    ((data.data.len() / self.channels as usize) as f64 / self.sample_rate as f64
-                        * 10_000_000_f64) as i64,
-                )
+                .SetSampleDuration(Timebase::HUNDRED_NANOSECONDS.from_samples(
+                    (data.data.len() / self.channels as usize) as u64,
+                    self.sample_rate,
+                ))
                 .map_err(WindowsError::from)?
         };
         Ok(self.transform.push(sample).await?)
@@ -177,7 +197,7 @@ impl EncodedData for AudioEncodedData {
     fn timestamp(&self) -> f64 {
         match &self.payload {
             Payload::Sample(sample) => {
-                (unsafe { sample.GetSampleTime().unwrap() } as f64) / 10_000_000_f64
+                Timebase::HUNDRED_NANOSECONDS.to_seconds(unsafe { sample.GetSampleTime().unwrap() })
             }
             Payload::Format(_media_type) => 0f64,
         }
@@ -187,7 +207,7 @@ impl EncodedData for AudioEncodedData {
         match &self.payload {
             Payload::Sample(sample) => unsafe {
                 sample
-                    .SetSampleTime((timestamp * 10_000_000_f64) as i64)
+                    .SetSampleTime(Timebase::HUNDRED_NANOSECONDS.to_units(timestamp))
                     .unwrap()
             },
             Payload::Format(_media_type) => {}
@@ -200,4 +220,11 @@ impl EncodedData for AudioEncodedData {
             Payload::Format(_media_type) => UniencSampleKind::Metadata,
         }
     }
+
+    fn size(&self) -> usize {
+        match &self.payload {
+            Payload::Sample(sample) => unsafe { sample.GetTotalLength() }.unwrap_or(0) as usize,
+            Payload::Format(_media_type) => 0,
+        }
+    }
 }
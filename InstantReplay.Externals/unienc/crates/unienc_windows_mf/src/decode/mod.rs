@@ -0,0 +1,196 @@
+use std::mem::ManuallyDrop;
+use std::path::{Path, PathBuf};
+
+use tokio::sync::mpsc;
+use unienc_common::{
+    DecodedVideoFrame, Decoder, PreciseSeek, SeekMode, Timebase, VideoFrameBgra32,
+    buffer::SharedBuffer,
+};
+use windows::Win32::Media::MediaFoundation::*;
+use windows::Win32::System::Com::StructuredStorage::{
+    PROPVARIANT, PROPVARIANT_0, PROPVARIANT_0_0, PROPVARIANT_0_0_0,
+};
+use windows::Win32::System::Com::{COINIT_MULTITHREADED, CoInitializeEx, CoUninitialize};
+use windows::Win32::System::Variant::VT_I8;
+use windows::core::Interface;
+use windows_core::HSTRING;
+
+use crate::common::UnsafeSend;
+use crate::error::{Result, WindowsError};
+use crate::startup::MediaFoundation;
+
+const VIDEO_STREAM: u32 = MF_SOURCE_READER_FIRST_VIDEO_STREAM.0 as u32;
+
+/// Decodes the first video track with IMFSourceReader, converting to RGB32 with the built-in video
+/// processor.
+pub struct MediaFoundationDecoder {
+    input_path: PathBuf,
+    rx: mpsc::Receiver<Result<DecodedVideoFrame>>,
+}
+
+impl MediaFoundationDecoder {
+    pub fn new(input_path: &Path) -> Result<Self> {
+        Self::open(input_path, None)
+    }
+
+    fn open(input_path: &Path, seek: Option<(f64, SeekMode)>) -> Result<Self> {
+        let media_foundation = MediaFoundation::start()?;
+        let reader = unsafe {
+            let mut attributes: Option<IMFAttributes> = None;
+            MFCreateAttributes(&mut attributes, 1)?;
+            let attributes = attributes.ok_or(WindowsError::Other(
+                "MFCreateAttributes returned null".to_string(),
+            ))?;
+            attributes.SetUINT32(&MF_SOURCE_READER_ENABLE_VIDEO_PROCESSING, 1)?;
+
+            let reader = MFCreateSourceReaderFromURL(&HSTRING::from(input_path), &attributes)?;
+            reader.SetStreamSelection(MF_SOURCE_READER_ALL_STREAMS.0 as u32, false)?;
+            reader.SetStreamSelection(VIDEO_STREAM, true)?;
+
+            let media_type = MFCreateMediaType()?;
+            media_type.SetGUID(&MF_MT_MAJOR_TYPE, &MFMediaType_Video)?;
+            media_type.SetGUID(&MF_MT_SUBTYPE, &MFVideoFormat_RGB32)?;
+            reader.SetCurrentMediaType(VIDEO_STREAM, None, &media_type)?;
+            reader
+        };
+
+        let (width, height) = unsafe {
+            let frame_size = reader
+                .GetCurrentMediaType(VIDEO_STREAM)?
+                .GetUINT64(&MF_MT_FRAME_SIZE)?;
+            ((frame_size >> 32) as u32, frame_size as u32)
+        };
+
+        // the source reader seeks to the closest keyframe before the position
+        if let Some((timestamp, _)) = seek {
+            let position = PROPVARIANT {
+                Anonymous: PROPVARIANT_0 {
+                    Anonymous: ManuallyDrop::new(PROPVARIANT_0_0 {
+                        vt: VT_I8,
+                        Anonymous: PROPVARIANT_0_0_0 {
+                            hVal: Timebase::HUNDRED_NANOSECONDS.to_units(timestamp.max(0.0)),
+                        },
+                        ..Default::default()
+                    }),
+                },
+            };
+            unsafe { reader.SetCurrentPosition(&windows_core::GUID::zeroed(), &position)? };
+        }
+        let target = match seek {
+            Some((timestamp, SeekMode::Precise)) => Some(timestamp),
+            _ => None,
+        };
+
+        // ReadSample blocks in synchronous mode, so samples are read on a dedicated thread
+        let (tx, rx) = mpsc::channel(4);
+        let reader = UnsafeSend(reader);
+        std::thread::spawn(move || {
+            let _ = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+            let frames = std::iter::from_fn(|| read_frame(&reader, width, height).transpose());
+            for frame in PreciseSeek::new(frames, target) {
+                let failed = frame.is_err();
+                if tx.blocking_send(frame).is_err() || failed {
+                    break;
+                }
+            }
+            drop(reader);
+            drop(media_foundation);
+            unsafe { CoUninitialize() };
+        });
+
+        Ok(Self {
+            input_path: input_path.to_owned(),
+            rx,
+        })
+    }
+}
+
+fn read_frame(
+    reader: &IMFSourceReader,
+    width: u32,
+    height: u32,
+) -> Result<Option<DecodedVideoFrame>> {
+    loop {
+        let mut flags = 0u32;
+        let mut timestamp = 0i64;
+        let mut sample: Option<IMFSample> = None;
+        unsafe {
+            reader.ReadSample(
+                VIDEO_STREAM,
+                0,
+                None,
+                Some(&mut flags),
+                Some(&mut timestamp),
+                Some(&mut sample),
+            )?
+        };
+
+        if flags & MF_SOURCE_READERF_ENDOFSTREAM.0 as u32 != 0 {
+            return Ok(None);
+        }
+        // the reader may report a stream tick (gap) without a sample
+        let Some(sample) = sample else { continue };
+
+        let buffer = unsafe { sample.ConvertToContiguousBuffer()? };
+        let data = copy_bgra(&buffer, width, height)?;
+
+        return Ok(Some(DecodedVideoFrame {
+            frame: VideoFrameBgra32::packed(SharedBuffer::new_unmanaged(data), width, height),
+            timestamp: Timebase::HUNDRED_NANOSECONDS.to_seconds(timestamp),
+        }));
+    }
+}
+
+fn copy_bgra(buffer: &IMFMediaBuffer, width: u32, height: u32) -> Result<Vec<u8>> {
+    let row_size = (width * 4) as usize;
+    let mut data = vec![0u8; row_size * height as usize];
+
+    // IMF2DBuffer gives the first scanline and a signed pitch, which takes care of bottom-up RGB
+    // surfaces
+    if let Ok(buffer_2d) = buffer.cast::<IMF2DBuffer>() {
+        let mut scanline0: *mut u8 = std::ptr::null_mut();
+        let mut pitch = 0i32;
+        unsafe {
+            buffer_2d.Lock2D(&mut scanline0, &mut pitch)?;
+            for (y, row) in data.chunks_exact_mut(row_size).enumerate() {
+                let src = scanline0.offset(y as isize * pitch as isize);
+                row.copy_from_slice(std::slice::from_raw_parts(src, row_size));
+            }
+            buffer_2d.Unlock2D()?;
+        }
+    } else {
+        let mut ptr: *mut u8 = std::ptr::null_mut();
+        let mut length = 0u32;
+        unsafe {
+            buffer.Lock(&mut ptr, None, Some(&mut length))?;
+            let len = usize::min(length as usize, data.len());
+            data[..len].copy_from_slice(std::slice::from_raw_parts(ptr, len));
+            buffer.Unlock()?;
+        }
+    }
+
+    // the X8 channel of RGB32 is undefined
+    for pixel in data.chunks_exact_mut(4) {
+        pixel[3] = 0xff;
+    }
+
+    Ok(data)
+}
+
+impl Decoder for MediaFoundationDecoder {
+    async fn pull(&mut self) -> unienc_common::Result<Option<DecodedVideoFrame>> {
+        match self.rx.recv().await {
+            Some(frame) => Ok(Some(frame?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn seek(
+        &mut self,
+        timestamp: f64,
+        mode: SeekMode,
+    ) -> unienc_common::Result<Option<DecodedVideoFrame>> {
+        *self = Self::open(&self.input_path, Some((timestamp, mode)))?;
+        self.pull().await
+    }
+}
@@ -2,19 +2,30 @@
 compile_error!("This crate can only be compiled for Windows platforms.");
 
 use std::path::Path;
-use unienc_common::{EncodingSystem, Runtime, UnsupportedBlitData};
+use unienc_common::{
+    DiagnosticCheck, DownmixedAudioEncoder, DownmixedAudioOptions, EncodingSystem, PaddedMuxer,
+    PaddedVideoEncoder, PaddedVideoOptions, Runtime, StillImageFormat, TrackedRuntime,
+    UnsupportedBlitData, VideoEncoderOptions, still_image::UnsupportedStillImageCapture,
+};
 
 pub mod audio;
 mod common;
+pub mod decode;
 pub mod error;
+mod loopback;
 pub(crate) mod mft;
 pub mod mux;
+pub mod passthrough;
+mod startup;
 pub mod video;
 
 pub use error::{Result, WindowsError};
 
 use audio::MediaFoundationAudioEncoder;
+use decode::MediaFoundationDecoder;
 use mux::MediaFoundationMuxer;
+use passthrough::{MediaFoundationAacPacketizer, MediaFoundationH264Packetizer};
+use startup::MediaFoundation;
 use video::MediaFoundationVideoEncoder;
 
 pub struct MediaFoundationEncodingSystem<
@@ -22,9 +33,22 @@ pub struct MediaFoundationEncodingSystem<
     A: unienc_common::AudioEncoderOptions,
     R: Runtime,
 > {
-    video_options: V,
-    audio_options: A,
-    runtime: R,
+    video_options: PaddedVideoOptions<V>,
+    audio_options: DownmixedAudioOptions<A>,
+    runtime: TrackedRuntime<R>,
+    media_foundation: Result<MediaFoundation>,
+}
+
+impl<V: unienc_common::VideoEncoderOptions, A: unienc_common::AudioEncoderOptions, R: Runtime>
+    MediaFoundationEncodingSystem<V, A, R>
+{
+    /// Fails every component with the startup error instead of an unrelated one later on.
+    fn check_started(&self) -> Result<()> {
+        self.media_foundation
+            .as_ref()
+            .map(|_| ())
+            .map_err(Clone::clone)
+    }
 }
 
 impl<
@@ -35,37 +59,50 @@ impl<
 {
     type VideoEncoderOptionsType = V;
     type AudioEncoderOptionsType = A;
-    type VideoEncoderType = MediaFoundationVideoEncoder;
-    type AudioEncoderType = MediaFoundationAudioEncoder;
-    type MuxerType = MediaFoundationMuxer;
+    type VideoEncoderType = PaddedVideoEncoder<MediaFoundationVideoEncoder>;
+    type AudioEncoderType = DownmixedAudioEncoder<MediaFoundationAudioEncoder>;
+    type MuxerType = PaddedMuxer<MediaFoundationMuxer>;
     type BlitSourceType = UnsupportedBlitData;
     type RuntimeType = R;
+    type H264PacketizerType = MediaFoundationH264Packetizer;
+    type AacPacketizerType = MediaFoundationAacPacketizer;
+    type DecoderType = MediaFoundationDecoder;
+    type StillImageCaptureType = UnsupportedStillImageCapture<UnsupportedBlitData>;
 
     fn new(video_options: &V, audio_options: &A, runtime: R) -> Self {
-        // Initialize Media Foundation
-        unsafe {
-            let _ = windows::Win32::Media::MediaFoundation::MFStartup(
-                windows::Win32::Media::MediaFoundation::MF_VERSION,
-                windows::Win32::Media::MediaFoundation::MFSTARTUP_NOSOCKET,
-            );
-        }
-
         Self {
-            video_options: *video_options,
-            audio_options: *audio_options,
-            runtime,
+            video_options: PaddedVideoOptions::new(*video_options),
+            // the AAC encoder takes 1, 2 or 6 channels
+            audio_options: DownmixedAudioOptions::new(*audio_options, |channels| channels == 6),
+            runtime: TrackedRuntime::new(runtime),
+            media_foundation: MediaFoundation::start(),
         }
     }
 
     fn new_video_encoder(&self) -> unienc_common::Result<Self::VideoEncoderType> {
-        MediaFoundationVideoEncoder::new(&self.video_options, &self.runtime).map_err(|e| e.into())
+        // Media Foundation ships no ProRes or DNxHR encoder
+        let codec = self.video_options.codec();
+        if !self.is_codec_supported(codec) {
+            return Err(unienc_common::CommonError::CodecNotSupported(codec));
+        }
+        if self.video_options.preserve_alpha() {
+            return Err(unienc_common::CommonError::AlphaNotSupported);
+        }
+        self.check_started()?;
+        MediaFoundationVideoEncoder::new(&self.video_options, &self.runtime)
+            .map_err(|e| e.into())
+            .map(PaddedVideoEncoder::new)
     }
 
     fn new_audio_encoder(&self) -> unienc_common::Result<Self::AudioEncoderType> {
-        MediaFoundationAudioEncoder::new(&self.audio_options, &self.runtime).map_err(|e| e.into())
+        self.check_started()?;
+        MediaFoundationAudioEncoder::new(&self.audio_options, &self.runtime)
+            .map_err(|e| e.into())
+            .map(|encoder| DownmixedAudioEncoder::new(encoder, &self.audio_options))
     }
 
     fn new_muxer(&self, output_path: &Path) -> unienc_common::Result<Self::MuxerType> {
+        self.check_started()?;
         MediaFoundationMuxer::new(
             output_path,
             &self.video_options,
@@ -73,15 +110,119 @@ impl<
             &self.runtime,
         )
         .map_err(|e| e.into())
+        .map(|muxer| PaddedMuxer::new(muxer, output_path, &self.video_options))
     }
-}
 
-impl<V: unienc_common::VideoEncoderOptions, A: unienc_common::AudioEncoderOptions, R: Runtime> Drop
-    for MediaFoundationEncodingSystem<V, A, R>
-{
-    fn drop(&mut self) {
+    fn new_h264_packetizer(&self) -> unienc_common::Result<Self::H264PacketizerType> {
+        Ok(MediaFoundationH264Packetizer::new(&self.video_options))
+    }
+
+    fn new_aac_packetizer(&self) -> unienc_common::Result<Self::AacPacketizerType> {
+        MediaFoundationAacPacketizer::new(&self.audio_options)
+    }
+
+    fn new_decoder(&self, input_path: &Path) -> unienc_common::Result<Self::DecoderType> {
+        self.check_started()?;
+        MediaFoundationDecoder::new(input_path).map_err(|e| e.into())
+    }
+
+    fn new_still_image_capture(
+        &self,
+        _format: StillImageFormat,
+    ) -> unienc_common::Result<Self::StillImageCaptureType> {
+        Err(unienc_common::CommonError::BlitNotSupported)
+    }
+
+    fn shutdown(self) -> impl Future<Output = unienc_common::Result<()>> + Send {
+        let idle = self.runtime.idle();
+        let media_foundation = self.media_foundation;
+        async move {
+            idle.await;
+            // components hold references of their own
+            match media_foundation {
+                Ok(media_foundation) => media_foundation.shutdown().map_err(Into::into),
+                // the startup error was reported by every component
+                Err(_) => Ok(()),
+            }
+        }
+    }
+
+    fn available_space(&self, directory: &Path) -> Option<u64> {
+        use std::os::windows::ffi::OsStrExt;
+        use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+        let path: Vec<u16> = directory.as_os_str().encode_wide().chain([0]).collect();
+        let mut available = 0u64;
         unsafe {
-            let _ = windows::Win32::Media::MediaFoundation::MFShutdown();
+            GetDiskFreeSpaceExW(
+                windows_core::PCWSTR(path.as_ptr()),
+                Some(&raw mut available),
+                None,
+                None,
+            )
         }
+        .ok()?;
+        Some(available)
+    }
+
+    fn self_test() -> Vec<DiagnosticCheck> {
+        use windows::Win32::Media::MediaFoundation::*;
+
+        let _media_foundation = match MediaFoundation::start() {
+            Ok(media_foundation) => media_foundation,
+            Err(err) => {
+                return vec![DiagnosticCheck::failed(
+                    "media_foundation",
+                    format!(
+                        "MFStartup failed: {err}. Media Foundation is missing on N editions of Windows until the Media Feature Pack is installed"
+                    ),
+                )];
+            }
+        };
+
+        vec![
+            DiagnosticCheck::passed("media_foundation", "MFStartup succeeded"),
+            mft_check(
+                "h264_encoder",
+                MFT_CATEGORY_VIDEO_ENCODER,
+                (MFMediaType_Video, MFVideoFormat_NV12),
+                (MFMediaType_Video, MFVideoFormat_H264),
+            ),
+            mft_check(
+                "aac_encoder",
+                MFT_CATEGORY_AUDIO_ENCODER,
+                (MFMediaType_Audio, MFAudioFormat_PCM),
+                (MFMediaType_Audio, MFAudioFormat_AAC),
+            ),
+        ]
+    }
+}
+
+fn mft_check(
+    name: &'static str,
+    category: windows_core::GUID,
+    (input_major, input_subtype): (windows_core::GUID, windows_core::GUID),
+    (output_major, output_subtype): (windows_core::GUID, windows_core::GUID),
+) -> DiagnosticCheck {
+    use windows::Win32::Media::MediaFoundation::MFT_REGISTER_TYPE_INFO;
+
+    let available = mft::is_mft_available(
+        category,
+        MFT_REGISTER_TYPE_INFO {
+            guidMajorType: input_major,
+            guidSubtype: input_subtype,
+        },
+        MFT_REGISTER_TYPE_INFO {
+            guidMajorType: output_major,
+            guidSubtype: output_subtype,
+        },
+    );
+    if available {
+        DiagnosticCheck::passed(name, "Media Foundation transform found")
+    } else {
+        DiagnosticCheck::failed(
+            name,
+            "No Media Foundation transform found; install the Media Feature Pack",
+        )
     }
 }
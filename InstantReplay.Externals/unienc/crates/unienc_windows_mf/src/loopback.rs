@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use windows::Win32::Media::Audio::*;
+use windows::Win32::System::Com::{
+    CLSCTX_ALL, COINIT_MULTITHREADED, CoCreateInstance, CoInitializeEx, CoUninitialize,
+};
+
+use crate::error::{Result, WindowsError};
+
+// 100ns units
+const BUFFER_DURATION: i64 = 2_000_000;
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Captured audio that has not been mixed yet is dropped beyond this, in seconds.
+const MAX_PENDING: f64 = 1.0;
+
+/// Captures what the default render device is playing (WASAPI loopback), converted to the
+/// encoder's PCM format, so other applications' audio can be mixed into the recording.
+pub struct LoopbackCapture {
+    pending: Arc<Mutex<VecDeque<i16>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LoopbackCapture {
+    pub fn new(sample_rate: u32, channels: u32) -> Result<Self> {
+        let pending = Arc::new(Mutex::new(VecDeque::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let max_pending = (sample_rate as f64 * MAX_PENDING) as usize * channels as usize;
+
+        // the audio client is created and used on the capture thread
+        let (init_tx, init_rx) = std::sync::mpsc::channel();
+        let thread = {
+            let pending = pending.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let _ = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+                match start_capture(sample_rate, channels) {
+                    Ok((client, capture)) => {
+                        let _ = init_tx.send(Ok(()));
+                        while !stop.load(Ordering::Relaxed) {
+                            std::thread::sleep(POLL_INTERVAL);
+                            if let Err(err) =
+                                read_packets(&capture, channels, &pending, max_pending)
+                            {
+                                unienc_common::log!("Loopback capture stopped: {err}");
+                                break;
+                            }
+                        }
+                        let _ = unsafe { client.Stop() };
+                    }
+                    Err(err) => {
+                        let _ = init_tx.send(Err(err));
+                    }
+                }
+                unsafe { CoUninitialize() };
+            })
+        };
+
+        init_rx
+            .recv()
+            .map_err(|_| WindowsError::Other("Loopback capture thread exited".to_string()))??;
+
+        Ok(Self {
+            pending,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Adds captured audio to `data` in place. Mixes only what has been captured so far, so the
+    /// system audio never delays the samples it is mixed into.
+    pub fn mix_into(&self, data: &mut [i16]) {
+        let Ok(mut pending) = self.pending.lock() else {
+            return;
+        };
+        let len = data.len().min(pending.len());
+        for (dst, src) in data.iter_mut().zip(pending.drain(..len)) {
+            *dst = dst.saturating_add(src);
+        }
+    }
+}
+
+impl Drop for LoopbackCapture {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn start_capture(sample_rate: u32, channels: u32) -> Result<(IAudioClient, IAudioCaptureClient)> {
+    unsafe {
+        let enumerator: IMMDeviceEnumerator =
+            CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+        let device = enumerator.GetDefaultAudioEndpoint(eRender, eConsole)?;
+        let client: IAudioClient = device.Activate(CLSCTX_ALL, None)?;
+
+        let block_align = (channels * 2) as u16;
+        let format = WAVEFORMATEX {
+            wFormatTag: WAVE_FORMAT_PCM as u16,
+            nChannels: channels as u16,
+            nSamplesPerSec: sample_rate,
+            nAvgBytesPerSec: sample_rate * block_align as u32,
+            nBlockAlign: block_align,
+            wBitsPerSample: 16,
+            cbSize: 0,
+        };
+
+        // AUTOCONVERTPCM lets the audio engine resample the mix format to the encoder's format
+        client.Initialize(
+            AUDCLNT_SHAREMODE_SHARED,
+            AUDCLNT_STREAMFLAGS_LOOPBACK
+                | AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM
+                | AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY,
+            BUFFER_DURATION,
+            0,
+            &format,
+            None,
+        )?;
+        let capture: IAudioCaptureClient = client.GetService()?;
+        client.Start()?;
+        Ok((client, capture))
+    }
+}
+
+fn read_packets(
+    capture: &IAudioCaptureClient,
+    channels: u32,
+    pending: &Mutex<VecDeque<i16>>,
+    max_pending: usize,
+) -> Result<()> {
+    loop {
+        if unsafe { capture.GetNextPacketSize()? } == 0 {
+            return Ok(());
+        }
+
+        let mut data = std::ptr::null_mut();
+        let mut frames = 0u32;
+        let mut flags = 0u32;
+        unsafe { capture.GetBuffer(&mut data, &mut frames, &mut flags, None, None)? };
+
+        let len = (frames * channels) as usize;
+        if let Ok(mut pending) = pending.lock() {
+            if flags & AUDCLNT_BUFFERFLAGS_SILENT.0 as u32 != 0 || data.is_null() {
+                pending.extend(std::iter::repeat_n(0, len));
+            } else {
+                let samples = unsafe { std::slice::from_raw_parts(data as *const i16, len) };
+                pending.extend(samples);
+            }
+            let overflow = pending.len().saturating_sub(max_pending);
+            pending.drain(..overflow);
+        }
+
+        unsafe { capture.ReleaseBuffer(frames)? };
+    }
+}
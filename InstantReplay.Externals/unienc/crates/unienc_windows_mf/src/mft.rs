@@ -1,16 +1,24 @@
 use tokio::sync::{mpsc, oneshot};
 use windows::Win32::Media::MediaFoundation::{IMFSample, IMFTransform, MFT_OUTPUT_STREAM_INFO};
-use windows::Win32::System::Com::CoTaskMemFree;
+use windows::Win32::System::Com::{
+    COINIT_MULTITHREADED, CoInitializeEx, CoTaskMemFree, CoUninitialize,
+};
 
 use crate::common::UnsafeSend;
 use crate::error::{Result, WindowsError};
+use crate::startup::MediaFoundation;
 use std::cell::Cell;
 use std::future::Future;
 use std::mem::ManuallyDrop;
 use std::ptr;
+use std::sync::{Arc, Mutex};
 use unienc_common::{Runtime, SpawnExt};
 use windows::Win32::Foundation::E_NOTIMPL;
+use windows::Win32::Foundation::{VARIANT_FALSE, VARIANT_TRUE};
 use windows::Win32::Media::MediaFoundation::*;
+use windows::Win32::System::Variant::{
+    VARIANT, VARIANT_0, VARIANT_0_0, VARIANT_0_0_0, VT_BOOL, VT_UI4,
+};
 use windows::core::*;
 
 pub trait MediaEventGeneratorCustom {
@@ -134,6 +142,15 @@ fn process_output(
     Ok(sample.into())
 }
 
+/// Whether any registered transform converts `input` to `output`.
+pub(crate) fn is_mft_available(
+    category: windows_core::GUID,
+    input: MFT_REGISTER_TYPE_INFO,
+    output: MFT_REGISTER_TYPE_INFO,
+) -> bool {
+    MftIter::new(category, input, output).next().is_some()
+}
+
 struct MftIter {
     category: windows_core::GUID,
     input: MFT_REGISTER_TYPE_INFO,
@@ -219,22 +236,30 @@ fn enum_mft(
     Ok(activates)
 }
 
+/// Value of an encoder property set through `ICodecAPI`.
+#[derive(Clone, Copy)]
+pub enum CodecApiValue {
+    Bool(bool),
+    UInt32(u32),
+}
+
 pub struct Transform {
     pipeline: Pipeline,
     #[allow(dead_code)]
     input_type: UnsafeSend<IMFMediaType>,
     output_type: UnsafeSend<IMFMediaType>,
+    /// Shared with the event loop of asynchronous transforms, which drains after this is dropped.
+    _media_foundation: Arc<MediaFoundation>,
 }
 enum Pipeline {
     Async {
         sample_tx: mpsc::Sender<UnsafeSend<IMFSample>>,
     },
     Sync {
-        output_tx: mpsc::Sender<UnsafeSend<IMFSample>>,
-        transform: UnsafeSend<IMFTransform>,
-        input_id: u32,
-        output_id: u32,
-        output_info: MFT_OUTPUT_STREAM_INFO,
+        sample_tx: mpsc::Sender<UnsafeSend<IMFSample>>,
+        // set before the sample channel closes when the transform fails, so pushes report the
+        // failure instead of a closed channel
+        error: Arc<Mutex<Option<WindowsError>>>,
     },
 }
 
@@ -245,6 +270,7 @@ impl Transform {
         output: MFT_REGISTER_TYPE_INFO,
         input_type: IMFMediaType,
         output_type: IMFMediaType,
+        codec_api: &[(GUID, CodecApiValue)],
         runtime: &impl Runtime,
     ) -> Result<(Self, mpsc::Receiver<UnsafeSend<IMFSample>>)> {
         let mfts = MftIter::new(category, input, output);
@@ -256,15 +282,21 @@ impl Transform {
 
         for activate in mfts {
             if let Some(_r) = &result {
-                println!("Skipping MFT: {}", Self::get_name(&activate)?);
+                unienc_common::log!("Skipping MFT: {}", Self::get_name(&activate)?);
                 continue;
             }
-            match Self::try_activate(activate, &mut input_type, &mut output_type, runtime) {
+            match Self::try_activate(
+                activate,
+                &mut input_type,
+                &mut output_type,
+                codec_api,
+                runtime,
+            ) {
                 Ok(r) => {
                     result = Some(r);
                 }
                 Err(err) => {
-                    println!("Failed to activate MFT: {:?}", err);
+                    unienc_common::log!("Failed to activate MFT: {:?}", err);
                 }
             };
         }
@@ -286,13 +318,48 @@ impl Transform {
         Ok(value)
     }
 
+    /// Properties are set before the media types, as some encoders only read them then. Encoders
+    /// that do not support a property keep their default.
+    fn set_codec_api(transform: &IMFTransform, values: &[(GUID, CodecApiValue)]) {
+        let Ok(codec_api) = transform.cast::<ICodecAPI>() else {
+            unienc_common::log!("MFT does not support ICodecAPI");
+            return;
+        };
+        for (api, value) in values {
+            let (vt, value) = match *value {
+                CodecApiValue::Bool(value) => (
+                    VT_BOOL,
+                    VARIANT_0_0_0 {
+                        boolVal: if value { VARIANT_TRUE } else { VARIANT_FALSE },
+                    },
+                ),
+                CodecApiValue::UInt32(value) => (VT_UI4, VARIANT_0_0_0 { ulVal: value }),
+            };
+            let variant = VARIANT {
+                Anonymous: VARIANT_0 {
+                    Anonymous: ManuallyDrop::new(VARIANT_0_0 {
+                        vt,
+                        Anonymous: value,
+                        ..Default::default()
+                    }),
+                },
+            };
+            if let Err(err) = unsafe { codec_api.SetValue(api, &variant) } {
+                unienc_common::log!("Failed to set codec API value {:?}: {:?}", api, err);
+            }
+        }
+    }
+
     fn try_activate(
         activate: IMFActivate,
         input_type: &mut Option<IMFMediaType>,
         output_type: &mut Option<IMFMediaType>,
+        codec_api: &[(GUID, CodecApiValue)],
         runtime: &impl Runtime,
     ) -> Result<(Self, mpsc::Receiver<UnsafeSend<IMFSample>>)> {
-        println!("Trying MFT: {}", Self::get_name(&activate)?);
+        unienc_common::log!("Trying MFT: {}", Self::get_name(&activate)?);
+
+        let media_foundation = Arc::new(MediaFoundation::start()?);
 
         let is_async = unsafe { activate.GetUINT32(&MF_TRANSFORM_ASYNC) }.unwrap_or(0) != 0;
         let transform = unsafe { activate.ActivateObject::<IMFTransform>()? };
@@ -302,6 +369,10 @@ impl Transform {
             unsafe { attributes.SetUINT32(&MF_TRANSFORM_ASYNC_UNLOCK, 1)? };
         }
 
+        if !codec_api.is_empty() {
+            Self::set_codec_api(&transform, codec_api);
+        }
+
         let mut input_streams = 0;
         let mut output_streams = 0;
         unsafe { transform.GetStreamCount(&mut input_streams, &mut output_streams)? };
@@ -352,8 +423,10 @@ impl Transform {
             let (sample_tx, sample_rx) = mpsc::channel::<UnsafeSend<IMFSample>>(32);
 
             let transform = UnsafeSend(transform);
+            let media_foundation_clone = media_foundation.clone();
 
             runtime.spawn_ret(async move {
+                let _media_foundation = media_foundation_clone;
                 let mut sample_rx = sample_rx;
                 loop {
                     match generator.get_event().await {
@@ -386,17 +459,20 @@ impl Transform {
                                 }
                                 #[allow(non_upper_case_globals)]
                                 METransformDrainComplete => {
-                                    println!("Transform drain complete");
+                                    unienc_common::log!("Transform drain complete");
                                     // end - generator and transform are dropped here
                                     break;
                                 }
                                 _ => {
-                                    println!("Unhandled media event type: {:?}", event_type);
+                                    unienc_common::log!(
+                                        "Unhandled media event type: {:?}",
+                                        event_type
+                                    );
                                 }
                             }
                         }
                         Err(e) => {
-                            println!("Error receiving media event: {:?}", e);
+                            unienc_common::log!("Error receiving media event: {:?}", e);
                             break;
                         }
                     }
@@ -411,25 +487,53 @@ impl Transform {
                     output_type: UnsafeSend(
                         output_type.take().ok_or(WindowsError::OutputTypeNone)?,
                     ),
+                    _media_foundation: media_foundation,
                 },
                 output_rx,
             ))
         } else {
             unsafe { transform.ProcessMessage(MFT_MESSAGE_NOTIFY_BEGIN_STREAMING, 0)? };
 
+            let (sample_tx, sample_rx) = mpsc::channel::<UnsafeSend<IMFSample>>(32);
+            let error = Arc::new(Mutex::new(None));
+            let error_clone = error.clone();
+
+            let transform = UnsafeSend(transform);
+            let media_foundation_clone = media_foundation.clone();
+
+            // synchronous transforms encode inside ProcessInput, so each runs on its own thread and
+            // the video encoder, the audio encoder and the muxer proceed in parallel
+            std::thread::spawn(move || {
+                let _ = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+                let mut sample_rx = sample_rx;
+                let result = run_sync(
+                    &transform,
+                    input_id,
+                    output_id,
+                    &output_info,
+                    &mut sample_rx,
+                    &output_tx,
+                );
+                if let Err(e) = &result {
+                    unienc_common::log!("Transform failed: {e}");
+                    if let Ok(mut error) = error_clone.lock() {
+                        *error = Some(e.clone());
+                    }
+                }
+                drop(sample_rx);
+                drop(transform);
+                drop(media_foundation_clone);
+                unsafe { CoUninitialize() };
+            });
+
             Ok((
                 Self {
-                    pipeline: Pipeline::Sync {
-                        output_tx,
-                        transform: UnsafeSend(transform),
-                        input_id,
-                        output_id,
-                        output_info,
-                    },
+                    pipeline: Pipeline::Sync { sample_tx, error },
                     input_type: UnsafeSend(input_type.take().ok_or(WindowsError::InputTypeNone)?),
                     output_type: UnsafeSend(
                         output_type.take().ok_or(WindowsError::OutputTypeNone)?,
                     ),
+                    _media_foundation: media_foundation,
                 },
                 output_rx,
             ))
@@ -442,31 +546,14 @@ impl Transform {
                 .send(sample)
                 .await
                 .map_err(|e| WindowsError::SampleSendFailed(e.to_string())),
-            Pipeline::Sync {
-                output_tx,
-                transform,
-                input_id,
-                output_id,
-                output_info,
-            } => {
-                unsafe { transform.ProcessInput(*input_id, &*sample, 0)? };
-                loop {
-                    match process_output(transform, output_info, *output_id) {
-                        Ok(data) => {
-                            output_tx.send(data).await?;
-                            continue;
-                        }
-                        Err(err) => {
-                            if let WindowsError::Windows(err) = err {
-                                if err.code() == MF_E_TRANSFORM_NEED_MORE_INPUT {
-                                    return Ok(());
-                                } else {
-                                    return Err(err.into());
-                                }
-                            }
-                        }
-                    }
+            Pipeline::Sync { sample_tx, error } => {
+                let failure = || error.lock().ok()?.clone();
+                if let Some(e) = failure() {
+                    return Err(e);
                 }
+                sample_tx.send(sample).await.map_err(|e| {
+                    failure().unwrap_or_else(|| WindowsError::SampleSendFailed(e.to_string()))
+                })
             }
         }
     }
@@ -481,51 +568,47 @@ impl Transform {
     }
 }
 
-impl Drop for Transform {
-    fn drop(&mut self) {
-        if let Pipeline::Sync {
-            output_tx,
-            transform,
-            input_id: _,
-            output_id,
-            output_info,
-        } = &mut self.pipeline
-        {
-            unsafe {
-                transform
-                    .ProcessMessage(MFT_MESSAGE_NOTIFY_END_OF_STREAM, 0)
-                    .unwrap()
-            };
-            unsafe {
-                transform
-                    .ProcessMessage(MFT_MESSAGE_COMMAND_DRAIN, 0)
-                    .unwrap()
-            };
+/// Feeds a synchronous transform until the sample channel closes, then drains it. Stops early once
+/// the output is dropped.
+fn run_sync(
+    transform: &IMFTransform,
+    input_id: u32,
+    output_id: u32,
+    output_info: &MFT_OUTPUT_STREAM_INFO,
+    sample_rx: &mut mpsc::Receiver<UnsafeSend<IMFSample>>,
+    output_tx: &mpsc::Sender<UnsafeSend<IMFSample>>,
+) -> Result<()> {
+    while let Some(sample) = sample_rx.blocking_recv() {
+        unsafe { transform.ProcessInput(input_id, &*sample, 0)? };
+        if !send_output(transform, output_info, output_id, output_tx)? {
+            return Ok(());
+        }
+    }
 
-            let transform = UnsafeSend(transform.clone());
-            let output_tx = output_tx.clone();
-            let output_info = *output_info;
-            let output_id = *output_id;
-
-            loop {
-                match process_output(&transform, &output_info, output_id) {
-                    Ok(data) => {
-                        let Ok(_) = output_tx.try_send(data) else {
-                            return; // channel is already closed
-                        };
-                        continue;
-                    }
-                    Err(err) => {
-                        if let WindowsError::Windows(err) = err {
-                            if err.code() == MF_E_TRANSFORM_NEED_MORE_INPUT {
-                                return;
-                            } else {
-                                panic!("{:?}", err)
-                            }
-                        }
-                    }
+    unsafe { transform.ProcessMessage(MFT_MESSAGE_NOTIFY_END_OF_STREAM, 0)? };
+    unsafe { transform.ProcessMessage(MFT_MESSAGE_COMMAND_DRAIN, 0)? };
+    send_output(transform, output_info, output_id, output_tx)?;
+    Ok(())
+}
+
+/// Sends the samples the transform has ready. Returns false if the output was dropped.
+fn send_output(
+    transform: &IMFTransform,
+    output_info: &MFT_OUTPUT_STREAM_INFO,
+    output_id: u32,
+    output_tx: &mpsc::Sender<UnsafeSend<IMFSample>>,
+) -> Result<bool> {
+    loop {
+        match process_output(transform, output_info, output_id) {
+            Ok(data) => {
+                if output_tx.blocking_send(data).is_err() {
+                    return Ok(false);
                 }
             }
+            Err(WindowsError::Windows(err)) if err.code() == MF_E_TRANSFORM_NEED_MORE_INPUT => {
+                return Ok(true);
+            }
+            Err(err) => return Err(err),
         }
     }
 }
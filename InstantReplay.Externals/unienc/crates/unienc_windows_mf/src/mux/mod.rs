@@ -1,8 +1,10 @@
 use crate::error::{OptionExt, Result, WindowsError};
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
 use tokio::sync::oneshot;
 use unienc_common::SpawnExt;
+use unienc_common::output_path::extended_length_path;
 use unienc_common::{
     AudioEncoderOptions, CompletionHandle, Muxer, MuxerInput, Runtime, VideoEncoderOptions,
 };
@@ -14,6 +16,7 @@ use crate::audio::AudioEncodedData;
 use crate::common::{Payload, UnsafeSend};
 use crate::mft::AsyncCallback;
 use crate::mft::MediaEventGeneratorCustom;
+use crate::startup::MediaFoundation;
 use crate::video::VideoEncodedData;
 use windows::core::Interface;
 
@@ -69,12 +72,16 @@ impl MediaFoundationMuxer {
         _audio_options: &A,
         runtime: &R,
     ) -> Result<Self> {
+        // held by the task that writes the file, which outlives the muxer
+        let media_foundation = MediaFoundation::start()?;
+        // MFCreateFile rejects paths beyond MAX_PATH unless they are extended
+        let output_path = extended_length_path(output_path);
         let file = UnsafeSend(unsafe {
             MFCreateFile(
                 MF_ACCESSMODE_READWRITE,
                 MF_OPENMODE_DELETE_IF_EXIST,
                 MF_FILEFLAGS_NONE,
-                &HSTRING::from(output_path),
+                &HSTRING::from(&*output_path),
             )?
         });
 
@@ -88,6 +95,7 @@ impl MediaFoundationMuxer {
         let runtime_clone = runtime.clone();
 
         runtime.spawn_ret(async move {
+            let _media_foundation = media_foundation;
             let result: Result<()> = async move {
                 let runtime_clone = runtime_clone.clone();
 
@@ -128,8 +136,8 @@ impl MediaFoundationMuxer {
                         .send(Ok(audio_stream))
                         .map_err(|_| WindowsError::StreamSendFailed)?;
 
-                    video_finish_rx.await?;
-                    audio_finish_rx.await?;
+                    video_finish_rx.await??;
+                    audio_finish_rx.await??;
 
                     if let Some(finalizable) = finalizable {
                         let finalizable = UnsafeSend(finalizable);
@@ -197,61 +205,105 @@ impl MediaFoundationMuxer {
 
 struct Stream {
     sample_tx: mpsc::Sender<UnsafeSend<IMFSample>>,
+    // set before the sample channel closes when the stream sink fails, so pushes report the
+    // failure instead of a closed channel
+    error: Arc<Mutex<Option<WindowsError>>>,
 }
 
 impl Stream {
     pub fn new(
         stream: IMFStreamSink,
         runtime: &impl Runtime,
-    ) -> Result<(Self, oneshot::Receiver<()>)> {
+    ) -> Result<(Self, oneshot::Receiver<Result<()>>)> {
         let stream = UnsafeSend(stream);
         let stream_cap = UnsafeSend(stream.clone());
 
         let (sample_tx, sample_rx) = mpsc::channel::<UnsafeSend<IMFSample>>(32);
-        let (finish_tx, finish_rx) = oneshot::channel::<()>();
+        let (finish_tx, finish_rx) = oneshot::channel::<Result<()>>();
+        let error = Arc::new(Mutex::new(None));
+        let error_clone = error.clone();
 
         runtime.spawn_ret(async move {
             let mut sample_rx = sample_rx;
             let mut finish_tx = Some(finish_tx);
-            while let Ok(event) = stream_cap.get_event().await {
-                let event_type: u32 = unsafe { event.GetType()? };
-                match MF_EVENT_TYPE(event_type as i32) {
-                    #[allow(non_upper_case_globals)]
-                    MEStreamSinkRequestSample => {
-                        if let Some(sample) = sample_rx.recv().await {
-                            unsafe { stream_cap.ProcessSample(&*sample)? };
-                        } else {
-                            // Some Windows builds (observed on 26200 with
-                            // mfmp4srcsnk.dll 10.0.26100.8457) reject PlaceMarker with
-                            // MF_E_INVALIDTYPE for every marker type. The marker only
-                            // tells us the sink consumed everything; treat failure as
-                            // non-fatal so finalization still runs and writes the moov.
-                            if let Err(e) = unsafe {
-                                stream_cap.PlaceMarker(
-                                    MFSTREAMSINK_MARKER_ENDOFSEGMENT,
-                                    std::ptr::null(),
-                                    std::ptr::null(),
-                                )
-                            } {
-                                println!("PlaceMarker(ENDOFSEGMENT) failed (non-fatal): {:?}", e);
+            let result: Result<()> = async {
+                loop {
+                    let event = match stream_cap.get_event().await {
+                        Ok(event) => event,
+                        // the sink shuts down once it has been finalized
+                        Err(_) if finish_tx.is_none() => return Ok(()),
+                        Err(e) => return Err(e),
+                    };
+                    let event_type: u32 = unsafe { event.GetType()? };
+                    match MF_EVENT_TYPE(event_type as i32) {
+                        #[allow(non_upper_case_globals)]
+                        MEStreamSinkRequestSample => {
+                            if let Some(sample) = sample_rx.recv().await {
+                                unsafe { stream_cap.ProcessSample(&*sample)? };
+                            } else {
+                                // Some Windows builds (observed on 26200 with
+                                // mfmp4srcsnk.dll 10.0.26100.8457) reject PlaceMarker with
+                                // MF_E_INVALIDTYPE for every marker type. The marker only
+                                // tells us the sink consumed everything; treat failure as
+                                // non-fatal so finalization still runs and writes the moov.
+                                if let Err(e) = unsafe {
+                                    stream_cap.PlaceMarker(
+                                        MFSTREAMSINK_MARKER_ENDOFSEGMENT,
+                                        std::ptr::null(),
+                                        std::ptr::null(),
+                                    )
+                                } {
+                                    unienc_common::log!(
+                                        "PlaceMarker(ENDOFSEGMENT) failed (non-fatal): {:?}",
+                                        e
+                                    );
+                                }
+                                if let Some(finish_tx) = finish_tx.take() {
+                                    finish_tx
+                                        .send(Ok(()))
+                                        .map_err(|_e| WindowsError::FinishSignalSendFailed)?
+                                };
                             }
-                            if let Some(finish_tx) = finish_tx.take() {
-                                finish_tx
-                                    .send(())
-                                    .map_err(|_e| WindowsError::FinishSignalSendFailed)?
-                            };
                         }
-                    }
-                    _ => {
-                        println!("Unhandled media sink event type: {:?}", event_type);
+                        _ => {
+                            unienc_common::log!(
+                                "Unhandled media sink event type: {:?}",
+                                event_type
+                            );
+                        }
                     }
                 }
             }
+            .await;
 
-            Result::<()>::Ok(())
+            if let Err(e) = &result {
+                unienc_common::log!("Media sink stream failed: {e}");
+                if let Ok(mut error) = error_clone.lock() {
+                    *error = Some(e.clone());
+                }
+                if let Some(finish_tx) = finish_tx.take() {
+                    let _ = finish_tx.send(Err(e.clone()));
+                }
+            }
+            drop(sample_rx);
+            result
         });
 
-        Ok((Self { sample_tx }, finish_rx))
+        Ok((Self { sample_tx, error }, finish_rx))
+    }
+
+    fn error(&self) -> Option<WindowsError> {
+        self.error.lock().ok()?.clone()
+    }
+
+    async fn push(&self, sample: UnsafeSend<IMFSample>) -> Result<()> {
+        if let Some(e) = self.error() {
+            return Err(e);
+        }
+        self.sample_tx.send(sample).await.map_err(|e| {
+            self.error()
+                .unwrap_or_else(|| WindowsError::MuxerSendFailed(e.to_string()))
+        })
     }
 }
 
@@ -302,19 +354,17 @@ impl MuxerInput for VideoMuxerInputImpl {
                     .stream
                     .some()
                     .ok_or(WindowsError::StreamNotInitialized)?;
-                stream
-                    .sample_tx
-                    .send(sample)
-                    .await
-                    .map_err(|e| WindowsError::MuxerSendFailed(e.to_string()))?;
+                stream.push(sample).await?;
                 Ok(())
             }
         }
     }
 
     async fn finish(self) -> unienc_common::Result<()> {
-        drop(self.stream);
-        Ok(())
+        match self.stream.some().and_then(Stream::error) {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
     }
 }
 
@@ -339,19 +389,17 @@ impl MuxerInput for AudioMuxerInputImpl {
                     .stream
                     .some()
                     .ok_or(WindowsError::StreamNotInitialized)?;
-                stream
-                    .sample_tx
-                    .send(sample)
-                    .await
-                    .map_err(|e| WindowsError::MuxerSendFailed(e.to_string()))?;
+                stream.push(sample).await?;
                 Ok(())
             }
         }
     }
 
     async fn finish(self) -> unienc_common::Result<()> {
-        drop(self.stream);
-        Ok(())
+        match self.stream.some().and_then(Stream::error) {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
     }
 }
 
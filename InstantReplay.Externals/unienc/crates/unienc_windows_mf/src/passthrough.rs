@@ -0,0 +1,211 @@
+use unienc_common::passthrough::{aac, h264::AccessUnit};
+use unienc_common::{
+    AacPacketizer, AudioEncoderOptions, H264Packetizer, Timebase, VideoEncoderOptions,
+};
+use windows::Win32::Media::MediaFoundation::*;
+
+use crate::audio::AudioEncodedData;
+use crate::common::{Payload, UnsafeSend};
+use crate::error::{Result, WindowsError};
+use crate::video::VideoEncodedData;
+
+fn create_sample(data: &[u8], time: i64, duration: i64) -> Result<IMFSample> {
+    unsafe {
+        let buffer = MFCreateMemoryBuffer(data.len() as u32)?;
+        let mut ptr: *mut u8 = std::ptr::null_mut();
+        buffer.Lock(&mut ptr, None, None)?;
+        std::slice::from_raw_parts_mut(ptr, data.len()).copy_from_slice(data);
+        buffer.Unlock()?;
+        buffer.SetCurrentLength(data.len() as u32)?;
+
+        let sample = MFCreateSample()?;
+        sample.AddBuffer(&buffer)?;
+        sample.SetSampleTime(time)?;
+        sample.SetSampleDuration(duration)?;
+        Ok(sample)
+    }
+}
+
+/// Emits an H.264 media type built from the first access unit's parameter sets, followed by
+/// Annex-B samples.
+pub struct MediaFoundationH264Packetizer {
+    width: u32,
+    height: u32,
+    fps_hint: u32,
+    bitrate: u32,
+    format_sent: bool,
+}
+
+impl MediaFoundationH264Packetizer {
+    pub fn new<V: VideoEncoderOptions>(options: &V) -> Self {
+        Self {
+            width: options.width(),
+            height: options.height(),
+            fps_hint: options.fps_hint().max(1),
+            bitrate: options.bitrate(),
+            format_sent: false,
+        }
+    }
+
+    fn create_media_type(&self, sps: &[u8], pps: &[u8]) -> Result<IMFMediaType> {
+        let mut sequence_header = Vec::with_capacity(sps.len() + pps.len() + 8);
+        for parameter_set in [sps, pps] {
+            sequence_header.extend_from_slice(&[0, 0, 0, 1]);
+            sequence_header.extend_from_slice(parameter_set);
+        }
+
+        unsafe {
+            let media_type = MFCreateMediaType()?;
+            media_type.SetGUID(&MF_MT_MAJOR_TYPE, &MFMediaType_Video)?;
+            media_type.SetGUID(&MF_MT_SUBTYPE, &MFVideoFormat_H264)?;
+            media_type.SetUINT32(&MF_MT_AVG_BITRATE, self.bitrate)?;
+            media_type.SetUINT64(&MF_MT_FRAME_RATE, ((self.fps_hint as u64) << 32) + 1)?;
+            media_type.SetUINT64(
+                &MF_MT_FRAME_SIZE,
+                ((self.width as u64) << 32) + self.height as u64,
+            )?;
+            media_type.SetUINT32(&MF_MT_INTERLACE_MODE, MFVideoInterlace_Progressive.0 as u32)?;
+            media_type.SetBlob(&MF_MT_MPEG_SEQUENCE_HEADER, &sequence_header)?;
+            Ok(media_type)
+        }
+    }
+
+    fn packetize_impl(
+        &mut self,
+        access_unit: &[u8],
+        timestamp: f64,
+    ) -> Result<Vec<VideoEncodedData>> {
+        let access_unit = AccessUnit::parse(access_unit)?;
+        let mut data = Vec::with_capacity(2);
+
+        if !self.format_sent {
+            let (Some(sps), Some(pps)) = (access_unit.sps, access_unit.pps) else {
+                return Err(WindowsError::Other(
+                    "The first H.264 access unit must contain SPS and PPS".to_string(),
+                ));
+            };
+            data.push(VideoEncodedData {
+                payload: Payload::Format(UnsafeSend(self.create_media_type(sps, pps)?)),
+            });
+            self.format_sent = true;
+        }
+
+        let time = Timebase::HUNDRED_NANOSECONDS.to_units(timestamp);
+        let sample = create_sample(
+            &access_unit.to_annexb(),
+            time,
+            Timebase::HUNDRED_NANOSECONDS.to_units(1.0 / self.fps_hint as f64),
+        )?;
+        unsafe {
+            sample.SetUINT32(&MFSampleExtension_CleanPoint, access_unit.is_idr as u32)?;
+        }
+        data.push(VideoEncodedData {
+            payload: Payload::Sample(UnsafeSend(sample)),
+        });
+        Ok(data)
+    }
+}
+
+impl H264Packetizer for MediaFoundationH264Packetizer {
+    type Data = VideoEncodedData;
+
+    fn packetize(
+        &mut self,
+        access_unit: &[u8],
+        timestamp: f64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        self.packetize_impl(access_unit, timestamp)
+            .map_err(Into::into)
+    }
+}
+
+/// Emits a raw AAC media type (MF_MT_AAC_PAYLOAD_TYPE = 0) before the first frame.
+pub struct MediaFoundationAacPacketizer {
+    sample_rate: u32,
+    channels: u32,
+    bitrate: u32,
+    format_sent: bool,
+}
+
+impl MediaFoundationAacPacketizer {
+    pub fn new<A: AudioEncoderOptions>(options: &A) -> unienc_common::Result<Self> {
+        aac::audio_specific_config(options.sample_rate(), options.channels())?;
+        Ok(Self {
+            sample_rate: options.sample_rate(),
+            channels: options.channels(),
+            bitrate: options.bitrate(),
+            format_sent: false,
+        })
+    }
+
+    fn create_media_type(&self) -> Result<IMFMediaType> {
+        const AAC_LC_PROFILE_LEVEL: u16 = 0x29;
+
+        // MF_MT_USER_DATA holds the HEAACWAVEINFO fields following WAVEFORMATEX and then the
+        // AudioSpecificConfig
+        let mut user_data = Vec::with_capacity(14);
+        user_data.extend_from_slice(&0u16.to_le_bytes()); // wPayloadType: raw
+        user_data.extend_from_slice(&AAC_LC_PROFILE_LEVEL.to_le_bytes());
+        user_data.extend_from_slice(&[0; 8]); // wStructType, wReserved1, dwReserved2
+        user_data.extend_from_slice(&aac::audio_specific_config(
+            self.sample_rate,
+            self.channels,
+        )?);
+
+        unsafe {
+            let media_type = MFCreateMediaType()?;
+            media_type.SetGUID(&MF_MT_MAJOR_TYPE, &MFMediaType_Audio)?;
+            media_type.SetGUID(&MF_MT_SUBTYPE, &MFAudioFormat_AAC)?;
+            media_type.SetUINT32(&MF_MT_AUDIO_BITS_PER_SAMPLE, 16)?;
+            media_type.SetUINT32(&MF_MT_AUDIO_SAMPLES_PER_SECOND, self.sample_rate)?;
+            media_type.SetUINT32(&MF_MT_AUDIO_NUM_CHANNELS, self.channels)?;
+            media_type.SetUINT32(&MF_MT_AUDIO_AVG_BYTES_PER_SECOND, self.bitrate >> 3)?;
+            media_type.SetUINT32(&MF_MT_AUDIO_BLOCK_ALIGNMENT, 1)?;
+            media_type.SetUINT32(&MF_MT_AAC_PAYLOAD_TYPE, 0)?;
+            media_type.SetUINT32(
+                &MF_MT_AAC_AUDIO_PROFILE_LEVEL_INDICATION,
+                AAC_LC_PROFILE_LEVEL as u32,
+            )?;
+            media_type.SetBlob(&MF_MT_USER_DATA, &user_data)?;
+            Ok(media_type)
+        }
+    }
+
+    fn packetize_impl(
+        &mut self,
+        frame: &[u8],
+        timestamp_in_samples: u64,
+    ) -> Result<Vec<AudioEncodedData>> {
+        let mut data = Vec::with_capacity(2);
+
+        if !self.format_sent {
+            data.push(AudioEncodedData {
+                payload: Payload::Format(UnsafeSend(self.create_media_type()?)),
+            });
+            self.format_sent = true;
+        }
+
+        let sample = create_sample(
+            frame,
+            Timebase::HUNDRED_NANOSECONDS.from_samples(timestamp_in_samples, self.sample_rate),
+            Timebase::HUNDRED_NANOSECONDS.from_samples(aac::SAMPLES_PER_FRAME, self.sample_rate),
+        )?;
+        data.push(AudioEncodedData {
+            payload: Payload::Sample(UnsafeSend(sample)),
+        });
+        Ok(data)
+    }
+}
+
+impl AacPacketizer for MediaFoundationAacPacketizer {
+    type Data = AudioEncodedData;
+
+    fn packetize(
+        &mut self,
+        frame: &[u8],
+        timestamp_in_samples: u64,
+    ) -> unienc_common::Result<Vec<Self::Data>> {
+        self.packetize_impl(frame, timestamp_in_samples)
+            .map_err(Into::into)
+    }
+}
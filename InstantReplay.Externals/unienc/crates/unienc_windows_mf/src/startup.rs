@@ -0,0 +1,34 @@
+//! Media Foundation startup, shared by the encoding system, the self test and every component
+//! that calls into Media Foundation. `MFStartup` is reference counted, so each holder keeps its own
+//! reference and a muxer or decoder that outlives its encoding system keeps working.
+
+use windows::Win32::Media::MediaFoundation::{
+    MF_VERSION, MFSTARTUP_NOSOCKET, MFShutdown, MFStartup,
+};
+
+use crate::Result;
+
+/// One `MFStartup` reference, released on drop.
+pub(crate) struct MediaFoundation(());
+
+impl MediaFoundation {
+    pub fn start() -> Result<Self> {
+        unsafe { MFStartup(MF_VERSION, MFSTARTUP_NOSOCKET)? };
+        Ok(Self(()))
+    }
+
+    /// Releases the reference, reporting the error that dropping it would ignore.
+    pub fn shutdown(self) -> Result<()> {
+        std::mem::forget(self);
+        unsafe { MFShutdown()? };
+        Ok(())
+    }
+}
+
+impl Drop for MediaFoundation {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = MFShutdown();
+        }
+    }
+}
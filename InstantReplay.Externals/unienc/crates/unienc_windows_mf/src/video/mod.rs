@@ -2,13 +2,13 @@ use crate::error::{Result, WindowsError};
 use bincode::{Decode, Encode};
 use tokio::sync::mpsc;
 use unienc_common::{
-    EncodedData, Encoder, EncoderInput, EncoderOutput, Runtime, UniencSampleKind,
-    UnsupportedBlitData, VideoEncoderOptions, VideoFrame, VideoSample,
+    EncodedData, Encoder, EncoderInput, EncoderOutput, LatencyMode, Runtime, Timebase,
+    UniencSampleKind, UnsupportedBlitData, VideoEncoderOptions, VideoFrame, VideoSample,
 };
 use windows::Win32::Media::MediaFoundation::*;
 
 use crate::common::*;
-use crate::mft::Transform;
+use crate::mft::{CodecApiValue, Transform};
 
 pub struct MediaFoundationVideoEncoder {
     transform: Transform,
@@ -48,6 +48,24 @@ impl MediaFoundationVideoEncoder {
             output_type
         };
 
+        let codec_api = match options.latency_mode() {
+            LatencyMode::Realtime => Vec::new(),
+            LatencyMode::Offline => {
+                let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+                vec![
+                    (CODECAPI_AVLowLatencyMode, CodecApiValue::Bool(false)),
+                    (
+                        CODECAPI_AVEncCommonQualityVsSpeed,
+                        CodecApiValue::UInt32(100),
+                    ),
+                    (
+                        CODECAPI_AVEncNumWorkerThreads,
+                        CodecApiValue::UInt32(threads as u32),
+                    ),
+                ]
+            }
+        };
+
         let (transform, output_rx) = Transform::new(
             MFT_CATEGORY_VIDEO_ENCODER,
             MFT_REGISTER_TYPE_INFO {
@@ -60,6 +78,7 @@ impl MediaFoundationVideoEncoder {
             },
             input_type,
             output_type,
+            &codec_api,
             runtime,
         )?;
 
@@ -111,7 +130,7 @@ impl EncoderInput for VideoEncoderInputImpl {
 
         // BGRA to NV12
         {
-            let (y, u, v) = frame.to_yuv420_planes(None)?;
+            let (y, u, v) = frame.to_yuv420_planes(None);
             let length = (y.len() + u.len() + v.len()) as u32;
             let buffer = unsafe { MFCreateMemoryBuffer(length).map_err(WindowsError::from)? };
 
@@ -146,12 +165,12 @@ impl EncoderInput for VideoEncoderInputImpl {
 
         unsafe {
             sample
-                .SetSampleTime((data.timestamp * 10_000_000_f64) as i64)
+                .SetSampleTime(Timebase::HUNDRED_NANOSECONDS.to_units(data.timestamp))
                 .map_err(WindowsError::from)?
         };
         unsafe {
             sample
-                .SetSampleDuration((1.0_f64 / self.fps_hint * 10_000_000_f64) as i64)
+                .SetSampleDuration(Timebase::HUNDRED_NANOSECONDS.to_units(1.0 / self.fps_hint))
                 .map_err(WindowsError::from)?
         };
         Ok(self.transform.push(sample).await?)
@@ -184,7 +203,7 @@ impl EncodedData for VideoEncodedData {
             Payload::Sample(sample) => unsafe {
                 sample
                     .GetSampleTime()
-                    .map(|t| t as f64 / 10_000_000_f64)
+                    .map(|t| Timebase::HUNDRED_NANOSECONDS.to_seconds(t))
                     .unwrap_or(0.0)
             },
             Payload::Format(_) => 0.0,
@@ -194,7 +213,7 @@ impl EncodedData for VideoEncodedData {
     fn set_timestamp(&mut self, timestamp: f64) {
         match &self.payload {
             Payload::Sample(sample) => {
-                let sample_time = (timestamp * 10_000_000_f64) as i64;
+                let sample_time = Timebase::HUNDRED_NANOSECONDS.to_units(timestamp);
                 unsafe { sample.SetSampleTime(sample_time) }.unwrap();
                 // set the DTS
                 // it assumes there is no B frame
@@ -229,4 +248,23 @@ impl EncodedData for VideoEncodedData {
             Payload::Format(_) => UniencSampleKind::Metadata,
         }
     }
+
+    fn size(&self) -> usize {
+        match &self.payload {
+            Payload::Sample(sample) => unsafe { sample.GetTotalLength() }.unwrap_or(0) as usize,
+            Payload::Format(_) => 0,
+        }
+    }
+
+    fn qp(&self) -> Option<u32> {
+        match &self.payload {
+            // set on output samples by encoders that report QP, in the low 16 bits
+            Payload::Sample(sample) => {
+                unsafe { sample.GetUINT64(&MFSampleExtension_VideoEncodeQP) }
+                    .ok()
+                    .map(|qp| (qp & 0xffff) as u32)
+            }
+            Payload::Format(_) => None,
+        }
+    }
 }
@@ -0,0 +1,49 @@
+// --------------------------------------------------------------
+// Copyright 2025 CyberAgent, Inc.
+// --------------------------------------------------------------
+
+namespace InstantReplay
+{
+    /// <summary>
+    ///     Throughput achieved by a dry run of <see cref="UnboundedRecordingSession" />, measured on the timeline of
+    ///     the recording, so paused periods are not counted. Frames dropped before encoding lower the frame rate.
+    /// </summary>
+    public readonly struct DryRunReport
+    {
+        /// <summary>
+        ///     Number of video frames encoded.
+        /// </summary>
+        public long VideoFrameCount { get; }
+
+        /// <summary>
+        ///     Video frames encoded per second of recording.
+        /// </summary>
+        public double FramesPerSecond { get; }
+
+        /// <summary>
+        ///     Encoded video bits per second of recording.
+        /// </summary>
+        public double VideoBitrate { get; }
+
+        /// <summary>
+        ///     Encoded audio bits per second of recording.
+        /// </summary>
+        public double AudioBitrate { get; }
+
+        internal DryRunReport(NullMuxerInput video, NullMuxerInput audio)
+        {
+            video.GetStatistics(out var videoCount, out var videoBytes, out var videoRate);
+            audio.GetStatistics(out var audioCount, out var audioBytes, out var audioRate);
+            VideoFrameCount = videoCount;
+            FramesPerSecond = videoRate;
+            VideoBitrate = videoCount > 0 ? videoBytes * 8.0 / videoCount * videoRate : 0;
+            AudioBitrate = audioCount > 0 ? audioBytes * 8.0 / audioCount * audioRate : 0;
+        }
+
+        public override string ToString()
+        {
+            return
+                $"{VideoFrameCount} frames at {FramesPerSecond:F1} fps, video {VideoBitrate / 1000:F0} kbps, audio {AudioBitrate / 1000:F0} kbps";
+        }
+    }
+}
@@ -8,7 +8,7 @@ using System.Threading.Tasks;
 
 namespace InstantReplay
 {
-    internal class AudioSampleProviderSubscription
+    internal class AudioSampleProviderSubscription : IPipelineSubscription
     {
         private readonly bool _disposeProvider;
         private readonly IPipelineInput<InputAudioFrame> _next;
@@ -8,7 +8,7 @@ using System.Threading.Tasks;
 
 namespace InstantReplay
 {
-    internal class FrameProviderSubscription : IDisposable
+    internal class FrameProviderSubscription : IPipelineSubscription
     {
         private readonly bool _disposeProvider;
         private readonly IPipelineInput<IFrameProvider.Frame> _next;
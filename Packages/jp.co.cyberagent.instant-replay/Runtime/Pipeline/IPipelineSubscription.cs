@@ -0,0 +1,20 @@
+// --------------------------------------------------------------
+// Copyright 2026 CyberAgent, Inc.
+// --------------------------------------------------------------
+
+using System;
+using System.Threading.Tasks;
+
+namespace InstantReplay
+{
+    /// <summary>
+    ///     Subscription of a pipeline to the provider of its input.
+    /// </summary>
+    internal interface IPipelineSubscription : IDisposable
+    {
+        /// <summary>
+        ///     Stops receiving input and waits for the pipeline to process what it has received.
+        /// </summary>
+        ValueTask CompleteAsync();
+    }
+}
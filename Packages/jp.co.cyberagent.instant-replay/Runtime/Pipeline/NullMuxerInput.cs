@@ -0,0 +1,67 @@
+// --------------------------------------------------------------
+// Copyright 2025 CyberAgent, Inc.
+// --------------------------------------------------------------
+
+using System;
+using System.Threading.Tasks;
+using UniEnc;
+
+namespace InstantReplay
+{
+    /// <summary>
+    ///     Discards encoded frames in place of a muxer, counting them and their size for a dry run.
+    /// </summary>
+    internal class NullMuxerInput : IPipelineInput<EncodedFrame>
+    {
+        private readonly object _lock = new();
+        private long _bytes;
+        private long _count;
+        private double _firstTimestamp;
+        private double _lastTimestamp;
+
+        public bool WillAccept()
+        {
+            return true;
+        }
+
+        public void Push(EncodedFrame value)
+        {
+            using (value)
+            {
+                lock (_lock)
+                {
+                    if (_count == 0)
+                        _firstTimestamp = _lastTimestamp = value.Timestamp;
+
+                    _lastTimestamp = Math.Max(_lastTimestamp, value.Timestamp);
+                    _bytes += value.Data.Length;
+                    _count++;
+                }
+            }
+        }
+
+        public ValueTask CompleteAsync(Exception exception = null)
+        {
+            return default;
+        }
+
+        public void Dispose()
+        {
+        }
+
+        /// <summary>
+        ///     Gets the number of frames discarded so far, their total size, and how many of them were pushed per
+        ///     second of their timestamps.
+        /// </summary>
+        public void GetStatistics(out long count, out long bytes, out double rate)
+        {
+            lock (_lock)
+            {
+                count = _count;
+                bytes = _bytes;
+                var duration = _lastTimestamp - _firstTimestamp;
+                rate = count > 1 && duration > 0 ? (count - 1) / duration : 0;
+            }
+        }
+    }
+}
@@ -55,6 +55,12 @@ namespace InstantReplay
 
         public bool ForceReadback { get; set; }
 
+        /// <summary>
+        ///     Times a track of <see cref="RealtimeInstantReplaySession" /> whose encoder fails is restarted automatically
+        ///     with a new encoder, while the other track keeps recording. 0 leaves a failed track stopped.
+        /// </summary>
+        public int MaxTrackRestarts { get; set; }
+
         public static ref readonly RealtimeEncodingOptions Default => ref DefaultValue; 
         private static readonly RealtimeEncodingOptions DefaultValue =
             new()
@@ -75,7 +81,8 @@ namespace InstantReplay
                 MaxMemoryUsageBytesForCompressedFrames = 20 * 1024 * 1024, // 20 MiB
                 FixedFrameRate = 30.0,
                 VideoInputQueueSize = 5,
-                AudioInputQueueSizeSeconds = 1.0
+                AudioInputQueueSizeSeconds = 1.0,
+                MaxTrackRestarts = 3
             };
     }
 }
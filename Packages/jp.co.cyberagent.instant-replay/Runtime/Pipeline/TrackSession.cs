@@ -0,0 +1,167 @@
+// --------------------------------------------------------------
+// Copyright 2026 CyberAgent, Inc.
+// --------------------------------------------------------------
+
+using System;
+using System.Threading.Tasks;
+
+namespace InstantReplay
+{
+    /// <summary>
+    ///     Recording of a single track, independent of the other tracks of the session. The pipeline of the track,
+    ///     including its encoder, can be replaced with a new one while the other tracks keep recording, which is done
+    ///     automatically when it fails, up to a number of times.
+    /// </summary>
+    internal sealed class TrackSession : IDisposable
+    {
+        private readonly object _lock = new();
+        private readonly int _maxAutomaticRestarts;
+        private readonly string _name;
+        private readonly Action<Exception> _onException;
+        private readonly Func<Action<Exception>, IPipelineSubscription> _start;
+        private int _automaticRestarts;
+        private bool _completed;
+        private IPipelineSubscription _current;
+        private bool _disposed;
+
+        /// <param name="name">Name of the track in logs.</param>
+        /// <param name="start">Creates a new pipeline that reports its failures to the given callback.</param>
+        /// <param name="maxAutomaticRestarts">Times the pipeline is replaced automatically after failures.</param>
+        /// <param name="onException">Receives the failures of the track.</param>
+        public TrackSession(string name, Func<Action<Exception>, IPipelineSubscription> start,
+            int maxAutomaticRestarts, Action<Exception> onException)
+        {
+            _name = name;
+            _start = start ?? throw new ArgumentNullException(nameof(start));
+            _maxAutomaticRestarts = maxAutomaticRestarts;
+            _onException = onException;
+            _current = Start();
+        }
+
+        /// <summary>
+        ///     Whether the track stopped recording after a failure.
+        /// </summary>
+        public bool IsFailed { get; private set; }
+
+        public void Dispose()
+        {
+            lock (_lock)
+            {
+                if (_disposed) return;
+                _disposed = true;
+                _current?.Dispose();
+                _current = null;
+            }
+        }
+
+        /// <summary>
+        ///     Replaces the pipeline with a new one. Data the previous pipeline has not processed yet is dropped.
+        /// </summary>
+        public void Restart()
+        {
+            lock (_lock)
+            {
+                if (_disposed)
+                    throw new ObjectDisposedException(nameof(TrackSession));
+                if (_completed)
+                    throw new InvalidOperationException($"Cannot restart the {_name} track after it completed.");
+
+                RestartCore();
+            }
+        }
+
+        /// <summary>
+        ///     Stops recording and waits for the pipeline to process what it has received. A failure of the track is
+        ///     reported instead of thrown, so the other tracks can still be exported.
+        /// </summary>
+        public async ValueTask CompleteAsync()
+        {
+            IPipelineSubscription current;
+            lock (_lock)
+            {
+                _completed = true;
+                current = _current;
+            }
+
+            if (current == null)
+                return;
+
+            try
+            {
+                await current.CompleteAsync();
+            }
+            catch (Exception ex)
+            {
+                bool reported;
+                lock (_lock)
+                {
+                    reported = IsFailed;
+                    IsFailed = true;
+                }
+
+                if (!reported)
+                    Report(ex);
+            }
+        }
+
+        private IPipelineSubscription Start()
+        {
+            IPipelineSubscription subscription = null;
+            // ReSharper disable once AccessToModifiedClosure
+            subscription = _start(ex => OnException(subscription, ex));
+            return subscription;
+        }
+
+        private void RestartCore()
+        {
+            var previous = _current;
+            _current = null;
+            previous?.Dispose();
+
+            // stays failed if a new pipeline cannot be created
+            IsFailed = true;
+            _current = Start();
+            IsFailed = false;
+        }
+
+        private void OnException(IPipelineSubscription subscription, Exception exception)
+        {
+            lock (_lock)
+            {
+                // failures of a pipeline already replaced are not reported again
+                if (_disposed || subscription != _current)
+                    return;
+                IsFailed = true;
+            }
+
+            Report(exception);
+
+            try
+            {
+                lock (_lock)
+                {
+                    if (_disposed || _completed || subscription != _current ||
+                        _automaticRestarts >= _maxAutomaticRestarts)
+                        return;
+
+                    _automaticRestarts++;
+                    ILogger.LogWarningCore(
+                        $"Restarting the {_name} track after a failure ({_automaticRestarts}/{_maxAutomaticRestarts}).");
+                    RestartCore();
+                }
+            }
+            catch (Exception ex)
+            {
+                Report(ex);
+            }
+        }
+
+        private void Report(Exception exception)
+        {
+            if (_onException != null)
+                _onException(exception);
+            else
+                ILogger.LogExceptionCore(exception);
+        }
+    }
+}
@@ -17,12 +17,16 @@ namespace InstantReplay
     /// </summary>
     public class RealtimeInstantReplaySession : IDisposable
     {
-        private readonly AudioSampleProviderSubscription _audioPipeline;
+        private readonly TrackSession _audioTrack;
+        private readonly IAudioSampleProvider _audioSampleProvider;
         private readonly BoundedEncodedFrameBuffer _buffer;
+        private readonly bool _disposeAudioSampleProvider;
+        private readonly bool _disposeFrameProvider;
         private readonly EncodingSystem _encodingSystem;
+        private readonly IFrameProvider _frameProvider;
         private readonly object _lock = new();
         private readonly TemporalController _temporalController = new();
-        private readonly FrameProviderSubscription _videoPipeline;
+        private readonly TrackSession _videoTrack;
         private bool _disposed;
 
         /// <summary>
@@ -66,9 +70,12 @@ namespace InstantReplay
                 null => 0
             };
 
+            _frameProvider = frameProvider;
+            _disposeFrameProvider = disposeFrameProvider;
+            _audioSampleProvider = audioSampleProvider;
+            _disposeAudioSampleProvider = disposeAudioSampleProvider;
+
             var encodingSystem = _encodingSystem = new EncodingSystem(options.VideoOptions, options.AudioOptions);
-            var videoEncoder = encodingSystem.CreateVideoEncoder();
-            var audioEncoder = encodingSystem.CreateAudioEncoder();
             var buffer = _buffer = new BoundedEncodedFrameBuffer(options.MaxMemoryUsageBytesForCompressedFrames);
 
             // ReSharper disable once ConvertToLocalFunction
@@ -85,60 +92,72 @@ namespace InstantReplay
                 }
             };
 
-            if (!options.ForceReadback && encodingSystem.IsBlitSupported())
-            {
-                _videoPipeline = new FrameProviderSubscription(frameProvider, disposeFrameProvider, onException,
-                    new VideoTemporalAdjuster<IFrameProvider.Frame>(
-                        _temporalController,
-                        fixedFrameInterval,
-                        options.VideoLagAdjustmentThreshold).AsInput(
-                        new DirectFrameDataTransform().AsInput(
-                            new DroppingChannelInput<LazyVideoFrameData>(
-                                options.VideoInputQueueSize,
-                                onLazyVideoFrameDataDropped,
-                                new VideoEncoderInput(videoEncoder,
-                                    new BoundedEncodedDataBufferVideoInput(buffer).AsAsync())))));
-            }
-            else
+            // each track gets a new encoder when it is restarted, while the other keeps recording; the options are
+            // copied since `in` parameters cannot be captured
+            var trackOptions = options;
+
+            IPipelineSubscription StartVideo(Action<Exception> onTrackException)
             {
-                var preprocessor = FramePreprocessor.WithFixedSize(
-                    (int)options.VideoOptions.Width,
-                    (int)options.VideoOptions.Height,
-                    // RGBA to BGRA
-                    new Matrix4x4(new Vector4(0, 0, 1, 0),
-                        new Vector4(0, 1, 0, 0),
-                        new Vector4(1, 0, 0, 0),
-                        new Vector4(0, 0, 0, 1)
-                    ));
-
-                _videoPipeline = new FrameProviderSubscription(frameProvider, disposeFrameProvider, onException,
-                    new VideoTemporalAdjuster<IFrameProvider.Frame>(
-                        _temporalController,
-                        fixedFrameInterval,
-                        options.VideoLagAdjustmentThreshold).AsInput(
-                        new FramePreprocessorInput(preprocessor, true).AsInput(
-                            new AsyncGPUReadbackTransform(new SharedBufferPool((nuint)uncompressedLimit)).AsInput(
+                if (!trackOptions.ForceReadback && encodingSystem.IsBlitSupported())
+                {
+                    return new FrameProviderSubscription(frameProvider, false, onTrackException,
+                        new VideoTemporalAdjuster<IFrameProvider.Frame>(
+                            _temporalController,
+                            fixedFrameInterval,
+                            trackOptions.VideoLagAdjustmentThreshold).AsInput(
+                            new DirectFrameDataTransform().AsInput(
                                 new DroppingChannelInput<LazyVideoFrameData>(
-                                    options.VideoInputQueueSize,
+                                    trackOptions.VideoInputQueueSize,
                                     onLazyVideoFrameDataDropped,
-                                    new VideoEncoderInput(videoEncoder,
-                                        new BoundedEncodedDataBufferVideoInput(buffer).AsAsync()))))));
+                                    new VideoEncoderInput(encodingSystem.CreateVideoEncoder(),
+                                        new BoundedEncodedDataBufferVideoInput(buffer).AsAsync())))));
+                }
+                else
+                {
+                    var preprocessor = FramePreprocessor.WithFixedSize(
+                        (int)trackOptions.VideoOptions.Width,
+                        (int)trackOptions.VideoOptions.Height,
+                        // RGBA to BGRA
+                        new Matrix4x4(new Vector4(0, 0, 1, 0),
+                            new Vector4(0, 1, 0, 0),
+                            new Vector4(1, 0, 0, 0),
+                            new Vector4(0, 0, 0, 1)
+                        ));
+
+                    return new FrameProviderSubscription(frameProvider, false, onTrackException,
+                        new VideoTemporalAdjuster<IFrameProvider.Frame>(
+                            _temporalController,
+                            fixedFrameInterval,
+                            trackOptions.VideoLagAdjustmentThreshold).AsInput(
+                            new FramePreprocessorInput(preprocessor, true).AsInput(
+                                new AsyncGPUReadbackTransform(new SharedBufferPool((nuint)uncompressedLimit)).AsInput(
+                                    new DroppingChannelInput<LazyVideoFrameData>(
+                                        trackOptions.VideoInputQueueSize,
+                                        onLazyVideoFrameDataDropped,
+                                        new VideoEncoderInput(encodingSystem.CreateVideoEncoder(),
+                                            new BoundedEncodedDataBufferVideoInput(buffer).AsAsync()))))));
+                }
             }
 
             var audioInputQueueSizeSeconds = options.AudioInputQueueSizeSeconds ?? 1.0;
             var audioInputQueueSizeSamples = (int)(options.AudioOptions.SampleRate * options.AudioOptions.Channels *
                                                    audioInputQueueSizeSeconds);
 
-            _audioPipeline = new AudioSampleProviderSubscription(audioSampleProvider, disposeAudioSampleProvider,
-                onException,
-                new AudioTemporalAdjuster(
-                    _temporalController,
-                    options.AudioOptions.SampleRate,
-                    options.AudioOptions.Channels,
-                    options.AudioLagAdjustmentThreshold).AsInput(
-                    new PcmAudioFrameDroppingChannelInput(audioInputQueueSizeSamples,
-                        new AudioEncoderInput(audioEncoder,
-                            new BoundedEncodedDataBufferAudioInput(buffer).AsAsync()))));
+            IPipelineSubscription StartAudio(Action<Exception> onTrackException)
+            {
+                return new AudioSampleProviderSubscription(audioSampleProvider, false, onTrackException,
+                    new AudioTemporalAdjuster(
+                        _temporalController,
+                        trackOptions.AudioOptions.SampleRate,
+                        trackOptions.AudioOptions.Channels,
+                        trackOptions.AudioLagAdjustmentThreshold).AsInput(
+                        new PcmAudioFrameDroppingChannelInput(audioInputQueueSizeSamples,
+                            new AudioEncoderInput(encodingSystem.CreateAudioEncoder(),
+                                new BoundedEncodedDataBufferAudioInput(buffer).AsAsync()))));
+            }
+
+            _videoTrack = new TrackSession("video", StartVideo, options.MaxTrackRestarts, onException);
+            _audioTrack = new TrackSession("audio", StartAudio, options.MaxTrackRestarts, onException);
 
             _temporalController.Resume();
             State = SessionState.Recording;
@@ -146,6 +165,18 @@ namespace InstantReplay
 
         public bool IsPaused => _temporalController.IsPaused;
 
+        /// <summary>
+        ///     Whether video recording stopped after a failure, while audio may still be recording. Frames recorded
+        ///     before the failure are still exported.
+        /// </summary>
+        public bool IsVideoTrackFailed => _videoTrack.IsFailed;
+
+        /// <summary>
+        ///     Whether audio recording stopped after a failure, while video may still be recording. Samples recorded
+        ///     before the failure are still exported.
+        /// </summary>
+        public bool IsAudioTrackFailed => _audioTrack.IsFailed;
+
         /// <summary>
         ///     Gets the current state of the session.
         /// </summary>
@@ -160,8 +191,12 @@ namespace InstantReplay
             {
                 if (_disposed) return;
                 _disposed = true;
-                _videoPipeline.Dispose();
-                _audioPipeline.Dispose();
+                _videoTrack.Dispose();
+                _audioTrack.Dispose();
+                if (_disposeFrameProvider)
+                    _frameProvider.Dispose();
+                if (_disposeAudioSampleProvider)
+                    _audioSampleProvider.Dispose();
                 _encodingSystem.Dispose();
                 _buffer.Dispose();
             }
@@ -207,7 +242,8 @@ namespace InstantReplay
                 _temporalController.Pause();
             }
 
-            await Task.WhenAll(_videoPipeline.CompleteAsync().AsTask(), _audioPipeline.CompleteAsync().AsTask());
+            // a failed track is reported to onException, and the frames it recorded are still exported
+            await Task.WhenAll(_videoTrack.CompleteAsync().AsTask(), _audioTrack.CompleteAsync().AsTask());
 
             try
             {
@@ -317,6 +353,40 @@ namespace InstantReplay
             await muxer.CompleteAsync();
         }
 
+        /// <summary>
+        ///     Restarts video recording with a new encoder while audio keeps recording, such as after the video
+        ///     encoder failed. Frames recorded so far are kept; frames queued for the previous encoder are dropped.
+        /// </summary>
+        /// <exception cref="InvalidOperationException">Thrown if called when not in Recording state</exception>
+        public void RestartVideoTrack()
+        {
+            RestartTrack(_videoTrack);
+        }
+
+        /// <summary>
+        ///     Restarts audio recording with a new encoder while video keeps recording, such as after the audio
+        ///     encoder failed. Samples recorded so far are kept; samples queued for the previous encoder are dropped.
+        /// </summary>
+        /// <exception cref="InvalidOperationException">Thrown if called when not in Recording state</exception>
+        public void RestartAudioTrack()
+        {
+            RestartTrack(_audioTrack);
+        }
+
+        private void RestartTrack(TrackSession track)
+        {
+            lock (_lock)
+            {
+                if (_disposed)
+                    throw new ObjectDisposedException(nameof(RealtimeInstantReplaySession));
+
+                if (State != SessionState.Recording)
+                    throw new InvalidOperationException($"Cannot restart a track when state is {State}.");
+
+                track.Restart();
+            }
+        }
+
         /// <summary>
         ///     Pauses the recording.
         /// </summary>
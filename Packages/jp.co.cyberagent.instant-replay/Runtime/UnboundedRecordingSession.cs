@@ -16,6 +16,8 @@ namespace InstantReplay
     public class UnboundedRecordingSession : IDisposable
     {
         private readonly AudioSampleProviderSubscription _audioPipeline;
+        private readonly NullMuxerInput _dryRunAudio;
+        private readonly NullMuxerInput _dryRunVideo;
         private readonly object _lock = new();
         private readonly Muxer _muxer;
         private readonly TemporalController _temporalController = new();
@@ -30,6 +32,20 @@ namespace InstantReplay
             IAudioSampleProvider audioSampleProvider = null,
             bool disposeAudioSampleProvider = true,
             Action<Exception> onException = null)
+            : this(outputPath, false, options, frameProvider, disposeFrameProvider, audioSampleProvider,
+                disposeAudioSampleProvider, onException)
+        {
+        }
+
+        private UnboundedRecordingSession(
+            string outputPath,
+            bool dryRun,
+            RealtimeEncodingOptions options,
+            IFrameProvider frameProvider,
+            bool disposeFrameProvider,
+            IAudioSampleProvider audioSampleProvider,
+            bool disposeAudioSampleProvider,
+            Action<Exception> onException)
         {
             if (frameProvider == null)
             {
@@ -63,7 +79,21 @@ namespace InstantReplay
             using var encodingSystem = new EncodingSystem(options.VideoOptions, options.AudioOptions);
             var videoEncoder = encodingSystem.CreateVideoEncoder();
             var audioEncoder = encodingSystem.CreateAudioEncoder();
-            var muxer = _muxer = encodingSystem.CreateMuxer(outputPath);
+
+            // a dry run discards the encoded frames instead of muxing them, so that devices can be qualified without
+            // filling storage
+            IAsyncPipelineInput<EncodedFrame> videoOutput, audioOutput;
+            if (dryRun)
+            {
+                videoOutput = (_dryRunVideo = new NullMuxerInput()).AsAsync();
+                audioOutput = (_dryRunAudio = new NullMuxerInput()).AsAsync();
+            }
+            else
+            {
+                var muxer = _muxer = encodingSystem.CreateMuxer(outputPath);
+                videoOutput = new MuxerVideoInput(muxer);
+                audioOutput = new MuxerAudioInput(muxer);
+            }
 
             // ReSharper disable once ConvertToLocalFunction
             Action<LazyVideoFrameData> onLazyVideoFrameDataDropped = async static dropped =>
@@ -91,9 +121,7 @@ namespace InstantReplay
                             new DroppingChannelInput<LazyVideoFrameData>(
                                 options.VideoInputQueueSize,
                                 onLazyVideoFrameDataDropped,
-                                new VideoEncoderInput(videoEncoder,
-                                    new MuxerVideoInput(muxer)
-                                )))));
+                                new VideoEncoderInput(videoEncoder, videoOutput)))));
             }
             else
             {
@@ -117,8 +145,7 @@ namespace InstantReplay
                                 new DroppingChannelInput<LazyVideoFrameData>(
                                     options.VideoInputQueueSize,
                                     onLazyVideoFrameDataDropped,
-                                    new VideoEncoderInput(videoEncoder,
-                                        new MuxerVideoInput(muxer)))))));
+                                    new VideoEncoderInput(videoEncoder, videoOutput))))));
             }
 
             var audioInputQueueSizeSeconds = options.AudioInputQueueSizeSeconds ?? 1.0;
@@ -133,14 +160,48 @@ namespace InstantReplay
                     options.AudioOptions.Channels,
                     options.AudioLagAdjustmentThreshold).AsInput(
                     new PcmAudioFrameDroppingChannelInput(audioInputQueueSizeSamples,
-                        new AudioEncoderInput(audioEncoder,
-                            new MuxerAudioInput(muxer)))));
+                        new AudioEncoderInput(audioEncoder, audioOutput))));
 
             _temporalController.Resume();
         }
 
         public bool IsPaused => _temporalController.IsPaused;
 
+        /// <summary>
+        ///     Whether the session was created by <see cref="CreateDryRun" /> and writes no file.
+        /// </summary>
+        public bool IsDryRun => _dryRunVideo != null;
+
+        /// <summary>
+        ///     Creates a session that captures and encodes like a recording but discards the encoded frames instead of
+        ///     writing them to a file, to measure the frame rate and bitrate a device achieves with
+        ///     <see cref="GetDryRunReport" />. Recording starts automatically upon construction.
+        /// </summary>
+        public static UnboundedRecordingSession CreateDryRun(
+            RealtimeEncodingOptions options,
+            IFrameProvider frameProvider = null,
+            bool disposeFrameProvider = true,
+            IAudioSampleProvider audioSampleProvider = null,
+            bool disposeAudioSampleProvider = true,
+            Action<Exception> onException = null)
+        {
+            return new UnboundedRecordingSession(null, true, options, frameProvider, disposeFrameProvider,
+                audioSampleProvider, disposeAudioSampleProvider, onException);
+        }
+
+        /// <summary>
+        ///     Gets the throughput achieved so far by a dry run, which is final once <see cref="CompleteAsync" /> has
+        ///     completed.
+        /// </summary>
+        /// <exception cref="InvalidOperationException">Thrown if the session is not a dry run</exception>
+        public DryRunReport GetDryRunReport()
+        {
+            if (!IsDryRun)
+                throw new InvalidOperationException("The session is not a dry run.");
+
+            return new DryRunReport(_dryRunVideo, _dryRunAudio);
+        }
+
         /// <summary>
         ///     Disposes the session and releases all resources.
         /// </summary>
@@ -173,12 +234,15 @@ namespace InstantReplay
         }
 
         /// <summary>
-        ///     Completes the recording and finalizes the output file.
+        ///     Completes the recording and finalizes the output file, or logs the report of a dry run.
         /// </summary>
         public async ValueTask CompleteAsync()
         {
             await Task.WhenAll(_videoPipeline.CompleteAsync().AsTask(), _audioPipeline.CompleteAsync().AsTask());
-            await _muxer.CompleteAsync();
+            if (IsDryRun)
+                ILogger.LogCore($"Dry run completed: {GetDryRunReport()}");
+            else
+                await _muxer.CompleteAsync();
         }
     }
 }
@@ -22,6 +22,8 @@ namespace UniEnc.Unity
             _mainThread = Thread.CurrentThread;
             MainThreadContext = SynchronizationContext.Current;
 
+            Utils.SetProjectColorSpace(QualitySettings.activeColorSpace == ColorSpace.Gamma);
+
             var system = PlayerLoop.GetCurrentPlayerLoop();
             InsertAfter<Update, Update.ScriptRunBehaviourUpdate>(
                 new PlayerLoopSystem
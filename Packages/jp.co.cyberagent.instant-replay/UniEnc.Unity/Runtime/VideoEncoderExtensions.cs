@@ -2,20 +2,39 @@ using System;
 using System.Threading.Tasks;
 using UnityEngine;
 using UnityEngine.Experimental.Rendering;
+using UnityEngine.Rendering;
 
 namespace UniEnc.Unity
 {
     public static class VideoEncoderExtensions
     {
+        /// <param name="stereoMode">
+        ///     Eyes to capture when <paramref name="source" /> is an XR texture array with a slice per eye
+        /// </param>
+        /// <param name="projection">
+        ///     <see cref="Projection.Equirectangular" /> when <paramref name="source" /> is a cubemap to unwrap into a
+        ///     360 degree frame
+        /// </param>
         public static ValueTask PushFrameAsync(this VideoEncoder encoder, Texture source, double timestamp,
-            bool flipVertically = false)
+            bool flipVertically = false, StereoMode stereoMode = StereoMode.Mono,
+            Projection projection = Projection.Flat)
         {
+            if (projection == Projection.Equirectangular)
+            {
+                if (source.dimension != TextureDimension.Cube)
+                    throw new ArgumentException("Equirectangular capture requires a cubemap.", nameof(source));
+            }
+            else if (stereoMode != StereoMode.Mono && source.dimension != TextureDimension.Tex2DArray)
+            {
+                throw new ArgumentException("Stereo capture requires a texture array.", nameof(source));
+            }
+
             var textureHandle = TextureHandle.Alloc(source);
             try
             {
                 return encoder.UnsafePushUnityFrameAsync(textureHandle, (uint)source.width,
                     (uint)source.height, source.graphicsFormat, QualitySettings.activeColorSpace == ColorSpace.Gamma,
-                    timestamp, flipVertically);
+                    timestamp, flipVertically, stereoMode, projection);
             }
             catch (ObjectDisposedException)
             {
@@ -26,10 +45,12 @@ namespace UniEnc.Unity
 
         public static ValueTask UnsafePushUnityFrameAsync(this VideoEncoder encoder, TextureHandle textureHandle,
             uint width, uint height, GraphicsFormat graphicsFormat, bool isGammaWorkflow, double timestamp,
-            bool flipVertically = false)
+            bool flipVertically = false, StereoMode stereoMode = StereoMode.Mono,
+            Projection projection = Projection.Flat)
         {
             return encoder.UnsafePushTextureTokenAsync(textureHandle.value, width, height, (uint)graphicsFormat,
-                isGammaWorkflow, timestamp, (nuint)GraphicsEventIssuer.OnIssueGraphicsEventPtr, flipVertically);
+                isGammaWorkflow, timestamp, (nuint)GraphicsEventIssuer.OnIssueGraphicsEventPtr, flipVertically,
+                stereoMode, projection);
         }
 
         [Obsolete("Use the overload that takes a TextureHandle instead of a raw native texture pointer.", true)]
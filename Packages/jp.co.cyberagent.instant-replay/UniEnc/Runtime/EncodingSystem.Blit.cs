@@ -18,5 +18,41 @@ namespace UniEnc
                 }
             }
         }
+
+        /// <summary>
+        ///     Whether video encoders keep the alpha channel when <see cref="VideoEncoderOptions.PreserveAlpha" /> is
+        ///     set.
+        /// </summary>
+        public bool IsAlphaSupported()
+        {
+            lock (_lock)
+            {
+                _ = _handle ?? throw new ObjectDisposedException(nameof(EncodingSystem));
+
+                unsafe
+                {
+                    return NativeMethods.unienc_is_alpha_supported(
+                        (PlatformEncodingSystem*)_handle.DangerousGetHandle());
+                }
+            }
+        }
+
+        /// <summary>
+        ///     Whether video encoders can encode <paramref name="codec" />. ProRes is available on macOS and Linux,
+        ///     DNxHR on Linux.
+        /// </summary>
+        public bool IsCodecSupported(VideoCodec codec)
+        {
+            lock (_lock)
+            {
+                _ = _handle ?? throw new ObjectDisposedException(nameof(EncodingSystem));
+
+                unsafe
+                {
+                    return NativeMethods.unienc_is_codec_supported(
+                        (PlatformEncodingSystem*)_handle.DangerousGetHandle(), (UniencVideoCodec)codec);
+                }
+            }
+        }
     }
 }
@@ -135,49 +135,96 @@ namespace UniEnc
         /// <summary>
         ///     Creates a new muxer for combining video and audio streams.
         /// </summary>
+        /// <param name="outputPath">
+        ///     Path of the file to write, in a directory that exists. URIs such as Android <c>content://</c> ones are
+        ///     not paths; open them and use <see cref="CreateMuxer(int)" />.
+        /// </param>
         public Muxer CreateMuxer(string outputPath)
+        {
+            if (string.IsNullOrEmpty(outputPath))
+                throw new ArgumentNullException(nameof(outputPath));
+
+            unsafe
+            {
+                var pathBytes = Encoding.UTF8.GetBytes(outputPath + '\0');
+                fixed (byte* pathPtr = pathBytes)
+                {
+                    return CreateMuxerCore(pathPtr, -1, IntPtr.Zero);
+                }
+            }
+        }
+
+        /// <summary>
+        ///     Creates a new muxer writing to an open file descriptor, such as the one of
+        ///     <c>ContentResolver.openFileDescriptor(uri, "rw")</c> for an Android scoped storage document.
+        ///     The descriptor stays owned by the caller and must stay open until the muxer completes. On Android it
+        ///     must be a seekable file; elsewhere the file is written into the descriptor once the muxer completes,
+        ///     so it may also be a pipe. Not supported on Windows; use <see cref="CreateMuxer(IntPtr)" />.
+        /// </summary>
+        public Muxer CreateMuxer(int fileDescriptor)
+        {
+            if (fileDescriptor < 0)
+                throw new ArgumentOutOfRangeException(nameof(fileDescriptor));
+
+            unsafe
+            {
+                return CreateMuxerCore(null, fileDescriptor, IntPtr.Zero);
+            }
+        }
+
+        /// <summary>
+        ///     Creates a new muxer writing to an open file handle, such as the one of a <c>FileStream</c> or a pipe,
+        ///     once the muxer completes. The handle stays owned by the caller and must stay open until then. Only
+        ///     supported on Windows.
+        /// </summary>
+        public Muxer CreateMuxer(IntPtr fileHandle)
+        {
+            if (fileHandle == IntPtr.Zero)
+                throw new ArgumentNullException(nameof(fileHandle));
+
+            unsafe
+            {
+                return CreateMuxerCore(null, -1, fileHandle);
+            }
+        }
+
+        private unsafe Muxer CreateMuxerCore(byte* pathPtr, int fileDescriptor, IntPtr fileHandle)
         {
             lock (_lock)
             {
                 _ = _handle ?? throw new ObjectDisposedException(nameof(EncodingSystem));
 
-                if (string.IsNullOrEmpty(outputPath))
-                    throw new ArgumentNullException(nameof(outputPath));
+                Mutex* videoInput = null;
+                Mutex* audioInput = null;
+                Mutex* completionHandle = null;
 
-                unsafe
-                {
-                    Mutex* videoInput = null;
-                    Mutex* audioInput = null;
-                    Mutex* completionHandle = null;
+                var context = CallbackHelper.SimpleCallbackContext.Rent();
+                var contextHandle = CallbackHelper.CreateSendPtr(context);
+                var task = context.Task;
 
-                    var context = CallbackHelper.SimpleCallbackContext.Rent();
-                    var contextHandle = CallbackHelper.CreateSendPtr(context);
-                    var task = context.Task;
+                using var runtime = RuntimeWrapper.GetScope();
 
-                    var pathBytes = Encoding.UTF8.GetBytes(outputPath + '\0');
-                    fixed (byte* pathPtr = pathBytes)
-                    {
-                        using var runtime = RuntimeWrapper.GetScope();
-
-                        var success = NativeMethods.unienc_new_muxer(
-                            runtime.Runtime,
-                            (PlatformEncodingSystem*)_handle.DangerousGetHandle(),
-                            pathPtr,
-                            &videoInput,
-                            &audioInput,
-                            &completionHandle,
-                            CallbackHelper.GetSimpleCallbackPtr(),
-                            contextHandle);
-
-                        if (task.IsCompleted)
-                            task.GetAwaiter().GetResult(); // throws if there was an error
-
-                        if (!success || videoInput == null || audioInput == null || completionHandle == null)
-                            throw new UniEncException(UniencErrorKind.InitializationError, "Failed to create muxer");
-
-                        return new Muxer((IntPtr)videoInput, (IntPtr)audioInput, (IntPtr)completionHandle);
-                    }
-                }
+                var system = (PlatformEncodingSystem*)_handle.DangerousGetHandle();
+                bool success;
+                if (pathPtr != null)
+                    success = NativeMethods.unienc_new_muxer(runtime.Runtime, system, pathPtr, &videoInput,
+                        &audioInput, &completionHandle, CallbackHelper.GetSimpleCallbackPtr(), contextHandle);
+                else if (fileHandle != IntPtr.Zero)
+                    success = NativeMethods.unienc_new_muxer_with_handle(runtime.Runtime, system,
+                        (void*)fileHandle, &videoInput, &audioInput, &completionHandle,
+                        CallbackHelper.GetSimpleCallbackPtr(), contextHandle);
+                else
+                    success = NativeMethods.unienc_new_muxer_with_fd(runtime.Runtime, system, fileDescriptor,
+                        &videoInput, &audioInput, &completionHandle, CallbackHelper.GetSimpleCallbackPtr(),
+                        contextHandle);
+
+                if (task.IsCompleted)
+                    task.GetAwaiter().GetResult(); // throws if there was an error
+
+                if (!success || videoInput == null || audioInput == null || completionHandle == null)
+                    throw new UniEncException(UniencErrorKind.InitializationError, "Failed to create muxer");
+
+                return new Muxer((IntPtr)videoInput, (IntPtr)audioInput, (IntPtr)completionHandle);
             }
         }
 
@@ -26,6 +26,45 @@ namespace UniEnc.Native
         [DllImport(__DllName, EntryPoint = "unienc_audio_encoder_push", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_audio_encoder_push(Runtime* runtime, SendPtr input, SendPtr data, nuint sample_count, ulong timestamp_in_samples, nuint callback, SendPtr user_data);
 
+        /// <summary>
+        ///  Enables loudness-spike and voice-activity detection on the audio pushed after this call. The
+        ///  callback is invoked with each hint from the thread pushing audio, for as long as the input is
+        ///  alive. `audio_options` must match the ones the encoding system was created with.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_audio_encoder_set_highlight_callback", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_audio_encoder_set_highlight_callback(Runtime* runtime, SendPtr input, AudioEncoderOptionsNative* audio_options, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Measures the loudness of the audio pushed after this call into `meter`, which can be shared by
+        ///  several inputs to measure their audio together.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_audio_encoder_set_loudness_meter", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_audio_encoder_set_loudness_meter(Runtime* runtime, SendPtr input, Mutex* meter, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Delivers the audio pushed after this call as it is encoded, with the gain and drift
+        ///  compensation applied, for a level meter in the recording UI. The callback is invoked with each
+        ///  push from the thread pushing audio, for as long as the input is alive. System audio captured
+        ///  by `unienc_audio_encoder_set_system_audio_capture` is mixed in later and not included.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_audio_encoder_set_monitor_callback", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_audio_encoder_set_monitor_callback(Runtime* runtime, SendPtr input, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Scales the audio pushed after this call by `gain_db`. Used when transcoding an export to apply
+        ///  the gain reported by `unienc_audio_loudness_get`; remuxed audio is copied as is.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_audio_encoder_set_gain", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_audio_encoder_set_gain(Runtime* runtime, SendPtr input, double gain_db, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Starts or stops mixing the audio other applications play (such as voice chat) into the pushed
+        ///  samples. Only supported on Windows, where it uses WASAPI loopback capture of the default output
+        ///  device.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_audio_encoder_set_system_audio_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_audio_encoder_set_system_audio_capture(Runtime* runtime, SendPtr input, [MarshalAs(UnmanagedType.U1)] bool enabled, nuint callback, SendPtr user_data);
+
         [DllImport(__DllName, EntryPoint = "unienc_audio_encoder_pull", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_audio_encoder_pull(Runtime* runtime, SendPtr output, nuint callback, SendPtr user_data);
 
@@ -35,6 +74,400 @@ namespace UniEnc.Native
         [DllImport(__DllName, EntryPoint = "unienc_free_audio_encoder_output", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_free_audio_encoder_output(Runtime* runtime, SendPtr audio_output);
 
+        /// <summary>
+        ///  Delivers the peak/RMS values accumulated so far, one point per `interval` seconds. Call after
+        ///  the audio input has been finished to get the complete waveform. The data is only valid during
+        ///  the callback.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_audio_waveform_get", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_audio_waveform_get(Runtime* runtime, Mutex* waveform, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_audio_waveform", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_audio_waveform(Runtime* runtime, Mutex* waveform);
+
+        /// <summary>
+        ///  `audio_options` must match the ones the encoding system was created with.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_audio_loudness_meter", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern Mutex* unienc_new_audio_loudness_meter(AudioEncoderOptionsNative* audio_options);
+
+        /// <summary>
+        ///  Delivers the integrated loudness measured so far and the gain in dB that normalizes it to
+        ///  `target_lufs` (-16 is a common target for online video) without the sample peak exceeding
+        ///  -1 dBFS. Fails if no audio above the EBU R128 absolute gate has been measured.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_audio_loudness_get", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_audio_loudness_get(Runtime* runtime, Mutex* meter, double target_lufs, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_audio_loudness_meter", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_audio_loudness_meter(Runtime* runtime, Mutex* meter);
+
+        /// <summary>
+        ///  Measures `stage` with `frames` synthetic frames at the size of `video_options`. Mux writes a
+        ///  file at `output_path`, which is removed afterwards and is ignored by the other stages.
+        ///  `callback` is called once the run completes.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_run_benchmark", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_run_benchmark(Runtime* runtime, VideoEncoderOptionsNative* video_options, AudioEncoderOptionsNative* audio_options, UniencBenchmarkStage stage, uint frames, byte* output_path, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Measures blitting the texture of `texture_token`, the size of `video_options`, into a video
+        ///  encoder `frames` times. Blitted frames are encoded as they are pushed, so the result includes
+        ///  encoding; compare it with the `Encode` stage at the same size. `callback` is called once the
+        ///  run completes.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_run_blit_benchmark", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_run_blit_benchmark(Runtime* runtime, VideoEncoderOptionsNative* video_options, AudioEncoderOptionsNative* audio_options, nuint texture_token, uint graphics_format, [MarshalAs(UnmanagedType.U1)] bool is_gamma_workflow, uint frames, nuint issue_graphics_event_callback, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Encodes `frames` frames of moving color bars the backend draws into a texture of its own and
+        ///  blits like a Unity texture, to test the GPU path on a device without Unity. The graphics events
+        ///  are issued through `issue_graphics_event_callback`, or run on the runtime when it is 0, such as
+        ///  in a host that set its own device. Fails where the backend cannot blit. `callback` is called
+        ///  once the run completes.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_run_test_pattern", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_run_test_pattern(Runtime* runtime, VideoEncoderOptionsNative* video_options, AudioEncoderOptionsNative* audio_options, uint frames, nuint issue_graphics_event_callback, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  `retention` is the number of seconds kept behind the newest cue, usually the length of the
+        ///  recording buffer. Zero or less keeps every cue.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_caption_track", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern Mutex* unienc_new_caption_track(Runtime* runtime, double retention);
+
+        /// <summary>
+        ///  Shows the UTF-8 `text` from `start` to `end`. Cues overlapping a later one are cut at its start
+        ///  in the timed text track.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_caption_push", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_caption_push(Runtime* runtime, Mutex* track, double start, double end, byte* text, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Writes the cues between `start_timestamp` and `end_timestamp` to `path` as WebVTT. Pass the
+        ///  timestamp of the first video frame written to the output file as `start_timestamp` so the
+        ///  captions share the video's timeline.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_caption_write_webvtt", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_caption_write_webvtt(Runtime* runtime, Mutex* track, byte* path, double start_timestamp, double end_timestamp, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Adds the cues between `start_timestamp` and `end_timestamp` to the finished MP4 file at `path`
+        ///  as a timed text track in `language`, an ISO 639-2 code such as `eng`, or `und` if null. Pass the
+        ///  timestamp of the first video frame written to the file as `start_timestamp`. The file is left
+        ///  as is if no cue is shown.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_caption_add_track", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_caption_add_track(Runtime* runtime, Mutex* track, byte* path, byte* language, double start_timestamp, double end_timestamp, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_caption_track", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_caption_track(Runtime* runtime, Mutex* track);
+
+        /// <summary>
+        ///  `fps_hint` should be the one of the video encoder options.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_media_clock", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern Mutex* unienc_new_media_clock(Runtime* runtime, uint fps_hint);
+
+        /// <summary>
+        ///  Continues the output timeline from the latest pushed sample when the host clock has been reset.
+        ///  Backward resets are also detected without calling this.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_media_clock_rebase", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_media_clock_rebase(Runtime* runtime, Mutex* clock);
+
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_set_clock", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_set_clock(Runtime* runtime, SendPtr input, Mutex* clock, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  `audio_options` must match the ones the encoding system was created with.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_audio_encoder_set_clock", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_audio_encoder_set_clock(Runtime* runtime, SendPtr input, AudioEncoderOptionsNative* audio_options, Mutex* clock, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Slowly resamples pushed audio so that it stays in sync with the wall clock, which video
+        ///  timestamps follow, over long recordings. Works with or without a media clock.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_audio_encoder_set_drift_compensation", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_audio_encoder_set_drift_compensation(Runtime* runtime, SendPtr input, AudioEncoderOptionsNative* audio_options, [MarshalAs(UnmanagedType.U1)] bool enabled, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_audio_encoder_get_drift_stats", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_audio_encoder_get_drift_stats(Runtime* runtime, SendPtr input, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_media_clock", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_media_clock(Runtime* runtime, Mutex* clock);
+
+        /// <summary>
+        ///  Makes pushes to encoder and muxer inputs started afterwards fail with `TimeoutError` when they
+        ///  take more than `seconds`, including the wait for earlier pushes to the same input. Zero or less
+        ///  lets them wait indefinitely, which is the default. An encoder whose push timed out may not
+        ///  accept further samples, so the recording should be treated as failed.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_set_push_deadline", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_set_push_deadline(double seconds);
+
+        /// <summary>
+        ///  Makes the pushes to encoder and muxer inputs in progress fail right away, such as before
+        ///  tearing down a recording whose encoder stopped responding. Later pushes are not affected.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_cancel_pending_pushes", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_cancel_pending_pushes();
+
+        [DllImport(__DllName, EntryPoint = "unienc_new_decoder", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_decoder(Runtime* runtime, PlatformEncodingSystem* system, byte* input_path, Mutex** decoder_out, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Pulls the next decoded BGRA frame. The callback receives null data after the last frame. The
+        ///  frame data is only valid during the callback.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_decoder_pull", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_decoder_pull(Runtime* runtime, SendPtr decoder, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Seeks to `timestamp` seconds and delivers the frame shown at that time, following the same
+        ///  callback contract as `unienc_decoder_pull`. With `precise` unset, the preceding keyframe is
+        ///  returned instead where the platform supports it.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_decoder_seek", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_decoder_seek(Runtime* runtime, SendPtr decoder, double timestamp, [MarshalAs(UnmanagedType.U1)] bool precise, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_decoder", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_decoder(Runtime* runtime, SendPtr decoder);
+
+        /// <summary>
+        ///  Checks that the native library was built for this platform and that its backend works here,
+        ///  before any runtime or encoding system is created. `callback` is called synchronously with one
+        ///  entry per check; the report is only valid during the callback.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_self_test", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_self_test(nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Checks that recording with these options can work before the host offers to record: the backend
+        ///  self test including graphics interception, then codec, alpha, resolution and the free space in
+        ///  `output_dir`, which may be null to skip that check. `callback` is called synchronously with one
+        ///  entry per check; the report is only valid during the callback.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_check_support", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_check_support(Runtime* runtime, VideoEncoderOptionsNative* video_options, AudioEncoderOptionsNative* audio_options, byte* output_dir, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Recommends the bitrate, frame rate and keyframe interval to record frames of `width` by
+        ///  `height` with on this device, from what its encoder supports, along with the audio bitrate.
+        ///  Needs no runtime or encoding system. `callback` is called synchronously.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_get_recommended_options", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_get_recommended_options(uint width, uint height, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Keeps the last `capacity` bytes of native log lines and pipeline events in memory so they can be
+        ///  attached to a bug report when an export fails, discarding those kept so far. A `capacity` of
+        ///  zero stops keeping them.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_set_log_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_set_log_capture(nuint capacity);
+
+        /// <summary>
+        ///  Passes the kept log lines to `callback` synchronously; they are only valid during the callback.
+        ///  Fails if the log capture is off.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_dump_log_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_dump_log_capture(nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Puts the native profiler markers in the Unity profiler `category`, or in the builtin `Other`
+        ///  category if it is 0xFFFF, and prepends `prefix` to their names, so captures with markers of
+        ///  other native plugins stay apart. `prefix` may be null for none. Fails once a marker was created,
+        ///  so call it before the first recording. `callback` is called synchronously.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_set_profiler_naming", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_set_profiler_naming(ushort category, byte* prefix, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Reports where each track of the export in progress stands, such as when finishing it does not
+        ///  call back: the last sample the muxer accepted and the encoder pulls, muxer pushes and finishes
+        ///  still in flight. The progress covers the whole process and is reset when a muxer is created.
+        ///  `callback` is called synchronously.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_get_pipeline_progress", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_get_pipeline_progress(nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Creates encoders recording `video_options` and `audio_options` to `output_path`, a UTF-8 file
+        ///  path, while publishing the same frames, scaled to the size and bitrate of
+        ///  `stream_video_options`, to `url`: over SRT for `srt://`, WHIP for `http(s)://` authorizing with
+        ///  `bearer_token` if not null, and RTMP otherwise. The stream reconnects like
+        ///  `unienc_new_rtmp_muxer`, calling `on_stream_event` with `stream_user_data`.
+        ///
+        ///  When the stream fails for good or a push to it stalls, it is detached and `on_stream_error` is
+        ///  called once with `stream_user_data`; the file is recorded as if alone. Only frames in memory can
+        ///  be pushed to `video_input_out`. `on_complete` is called with the result of the file once both
+        ///  inputs have been freed and it is written, and `sink_out` is freed after that.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_dual_sink", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_dual_sink(Runtime* runtime, VideoEncoderOptionsNative* video_options, AudioEncoderOptionsNative* audio_options, VideoEncoderOptionsNative* stream_video_options, byte* output_path, byte* url, byte* bearer_token, UniencRetryPolicy* retry_policy, nuint on_stream_event, nuint on_stream_error, SendPtr stream_user_data, DualSink** sink_out, Mutex** video_input_out, Mutex** audio_input_out, nuint on_complete, SendPtr on_complete_user_data, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Same as `unienc_video_encoder_push_shared_buffer_with_format`, for the video input of a dual
+        ///  sink.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_dual_sink_video_input_push_shared_buffer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_dual_sink_video_input_push_shared_buffer(Runtime* runtime, SendPtr input, SendPtr buffer, uint width, uint height, uint stride, UniencPixelFormat pixel_format, double timestamp, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Frees the video input of a dual sink, which ends the video of both the file and the stream.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_free_dual_sink_video_input", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_dual_sink_video_input(Runtime* runtime, SendPtr video_input);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_dual_sink", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_dual_sink(DualSink* sink);
+
+        [DllImport(__DllName, EntryPoint = "unienc_new_frame_snapshot", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern FrameSnapshot* unienc_new_frame_snapshot(Runtime* runtime);
+
+        /// <summary>
+        ///  Keeps a copy of every frame pushed to `input` afterwards as a shared buffer in `snapshot`,
+        ///  after filters. Blitted frames are not kept. A null `snapshot` stops keeping frames.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_set_frame_snapshot", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_set_frame_snapshot(Runtime* runtime, SendPtr input, FrameSnapshot* snapshot, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Copies the BGRA pixels of the latest frame kept, rows packed without padding, into `data` if
+        ///  its `len` is at least `width * height * 4` bytes, and reports the frame. When `data` is too
+        ///  small nothing is copied and `copied` is false, so a buffer can be sized from `width` and
+        ///  `height`. Frames keep being encoded meanwhile. `callback` is called synchronously.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_frame_snapshot_copy", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_frame_snapshot_copy(Runtime* runtime, FrameSnapshot* snapshot, byte* data, nuint len, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_frame_snapshot", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_frame_snapshot(Runtime* runtime, FrameSnapshot* snapshot);
+
+        /// <summary>
+        ///  Keeps the stats of up to `capacity` frames between drains.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_frame_stats", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern FrameStatsRing* unienc_new_frame_stats(Runtime* runtime, nuint capacity);
+
+        /// <summary>
+        ///  Records the stats of frames pulled from `output` afterwards.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_set_frame_stats", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_set_frame_stats(Runtime* runtime, SendPtr output, FrameStatsRing* stats, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Takes the recorded frames, oldest first. `callback` is called synchronously, and the list is
+        ///  only valid during it.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_frame_stats_drain", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_frame_stats_drain(Runtime* runtime, FrameStatsRing* stats, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_frame_stats", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_frame_stats(Runtime* runtime, FrameStatsRing* stats);
+
+        /// <summary>
+        ///  Creates or truncates the spool file at `path`, with `slot_count` slots of `slot_size` bytes,
+        ///  discarding an export interrupted there. `quality` ranges from 0.0 to 1.0,
+        ///  `max_bytes_per_second` limits the disk writes or is 0 for no limit, and `queue_capacity` is the
+        ///  number of frames waiting to be written before pushes fail.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_jpeg_spool", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_jpeg_spool(Runtime* runtime, byte* path, uint slot_count, uint slot_size, float quality, UniencJpegSubsampling subsampling, ulong max_bytes_per_second, uint queue_capacity, JpegSpool** spool_out, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Queues a frame to be written into `slot`, replacing the frame there. The callback is invoked
+        ///  from the writer thread once the slot is complete, or right away if the queue is full. Rows are
+        ///  `stride` bytes apart, or tightly packed if `stride` is 0.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_jpeg_spool_push", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_jpeg_spool_push(Runtime* runtime, JpegSpool* spool, uint slot, double timestamp, SendPtr buffer, uint width, uint height, uint stride, UniencPixelFormat pixel_format, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Delivers the frames written so far, oldest first. `callback` is called synchronously, and the
+        ///  list is only valid during it.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_jpeg_spool_frames", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_jpeg_spool_frames(Runtime* runtime, JpegSpool* spool, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Starts exporting the frames written so far to `output_path`: pushes are rejected and the spool
+        ///  file is kept if the app quits, until `unienc_jpeg_spool_finish_export`. `metadata` of `size`
+        ///  bytes is kept for the next launch, such as the export settings, and may be null if `size` is 0.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_jpeg_spool_begin_export", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_jpeg_spool_begin_export(Runtime* runtime, JpegSpool* spool, byte* output_path, byte* metadata, nuint size, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Records how far the export has got, such as the timestamp of the last frame muxed. Each call
+        ///  rewrites the checkpoint, so call it every few seconds rather than per frame.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_jpeg_spool_set_export_progress", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_jpeg_spool_set_export_progress(Runtime* runtime, JpegSpool* spool, double progress, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Ends the export, whether or not it succeeded, and removes its checkpoint.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_jpeg_spool_finish_export", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_jpeg_spool_finish_export(Runtime* runtime, JpegSpool* spool, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Looks for an export interrupted while reading the spool file at `path`, to be called on launch
+        ///  before the spool is created again. `callback` is called synchronously, and the export is only
+        ///  valid during it. The partially written output cannot be completed, so the export is run again
+        ///  from the frames, then `unienc_discard_interrupted_export` is called.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_find_interrupted_export", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_find_interrupted_export(byte* path, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Removes the spool file at `path` and the checkpoint of its interrupted export.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_discard_interrupted_export", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_discard_interrupted_export(byte* path, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Waits for the queued frames to be written, then removes the spool file unless it is being
+        ///  exported.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_free_jpeg_spool", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_jpeg_spool(Runtime* runtime, JpegSpool* spool);
+
+        /// <summary>
+        ///  Creates encoders for renditions of a clip of `video_options` and `audio_options`: the source
+        ///  size and each of the `count` short sides in `short_sides` below it, or 1080, 720 and 480 if
+        ///  `short_sides` is null. Each rendition is written to `{name}_{label}.mp4` in `output_directory`,
+        ///  such as `clip_720p.mp4`. No playlist is written, as the files are not segmented.
+        ///
+        ///  Only frames in memory can be pushed to `video_input_out`, which scales them for each rendition.
+        ///  `on_complete` is called once both inputs have been freed and every file is written, and
+        ///  `export_out` is freed after that.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_ladder_export", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_ladder_export(Runtime* runtime, VideoEncoderOptionsNative* video_options, AudioEncoderOptionsNative* audio_options, uint* short_sides, nuint count, byte* output_directory, byte* name, LadderExport** export_out, Mutex** video_input_out, Mutex** audio_input_out, nuint on_complete, SendPtr on_complete_user_data, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Same as `unienc_video_encoder_push_shared_buffer_with_format`, for the video input of a ladder
+        ///  export.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_ladder_video_input_push_shared_buffer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_ladder_video_input_push_shared_buffer(Runtime* runtime, SendPtr input, SendPtr buffer, uint width, uint height, uint stride, UniencPixelFormat pixel_format, double timestamp, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Frees the video input of a ladder export, which ends the video of every rendition.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_free_ladder_video_input", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_ladder_video_input(Runtime* runtime, SendPtr video_input);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_ladder_export", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_ladder_export(LadderExport* export);
+
         [DllImport(__DllName, EntryPoint = "unienc_muxer_push_video", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_muxer_push_video(Runtime* runtime, SendPtr video_input, SendPtr data, nuint size, double timestamp, nuint callback, SendPtr user_data);
 
@@ -47,9 +480,74 @@ namespace UniEnc.Native
         [DllImport(__DllName, EntryPoint = "unienc_muxer_finish_audio", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_muxer_finish_audio(Runtime* runtime, SendPtr audio_input, nuint callback, SendPtr user_data);
 
+        /// <summary>
+        ///  Completes the file once both inputs are finished. If no video sample was pushed, the file is
+        ///  removed and this fails with `EmptyRecording` right away on every platform.
+        /// </summary>
         [DllImport(__DllName, EntryPoint = "unienc_muxer_complete", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_muxer_complete(Runtime* runtime, SendPtr completion_handle, nuint callback, SendPtr user_data);
 
+        /// <summary>
+        ///  Tags the file as monoscopic equirectangular 360 video. The spherical metadata is written into
+        ///  the finished file when the muxer is completed, so this must be called before that.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_muxer_set_spherical", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_muxer_set_spherical(Runtime* runtime, SendPtr completion_handle, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Adds a timecode track starting at `start_unix_seconds`, the wallclock time of the first frame,
+        ///  counting `frame_rate` frames per second. The track is written into the finished file when the
+        ///  muxer is completed, so this must be called before that.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_muxer_set_timecode", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_muxer_set_timecode(Runtime* runtime, SendPtr completion_handle, double start_unix_seconds, uint frame_rate, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Stops the muxer once samples reach `max_duration_seconds` after the first pushed one. Samples
+        ///  beyond the limit are dropped, including those already in flight, and each track is finished at
+        ///  its first such sample. Once both are finished, the muxer is completed and `on_complete` is
+        ///  called with the result.
+        ///
+        ///  Call this before pushing samples. A track that gets no sample past the limit is finished by
+        ///  its regular finish call, which keeps succeeding for tracks finished by the limit. The
+        ///  completion handle is consumed, so `unienc_muxer_complete` must not be called once
+        ///  `on_complete` has fired. `on_complete` is not called if the limit is never reached.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_muxer_set_max_duration", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_muxer_set_max_duration(Runtime* runtime, SendPtr video_input, SendPtr audio_input, SendPtr completion_handle, double max_duration_seconds, nuint on_complete, SendPtr on_complete_user_data, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Delivers each encoded video sample pushed after this call to `callback` as well, serialized
+        ///  as `unienc_video_encoder_pull` delivers it, such as for a custom network sender. The callback is
+        ///  invoked from the thread pushing samples, for as long as the input is alive. Samples past a limit
+        ///  set with `unienc_muxer_set_limits` are still delivered.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_muxer_set_video_sample_callback", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_muxer_set_video_sample_callback(Runtime* runtime, SendPtr video_input, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Same as `unienc_muxer_set_video_sample_callback`, for encoded audio samples.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_muxer_set_audio_sample_callback", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_muxer_set_audio_sample_callback(Runtime* runtime, SendPtr audio_input, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Like `unienc_muxer_set_max_duration`, also stopping the muxer before the file exceeds
+        ///  `max_output_bytes`, at the end of the last GOP expected to fit. A GOP larger than all previous
+        ///  ones is cut short instead, so the file never exceeds the size. Either limit may be 0 for none,
+        ///  and `on_complete` is called when the first is reached.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_muxer_set_limits", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_muxer_set_limits(Runtime* runtime, SendPtr video_input, SendPtr audio_input, SendPtr completion_handle, double max_duration_seconds, ulong max_output_bytes, nuint on_complete, SendPtr on_complete_user_data, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Video bitrate in bits per second that keeps a file of `duration_seconds` under
+        ///  `max_output_bytes` with audio of `audio_bitrate`, leaving a margin for rate control and the
+        ///  container. 0 if the audio alone does not fit.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_fit_video_bitrate", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern uint unienc_fit_video_bitrate(ulong max_output_bytes, double duration_seconds, uint audio_bitrate);
+
         [DllImport(__DllName, EntryPoint = "unienc_free_muxer_video_input", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_free_muxer_video_input(SendPtr video_input);
 
@@ -59,12 +557,392 @@ namespace UniEnc.Native
         [DllImport(__DllName, EntryPoint = "unienc_free_muxer_completion_handle", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_free_muxer_completion_handle(SendPtr completion_handle);
 
+        /// <summary>
+        ///  Number of encoded samples each track of muxers created afterwards queues before pushes wait
+        ///  for the file writer. Only supported on iOS and macOS.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_muxer_set_queue_capacity", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_muxer_set_queue_capacity(nuint capacity, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Sets the layout of the MP4 and QuickTime files written by muxers created afterwards: with the
+        ///  index moved to the front for streaming players, or in fragments so that a recording cut short
+        ///  by a crash remains playable. Only supported with the ffmpeg backend.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_ffmpeg_set_file_layout", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_ffmpeg_set_file_layout(UniencFileLayout* layout, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_new_h264_packetizer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_h264_packetizer(Runtime* runtime, PlatformEncodingSystem* system, Mutex** packetizer_out, nuint on_error, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_new_aac_packetizer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_aac_packetizer(Runtime* runtime, PlatformEncodingSystem* system, Mutex** packetizer_out, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Pushes a single H.264 access unit in Annex-B format. The first access unit must be an IDR
+        ///  frame carrying SPS and PPS.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_muxer_push_h264", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_muxer_push_h264(Runtime* runtime, SendPtr video_input, SendPtr packetizer, SendPtr data, nuint size, double timestamp, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Pushes a single raw AAC-LC frame without ADTS header.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_muxer_push_aac", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_muxer_push_aac(Runtime* runtime, SendPtr audio_input, SendPtr packetizer, SendPtr data, nuint size, ulong timestamp_in_samples, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_h264_packetizer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_h264_packetizer(SendPtr packetizer);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_aac_packetizer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_aac_packetizer(SendPtr packetizer);
+
+        /// <summary>
+        ///  Sets how the ffmpeg backend starts the processes it runs afterwards: with the game's
+        ///  environment cleared down to what ffmpeg needs, extra variables, another working directory or a
+        ///  lower priority. Processes are started without a console window and killed once dropped unless
+        ///  `options` says otherwise, and reaped either way. Only supported with the ffmpeg backend.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_ffmpeg_set_process_options", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_ffmpeg_set_process_options(UniencProcessOptions* options, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Keeps up to `max_memory_bytes` of encoded samples, evicting the oldest GOPs first. 0 means
+        ///  unlimited.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_replay_buffer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern ReplayBufferSession* unienc_new_replay_buffer(Runtime* runtime, nuint max_memory_bytes);
+
+        /// <summary>
+        ///  Takes the encoder outputs and forwards every sample pulled from them into the buffer.
+        ///  `callback` is called once both encoders have ended, or with the first error.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_replay_buffer_attach", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_replay_buffer_attach(Runtime* runtime, ReplayBufferSession* session, SendPtr video_output, SendPtr audio_output, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Writes the samples of the last `duration_seconds` that `filter` keeps to an MP4 file at
+        ///  `output_path`, starting at the closest keyframe. 0 or less exports everything buffered. The
+        ///  track of a kind the filter drops is written empty. Exported samples are removed from the buffer,
+        ///  which keeps receiving new ones.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_replay_buffer_export", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_replay_buffer_export(Runtime* runtime, ReplayBufferSession* session, PlatformEncodingSystem* system, byte* output_path, double duration_seconds, UniencExportFilter filter, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Stops forwarding samples from attached encoders, whose outputs are dropped.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_free_replay_buffer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_replay_buffer(Runtime* runtime, ReplayBufferSession* session);
+
+        /// <summary>
+        ///  `retention` is the number of seconds kept behind the newest event, usually the length of the
+        ///  recording buffer. Zero or less keeps every event.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_replay_data_track", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern Mutex* unienc_new_replay_data_track(Runtime* runtime, double retention);
+
+        /// <summary>
+        ///  `data` is copied and may be null when `size` is zero.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_replay_data_push", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_replay_data_push(Runtime* runtime, Mutex* track, double timestamp, uint kind, byte* data, nuint size, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Sets the offset of a shared reference clock measured at `timestamp`, so the written sidecar
+        ///  carries sync markers on that clock. `offset` is the seconds added to a pipeline timestamp to get
+        ///  the reference time and `uncertainty` its error bound, usually half the round trip to the time
+        ///  server.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_replay_data_set_clock_offset", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_replay_data_set_clock_offset(Runtime* runtime, Mutex* track, double timestamp, double offset, double uncertainty, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Writes the events between `start_timestamp` and `end_timestamp` to `path`. Pass the timestamp of
+        ///  the first video frame written to the output file as `start_timestamp` so the sidecar shares the
+        ///  video's timeline.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_replay_data_write", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_replay_data_write(Runtime* runtime, Mutex* track, byte* path, double start_timestamp, double end_timestamp, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_replay_data_track", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_replay_data_track(Runtime* runtime, Mutex* track);
+
+        /// <summary>
+        ///  Starts capturing into `video_input` and, if not null, `audio_input`. The handle is written to
+        ///  `capture_out` immediately and `callback` reports whether the capture actually started. Nothing
+        ///  else should be pushed to the inputs until the capture is stopped.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_start_replay_kit_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_start_replay_kit_capture(Runtime* runtime, SendPtr video_input, SendPtr audio_input, AudioEncoderOptionsNative* audio_options, Mutex** capture_out, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_stop_replay_kit_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_stop_replay_kit_capture(Runtime* runtime, SendPtr capture, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Freeing does not stop a running capture.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_free_replay_kit_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_replay_kit_capture(Runtime* runtime, SendPtr capture);
+
+        /// <summary>
+        ///  Starts capturing into `video_input`, scaled to the size in `video_options`. The handle is
+        ///  written to `capture_out` immediately and `callback` reports whether the capture actually
+        ///  started. Nothing else should be pushed to the input until the capture is stopped.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_start_screen_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_start_screen_capture(Runtime* runtime, SendPtr video_input, VideoEncoderOptionsNative* video_options, UniencScreenCaptureTarget target, Mutex** capture_out, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_stop_screen_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_stop_screen_capture(Runtime* runtime, SendPtr capture, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Freeing does not stop a running capture.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_free_screen_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_screen_capture(Runtime* runtime, SendPtr capture);
+
+        /// <summary>
+        ///  Records the samples pushed to video and audio encoders from now on, discarding a capture in
+        ///  progress. Pixels and audio are kept until they add up to `data_limit` bytes, and only their
+        ///  hashes afterwards; replays push synthetic frames and silence in their place.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_start_session_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_start_session_capture(nuint data_limit);
+
+        /// <summary>
+        ///  Stops the capture in progress and writes it to `output_path`, or discards it if
+        ///  `output_path` is null. Fails if no capture is on.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_stop_session_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_stop_session_capture(Runtime* runtime, byte* output_path, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Replays the capture at `capture_path` into new encoders created with `video_options` and
+        ///  `audio_options`, pushing its samples in their captured order and writing the result to
+        ///  `output_path`. Runs on its own encoding system, so it must not overlap a recording. `callback`
+        ///  is called once the file is complete.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_replay_session_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_replay_session_capture(Runtime* runtime, VideoEncoderOptionsNative* video_options, AudioEncoderOptionsNative* audio_options, byte* capture_path, byte* output_path, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Creates the directory of a new session in `root`, creating `root` as well if needed.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_session_dir", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_session_dir(byte* root, SessionDir** session_out, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Passes the path of the session directory to `callback` synchronously; it is only valid during
+        ///  the callback.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_session_dir_path", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_session_dir_path(SessionDir* session, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Removes the session directory with everything in it and frees `session`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_complete_session_dir", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_complete_session_dir(Runtime* runtime, SessionDir* session, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Keeps the session directory, marking it as failed for `reason`, and frees `session`. `reason`
+        ///  may be null if there is none to give.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_fail_session_dir", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_fail_session_dir(SessionDir* session, byte* reason);
+
+        /// <summary>
+        ///  Frees `session`, keeping its directory as failed if it was not completed.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_free_session_dir", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_session_dir(SessionDir* session);
+
+        /// <summary>
+        ///  Removes the session directories in `root` last modified at least `retention_seconds` ago,
+        ///  except those of this process, whether they failed or were left by a crash. Other files in
+        ///  `root` are left alone.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_purge_stale_session_dirs", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_purge_stale_session_dirs(Runtime* runtime, byte* root, double retention_seconds, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Creates an encoding system with encoders for a clip ready to be shared, written to
+        ///  `output_path`: scaled down to 720p, with a capped bitrate, audio normalized to the loudness
+        ///  measured by `loudness_meter` (which may be null), at most 60 seconds long and with the movie
+        ///  header at the front. `max_output_bytes` also keeps the file under a size, or is 0.
+        ///
+        ///  `video_options` and `audio_options` describe the source and `duration_seconds` its expected
+        ///  length; they are overwritten with the options used. Blitted frames are scaled, while pushed
+        ///  frames must have the frame size of the options used. Encoded samples are muxed natively, and
+        ///  `on_complete` is called once both inputs have been freed and the file is written. `system_out`
+        ///  is freed after that.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_share_export", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_share_export(Runtime* runtime, VideoEncoderOptionsNative* video_options, AudioEncoderOptionsNative* audio_options, double duration_seconds, Mutex* loudness_meter, ulong max_output_bytes, byte* output_path, PlatformEncodingSystem** system_out, Mutex** video_input_out, Mutex** audio_input_out, nuint on_complete, SendPtr on_complete_user_data, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  `quality` is only used for JPEG and ranges from 0.0 to 1.0. `Bgra32` delivers the read-back
+        ///  pixels without encoding them. Fails where blitting is not supported.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_still_image_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_still_image_capture(Runtime* runtime, PlatformEncodingSystem* system, UniencStillImageFormat format, float quality, Mutex** capture_out, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Captures a blit source and delivers the encoded image. The image data is only valid during the
+        ///  callback.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_still_image_capture_push_blit_source", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_still_image_capture_push_blit_source(Runtime* runtime, SendPtr capture, nuint texture_token, uint width, uint height, uint graphics_format, [MarshalAs(UnmanagedType.U1)] bool flip_vertically, [MarshalAs(UnmanagedType.U1)] bool is_gamma_workflow, double timestamp, nuint issue_graphics_event_callback, nuint callback, SendPtr user_data);
+
+        [DllImport(__DllName, EntryPoint = "unienc_free_still_image_capture", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_free_still_image_capture(Runtime* runtime, SendPtr capture);
+
         [DllImport(__DllName, EntryPoint = "unienc_video_encoder_push_shared_buffer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_video_encoder_push_shared_buffer(Runtime* runtime, SendPtr input, SendPtr buffer, uint width, uint height, double timestamp, nuint callback, SendPtr user_data);
 
+        /// <summary>
+        ///  Pushes pixels in `pixel_format`, such as RGBA or RGB565 GPU readbacks, which are converted to
+        ///  BGRA natively. Rows are `stride` bytes apart, or tightly packed if `stride` is 0, so padded
+        ///  readbacks can be pushed without repacking.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_push_shared_buffer_with_format", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_push_shared_buffer_with_format(Runtime* runtime, SendPtr input, SendPtr buffer, uint width, uint height, uint stride, UniencPixelFormat pixel_format, double timestamp, nuint callback, SendPtr user_data);
+
         [DllImport(__DllName, EntryPoint = "unienc_video_encoder_push_blit_source", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_video_encoder_push_blit_source(Runtime* runtime, SendPtr input, nuint texture_token, uint width, uint height, uint graphics_format, [MarshalAs(UnmanagedType.U1)] bool flip_vertically, [MarshalAs(UnmanagedType.U1)] bool is_gamma_workflow, double timestamp, nuint issue_graphics_event_callback, nuint callback, SendPtr user_data);
 
+        /// <summary>
+        ///  Pushes a blit source that may be an XR texture array. `width` and `height` are the size of a
+        ///  single eye; with `SideBySide` both eyes are fitted into the frame next to each other.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_push_blit_source_with_stereo_mode", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_push_blit_source_with_stereo_mode(Runtime* runtime, SendPtr input, nuint texture_token, uint width, uint height, uint graphics_format, [MarshalAs(UnmanagedType.U1)] bool flip_vertically, [MarshalAs(UnmanagedType.U1)] bool is_gamma_workflow, UniencStereoMode stereo_mode, double timestamp, nuint issue_graphics_event_callback, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Pushes a blit source that may also be a cubemap. With `Equirectangular` the cubemap is
+        ///  unwrapped to fill the whole frame and `stereo_mode` is ignored; pair it with
+        ///  `unienc_muxer_set_spherical` so players recognize the file as 360 video.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_push_blit_source_with_projection", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_push_blit_source_with_projection(Runtime* runtime, SendPtr input, nuint texture_token, uint width, uint height, uint graphics_format, [MarshalAs(UnmanagedType.U1)] bool flip_vertically, [MarshalAs(UnmanagedType.U1)] bool is_gamma_workflow, UniencStereoMode stereo_mode, UniencProjection projection, double timestamp, nuint issue_graphics_event_callback, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Records the device display through a MediaProjection (a `jobject` the user granted) instead of
+        ///  pushed frames. Only supported on Android, and only before anything has been pushed to `input`.
+        ///  `timestamp` is the current time on the clock the other samples are pushed with.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_start_media_projection", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_start_media_projection(Runtime* runtime, SendPtr input, void* media_projection, uint density_dpi, double timestamp, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Encodes every frame pushed to `input` afterwards with `tier_input` too, reusing the same blit,
+        ///  so that an encoding system created with smaller video options records a lower quality tier of
+        ///  the same frames. Frames are scaled by the tier encoder, whose output is pulled as usual.
+        ///  `tier_input` is consumed and must not be pushed to. A tier that fails to encode a frame is
+        ///  dropped, ending its output, without failing the push to `input`. Only supported on iOS and
+        ///  macOS.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_add_tier", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_add_tier(Runtime* runtime, SendPtr input, SendPtr tier_input, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Retimes frames pushed afterwards to exactly `numerator`/`denominator` frames per second (such
+        ///  as 30000/1001), starting at the next frame. Missing frames are repeated and extra ones dropped,
+        ///  or with `blend`, output frames are blended from the two closest shared buffer frames. A
+        ///  `numerator` of 0 passes frames through with their own timestamps again.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_set_frame_rate", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_set_frame_rate(Runtime* runtime, SendPtr input, uint numerator, uint denominator, [MarshalAs(UnmanagedType.U1)] bool blend, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Detects shared buffer frames identical to the previous one from the next frame on, such as on
+        ///  menus and pause screens. `Skip` leaves them out, holding the previous frame for up to a second
+        ///  at a time, so the video has a variable frame rate; `Repeat` encodes an exact copy of the
+        ///  previous frame instead, which takes the fewest bits. Blitted frames are never detected.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_set_dedup", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_set_dedup(Runtime* runtime, SendPtr input, UniencDedupMode mode, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Records a time-lapse from the next frame on: only every `factor`th frame pushed is encoded, and
+        ///  the kept frames are retimed to follow each other at the interval frames are pushed at, so the
+        ///  video plays `factor` times as fast at the normal frame rate. Audio pushed to `audio_input`,
+        ///  which may be null, is left out meanwhile, and `audio_muxer_input`, which may be null too, stops
+        ///  holding the video back. Audio resuming at normal speed is moved back to follow the video on the
+        ///  clock set on both encoders. A `factor` of 0 or 1 records at normal speed again.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_set_timelapse", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_set_timelapse(Runtime* runtime, SendPtr input, SendPtr audio_input, SendPtr audio_muxer_input, uint factor, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Asks the encoder for a keyframe at shared buffer frames whose luma histogram differs from the
+        ///  previous frame by more than `threshold`, the share of pixels changing brightness (around 0.5
+        ///  for hard cuts only), so seeking in exported replays lands on the new scene. Keyframes are
+        ///  requested at most twice a second. 0 turns detection off. Media Foundation and ffmpeg cannot be
+        ///  asked for keyframes and ignore it.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_set_scene_cut_keyframes", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_set_scene_cut_keyframes(Runtime* runtime, SendPtr input, float threshold, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Blurs `count` regions of the frames pushed to `input` afterwards, after the filters added
+        ///  before. Each pixel in a region becomes the average of the pixels up to `radius` away inside it.
+        ///  Only frames pushed as shared buffers can be blurred; blitted frames are rejected from then on.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_add_blur_regions", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_add_blur_regions(Runtime* runtime, SendPtr input, UniencRegion* regions, nuint count, uint radius, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Passes the frames pushed to `input` afterwards to `process` (a `UniencFilterCallback`), after
+        ///  the filters added before. Blitted frames are passed to `process_texture` (a
+        ///  `UniencTextureFilterCallback`) instead, or rejected if it is null. `filter_user_data` is passed
+        ///  to both until `input` is freed or its filters are cleared.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_add_filter_callback", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_add_filter_callback(Runtime* runtime, SendPtr input, nuint process, nuint process_texture, SendPtr filter_user_data, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Removes the filters added to `input`, starting with the next frame.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_clear_filters", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_clear_filters(Runtime* runtime, SendPtr input, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Downscales every `interval`-th frame pushed to `input` afterwards into JPEG sprite sheets of
+        ///  `columns` x `rows` tiles, written next to `video_path` with a JSON index of the tile timestamps.
+        ///  Only frames pushed as shared buffers get thumbnails. `quality` ranges from 0.0 to 1.0.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_set_storyboard", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_set_storyboard(Runtime* runtime, SendPtr input, byte* video_path, uint interval, uint tile_width, uint tile_height, uint columns, uint rows, float quality, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Writes the remaining sheet and the index of the storyboard, and reports the first failure to
+        ///  write a sheet, which stops adding thumbnails without interrupting encoding.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_finish_storyboard", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_finish_storyboard(Runtime* runtime, SendPtr input, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Writes every frame pushed to `input` afterwards as an RGBA PNG into `directory`, keeping the
+        ///  alpha channel on encoding systems that cannot encode it. Only frames pushed as shared buffers
+        ///  are written.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_set_png_sequence", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_set_png_sequence(Runtime* runtime, SendPtr input, byte* directory, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Writes the index of the PNG sequence, and reports the first failure to write a frame, which
+        ///  stops the sequence without interrupting encoding.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_video_encoder_finish_png_sequence", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_video_encoder_finish_png_sequence(Runtime* runtime, SendPtr input, nuint callback, SendPtr user_data);
+
         [DllImport(__DllName, EntryPoint = "unienc_video_encoder_pull", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_video_encoder_pull(Runtime* runtime, SendPtr output, nuint callback, SendPtr user_data);
 
@@ -74,6 +952,38 @@ namespace UniEnc.Native
         [DllImport(__DllName, EntryPoint = "unienc_free_video_encoder_output", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_free_video_encoder_output(Runtime* runtime, SendPtr video_output);
 
+        /// <summary>
+        ///  Chooses the H.264 encoder of video encoders created afterwards. `preferred` is an encoder name
+        ///  used whenever the device has it, or null. When MediaCodec picks an encoder on the denylist,
+        ///  another one is used instead. `denylist` holds `denylist_count` names, which match by prefix
+        ///  when they end with `*`; a null `denylist` keeps the built-in one. Android only.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_android_set_video_encoder_policy", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_android_set_video_encoder_policy(byte* preferred, byte** denylist, nuint denylist_count, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Lists the H.264 encoders of the device, in MediaCodec's order of preference. `callback` is
+        ///  called synchronously, and the list is only valid during it. Android only.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_android_list_video_encoders", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_android_list_video_encoders(nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Sets how long video encoders created afterwards may hold frames without output before they are
+        ///  flushed. The frames they held are dropped and the recording continues from a keyframe after
+        ///  the gap. `seconds` of zero or less never flushes; the default is 5. Android only.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_android_set_video_stall_timeout", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_android_set_video_stall_timeout(double seconds, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Stats of the latest video encoder still alive, with the metrics MediaCodec reported at its
+        ///  start, its latest stall or the end of its input. `callback` is called synchronously, and the
+        ///  codec name is only valid during it. Android only.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_android_get_video_codec_stats", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_android_get_video_codec_stats(nuint callback, SendPtr user_data);
+
         [DllImport(__DllName, EntryPoint = "unienc_new_runtime", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern Runtime* unienc_new_runtime();
 
@@ -89,6 +999,14 @@ namespace UniEnc.Native
         [DllImport(__DllName, EntryPoint = "unienc_free_encoding_system", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_free_encoding_system(PlatformEncodingSystem* system);
 
+        /// <summary>
+        ///  Frees the system once the tasks of its components have finished, reporting the errors that
+        ///  `unienc_free_encoding_system` ignores. `system` must not be used or freed afterwards, and a new
+        ///  system can be created right away.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_shutdown_encoding_system", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_shutdown_encoding_system(Runtime* runtime, PlatformEncodingSystem* system, nuint callback, SendPtr user_data);
+
         [DllImport(__DllName, EntryPoint = "unienc_new_video_encoder", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         [return: MarshalAs(UnmanagedType.U1)]
         internal static extern bool unienc_new_video_encoder(Runtime* runtime, PlatformEncodingSystem* system, Mutex** input_out, Mutex** output_out, nuint on_error, SendPtr user_data);
@@ -97,17 +1015,148 @@ namespace UniEnc.Native
         [return: MarshalAs(UnmanagedType.U1)]
         internal static extern bool unienc_new_audio_encoder(Runtime* runtime, PlatformEncodingSystem* system, Mutex** input_out, Mutex** output_out, nuint on_error, SendPtr user_data);
 
+        /// <summary>
+        ///  Same as `unienc_new_audio_encoder`, but also accumulates peak/RMS values of the pushed audio.
+        ///  `audio_options` must match the ones the encoding system was created with.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_audio_encoder_with_waveform", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_audio_encoder_with_waveform(Runtime* runtime, PlatformEncodingSystem* system, AudioEncoderOptionsNative* audio_options, Mutex** input_out, Mutex** output_out, Mutex** waveform_out, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Creates a muxer writing to `output_path`, a UTF-8 file path in a directory that exists. URIs
+        ///  such as Android `content://` ones are rejected; open them and use `unienc_new_muxer_with_fd`.
+        /// </summary>
         [DllImport(__DllName, EntryPoint = "unienc_new_muxer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         [return: MarshalAs(UnmanagedType.U1)]
         internal static extern bool unienc_new_muxer(Runtime* runtime, PlatformEncodingSystem* system, byte* output_path, Mutex** video_input_out, Mutex** audio_input_out, Mutex** completion_handle_out, nuint on_error, SendPtr user_data);
 
+        /// <summary>
+        ///  Creates a muxer writing to the open file descriptor `fd`, such as one of an Android scoped
+        ///  storage document opened with mode "rw", an app sandbox file or a socket. The descriptor stays
+        ///  owned by the caller and must stay open until the muxer completes. On Android it must be a
+        ///  seekable file written by `MediaMuxer` in place; elsewhere a temporary file is muxed and copied
+        ///  into the descriptor from its current position on completion, so it may also be a pipe or a
+        ///  socket. Not supported on Windows; use `unienc_new_muxer_with_handle`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_muxer_with_fd", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_muxer_with_fd(Runtime* runtime, PlatformEncodingSystem* system, int fd, Mutex** video_input_out, Mutex** audio_input_out, Mutex** completion_handle_out, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Creates a muxer writing to the open file `handle`, such as one of an app sandbox file or a
+        ///  pipe. The handle stays owned by the caller and must stay open until the muxer completes. A
+        ///  temporary file is muxed and copied into the handle from its current position on completion.
+        ///  Only supported on Windows.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_muxer_with_handle", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_muxer_with_handle(Runtime* runtime, PlatformEncodingSystem* system, void* handle, Mutex** video_input_out, Mutex** audio_input_out, Mutex** completion_handle_out, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Creates a muxer publishing FLV to the RTMP `url` of an ingest server, such as
+        ///  `rtmp://live.twitch.tv/app/&lt;stream key&gt;`, instead of writing a file. The encoders are created
+        ///  and pushed to as for a recording; finishing the muxer ends the stream. Only H.264 can be
+        ///  published, and only with the ffmpeg backend; the others fail. With `retry_policy`, the
+        ///  connection is opened again when it fails, and the samples pushed meanwhile are buffered and sent
+        ///  once reconnected; `on_event` (a `UniencDataCallback&lt;UniencReconnectEvent&gt;`), which may be 0, is
+        ///  called with `event_user_data` on each change of the connection. With a null `retry_policy`,
+        ///  pushes fail with the connection.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_rtmp_muxer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_rtmp_muxer(Runtime* runtime, PlatformEncodingSystem* system, byte* url, UniencRetryPolicy* retry_policy, nuint on_event, SendPtr event_user_data, Mutex** video_input_out, Mutex** audio_input_out, Mutex** completion_handle_out, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Creates a muxer publishing over WebRTC to the WHIP endpoint `url`, for spectating with less
+        ///  than a second of latency, authorizing with `bearer_token`, which may be null for none. The
+        ///  encoders are created and pushed to as for a recording, and the audio is transcoded to Opus;
+        ///  finishing the muxer ends the session. Only H.264 can be published, and only with the ffmpeg
+        ///  backend on ffmpeg 8 or later, whose `whip` muxer runs the WebRTC session out of process; the
+        ///  others fail. Reconnects like `unienc_new_rtmp_muxer`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_whip_muxer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_whip_muxer(Runtime* runtime, PlatformEncodingSystem* system, byte* url, byte* bearer_token, UniencRetryPolicy* retry_policy, nuint on_event, SendPtr event_user_data, Mutex** video_input_out, Mutex** audio_input_out, Mutex** completion_handle_out, nuint on_error, SendPtr user_data);
+
+        /// <summary>
+        ///  Creates a muxer publishing MPEG-TS over SRT to `url`, such as
+        ///  `srt://ingest.example.com:9000?streamid=match1&amp;passphrase=...`, with the SRT options in its
+        ///  query. The encoders are created and pushed to as for a recording; finishing the muxer ends the
+        ///  stream. Only H.264 can be published, and only with the ffmpeg backend built with libsrt; the
+        ///  others fail. Reconnects like `unienc_new_rtmp_muxer`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_new_srt_muxer", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_new_srt_muxer(Runtime* runtime, PlatformEncodingSystem* system, byte* url, UniencRetryPolicy* retry_policy, nuint on_event, SendPtr event_user_data, Mutex** video_input_out, Mutex** audio_input_out, Mutex** completion_handle_out, nuint on_error, SendPtr user_data);
+
         [DllImport(__DllName, EntryPoint = "unienc_is_blit_supported", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         [return: MarshalAs(UnmanagedType.U1)]
         internal static extern bool unienc_is_blit_supported(PlatformEncodingSystem* system);
 
+        [DllImport(__DllName, EntryPoint = "unienc_is_alpha_supported", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_is_alpha_supported(PlatformEncodingSystem* system);
+
+        [DllImport(__DllName, EntryPoint = "unienc_is_codec_supported", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool unienc_is_codec_supported(PlatformEncodingSystem* system, UniencVideoCodec codec);
+
         [DllImport(__DllName, EntryPoint = "unienc_free_graphics_event_context", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         internal static extern void unienc_free_graphics_event_context(void* context);
 
+        /// <summary>
+        ///  Reports the color space of the Unity project, so blit sources pushed with a contradicting
+        ///  `is_gamma_workflow` are warned about. Call it once when the plugin loads.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_set_project_color_space", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_set_project_color_space([MarshalAs(UnmanagedType.U1)] bool is_gamma_workflow);
+
+        /// <summary>
+        ///  Limits how far the pools backing in-flight Vulkan blits may grow. Blits beyond the limits are
+        ///  dropped and counted in `unienc_vulkan_get_pool_stats`. Android builds with the `blit` feature
+        ///  only; can be called at any time.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_vulkan_set_pool_limits", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_vulkan_set_pool_limits(uint max_descriptor_sets, uint max_fences, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  `callback` is called synchronously.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_vulkan_get_pool_stats", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_vulkan_get_pool_stats(nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Captures the GPU work of the next Metal blit, which is labelled "unienc blit" with a debug group
+        ///  around each draw. `output` is the path of a `.gputrace` document to write, which needs
+        ///  `MetalCaptureEnabled` in the app's Info.plist, or null to open the capture in an attached
+        ///  Xcode. Apple builds with the `blit` feature only.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_metal_capture_next_blit", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_metal_capture_next_blit(byte* output, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Blits with the Vulkan device of a host that does not load the plugin from Unity, such as a
+        ///  custom engine or a test harness. The host runs the graphics events of blits itself: it writes
+        ///  the `VkImage*` of the texture to the context it is given, then calls the given function with
+        ///  event id 0 and the context on a thread that may submit to `queue`. Android builds with the
+        ///  `blit` feature only; call it before the first blit. Fails if a device is already in use.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_vulkan_set_device", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_vulkan_set_device(void* get_instance_proc_addr, void* instance, void* device, uint queue_family_index, void* queue, nuint callback, SendPtr user_data);
+
+        /// <summary>
+        ///  Blits with the Metal device of a host that does not load the plugin from Unity, such as a
+        ///  custom engine or a test harness. Blits are submitted to `command_queue`, or to a queue of their
+        ///  own if it is null, and the host must commit the work writing a source texture before the
+        ///  graphics event of its blit runs. The host runs the graphics events of blits itself: it
+        ///  writes the `MTLTexture` to the context it is given, then calls the given function with event id
+        ///  0 and the context. Apple builds with the `blit` feature only; call it before the first blit.
+        ///  Fails if a device is already in use.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "unienc_metal_set_device", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void unienc_metal_set_device(void* device, void* command_queue, nuint callback, SendPtr user_data);
+
         [DllImport(__DllName, EntryPoint = "unienc_new_shared_buffer_pool", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         [return: MarshalAs(UnmanagedType.U1)]
         internal static extern bool unienc_new_shared_buffer_pool(nuint limit, Mutex** pool_out, nuint _on_error, void* _user_data);
@@ -123,9 +1172,14 @@ namespace UniEnc.Native
         internal static extern void unienc_free_shared_buffer(SharedBuffer* buffer);
 
         [DllImport(__DllName, EntryPoint = "unienc_dummy", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
-        internal static extern void unienc_dummy(UniencErrorKind _error_kind, UniencErrorNative _error_native, UniencSampleData _sample);
+        internal static extern void unienc_dummy(UniencErrorKind _error_kind, UniencErrorNative _error_native, UniencSampleData _sample, UniencDecodedFrameData _decoded_frame, UniencStillImageData _still_image, UniencWaveformData _waveform, UniencHighlightHint _highlight_hint, UniencSelfTestReport _self_test_report, UniencDriftStats _drift_stats, UniencLoudness _loudness, UniencAudioSamples _audio_samples, UniencVulkanPoolStats _vulkan_pool_stats, UniencVideoCodecStats _video_codec_stats, UniencBenchmarkResult _benchmark_result, UniencFrameSnapshot _frame_snapshot, UniencEncoderList _encoder_list, UniencFrameStatsList _frame_stats, UniencSpooledFrameList _spooled_frames, UniencInterruptedExport _interrupted_export, UniencLogCapture _log_capture, UniencPipelineProgress _pipeline_progress, UniencReconnectEvent _reconnect_event, UniencRecommendedOptions _recommended_options, UniencSessionReplay _session_replay, UniencPurgeReport _purge_report, UniencSessionDirPath _session_dir_path);
+
 
+    }
 
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct ReplayBufferSession
+    {
     }
 
     [StructLayout(LayoutKind.Sequential)]
@@ -137,6 +1191,451 @@ namespace UniEnc.Native
         public UniencSampleKind kind;
     }
 
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencDecodedFrameData
+    {
+        public byte* data;
+        public nuint size;
+        public uint width;
+        public uint height;
+        public double timestamp;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencStillImageData
+    {
+        public byte* data;
+        public nuint size;
+        public uint width;
+        public uint height;
+        public double timestamp;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencWaveformPoint
+    {
+        public float peak;
+        public float rms;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencWaveformData
+    {
+        public UniencWaveformPoint* points;
+        public nuint count;
+        public double interval;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencHighlightHint
+    {
+        public UniencHighlightKind kind;
+        public double timestamp;
+        public float score;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencDriftStats
+    {
+        /// <summary>
+        ///  Seconds the audio clock is ahead of the wall clock.
+        /// </summary>
+        public double drift;
+        /// <summary>
+        ///  Seconds of audio removed (positive) or inserted (negative) so far.
+        /// </summary>
+        public double correction;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencLoudness
+    {
+        /// <summary>
+        ///  Integrated loudness, in LUFS.
+        /// </summary>
+        public double integrated;
+        /// <summary>
+        ///  Largest absolute sample value, normalized to 0.0..=1.0.
+        /// </summary>
+        public float peak;
+        /// <summary>
+        ///  Gain in dB that normalizes the audio to the requested target.
+        /// </summary>
+        public double gain;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencAudioSamples
+    {
+        /// <summary>
+        ///  Interleaved samples, valid only during the callback.
+        /// </summary>
+        public short* data;
+        public nuint count;
+        public ulong timestamp_in_samples;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencFrameStats
+    {
+        public double timestamp;
+        /// <summary>
+        ///  Encoded size in bytes.
+        /// </summary>
+        public ulong size;
+        [MarshalAs(UnmanagedType.U1)] public bool is_key;
+        /// <summary>
+        ///  Average quantization parameter, or -1 when the encoder does not report it.
+        /// </summary>
+        public int qp;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencFrameStatsList
+    {
+        public UniencFrameStats* frames;
+        public nuint count;
+        /// <summary>
+        ///  Frames recorded but overwritten before this drain.
+        /// </summary>
+        public ulong dropped;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencSpooledFrame
+    {
+        public uint slot;
+        public double timestamp;
+        /// <summary>
+        ///  Encoded JPEG.
+        /// </summary>
+        public byte* data;
+        public nuint size;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencSpooledFrameList
+    {
+        public UniencSpooledFrame* frames;
+        public nuint count;
+    }
+
+    /// <summary>
+    ///  Export interrupted in a previous session. `found` is false if there was none.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencInterruptedExport
+    {
+        [MarshalAs(UnmanagedType.U1)] public bool found;
+        /// <summary>
+        ///  UTF-8, null-terminated.
+        /// </summary>
+        public byte* output_path;
+        public double progress;
+        public byte* metadata;
+        public nuint metadata_size;
+        public UniencSpooledFrameList frames;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencVulkanPoolStats
+    {
+        public uint descriptor_sets;
+        public uint descriptor_set_limit;
+        /// <summary>
+        ///  Blits dropped because no descriptor set was available.
+        /// </summary>
+        public ulong descriptor_set_starvations;
+        public uint fences;
+        public uint fence_limit;
+        public ulong fence_starvations;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencVideoCodecStats
+    {
+        /// <summary>
+        ///  Null if the device does not report it.
+        /// </summary>
+        public byte* codec_name;
+        /// <summary>
+        ///  Zero if the device does not report it.
+        /// </summary>
+        public int width;
+        public int height;
+        /// <summary>
+        ///  Microseconds from queueing a frame to its output, or -1 if the device does not report it.
+        /// </summary>
+        public long latency_avg_us;
+        public long latency_max_us;
+        public ulong frames_queued;
+        public ulong frames_output;
+        /// <summary>
+        ///  Times the encoder stalled and was flushed.
+        /// </summary>
+        public uint stalls;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencBenchmarkResult
+    {
+        public uint frames;
+        public double seconds;
+        public double frames_per_second;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencSessionReplay
+    {
+        public uint video_frames;
+        public uint audio_samples;
+        /// <summary>
+        ///  Frames and audio samples pushed in place of content the capture did not keep.
+        /// </summary>
+        public uint synthesized;
+        /// <summary>
+        ///  From the first push until the file was complete.
+        /// </summary>
+        public double seconds;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencPurgeReport
+    {
+        public uint removed;
+        /// <summary>
+        ///  Directories within the retention period, of this process, or that could not be removed.
+        /// </summary>
+        public uint kept;
+    }
+
+    /// <summary>
+    ///  Path of a session directory.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencSessionDirPath
+    {
+        /// <summary>
+        ///  UTF-8, not null-terminated.
+        /// </summary>
+        public byte* data;
+        public nuint len;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencFrameSnapshot
+    {
+        public uint width;
+        public uint height;
+        public double timestamp;
+        /// <summary>
+        ///  False when the buffer was too small, and nothing was copied.
+        /// </summary>
+        [MarshalAs(UnmanagedType.U1)] public bool copied;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencEncoderInfo
+    {
+        public byte* name;
+        /// <summary>
+        ///  Always false before Android 10, which cannot tell.
+        /// </summary>
+        [MarshalAs(UnmanagedType.U1)] public bool hardware_accelerated;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencEncoderList
+    {
+        public UniencEncoderInfo* encoders;
+        public nuint count;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencDiagnosticCheck
+    {
+        public byte* name;
+        [MarshalAs(UnmanagedType.U1)] public bool passed;
+        public byte* message;
+    }
+
+    /// <summary>
+    ///  Native log lines and pipeline events kept by the log capture, oldest first.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencLogCapture
+    {
+        /// <summary>
+        ///  UTF-8, not null-terminated.
+        /// </summary>
+        public byte* data;
+        public nuint len;
+    }
+
+    /// <summary>
+    ///  Progress of one track, in seconds, where -1 means none.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencTrackProgress
+    {
+        /// <summary>
+        ///  Timestamp of the last sample the muxer accepted, valid when `since_accepted` is not -1.
+        /// </summary>
+        public double last_accepted;
+        public double since_accepted;
+        /// <summary>
+        ///  Time the oldest operation in flight at each stage has been running.
+        /// </summary>
+        public double encoder_pull;
+        public double muxer_push;
+        public double sink_write;
+        /// <summary>
+        ///  The stage in flight the longest, where the track is most likely blocked.
+        /// </summary>
+        public UniencPipelineStage blocked;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencPipelineProgress
+    {
+        public UniencTrackProgress video;
+        public UniencTrackProgress audio;
+    }
+
+    /// <summary>
+    ///  When a network output reconnects after its connection fails. Durations are in seconds.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencRetryPolicy
+    {
+        /// <summary>
+        ///  Attempts made before the output gives up; 0 gives up right away.
+        /// </summary>
+        public uint max_attempts;
+        /// <summary>
+        ///  Wait before the first attempt, doubled after each failed attempt.
+        /// </summary>
+        public double initial_backoff;
+        public double max_backoff;
+        /// <summary>
+        ///  Seconds of samples buffered per track while disconnected.
+        /// </summary>
+        public double max_buffered;
+    }
+
+    /// <summary>
+    ///  Layout of the files written by the ffmpeg backend.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencFileLayout
+    {
+        /// <summary>
+        ///  Whether to move the index of a file ahead of its media data once it is written.
+        /// </summary>
+        [MarshalAs(UnmanagedType.U1)] public bool faststart;
+        /// <summary>
+        ///  Whether to write fragments each playable once written, so that a file cut short by a crash
+        ///  plays up to its last one.
+        /// </summary>
+        [MarshalAs(UnmanagedType.U1)] public bool fragmented;
+        /// <summary>
+        ///  Seconds a fragment may last at most, or 0 to start fragments at keyframes only.
+        /// </summary>
+        public double fragment_duration;
+    }
+
+    /// <summary>
+    ///  How the ffmpeg backend starts its processes.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencProcessOptions
+    {
+        /// <summary>
+        ///  Whether to pass only the variables ffmpeg needs of the game's environment, such as `PATH`.
+        /// </summary>
+        [MarshalAs(UnmanagedType.U1)] public bool clear_environment;
+        /// <summary>
+        ///  `environment_count` variables as `NAME=VALUE`, set on top of the environment.
+        /// </summary>
+        public byte** environment;
+        public nuint environment_count;
+        /// <summary>
+        ///  Directory the processes run in, or null for the working directory of the game.
+        /// </summary>
+        public byte* working_directory;
+        /// <summary>
+        ///  Added to the niceness of the processes; positive values lower their priority.
+        /// </summary>
+        public int niceness;
+        /// <summary>
+        ///  Whether to start the processes without a console window, on Windows.
+        /// </summary>
+        [MarshalAs(UnmanagedType.U1)] public bool hide_window;
+        /// <summary>
+        ///  Whether to kill a process dropped before it exits.
+        /// </summary>
+        [MarshalAs(UnmanagedType.U1)] public bool kill_on_drop;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencReconnectEvent
+    {
+        public UniencReconnectEventKind kind;
+        /// <summary>
+        ///  Attempt counted from 1, for `Reconnecting` and `Reconnected`.
+        /// </summary>
+        public uint attempt;
+        /// <summary>
+        ///  Samples lost since the disconnection, for `Reconnected`.
+        /// </summary>
+        public ulong dropped;
+        /// <summary>
+        ///  Why the connection failed, for `Disconnected` and `GaveUp`, or null.
+        /// </summary>
+        public byte* error;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencSelfTestReport
+    {
+        public UniencDiagnosticCheck* checks;
+        public nuint count;
+        /// <summary>
+        ///  Whether every check passed.
+        /// </summary>
+        [MarshalAs(UnmanagedType.U1)] public bool passed;
+    }
+
+    /// <summary>
+    ///  Encoder settings recommended for a frame size on this device.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencRecommendedOptions
+    {
+        /// <summary>
+        ///  In bits per second.
+        /// </summary>
+        public uint bitrate;
+        public uint fps_hint;
+        /// <summary>
+        ///  Frames between keyframes.
+        /// </summary>
+        public uint keyframe_interval;
+        /// <summary>
+        ///  In bits per second.
+        /// </summary>
+        public uint audio_bitrate;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal unsafe partial struct UniencRegion
+    {
+        public uint x;
+        public uint y;
+        public uint width;
+        public uint height;
+    }
+
     [StructLayout(LayoutKind.Sequential)]
     internal unsafe partial struct VideoEncoderOptionsNative
     {
@@ -144,6 +1643,15 @@ namespace UniEnc.Native
         public uint height;
         public uint fps_hint;
         public uint bitrate;
+        /// <summary>
+        ///  Prioritizes quality over latency, for sessions that transcode an export.
+        /// </summary>
+        [MarshalAs(UnmanagedType.U1)] public bool offline;
+        /// <summary>
+        ///  Keeps the alpha channel, on encoding systems that support it.
+        /// </summary>
+        [MarshalAs(UnmanagedType.U1)] public bool preserve_alpha;
+        public UniencVideoCodec codec;
     }
 
     [StructLayout(LayoutKind.Sequential)]
@@ -168,6 +1676,97 @@ namespace UniEnc.Native
     }
 
 
+    internal enum UniencStillImageFormat : uint
+    {
+        Png = 0,
+        Jpeg = 1,
+        Bgra32 = 2,
+    }
+
+    internal enum UniencPixelFormat : uint
+    {
+        Bgra32 = 0,
+        Rgba32 = 1,
+        Rgb565 = 2,
+    }
+
+    internal enum UniencDedupMode : uint
+    {
+        Off = 0,
+        Skip = 1,
+        Repeat = 2,
+    }
+
+    internal enum UniencExportFilter : uint
+    {
+        All = 0,
+        VideoOnly = 1,
+        AudioOnly = 2,
+        Keyframes = 3,
+    }
+
+    internal enum UniencJpegSubsampling : uint
+    {
+        Yuv444 = 0,
+        Yuv420 = 1,
+    }
+
+    internal enum UniencStereoMode : uint
+    {
+        Mono = 0,
+        LeftEye = 1,
+        RightEye = 2,
+        SideBySide = 3,
+    }
+
+    internal enum UniencProjection : uint
+    {
+        Flat = 0,
+        Equirectangular = 1,
+    }
+
+    internal enum UniencVideoCodec : uint
+    {
+        H264 = 0,
+        ProRes = 1,
+        DnxHr = 2,
+    }
+
+    internal enum UniencScreenCaptureTarget : uint
+    {
+        CurrentWindow = 0,
+        MainDisplay = 1,
+    }
+
+    internal enum UniencHighlightKind : uint
+    {
+        LoudnessSpike = 0,
+        VoiceActivity = 1,
+    }
+
+    internal enum UniencBenchmarkStage : uint
+    {
+        Convert = 0,
+        Encode = 1,
+        Mux = 2,
+    }
+
+    internal enum UniencPipelineStage : uint
+    {
+        None = 0,
+        EncoderPull = 1,
+        MuxerPush = 2,
+        SinkWrite = 3,
+    }
+
+    internal enum UniencReconnectEventKind : uint
+    {
+        Disconnected = 0,
+        Reconnecting = 1,
+        Reconnected = 2,
+        GaveUp = 3,
+    }
+
     internal enum UniencErrorKind : uint
     {
         Success = 0,
@@ -181,6 +1780,7 @@ namespace UniEnc.Native
         TimeoutError = 8,
         InvalidInput = 9,
         PlatformError = 10,
+        EmptyRecording = 11,
     }
 
 
@@ -179,6 +179,66 @@ namespace UniEnc
             }
         }
 
+        /// <summary>
+        ///     Tags the output as monoscopic equirectangular 360 video. Must be called before <see cref="CompleteAsync" />.
+        /// </summary>
+        public ValueTask SetSphericalAsync()
+        {
+            lock (_lock)
+            {
+                _ = _completionHandle ?? throw new ObjectDisposedException(nameof(_completionHandle));
+
+                var context = CallbackHelper.SimpleCallbackContext.Rent();
+                var contextHandle = CallbackHelper.CreateSendPtr(context);
+                using var runtime = RuntimeWrapper.GetScope();
+
+                unsafe
+                {
+                    NativeMethods.unienc_muxer_set_spherical(
+                        runtime.Runtime,
+                        _completionHandle.DangerousGetHandle(),
+                        CallbackHelper.GetSimpleCallbackPtr(),
+                        contextHandle);
+                }
+
+                return context.Task;
+            }
+        }
+
+        /// <summary>
+        ///     Adds a timecode track starting at <paramref name="start" />, the wallclock time of the first frame, so
+        ///     editors can sync replays of the same match from several players. The timecode is the time of day in
+        ///     UTC. Must be called before <see cref="CompleteAsync" />.
+        /// </summary>
+        /// <param name="frameRate">Timecode frames per second, usually <see cref="VideoEncoderOptions.FpsHint" /></param>
+        public ValueTask SetTimecodeAsync(DateTimeOffset start, uint frameRate)
+        {
+            if (frameRate == 0 || frameRate > 255)
+                throw new ArgumentOutOfRangeException(nameof(frameRate));
+
+            lock (_lock)
+            {
+                _ = _completionHandle ?? throw new ObjectDisposedException(nameof(_completionHandle));
+
+                var context = CallbackHelper.SimpleCallbackContext.Rent();
+                var contextHandle = CallbackHelper.CreateSendPtr(context);
+                using var runtime = RuntimeWrapper.GetScope();
+
+                unsafe
+                {
+                    NativeMethods.unienc_muxer_set_timecode(
+                        runtime.Runtime,
+                        _completionHandle.DangerousGetHandle(),
+                        start.ToUnixTimeMilliseconds() / 1000.0,
+                        frameRate,
+                        CallbackHelper.GetSimpleCallbackPtr(),
+                        contextHandle);
+                }
+
+                return context.Task;
+            }
+        }
+
         private void Dispose(bool disposing)
         {
             lock (_lock)
@@ -36,6 +36,31 @@ namespace UniEnc.Native
     {
     }
 
+    // opaque
+    internal struct JpegSpool
+    {
+    }
+
+    // opaque
+    internal struct LadderExport
+    {
+    }
+
+    // opaque
+    internal struct DualSink
+    {
+    }
+
+    // opaque
+    internal struct FrameSnapshot
+    {
+    }
+
+    // opaque
+    internal struct SessionDir
+    {
+    }
+
     internal struct PlatformEncodingSystem
     {
     }
@@ -53,4 +78,72 @@ namespace UniEnc
         Key,
         Metadata
     }
+
+    /// <summary>
+    ///     Layout of raw video frame pixels. Frames not in BGRA are converted natively.
+    /// </summary>
+    public enum PixelFormat : uint
+    {
+        Bgra32,
+        Rgba32,
+        Rgb565
+    }
+
+    /// <summary>
+    ///     Eyes of an XR texture array to capture. Except for <see cref="Mono" />, the source must be a texture
+    ///     array with a slice per eye.
+    /// </summary>
+    public enum StereoMode : uint
+    {
+        /// <summary>
+        ///     The source is a plain 2D texture.
+        /// </summary>
+        Mono,
+        LeftEye,
+        RightEye,
+
+        /// <summary>
+        ///     Both eyes next to each other, left eye on the left half.
+        /// </summary>
+        SideBySide
+    }
+
+    /// <summary>
+    ///     How a blit source maps onto the encoded frame.
+    /// </summary>
+    public enum Projection : uint
+    {
+        /// <summary>
+        ///     The source is scaled to fit the frame.
+        /// </summary>
+        Flat,
+
+        /// <summary>
+        ///     The source is a cubemap, unwrapped to fill the whole frame with a 360 degree view. Only monoscopic
+        ///     capture is supported. Call <see cref="Muxer.SetSphericalAsync" /> so players recognize the output as
+        ///     360 video.
+        /// </summary>
+        Equirectangular
+    }
+
+    /// <summary>
+    ///     Codec of the encoded video. Check <see cref="EncodingSystem.IsCodecSupported" /> before using the
+    ///     intermediate codecs.
+    /// </summary>
+    public enum VideoCodec : uint
+    {
+        H264,
+
+        /// <summary>
+        ///     Apple ProRes 422 HQ, or ProRes 4444 with <see cref="VideoEncoderOptions.PreserveAlpha" />, for editing
+        ///     without another lossy generation. Written into a QuickTime movie, so name the output <c>.mov</c>. The
+        ///     bitrate is ignored.
+        /// </summary>
+        ProRes,
+
+        /// <summary>
+        ///     Avid DNxHR HQ in a QuickTime movie, so name the output <c>.mov</c>. The bitrate is ignored.
+        /// </summary>
+        DnxHr
+    }
 }
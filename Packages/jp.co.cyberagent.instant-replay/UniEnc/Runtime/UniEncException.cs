@@ -31,6 +31,11 @@ namespace UniEnc
         /// </summary>
         internal UniencErrorKind ErrorKind { get; }
 
+        /// <summary>
+        ///     Whether the recording was stopped before any video frame was written, so no file was made.
+        /// </summary>
+        public bool IsEmptyRecording => ErrorKind == UniencErrorKind.EmptyRecording;
+
         private static string FormatMessage(UniencErrorKind errorKind, string message)
         {
             return string.IsNullOrEmpty(message)
@@ -4,11 +4,21 @@
 
 using System;
 using System.Runtime.InteropServices;
+using UniEnc.Native;
 
 namespace UniEnc
 {
     public static class Utils
     {
+        /// <summary>
+        ///     Reports the color space of the project so that frames pushed with a contradicting
+        ///     <c>isGammaWorkflow</c> are logged as a warning.
+        /// </summary>
+        public static void SetProjectColorSpace(bool isGammaWorkflow)
+        {
+            NativeMethods.unienc_set_project_color_space(isGammaWorkflow);
+        }
+
         internal static SafeHandleScope GetScope(this SafeHandle handle)
         {
             return new SafeHandleScope(handle);
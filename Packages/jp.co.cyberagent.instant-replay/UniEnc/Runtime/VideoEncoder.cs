@@ -1,4 +1,5 @@
 using System;
+using System.Text;
 using System.Threading.Tasks;
 using UniEnc.Native;
 
@@ -31,11 +32,14 @@ namespace UniEnc
         /// <summary>
         ///     Pushes a raw video frame to the encoder.
         /// </summary>
-        /// <param name="frameData">Raw frame data (BGRA)</param>
+        /// <param name="frameData">Raw frame data</param>
         /// <param name="width">Frame width in pixels</param>
         /// <param name="height">Frame height in pixels</param>
         /// <param name="timestamp">Frame timestamp in seconds</param>
-        public ValueTask PushFrameAsync<T>(in SharedBuffer<T> frameData, uint width, uint height, double timestamp)
+        /// <param name="pixelFormat">Pixel layout of <paramref name="frameData" /></param>
+        /// <param name="stride">Bytes from the start of one row to the next, or 0 if rows are tightly packed</param>
+        public ValueTask PushFrameAsync<T>(in SharedBuffer<T> frameData, uint width, uint height, double timestamp,
+            PixelFormat pixelFormat = PixelFormat.Bgra32, uint stride = 0)
             where T : struct, IDisposable
         {
             lock (_lock)
@@ -52,12 +56,14 @@ namespace UniEnc
                     {
                         using var runtime = RuntimeWrapper.GetScope();
 
-                        NativeMethods.unienc_video_encoder_push_shared_buffer(
+                        NativeMethods.unienc_video_encoder_push_shared_buffer_with_format(
                             runtime.Runtime,
                             _inputHandle.DangerousGetHandle(),
                             frameData.MoveOut(),
                             width,
                             height,
+                            stride,
+                            (UniencPixelFormat)pixelFormat,
                             timestamp,
                             CallbackHelper.GetSimpleCallbackPtr(),
                             contextHandle);
@@ -75,7 +81,8 @@ namespace UniEnc
 
         public ValueTask UnsafePushTextureTokenAsync(nuint textureToken, uint width, uint height,
             uint unityGraphicsFormat, bool isGammaWorkflow, double timestamp, nuint onIssueGraphicsEventPtr,
-            bool flipVertically = false)
+            bool flipVertically = false, StereoMode stereoMode = StereoMode.Mono,
+            Projection projection = Projection.Flat)
         {
             lock (_lock)
             {
@@ -91,7 +98,7 @@ namespace UniEnc
                     {
                         using var runtime = RuntimeWrapper.GetScope();
 
-                        NativeMethods.unienc_video_encoder_push_blit_source(
+                        NativeMethods.unienc_video_encoder_push_blit_source_with_projection(
                             runtime.Runtime,
                             _inputHandle.DangerousGetHandle(),
                             textureToken,
@@ -100,6 +107,8 @@ namespace UniEnc
                             unityGraphicsFormat,
                             flipVertically,
                             isGammaWorkflow,
+                            (UniencStereoMode)stereoMode,
+                            (UniencProjection)projection,
                             timestamp,
                             onIssueGraphicsEventPtr,
                             CallbackHelper.GetSimpleCallbackPtr(),
@@ -129,6 +138,84 @@ namespace UniEnc
             NativeMethods.unienc_free_graphics_event_context((void*)context);
         }
 
+        /// <summary>
+        ///     Writes every frame pushed afterwards as an RGBA PNG into <paramref name="directory" />, with a
+        ///     <c>frames.json</c> index of their timestamps. Only frames pushed with <see cref="PushFrameAsync{T}" />
+        ///     are written.
+        /// </summary>
+        public ValueTask SetPngSequenceAsync(string directory)
+        {
+            lock (_lock)
+            {
+                _ = _inputHandle ?? throw new ObjectDisposedException(nameof(_inputHandle));
+
+                var context = CallbackHelper.SimpleCallbackContext.Rent();
+
+                try
+                {
+                    var contextHandle = CallbackHelper.CreateSendPtr(context);
+
+                    unsafe
+                    {
+                        using var runtime = RuntimeWrapper.GetScope();
+                        var directoryBytes = Encoding.UTF8.GetBytes(directory + '\0');
+                        fixed (byte* directoryPtr = directoryBytes)
+                        {
+                            NativeMethods.unienc_video_encoder_set_png_sequence(
+                                runtime.Runtime,
+                                _inputHandle.DangerousGetHandle(),
+                                directoryPtr,
+                                CallbackHelper.GetSimpleCallbackPtr(),
+                                contextHandle);
+                        }
+                    }
+
+                    return context.Task;
+                }
+                catch
+                {
+                    context.Return();
+                    throw;
+                }
+            }
+        }
+
+        /// <summary>
+        ///     Writes the index of the PNG sequence and stops writing frames. Throws if a frame could not be written.
+        /// </summary>
+        public ValueTask FinishPngSequenceAsync()
+        {
+            lock (_lock)
+            {
+                _ = _inputHandle ?? throw new ObjectDisposedException(nameof(_inputHandle));
+
+                var context = CallbackHelper.SimpleCallbackContext.Rent();
+
+                try
+                {
+                    var contextHandle = CallbackHelper.CreateSendPtr(context);
+
+                    unsafe
+                    {
+                        using var runtime = RuntimeWrapper.GetScope();
+
+                        NativeMethods.unienc_video_encoder_finish_png_sequence(
+                            runtime.Runtime,
+                            _inputHandle.DangerousGetHandle(),
+                            CallbackHelper.GetSimpleCallbackPtr(),
+                            contextHandle);
+                    }
+
+                    return context.Task;
+                }
+                catch
+                {
+                    context.Return();
+                    throw;
+                }
+            }
+        }
+
         /// <summary>
         ///     Pulls an encoded frame from the encoder.
         /// </summary>
@@ -28,6 +28,25 @@ namespace UniEnc
         /// </summary>
         public uint Bitrate { get; set; }
 
+        /// <summary>
+        ///     Prioritizes quality over latency by lifting realtime constraints of the encoder. Use for offline
+        ///     transcoding, not for recording while the game runs.
+        /// </summary>
+        public bool Offline { get; set; }
+
+        /// <summary>
+        ///     Keeps the alpha channel of the frames, for transparent overlays. Creating encoders fails on encoding
+        ///     systems where <see cref="EncodingSystem.IsAlphaSupported" /> is false; record a PNG sequence with
+        ///     <see cref="VideoEncoder.SetPngSequenceAsync" /> there instead.
+        /// </summary>
+        public bool PreserveAlpha { get; set; }
+
+        /// <summary>
+        ///     Codec of the encoded video. Creating encoders fails on encoding systems where
+        ///     <see cref="EncodingSystem.IsCodecSupported" /> is false for it.
+        /// </summary>
+        public VideoCodec Codec { get; set; }
+
         /// <summary>
         ///     Validates the options and throws if invalid.
         /// </summary>
@@ -54,7 +73,10 @@ namespace UniEnc
                 width = Width,
                 height = Height,
                 fps_hint = FpsHint,
-                bitrate = Bitrate
+                bitrate = Bitrate,
+                offline = Offline,
+                preserve_alpha = PreserveAlpha,
+                codec = (UniencVideoCodec)Codec
             };
         }
     }